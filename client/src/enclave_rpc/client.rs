@@ -11,12 +11,13 @@ use anyhow::Result;
 use futures::{
     future,
     prelude::*,
+    stream,
     sync::{mpsc, oneshot},
 };
 #[cfg(not(target_env = "sgx"))]
 use grpcio::Channel;
 use io_context::Context;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 use tokio_executor::spawn;
 
@@ -25,10 +26,11 @@ use oasis_core_runtime::common::runtime::RuntimeId;
 use oasis_core_runtime::{
     common::{cbor, sgx::avr::EnclaveIdentity},
     enclave_rpc::{
-        session::{Builder, Session},
+        session::{Builder, Session, SessionState},
         types,
     },
     protocol::Protocol,
+    storage::KeyValue,
 };
 
 #[cfg(not(target_env = "sgx"))]
@@ -36,7 +38,7 @@ use super::api::EnclaveRPCClient;
 #[cfg(not(target_env = "sgx"))]
 use super::transport::GrpcTransport;
 use super::transport::{RuntimeTransport, Transport};
-use crate::BoxFuture;
+use crate::{BoxFuture, BoxStream};
 
 /// Internal send queue backlog.
 const SENDQ_BACKLOG: usize = 10;
@@ -50,6 +52,12 @@ pub enum RpcClientError {
     ExpectedResponseMessage(types::Message),
     #[error("expected close message, received: {0:?}")]
     ExpectedCloseMessage(types::Message),
+    #[error("unexpected response stream")]
+    UnexpectedStream,
+    #[error("expected response stream, received: {0:?}")]
+    ExpectedStream(types::Body),
+    #[error("unexpected stream chunk (expected {expected} got {seq})")]
+    UnexpectedChunk { expected: u64, seq: u64 },
     #[error("transport error")]
     Transport,
     #[error("client dropped")]
@@ -58,11 +66,26 @@ pub enum RpcClientError {
 
 type SendqRequest = (
     Arc<Context>,
-    types::Request,
+    types::Message,
     oneshot::Sender<Result<types::Response>>,
     usize,
 );
 
+/// Storage where the session state is persisted.
+struct SessionStorage {
+    storage: Arc<dyn KeyValue>,
+    key: Vec<u8>,
+}
+
+/// Session state as persisted in storage.
+#[derive(Serialize, Deserialize)]
+struct PersistedSession {
+    id: types::SessionID,
+    /// Sealed session state.
+    #[serde(with = "serde_bytes")]
+    state: Vec<u8>,
+}
+
 struct MultiplexedSession {
     /// Session builder for resetting sessions.
     builder: Builder,
@@ -70,6 +93,11 @@ struct MultiplexedSession {
     id: types::SessionID,
     /// Current underlying protocol session.
     inner: Session,
+    /// Storage where the session state is persisted (if any).
+    storage: Option<SessionStorage>,
+    /// Flag indicating whether the persisted session should be resumed
+    /// before establishing a new session.
+    resume_pending: bool,
 }
 
 impl MultiplexedSession {
@@ -78,12 +106,81 @@ impl MultiplexedSession {
             builder: builder.clone(),
             id: types::SessionID::random(),
             inner: builder.build_initiator(),
+            storage: None,
+            resume_pending: false,
         }
     }
 
     fn reset(&mut self) {
         self.id = types::SessionID::random();
         self.inner = self.builder.clone().build_initiator();
+
+        // Keep the persisted session if it has not been resumed yet, as it
+        // may still be valid (e.g., if the session is reset because the
+        // remote enclave identities have been configured).
+        if !self.resume_pending {
+            // If the persisted session cannot be cleared it will fail to
+            // be resumed, as the remote enclave no longer has it.
+            let _ = self.persist();
+        }
+    }
+
+    /// Resume the persisted session, if any.
+    ///
+    /// If the persisted session cannot be resumed, a new session will be
+    /// established instead.
+    fn resume(&mut self) {
+        if !self.resume_pending {
+            return;
+        }
+        self.resume_pending = false;
+
+        if let Ok(Some((id, inner))) = self.load() {
+            self.id = id;
+            self.inner = inner;
+        }
+    }
+
+    fn load(&self) -> Result<Option<(types::SessionID, Session)>> {
+        let storage = match self.storage {
+            Some(ref storage) => storage,
+            None => return Ok(None),
+        };
+        let persisted = storage.storage.get(storage.key.clone())?;
+        if persisted.is_empty() {
+            return Ok(None);
+        }
+        let persisted: PersistedSession = cbor::from_slice(&persisted)?;
+        let state = match SessionState::unseal(&persisted.state)? {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        Ok(Some((
+            persisted.id,
+            self.builder.clone().build_resumed(state)?,
+        )))
+    }
+
+    /// Persist the current session state, if configured.
+    ///
+    /// This must be called after each (resumption) handshake and after the
+    /// session is closed.
+    fn persist(&self) -> Result<()> {
+        let storage = match self.storage {
+            Some(ref storage) => storage,
+            None => return Ok(()),
+        };
+        let value = if self.inner.is_connected() {
+            cbor::to_vec(&PersistedSession {
+                id: self.id,
+                state: self.inner.export_state()?.seal(),
+            })
+        } else {
+            vec![]
+        };
+
+        storage.storage.insert(storage.key.clone(), value)
     }
 }
 
@@ -165,16 +262,71 @@ impl RpcClient {
         };
 
         Box::new(
-            self.execute_call(ctx, request)
-                .and_then(|response| match response.body {
+            Self::execute_call(self.inner.clone(), ctx, types::Message::Request(request)).and_then(
+                |response| match response.body {
                     types::Body::Success(value) => Ok(cbor::from_value(value)?),
                     types::Body::Error(error) => Err(RpcClientError::CallFailed(error).into()),
-                }),
+                    types::Body::Chunk(_) => Err(RpcClientError::UnexpectedStream.into()),
+                },
+            ),
         )
     }
 
-    fn execute_call(&self, ctx: Context, request: types::Request) -> BoxFuture<types::Response> {
+    /// Call a remote streaming method.
+    ///
+    /// The items of the response stream are requested one at a time as the
+    /// returned stream is polled. Any other call made using the same client
+    /// before the stream has ended cancels the stream.
+    pub fn call_stream<C, O>(&self, ctx: Context, method: &'static str, args: C) -> BoxStream<O>
+    where
+        C: Serialize,
+        O: DeserializeOwned + Send + 'static,
+    {
+        let request = types::Message::Request(types::Request {
+            method: method.to_owned(),
+            args: cbor::to_value(args),
+        });
         let inner = self.inner.clone();
+        let ctx = ctx.freeze();
+
+        // The state is the sequence number of the next chunk and the message
+        // requesting it, or None after the end of the stream.
+        Box::new(
+            stream::unfold(Some((0, request)), move |state| {
+                let (seq, request) = state?;
+                Some(
+                    Self::execute_call(inner.clone(), Context::create_child(&ctx), request)
+                        .and_then(move |response| match response.body {
+                            types::Body::Chunk(chunk) if chunk.seq != seq => {
+                                Err(RpcClientError::UnexpectedChunk {
+                                    expected: seq,
+                                    seq: chunk.seq,
+                                }
+                                .into())
+                            }
+                            types::Body::Chunk(types::StreamChunk {
+                                item: Some(item), ..
+                            }) => Ok((
+                                Some(cbor::from_value(item)?),
+                                Some((seq + 1, types::Message::StreamNext { seq: seq + 1 })),
+                            )),
+                            types::Body::Chunk(_) => Ok((None, None)),
+                            types::Body::Error(error) => {
+                                Err(RpcClientError::CallFailed(error).into())
+                            }
+                            body => Err(RpcClientError::ExpectedStream(body).into()),
+                        }),
+                )
+            })
+            .filter_map(|item| item),
+        )
+    }
+
+    fn execute_call(
+        inner: Arc<Inner>,
+        ctx: Context,
+        message: types::Message,
+    ) -> BoxFuture<types::Response> {
         Box::new(future::lazy(move || {
             // Spawn a new controller if we haven't spawned one yet.
             if !inner
@@ -191,15 +343,15 @@ impl RpcClient {
                 let inner = inner.clone();
                 let inner2 = inner.clone();
                 spawn(
-                    rx.for_each(move |(ctx, request, rsp_tx, retries)| {
+                    rx.for_each(move |(ctx, message, rsp_tx, retries)| {
                         let inner = inner.clone();
                         let inner2 = inner.clone();
-                        let request2 = request.clone();
+                        let message2 = message.clone();
                         let ctx2 = ctx.clone();
 
                         Self::connect(inner.clone(), Context::create_child(&ctx))
                             .and_then(move |_| {
-                                Self::call_raw(inner.clone(), Context::create_child(&ctx), request)
+                                Self::call_raw(inner.clone(), Context::create_child(&ctx), message)
                             })
                             .then(
                                 move |result| -> Box<dyn Future<Item = (), Error = ()> + Send> {
@@ -214,7 +366,7 @@ impl RpcClient {
                                                 inner2
                                                     .sendq
                                                     .clone()
-                                                    .send((ctx2, request2, rsp_tx, retries + 1))
+                                                    .send((ctx2, message2, rsp_tx, retries + 1))
                                                     .map(|_| ())
                                                     .or_else(|err| {
                                                         let (_, _, rsp_tx, _) = err.into_inner();
@@ -243,7 +395,7 @@ impl RpcClient {
             inner
                 .sendq
                 .clone()
-                .send((ctx.freeze(), request, rsp_tx, 0))
+                .send((ctx.freeze(), message, rsp_tx, 0))
                 .map_err(|err| err.into())
                 .and_then(move |_| rsp_rx.map_err(|err| err.into()).and_then(|result| result))
         }))
@@ -252,6 +404,7 @@ impl RpcClient {
     fn connect(inner: Arc<Inner>, ctx: Context) -> BoxFuture<()> {
         Box::new(future::lazy(move || -> BoxFuture<()> {
             let mut session = inner.session.lock().unwrap();
+            session.resume();
             if session.inner.is_connected() {
                 return Box::new(future::ok(()));
             }
@@ -280,6 +433,13 @@ impl RpcClient {
                         if let Err(error) = session.inner.process_data(data, &mut buffer) {
                             return Box::new(future::err(error));
                         }
+                        if let Err(error) = session.persist() {
+                            return Box::new(future::err(error));
+                        }
+                        // Resumption handshakes have no third message.
+                        if buffer.is_empty() {
+                            return Box::new(future::ok(()));
+                        }
 
                         let ctx = Context::create_child(&fctx);
                         Box::new(
@@ -328,7 +488,7 @@ impl RpcClient {
                     match msg {
                         types::Message::Close => {
                             session.inner.close();
-                            Ok(())
+                            session.persist()
                         }
                         msg => Err(RpcClientError::ExpectedCloseMessage(msg).into()),
                     }
@@ -339,10 +499,12 @@ impl RpcClient {
     fn call_raw(
         inner: Arc<Inner>,
         ctx: Context,
-        request: types::Request,
+        msg: types::Message,
     ) -> BoxFuture<types::Response> {
-        let method = request.method.clone();
-        let msg = types::Message::Request(request);
+        let method = match msg {
+            types::Message::Request(ref request) => request.method.clone(),
+            _ => String::new(),
+        };
         let mut session = inner.session.lock().unwrap();
         let mut buffer = vec![];
         if let Err(error) = session.inner.write_message(msg, &mut buffer) {
@@ -377,6 +539,23 @@ impl RpcClient {
         )
     }
 
+    /// Persist the session state in the given (untrusted) storage under the
+    /// given key, sealed to the enclave identity, and resume the persisted
+    /// session (if any) instead of establishing a new one.
+    ///
+    /// This avoids a new attested handshake with the remote enclave after a
+    /// restart, as long as the remote enclave has kept the session. Instead,
+    /// a resumption handshake derives fresh keys for the resumed session (see
+    /// `SessionState`).
+    pub fn persist_session(&self, storage: Arc<dyn KeyValue>, key: &[u8]) {
+        let mut session = self.inner.session.lock().unwrap();
+        session.storage = Some(SessionStorage {
+            storage,
+            key: key.to_vec(),
+        });
+        session.resume_pending = !session.inner.is_connected();
+    }
+
     /// Update session enclaves if changed.
     pub fn update_enclaves(&self, enclaves: Option<HashSet<EnclaveIdentity>>) {
         let mut session = self.inner.session.lock().unwrap();
@@ -389,19 +568,24 @@ impl RpcClient {
 
 #[cfg(test)]
 mod test {
-    use std::sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
     };
 
-    use anyhow::anyhow;
-    use futures::future;
+    use anyhow::{anyhow, Result};
+    use futures::{future, Stream};
     use io_context::Context;
     use tokio::runtime::Runtime;
 
     use oasis_core_runtime::{
-        enclave_rpc::{demux::Demux, session, types},
+        common::cbor,
+        enclave_rpc::{demux::Demux, session, stream::ResponseStream, types},
         rak::RAK,
+        storage::KeyValue,
     };
 
     use super::{super::transport::Transport, RpcClient};
@@ -412,6 +596,7 @@ mod test {
         rak: Arc<RAK>,
         demux: Arc<Mutex<Demux>>,
         next_error: Arc<AtomicBool>,
+        handshake_frames: Arc<AtomicUsize>,
     }
 
     impl MockTransport {
@@ -422,6 +607,7 @@ mod test {
                 rak: rak.clone(),
                 demux: Arc::new(Mutex::new(Demux::new(rak))),
                 next_error: Arc::new(AtomicBool::new(false)),
+                handshake_frames: Arc::new(AtomicUsize::new(0)),
             }
         }
 
@@ -452,14 +638,30 @@ mod test {
                 Err(err) => Box::new(future::err(err)),
                 Ok(Some((session_id, _session_info, message, _untrusted_plaintext))) => {
                     // Message, process and write reply.
-                    let body = match message {
+                    let response = match message {
+                        types::Message::Request(rq) if rq.method == "stream" => {
+                            // Stream back the given number of items.
+                            let count: u64 = cbor::from_value(rq.args).unwrap();
+                            let mut stream = ResponseStream::new(Box::new(
+                                (0..count).map(|item| Ok(cbor::to_value(item))),
+                            ));
+                            let response = stream.next_response(0);
+                            demux.set_stream(session_id, Some(stream)).unwrap();
+                            response
+                        }
                         types::Message::Request(rq) => {
                             // Just echo back what was given.
-                            types::Body::Success(rq.args)
+                            demux.set_stream(session_id, None).unwrap();
+                            types::Response {
+                                body: types::Body::Success(rq.args),
+                            }
+                        }
+                        types::Message::StreamNext { seq } => {
+                            demux.next_stream_response(session_id, seq).unwrap()
                         }
                         _ => panic!("unhandled message type"),
                     };
-                    let response = types::Message::Response(types::Response { body });
+                    let response = types::Message::Response(response);
 
                     let mut buffer = Vec::new();
                     match demux.write_message(session_id, response, &mut buffer) {
@@ -469,6 +671,7 @@ mod test {
                 }
                 Ok(None) => {
                     // Handshake.
+                    self.handshake_frames.fetch_add(1, Ordering::SeqCst);
                     Box::new(future::ok(buffer))
                 }
             }
@@ -505,4 +708,111 @@ mod test {
             .unwrap();
         assert_eq!(result, 44, "call should work");
     }
+
+    #[test]
+    fn test_rpc_client_stream() {
+        let mut rt = Runtime::new().unwrap();
+        let transport = MockTransport::new();
+        let client = RpcClient::new(Box::new(transport.clone()), session::Builder::new());
+
+        let items: Vec<u64> = rt
+            .block_on(
+                client
+                    .call_stream(Context::background(), "stream", 3u64)
+                    .collect(),
+            )
+            .unwrap();
+        assert_eq!(vec![0, 1, 2], items, "stream should return all items");
+
+        let items: Vec<u64> = rt
+            .block_on(
+                client
+                    .call_stream(Context::background(), "stream", 0u64)
+                    .collect(),
+            )
+            .unwrap();
+        assert!(items.is_empty(), "stream should be empty");
+
+        // Another call cancels the stream.
+        let stream = client.call_stream::<_, u64>(Context::background(), "stream", 3u64);
+        let (item, stream) = rt.block_on(stream.into_future()).ok().unwrap();
+        assert_eq!(Some(0), item);
+        let result: u64 = rt
+            .block_on(client.call(Context::background(), "test", 42))
+            .unwrap();
+        assert_eq!(result, 42, "call should work");
+        assert!(
+            rt.block_on(stream.collect()).is_err(),
+            "stream should be cancelled"
+        );
+    }
+
+    #[derive(Default)]
+    struct MemoryKeyValue(Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+    impl KeyValue for MemoryKeyValue {
+        fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .get(&key)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+            self.0.lock().unwrap().insert(key, value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rpc_client_persist_session() {
+        let mut rt = Runtime::new().unwrap();
+        let transport = MockTransport::new();
+        let storage = Arc::new(MemoryKeyValue::default());
+
+        let client = RpcClient::new(Box::new(transport.clone()), session::Builder::new());
+        client.persist_session(storage.clone(), b"session");
+        let result: u64 = rt
+            .block_on(client.call(Context::background(), "test", 42))
+            .unwrap();
+        assert_eq!(result, 42, "call should work");
+        assert_eq!(2, transport.handshake_frames.load(Ordering::SeqCst));
+        let persisted = storage.get(b"session".to_vec()).unwrap();
+        assert!(!persisted.is_empty());
+
+        // A new client (e.g., after a restart) resumes the persisted session
+        // with a single-round-trip resumption handshake.
+        let resumed = RpcClient::new(Box::new(transport.clone()), session::Builder::new());
+        resumed.persist_session(storage.clone(), b"session");
+        let result: u64 = rt
+            .block_on(resumed.call(Context::background(), "test", 43))
+            .unwrap();
+        assert_eq!(result, 43, "call should work");
+        assert_eq!(3, transport.handshake_frames.load(Ordering::SeqCst));
+
+        // A rolled back session cannot be resumed, so a new session is
+        // established.
+        storage.insert(b"session".to_vec(), persisted).unwrap();
+        let resumed = RpcClient::new(Box::new(transport.clone()), session::Builder::new());
+        resumed.persist_session(storage.clone(), b"session");
+        let result: u64 = rt
+            .block_on(resumed.call(Context::background(), "test", 44))
+            .unwrap();
+        assert_eq!(result, 44, "call should work");
+        assert_eq!(5, transport.handshake_frames.load(Ordering::SeqCst));
+
+        // If the remote enclave no longer has the session, a new session is
+        // established.
+        transport.reset();
+        let resumed = RpcClient::new(Box::new(transport.clone()), session::Builder::new());
+        resumed.persist_session(storage.clone(), b"session");
+        let result: u64 = rt
+            .block_on(resumed.call(Context::background(), "test", 45))
+            .unwrap();
+        assert_eq!(result, 45, "call should work");
+        assert!(transport.handshake_frames.load(Ordering::SeqCst) > 5);
+    }
 }
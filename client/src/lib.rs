@@ -12,6 +12,9 @@ pub mod transaction;
 /// Boxed future type.
 pub type BoxFuture<T> = Box<dyn futures::Future<Item = T, Error = anyhow::Error> + Send>;
 
+/// Boxed stream type.
+pub type BoxStream<T> = Box<dyn futures::Stream<Item = T, Error = anyhow::Error> + Send>;
+
 // Re-exports.
 pub use self::enclave_rpc::RpcClient;
 #[cfg(not(target_env = "sgx"))]
@@ -30,6 +30,8 @@ pub enum TxnClientError {
     WatcherClosed,
     #[error("transaction failed: {0}")]
     TxnFailed(String),
+    #[error("transaction ran out of gas (limit {0})")]
+    OutOfGas(u64),
 }
 
 /// Interface for the node's client interface.
@@ -70,6 +72,7 @@ impl TxnClient {
         let call = TxnCall {
             method: method.to_owned(),
             args: cbor::to_value(args),
+            gas_limit: None,
         };
 
         Box::new(
@@ -454,5 +457,6 @@ where
     match output {
         TxnOutput::Success(data) => Ok(cbor::from_value(data)?),
         TxnOutput::Error(error) => Err(TxnClientError::TxnFailed(error).into()),
+        TxnOutput::OutOfGas { limit } => Err(TxnClientError::OutOfGas(limit).into()),
     }
 }
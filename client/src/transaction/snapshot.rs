@@ -70,6 +70,7 @@ impl Clone for BlockSnapshot {
                 namespace: self.block.header.namespace,
                 version: self.block.header.round,
                 hash: self.block.header.state_root,
+                ..Default::default()
             })
             .new(Box::new(read_syncer.clone()));
 
@@ -90,6 +91,7 @@ impl BlockSnapshot {
                 namespace: block.header.namespace,
                 version: block.header.round,
                 hash: block.header.state_root,
+                ..Default::default()
             })
             .new(Box::new(read_syncer.clone()));
 
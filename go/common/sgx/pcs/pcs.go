@@ -0,0 +1,84 @@
+// Package pcs provides types for Intel SGX DCAP quotes and the collateral
+// obtained from the Intel Provisioning Certification Service.
+package pcs
+
+import (
+	"encoding/hex"
+	"fmt"
+)
+
+// QuoteBundle is a DCAP quote bundled with the collateral required to
+// verify it.
+//
+// NOTE: This must be kept in sync with runtime/src/common/sgx/pcs.rs.
+type QuoteBundle struct {
+	// Quote is the quote generated by the quoting enclave.
+	Quote []byte `json:"quote"`
+
+	// TCB is the collateral of the platform the quote has been generated on.
+	TCB TCBBundle `json:"tcb"`
+}
+
+// TCBBundle is the collateral obtained from the PCS.
+type TCBBundle struct {
+	// TCBInfo is the TCB info of the platform FMSPC.
+	TCBInfo SignedCollateral `json:"tcb_info"`
+
+	// QEIdentity is the identity of the quoting enclave.
+	QEIdentity SignedCollateral `json:"qe_identity"`
+
+	// Certificates is the PEM-encoded TCB signing certificate chain.
+	Certificates []byte `json:"certificates"`
+}
+
+// SignedCollateral is collateral signed by the TCB signing key.
+type SignedCollateral struct {
+	// Body is the signed JSON body (the tcbInfo or enclaveIdentity object
+	// exactly as returned by the PCS).
+	Body []byte `json:"body"`
+
+	// Signature is the raw ECDSA P-256 signature of the body (r || s).
+	Signature []byte `json:"signature"`
+}
+
+// FMSPCSize is the size of an FMSPC in bytes.
+const FMSPCSize = 6
+
+// FMSPC is the Family-Model-Stepping-Platform-CustomSKU of an SGX platform.
+type FMSPC [FMSPCSize]byte
+
+// MarshalBinary encodes an FMSPC into binary form.
+func (f FMSPC) MarshalBinary() ([]byte, error) {
+	return append([]byte{}, f[:]...), nil
+}
+
+// UnmarshalBinary decodes a binary marshaled FMSPC.
+func (f *FMSPC) UnmarshalBinary(data []byte) error {
+	if len(data) != FMSPCSize {
+		return fmt.Errorf("pcs: malformed FMSPC")
+	}
+
+	copy(f[:], data)
+
+	return nil
+}
+
+// MarshalText encodes an FMSPC into text form.
+func (f FMSPC) MarshalText() ([]byte, error) {
+	return []byte(f.String()), nil
+}
+
+// UnmarshalText decodes a text marshaled FMSPC.
+func (f *FMSPC) UnmarshalText(text []byte) error {
+	b, err := hex.DecodeString(string(text))
+	if err != nil {
+		return err
+	}
+
+	return f.UnmarshalBinary(b)
+}
+
+// String returns the string representation of an FMSPC.
+func (f FMSPC) String() string {
+	return hex.EncodeToString(f[:])
+}
@@ -0,0 +1,40 @@
+package sgx
+
+import "github.com/oasisprotocol/oasis-core/go/common/sgx/pcs"
+
+// TCBLevel is the TCB level of an attested platform.
+type TCBLevel string
+
+// TCB levels of attested platforms, from the most secure one.
+const (
+	TCBLevelUpToDate                          TCBLevel = "UpToDate"
+	TCBLevelSWHardeningNeeded                 TCBLevel = "SWHardeningNeeded"
+	TCBLevelConfigurationNeeded               TCBLevel = "ConfigurationNeeded"
+	TCBLevelConfigurationAndSWHardeningNeeded TCBLevel = "ConfigurationAndSWHardeningNeeded"
+	TCBLevelOutOfDate                         TCBLevel = "OutOfDate"
+	TCBLevelOutOfDateConfigurationNeeded      TCBLevel = "OutOfDateConfigurationNeeded"
+)
+
+// QuotePolicy is the policy for accepting remote attestations, enforced by
+// the runtime when verifying quotes.
+//
+// The policy can only restrict which quotes are accepted by the runtime, as
+// the host is not trusted by it.
+//
+// NOTE: This must be kept in sync with runtime/src/common/sgx/mod.rs.
+type QuotePolicy struct {
+	// MaxQuoteAge is the maximum age of a quote in seconds. As DCAP quotes
+	// are not timestamped, their age is the age of the collateral.
+	MaxQuoteAge *uint64 `json:"max_quote_age,omitempty"`
+
+	// AllowedTCBLevels are the accepted TCB levels. If empty, all TCB levels
+	// are accepted.
+	AllowedTCBLevels []TCBLevel `json:"allowed_tcb_levels,omitempty"`
+
+	// MinTCBEvaluationDataNumber is the minimum TCB evaluation data number of
+	// the DCAP collateral.
+	MinTCBEvaluationDataNumber uint32 `json:"min_tcb_evaluation_data_number,omitempty"`
+
+	// BlockedFMSPCs are the platforms whose DCAP quotes are rejected.
+	BlockedFMSPCs []pcs.FMSPC `json:"blocked_fmspcs,omitempty"`
+}
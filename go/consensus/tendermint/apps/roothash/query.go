@@ -14,6 +14,8 @@ import (
 type Query interface {
 	LatestBlock(context.Context, common.Namespace) (*block.Block, error)
 	GenesisBlock(context.Context, common.Namespace) (*block.Block, error)
+	LastRoundResults(context.Context, common.Namespace) (*roothash.RoundResults, error)
+	IncomingMessageQueue(context.Context, common.Namespace, uint64, uint32) ([]*block.IncomingMessage, error)
 	Genesis(context.Context) (*roothash.Genesis, error)
 }
 
@@ -51,6 +53,26 @@ func (rq *rootHashQuerier) GenesisBlock(ctx context.Context, id common.Namespace
 	return runtime.GenesisBlock, nil
 }
 
+func (rq *rootHashQuerier) LastRoundResults(ctx context.Context, id common.Namespace) (*roothash.RoundResults, error) {
+	runtime, err := rq.state.RuntimeState(ctx, id)
+	if err != nil {
+		return nil, err
+	}
+	if runtime.LastRoundResults == nil {
+		return &roothash.RoundResults{}, nil
+	}
+	return runtime.LastRoundResults, nil
+}
+
+func (rq *rootHashQuerier) IncomingMessageQueue(
+	ctx context.Context,
+	id common.Namespace,
+	offset uint64,
+	limit uint32,
+) ([]*block.IncomingMessage, error) {
+	return rq.state.IncomingMessageQueue(ctx, id, offset, limit)
+}
+
 func (app *rootHashApplication) QueryFactory() interface{} {
 	return &QueryFactory{app.state}
 }
@@ -3,13 +3,13 @@ package roothash
 
 import (
 	"bytes"
-	"errors"
 	"fmt"
 
 	"github.com/tendermint/tendermint/abci/types"
 
 	"github.com/oasisprotocol/oasis-core/go/common"
 	"github.com/oasisprotocol/oasis-core/go/common/cbor"
+	"github.com/oasisprotocol/oasis-core/go/common/errors"
 	"github.com/oasisprotocol/oasis-core/go/common/logging"
 	"github.com/oasisprotocol/oasis-core/go/consensus/api/transaction"
 	tmapi "github.com/oasisprotocol/oasis-core/go/consensus/tendermint/api"
@@ -239,6 +239,8 @@ func (app *rootHashApplication) emitEmptyBlock(ctx *tmapi.Context, runtime *root
 
 	runtime.CurrentBlock = blk
 	runtime.CurrentBlockHeight = ctx.BlockHeight()
+	// Empty blocks do not contain any messages.
+	runtime.LastRoundResults = nil
 	if runtime.ExecutorPool != nil {
 		// Clear timeout if there was one scheduled.
 		if runtime.ExecutorPool.NextTimeout != commitment.TimeoutNever {
@@ -280,6 +282,13 @@ func (app *rootHashApplication) ExecuteTx(ctx *tmapi.Context, tx *transaction.Tr
 		}
 
 		return app.executorProposerTimeout(ctx, state, &xc)
+	case roothash.MethodSubmitMsg:
+		var msg roothash.SubmitMsg
+		if err := cbor.Unmarshal(tx.Body, &msg); err != nil {
+			return err
+		}
+
+		return app.submitMsg(ctx, state, &msg)
 	default:
 		return roothash.ErrInvalidArgument
 	}
@@ -456,7 +465,12 @@ func (app *rootHashApplication) tryFinalizeExecutorCommits(
 		blk := block.NewEmptyBlock(rtState.CurrentBlock, uint64(ctx.Now().Unix()), block.Normal)
 		blk.Header.IORoot = *hdr.IORoot
 		blk.Header.StateRoot = *hdr.StateRoot
-		// Messages omitted on purpose.
+		blk.Header.Messages = hdr.Messages
+
+		// Remove the incoming messages processed by the runtime from the queue.
+		if err = app.dequeueIncomingMessages(ctx, runtime.ID, &hdr); err != nil {
+			break
+		}
 
 		// Timeout will be cleared by caller.
 		rtState.ExecutorPool.ResetCommitments()
@@ -505,35 +519,60 @@ func (app *rootHashApplication) tryFinalizeExecutorCommits(
 	return nil, nil
 }
 
-func (app *rootHashApplication) postProcessFinalizedBlock(ctx *tmapi.Context, rtState *roothashState.RuntimeState, blk *block.Block) error {
-	sc := ctx.StartCheckpoint()
-	defer sc.Close()
+// dequeueIncomingMessages verifies that the incoming messages processed by the
+// runtime, as committed to in the given header, are the messages at the head of
+// the incoming message queue and removes them from the queue.
+func (app *rootHashApplication) dequeueIncomingMessages(
+	ctx *tmapi.Context,
+	runtimeID common.Namespace,
+	hdr *commitment.ComputeResultsHeader,
+) error {
+	if hdr.InMessagesCount == 0 {
+		return nil
+	}
+	if hdr.InMessagesHash == nil {
+		return roothash.ErrInvalidArgument
+	}
 
-	for _, message := range blk.Header.Messages {
-		// Currently there are no valid roothash messages, so any message
-		// is treated as unsatisfactory. This is the place which would
-		// otherwise contain message handlers.
-		unsat := errors.New("tendermint/roothash: message is invalid")
-
-		if unsat != nil {
-			ctx.Logger().Error("handler not satisfied with message",
-				"err", unsat,
-				"message", message,
-				logging.LogEvent, roothash.LogEventMessageUnsat,
-			)
+	state := roothashState.NewMutableState(ctx.State())
+	msgs, err := state.IncomingMessageQueue(ctx, runtimeID, 0, hdr.InMessagesCount)
+	if err != nil {
+		return fmt.Errorf("failed to fetch incoming message queue: %w", err)
+	}
+	if uint32(len(msgs)) != hdr.InMessagesCount {
+		ctx.Logger().Error("runtime processed more incoming messages than queued",
+			"runtime_id", runtimeID,
+			"in_msgs_count", hdr.InMessagesCount,
+			"queue_size", len(msgs),
+		)
+		return roothash.ErrInvalidArgument
+	}
+	if inMsgsHash := block.InMessagesHash(msgs); !hdr.InMessagesHash.Equal(&inMsgsHash) {
+		ctx.Logger().Error("incoming message hash mismatch",
+			"runtime_id", runtimeID,
+			"expected", inMsgsHash,
+			"actual", hdr.InMessagesHash,
+		)
+		return roothash.ErrInvalidArgument
+	}
 
-			// Substitute empty block.
-			if err := app.emitEmptyBlock(ctx, rtState, block.RoundFailed); err != nil {
-				return fmt.Errorf("failed to emit empty block: %w", err)
-			}
+	return state.DequeueIncomingMessages(ctx, runtimeID, hdr.InMessagesCount)
+}
 
-			return nil
+func (app *rootHashApplication) postProcessFinalizedBlock(ctx *tmapi.Context, rtState *roothashState.RuntimeState, blk *block.Block) error {
+	// Execute the messages emitted by the runtime. Messages that fail do not
+	// fail the round, their results are made available to the runtime in the
+	// following round instead.
+	rtState.LastRoundResults = nil
+	if len(blk.Header.Messages) > 0 {
+		results := &roothash.RoundResults{}
+		for idx, message := range blk.Header.Messages {
+			results.Messages = append(results.Messages, app.processRuntimeMessage(ctx, rtState, uint32(idx), message))
 		}
+		rtState.LastRoundResults = results
 	}
 
-	sc.Commit()
-
-	// All good. Hook up the new block.
+	// Hook up the new block.
 	rtState.CurrentBlock = blk
 	rtState.CurrentBlockHeight = ctx.BlockHeight()
 
@@ -549,6 +588,46 @@ func (app *rootHashApplication) postProcessFinalizedBlock(ctx *tmapi.Context, rt
 	return nil
 }
 
+// processRuntimeMessage executes a single runtime message and returns the
+// result of its execution. Any state changes made by a failed message are
+// reverted.
+func (app *rootHashApplication) processRuntimeMessage(
+	ctx *tmapi.Context,
+	rtState *roothashState.RuntimeState,
+	index uint32,
+	message *block.Message,
+) *roothash.MessageEvent {
+	sc := ctx.StartCheckpoint()
+	defer sc.Close()
+
+	var err error
+	switch {
+	case message.Staking != nil:
+		err = stakingapp.ExecuteRuntimeMessage(ctx, rtState.Runtime.ID, message.Staking)
+	default:
+		err = roothash.ErrInvalidArgument
+	}
+	if err != nil {
+		ctx.Logger().Debug("runtime message failed",
+			"err", err,
+			"runtime_id", rtState.Runtime.ID,
+			"index", index,
+			logging.LogEvent, roothash.LogEventMessageUnsat,
+		)
+
+		module, code := errors.Code(err)
+		return &roothash.MessageEvent{
+			Module: module,
+			Code:   code,
+			Index:  index,
+		}
+	}
+
+	sc.Commit()
+
+	return &roothash.MessageEvent{Index: index}
+}
+
 func (app *rootHashApplication) tryFinalizeBlock(
 	ctx *tmapi.Context,
 	rtState *roothashState.RuntimeState,
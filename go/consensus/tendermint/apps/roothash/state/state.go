@@ -28,8 +28,32 @@ var (
 	//
 	// The format is (height, runtimeID). Value is runtimeID.
 	roundTimeoutQueueKeyFmt = keyformat.New(0x22, int64(0), keyformat.H(&common.Namespace{}))
+	// inMsgQueueMetaKeyFmt is the key format used for incoming message queue
+	// metadata.
+	//
+	// Value is CBOR-serialized IncomingMessageQueueMeta.
+	inMsgQueueMetaKeyFmt = keyformat.New(0x23, keyformat.H(&common.Namespace{}))
+	// inMsgQueueKeyFmt is the key format used for the incoming message queue.
+	//
+	// The format is (runtimeID, sequence number). Value is CBOR-serialized
+	// block.IncomingMessage.
+	inMsgQueueKeyFmt = keyformat.New(0x24, keyformat.H(&common.Namespace{}), uint64(0))
 )
 
+// IncomingMessageQueueMeta is the metadata of the incoming message queue of
+// a runtime.
+type IncomingMessageQueueMeta struct {
+	// Size is the number of queued messages.
+	Size uint32 `json:"size,omitempty"`
+	// NextSequenceNumber is the sequence number of the next queued message.
+	NextSequenceNumber uint64 `json:"next_sequence_number,omitempty"`
+}
+
+// head returns the sequence number of the first queued message.
+func (m *IncomingMessageQueueMeta) head() uint64 {
+	return m.NextSequenceNumber - uint64(m.Size)
+}
+
 // RuntimeState is the per-runtime roothash state.
 type RuntimeState struct {
 	Runtime   *registry.Runtime `json:"runtime"`
@@ -40,6 +64,10 @@ type RuntimeState struct {
 	CurrentBlock       *block.Block `json:"current_block"`
 	CurrentBlockHeight int64        `json:"current_block_height"`
 
+	// LastRoundResults are the results of executing the messages contained
+	// in the current block, if any.
+	LastRoundResults *roothash.RoundResults `json:"last_round_results,omitempty"`
+
 	ExecutorPool *commitment.Pool `json:"executor_pool"`
 }
 
@@ -143,6 +171,58 @@ func (s *ImmutableState) Runtimes(ctx context.Context) ([]*RuntimeState, error)
 	return runtimes, nil
 }
 
+// IncomingMessageQueueMeta returns the incoming message queue metadata of
+// the given runtime.
+func (s *ImmutableState) IncomingMessageQueueMeta(ctx context.Context, runtimeID common.Namespace) (*IncomingMessageQueueMeta, error) {
+	raw, err := s.is.Get(ctx, inMsgQueueMetaKeyFmt.Encode(&runtimeID))
+	if err != nil {
+		return nil, api.UnavailableStateError(err)
+	}
+	var meta IncomingMessageQueueMeta
+	if raw == nil {
+		return &meta, nil
+	}
+	if err = cbor.Unmarshal(raw, &meta); err != nil {
+		return nil, api.UnavailableStateError(err)
+	}
+	return &meta, nil
+}
+
+// IncomingMessageQueue returns at most limit queued incoming messages of the
+// given runtime, in queue order, skipping the first offset messages.
+func (s *ImmutableState) IncomingMessageQueue(
+	ctx context.Context,
+	runtimeID common.Namespace,
+	offset uint64,
+	limit uint32,
+) ([]*block.IncomingMessage, error) {
+	meta, err := s.IncomingMessageQueueMeta(ctx, runtimeID)
+	if err != nil {
+		return nil, err
+	}
+	if offset >= uint64(meta.Size) {
+		return nil, nil
+	}
+
+	var msgs []*block.IncomingMessage
+	for seq := meta.head() + offset; seq < meta.NextSequenceNumber && uint32(len(msgs)) < limit; seq++ {
+		raw, err := s.is.Get(ctx, inMsgQueueKeyFmt.Encode(&runtimeID, seq))
+		if err != nil {
+			return nil, api.UnavailableStateError(err)
+		}
+		if raw == nil {
+			return nil, fmt.Errorf("tendermint/roothash: incoming message %d missing from queue", seq)
+		}
+
+		var msg block.IncomingMessage
+		if err = cbor.Unmarshal(raw, &msg); err != nil {
+			return nil, api.UnavailableStateError(err)
+		}
+		msgs = append(msgs, &msg)
+	}
+	return msgs, nil
+}
+
 // ConsensusParameters returns the roothash consensus parameters.
 func (s *ImmutableState) ConsensusParameters(ctx context.Context) (*roothash.ConsensusParameters, error) {
 	raw, err := s.is.Get(ctx, parametersKeyFmt.Encode())
@@ -200,3 +280,45 @@ func (s *MutableState) ClearRoundTimeout(ctx context.Context, runtimeID common.N
 	err := s.ms.Remove(ctx, roundTimeoutQueueKeyFmt.Encode(height, &runtimeID))
 	return api.UnavailableStateError(err)
 }
+
+// EnqueueIncomingMessage appends an incoming message to the queue of the given
+// runtime, assigning it the next sequence number.
+func (s *MutableState) EnqueueIncomingMessage(ctx context.Context, runtimeID common.Namespace, msg *block.IncomingMessage) error {
+	meta, err := s.IncomingMessageQueueMeta(ctx, runtimeID)
+	if err != nil {
+		return err
+	}
+
+	msg.ID = meta.NextSequenceNumber
+	if err = s.ms.Insert(ctx, inMsgQueueKeyFmt.Encode(&runtimeID, msg.ID), cbor.Marshal(msg)); err != nil {
+		return api.UnavailableStateError(err)
+	}
+
+	meta.Size++
+	meta.NextSequenceNumber++
+	err = s.ms.Insert(ctx, inMsgQueueMetaKeyFmt.Encode(&runtimeID), cbor.Marshal(meta))
+	return api.UnavailableStateError(err)
+}
+
+// DequeueIncomingMessages removes the given number of messages from the head
+// of the incoming message queue of the given runtime.
+func (s *MutableState) DequeueIncomingMessages(ctx context.Context, runtimeID common.Namespace, count uint32) error {
+	meta, err := s.IncomingMessageQueueMeta(ctx, runtimeID)
+	if err != nil {
+		return err
+	}
+	if count > meta.Size {
+		return fmt.Errorf("tendermint/roothash: cannot dequeue %d messages from a queue of %d", count, meta.Size)
+	}
+
+	head := meta.head()
+	for seq := head; seq < head+uint64(count); seq++ {
+		if err = s.ms.Remove(ctx, inMsgQueueKeyFmt.Encode(&runtimeID, seq)); err != nil {
+			return api.UnavailableStateError(err)
+		}
+	}
+
+	meta.Size -= count
+	err = s.ms.Insert(ctx, inMsgQueueMetaKeyFmt.Encode(&runtimeID), cbor.Marshal(meta))
+	return api.UnavailableStateError(err)
+}
@@ -0,0 +1,69 @@
+package state
+
+import (
+	"testing"
+	"time"
+
+	"github.com/stretchr/testify/require"
+
+	"github.com/oasisprotocol/oasis-core/go/common"
+	abciAPI "github.com/oasisprotocol/oasis-core/go/consensus/tendermint/api"
+	"github.com/oasisprotocol/oasis-core/go/roothash/api/block"
+)
+
+func TestIncomingMessageQueue(t *testing.T) {
+	require := require.New(t)
+
+	now := time.Unix(1580461674, 0)
+	appState := abciAPI.NewMockApplicationState(&abciAPI.MockApplicationStateConfig{})
+	ctx := appState.NewContext(abciAPI.ContextBeginBlock, now)
+	defer ctx.Close()
+
+	s := NewMutableState(ctx.State())
+
+	var runtimeID common.Namespace
+	require.NoError(runtimeID.UnmarshalHex("8000000000000000000000000000000000000000000000000000000000000000"))
+
+	// Empty queue.
+	meta, err := s.IncomingMessageQueueMeta(ctx, runtimeID)
+	require.NoError(err, "IncomingMessageQueueMeta")
+	require.EqualValues(0, meta.Size, "queue should be empty")
+	msgs, err := s.IncomingMessageQueue(ctx, runtimeID, 0, 10)
+	require.NoError(err, "IncomingMessageQueue")
+	require.Empty(msgs, "queue should be empty")
+
+	// Enqueue some messages.
+	for i := 0; i < 3; i++ {
+		err = s.EnqueueIncomingMessage(ctx, runtimeID, &block.IncomingMessage{Tag: uint64(i)})
+		require.NoError(err, "EnqueueIncomingMessage")
+	}
+	msgs, err = s.IncomingMessageQueue(ctx, runtimeID, 0, 10)
+	require.NoError(err, "IncomingMessageQueue")
+	require.Len(msgs, 3, "all messages should be queued")
+	for i, msg := range msgs {
+		require.EqualValues(i, msg.ID, "messages should be assigned sequence numbers")
+		require.EqualValues(i, msg.Tag, "messages should be returned in queue order")
+	}
+	msgs, err = s.IncomingMessageQueue(ctx, runtimeID, 1, 1)
+	require.NoError(err, "IncomingMessageQueue")
+	require.Len(msgs, 1, "limit should be respected")
+	require.EqualValues(1, msgs[0].ID, "offset should be respected")
+
+	// Dequeue messages from the head of the queue.
+	err = s.DequeueIncomingMessages(ctx, runtimeID, 4)
+	require.Error(err, "DequeueIncomingMessages should fail for more messages than queued")
+	err = s.DequeueIncomingMessages(ctx, runtimeID, 2)
+	require.NoError(err, "DequeueIncomingMessages")
+	msgs, err = s.IncomingMessageQueue(ctx, runtimeID, 0, 10)
+	require.NoError(err, "IncomingMessageQueue")
+	require.Len(msgs, 1, "dequeued messages should be removed")
+	require.EqualValues(2, msgs[0].ID, "remaining message should be at the head of the queue")
+
+	// Sequence numbers are not reused.
+	err = s.EnqueueIncomingMessage(ctx, runtimeID, &block.IncomingMessage{})
+	require.NoError(err, "EnqueueIncomingMessage")
+	meta, err = s.IncomingMessageQueueMeta(ctx, runtimeID)
+	require.NoError(err, "IncomingMessageQueueMeta")
+	require.EqualValues(2, meta.Size, "queue size should be updated")
+	require.EqualValues(4, meta.NextSequenceNumber, "sequence numbers should not be reused")
+}
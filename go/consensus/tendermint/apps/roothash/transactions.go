@@ -12,10 +12,12 @@ import (
 	registryState "github.com/oasisprotocol/oasis-core/go/consensus/tendermint/apps/registry/state"
 	roothashState "github.com/oasisprotocol/oasis-core/go/consensus/tendermint/apps/roothash/state"
 	schedulerState "github.com/oasisprotocol/oasis-core/go/consensus/tendermint/apps/scheduler/state"
+	stakingapp "github.com/oasisprotocol/oasis-core/go/consensus/tendermint/apps/staking"
 	roothash "github.com/oasisprotocol/oasis-core/go/roothash/api"
 	"github.com/oasisprotocol/oasis-core/go/roothash/api/block"
 	"github.com/oasisprotocol/oasis-core/go/roothash/api/commitment"
 	scheduler "github.com/oasisprotocol/oasis-core/go/scheduler/api"
+	staking "github.com/oasisprotocol/oasis-core/go/staking/api"
 )
 
 var _ commitment.SignatureVerifier = (*roothashSignatureVerifier)(nil)
@@ -245,3 +247,76 @@ func (app *rootHashApplication) executorCommit(
 
 	return nil
 }
+
+func (app *rootHashApplication) submitMsg(
+	ctx *abciAPI.Context,
+	state *roothashState.MutableState,
+	msg *roothash.SubmitMsg,
+) error {
+	if ctx.IsCheckOnly() {
+		return nil
+	}
+
+	// Charge gas for this transaction.
+	params, err := state.ConsensusParameters(ctx)
+	if err != nil {
+		ctx.Logger().Error("SubmitMsg: failed to fetch consensus parameters",
+			"err", err,
+		)
+		return err
+	}
+	if err = ctx.Gas().UseGas(1, roothash.GasOpSubmitMsg, params.GasCosts); err != nil {
+		return err
+	}
+
+	rtState, err := state.RuntimeState(ctx, msg.ID)
+	if err != nil {
+		return roothash.ErrInvalidRuntime
+	}
+	if rtState.Suspended {
+		return roothash.ErrRuntimeSuspended
+	}
+
+	// Ensure the queue has room for the message.
+	meta, err := state.IncomingMessageQueueMeta(ctx, msg.ID)
+	if err != nil {
+		return fmt.Errorf("failed to fetch incoming message queue metadata: %w", err)
+	}
+	if meta.Size >= rtState.Runtime.Executor.MaxInMessages {
+		ctx.Logger().Debug("SubmitMsg: incoming message queue full",
+			"runtime_id", msg.ID,
+			"queue_size", meta.Size,
+		)
+		return roothash.ErrIncomingMessageQueueFull
+	}
+
+	// Move the fee and the deposited tokens to the runtime account.
+	caller := staking.NewAddress(ctx.TxSigner())
+	amount := msg.Fee.Clone()
+	if err = amount.Add(&msg.Tokens); err != nil {
+		return roothash.ErrInvalidArgument
+	}
+	if !amount.IsZero() {
+		if err = stakingapp.DepositToRuntime(ctx, caller, msg.ID, amount); err != nil {
+			ctx.Logger().Debug("SubmitMsg: failed to deposit tokens to runtime",
+				"err", err,
+				"runtime_id", msg.ID,
+				"caller", caller,
+			)
+			return err
+		}
+	}
+
+	inMsg := &block.IncomingMessage{
+		Caller: caller,
+		Tag:    msg.Tag,
+		Fee:    msg.Fee,
+		Tokens: msg.Tokens,
+		Data:   msg.Data,
+	}
+	if err = state.EnqueueIncomingMessage(ctx, msg.ID, inMsg); err != nil {
+		return fmt.Errorf("failed to enqueue incoming message: %w", err)
+	}
+
+	return nil
+}
@@ -0,0 +1,53 @@
+package staking
+
+import (
+	"fmt"
+
+	"github.com/oasisprotocol/oasis-core/go/common"
+	"github.com/oasisprotocol/oasis-core/go/common/quantity"
+	"github.com/oasisprotocol/oasis-core/go/consensus/tendermint/api"
+	stakingState "github.com/oasisprotocol/oasis-core/go/consensus/tendermint/apps/staking/state"
+	"github.com/oasisprotocol/oasis-core/go/roothash/api/block"
+	staking "github.com/oasisprotocol/oasis-core/go/staking/api"
+)
+
+// ExecuteRuntimeMessage executes a staking message emitted by the given
+// runtime, on behalf of its runtime account.
+//
+// The caller is responsible for validating the message and for reverting any
+// state changes in case of an error.
+func ExecuteRuntimeMessage(ctx *api.Context, runtimeID common.Namespace, msg *block.StakingMessage) error {
+	state := stakingState.NewMutableState(ctx.State())
+	params, err := state.ConsensusParameters(ctx)
+	if err != nil {
+		return fmt.Errorf("failed to fetch consensus parameters: %w", err)
+	}
+
+	runtimeAddr := staking.NewRuntimeAddress(runtimeID)
+	switch {
+	case msg.Transfer != nil:
+		return doTransfer(ctx, state, params, runtimeAddr, msg.Transfer)
+	case msg.Withdraw != nil:
+		return doWithdraw(ctx, state, params, runtimeAddr, msg.Withdraw)
+	default:
+		return staking.ErrInvalidArgument
+	}
+}
+
+// DepositToRuntime transfers the given amount of tokens from the given account
+// to the runtime account of the given runtime.
+//
+// The caller is responsible for reverting any state changes in case of an
+// error.
+func DepositToRuntime(ctx *api.Context, fromAddr staking.Address, runtimeID common.Namespace, amount *quantity.Quantity) error {
+	state := stakingState.NewMutableState(ctx.State())
+	params, err := state.ConsensusParameters(ctx)
+	if err != nil {
+		return fmt.Errorf("failed to fetch consensus parameters: %w", err)
+	}
+
+	return doTransfer(ctx, state, params, fromAddr, &staking.Transfer{
+		To:     staking.NewRuntimeAddress(runtimeID),
+		Amount: *amount,
+	})
+}
@@ -35,7 +35,17 @@ func (app *stakingApplication) transfer(ctx *api.Context, state *stakingState.Mu
 		return err
 	}
 
-	fromAddr := staking.NewAddress(ctx.TxSigner())
+	return doTransfer(ctx, state, params, staking.NewAddress(ctx.TxSigner()), xfer)
+}
+
+// doTransfer transfers tokens from the given account.
+func doTransfer(
+	ctx *api.Context,
+	state *stakingState.MutableState,
+	params *staking.ConsensusParameters,
+	fromAddr staking.Address,
+	xfer *staking.Transfer,
+) error {
 	if fromAddr.IsReserved() || !isTransferPermitted(params, fromAddr) {
 		return staking.ErrForbidden
 	}
@@ -95,7 +105,7 @@ func (app *stakingApplication) transfer(ctx *api.Context, state *stakingState.Mu
 		To:     xfer.To,
 		Amount: xfer.Amount,
 	}
-	ctx.EmitEvent(api.NewEventBuilder(app.Name()).Attribute(KeyTransfer, cbor.Marshal(evt)))
+	ctx.EmitEvent(api.NewEventBuilder(AppName).Attribute(KeyTransfer, cbor.Marshal(evt)))
 
 	return nil
 }
@@ -523,13 +533,24 @@ func (app *stakingApplication) withdraw(
 		return err
 	}
 
+	return doWithdraw(ctx, state, params, staking.NewAddress(ctx.TxSigner()), withdraw)
+}
+
+// doWithdraw withdraws tokens to the given account, using an allowance given
+// to it.
+func doWithdraw(
+	ctx *api.Context,
+	state *stakingState.MutableState,
+	params *staking.ConsensusParameters,
+	toAddr staking.Address,
+	withdraw *staking.Withdraw,
+) error {
 	// Allowances are disabled in case either max allowances is zero or if transfers are disabled.
 	if params.DisableTransfers || params.MaxAllowances == 0 {
 		return staking.ErrForbidden
 	}
 
 	// Validate addresses -- if either is reserved or both are equal, the method should fail.
-	toAddr := staking.NewAddress(ctx.TxSigner())
 	if toAddr.IsReserved() || withdraw.From.IsReserved() {
 		return staking.ErrForbidden
 	}
@@ -582,7 +603,7 @@ func (app *stakingApplication) withdraw(
 		To:     toAddr,
 		Amount: withdraw.Amount,
 	}
-	ctx.EmitEvent(api.NewEventBuilder(app.Name()).Attribute(KeyTransfer, cbor.Marshal(xferEvt)))
+	ctx.EmitEvent(api.NewEventBuilder(AppName).Attribute(KeyTransfer, cbor.Marshal(xferEvt)))
 
 	awEvt := &staking.AllowanceChangeEvent{
 		Owner:        withdraw.From,
@@ -591,7 +612,7 @@ func (app *stakingApplication) withdraw(
 		Negative:     true,
 		AmountChange: withdraw.Amount,
 	}
-	ctx.EmitEvent(api.NewEventBuilder(app.Name()).Attribute(KeyAllowanceChange, cbor.Marshal(awEvt)))
+	ctx.EmitEvent(api.NewEventBuilder(AppName).Attribute(KeyAllowanceChange, cbor.Marshal(awEvt)))
 
 	return nil
 }
@@ -18,7 +18,19 @@ func (t *fullService) GetLightBlock(ctx context.Context, height int64) (*consens
 		return nil, err
 	}
 
-	commit, err := t.client.Commit(ctx, &height)
+	var tmHeight int64
+	if height == consensusAPI.HeightLatest {
+		// As in GetTendermintBlock, use our mux notion of latest height.
+		tmHeight = t.mux.State().BlockHeight()
+		if tmHeight == 0 {
+			// No committed blocks yet.
+			return nil, consensusAPI.ErrNoCommittedBlocks
+		}
+	} else {
+		tmHeight = height
+	}
+
+	commit, err := t.client.Commit(ctx, &tmHeight)
 	if err != nil {
 		return nil, fmt.Errorf("%w: tendermint: header query failed: %s", consensusAPI.ErrVersionNotFound, err.Error())
 	}
@@ -28,7 +40,7 @@ func (t *fullService) GetLightBlock(ctx context.Context, height int64) (*consens
 	}
 
 	// Don't use the client as that imposes stupid pagination. Access the state database directly.
-	vals, err := t.stateStore.LoadValidators(height)
+	vals, err := t.stateStore.LoadValidators(tmHeight)
 	if err != nil {
 		return nil, consensusAPI.ErrVersionNotFound
 	}
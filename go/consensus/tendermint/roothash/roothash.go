@@ -119,6 +119,30 @@ func (sc *serviceClient) getLatestBlockAt(ctx context.Context, id common.Namespa
 	return q.LatestBlock(ctx, id)
 }
 
+func (sc *serviceClient) GetLastRoundResults(ctx context.Context, id common.Namespace, height int64) (*api.RoundResults, error) {
+	q, err := sc.querier.QueryAt(ctx, height)
+	if err != nil {
+		return nil, err
+	}
+
+	return q.LastRoundResults(ctx, id)
+}
+
+func (sc *serviceClient) GetIncomingMessageQueue(
+	ctx context.Context,
+	id common.Namespace,
+	height int64,
+	offset uint64,
+	limit uint32,
+) ([]*block.IncomingMessage, error) {
+	q, err := sc.querier.QueryAt(ctx, height)
+	if err != nil {
+		return nil, err
+	}
+
+	return q.IncomingMessageQueue(ctx, id, offset, limit)
+}
+
 func (sc *serviceClient) WatchBlocks(id common.Namespace) (<-chan *api.AnnotatedBlock, *pubsub.Subscription, error) {
 	notifiers := sc.getRuntimeNotifiers(id)
 
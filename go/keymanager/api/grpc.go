@@ -17,6 +17,10 @@ var (
 	// `keymanager-runtime/src/methods.rs`.
 	getPublicKeyRequestMethod = "get_public_key"
 
+	// Make sure this always matches the appropriate method in
+	// `keymanager-runtime/src/methods.rs`.
+	getPublicEphemeralKeyRequestMethod = "get_public_ephemeral_key"
+
 	// serviceName is the gRPC service name.
 	serviceName = cmnGrpc.NewServiceName("KeyManager")
 
@@ -57,7 +61,7 @@ func (e *enclaveRPCEndpoint) AccessControlRequired(ctx context.Context, request
 	case "":
 		// Anyone can connect.
 		return false, nil
-	case getPublicKeyRequestMethod:
+	case getPublicKeyRequestMethod, getPublicEphemeralKeyRequestMethod:
 		// Anyone can get public keys.
 		//
 		// Note that this is also checked in the enclave, so if the node lied
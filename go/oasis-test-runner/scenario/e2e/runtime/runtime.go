@@ -358,6 +358,9 @@ func (sc *runtimeImpl) submitRuntimeTx(ctx context.Context, id common.Namespace,
 	if rsp.Error != nil {
 		return nil, fmt.Errorf("runtime tx failed: %s", *rsp.Error)
 	}
+	if rsp.OutOfGas != nil {
+		return nil, fmt.Errorf("runtime tx ran out of gas (limit %d)", rsp.OutOfGas.Limit)
+	}
 	return rsp.Success, nil
 }
 
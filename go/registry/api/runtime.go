@@ -92,6 +92,14 @@ type ExecutorParameters struct {
 
 	// RoundTimeout is the round timeout in consensus blocks.
 	RoundTimeout int64 `json:"round_timeout"`
+
+	// MaxMessages is the maximum number of messages that can be emitted by
+	// the runtime in a single round.
+	MaxMessages uint32 `json:"max_messages,omitempty"`
+
+	// MaxInMessages is the maximum number of incoming messages that can be
+	// queued for the runtime.
+	MaxInMessages uint32 `json:"max_in_messages,omitempty"`
 }
 
 // ValidateBasic performs basic executor parameter validity checks.
@@ -10,6 +10,7 @@ import (
 	"github.com/oasisprotocol/oasis-core/go/common/crypto/hash"
 	"github.com/oasisprotocol/oasis-core/go/common/errors"
 	"github.com/oasisprotocol/oasis-core/go/common/pubsub"
+	"github.com/oasisprotocol/oasis-core/go/common/quantity"
 	"github.com/oasisprotocol/oasis-core/go/consensus/api/transaction"
 	"github.com/oasisprotocol/oasis-core/go/oasis-node/cmd/common/flags"
 	"github.com/oasisprotocol/oasis-core/go/registry/api"
@@ -54,16 +55,25 @@ var (
 	// ErrProposerTimeoutNotAllowed is the error returned when proposer timeout is not allowed.
 	ErrProposerTimeoutNotAllowed = errors.New(ModuleName, 6, "roothash: proposer timeout not allowed")
 
+	// ErrIncomingMessageQueueFull is the error returned when the incoming
+	// message queue of a runtime is full.
+	ErrIncomingMessageQueueFull = errors.New(ModuleName, 7, "roothash: incoming message queue full")
+
 	// MethodExecutorCommit is the method name for executor commit submission.
 	MethodExecutorCommit = transaction.NewMethodName(ModuleName, "ExecutorCommit", ExecutorCommit{})
 
 	// MethodExecutorProposerTimeout is the method name for executor.
 	MethodExecutorProposerTimeout = transaction.NewMethodName(ModuleName, "ExecutorProposerTimeout", ExecutorProposerTimeoutRequest{})
 
+	// MethodSubmitMsg is the method name for submitting incoming runtime
+	// messages.
+	MethodSubmitMsg = transaction.NewMethodName(ModuleName, "SubmitMsg", SubmitMsg{})
+
 	// Methods is a list of all methods supported by the roothash backend.
 	Methods = []transaction.MethodName{
 		MethodExecutorCommit,
 		MethodExecutorProposerTimeout,
+		MethodSubmitMsg,
 	}
 )
 
@@ -78,6 +88,14 @@ type Backend interface {
 	// the latest state from the storage backend.
 	GetLatestBlock(ctx context.Context, runtimeID common.Namespace, height int64) (*block.Block, error)
 
+	// GetIncomingMessageQueue returns the queued incoming messages of the
+	// given runtime, starting at the given offset into the queue.
+	GetIncomingMessageQueue(ctx context.Context, runtimeID common.Namespace, height int64, offset uint64, limit uint32) ([]*block.IncomingMessage, error)
+
+	// GetLastRoundResults returns the results of executing the messages
+	// contained in the latest block.
+	GetLastRoundResults(ctx context.Context, runtimeID common.Namespace, height int64) (*RoundResults, error)
+
 	// WatchBlocks returns a channel that produces a stream of
 	// annotated blocks.
 	//
@@ -130,6 +148,26 @@ func NewRequestProposerTimeoutTx(nonce uint64, fee *transaction.Fee, runtimeID c
 	})
 }
 
+// SubmitMsg is a request to queue an incoming message for a runtime.
+type SubmitMsg struct {
+	// ID is the identifier of the runtime.
+	ID common.Namespace `json:"id"`
+	// Tag is a runtime-specific tag of the message.
+	Tag uint64 `json:"tag,omitempty"`
+	// Fee is the fee paid to the runtime for processing the message.
+	Fee quantity.Quantity `json:"fee,omitempty"`
+	// Tokens are the tokens deposited to the runtime with the message.
+	Tokens quantity.Quantity `json:"tokens,omitempty"`
+	// Data is the runtime-specific payload of the message.
+	Data []byte `json:"data,omitempty"`
+}
+
+// NewSubmitMsgTx creates a new incoming runtime message submission
+// transaction.
+func NewSubmitMsgTx(nonce uint64, fee *transaction.Fee, msg *SubmitMsg) *transaction.Transaction {
+	return transaction.NewTransaction(nonce, fee, MethodSubmitMsg, msg)
+}
+
 // AnnotatedBlock is an annotated roothash block.
 type AnnotatedBlock struct {
 	// Height is the underlying roothash backend's block height that
@@ -140,6 +178,27 @@ type AnnotatedBlock struct {
 	Block *block.Block `json:"block"`
 }
 
+// MessageEvent is the result of executing a runtime message.
+type MessageEvent struct {
+	// Module is the module of the error, if any.
+	Module string `json:"module,omitempty"`
+	// Code is the code of the error, if any.
+	Code uint32 `json:"code,omitempty"`
+	// Index is the index of the message in the block which emitted it.
+	Index uint32 `json:"index,omitempty"`
+}
+
+// IsSuccess returns true if the message was executed successfully.
+func (me *MessageEvent) IsSuccess() bool {
+	return me.Code == errors.CodeNoError
+}
+
+// RoundResults are the results of executing the messages emitted in a round.
+type RoundResults struct {
+	// Messages are the results of the emitted messages, in emission order.
+	Messages []*MessageEvent `json:"messages,omitempty"`
+}
+
 // ExecutorCommittedEvent is an event emitted each time an executor node commits.
 type ExecutorCommittedEvent struct {
 	// Commit is the executor commitment.
@@ -208,6 +267,10 @@ const (
 
 	// GasOpProposerTimeout is the gas operation identifier for executor propose timeout cost.
 	GasOpProposerTimeout transaction.Op = "proposer_timeout"
+
+	// GasOpSubmitMsg is the gas operation identifier for incoming runtime
+	// message submission cost.
+	GasOpSubmitMsg transaction.Op = "submit_msg"
 )
 
 // XXX: Define reasonable default gas costs.
@@ -216,6 +279,7 @@ const (
 var DefaultGasCosts = transaction.Costs{
 	GasOpComputeCommit:   1000,
 	GasOpProposerTimeout: 1000,
+	GasOpSubmitMsg:       1000,
 }
 
 // SanityCheckBlocks examines the blocks table.
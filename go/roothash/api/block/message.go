@@ -1,6 +1,82 @@
 package block
 
+import (
+	"fmt"
+
+	"github.com/oasisprotocol/oasis-core/go/common/cbor"
+	"github.com/oasisprotocol/oasis-core/go/common/crypto/hash"
+	"github.com/oasisprotocol/oasis-core/go/common/quantity"
+	staking "github.com/oasisprotocol/oasis-core/go/staking/api"
+)
+
 // Message is a roothash message that can be sent by a runtime.
+//
+// Messages are executed by the consensus layer once the block which contains
+// them is finalized, and the results of their execution are made available to
+// the runtime in the following round.
 type Message struct {
-	// No valid messages are currently defined.
+	Staking *StakingMessage `json:"staking,omitempty"`
+}
+
+// ValidateBasic performs basic validation of the runtime message.
+func (m *Message) ValidateBasic() error {
+	switch {
+	case m.Staking != nil:
+		return m.Staking.ValidateBasic()
+	default:
+		return fmt.Errorf("runtime message has no fields set")
+	}
+}
+
+// StakingMessage is a runtime message that allows a runtime to perform staking
+// operations on behalf of its runtime account.
+type StakingMessage struct {
+	cbor.Versioned
+
+	// Transfer transfers tokens from the runtime account.
+	Transfer *staking.Transfer `json:"transfer,omitempty"`
+	// Withdraw withdraws tokens to the runtime account, using an allowance
+	// given to the runtime account.
+	Withdraw *staking.Withdraw `json:"withdraw,omitempty"`
+}
+
+// ValidateBasic performs basic validation of the staking message.
+func (sm *StakingMessage) ValidateBasic() error {
+	if sm.V != 0 {
+		return fmt.Errorf("staking runtime message has unsupported version %d", sm.V)
+	}
+
+	switch {
+	case sm.Transfer != nil && sm.Withdraw == nil:
+		return nil
+	case sm.Transfer == nil && sm.Withdraw != nil:
+		return nil
+	default:
+		return fmt.Errorf("staking runtime message must have exactly one field set")
+	}
+}
+
+// IncomingMessage is a message sent from the consensus layer to a runtime.
+//
+// Incoming messages are queued by the consensus layer and processed by the
+// runtime in queue order. Any fee and tokens attached to a message are moved
+// to the runtime account when the message is queued.
+type IncomingMessage struct {
+	// ID is the sequence number of the message in the queue.
+	ID uint64 `json:"id"`
+	// Caller is the address of the account which submitted the message.
+	Caller staking.Address `json:"caller"`
+	// Tag is a runtime-specific tag of the message.
+	Tag uint64 `json:"tag,omitempty"`
+	// Fee is the fee paid to the runtime for processing the message.
+	Fee quantity.Quantity `json:"fee,omitempty"`
+	// Tokens are the tokens deposited to the runtime with the message.
+	Tokens quantity.Quantity `json:"tokens,omitempty"`
+	// Data is the runtime-specific payload of the message.
+	Data []byte `json:"data,omitempty"`
+}
+
+// InMessagesHash returns the hash of the given processed incoming messages.
+func InMessagesHash(msgs []*IncomingMessage) hash.Hash {
+	return hash.NewFrom(msgs)
 }
@@ -0,0 +1,62 @@
+package block
+
+import (
+	"encoding/hex"
+	"testing"
+
+	"github.com/stretchr/testify/require"
+
+	"github.com/oasisprotocol/oasis-core/go/common/cbor"
+	"github.com/oasisprotocol/oasis-core/go/common/quantity"
+	staking "github.com/oasisprotocol/oasis-core/go/staking/api"
+)
+
+func TestMessageValidateBasic(t *testing.T) {
+	require := require.New(t)
+
+	for _, tc := range []struct {
+		msg   Message
+		valid bool
+	}{
+		{Message{}, false},
+		{Message{Staking: &StakingMessage{}}, false},
+		{Message{Staking: &StakingMessage{Transfer: &staking.Transfer{}}}, true},
+		{Message{Staking: &StakingMessage{Withdraw: &staking.Withdraw{}}}, true},
+		{Message{Staking: &StakingMessage{Transfer: &staking.Transfer{}, Withdraw: &staking.Withdraw{}}}, false},
+		{Message{Staking: &StakingMessage{Versioned: cbor.NewVersioned(1), Transfer: &staking.Transfer{}}}, false},
+	} {
+		err := tc.msg.ValidateBasic()
+		if tc.valid {
+			require.NoError(err, "ValidateBasic(%+v)", tc.msg)
+		} else {
+			require.Error(err, "ValidateBasic(%+v)", tc.msg)
+		}
+	}
+}
+
+func TestMessageSerialization(t *testing.T) {
+	require := require.New(t)
+
+	// NOTE: These cases MUST be synced with runtime/src/common/roothash.rs.
+	amount := *quantity.NewFromUint64(1000)
+	for _, tc := range []struct {
+		msg         Message
+		expectedHex string
+	}{
+		{
+			Message{Staking: &StakingMessage{Transfer: &staking.Transfer{Amount: amount}}},
+			"a1677374616b696e67a2617600687472616e73666572a262746f5500000000000000000000000000000000000000000066616d6f756e744203e8",
+		},
+		{
+			Message{Staking: &StakingMessage{Withdraw: &staking.Withdraw{Amount: amount}}},
+			"a1677374616b696e67a2617600687769746864726177a26466726f6d5500000000000000000000000000000000000000000066616d6f756e744203e8",
+		},
+	} {
+		enc := cbor.Marshal(tc.msg)
+		require.EqualValues(tc.expectedHex, hex.EncodeToString(enc), "serialization should match")
+
+		var dec Message
+		require.NoError(cbor.Unmarshal(enc, &dec), "Unmarshal")
+		require.EqualValues(tc.msg, dec, "serialization should round-trip")
+	}
+}
@@ -41,6 +41,11 @@ type ComputeResultsHeader struct {
 	IORoot    *hash.Hash       `json:"io_root,omitempty"`
 	StateRoot *hash.Hash       `json:"state_root,omitempty"`
 	Messages  []*block.Message `json:"messages,omitempty"`
+
+	// InMessagesHash is the hash of the processed incoming messages, if any.
+	InMessagesHash *hash.Hash `json:"in_msgs_hash,omitempty"`
+	// InMessagesCount is the number of processed incoming messages.
+	InMessagesCount uint32 `json:"in_msgs_count,omitempty"`
 }
 
 // IsParentOf returns true iff the header is the parent of a child header.
@@ -214,11 +214,20 @@ func (p *Pool) addOpenExecutorCommitment(
 		return ErrNoRuntime
 	}
 
-	// Make sure the commitment does not contain any messages as these are currently
-	// not supported and should be rejected.
-	if len(header.Messages) > 0 {
+	// Make sure the commitment does not contain more messages than the runtime
+	// is allowed to emit and that all messages are well-formed.
+	if uint32(len(header.Messages)) > p.Runtime.Executor.MaxMessages {
 		return ErrInvalidMessages
 	}
+	for _, msg := range header.Messages {
+		if err := msg.ValidateBasic(); err != nil {
+			logger.Debug("executor commitment contains an invalid message",
+				"node_id", id,
+				"err", err,
+			)
+			return ErrInvalidMessages
+		}
+	}
 
 	// Check if the block is based on the previous block.
 	if !header.IsParentOf(&blk.Header) {
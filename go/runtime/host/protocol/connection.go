@@ -68,6 +68,15 @@ type Handler interface {
 	Handle(ctx context.Context, body *Body) (*Body, error)
 }
 
+type requestIDKey struct{}
+
+// RequestIDFromContext returns the identifier of the runtime request that is
+// being handled, if any.
+func RequestIDFromContext(ctx context.Context) (uint64, bool) {
+	id, ok := ctx.Value(requestIDKey{}).(uint64)
+	return id, ok
+}
+
 // Notifier is a protocol runtime notifier interface.
 type Notifier interface {
 	// Start the notifier.
@@ -112,6 +121,10 @@ type Connection interface {
 	//
 	// Only one of InitHost/InitGuest can be called otherwise the method may panic.
 	InitGuest(ctx context.Context, conn net.Conn) error
+
+	// AttestationRefreshRequests returns a channel on which a value is sent whenever the runtime
+	// requests its attestation to be refreshed.
+	AttestationRefreshRequests() <-chan struct{}
 }
 
 // state is the connection state.
@@ -168,9 +181,10 @@ type connection struct { // nolint: maligned
 	pendingRequests map[uint64]chan *Body
 	nextRequestID   uint64
 
-	outCh   chan *Message
-	closeCh chan struct{}
-	quitWg  sync.WaitGroup
+	outCh           chan *Message
+	closeCh         chan struct{}
+	attestRefreshCh chan struct{}
+	quitWg          sync.WaitGroup
 
 	logger *logging.Logger
 }
@@ -371,6 +385,25 @@ func newResponseMessage(req *Message, body *Body) *Message {
 func (c *connection) handleMessage(ctx context.Context, message *Message) {
 	switch message.MessageType {
 	case MessageRequest:
+		// Log records are accepted in any state, as the runtime starts
+		// forwarding them during initialization.
+		if message.Body.HostLogRequest != nil {
+			c.handleLogRequest(message.Body.HostLogRequest)
+			_ = c.sendMessage(ctx, newResponseMessage(message, &Body{HostLogResponse: &Empty{}}))
+			return
+		}
+
+		// Attestation refresh requests are handled by the runtime provisioner.
+		if message.Body.HostAttestationRefreshRequest != nil {
+			select {
+			case c.attestRefreshCh <- struct{}{}:
+			default:
+				// A refresh is already pending.
+			}
+			_ = c.sendMessage(ctx, newResponseMessage(message, &Body{HostAttestationRefreshResponse: &Empty{}}))
+			return
+		}
+
 		// Incoming request.
 		var allowed bool
 		state := c.getState()
@@ -408,6 +441,7 @@ func (c *connection) handleMessage(ctx context.Context, message *Message) {
 		}
 
 		// Call actual handler.
+		ctx = context.WithValue(ctx, requestIDKey{}, message.ID)
 		body, err := c.handler.Handle(ctx, &message.Body)
 		if err != nil {
 			body = errorToBody(err)
@@ -442,6 +476,32 @@ func (c *connection) handleMessage(ctx context.Context, message *Message) {
 	}
 }
 
+// Implements Connection.
+func (c *connection) AttestationRefreshRequests() <-chan struct{} {
+	return c.attestRefreshCh
+}
+
+func (c *connection) handleLogRequest(rq *HostLogRequest) {
+	for _, record := range rq.Records {
+		keyvals := make([]interface{}, 0, 2*len(record.Fields)+2)
+		keyvals = append(keyvals, "runtime_module", record.Module)
+		for key, value := range record.Fields {
+			keyvals = append(keyvals, key, value)
+		}
+
+		switch record.Level {
+		case "trace", "debug":
+			c.logger.Debug(record.Msg, keyvals...)
+		case "info":
+			c.logger.Info(record.Msg, keyvals...)
+		case "warn":
+			c.logger.Warn(record.Msg, keyvals...)
+		default:
+			c.logger.Error(record.Msg, keyvals...)
+		}
+	}
+}
+
 func (c *connection) workerIncoming() {
 	ctx, cancel := context.WithCancel(context.Background())
 	defer func() {
@@ -516,7 +576,8 @@ func (c *connection) InitHost(ctx context.Context, conn net.Conn) (*version.Vers
 
 	// Check Runtime Host Protocol version.
 	rsp, err := c.call(ctx, &Body{RuntimeInfoRequest: &RuntimeInfoRequest{
-		RuntimeID: c.runtimeID,
+		RuntimeID:           c.runtimeID,
+		HostProtocolVersion: version.RuntimeHostProtocol.ToU64(),
 	}})
 	switch {
 	default:
@@ -565,6 +626,7 @@ func NewConnection(logger *logging.Logger, runtimeID common.Namespace, handler H
 		pendingRequests: make(map[uint64]chan *Body),
 		outCh:           make(chan *Message),
 		closeCh:         make(chan struct{}),
+		attestRefreshCh: make(chan struct{}, 1),
 		logger:          logger,
 	}
 
@@ -0,0 +1,26 @@
+package protocol
+
+import "github.com/oasisprotocol/oasis-core/go/common/errors"
+
+// QueryModuleName is the name of the error module of runtime query errors.
+//
+// NOTE: The errors must be synchronized with runtime/src/query.rs.
+const QueryModuleName = "runtime/query"
+
+var (
+	// ErrQueryMethodNotFound is the error returned when the queried method
+	// is not registered by the runtime.
+	ErrQueryMethodNotFound = errors.New(QueryModuleName, 1, "query: method not found")
+
+	// ErrQueryMalformedArguments is the error returned when the query
+	// arguments cannot be decoded by the runtime.
+	ErrQueryMalformedArguments = errors.New(QueryModuleName, 2, "query: malformed arguments")
+
+	// ErrQueryFailed is the error returned when the queried method fails.
+	ErrQueryFailed = errors.New(QueryModuleName, 3, "query: failed")
+
+	// ErrQueryConsensusNotAvailable is the error returned when the queried
+	// method needs the consensus state, but the runtime does not verify the
+	// consensus layer.
+	ErrQueryConsensusNotAvailable = errors.New(QueryModuleName, 4, "query: consensus state not available")
+)
@@ -5,9 +5,15 @@ import (
 	"reflect"
 
 	"github.com/oasisprotocol/oasis-core/go/common"
+	"github.com/oasisprotocol/oasis-core/go/common/cbor"
 	"github.com/oasisprotocol/oasis-core/go/common/crypto/hash"
 	"github.com/oasisprotocol/oasis-core/go/common/crypto/signature"
+	"github.com/oasisprotocol/oasis-core/go/common/sgx"
 	"github.com/oasisprotocol/oasis-core/go/common/sgx/ias"
+	"github.com/oasisprotocol/oasis-core/go/common/sgx/pcs"
+	consensus "github.com/oasisprotocol/oasis-core/go/consensus/api"
+	epochtime "github.com/oasisprotocol/oasis-core/go/epochtime/api"
+	roothashAPI "github.com/oasisprotocol/oasis-core/go/roothash/api"
 	roothash "github.com/oasisprotocol/oasis-core/go/roothash/api/block"
 	"github.com/oasisprotocol/oasis-core/go/roothash/api/commitment"
 	"github.com/oasisprotocol/oasis-core/go/runtime/transaction"
@@ -57,28 +63,42 @@ type Body struct {
 	Error *Error `json:",omitempty"`
 
 	// Runtime interface.
-	RuntimeInfoRequest                    *RuntimeInfoRequest                    `json:",omitempty"`
-	RuntimeInfoResponse                   *RuntimeInfoResponse                   `json:",omitempty"`
-	RuntimePingRequest                    *Empty                                 `json:",omitempty"`
-	RuntimeShutdownRequest                *Empty                                 `json:",omitempty"`
-	RuntimeCapabilityTEERakInitRequest    *RuntimeCapabilityTEERakInitRequest    `json:",omitempty"`
-	RuntimeCapabilityTEERakInitResponse   *Empty                                 `json:",omitempty"`
-	RuntimeCapabilityTEERakReportRequest  *Empty                                 `json:",omitempty"`
-	RuntimeCapabilityTEERakReportResponse *RuntimeCapabilityTEERakReportResponse `json:",omitempty"`
-	RuntimeCapabilityTEERakAvrRequest     *RuntimeCapabilityTEERakAvrRequest     `json:",omitempty"`
-	RuntimeCapabilityTEERakAvrResponse    *Empty                                 `json:",omitempty"`
-	RuntimeRPCCallRequest                 *RuntimeRPCCallRequest                 `json:",omitempty"`
-	RuntimeRPCCallResponse                *RuntimeRPCCallResponse                `json:",omitempty"`
-	RuntimeLocalRPCCallRequest            *RuntimeLocalRPCCallRequest            `json:",omitempty"`
-	RuntimeLocalRPCCallResponse           *RuntimeLocalRPCCallResponse           `json:",omitempty"`
-	RuntimeCheckTxBatchRequest            *RuntimeCheckTxBatchRequest            `json:",omitempty"`
-	RuntimeCheckTxBatchResponse           *RuntimeCheckTxBatchResponse           `json:",omitempty"`
-	RuntimeExecuteTxBatchRequest          *RuntimeExecuteTxBatchRequest          `json:",omitempty"`
-	RuntimeExecuteTxBatchResponse         *RuntimeExecuteTxBatchResponse         `json:",omitempty"`
-	RuntimeAbortRequest                   *Empty                                 `json:",omitempty"`
-	RuntimeAbortResponse                  *Empty                                 `json:",omitempty"`
-	RuntimeKeyManagerPolicyUpdateRequest  *RuntimeKeyManagerPolicyUpdateRequest  `json:",omitempty"`
-	RuntimeKeyManagerPolicyUpdateResponse *Empty                                 `json:",omitempty"`
+	RuntimeInfoRequest                            *RuntimeInfoRequest                           `json:",omitempty"`
+	RuntimeInfoResponse                           *RuntimeInfoResponse                          `json:",omitempty"`
+	RuntimePingRequest                            *Empty                                        `json:",omitempty"`
+	RuntimeShutdownRequest                        *Empty                                        `json:",omitempty"`
+	RuntimeCapabilityTEERakInitRequest            *RuntimeCapabilityTEERakInitRequest           `json:",omitempty"`
+	RuntimeCapabilityTEERakInitResponse           *Empty                                        `json:",omitempty"`
+	RuntimeCapabilityTEERakReportRequest          *Empty                                        `json:",omitempty"`
+	RuntimeCapabilityTEERakReportResponse         *RuntimeCapabilityTEERakReportResponse        `json:",omitempty"`
+	RuntimeCapabilityTEERakAvrRequest             *RuntimeCapabilityTEERakAvrRequest            `json:",omitempty"`
+	RuntimeCapabilityTEERakAvrResponse            *Empty                                        `json:",omitempty"`
+	RuntimeCapabilityTEERakQuoteRequest           *RuntimeCapabilityTEERakQuoteRequest          `json:",omitempty"`
+	RuntimeCapabilityTEERakQuoteResponse          *Empty                                        `json:",omitempty"`
+	RuntimeCapabilityTEEQuotePolicyUpdateRequest  *RuntimeCapabilityTEEQuotePolicyUpdateRequest `json:",omitempty"`
+	RuntimeCapabilityTEEQuotePolicyUpdateResponse *Empty                                        `json:",omitempty"`
+	RuntimeAttestationStatusRequest               *Empty                                        `json:",omitempty"`
+	RuntimeAttestationStatusResponse              *RuntimeAttestationStatusResponse             `json:",omitempty"`
+	RuntimeRPCCallRequest                         *RuntimeRPCCallRequest                        `json:",omitempty"`
+	RuntimeRPCCallResponse                        *RuntimeRPCCallResponse                       `json:",omitempty"`
+	RuntimeLocalRPCCallRequest                    *RuntimeLocalRPCCallRequest                   `json:",omitempty"`
+	RuntimeLocalRPCCallResponse                   *RuntimeLocalRPCCallResponse                  `json:",omitempty"`
+	RuntimeCheckTxBatchRequest                    *RuntimeCheckTxBatchRequest                   `json:",omitempty"`
+	RuntimeCheckTxBatchResponse                   *RuntimeCheckTxBatchResponse                  `json:",omitempty"`
+	RuntimeExecuteTxBatchRequest                  *RuntimeExecuteTxBatchRequest                 `json:",omitempty"`
+	RuntimeExecuteTxBatchResponse                 *RuntimeExecuteTxBatchResponse                `json:",omitempty"`
+	RuntimeAbortRequest                           *Empty                                        `json:",omitempty"`
+	RuntimeAbortResponse                          *Empty                                        `json:",omitempty"`
+	RuntimeKeyManagerPolicyUpdateRequest          *RuntimeKeyManagerPolicyUpdateRequest         `json:",omitempty"`
+	RuntimeKeyManagerPolicyUpdateResponse         *Empty                                        `json:",omitempty"`
+	RuntimeMetricsRequest                         *Empty                                        `json:",omitempty"`
+	RuntimeMetricsResponse                        *RuntimeMetricsResponse                       `json:",omitempty"`
+	RuntimeQueryRequest                           *RuntimeQueryRequest                          `json:",omitempty"`
+	RuntimeQueryResponse                          *RuntimeQueryResponse                         `json:",omitempty"`
+	RuntimeIntrospectionRequest                   *Empty                                        `json:",omitempty"`
+	RuntimeIntrospectionResponse                  *RuntimeIntrospectionResponse                 `json:",omitempty"`
+	RuntimeConsensusEpochUpdateRequest            *RuntimeConsensusEpochUpdateRequest           `json:",omitempty"`
+	RuntimeConsensusEpochUpdateResponse           *Empty                                        `json:",omitempty"`
 
 	// Host interface.
 	HostRPCCallRequest          *HostRPCCallRequest          `json:",omitempty"`
@@ -89,6 +109,20 @@ type Body struct {
 	HostLocalStorageGetResponse *HostLocalStorageGetResponse `json:",omitempty"`
 	HostLocalStorageSetRequest  *HostLocalStorageSetRequest  `json:",omitempty"`
 	HostLocalStorageSetResponse *Empty                       `json:",omitempty"`
+
+	HostLocalStorageGetBatchRequest  *HostLocalStorageGetBatchRequest  `json:",omitempty"`
+	HostLocalStorageGetBatchResponse *HostLocalStorageGetBatchResponse `json:",omitempty"`
+	HostLocalStorageSetBatchRequest  *HostLocalStorageSetBatchRequest  `json:",omitempty"`
+	HostLocalStorageSetBatchResponse *Empty                            `json:",omitempty"`
+
+	HostLogRequest  *HostLogRequest `json:",omitempty"`
+	HostLogResponse *Empty          `json:",omitempty"`
+
+	HostAttestationRefreshRequest  *Empty `json:",omitempty"`
+	HostAttestationRefreshResponse *Empty `json:",omitempty"`
+
+	HostFetchConsensusBlockRequest  *HostFetchConsensusBlockRequest  `json:",omitempty"`
+	HostFetchConsensusBlockResponse *HostFetchConsensusBlockResponse `json:",omitempty"`
 }
 
 // Type returns the message type by determining the name of the first non-nil member.
@@ -117,6 +151,11 @@ type Error struct {
 type RuntimeInfoRequest struct {
 	// RuntimeID is the assigned runtime ID of the loaded runtime.
 	RuntimeID common.Namespace `json:"runtime_id"`
+
+	// HostProtocolVersion is the runtime protocol version supported by the
+	// host. The runtime will not use any messages introduced in later minor
+	// versions.
+	HostProtocolVersion uint64 `json:"host_protocol_version,omitempty"`
 }
 
 // RuntimeInfoResponse is a worker info response message body.
@@ -146,6 +185,36 @@ type RuntimeCapabilityTEERakAvrRequest struct {
 	AVR ias.AVRBundle `json:"avr"`
 }
 
+// RuntimeCapabilityTEERakQuoteRequest is a worker RFC 0009 CapabilityTEE RAK DCAP quote setup request message body.
+type RuntimeCapabilityTEERakQuoteRequest struct {
+	Quote pcs.QuoteBundle `json:"quote"`
+}
+
+// RuntimeCapabilityTEEQuotePolicyUpdateRequest is a worker RFC 0009 CapabilityTEE quote policy
+// update request message body.
+type RuntimeCapabilityTEEQuotePolicyUpdateRequest struct {
+	Policy sgx.QuotePolicy `json:"policy"`
+}
+
+// AttestationStatus is the attested status of the runtime identity.
+type AttestationStatus struct {
+	// Attested is true iff the runtime attestation key has an attestation which has not expired.
+	Attested bool `json:"attested"`
+	// AttestedAt is the time of the current attestation (POSIX timestamp).
+	AttestedAt *int64 `json:"attested_at"`
+	// ExpiresAt is the time at which the current attestation expires (POSIX timestamp).
+	ExpiresAt *int64 `json:"expires_at"`
+	// RefreshAt is the time from which the runtime requests a refresh (POSIX timestamp).
+	RefreshAt *int64 `json:"refresh_at"`
+	// LastRefreshError is the error of the last refresh request, if it failed.
+	LastRefreshError *string `json:"last_refresh_error"`
+}
+
+// RuntimeAttestationStatusResponse is a runtime attestation status response message body.
+type RuntimeAttestationStatusResponse struct {
+	Status AttestationStatus `json:"status"`
+}
+
 // RuntimeRPCCallRequest is a worker RPC call request message body.
 type RuntimeRPCCallRequest struct {
 	// Request.
@@ -182,6 +251,8 @@ type RuntimeCheckTxBatchRequest struct {
 type RuntimeCheckTxBatchResponse struct {
 	// Batch of runtime check results.
 	Results transaction.RawBatch `json:"results"`
+	// Meta is the metadata of each checked transaction, if any.
+	Meta []*transaction.CheckTxMetadata `json:"meta,omitempty"`
 }
 
 // ComputedBatch is a computed batch.
@@ -195,6 +266,20 @@ type ComputedBatch struct {
 	// If this runtime uses a TEE, then this is the signature of Header with
 	// node's RAK for this runtime.
 	RakSig signature.RawSignature `json:"rak_sig"`
+	// ScheduleStats are statistics about how the batch was scheduled.
+	ScheduleStats *ScheduleStats `json:"schedule_stats,omitempty"`
+}
+
+// ScheduleStats are statistics about how a transaction batch was scheduled
+// for execution by the runtime.
+type ScheduleStats struct {
+	// Partitions is the number of partitions the batch was split into.
+	Partitions uint64 `json:"partitions"`
+	// MaxPartitionSize is the number of calls in the largest partition.
+	MaxPartitionSize uint64 `json:"max_partition_size"`
+	// Reordered is the number of calls executed at a different position than
+	// their position in the batch.
+	Reordered uint64 `json:"reordered"`
 }
 
 // String returns a string representation of a computed batch.
@@ -211,6 +296,15 @@ type RuntimeExecuteTxBatchRequest struct {
 	Inputs transaction.RawBatch `json:"inputs"`
 	// Block on which the batch computation should be based.
 	Block roothash.Block `json:"block"`
+	// Beacon is the consensus beacon at the height of the block, from which
+	// the runtime derives randomness for the batch.
+	Beacon []byte `json:"beacon,omitempty"`
+	// MessageResults are the results of executing the messages emitted by
+	// the runtime in the round of the block.
+	MessageResults []*roothashAPI.MessageEvent `json:"message_results,omitempty"`
+	// IncomingMessages are the incoming messages queued for the runtime, in
+	// queue order.
+	IncomingMessages []*roothash.IncomingMessage `json:"in_msgs,omitempty"`
 }
 
 // RuntimeExecuteTxBatchResponse is a worker execute tx batch response message body.
@@ -224,6 +318,97 @@ type RuntimeKeyManagerPolicyUpdateRequest struct {
 	SignedPolicyRaw []byte `json:"signed_policy_raw"`
 }
 
+// RuntimeConsensusEpochUpdateRequest is a runtime consensus epoch update
+// notification message body.
+type RuntimeConsensusEpochUpdateRequest struct {
+	// Epoch is the current consensus epoch.
+	Epoch epochtime.EpochTime `json:"epoch"`
+	// Height is the consensus block height at the start of the epoch.
+	Height int64 `json:"height"`
+	// Beacon is the consensus beacon at the start of the epoch.
+	Beacon []byte `json:"beacon,omitempty"`
+}
+
+// HistogramBucket is a bucket of a runtime histogram metric.
+type HistogramBucket struct {
+	// UpperBound is the inclusive upper bound of the bucket.
+	UpperBound float64 `json:"upper_bound"`
+	// Count is the number of observations less than or equal to the upper bound.
+	Count uint64 `json:"count"`
+}
+
+// CounterValue is the value of a runtime counter metric.
+type CounterValue struct {
+	Value uint64 `json:"value"`
+}
+
+// GaugeValue is the value of a runtime gauge metric.
+type GaugeValue struct {
+	Value int64 `json:"value"`
+}
+
+// HistogramValue is the value of a runtime histogram metric.
+type HistogramValue struct {
+	Buckets []HistogramBucket `json:"buckets"`
+	Sum     float64           `json:"sum"`
+	Count   uint64            `json:"count"`
+}
+
+// MetricValue is the value of a runtime metric, exactly one of the fields is set.
+type MetricValue struct {
+	Counter   *CounterValue   `json:",omitempty"`
+	Gauge     *GaugeValue     `json:",omitempty"`
+	Histogram *HistogramValue `json:",omitempty"`
+}
+
+// MetricFamily is a named runtime metric.
+type MetricFamily struct {
+	Name  string      `json:"name"`
+	Help  string      `json:"help"`
+	Value MetricValue `json:"value"`
+}
+
+// RuntimeMetricsResponse is a runtime metrics response message body.
+type RuntimeMetricsResponse struct {
+	Metrics []MetricFamily `json:"metrics"`
+}
+
+// RuntimeQueryRequest is a runtime query request message body.
+type RuntimeQueryRequest struct {
+	// Method is the name of the queried method.
+	Method string `json:"method"`
+	// Args are the method arguments.
+	Args cbor.RawMessage `json:"args"`
+	// Block is the block whose state should be queried.
+	Block roothash.Block `json:"block"`
+}
+
+// RuntimeQueryResponse is a runtime query response message body.
+type RuntimeQueryResponse struct {
+	// Data is the query result.
+	Data cbor.RawMessage `json:"data"`
+}
+
+// MethodInfo is a description of a runtime method.
+type MethodInfo struct {
+	// Name is the method name.
+	Name string `json:"name"`
+	// Args is the schema of the method arguments.
+	Args cbor.RawMessage `json:"args"`
+	// Output is the schema of the method output.
+	Output cbor.RawMessage `json:"output"`
+}
+
+// RuntimeIntrospectionResponse is a runtime introspection response message body.
+type RuntimeIntrospectionResponse struct {
+	// Methods are the transaction methods, ordered by name.
+	Methods []MethodInfo `json:"methods"`
+	// Queries are the query methods, ordered by name.
+	Queries []MethodInfo `json:"queries"`
+	// Features are the names of the optional features used by the runtime.
+	Features []string `json:"features"`
+}
+
 // HostRPCCallRequest is a host RPC call request message body.
 type HostRPCCallRequest struct {
 	Endpoint string `json:"endpoint"`
@@ -235,16 +420,63 @@ type HostRPCCallResponse struct {
 	Response []byte `json:"response"`
 }
 
+// HostStorageEndpoint is the host storage endpoint.
+type HostStorageEndpoint uint8
+
+const (
+	// HostStorageEndpointRuntime is the runtime state storage endpoint.
+	HostStorageEndpointRuntime HostStorageEndpoint = 0
+	// HostStorageEndpointConsensus is the consensus layer state storage endpoint.
+	HostStorageEndpointConsensus HostStorageEndpoint = 1
+)
+
 // HostStorageSyncRequest is a host storage read syncer request message body.
 type HostStorageSyncRequest struct {
-	SyncGet         *storage.GetRequest         `json:",omitempty"`
-	SyncGetPrefixes *storage.GetPrefixesRequest `json:",omitempty"`
-	SyncIterate     *storage.IterateRequest     `json:",omitempty"`
+	SyncGet         *storage.GetRequest          `json:",omitempty"`
+	SyncGetPrefixes *storage.GetPrefixesRequest  `json:",omitempty"`
+	SyncIterate     *storage.IterateRequest      `json:",omitempty"`
+	SyncNextChunk   *HostStorageNextChunkRequest `json:",omitempty"`
+	SyncGetMulti    *HostStorageGetMultiRequest  `json:",omitempty"`
+
+	// Endpoint is the storage endpoint the request is for.
+	Endpoint HostStorageEndpoint `json:"endpoint,omitempty"`
+}
+
+// HostStorageGetMultiRequest is a request for proofs of multiple keys in the
+// same tree.
+type HostStorageGetMultiRequest struct {
+	Tree storage.TreeID `json:"tree"`
+	Keys [][]byte       `json:"keys"`
+}
+
+// HostStorageNextChunkRequest is a request for the next chunk of a partial
+// proof response.
+type HostStorageNextChunkRequest struct {
+	// Request is the identifier of the request whose response is being
+	// continued.
+	Request uint64 `json:"request"`
+	// Chunk is the index of the requested chunk, starting at 1 for the chunk
+	// following the initial response.
+	Chunk uint32 `json:"chunk"`
 }
 
 // HostStorageSyncResponse is a host storage read syncer response body.
 type HostStorageSyncResponse struct {
-	ProofResponse *storage.ProofResponse `json:",omitempty"`
+	ProofResponse        *storage.ProofResponse           `json:",omitempty"`
+	PartialProofResponse *HostStoragePartialProofResponse `json:",omitempty"`
+	// ProofResponses are the proof responses for each key of a SyncGetMulti
+	// request, in order.
+	ProofResponses []*storage.ProofResponse `json:",omitempty"`
+}
+
+// HostStoragePartialProofResponse is a chunk of a proof response that is too
+// large to fit into a single message.
+//
+// The entries of all chunks, in order, form the entries of the full proof.
+type HostStoragePartialProofResponse struct {
+	Proof storage.Proof `json:"proof"`
+	// More is true if more chunks follow this one.
+	More bool `json:"more"`
 }
 
 // HostLocalStorageGetRequest is a host local storage get request message body.
@@ -262,3 +494,54 @@ type HostLocalStorageSetRequest struct {
 	Key   []byte `json:"key"`
 	Value []byte `json:"value"`
 }
+
+// HostLocalStorageGetBatchRequest is a host local storage batch get request message body.
+type HostLocalStorageGetBatchRequest struct {
+	Keys [][]byte `json:"keys"`
+}
+
+// HostLocalStorageGetBatchResponse is a host local storage batch get response message body.
+type HostLocalStorageGetBatchResponse struct {
+	Values [][]byte `json:"values"`
+}
+
+// LocalStorageItem is a key/value pair stored in the host local storage.
+type LocalStorageItem struct {
+	Key   []byte `json:"key"`
+	Value []byte `json:"value"`
+}
+
+// HostLocalStorageSetBatchRequest is a host local storage batch set request message body.
+//
+// Either all or none of the items are stored.
+type HostLocalStorageSetBatchRequest struct {
+	Items []LocalStorageItem `json:"items"`
+}
+
+// LogRecord is a structured log record emitted by the runtime.
+type LogRecord struct {
+	// Level is one of trace, debug, info, warn or error.
+	Level  string            `json:"level"`
+	Module string            `json:"module"`
+	Msg    string            `json:"msg"`
+	Fields map[string]string `json:"fields,omitempty"`
+}
+
+// HostFetchConsensusBlockRequest is a request to the host to fetch a consensus
+// light block.
+type HostFetchConsensusBlockRequest struct {
+	// Height is the height of the light block, or consensus.HeightLatest for
+	// the latest light block.
+	Height int64 `json:"height"`
+}
+
+// HostFetchConsensusBlockResponse is a response from the host fetching a
+// consensus light block.
+type HostFetchConsensusBlockResponse struct {
+	Block consensus.LightBlock `json:"block"`
+}
+
+// HostLogRequest is a host log request message body.
+type HostLogRequest struct {
+	Records []LogRecord `json:"records"`
+}
@@ -72,6 +72,10 @@ type RuntimeExtra struct {
 	// UnsafeDebugGenerateSigstruct allows the generation of a dummy SIGSTRUCT
 	// if an actual signature is unavailable.
 	UnsafeDebugGenerateSigstruct bool
+
+	// QuotePolicy is the policy for accepting remote attestations enforced by
+	// the runtime. If not specified, the runtime defaults are used.
+	QuotePolicy *sgx.QuotePolicy
 }
 
 type teeState struct {
@@ -88,6 +92,8 @@ type sgxProvisioner struct {
 
 	cfg Config
 
+	quotePolicies map[common.Namespace]*sgx.QuotePolicy
+
 	sandbox host.Provisioner
 	ias     ias.Endpoint
 	aesm    *aesm.Client
@@ -231,6 +237,24 @@ func (s *sgxProvisioner) initCapabilityTEE(ctx context.Context, rt host.Runtime,
 	ts.spid = spidInfo.SPID
 	ts.quoteType = &spidInfo.QuoteSignatureType
 
+	// Configure the quote policy before attesting, so that it also applies to
+	// the runtime's own attestation.
+	s.Lock()
+	quotePolicy := s.quotePolicies[ts.runtimeID]
+	s.Unlock()
+	if quotePolicy != nil {
+		if _, err = conn.Call(
+			ctx,
+			&protocol.Body{
+				RuntimeCapabilityTEEQuotePolicyUpdateRequest: &protocol.RuntimeCapabilityTEEQuotePolicyUpdateRequest{
+					Policy: *quotePolicy,
+				},
+			},
+		); err != nil {
+			return nil, fmt.Errorf("error while configuring quote policy: %w", err)
+		}
+	}
+
 	if _, err = conn.Call(
 		ctx,
 		&protocol.Body{
@@ -332,25 +356,34 @@ func (s *sgxProvisioner) attestationWorker(ts *teeState, p process.Process, conn
 		case <-t.C:
 			// Update CapabilityTEE.
 			logger.Info("regenerating CapabilityTEE")
+		case <-conn.AttestationRefreshRequests():
+			// Update CapabilityTEE as requested by the runtime.
+			logger.Info("regenerating CapabilityTEE at the request of the runtime")
+		}
 
-			capabilityTEE, err := s.updateCapabilityTEE(context.Background(), ts, conn)
-			if err != nil {
-				logger.Error("failed to regenerate CapabilityTEE",
-					"err", err,
-				)
-				continue
-			}
-
-			// Emit event about the updated CapabilityTEE.
-			ts.eventEmitter.EmitEvent(&host.Event{Updated: &host.UpdatedEvent{
-				CapabilityTEE: capabilityTEE,
-			}})
+		capabilityTEE, err := s.updateCapabilityTEE(context.Background(), ts, conn)
+		if err != nil {
+			logger.Error("failed to regenerate CapabilityTEE",
+				"err", err,
+			)
+			continue
 		}
+
+		// Emit event about the updated CapabilityTEE.
+		ts.eventEmitter.EmitEvent(&host.Event{Updated: &host.UpdatedEvent{
+			CapabilityTEE: capabilityTEE,
+		}})
 	}
 }
 
 // Implements host.Provisioner.
 func (s *sgxProvisioner) NewRuntime(ctx context.Context, cfg host.Config) (host.Runtime, error) {
+	if rtExtra, ok := cfg.Extra.(*RuntimeExtra); ok {
+		s.Lock()
+		s.quotePolicies[cfg.RuntimeID] = rtExtra.QuotePolicy
+		s.Unlock()
+	}
+
 	return s.sandbox.NewRuntime(ctx, cfg)
 }
 
@@ -362,10 +395,11 @@ func New(cfg Config) (host.Provisioner, error) {
 	}
 
 	s := &sgxProvisioner{
-		cfg:    cfg,
-		ias:    cfg.IAS,
-		aesm:   aesm.NewClient(aesmdSocketPath),
-		logger: logging.GetLogger("runtime/host/sgx"),
+		cfg:           cfg,
+		quotePolicies: make(map[common.Namespace]*sgx.QuotePolicy),
+		ias:           cfg.IAS,
+		aesm:          aesm.NewClient(aesmdSocketPath),
+		logger:        logging.GetLogger("runtime/host/sgx"),
 	}
 	p, err := sandbox.New(sandbox.Config{
 		GetSandboxConfig:  s.getSandboxConfig,
@@ -31,6 +31,12 @@ type LocalStorage interface {
 	// Set sets a key to a specific value.
 	Set(key, value []byte) error
 
+	// GetBatch retrieves previously stored values under the given keys.
+	GetBatch(keys [][]byte) ([][]byte, error)
+
+	// SetBatch atomically sets multiple keys to specific values.
+	SetBatch(keys, values [][]byte) error
+
 	// Stop stops local storage.
 	Stop()
 }
@@ -92,6 +98,70 @@ func (s *localStorage) Set(key, value []byte) error {
 	return nil
 }
 
+func (s *localStorage) GetBatch(keys [][]byte) ([][]byte, error) {
+	for _, key := range keys {
+		if len(key) == 0 {
+			return nil, errInvalidKey
+		}
+	}
+
+	values := make([][]byte, 0, len(keys))
+	if err := s.db.View(func(tx *badger.Txn) error {
+		for _, key := range keys {
+			var value []byte
+			item, txErr := tx.Get(key)
+			switch txErr {
+			case nil:
+				if txErr = item.Value(func(val []byte) error {
+					value = append([]byte{}, val...)
+					return nil
+				}); txErr != nil {
+					return txErr
+				}
+			case badger.ErrKeyNotFound:
+			default:
+				return txErr
+			}
+			values = append(values, cbor.FixSliceForSerde(value))
+		}
+		return nil
+	}); err != nil {
+		s.logger.Error("failed batch get",
+			"err", err,
+		)
+		return nil, err
+	}
+
+	return values, nil
+}
+
+func (s *localStorage) SetBatch(keys, values [][]byte) error {
+	if len(keys) != len(values) {
+		return fmt.Errorf("localstorage: mismatched batch (keys: %d values: %d)", len(keys), len(values))
+	}
+	for _, key := range keys {
+		if len(key) == 0 {
+			return errInvalidKey
+		}
+	}
+
+	if err := s.db.Update(func(tx *badger.Txn) error {
+		for i, key := range keys {
+			if txErr := tx.Set(key, values[i]); txErr != nil {
+				return txErr
+			}
+		}
+		return nil
+	}); err != nil {
+		s.logger.Error("failed batch put",
+			"err", err,
+		)
+		return err
+	}
+
+	return nil
+}
+
 func (s *localStorage) Stop() {
 	s.gc.Close()
 	if err := s.db.Close(); err != nil {
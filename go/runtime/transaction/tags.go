@@ -16,3 +16,22 @@ type Tag struct {
 
 // Tags is a set of tags.
 type Tags []Tag
+
+// Event is a key/value pair of arbitrary byte blobs with runtime-dependent
+// semantics, together with a list of topics under which it is indexed.
+type Event struct {
+	// Key is the event key.
+	Key []byte
+	// Value is the event value.
+	Value []byte
+	// Topics are the topics the event is indexed under.
+	Topics [][]byte
+	// TxHash is the hash of the transaction that emitted the event.
+	TxHash hash.Hash
+	// Index is the index of the event among the events emitted by the
+	// transaction.
+	Index uint32
+}
+
+// Events is a list of events.
+type Events []Event
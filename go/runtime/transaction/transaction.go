@@ -83,6 +83,13 @@ var (
 	// This is kept separate so that clients can query only tags they are
 	// interested in instead of needing to go through all transactions.
 	tagKeyFmt = keyformat.New('E', []byte{}, &hash.Hash{})
+	// eventKeyFmt is the key format used for emitted events.
+	eventKeyFmt = keyformat.New('V', &hash.Hash{}, uint32(0))
+	// eventTopicKeyFmt is the key format used for indexing emitted events by
+	// the hash of their topics, with the event key as value.
+	//
+	// Topics are hashed so that a topic is never a prefix of another topic.
+	eventTopicKeyFmt = keyformat.New('I', &hash.Hash{}, &hash.Hash{}, uint32(0))
 )
 
 // inputArtifacts are the input transaction artifacts.
@@ -111,6 +118,20 @@ type outputArtifacts struct {
 	Output []byte
 }
 
+// eventArtifacts are the artifacts of an emitted event.
+//
+// These are the artifacts that are stored CBOR-serialized in the Merkle tree.
+type eventArtifacts struct {
+	_ struct{} `cbor:",toarray"` // nolint
+
+	// Key is the event key.
+	Key []byte
+	// Value is the event value.
+	Value []byte
+	// Topics are the topics the event is indexed under.
+	Topics [][]byte
+}
+
 // Transaction is an executed (or executing) transaction.
 //
 // This is the transaction representation used for convenience as a collection
@@ -201,6 +222,28 @@ func (t *Tree) AddTransaction(ctx context.Context, tx Transaction, tags Tags) er
 	return nil
 }
 
+// AddEvents adds events emitted by the given transaction.
+//
+// Each event is stored under the transaction hash and its index, and indexed
+// under each of its topics.
+func (t *Tree) AddEvents(ctx context.Context, txHash hash.Hash, events Events) error {
+	for i, ev := range events {
+		index := uint32(i)
+		ea := eventArtifacts{Key: ev.Key, Value: ev.Value, Topics: ev.Topics}
+		if err := t.tree.Insert(ctx, eventKeyFmt.Encode(&txHash, index), cbor.Marshal(ea)); err != nil {
+			return fmt.Errorf("transaction: event insert failed: %w", err)
+		}
+		for _, topic := range ev.Topics {
+			topicHash := hash.NewFromBytes(topic)
+			if err := t.tree.Insert(ctx, eventTopicKeyFmt.Encode(&topicHash, &txHash, index), ev.Key); err != nil {
+				return fmt.Errorf("transaction: event topic insert failed: %w", err)
+			}
+		}
+	}
+
+	return nil
+}
+
 // inBatchOrder is a helper for sorting transactions in batch order.
 type inBatchOrder struct {
 	order []uint32
@@ -428,6 +471,77 @@ func (t *Tree) GetTags(ctx context.Context) (Tags, error) {
 	return tags, nil
 }
 
+// GetEvents retrieves all events emitted in this tree, ordered by the
+// emitting transaction hash and then in emission order.
+func (t *Tree) GetEvents(ctx context.Context) (Events, error) {
+	it := t.tree.NewIterator(ctx, mkvs.IteratorPrefetch(prefetchArtifactCount))
+	defer it.Close()
+
+	var events Events
+	for it.Seek(eventKeyFmt.Encode()); it.Valid(); it.Next() {
+		var decHash hash.Hash
+		var decIndex uint32
+		if !eventKeyFmt.Decode(it.Key(), &decHash, &decIndex) {
+			break
+		}
+
+		var ea eventArtifacts
+		if err := cbor.Unmarshal(it.Value(), &ea); err != nil {
+			return nil, fmt.Errorf("transaction: malformed event artifacts: %w", err)
+		}
+		events = append(events, Event{
+			Key:    ea.Key,
+			Value:  ea.Value,
+			Topics: ea.Topics,
+			TxHash: decHash,
+			Index:  decIndex,
+		})
+	}
+	if it.Err() != nil {
+		return nil, fmt.Errorf("transaction: get events failed: %w", it.Err())
+	}
+
+	return events, nil
+}
+
+// GetEventsByTopic retrieves all events emitted in this tree with the given
+// topic, ordered by the emitting transaction hash and then in emission order.
+func (t *Tree) GetEventsByTopic(ctx context.Context, topic []byte) (Events, error) {
+	it := t.tree.NewIterator(ctx, mkvs.IteratorPrefetch(prefetchArtifactCount))
+	defer it.Close()
+
+	topicHash := hash.NewFromBytes(topic)
+	var events Events
+	for it.Seek(eventTopicKeyFmt.Encode(&topicHash)); it.Valid(); it.Next() {
+		var decTopicHash, decHash hash.Hash
+		var decIndex uint32
+		if !eventTopicKeyFmt.Decode(it.Key(), &decTopicHash, &decHash, &decIndex) || !decTopicHash.Equal(&topicHash) {
+			break
+		}
+
+		raw, err := t.tree.Get(ctx, eventKeyFmt.Encode(&decHash, decIndex))
+		if err != nil {
+			return nil, fmt.Errorf("transaction: get event failed: %w", err)
+		}
+		var ea eventArtifacts
+		if err = cbor.Unmarshal(raw, &ea); err != nil {
+			return nil, fmt.Errorf("transaction: malformed event artifacts: %w", err)
+		}
+		events = append(events, Event{
+			Key:    ea.Key,
+			Value:  ea.Value,
+			Topics: ea.Topics,
+			TxHash: decHash,
+			Index:  decIndex,
+		})
+	}
+	if it.Err() != nil {
+		return nil, fmt.Errorf("transaction: get events by topic failed: %w", it.Err())
+	}
+
+	return events, nil
+}
+
 // Commit commits the updates to the underlying Merkle tree and returns the
 // write log and root hash.
 func (t *Tree) Commit(ctx context.Context) (writelog.WriteLog, hash.Hash, error) {
@@ -180,3 +180,49 @@ func TestTransactionInvalidBatchOrder(t *testing.T) {
 	_, err = tree.GetInputBatch(ctx, 0, 0)
 	require.Error(t, err, "GetInputBatch should fail with inconsistent order")
 }
+
+func TestTransactionEvents(t *testing.T) {
+	ctx := context.Background()
+	store := mkvs.New(nil, nil)
+
+	var emptyRoot node.Root
+	emptyRoot.Empty()
+
+	tree := NewTree(store, emptyRoot)
+
+	txA := hash.NewFromBytes([]byte("tx A"))
+	txB := hash.NewFromBytes([]byte("tx B"))
+	err := tree.AddEvents(ctx, txA, Events{
+		{Key: []byte("transfer"), Value: []byte("1"), Topics: [][]byte{[]byte("alice"), []byte("bob")}},
+		{Key: []byte("burn"), Value: []byte("2"), Topics: [][]byte{[]byte("alice")}},
+	})
+	require.NoError(t, err, "AddEvents")
+	err = tree.AddEvents(ctx, txB, Events{
+		{Key: []byte("transfer"), Value: []byte("3"), Topics: [][]byte{[]byte("bob"), []byte("carol")}},
+	})
+	require.NoError(t, err, "AddEvents")
+
+	events, err := tree.GetEvents(ctx)
+	require.NoError(t, err, "GetEvents")
+	require.Len(t, events, 3, "all emitted events should be there")
+
+	for _, tc := range []struct {
+		topic  string
+		values []string
+	}{
+		{"alice", []string{"1", "2"}},
+		{"bob", []string{"1", "3"}},
+		{"carol", []string{"3"}},
+		{"dave", nil},
+	} {
+		events, err = tree.GetEventsByTopic(ctx, []byte(tc.topic))
+		require.NoError(t, err, "GetEventsByTopic")
+
+		var values []string
+		for _, ev := range events {
+			require.Contains(t, ev.Topics, []byte(tc.topic), "event should have the topic")
+			values = append(values, string(ev.Value))
+		}
+		require.ElementsMatch(t, tc.values, values, "events with topic %s should be returned", tc.topic)
+	}
+}
@@ -10,6 +10,9 @@ type TxnCall struct {
 	Method string `json:"method"`
 	// Args are the method arguments.
 	Args interface{} `json:"args"`
+	// GasLimit is the maximum amount of gas the call may use, if gas is
+	// metered by the runtime.
+	GasLimit *uint64 `json:"gas_limit,omitempty"`
 }
 
 // TxnOutput is a transaction call output.
@@ -18,10 +21,37 @@ type TxnOutput struct {
 	Success cbor.RawMessage
 	// Error is a string describing the error message.
 	Error *string
+	// OutOfGas is set if the call ran out of gas.
+	OutOfGas *TxnOutOfGas
+}
+
+// TxnOutOfGas is the output of a transaction call which ran out of gas.
+type TxnOutOfGas struct {
+	// Limit is the gas limit of the call.
+	Limit uint64 `json:"limit"`
 }
 
 // TxnCheckResult is the result of a successful CheckTx call.
 type TxnCheckResult struct {
 	// PredictedReadWriteSet is the predicted read/write set.
 	PredictedReadWriteSet ReadWriteSet `json:"predicted_rw_set"`
+	// Meta is the metadata used to prioritize the transaction.
+	Meta *CheckTxMetadata `json:"meta,omitempty"`
+}
+
+// CheckTxMetadata is the transaction metadata returned by a successful
+// CheckTx call, used to order and limit transactions in the queue.
+type CheckTxMetadata struct {
+	// Priority is the priority of the transaction, higher priority
+	// transactions are scheduled first.
+	Priority uint64 `json:"priority,omitempty"`
+	// Weights are runtime-specific weights of the transaction (e.g., gas or
+	// size), which can be used to limit the total weight of a batch.
+	Weights map[string]uint64 `json:"weights,omitempty"`
+	// Sender is the sender of the transaction (e.g., an account address).
+	Sender []byte `json:"sender,omitempty"`
+	// SenderSeq is the sequence number (nonce) of the transaction for its
+	// sender. Transactions of the same sender are scheduled in sequence
+	// order.
+	SenderSeq uint64 `json:"sender_seq,omitempty"`
 }
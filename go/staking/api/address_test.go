@@ -1,10 +1,12 @@
 package api
 
 import (
+	"encoding/hex"
 	"testing"
 
 	"github.com/stretchr/testify/require"
 
+	"github.com/oasisprotocol/oasis-core/go/common"
 	"github.com/oasisprotocol/oasis-core/go/common/crypto/signature"
 )
 
@@ -35,3 +37,15 @@ func TestReserved(t *testing.T) {
 	require.True(pk2.IsBlacklisted(), "public key for test address 2 should be blacklisted")
 	require.False(pk2.IsValid(), "public key for test address 2 should be invalid")
 }
+
+func TestRuntimeAddress(t *testing.T) {
+	require := require.New(t)
+
+	// NOTE: This address MUST be synced with runtime/src/common/staking.rs.
+	var id common.Namespace
+	require.NoError(id.UnmarshalHex("8000000000000000000000000000000000000000000000000000000000000000"))
+
+	addr := NewRuntimeAddress(id)
+	require.EqualValues("001a1b0eef616533bec4de188b53cc89cd1381cd33", hex.EncodeToString(addr[:]))
+	require.True(addr.IsValid(), "runtime address should be valid")
+}
@@ -0,0 +1,87 @@
+package mkvs
+
+import (
+	"context"
+	"encoding/json"
+	"io/ioutil"
+	"path/filepath"
+	"testing"
+
+	"github.com/stretchr/testify/require"
+
+	"github.com/oasisprotocol/oasis-core/go/common/cbor"
+	"github.com/oasisprotocol/oasis-core/go/common/crypto/hash"
+	"github.com/oasisprotocol/oasis-core/go/storage/mkvs/node"
+	"github.com/oasisprotocol/oasis-core/go/storage/mkvs/syncer"
+	"github.com/oasisprotocol/oasis-core/go/storage/mkvs/writelog"
+)
+
+// encodingTestVectors are the node, write log and proof encoding test
+// vectors shared with the Rust implementation, which generates them.
+type encodingTestVectors struct {
+	Nodes []struct {
+		Encoded []byte `json:"encoded"`
+		Hash    []byte `json:"hash"`
+	} `json:"nodes"`
+	WriteLogs []struct {
+		Encoded []byte `json:"encoded"`
+		Entries []struct {
+			Key   []byte `json:"key"`
+			Value []byte `json:"value"`
+		} `json:"entries"`
+	} `json:"write_logs"`
+	Proofs []struct {
+		Root    []byte `json:"root"`
+		Key     []byte `json:"key"`
+		Value   []byte `json:"value"`
+		Encoded []byte `json:"encoded"`
+	} `json:"proofs"`
+}
+
+func TestEncodingTestVectors(t *testing.T) {
+	data, err := ioutil.ReadFile(filepath.Join("testdata", "encoding.json"))
+	require.NoError(t, err, "failed to read the fixture file")
+
+	var vectors encodingTestVectors
+	err = json.Unmarshal(data, &vectors)
+	require.NoError(t, err, "failed to unmarshal fixture")
+
+	for _, v := range vectors.Nodes {
+		n, err := node.UnmarshalBinary(v.Encoded)
+		require.NoError(t, err, "UnmarshalBinary")
+		n.UpdateHash()
+		h := n.GetHash()
+		require.EqualValues(t, v.Hash, h[:], "node hash should match")
+
+		encoded, err := n.MarshalBinary()
+		require.NoError(t, err, "MarshalBinary")
+		require.EqualValues(t, v.Encoded, encoded, "node encoding should round-trip")
+	}
+
+	for _, v := range vectors.WriteLogs {
+		var wl writelog.WriteLog
+		err = cbor.Unmarshal(v.Encoded, &wl)
+		require.NoError(t, err, "failed to decode write log")
+
+		expected := make(writelog.WriteLog, 0, len(v.Entries))
+		for _, entry := range v.Entries {
+			expected = append(expected, writelog.LogEntry{Key: entry.Key, Value: entry.Value})
+		}
+		require.True(t, expected.Equal(wl), "write log should match")
+		require.EqualValues(t, v.Encoded, cbor.Marshal(wl), "write log encoding should round-trip")
+	}
+
+	var pv syncer.ProofVerifier
+	for _, v := range vectors.Proofs {
+		var proof syncer.Proof
+		err = cbor.Unmarshal(v.Encoded, &proof)
+		require.NoError(t, err, "failed to decode proof")
+
+		var root hash.Hash
+		err = root.UnmarshalBinary(v.Root)
+		require.NoError(t, err, "UnmarshalBinary")
+		_, err = pv.VerifyProof(context.Background(), root, &proof)
+		require.NoError(t, err, "VerifyProof")
+		require.EqualValues(t, v.Encoded, cbor.Marshal(proof), "proof encoding should round-trip")
+	}
+}
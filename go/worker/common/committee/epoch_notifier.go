@@ -0,0 +1,104 @@
+package committee
+
+import (
+	"context"
+	"sync"
+
+	"github.com/oasisprotocol/oasis-core/go/common/logging"
+	consensus "github.com/oasisprotocol/oasis-core/go/consensus/api"
+	epochtime "github.com/oasisprotocol/oasis-core/go/epochtime/api"
+	"github.com/oasisprotocol/oasis-core/go/runtime/host"
+	"github.com/oasisprotocol/oasis-core/go/runtime/host/protocol"
+)
+
+// epochNotifier is a runtime host notifier which notifies the runtime of
+// consensus epoch transitions.
+type epochNotifier struct {
+	sync.Mutex
+
+	ctx context.Context
+
+	stopCh chan struct{}
+
+	started   bool
+	consensus consensus.Backend
+	host      host.Runtime
+
+	logger *logging.Logger
+}
+
+func (n *epochNotifier) notifyEpoch(epoch epochtime.EpochTime) error {
+	height, err := n.consensus.EpochTime().GetEpochBlock(n.ctx, epoch)
+	if err != nil {
+		return err
+	}
+	beacon, err := n.consensus.Beacon().GetBeacon(n.ctx, height)
+	if err != nil {
+		return err
+	}
+
+	req := &protocol.Body{RuntimeConsensusEpochUpdateRequest: &protocol.RuntimeConsensusEpochUpdateRequest{
+		Epoch:  epoch,
+		Height: height,
+		Beacon: beacon,
+	}}
+	_, err = n.host.Call(n.ctx, req)
+	return err
+}
+
+func (n *epochNotifier) watchEpochs() {
+	epochCh, epochSub := n.consensus.EpochTime().WatchEpochs()
+	defer epochSub.Close()
+
+	for {
+		select {
+		case <-n.ctx.Done():
+			n.logger.Warn("contex canceled")
+			return
+		case <-n.stopCh:
+			n.logger.Warn("termination requested")
+			return
+		case epoch := <-epochCh:
+			if err := n.notifyEpoch(epoch); err != nil {
+				n.logger.Error("failed dispatching epoch update to runtime",
+					"err", err,
+					"epoch", epoch,
+				)
+				continue
+			}
+			n.logger.Debug("epoch update dispatched", "epoch", epoch)
+		}
+	}
+}
+
+// Implements protocol.Notifier.
+func (n *epochNotifier) Start() error {
+	n.Lock()
+	defer n.Unlock()
+
+	if n.started {
+		return nil
+	}
+	n.started = true
+
+	go n.watchEpochs()
+
+	return nil
+}
+
+// Implements protocol.Notifier.
+func (n *epochNotifier) Stop() {
+	close(n.stopCh)
+}
+
+// NewEpochNotifier creates a new runtime host notifier which notifies the
+// runtime of consensus epoch transitions.
+func NewEpochNotifier(ctx context.Context, consensusBackend consensus.Backend, host host.Runtime) protocol.Notifier {
+	return &epochNotifier{
+		ctx:       ctx,
+		stopCh:    make(chan struct{}),
+		consensus: consensusBackend,
+		host:      host,
+		logger:    logging.GetLogger("committee/epoch-notifier"),
+	}
+}
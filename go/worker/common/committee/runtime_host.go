@@ -7,6 +7,7 @@ import (
 
 	"github.com/opentracing/opentracing-go"
 
+	"github.com/oasisprotocol/oasis-core/go/common/cache/lru"
 	"github.com/oasisprotocol/oasis-core/go/common/cbor"
 	"github.com/oasisprotocol/oasis-core/go/common/logging"
 	keymanagerApi "github.com/oasisprotocol/oasis-core/go/keymanager/api"
@@ -16,11 +17,25 @@ import (
 	"github.com/oasisprotocol/oasis-core/go/runtime/localstorage"
 	runtimeRegistry "github.com/oasisprotocol/oasis-core/go/runtime/registry"
 	storage "github.com/oasisprotocol/oasis-core/go/storage/api"
+	"github.com/oasisprotocol/oasis-core/go/storage/mkvs/syncer"
+)
+
+const (
+	// maxProofChunkSize is the maximum size of the proof entries sent in a
+	// single storage sync response, leaving room for the rest of the message.
+	// Larger proofs are sent in chunks.
+	maxProofChunkSize = 15 * 1024 * 1024
+	// proofEntryOverhead is the maximum encoding overhead of a proof entry.
+	proofEntryOverhead = 9
+	// partialResponseCacheSize is the number of chunked storage sync responses
+	// kept around for the runtime to fetch the remaining chunks.
+	partialResponseCacheSize = 16
 )
 
 var (
-	errMethodNotSupported   = errors.New("method not supported")
-	errEndpointNotSupported = errors.New("RPC endpoint not supported")
+	errMethodNotSupported     = errors.New("method not supported")
+	errEndpointNotSupported   = errors.New("RPC endpoint not supported")
+	errUnknownPartialResponse = errors.New("unknown partial storage sync response")
 )
 
 // computeRuntimeHostHandler is a runtime host handler suitable for compute runtimes.
@@ -32,6 +47,8 @@ type computeRuntimeHostHandler struct {
 	keyManager       keymanagerApi.Backend
 	keyManagerClient *keymanagerClient.Client
 	localStorage     localstorage.LocalStorage
+
+	partialResponses *lru.Cache
 }
 
 func (h *computeRuntimeHostHandler) Handle(ctx context.Context, body *protocol.Body) (*protocol.Body, error) {
@@ -57,26 +74,56 @@ func (h *computeRuntimeHostHandler) Handle(ctx context.Context, body *protocol.B
 	// Storage.
 	if body.HostStorageSyncRequest != nil {
 		rq := body.HostStorageSyncRequest
+		if rq.SyncNextChunk != nil {
+			return h.handleNextProofChunk(rq.SyncNextChunk)
+		}
+
 		span, sctx := opentracing.StartSpanFromContext(ctx, "storage.Sync")
 		defer span.Finish()
 
-		// Prioritize nodes that signed the last storage receipts.
-		h.node.CrossNode.Lock()
-		blk := h.node.CurrentBlock
-		h.node.CrossNode.Unlock()
-		if blk != nil {
-			sctx = storage.WithNodePriorityHintFromSignatures(sctx, blk.Header.StorageSignatures)
+		var rs syncer.ReadSyncer
+		switch rq.Endpoint {
+		case protocol.HostStorageEndpointRuntime:
+			// Prioritize nodes that signed the last storage receipts.
+			h.node.CrossNode.Lock()
+			blk := h.node.CurrentBlock
+			h.node.CrossNode.Unlock()
+			if blk != nil {
+				sctx = storage.WithNodePriorityHintFromSignatures(sctx, blk.Header.StorageSignatures)
+			}
+
+			rs = h.storage
+		case protocol.HostStorageEndpointConsensus:
+			// Consensus state proofs are verified by the runtime against verified headers.
+			rs = h.node.Consensus.State()
+		default:
+			return nil, errEndpointNotSupported
+		}
+
+		if rq.SyncGetMulti != nil {
+			rsps := make([]*storage.ProofResponse, 0, len(rq.SyncGetMulti.Keys))
+			for _, key := range rq.SyncGetMulti.Keys {
+				rsp, err := rs.SyncGet(sctx, &storage.GetRequest{
+					Tree: rq.SyncGetMulti.Tree,
+					Key:  key,
+				})
+				if err != nil {
+					return nil, err
+				}
+				rsps = append(rsps, rsp)
+			}
+			return &protocol.Body{HostStorageSyncResponse: &protocol.HostStorageSyncResponse{ProofResponses: rsps}}, nil
 		}
 
 		var rsp *storage.ProofResponse
 		var err error
 		switch {
 		case rq.SyncGet != nil:
-			rsp, err = h.storage.SyncGet(sctx, rq.SyncGet)
+			rsp, err = rs.SyncGet(sctx, rq.SyncGet)
 		case rq.SyncGetPrefixes != nil:
-			rsp, err = h.storage.SyncGetPrefixes(sctx, rq.SyncGetPrefixes)
+			rsp, err = rs.SyncGetPrefixes(sctx, rq.SyncGetPrefixes)
 		case rq.SyncIterate != nil:
-			rsp, err = h.storage.SyncIterate(sctx, rq.SyncIterate)
+			rsp, err = rs.SyncIterate(sctx, rq.SyncIterate)
 		default:
 			return nil, errMethodNotSupported
 		}
@@ -84,7 +131,18 @@ func (h *computeRuntimeHostHandler) Handle(ctx context.Context, body *protocol.B
 			return nil, err
 		}
 
-		return &protocol.Body{HostStorageSyncResponse: &protocol.HostStorageSyncResponse{ProofResponse: rsp}}, nil
+		return h.proofResponseBody(ctx, rsp), nil
+	}
+	// Consensus light blocks.
+	if rq := body.HostFetchConsensusBlockRequest; rq != nil {
+		// The runtime verifies light blocks against its trust root.
+		lb, err := h.node.Consensus.GetLightBlock(ctx, rq.Height)
+		if err != nil {
+			return nil, err
+		}
+		return &protocol.Body{HostFetchConsensusBlockResponse: &protocol.HostFetchConsensusBlockResponse{
+			Block: *lb,
+		}}, nil
 	}
 	// Local storage.
 	if body.HostLocalStorageGetRequest != nil {
@@ -100,10 +158,91 @@ func (h *computeRuntimeHostHandler) Handle(ctx context.Context, body *protocol.B
 		}
 		return &protocol.Body{HostLocalStorageSetResponse: &protocol.Empty{}}, nil
 	}
+	if body.HostLocalStorageGetBatchRequest != nil {
+		values, err := h.localStorage.GetBatch(body.HostLocalStorageGetBatchRequest.Keys)
+		if err != nil {
+			return nil, err
+		}
+		return &protocol.Body{HostLocalStorageGetBatchResponse: &protocol.HostLocalStorageGetBatchResponse{Values: values}}, nil
+	}
+	if body.HostLocalStorageSetBatchRequest != nil {
+		items := body.HostLocalStorageSetBatchRequest.Items
+		keys := make([][]byte, 0, len(items))
+		values := make([][]byte, 0, len(items))
+		for _, item := range items {
+			keys = append(keys, item.Key)
+			values = append(values, item.Value)
+		}
+		if err := h.localStorage.SetBatch(keys, values); err != nil {
+			return nil, err
+		}
+		return &protocol.Body{HostLocalStorageSetBatchResponse: &protocol.Empty{}}, nil
+	}
 
 	return nil, errMethodNotSupported
 }
 
+// proofResponseBody returns the storage sync response body for the given proof
+// response. Proofs that are too large for a single message are split into
+// chunks and only the first one is returned, the runtime requests the others
+// with SyncNextChunk requests.
+func (h *computeRuntimeHostHandler) proofResponseBody(ctx context.Context, rsp *storage.ProofResponse) *protocol.Body {
+	chunks := splitProof(&rsp.Proof)
+	id, ok := protocol.RequestIDFromContext(ctx)
+	if len(chunks) == 1 || !ok {
+		return &protocol.Body{HostStorageSyncResponse: &protocol.HostStorageSyncResponse{ProofResponse: rsp}}
+	}
+
+	_ = h.partialResponses.Put(id, chunks)
+	return &protocol.Body{HostStorageSyncResponse: &protocol.HostStorageSyncResponse{PartialProofResponse: chunks[0]}}
+}
+
+func (h *computeRuntimeHostHandler) handleNextProofChunk(rq *protocol.HostStorageNextChunkRequest) (*protocol.Body, error) {
+	v, ok := h.partialResponses.Get(rq.Request)
+	if !ok {
+		return nil, errUnknownPartialResponse
+	}
+	chunks := v.([]*protocol.HostStoragePartialProofResponse)
+	if rq.Chunk == 0 || int(rq.Chunk) >= len(chunks) {
+		return nil, errUnknownPartialResponse
+	}
+
+	chunk := chunks[rq.Chunk]
+	if !chunk.More {
+		// The last chunk has been requested, the response is no longer needed.
+		h.partialResponses.Remove(rq.Request)
+	}
+	return &protocol.Body{HostStorageSyncResponse: &protocol.HostStorageSyncResponse{PartialProofResponse: chunk}}, nil
+}
+
+// splitProof splits the proof into chunks whose entries take at most
+// maxProofChunkSize bytes. Entries that are larger get a chunk of their own.
+func splitProof(proof *storage.Proof) []*protocol.HostStoragePartialProofResponse {
+	newChunk := func() *protocol.HostStoragePartialProofResponse {
+		return &protocol.HostStoragePartialProofResponse{
+			Proof: storage.Proof{UntrustedRoot: proof.UntrustedRoot},
+			More:  true,
+		}
+	}
+
+	var chunks []*protocol.HostStoragePartialProofResponse
+	chunk := newChunk()
+	var size int
+	for _, entry := range proof.Entries {
+		entrySize := len(entry) + proofEntryOverhead
+		if size > 0 && size+entrySize > maxProofChunkSize {
+			chunks = append(chunks, chunk)
+			chunk = newChunk()
+			size = 0
+		}
+		chunk.Proof.Entries = append(chunk.Proof.Entries, entry)
+		size += entrySize
+	}
+	chunk.More = false
+
+	return append(chunks, chunk)
+}
+
 // Implements RuntimeHostHandlerFactory.
 func (n *Node) GetRuntime() runtimeRegistry.Runtime {
 	return n.Runtime
@@ -118,10 +257,11 @@ type computeRuntimeHostNotifier struct {
 
 	stopCh chan struct{}
 
-	started    bool
-	runtime    runtimeRegistry.Runtime
-	host       host.Runtime
-	keyManager keymanagerApi.Backend
+	started       bool
+	runtime       runtimeRegistry.Runtime
+	host          host.Runtime
+	keyManager    keymanagerApi.Backend
+	epochNotifier protocol.Notifier
 
 	logger *logging.Logger
 }
@@ -190,28 +330,33 @@ func (n *computeRuntimeHostNotifier) Start() error {
 
 	go n.watchPolicyUpdates()
 
-	return nil
+	return n.epochNotifier.Start()
 }
 
 // Implements protocol.Notifier.
 func (n *computeRuntimeHostNotifier) Stop() {
+	n.epochNotifier.Stop()
 	close(n.stopCh)
 }
 
 // Implements RuntimeHostHandlerFactory.
 func (n *Node) NewNotifier(ctx context.Context, host host.Runtime) protocol.Notifier {
 	return &computeRuntimeHostNotifier{
-		ctx:        ctx,
-		stopCh:     make(chan struct{}),
-		runtime:    n.Runtime,
-		host:       host,
-		keyManager: n.KeyManager,
-		logger:     logging.GetLogger("committee/runtime-host"),
+		ctx:           ctx,
+		stopCh:        make(chan struct{}),
+		runtime:       n.Runtime,
+		host:          host,
+		keyManager:    n.KeyManager,
+		epochNotifier: NewEpochNotifier(ctx, n.Consensus, host),
+		logger:        logging.GetLogger("committee/runtime-host"),
 	}
 }
 
 // Implements RuntimeHostHandlerFactory.
 func (n *Node) NewRuntimeHostHandler() protocol.Handler {
+	// Creating a cache with a fixed number of slots cannot fail.
+	partialResponses, _ := lru.New(lru.Capacity(partialResponseCacheSize, false))
+
 	return &computeRuntimeHostHandler{
 		node:             n,
 		runtime:          n.Runtime,
@@ -219,5 +364,6 @@ func (n *Node) NewRuntimeHostHandler() protocol.Handler {
 		keyManager:       n.KeyManager,
 		keyManagerClient: n.KeyManagerClient,
 		localStorage:     n.Runtime.LocalStorage(),
+		partialResponses: partialResponses,
 	}
 }
@@ -1,7 +1,9 @@
 package common
 
 import (
+	"encoding/json"
 	"fmt"
+	"io/ioutil"
 	"os"
 	"time"
 
@@ -11,6 +13,7 @@ import (
 	"github.com/oasisprotocol/oasis-core/go/common"
 	"github.com/oasisprotocol/oasis-core/go/common/logging"
 	"github.com/oasisprotocol/oasis-core/go/common/node"
+	"github.com/oasisprotocol/oasis-core/go/common/sgx"
 	ias "github.com/oasisprotocol/oasis-core/go/ias/api"
 	cmdFlags "github.com/oasisprotocol/oasis-core/go/oasis-node/cmd/common/flags"
 	runtimeHost "github.com/oasisprotocol/oasis-core/go/runtime/host"
@@ -43,6 +46,10 @@ var (
 	// The value should be a map of runtime IDs to corresponding resource
 	// paths.
 	CfgRuntimeSGXSignatures = "worker.runtime.sgx.signatures"
+	// CfgRuntimeSGXQuotePolicies configures quote policies for supported
+	// runtimes. The value should be a map of runtime IDs to corresponding
+	// paths of JSON-encoded policies.
+	CfgRuntimeSGXQuotePolicies = "worker.runtime.sgx.quote_policies"
 
 	cfgSandboxBinary        = "worker.runtime.sandbox_binary"
 	cfgStorageCommitTimeout = "worker.storage_commit_timeout"
@@ -197,6 +204,7 @@ func NewConfig(ias ias.Endpoint) (*Config, error) {
 
 		// Configure runtimes.
 		runtimeSGXSignatures := viper.GetStringMapString(CfgRuntimeSGXSignatures)
+		runtimeSGXQuotePolicies := viper.GetStringMapString(CfgRuntimeSGXQuotePolicies)
 		rh.Runtimes = make(map[common.Namespace]runtimeHost.Config)
 		for runtimeID, path := range viper.GetStringMapString(CfgRuntimePaths) {
 			var id common.Namespace
@@ -212,16 +220,25 @@ func NewConfig(ias ias.Endpoint) (*Config, error) {
 			// This config is SGX specific, but that's all that's supported
 			// right now that needs this anyway, the non-SGX provisioner
 			// currently ignores this.
+			var rtExtra hostSgx.RuntimeExtra
 			if sigPath := runtimeSGXSignatures[runtimeID]; sigPath != "" {
-				runtimeHostCfg.Extra = &hostSgx.RuntimeExtra{
-					SignaturePath: sigPath,
-				}
+				rtExtra.SignaturePath = sigPath
 			} else {
 				// HACK HACK HACK: Allow dummy SIGSTRUCT generation.
-				runtimeHostCfg.Extra = &hostSgx.RuntimeExtra{
-					UnsafeDebugGenerateSigstruct: true,
+				rtExtra.UnsafeDebugGenerateSigstruct = true
+			}
+			if policyPath := runtimeSGXQuotePolicies[runtimeID]; policyPath != "" {
+				rawPolicy, err := ioutil.ReadFile(policyPath)
+				if err != nil {
+					return nil, fmt.Errorf("failed to load quote policy for runtime '%s': %w", runtimeID, err)
+				}
+				var policy sgx.QuotePolicy
+				if err = json.Unmarshal(rawPolicy, &policy); err != nil {
+					return nil, fmt.Errorf("malformed quote policy for runtime '%s': %w", runtimeID, err)
 				}
+				rtExtra.QuotePolicy = &policy
 			}
+			runtimeHostCfg.Extra = &rtExtra
 
 			rh.Runtimes[id] = runtimeHostCfg
 		}
@@ -244,6 +261,7 @@ func init() {
 	Flags.String(CfgRuntimeSGXLoader, "", "(for SGX runtimes) Path to SGXS runtime loader binary")
 	Flags.StringToString(CfgRuntimePaths, nil, "Paths to runtime resources (format: <rt1-ID>=<path>,<rt2-ID>=<path>)")
 	Flags.StringToString(CfgRuntimeSGXSignatures, nil, "(for SGX runtimes) Paths to signatures (format: <rt1-ID>=<path>,<rt2-ID>=<path>")
+	Flags.StringToString(CfgRuntimeSGXQuotePolicies, nil, "(for SGX runtimes) Paths to JSON-encoded quote policies (format: <rt1-ID>=<path>,<rt2-ID>=<path>")
 
 	Flags.String(cfgSandboxBinary, "/usr/bin/bwrap", "Path to the sandbox binary (bubblewrap)")
 
@@ -254,10 +254,13 @@ func (n *Node) HandlePeerMessage(ctx context.Context, message *p2p.Message, isOw
 
 		if n.scheduleCheckTxEnabled {
 			// Check transaction before queuing it.
-			if err := n.checkTx(ctx, tx); err != nil {
+			meta, err := n.checkTx(ctx, tx)
+			if err != nil {
 				return true, err
 			}
-			n.logger.Debug("worker CheckTx successful, queuing transaction")
+			n.logger.Debug("worker CheckTx successful, queuing transaction",
+				"meta", meta,
+			)
 		}
 
 		err := n.QueueTx(tx)
@@ -566,13 +569,16 @@ func (n *Node) HandleNewBlockLocked(blk *block.Block) {
 }
 
 // checkTx requests the runtime to check the validity of the given transaction.
-func (n *Node) checkTx(ctx context.Context, tx []byte) error {
+//
+// On success, the transaction metadata returned by the runtime (if any) is
+// returned.
+func (n *Node) checkTx(ctx context.Context, tx []byte) (*transaction.CheckTxMetadata, error) {
 	n.commonNode.CrossNode.Lock()
 	currentBlock := n.commonNode.CurrentBlock
 	n.commonNode.CrossNode.Unlock()
 
 	if currentBlock == nil {
-		return errNotReady
+		return nil, errNotReady
 	}
 
 	checkRq := &protocol.Body{
@@ -584,7 +590,7 @@ func (n *Node) checkTx(ctx context.Context, tx []byte) error {
 	rt := n.GetHostedRuntime()
 	if rt == nil {
 		n.logger.Error("CheckTx: hosted runtime not initialized")
-		return errNotReady
+		return nil, errNotReady
 	}
 	resp, err := rt.Call(ctx, checkRq)
 	switch {
@@ -592,18 +598,23 @@ func (n *Node) checkTx(ctx context.Context, tx []byte) error {
 		n.logger.Error("CheckTx: runtime call error",
 			"err", err,
 		)
-		return p2pError.Permanent(err)
+		return nil, p2pError.Permanent(err)
 	case resp.RuntimeCheckTxBatchResponse == nil:
 		n.logger.Error("CheckTx: runtime response is nil")
-		return errCheckTxFailed
+		return nil, errCheckTxFailed
 	case resp.RuntimeCheckTxBatchResponse.Results == nil:
 		n.logger.Error("CheckTx: response contains no results")
-		return errCheckTxFailed
+		return nil, errCheckTxFailed
 	case len(resp.RuntimeCheckTxBatchResponse.Results) != 1:
 		n.logger.Error("CheckTx: runtime response doesn't contain exactly one result",
 			"num_results", len(resp.RuntimeCheckTxBatchResponse.Results),
 		)
-		return errCheckTxFailed
+		return nil, errCheckTxFailed
+	case resp.RuntimeCheckTxBatchResponse.Meta != nil && len(resp.RuntimeCheckTxBatchResponse.Meta) != 1:
+		n.logger.Error("CheckTx: runtime response doesn't contain exactly one metadata entry",
+			"num_meta", len(resp.RuntimeCheckTxBatchResponse.Meta),
+		)
+		return nil, errCheckTxFailed
 	}
 
 	// Interpret CheckTx result.
@@ -613,16 +624,26 @@ func (n *Node) checkTx(ctx context.Context, tx []byte) error {
 		n.logger.Error("CheckTx: runtime response failed to deserialize",
 			"err", err,
 		)
-		return errCheckTxFailed
+		return nil, errCheckTxFailed
 	}
 	if result.Error != nil {
 		n.logger.Error("CheckTx: runtime failed with error",
 			"err", result.Error,
 		)
-		return fmt.Errorf("%w: %s", errCheckTxFailed, *result.Error)
+		return nil, fmt.Errorf("%w: %s", errCheckTxFailed, *result.Error)
+	}
+	if result.OutOfGas != nil {
+		n.logger.Error("CheckTx: runtime ran out of gas",
+			"gas_limit", result.OutOfGas.Limit,
+		)
+		return nil, fmt.Errorf("%w: out of gas (limit %d)", errCheckTxFailed, result.OutOfGas.Limit)
 	}
 
-	return nil
+	// Older runtimes do not return any metadata.
+	if resp.RuntimeCheckTxBatchResponse.Meta == nil {
+		return nil, nil
+	}
+	return resp.RuntimeCheckTxBatchResponse.Meta[0], nil
 }
 
 // QueueTx queues a runtime transaction for scheduling.
@@ -974,6 +995,7 @@ func (n *Node) startProcessingBatchLocked(batch *unresolvedBatch) {
 	// Request the worker host to process a batch. This is done in a separate
 	// goroutine so that the committee node can continue processing blocks.
 	blk := n.commonNode.CurrentBlock
+	height := n.commonNode.CurrentBlockHeight
 	go func() {
 		defer close(done)
 
@@ -987,11 +1009,55 @@ func (n *Node) startProcessingBatchLocked(batch *unresolvedBatch) {
 			)
 			return
 		}
+		// Use the beacon at the height of the block, so that all executors
+		// provide the same randomness to the runtime.
+		beacon, err := n.commonNode.Consensus.Beacon().GetBeacon(ctx, height)
+		if err != nil {
+			n.logger.Error("failed to get beacon",
+				"err", err,
+				"height", height,
+			)
+			return
+		}
+		// Deliver the results of the messages emitted in the previous round.
+		roundResults, err := n.commonNode.Consensus.RootHash().GetLastRoundResults(ctx, n.commonNode.Runtime.ID(), height)
+		if err != nil {
+			n.logger.Error("failed to get last round results",
+				"err", err,
+				"height", height,
+			)
+			return
+		}
+		// Deliver the incoming messages queued for the runtime.
+		rtDesc, err := n.commonNode.Runtime.RegistryDescriptor(ctx)
+		if err != nil {
+			n.logger.Error("failed to fetch runtime registry descriptor",
+				"err", err,
+			)
+			return
+		}
+		inMsgs, err := n.commonNode.Consensus.RootHash().GetIncomingMessageQueue(
+			ctx,
+			n.commonNode.Runtime.ID(),
+			height,
+			0,
+			rtDesc.Executor.MaxInMessages,
+		)
+		if err != nil {
+			n.logger.Error("failed to get incoming message queue",
+				"err", err,
+				"height", height,
+			)
+			return
+		}
 		rq := &protocol.Body{
 			RuntimeExecuteTxBatchRequest: &protocol.RuntimeExecuteTxBatchRequest{
-				IORoot: batch.ioRoot.Hash,
-				Inputs: resolvedBatch,
-				Block:  *blk,
+				IORoot:           batch.ioRoot.Hash,
+				Inputs:           resolvedBatch,
+				Block:            *blk,
+				Beacon:           beacon,
+				MessageResults:   roundResults.Messages,
+				IncomingMessages: inMsgs,
 			},
 		}
 		batchReadTime.With(n.getMetricLabels()).Observe(time.Since(readStartTime).Seconds())
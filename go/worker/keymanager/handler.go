@@ -75,6 +75,26 @@ func (h *hostHandler) Handle(ctx context.Context, body *protocol.Body) (*protoco
 		}
 		return &protocol.Body{HostLocalStorageSetResponse: &protocol.Empty{}}, nil
 	}
+	if body.HostLocalStorageGetBatchRequest != nil {
+		values, err := h.localStorage.GetBatch(body.HostLocalStorageGetBatchRequest.Keys)
+		if err != nil {
+			return nil, err
+		}
+		return &protocol.Body{HostLocalStorageGetBatchResponse: &protocol.HostLocalStorageGetBatchResponse{Values: values}}, nil
+	}
+	if body.HostLocalStorageSetBatchRequest != nil {
+		items := body.HostLocalStorageSetBatchRequest.Items
+		keys := make([][]byte, 0, len(items))
+		values := make([][]byte, 0, len(items))
+		for _, item := range items {
+			keys = append(keys, item.Key)
+			values = append(values, item.Value)
+		}
+		if err := h.localStorage.SetBatch(keys, values); err != nil {
+			return nil, err
+		}
+		return &protocol.Body{HostLocalStorageSetBatchResponse: &protocol.Empty{}}, nil
+	}
 	// RPC.
 	if body.HostRPCCallRequest != nil {
 		switch body.HostRPCCallRequest.Endpoint {
@@ -96,6 +116,16 @@ func (h *hostHandler) Handle(ctx context.Context, body *protocol.Body) (*protoco
 			return nil, errEndpointNotSupported
 		}
 	}
+	// Consensus light blocks.
+	if rq := body.HostFetchConsensusBlockRequest; rq != nil {
+		lb, err := h.w.commonWorker.Consensus.GetLightBlock(ctx, rq.Height)
+		if err != nil {
+			return nil, err
+		}
+		return &protocol.Body{HostFetchConsensusBlockResponse: &protocol.HostFetchConsensusBlockResponse{
+			Block: *lb,
+		}}, nil
+	}
 
 	return nil, errMethodNotSupported
 }
@@ -135,7 +135,7 @@ func (w *Worker) GetRuntime() runtimeRegistry.Runtime {
 
 // Implements workerCommon.RuntimeHostHandlerFactory.
 func (w *Worker) NewNotifier(ctx context.Context, host host.Runtime) protocol.Notifier {
-	return &protocol.NoOpNotifier{}
+	return committeeCommon.NewEpochNotifier(ctx, w.commonWorker.Consensus, host)
 }
 
 // Implements workerCommon.RuntimeHostHandlerFactory.
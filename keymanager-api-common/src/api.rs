@@ -1,17 +1,21 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     default::Default,
 };
 
 use base64;
-use rand::{rngs::OsRng, Rng};
+use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use x25519_dalek;
 
 use oasis_core_runtime::{
     common::{
-        crypto::signature::{PublicKey as OasisPublicKey, Signature, SignatureBundle},
+        crypto::{
+            secret::Secret,
+            signature::{PublicKey as OasisPublicKey, Signature, SignatureBundle},
+            vss::{Commitments, Index},
+        },
         runtime::RuntimeId,
         sgx::avr::EnclaveIdentity,
     },
@@ -19,10 +23,20 @@ use oasis_core_runtime::{
 };
 
 impl_bytes!(KeyPairId, 32, "A 256-bit key pair identifier.");
-impl_bytes!(PrivateKey, 32, "A private key.");
 impl_bytes!(PublicKey, 32, "A public key.");
-impl_bytes!(StateKey, 32, "A state key.");
-impl_bytes!(MasterSecret, 32, "A 256 bit master secret.");
+
+/// A private key.
+pub type PrivateKey = Secret<32>;
+/// A state key.
+pub type StateKey = Secret<32>;
+/// A 256 bit master secret.
+pub type MasterSecret = Secret<32>;
+/// Epoch number.
+pub type EpochTime = u64;
+/// Master secret generation.
+///
+/// Generation 0 is the initial master secret of the key manager.
+pub type Generation = u64;
 
 /// Key manager initialization request.
 #[derive(Clone, Serialize, Deserialize)]
@@ -63,9 +77,11 @@ pub struct SignedInitResponse {
 }
 
 /// Key manager replication request.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ReplicateRequest {
-    // Empty.
+    /// Generation of the master secret to replicate.
+    #[serde(default)]
+    pub generation: Generation,
 }
 
 /// Key manager replication response.
@@ -74,6 +90,104 @@ pub struct ReplicateResponse {
     pub master_secret: MasterSecret,
 }
 
+/// Key manager master secret generation proposal request.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProposeRequest {
+    /// Generation of the proposed master secret, which must follow the
+    /// latest active generation.
+    pub generation: Generation,
+}
+
+/// Key manager master secret generation activation request.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ActivateRequest {
+    /// Generation of the master secret to activate.
+    pub generation: Generation,
+    /// Checksum of the master secret, as agreed by the network.
+    #[serde(with = "serde_bytes")]
+    pub checksum: Vec<u8>,
+}
+
+/// Key manager master secret generation response.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GenerationResponse {
+    /// Generation of the master secret.
+    pub generation: Generation,
+    /// Checksum of the master secret.
+    #[serde(with = "serde_bytes")]
+    pub checksum: Vec<u8>,
+}
+
+/// Request to deal a random secret to the committee jointly generating the
+/// master secret of a generation.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DealRequest {
+    /// Generation of the master secret.
+    pub generation: Generation,
+    /// Index of this key manager in the committee (starting at 1).
+    pub index: Index,
+    /// Number of committee members required to reconstruct the secret.
+    pub threshold: u32,
+    /// Number of committee members.
+    pub participants: u32,
+}
+
+/// Response to a deal request.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DealResponse {
+    /// Generation of the master secret.
+    pub generation: Generation,
+    /// Commitments to the dealt secret, to be published to the committee.
+    pub commitments: Commitments,
+}
+
+/// Request to combine the shares dealt by all committee members into this
+/// key manager's share of the joint secret.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CombineRequest {
+    /// Generation of the master secret.
+    pub generation: Generation,
+    /// Published commitments of each committee member, by index.
+    pub commitments: BTreeMap<Index, Commitments>,
+}
+
+/// Response to a combine request.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CombineResponse {
+    /// Generation of the master secret.
+    pub generation: Generation,
+    /// Commitments to the joint secret.
+    pub commitments: Commitments,
+}
+
+/// Request to reconstruct the jointly generated master secret and propose
+/// it as the given generation.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReconstructRequest {
+    /// Generation of the master secret.
+    pub generation: Generation,
+}
+
+/// Request for the share of a committee member, dealt by another member.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DealtShareRequest {
+    /// Generation of the master secret.
+    pub generation: Generation,
+    /// Index of the dealer.
+    pub dealer: Index,
+    /// Index of the member receiving the share.
+    pub index: Index,
+}
+
+/// Request for a committee member's share of the joint secret.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JointShareRequest {
+    /// Generation of the master secret.
+    pub generation: Generation,
+    /// Index of the member holding the share.
+    pub index: Index,
+}
+
 /// Request runtime/key pair id tuple.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RequestIds {
@@ -81,6 +195,9 @@ pub struct RequestIds {
     pub runtime_id: RuntimeId,
     /// Key pair ID.
     pub key_pair_id: KeyPairId,
+    /// Generation of the master secret the keys are derived from.
+    #[serde(default)]
+    pub generation: Generation,
 }
 
 impl RequestIds {
@@ -88,16 +205,50 @@ impl RequestIds {
         Self {
             runtime_id,
             key_pair_id,
+            generation: 0,
         }
     }
 
+    /// Request keys derived from the given master secret generation.
+    pub fn with_generation(mut self, generation: Generation) -> Self {
+        self.generation = generation;
+        self
+    }
+
     pub fn to_cache_key(&self) -> Vec<u8> {
         let mut k = self.runtime_id.as_ref().to_vec();
         k.extend_from_slice(self.key_pair_id.as_ref());
+        k.extend_from_slice(&self.generation.to_be_bytes());
         k
     }
 }
 
+/// Request for an ephemeral key pair of the given epoch.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EphemeralKeyRequest {
+    /// Runtime ID.
+    pub runtime_id: RuntimeId,
+    /// Key pair ID.
+    pub key_pair_id: KeyPairId,
+    /// Epoch of the key pair.
+    pub epoch: EpochTime,
+}
+
+impl EphemeralKeyRequest {
+    pub fn new(runtime_id: RuntimeId, key_pair_id: KeyPairId, epoch: EpochTime) -> Self {
+        Self {
+            runtime_id,
+            key_pair_id,
+            epoch,
+        }
+    }
+
+    /// Runtime/key pair id tuple of the request.
+    pub fn to_request_ids(&self) -> RequestIds {
+        RequestIds::new(self.runtime_id, self.key_pair_id)
+    }
+}
+
 /// A key pair managed by the key manager.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct KeyPair {
@@ -117,13 +268,10 @@ impl KeyPair {
         let sk = x25519_dalek::StaticSecret::new(&mut rng);
         let pk = x25519_dalek::PublicKey::from(&sk);
 
-        let mut state_key = StateKey::default();
-        rng.fill(&mut state_key.0);
-
         KeyPair::new(
             PublicKey(*pk.as_bytes()),
-            PrivateKey(sk.to_bytes()),
-            state_key,
+            PrivateKey::new(sk.to_bytes()),
+            StateKey::generate(),
             vec![],
         )
     }
@@ -165,7 +313,7 @@ impl InputKeyPair {
     }
 
     pub fn get_sk(&self) -> PrivateKey {
-        self.sk
+        self.sk.clone()
     }
 }
 
@@ -184,6 +332,24 @@ pub struct SignedPublicKey {
     pub signature: Signature,
 }
 
+/// Context used for the ephemeral public key signature.
+pub const EPHEMERAL_PUBLIC_KEY_CONTEXT: [u8; 8] = *b"EkKmEPub";
+
+/// Signed ephemeral public key.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SignedPublicEphemeralKey {
+    /// Public key.
+    pub key: PublicKey,
+    /// Epoch of the key.
+    pub epoch: EpochTime,
+    /// Checksum of the key manager state.
+    #[serde(with = "serde_bytes")]
+    pub checksum: Vec<u8>,
+    /// Sign(sk, (key || checksum || epoch)) from the key manager, with the
+    /// epoch encoded in big endian.
+    pub signature: Signature,
+}
+
 /// Key manager error.
 #[derive(Error, Debug)]
 pub enum KeyManagerError {
@@ -207,10 +373,24 @@ pub enum KeyManagerError {
     PolicyInvalidSignature,
     #[error("policy has insufficient signatures")]
     PolicyInsufficientSignatures,
+    #[error("ephemeral keys of epoch {0} have expired")]
+    EphemeralKeyExpired(EpochTime),
+    #[error("ephemeral keys of epoch {0} are not available yet")]
+    EphemeralKeyNotAvailable(EpochTime),
+    #[error("consensus epoch is not known")]
+    EpochUnknown,
+    #[error("master secret generation {0} is not active")]
+    GenerationNotActive(Generation),
+    #[error("invalid master secret generation {0}")]
+    InvalidGeneration(Generation),
+    #[error("no secret sharing in progress for generation {0}")]
+    SharingNotInProgress(Generation),
+    #[error("secret sharing participant mismatch (expected {expected} got {actual})")]
+    WrongParticipant { expected: Index, actual: Index },
 }
 
 /// Key manager access control policy.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PolicySGX {
     pub serial: u32,
     pub id: RuntimeId,
@@ -218,7 +398,7 @@ pub struct PolicySGX {
 }
 
 /// Per enclave key manager access control policy.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EnclavePolicySGX {
     pub may_query: HashMap<RuntimeId, Vec<EnclaveIdentity>>,
     pub may_replicate: Vec<EnclaveIdentity>,
@@ -255,4 +435,12 @@ runtime_api! {
     pub fn get_public_key(RequestIds) -> Option<SignedPublicKey>;
 
     pub fn replicate_master_secret(ReplicateRequest) -> ReplicateResponse;
+
+    pub fn get_or_create_ephemeral_keys(EphemeralKeyRequest) -> KeyPair;
+
+    pub fn get_public_ephemeral_key(EphemeralKeyRequest) -> Option<SignedPublicEphemeralKey>;
+
+    pub fn get_dealt_share(DealtShareRequest) -> Share;
+
+    pub fn get_joint_share(JointShareRequest) -> Share;
 }
@@ -36,6 +36,16 @@ const POLICY_SIGN_CONTEXT: &'static [u8] = b"oasis-core/keymanager: policy";
 impl SignedPolicySGX {
     /// Verify the signatures and return the PolicySGX, if the signatures are correct.
     pub fn verify(&self) -> Result<PolicySGX> {
+        let trusted_signers = TRUSTED_SIGNERS.lock().unwrap();
+        self.verify_with(&trusted_signers)
+    }
+
+    /// Verify that the policy has been signed by at least `threshold` of the
+    /// given trusted signers and return the PolicySGX.
+    ///
+    /// Signatures by other signers are verified, but don't count towards the
+    /// threshold.
+    pub fn verify_with(&self, trusted_signers: &TrustedPolicySigners) -> Result<PolicySGX> {
         // Verify the signatures.
         let untrusted_policy_raw = cbor::to_vec(&self.policy);
         let mut signers: HashSet<OasisPublicKey> = HashSet::new();
@@ -56,12 +66,15 @@ impl SignedPolicySGX {
         }
 
         // Ensure that enough valid signatures from trusted signers are present.
-        let trusted_signers = TRUSTED_SIGNERS.lock().unwrap();
         let signers: HashSet<_> = trusted_signers.signers.intersection(&signers).collect();
         let multisig_threshold = match option_env!("OASIS_UNSAFE_KM_POLICY_KEYS") {
             Some(_) => 2,
             None => trusted_signers.threshold,
         };
+        if multisig_threshold == 0 {
+            // A zero threshold would accept unsigned policies.
+            return Err(KeyManagerError::PolicyInvalid.into());
+        }
         if signers.len() < multisig_threshold {
             return Err(KeyManagerError::PolicyInsufficientSignatures.into());
         }
@@ -69,3 +82,110 @@ impl SignedPolicySGX {
         Ok(self.policy.clone())
     }
 }
+
+impl PolicySGX {
+    /// Ensure that the policy may replace a previously accepted policy.
+    ///
+    /// Policy serial numbers must increase monotonically, so that an older
+    /// (but validly signed) policy cannot be replayed. A policy with the same
+    /// serial number is only accepted if it is identical to the previous one.
+    pub fn check_update(&self, previous: &PolicySGX) -> Result<()> {
+        if self.id != previous.id {
+            return Err(KeyManagerError::PolicyInvalid.into());
+        }
+        if self.serial < previous.serial {
+            return Err(KeyManagerError::PolicyRollback.into());
+        }
+        if self.serial == previous.serial && self != previous {
+            return Err(KeyManagerError::PolicyChanged.into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use oasis_core_runtime::common::{
+        crypto::signature::{PrivateKey, SignatureBundle, Signer},
+        runtime::RuntimeId,
+        sgx::avr::EnclaveIdentity,
+    };
+
+    use super::*;
+
+    fn sign(policy: &PolicySGX, signers: &[&PrivateKey]) -> SignedPolicySGX {
+        let raw = cbor::to_vec(policy);
+        SignedPolicySGX {
+            policy: policy.clone(),
+            signatures: signers
+                .iter()
+                .map(|sk| SignatureBundle {
+                    public_key: Some(sk.public_key()),
+                    signature: Signer::sign(*sk, &POLICY_SIGN_CONTEXT, &raw).unwrap(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_policy_quorum() {
+        let keys: Vec<PrivateKey> = (0..3).map(|_| PrivateKey::generate()).collect();
+        let untrusted = PrivateKey::generate();
+        let trusted_signers = TrustedPolicySigners {
+            signers: keys.iter().map(|sk| sk.public_key()).collect(),
+            threshold: 2,
+        };
+        let policy = PolicySGX {
+            serial: 1,
+            id: RuntimeId::default(),
+            enclaves: Default::default(),
+        };
+
+        assert!(sign(&policy, &[&keys[0], &keys[2]])
+            .verify_with(&trusted_signers)
+            .is_ok());
+        assert!(
+            sign(&policy, &[&keys[0], &keys[0], &untrusted])
+                .verify_with(&trusted_signers)
+                .is_err(),
+            "duplicate and untrusted signatures don't count"
+        );
+
+        let mut tampered = sign(&policy, &[&keys[0], &keys[1]]);
+        tampered.policy.serial = 2;
+        assert!(tampered.verify_with(&trusted_signers).is_err());
+
+        let no_threshold = TrustedPolicySigners {
+            threshold: 0,
+            ..trusted_signers
+        };
+        assert!(sign(&policy, &[]).verify_with(&no_threshold).is_err());
+    }
+
+    #[test]
+    fn test_policy_update() {
+        let previous = PolicySGX {
+            serial: 2,
+            id: RuntimeId::default(),
+            enclaves: Default::default(),
+        };
+
+        let mut policy = previous.clone();
+        assert!(policy.check_update(&previous).is_ok());
+        policy.serial = 3;
+        assert!(policy.check_update(&previous).is_ok());
+        policy.serial = 1;
+        assert!(policy.check_update(&previous).is_err(), "rollback");
+
+        let mut changed = previous.clone();
+        changed.enclaves.insert(
+            EnclaveIdentity::default(),
+            EnclavePolicySGX {
+                may_query: Default::default(),
+                may_replicate: vec![],
+            },
+        );
+        assert!(changed.check_update(&previous).is_err(), "same serial");
+    }
+}
@@ -0,0 +1,155 @@
+//! Local cache of keys fetched from the key manager.
+use std::{
+    hash::Hash,
+    time::{Duration, SystemTime},
+};
+
+use lru::LruCache;
+
+use oasis_core_keymanager_api_common::EpochTime;
+use oasis_core_runtime::common::time::insecure_posix_system_time;
+
+struct Entry<V> {
+    value: V,
+    /// Epoch in which the value has been fetched.
+    epoch: EpochTime,
+    /// Time after which the value must be fetched again (if any).
+    expires_at: Option<SystemTime>,
+}
+
+/// Size-bounded cache whose entries are invalidated after a time-to-live
+/// or an epoch transition.
+///
+/// Note that time is provided by the host, so a malicious host can only
+/// cause entries to stay cached longer (or shorter) than their TTL. Entries
+/// which must not outlive an epoch should rely on `invalidate_epoch`.
+pub struct KeyCache<K: Hash + Eq, V> {
+    entries: LruCache<K, Entry<V>>,
+    ttl: Option<Duration>,
+    epoch: EpochTime,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> KeyCache<K, V> {
+    /// Create a new cache holding at most `capacity` entries which never
+    /// expire.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: LruCache::new(capacity),
+            ttl: None,
+            epoch: 0,
+        }
+    }
+
+    /// Set the time-to-live of newly cached entries.
+    pub fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Epoch tagging newly cached entries.
+    pub fn epoch(&self) -> EpochTime {
+        self.epoch
+    }
+
+    /// Return the cached value for the given key, unless it has expired.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let expired = match self.entries.get(key)?.expires_at {
+            Some(expires_at) => insecure_posix_system_time() >= expires_at,
+            None => false,
+        };
+        if expired {
+            self.entries.pop(key);
+            return None;
+        }
+
+        self.entries.get(key).map(|entry| entry.value.clone())
+    }
+
+    /// Cache a value fetched in the given epoch (as returned by `epoch`
+    /// before fetching it).
+    ///
+    /// The value is not cached if its epoch has been invalidated while it
+    /// was being fetched.
+    pub fn put(&mut self, key: K, value: V, epoch: EpochTime) {
+        if epoch < self.epoch {
+            return;
+        }
+
+        let expires_at = self.ttl.map(|ttl| insecure_posix_system_time() + ttl);
+        self.entries.put(
+            key,
+            Entry {
+                value,
+                epoch,
+                expires_at,
+            },
+        );
+    }
+
+    /// Evict all entries fetched in the given or earlier epochs. Entries
+    /// cached afterwards are tagged with the next epoch.
+    pub fn invalidate_epoch(&mut self, epoch: EpochTime) {
+        let stale: Vec<K> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.epoch <= epoch)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            self.entries.pop(&key);
+        }
+        self.epoch = self.epoch.max(epoch.saturating_add(1));
+    }
+
+    /// Evict all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_cache() {
+        let mut cache = KeyCache::new(2);
+        cache.put("a", 1, cache.epoch());
+        cache.put("b", 2, cache.epoch());
+        assert_eq!(Some(1), cache.get(&"a"));
+        cache.put("c", 3, cache.epoch());
+        assert_eq!(
+            None,
+            cache.get(&"b"),
+            "least recently used entry is evicted"
+        );
+
+        // Epoch invalidation.
+        cache.invalidate_epoch(0);
+        assert_eq!(None, cache.get(&"a"));
+        assert_eq!(None, cache.get(&"c"));
+        assert_eq!(1, cache.epoch());
+        cache.put("a", 1, 0);
+        assert_eq!(
+            None,
+            cache.get(&"a"),
+            "values of invalidated epochs are not cached"
+        );
+        cache.put("a", 1, cache.epoch());
+        cache.invalidate_epoch(0);
+        assert_eq!(Some(1), cache.get(&"a"), "values of later epochs are kept");
+        assert_eq!(1, cache.epoch());
+
+        // Expiration.
+        cache.set_ttl(Some(Duration::from_secs(0)));
+        cache.put("b", 2, cache.epoch());
+        assert_eq!(None, cache.get(&"b"), "expired entries are evicted");
+        assert_eq!(Some(1), cache.get(&"a"));
+
+        cache.clear();
+        assert_eq!(None, cache.get(&"a"));
+
+        // Invalidating the last epoch does not overflow.
+        cache.invalidate_epoch(EpochTime::MAX);
+        assert_eq!(EpochTime::MAX, cache.epoch());
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     collections::HashSet,
     iter::FromIterator,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use anyhow::Result;
@@ -10,18 +11,17 @@ use futures::{future, prelude::*};
 #[cfg(not(target_env = "sgx"))]
 use grpcio::Channel;
 use io_context::Context;
-use lru::LruCache;
 
 use oasis_core_client::{create_rpc_api_client, BoxFuture, RpcClient};
 use oasis_core_keymanager_api_common::*;
 use oasis_core_runtime::{
-    common::{cbor, runtime::RuntimeId, sgx::avr::EnclaveIdentity},
+    common::{cbor, crypto::vss::Share, runtime::RuntimeId, sgx::avr::EnclaveIdentity},
     enclave_rpc::session,
     protocol::Protocol,
     rak::RAK,
 };
 
-use super::KeyManagerClient;
+use super::{cache::KeyCache, KeyManagerClient};
 
 with_api! {
     create_rpc_api_client!(Client, api);
@@ -36,9 +36,16 @@ struct Inner {
     /// RPC client.
     rpc_client: Client,
     /// Local cache for the get_or_create_keys KeyManager endpoint.
-    get_or_create_secret_keys_cache: RwLock<LruCache<KeyPairId, KeyPair>>,
+    get_or_create_secret_keys_cache: RwLock<KeyCache<(KeyPairId, Generation), KeyPair>>,
     /// Local cache for the get_public_key KeyManager endpoint.
-    get_public_key_cache: RwLock<LruCache<KeyPairId, SignedPublicKey>>,
+    get_public_key_cache: RwLock<KeyCache<KeyPairId, SignedPublicKey>>,
+    /// Local cache for the get_or_create_ephemeral_keys KeyManager endpoint.
+    get_or_create_ephemeral_keys_cache: RwLock<KeyCache<(KeyPairId, EpochTime), KeyPair>>,
+    /// Local cache for the get_public_ephemeral_key KeyManager endpoint.
+    get_public_ephemeral_key_cache:
+        RwLock<KeyCache<(KeyPairId, EpochTime), SignedPublicEphemeralKey>>,
+    /// Latest accepted key manager policy.
+    policy: RwLock<Option<PolicySGX>>,
 }
 
 /// A key manager client which talks to a remote key manager enclave.
@@ -52,8 +59,11 @@ impl RemoteClient {
             inner: Arc::new(Inner {
                 runtime_id,
                 rpc_client: Client::new(client),
-                get_or_create_secret_keys_cache: RwLock::new(LruCache::new(keys_cache_sizes)),
-                get_public_key_cache: RwLock::new(LruCache::new(keys_cache_sizes)),
+                get_or_create_secret_keys_cache: RwLock::new(KeyCache::new(keys_cache_sizes)),
+                get_public_key_cache: RwLock::new(KeyCache::new(keys_cache_sizes)),
+                get_or_create_ephemeral_keys_cache: RwLock::new(KeyCache::new(keys_cache_sizes)),
+                get_public_ephemeral_key_cache: RwLock::new(KeyCache::new(keys_cache_sizes)),
+                policy: RwLock::new(None),
             }),
         }
     }
@@ -71,6 +81,9 @@ impl RemoteClient {
             runtime_id,
             RpcClient::new_runtime(
                 session::Builder::new()
+                    .mutual_attestation(enclaves.is_some())
+                    // Deployed key managers only bind their RAK to the static key.
+                    .static_key_fallback(true)
                     .remote_enclaves(enclaves)
                     .local_rak(rak),
                 protocol,
@@ -133,16 +146,66 @@ impl RemoteClient {
         )
     }
 
+    /// Set the time-to-live of locally cached keys.
+    ///
+    /// By default cached keys only expire when evicted due to the cache size
+    /// or invalidated (see `KeyManagerClient::invalidate_epoch`).
+    pub fn set_cache_ttl(&self, ttl: Option<Duration>) {
+        // We explicitly only take one lock at a time.
+
+        let mut cache = self.inner.get_or_create_secret_keys_cache.write().unwrap();
+        cache.set_ttl(ttl);
+        drop(cache);
+
+        let mut cache = self.inner.get_public_key_cache.write().unwrap();
+        cache.set_ttl(ttl);
+        drop(cache);
+
+        let mut cache = self
+            .inner
+            .get_or_create_ephemeral_keys_cache
+            .write()
+            .unwrap();
+        cache.set_ttl(ttl);
+        drop(cache);
+
+        let mut cache = self.inner.get_public_ephemeral_key_cache.write().unwrap();
+        cache.set_ttl(ttl);
+        drop(cache);
+    }
+
     /// Set client allowed enclaves from key manager policy.
+    ///
+    /// The policy must be signed by enough trusted policy signers, and older
+    /// policies than the one already set are rejected.
     pub fn set_policy(&self, signed_policy_raw: Vec<u8>) -> Result<()> {
         let untrusted_policy: SignedPolicySGX = cbor::from_slice(&signed_policy_raw)?;
         let policy = untrusted_policy.verify()?;
+
+        let mut current = self.inner.policy.write().unwrap();
+        if let Some(ref previous) = *current {
+            policy.check_update(previous)?;
+        }
+
         let client = &self.inner.rpc_client.rpc_client;
         let policies: HashSet<EnclaveIdentity> =
             HashSet::from_iter(policy.enclaves.keys().cloned());
         client.update_enclaves(Some(policies));
+        *current = Some(policy);
         Ok(())
     }
+
+    /// Get the share of a committee member dealt by another key manager
+    /// enclave during joint master secret generation.
+    pub fn get_dealt_share(&self, ctx: Context, req: DealtShareRequest) -> BoxFuture<Share> {
+        self.inner.rpc_client.get_dealt_share(ctx, req)
+    }
+
+    /// Get the share of the joint master secret held by another key manager
+    /// enclave.
+    pub fn get_joint_share(&self, ctx: Context, req: JointShareRequest) -> BoxFuture<Share> {
+        self.inner.rpc_client.get_joint_share(ctx, req)
+    }
 }
 
 impl KeyManagerClient for RemoteClient {
@@ -156,23 +219,73 @@ impl KeyManagerClient for RemoteClient {
         let mut cache = self.inner.get_public_key_cache.write().unwrap();
         cache.clear();
         drop(cache);
+
+        let mut cache = self
+            .inner
+            .get_or_create_ephemeral_keys_cache
+            .write()
+            .unwrap();
+        cache.clear();
+        drop(cache);
+
+        let mut cache = self.inner.get_public_ephemeral_key_cache.write().unwrap();
+        cache.clear();
+        drop(cache);
+    }
+
+    fn invalidate_epoch(&self, epoch: EpochTime) {
+        // We explicitly only take one lock at a time.
+
+        let mut cache = self.inner.get_or_create_secret_keys_cache.write().unwrap();
+        cache.invalidate_epoch(epoch);
+        drop(cache);
+
+        let mut cache = self.inner.get_public_key_cache.write().unwrap();
+        cache.invalidate_epoch(epoch);
+        drop(cache);
+
+        let mut cache = self
+            .inner
+            .get_or_create_ephemeral_keys_cache
+            .write()
+            .unwrap();
+        cache.invalidate_epoch(epoch);
+        drop(cache);
+
+        let mut cache = self.inner.get_public_ephemeral_key_cache.write().unwrap();
+        cache.invalidate_epoch(epoch);
+        drop(cache);
     }
 
     fn get_or_create_keys(&self, ctx: Context, key_pair_id: KeyPairId) -> BoxFuture<KeyPair> {
+        self.get_or_create_keys_for_generation(ctx, key_pair_id, 0)
+    }
+
+    fn get_or_create_keys_for_generation(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        generation: Generation,
+    ) -> BoxFuture<KeyPair> {
         let mut cache = self.inner.get_or_create_secret_keys_cache.write().unwrap();
-        if let Some(keys) = cache.get(&key_pair_id) {
-            return Box::new(future::ok(keys.clone()));
+        if let Some(keys) = cache.get(&(key_pair_id, generation)) {
+            return Box::new(future::ok(keys));
         }
+        let epoch = cache.epoch();
+        drop(cache);
 
         // No entry in cache, fetch from key manager.
         let inner = self.inner.clone();
         Box::new(
             self.inner
                 .rpc_client
-                .get_or_create_keys(ctx, RequestIds::new(inner.runtime_id, key_pair_id))
+                .get_or_create_keys(
+                    ctx,
+                    RequestIds::new(inner.runtime_id, key_pair_id).with_generation(generation),
+                )
                 .and_then(move |keys| {
                     let mut cache = inner.get_or_create_secret_keys_cache.write().unwrap();
-                    cache.put(key_pair_id, keys.clone());
+                    cache.put((key_pair_id, generation), keys.clone(), epoch);
 
                     Ok(keys)
                 }),
@@ -186,8 +299,10 @@ impl KeyManagerClient for RemoteClient {
     ) -> BoxFuture<Option<SignedPublicKey>> {
         let mut cache = self.inner.get_public_key_cache.write().unwrap();
         if let Some(key) = cache.get(&key_pair_id) {
-            return Box::new(future::ok(Some(key.clone())));
+            return Box::new(future::ok(Some(key)));
         }
+        let epoch = cache.epoch();
+        drop(cache);
 
         // No entry in cache, fetch from key manager.
         let inner = self.inner.clone();
@@ -198,7 +313,77 @@ impl KeyManagerClient for RemoteClient {
                 .and_then(move |key| match key {
                     Some(key) => {
                         let mut cache = inner.get_public_key_cache.write().unwrap();
-                        cache.put(key_pair_id, key.clone());
+                        cache.put(key_pair_id, key.clone(), epoch);
+
+                        Ok(Some(key))
+                    }
+                    None => Ok(None),
+                }),
+        )
+    }
+
+    fn get_or_create_ephemeral_keys(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<KeyPair> {
+        let cache_key = (key_pair_id, epoch);
+        let mut cache = self
+            .inner
+            .get_or_create_ephemeral_keys_cache
+            .write()
+            .unwrap();
+        if let Some(keys) = cache.get(&cache_key) {
+            return Box::new(future::ok(keys));
+        }
+        drop(cache);
+
+        // No entry in cache, fetch from key manager.
+        let inner = self.inner.clone();
+        Box::new(
+            self.inner
+                .rpc_client
+                .get_or_create_ephemeral_keys(
+                    ctx,
+                    EphemeralKeyRequest::new(inner.runtime_id, key_pair_id, epoch),
+                )
+                .and_then(move |keys| {
+                    // Ephemeral keys are cached until their epoch is invalidated.
+                    let mut cache = inner.get_or_create_ephemeral_keys_cache.write().unwrap();
+                    cache.put(cache_key, keys.clone(), epoch);
+
+                    Ok(keys)
+                }),
+        )
+    }
+
+    fn get_public_ephemeral_key(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<Option<SignedPublicEphemeralKey>> {
+        let cache_key = (key_pair_id, epoch);
+        let mut cache = self.inner.get_public_ephemeral_key_cache.write().unwrap();
+        if let Some(key) = cache.get(&cache_key) {
+            return Box::new(future::ok(Some(key)));
+        }
+        drop(cache);
+
+        // No entry in cache, fetch from key manager.
+        let inner = self.inner.clone();
+        Box::new(
+            self.inner
+                .rpc_client
+                .get_public_ephemeral_key(
+                    ctx,
+                    EphemeralKeyRequest::new(inner.runtime_id, key_pair_id, epoch),
+                )
+                .and_then(move |key| match key {
+                    Some(key) => {
+                        let mut cache = inner.get_public_ephemeral_key_cache.write().unwrap();
+                        cache.put(cache_key, key.clone(), epoch);
 
                         Ok(Some(key))
                     }
@@ -207,11 +392,15 @@ impl KeyManagerClient for RemoteClient {
         )
     }
 
-    fn replicate_master_secret(&self, ctx: Context) -> BoxFuture<Option<MasterSecret>> {
+    fn replicate_master_secret(
+        &self,
+        ctx: Context,
+        generation: Generation,
+    ) -> BoxFuture<Option<MasterSecret>> {
         Box::new(
             self.inner
                 .rpc_client
-                .replicate_master_secret(ctx, ReplicateRequest {})
+                .replicate_master_secret(ctx, ReplicateRequest { generation })
                 .and_then(move |rsp| Ok(Some(rsp.master_secret))),
         )
     }
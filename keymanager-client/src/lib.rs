@@ -1,5 +1,6 @@
 //! Key manager client.
 
+pub mod cache;
 pub mod client;
 pub mod mock;
 
@@ -16,6 +17,12 @@ pub trait KeyManagerClient: Send + Sync {
     /// This will make the client re-fetch the keys from the key manager.
     fn clear_cache(&self);
 
+    /// Invalidate locally cached keys fetched in the given or earlier epochs.
+    ///
+    /// This should be called on epoch transitions (e.g., when the key manager
+    /// policy may have changed) so that keys are re-fetched when needed.
+    fn invalidate_epoch(&self, epoch: EpochTime);
+
     /// Get or create named key pair.
     ///
     /// If the key does not yet exist, the key manager will generate one. If
@@ -23,6 +30,18 @@ pub trait KeyManagerClient: Send + Sync {
     /// cache.
     fn get_or_create_keys(&self, ctx: Context, key_pair_id: KeyPairId) -> BoxFuture<KeyPair>;
 
+    /// Get or create named key pair derived from the given master secret
+    /// generation.
+    ///
+    /// Keys derived from older generations remain available after the master
+    /// secret has been rotated, so existing state can still be decrypted.
+    fn get_or_create_keys_for_generation(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        generation: Generation,
+    ) -> BoxFuture<KeyPair>;
+
     /// Get public key for a key pair id.
     fn get_public_key(
         &self,
@@ -30,8 +49,32 @@ pub trait KeyManagerClient: Send + Sync {
         key_pair_id: KeyPairId,
     ) -> BoxFuture<Option<SignedPublicKey>>;
 
-    /// Get a copy of the master secret for replication.
-    fn replicate_master_secret(&self, ctx: Context) -> BoxFuture<Option<MasterSecret>>;
+    /// Get or create named ephemeral key pair for the given epoch.
+    ///
+    /// Ephemeral keys of past epochs eventually expire in the key manager
+    /// and can no longer be obtained. Locally cached ephemeral keys are
+    /// evicted by `invalidate_epoch`.
+    fn get_or_create_ephemeral_keys(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<KeyPair>;
+
+    /// Get ephemeral public key for a key pair id and epoch.
+    fn get_public_ephemeral_key(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<Option<SignedPublicEphemeralKey>>;
+
+    /// Get a copy of the given master secret generation for replication.
+    fn replicate_master_secret(
+        &self,
+        ctx: Context,
+        generation: Generation,
+    ) -> BoxFuture<Option<MasterSecret>>;
 }
 
 impl<T: ?Sized + KeyManagerClient> KeyManagerClient for Arc<T> {
@@ -39,10 +82,23 @@ impl<T: ?Sized + KeyManagerClient> KeyManagerClient for Arc<T> {
         KeyManagerClient::clear_cache(&**self)
     }
 
+    fn invalidate_epoch(&self, epoch: EpochTime) {
+        KeyManagerClient::invalidate_epoch(&**self, epoch)
+    }
+
     fn get_or_create_keys(&self, ctx: Context, key_pair_id: KeyPairId) -> BoxFuture<KeyPair> {
         KeyManagerClient::get_or_create_keys(&**self, ctx, key_pair_id)
     }
 
+    fn get_or_create_keys_for_generation(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        generation: Generation,
+    ) -> BoxFuture<KeyPair> {
+        KeyManagerClient::get_or_create_keys_for_generation(&**self, ctx, key_pair_id, generation)
+    }
+
     fn get_public_key(
         &self,
         ctx: Context,
@@ -51,8 +107,30 @@ impl<T: ?Sized + KeyManagerClient> KeyManagerClient for Arc<T> {
         KeyManagerClient::get_public_key(&**self, ctx, key_pair_id)
     }
 
-    fn replicate_master_secret(&self, ctx: Context) -> BoxFuture<Option<MasterSecret>> {
-        KeyManagerClient::replicate_master_secret(&**self, ctx)
+    fn get_or_create_ephemeral_keys(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<KeyPair> {
+        KeyManagerClient::get_or_create_ephemeral_keys(&**self, ctx, key_pair_id, epoch)
+    }
+
+    fn get_public_ephemeral_key(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<Option<SignedPublicEphemeralKey>> {
+        KeyManagerClient::get_public_ephemeral_key(&**self, ctx, key_pair_id, epoch)
+    }
+
+    fn replicate_master_secret(
+        &self,
+        ctx: Context,
+        generation: Generation,
+    ) -> BoxFuture<Option<MasterSecret>> {
+        KeyManagerClient::replicate_master_secret(&**self, ctx, generation)
     }
 }
 
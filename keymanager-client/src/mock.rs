@@ -11,7 +11,8 @@ use super::KeyManagerClient;
 
 /// Mock key manager client which stores everything locally.
 pub struct MockClient {
-    keys: Mutex<HashMap<KeyPairId, KeyPair>>,
+    keys: Mutex<HashMap<(KeyPairId, Generation), KeyPair>>,
+    ephemeral_keys: Mutex<HashMap<(KeyPairId, EpochTime), KeyPair>>,
 }
 
 impl MockClient {
@@ -19,6 +20,7 @@ impl MockClient {
     pub fn new() -> Self {
         Self {
             keys: Mutex::new(HashMap::new()),
+            ephemeral_keys: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -26,13 +28,24 @@ impl MockClient {
 impl KeyManagerClient for MockClient {
     fn clear_cache(&self) {}
 
-    fn get_or_create_keys(&self, _ctx: Context, key_pair_id: KeyPairId) -> BoxFuture<KeyPair> {
+    fn invalidate_epoch(&self, _epoch: EpochTime) {}
+
+    fn get_or_create_keys(&self, ctx: Context, key_pair_id: KeyPairId) -> BoxFuture<KeyPair> {
+        self.get_or_create_keys_for_generation(ctx, key_pair_id, 0)
+    }
+
+    fn get_or_create_keys_for_generation(
+        &self,
+        _ctx: Context,
+        key_pair_id: KeyPairId,
+        generation: Generation,
+    ) -> BoxFuture<KeyPair> {
         let mut keys = self.keys.lock().unwrap();
-        let key = match keys.get(&key_pair_id) {
+        let key = match keys.get(&(key_pair_id, generation)) {
             Some(key) => key.clone(),
             None => {
                 let key = KeyPair::generate_mock();
-                keys.insert(key_pair_id, key.clone());
+                keys.insert((key_pair_id, generation), key.clone());
                 key
             }
         };
@@ -54,7 +67,45 @@ impl KeyManagerClient for MockClient {
         }))
     }
 
-    fn replicate_master_secret(&self, _ctx: Context) -> BoxFuture<Option<MasterSecret>> {
+    fn get_or_create_ephemeral_keys(
+        &self,
+        _ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<KeyPair> {
+        let mut keys = self.ephemeral_keys.lock().unwrap();
+        let key = keys
+            .entry((key_pair_id, epoch))
+            .or_insert_with(KeyPair::generate_mock)
+            .clone();
+
+        Box::new(future::ok(key))
+    }
+
+    fn get_public_ephemeral_key(
+        &self,
+        ctx: Context,
+        key_pair_id: KeyPairId,
+        epoch: EpochTime,
+    ) -> BoxFuture<Option<SignedPublicEphemeralKey>> {
+        Box::new(
+            self.get_or_create_ephemeral_keys(ctx, key_pair_id, epoch)
+                .map(move |ck| {
+                    Some(SignedPublicEphemeralKey {
+                        key: ck.input_keypair.get_pk(),
+                        epoch,
+                        checksum: vec![],
+                        signature: Signature::default(),
+                    })
+                }),
+        )
+    }
+
+    fn replicate_master_secret(
+        &self,
+        _ctx: Context,
+        _generation: Generation,
+    ) -> BoxFuture<Option<MasterSecret>> {
         unimplemented!();
     }
 }
@@ -1,9 +1,28 @@
 //! Key manager enclave context.
 use std::sync::Arc;
 
-use oasis_core_runtime::{common::runtime::RuntimeId, Protocol};
+use oasis_core_keymanager_client::RemoteClient;
+use oasis_core_runtime::{
+    common::runtime::RuntimeId, enclave_rpc::Context as RpcContext, runtime_context, Protocol,
+};
+
+use crate::policy::Policy;
 
 pub struct Context {
     pub runtime_id: RuntimeId,
     pub protocol: Arc<Protocol>,
 }
+
+/// Create a client for talking to the other key manager enclaves that this
+/// enclave may replicate from.
+pub(crate) fn new_replication_client(ctx: &mut RpcContext) -> RemoteClient {
+    let rctx = runtime_context!(ctx, Context);
+
+    RemoteClient::new_runtime_with_enclave_identities(
+        rctx.runtime_id,
+        Policy::global().may_replicate_from(),
+        rctx.protocol.clone(),
+        ctx.rak.clone(),
+        1, // Not used, doesn't matter.
+    )
+}
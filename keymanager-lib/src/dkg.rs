@@ -0,0 +1,290 @@
+//! Joint master secret generation by a committee of key manager enclaves.
+//!
+//! Instead of a single enclave generating a new master secret, every member
+//! of a committee deals a random secret using Pedersen verifiable secret
+//! sharing, and the master secret is derived from the sum of all dealt
+//! secrets. Shares are only exchanged between key manager enclaves allowed
+//! by the replication policy, so the master secret is only reconstructed
+//! inside attested enclaves and no single dealer ever knows it.
+//!
+//! The process is driven by the node, calling the following on every
+//! committee member:
+//!
+//! 1. `deal`, and publishing the returned commitments to the committee.
+//! 2. `combine`, with the published commitments of all members. Each member
+//!    fetches the shares dealt to it and verifies them.
+//! 3. `reconstruct`, which fetches the shares of the joint secret held by
+//!    other members, reconstructs the master secret and proposes it as a new
+//!    generation, to be activated with `Kdf::activate_master_secret`.
+//!
+//! The host is expected to route each share request to the committee member
+//! with the requested index, other members reject it.
+use std::sync::RwLock;
+
+use anyhow::Result;
+use io_context::Context as IoContext;
+use lazy_static::lazy_static;
+
+use oasis_core_keymanager_api_common::{
+    CombineRequest, CombineResponse, DealRequest, DealResponse, DealtShareRequest, Generation,
+    GenerationResponse, JointShareRequest, KeyManagerError, MasterSecret, ReconstructRequest,
+};
+use oasis_core_runtime::{
+    common::crypto::{
+        kdf::kmac256,
+        vss::{self, Commitments, Index, Share, VssError},
+    },
+    enclave_rpc::Context as RpcContext,
+    executor::Executor,
+    runtime_context, BUILD_INFO,
+};
+
+use crate::{
+    context::{new_replication_client, Context as KmContext},
+    kdf::Kdf,
+};
+
+lazy_static! {
+    // Global DKG object.
+    static ref DKG: Dkg = Dkg::new();
+
+    static ref JOINT_MASTER_SECRET_CUSTOM: &'static [u8] = {
+        match BUILD_INFO.is_secure {
+            true => b"ekiden-derive-joint-master-secret",
+            false => b"ekiden-derive-joint-master-secret-insecure",
+        }
+    };
+}
+
+/// State of a joint master secret generation.
+struct Round {
+    generation: Generation,
+    /// Index of this enclave in the committee.
+    index: Index,
+    threshold: u32,
+    participants: u32,
+    /// Commitments to the secret dealt by this enclave.
+    commitments: Commitments,
+    /// Shares of the secret dealt by this enclave, ordered by index.
+    shares: Vec<Share>,
+    /// Commitments to the joint secret and the share of this enclave.
+    joint: Option<(Commitments, Share)>,
+}
+
+struct Inner {
+    round: Option<Round>,
+}
+
+impl Inner {
+    fn get_round(&self, generation: Generation) -> Result<&Round> {
+        match self.round {
+            Some(ref round) if round.generation == generation => Ok(round),
+            _ => Err(KeyManagerError::SharingNotInProgress(generation).into()),
+        }
+    }
+}
+
+/// Distributed key generation, which jointly generates master secrets.
+pub struct Dkg {
+    inner: RwLock<Inner>,
+}
+
+impl Dkg {
+    fn new() -> Self {
+        Self {
+            inner: RwLock::new(Inner { round: None }),
+        }
+    }
+
+    /// Global DKG instance.
+    pub fn global<'a>() -> &'a Dkg {
+        &DKG
+    }
+
+    /// Deal a random secret to the committee generating the master secret
+    /// of the given generation.
+    ///
+    /// This replaces any generation in progress. Dealing again with the same
+    /// parameters returns the existing commitments.
+    pub fn deal(&self, req: &DealRequest) -> Result<DealResponse> {
+        if req.index == 0 || req.index > req.participants {
+            return Err(VssError::InvalidIndex(req.index).into());
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        if let Some(ref round) = inner.round {
+            if round.generation == req.generation
+                && round.index == req.index
+                && round.threshold == req.threshold
+                && round.participants == req.participants
+            {
+                return Ok(DealResponse {
+                    generation: req.generation,
+                    commitments: round.commitments.clone(),
+                });
+            }
+        }
+
+        let (commitments, shares) = vss::deal(req.threshold, req.participants)?;
+        inner.round = Some(Round {
+            generation: req.generation,
+            index: req.index,
+            threshold: req.threshold,
+            participants: req.participants,
+            commitments: commitments.clone(),
+            shares,
+            joint: None,
+        });
+
+        Ok(DealResponse {
+            generation: req.generation,
+            commitments,
+        })
+    }
+
+    /// Fetch and verify the shares dealt to this enclave by all committee
+    /// members, and combine them into its share of the joint secret.
+    pub fn combine(&self, req: &CombineRequest, ctx: &mut RpcContext) -> Result<CombineResponse> {
+        // Copy what is needed, so that the state is not locked while
+        // fetching shares from other enclaves.
+        let inner = self.inner.read().unwrap();
+        let round = inner.get_round(req.generation)?;
+        let index = round.index;
+        let own_share = round.shares[index as usize - 1].clone();
+        if req.commitments.len() != round.participants as usize
+            || req.commitments.get(&index) != Some(&round.commitments)
+        {
+            return Err(VssError::MismatchedCommitments.into());
+        }
+        drop(inner);
+
+        let km_client = new_replication_client(ctx);
+        let mut dealings = Vec::with_capacity(req.commitments.len());
+        for (&dealer, commitments) in &req.commitments {
+            let share = if dealer == index {
+                own_share.clone()
+            } else {
+                let result = km_client.get_dealt_share(
+                    IoContext::create_child(&ctx.io_ctx),
+                    DealtShareRequest {
+                        generation: req.generation,
+                        dealer,
+                        index,
+                    },
+                );
+                Executor::with_current(|executor| executor.block_on(result))?
+            };
+            if share.index != index {
+                return Err(VssError::InvalidIndex(share.index).into());
+            }
+            dealings.push((commitments.clone(), share));
+        }
+        let (commitments, share) = vss::combine(&dealings)?;
+
+        let mut inner = self.inner.write().unwrap();
+        match inner.round {
+            Some(ref mut round) if round.generation == req.generation => {
+                round.joint = Some((commitments.clone(), share));
+            }
+            _ => return Err(KeyManagerError::SharingNotInProgress(req.generation).into()),
+        }
+
+        Ok(CombineResponse {
+            generation: req.generation,
+            commitments,
+        })
+    }
+
+    /// Reconstruct the joint secret from the shares of enough committee
+    /// members and propose the master secret derived from it.
+    pub fn reconstruct(
+        &self,
+        req: &ReconstructRequest,
+        ctx: &mut RpcContext,
+    ) -> Result<GenerationResponse> {
+        let inner = self.inner.read().unwrap();
+        let round = inner.get_round(req.generation)?;
+        let (commitments, own_share) = match round.joint {
+            Some((ref commitments, ref share)) => (commitments.clone(), share.clone()),
+            None => return Err(KeyManagerError::SharingNotInProgress(req.generation).into()),
+        };
+        let (index, threshold, participants) = (round.index, round.threshold, round.participants);
+        drop(inner);
+
+        // Fetch shares until there are enough of them, skipping members
+        // which are unavailable or return invalid shares.
+        let km_client = new_replication_client(ctx);
+        let mut shares = vec![own_share];
+        for other in (1..=participants).filter(|&other| other != index) {
+            if shares.len() >= threshold as usize {
+                break;
+            }
+
+            let result = km_client.get_joint_share(
+                IoContext::create_child(&ctx.io_ctx),
+                JointShareRequest {
+                    generation: req.generation,
+                    index: other,
+                },
+            );
+            let share = match Executor::with_current(|executor| executor.block_on(result)) {
+                Ok(share) if share.index == other => share,
+                _ => continue,
+            };
+            if commitments.verify(&share).is_ok() {
+                shares.push(share);
+            }
+        }
+        let secret = vss::reconstruct(&shares, threshold as usize)?;
+
+        // KMAC256(secret, kmRuntimeID || generation, 32, "ekiden-derive-joint-master-secret")
+        let rctx = runtime_context!(ctx, KmContext);
+        let master_secret: MasterSecret = kmac256::<32>(
+            secret.as_ref(),
+            &JOINT_MASTER_SECRET_CUSTOM,
+            &[rctx.runtime_id.as_ref(), &req.generation.to_be_bytes()],
+        );
+
+        Kdf::global().propose_joint_master_secret(req.generation, master_secret)
+    }
+
+    /// Get the share of a committee member dealt by this enclave.
+    pub fn get_dealt_share(&self, req: &DealtShareRequest) -> Result<Share> {
+        let inner = self.inner.read().unwrap();
+        let round = inner.get_round(req.generation)?;
+        if req.dealer != round.index {
+            return Err(KeyManagerError::WrongParticipant {
+                expected: round.index,
+                actual: req.dealer,
+            }
+            .into());
+        }
+
+        match req
+            .index
+            .checked_sub(1)
+            .and_then(|i| round.shares.get(i as usize))
+        {
+            Some(share) => Ok(share.clone()),
+            None => Err(VssError::InvalidIndex(req.index).into()),
+        }
+    }
+
+    /// Get the share of the joint secret held by this enclave.
+    pub fn get_joint_share(&self, req: &JointShareRequest) -> Result<Share> {
+        let inner = self.inner.read().unwrap();
+        let round = inner.get_round(req.generation)?;
+        if req.index != round.index {
+            return Err(KeyManagerError::WrongParticipant {
+                expected: round.index,
+                actual: req.index,
+            }
+            .into());
+        }
+
+        match round.joint {
+            Some((_, ref share)) => Ok(share.clone()),
+            None => Err(KeyManagerError::SharingNotInProgress(req.generation).into()),
+        }
+    }
+}
@@ -1,31 +1,36 @@
 ///! Key Derivation Function.
-use std::sync::{Arc, RwLock};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+};
 
 use anyhow::Result;
 use io_context::Context as IoContext;
 use lazy_static::lazy_static;
 use lru::LruCache;
-use rand::{rngs::OsRng, Rng};
 use sgx_isa::Keypolicy;
-use sp800_185::{CShake, KMac};
 use x25519_dalek;
 use zeroize::Zeroize;
 
 use oasis_core_keymanager_api_common::{
-    InitRequest, InitResponse, KeyManagerError, KeyPair, MasterSecret, PrivateKey, PublicKey,
-    ReplicateResponse, RequestIds, SignedInitResponse, SignedPublicKey, StateKey,
-    INIT_RESPONSE_CONTEXT, PUBLIC_KEY_CONTEXT,
+    ActivateRequest, EphemeralKeyRequest, EpochTime, Generation, GenerationResponse, InitRequest,
+    InitResponse, KeyManagerError, KeyPair, MasterSecret, PrivateKey, ProposeRequest, PublicKey,
+    ReplicateRequest, ReplicateResponse, RequestIds, SignedInitResponse, SignedPublicEphemeralKey,
+    SignedPublicKey, StateKey, EPHEMERAL_PUBLIC_KEY_CONTEXT, INIT_RESPONSE_CONTEXT,
+    PUBLIC_KEY_CONTEXT,
 };
-use oasis_core_keymanager_client::{KeyManagerClient, RemoteClient};
+use oasis_core_keymanager_client::KeyManagerClient;
 use oasis_core_runtime::{
     common::{
         cbor,
         crypto::{
+            kdf::{cshake256, kmac256},
             mrae::deoxysii::{DeoxysII, NONCE_SIZE, TAG_SIZE},
+            secret::Secret,
             signature,
         },
         runtime::RuntimeId,
-        sgx::egetkey::egetkey,
+        sgx::{egetkey::egetkey, seal},
     },
     enclave_rpc::Context as RpcContext,
     executor::Executor,
@@ -34,7 +39,10 @@ use oasis_core_runtime::{
     BUILD_INFO,
 };
 
-use crate::{context::Context as KmContext, policy::Policy};
+use crate::{
+    context::{new_replication_client, Context as KmContext},
+    policy::Policy,
+};
 
 lazy_static! {
     // Global KDF object.
@@ -54,6 +62,13 @@ lazy_static! {
         }
     };
 
+    static ref EPHEMERAL_KDF_CUSTOM: &'static [u8] = {
+        match BUILD_INFO.is_secure {
+            true => b"ekiden-derive-ephemeral-secret",
+            false => b"ekiden-derive-ephemeral-secret-insecure",
+        }
+    };
+
     static ref RUNTIME_XOF_CUSTOM: &'static [u8] = {
         match BUILD_INFO.is_secure {
             true => b"ekiden-derive-contract-keys",
@@ -76,74 +91,181 @@ const INSECURE_SIGNING_KEY_SEED: &str = "ekiden test key manager RAK seed";
 const MASTER_SECRET_STORAGE_KEY: &'static [u8] = b"keymanager_master_secret";
 const MASTER_SECRET_STORAGE_SIZE: usize = 32 + TAG_SIZE + NONCE_SIZE;
 const MASTER_SECRET_SEAL_CONTEXT: &'static [u8] = b"Ekiden Keymanager Seal master secret v0";
+const ACTIVE_GENERATIONS_STORAGE_KEY: &'static [u8] = b"keymanager_active_generations";
+const ACTIVE_GENERATIONS_SEAL_CONTEXT: &'static [u8] =
+    b"oasis-core/keymanager: active master secret generations";
+
+/// Number of epochs preceding the current consensus epoch for which
+/// ephemeral keys can still be obtained.
+const EPHEMERAL_KEY_EPOCHS: EpochTime = 2;
 
 /// Kdf, which derives key manager keys from a master secret.
+///
+/// The master secret can be rotated by proposing a new generation, which is
+/// then replicated to other key manager instances and activated once the
+/// network has agreed on its checksum. Keys derived from older generations
+/// remain available, so state encrypted with them can still be decrypted.
 pub struct Kdf {
     inner: RwLock<Inner>,
 }
 
+/// An active master secret generation (other than the initial one).
+struct ActiveGeneration {
+    master_secret: MasterSecret,
+    checksum: Vec<u8>,
+}
+
 struct Inner {
     /// Master secret.
     master_secret: Option<MasterSecret>,
     checksum: Option<Vec<u8>>,
+    /// Active master secret generations following the initial one.
+    generations: BTreeMap<Generation, ActiveGeneration>,
+    /// Proposed master secret generation, not yet active.
+    proposal: Option<(Generation, MasterSecret)>,
     runtime_id: Option<RuntimeId>,
     signer: Option<Arc<dyn signature::Signer>>,
     cache: LruCache<Vec<u8>, KeyPair>,
+    /// Random secrets of the ephemeral keys by runtime and epoch, only kept
+    /// in memory.
+    ephemeral_secrets: HashMap<RuntimeId, BTreeMap<EpochTime, Secret<32>>>,
+    /// Consensus epoch as of which expired ephemeral secrets have been purged.
+    ephemeral_epoch: Option<EpochTime>,
 }
 
 impl Inner {
     fn reset(&mut self) {
         self.master_secret = None;
         self.checksum = None;
+        self.generations.clear();
+        self.proposal = None;
         self.runtime_id = None;
         self.signer = None;
         self.cache.clear();
+        self.ephemeral_secrets.clear();
+        self.ephemeral_epoch = None;
     }
 
     fn derive_contract_key(&self, req: &RequestIds) -> Result<KeyPair> {
-        let checksum = self.get_checksum()?;
-        let mut contract_secret = self.derive_contract_secret(req)?;
-
-        // Note: The `name` parameter for cSHAKE is reserved for use by NIST.
-        let mut xof = CShake::new_cshake256(&vec![], &RUNTIME_XOF_CUSTOM);
-        xof.update(&contract_secret);
-        contract_secret.zeroize();
-        let mut xof = xof.xof();
+        let contract_secret = self.derive_contract_secret(req)?;
+        self.derive_keys(
+            &contract_secret,
+            self.get_generation_checksum(req.generation)?,
+        )
+    }
 
-        // State (storage) key.
-        let mut k = [0u8; 32];
-        xof.squeeze(&mut k);
-        let state_key = StateKey::from(k.to_vec());
+    fn derive_keys(&self, secret: &Secret<32>, checksum: Vec<u8>) -> Result<KeyPair> {
+        // The first half of the output is the state (storage) key, the second
+        // half is the private key.
+        let keys: Secret<64> = cshake256(&RUNTIME_XOF_CUSTOM, &[secret.as_ref()]);
+        let state_key = StateKey::from_slice(&keys.as_ref()[..32])?;
 
         // Public/private keypair.
-        xof.squeeze(&mut k);
+        let mut k = [0u8; 32];
+        k.copy_from_slice(&keys.as_ref()[32..]);
         let sk = x25519_dalek::StaticSecret::from(k);
         k.zeroize();
         let pk = x25519_dalek::PublicKey::from(&sk);
 
         Ok(KeyPair::new(
             PublicKey(*pk.as_bytes()),
-            PrivateKey(sk.to_bytes()),
+            PrivateKey::new(sk.to_bytes()),
             state_key,
             checksum,
         ))
     }
 
-    fn derive_contract_secret(&self, req: &RequestIds) -> Result<Vec<u8>> {
-        let master_secret = match self.master_secret.as_ref() {
-            Some(master_secret) => master_secret,
-            None => return Err(KeyManagerError::NotInitialized.into()),
-        };
-
-        let mut k = [0u8; 32];
+    fn derive_contract_secret(&self, req: &RequestIds) -> Result<Secret<32>> {
+        let master_secret = self.get_master_secret(req.generation)?;
 
         // KMAC256(master_secret, runtimeID || contractID, 32, "ekiden-derive-runtime-secret")
-        let mut f = KMac::new_kmac256(master_secret.as_ref(), &RUNTIME_KDF_CUSTOM);
-        f.update(req.runtime_id.as_ref());
-        f.update(req.key_pair_id.as_ref());
-        f.finalize(&mut k);
+        Ok(kmac256(
+            master_secret.as_ref(),
+            &RUNTIME_KDF_CUSTOM,
+            &[req.runtime_id.as_ref(), req.key_pair_id.as_ref()],
+        ))
+    }
+
+    /// Purge the ephemeral secrets of all runtimes which have expired as of
+    /// the given consensus epoch.
+    fn advance_ephemeral_epoch(&mut self, epoch: EpochTime) {
+        if self.ephemeral_epoch.map_or(false, |latest| latest >= epoch) {
+            return;
+        }
+        self.ephemeral_epoch = Some(epoch);
+        for secrets in self.ephemeral_secrets.values_mut() {
+            *secrets = secrets.split_off(&epoch.saturating_sub(EPHEMERAL_KEY_EPOCHS));
+        }
+        self.ephemeral_secrets
+            .retain(|_, secrets| !secrets.is_empty());
+    }
 
-        Ok(k.to_vec())
+    fn get_ephemeral_keys(
+        &mut self,
+        req: &EphemeralKeyRequest,
+        current_epoch: EpochTime,
+    ) -> Result<KeyPair> {
+        let checksum = self.get_checksum()?;
+        self.advance_ephemeral_epoch(current_epoch);
+        if req.epoch > current_epoch {
+            return Err(KeyManagerError::EphemeralKeyNotAvailable(req.epoch).into());
+        }
+        if req.epoch.saturating_add(EPHEMERAL_KEY_EPOCHS) < current_epoch {
+            return Err(KeyManagerError::EphemeralKeyExpired(req.epoch).into());
+        }
+
+        // Secrets are only generated for the current epoch, so the secrets of
+        // past epochs are gone for good once purged or after a restart.
+        let secrets = self.ephemeral_secrets.entry(req.runtime_id).or_default();
+        if !secrets.contains_key(&req.epoch) {
+            if req.epoch != current_epoch {
+                return Err(KeyManagerError::EphemeralKeyExpired(req.epoch).into());
+            }
+            secrets.insert(req.epoch, Secret::generate());
+        }
+        let secret = &secrets[&req.epoch];
+
+        // KMAC256(epoch_secret, runtimeID || keyPairID || epoch, 32, "ekiden-derive-ephemeral-secret")
+        let ephemeral_secret = kmac256(
+            secret.as_ref(),
+            &EPHEMERAL_KDF_CUSTOM,
+            &[
+                req.runtime_id.as_ref(),
+                req.key_pair_id.as_ref(),
+                &req.epoch.to_be_bytes(),
+            ],
+        );
+        self.derive_keys(&ephemeral_secret, checksum)
+    }
+
+    /// Master secret of the given active generation.
+    fn get_master_secret(&self, generation: Generation) -> Result<&MasterSecret> {
+        if generation == 0 {
+            return self
+                .master_secret
+                .as_ref()
+                .ok_or_else(|| KeyManagerError::NotInitialized.into());
+        }
+        match self.generations.get(&generation) {
+            Some(active) => Ok(&active.master_secret),
+            None => Err(KeyManagerError::GenerationNotActive(generation).into()),
+        }
+    }
+
+    /// Checksum of the master secret of the given active generation.
+    fn get_generation_checksum(&self, generation: Generation) -> Result<Vec<u8>> {
+        if generation == 0 {
+            return self.get_checksum();
+        }
+        match self.generations.get(&generation) {
+            Some(active) => Ok(active.checksum.clone()),
+            None => Err(KeyManagerError::GenerationNotActive(generation).into()),
+        }
+    }
+
+    /// Latest active master secret generation.
+    fn latest_generation(&self) -> Generation {
+        self.generations.keys().next_back().cloned().unwrap_or(0)
     }
 
     fn get_checksum(&self) -> Result<Vec<u8>> {
@@ -160,9 +282,13 @@ impl Kdf {
             inner: RwLock::new(Inner {
                 master_secret: None,
                 checksum: None,
+                generations: BTreeMap::new(),
+                proposal: None,
                 runtime_id: None,
                 signer: None,
                 cache: LruCache::new(1024),
+                ephemeral_secrets: HashMap::new(),
+                ephemeral_epoch: None,
             }),
         }
     }
@@ -218,31 +344,16 @@ impl Kdf {
             // once.
 
             // Attempt to load the master secret.
-            let (master_secret, did_replicate) = match Self::load_master_secret(&km_runtime_id) {
+            let (master_secret, did_replicate) = match Self::load_master_secret(&km_runtime_id, 0) {
                 Some(master_secret) => (master_secret, false),
                 None => {
                     // Couldn't load, fetch the master secret from another
                     // enclave instance.
-
-                    let rctx = runtime_context!(ctx, KmContext);
-
-                    let km_client = RemoteClient::new_runtime_with_enclave_identities(
-                        rctx.runtime_id,
-                        Policy::global().may_replicate_from(),
-                        rctx.protocol.clone(),
-                        ctx.rak.clone(),
-                        1, // Not used, doesn't matter.
-                    );
-
-                    let result =
-                        km_client.replicate_master_secret(IoContext::create_child(&ctx.io_ctx));
-                    let master_secret =
-                        Executor::with_current(|executor| executor.block_on(result))?;
-                    (master_secret.unwrap(), true)
+                    (Self::replicate_from_remote(ctx, 0)?, true)
                 }
             };
 
-            let checksum = Self::checksum_master_secret(&master_secret, &km_runtime_id);
+            let checksum = Self::checksum_master_secret(&master_secret, &km_runtime_id, 0);
             if req.checksum != checksum {
                 // We either loaded or replicated something that does
                 // not match the rest of the world.
@@ -253,7 +364,7 @@ impl Kdf {
             // The loaded/replicated master secret is consistent with the rest
             // of the world.   Ok to proceed.
             if did_replicate {
-                Self::save_master_secret(&master_secret, &km_runtime_id);
+                Self::save_master_secret(&master_secret, &km_runtime_id, 0);
             }
             inner.master_secret = Some(master_secret);
             inner.checksum = Some(checksum);
@@ -264,7 +375,7 @@ impl Kdf {
 
             // Attempt to load the master secret, the caller may just be
             // behind the rest of the world.
-            let master_secret = match Self::load_master_secret(&km_runtime_id) {
+            let master_secret = match Self::load_master_secret(&km_runtime_id, 0) {
                 Some(master_secret) => master_secret,
                 None => {
                     // Unable to load, perhaps we can generate?
@@ -272,17 +383,42 @@ impl Kdf {
                         return Err(KeyManagerError::ReplicationRequired.into());
                     }
 
-                    Self::generate_master_secret(&km_runtime_id)
+                    Self::generate_master_secret(&km_runtime_id, 0)
                 }
             };
 
             // Loaded or generated a master secret.  There is no checksum to
             // compare against, but that is expected when bootstrapping or
             // lagging.
-            inner.checksum = Some(Self::checksum_master_secret(&master_secret, &km_runtime_id));
+            inner.checksum = Some(Self::checksum_master_secret(
+                &master_secret,
+                &km_runtime_id,
+                0,
+            ));
             inner.master_secret = Some(master_secret);
         }
 
+        // Restore the master secret generations activated before a restart.
+        if inner.generations.is_empty() {
+            for generation in Self::load_active_generations(&km_runtime_id) {
+                // Generations whose master secret is missing must be
+                // activated again (replicating it from another instance).
+                let master_secret = match Self::load_master_secret(&km_runtime_id, generation) {
+                    Some(master_secret) => master_secret,
+                    None => continue,
+                };
+                let checksum =
+                    Self::checksum_master_secret(&master_secret, &km_runtime_id, generation);
+                inner.generations.insert(
+                    generation,
+                    ActiveGeneration {
+                        master_secret,
+                        checksum,
+                    },
+                );
+            }
+        }
+
         // If we make it this far, we have a master secret and checksum
         // that either matches the global state, will become the global
         // state, or should become the global state (rare).
@@ -349,11 +485,18 @@ impl Kdf {
     }
 
     /// Signs the public key using the key manager key.
-    pub fn sign_public_key(&self, key: PublicKey) -> Result<SignedPublicKey> {
+    ///
+    /// The signature binds the key to the checksum of the master secret
+    /// generation it has been derived from.
+    pub fn sign_public_key(
+        &self,
+        key: PublicKey,
+        generation: Generation,
+    ) -> Result<SignedPublicKey> {
         let mut body = key.as_ref().to_vec();
 
         let inner = self.inner.read().unwrap();
-        let checksum = inner.get_checksum()?;
+        let checksum = inner.get_generation_checksum(generation)?;
         body.extend_from_slice(&checksum);
 
         let signer = match inner.signer.as_ref() {
@@ -369,30 +512,295 @@ impl Kdf {
         })
     }
 
+    /// Get or create ephemeral keys of the given epoch.
+    ///
+    /// Ephemeral keys are derived from a random secret of the runtime and
+    /// epoch, which is never persisted nor replicated, so the keys are
+    /// forward-secret and specific to this key manager instance. Keys can
+    /// only be obtained for the current consensus epoch and the
+    /// `EPHEMERAL_KEY_EPOCHS` epochs preceding it, and the secrets of older
+    /// epochs are forgotten.
+    pub fn get_or_create_ephemeral_keys(
+        &self,
+        req: &EphemeralKeyRequest,
+        current_epoch: EpochTime,
+    ) -> Result<KeyPair> {
+        let mut inner = self.inner.write().unwrap();
+        inner.get_ephemeral_keys(req, current_epoch)
+    }
+
+    /// Get the public part of the ephemeral key.
+    pub fn get_public_ephemeral_key(
+        &self,
+        req: &EphemeralKeyRequest,
+        current_epoch: EpochTime,
+    ) -> Result<Option<PublicKey>> {
+        let mut inner = self.inner.write().unwrap();
+        let keys = inner.get_ephemeral_keys(req, current_epoch)?;
+        Ok(Some(keys.input_keypair.get_pk()))
+    }
+
+    /// Signs the ephemeral public key using the key manager key.
+    pub fn sign_public_ephemeral_key(
+        &self,
+        key: PublicKey,
+        epoch: EpochTime,
+    ) -> Result<SignedPublicEphemeralKey> {
+        let mut body = key.as_ref().to_vec();
+
+        let inner = self.inner.read().unwrap();
+        let checksum = inner.get_checksum()?;
+        body.extend_from_slice(&checksum);
+        body.extend_from_slice(&epoch.to_be_bytes());
+
+        let signer = match inner.signer.as_ref() {
+            Some(rak) => rak,
+            None => return Err(KeyManagerError::NotInitialized.into()),
+        };
+        let signature = signer.sign(&EPHEMERAL_PUBLIC_KEY_CONTEXT, &body)?;
+
+        Ok(SignedPublicEphemeralKey {
+            key,
+            epoch,
+            checksum,
+            signature,
+        })
+    }
+
+    /// Propose a new master secret generation.
+    ///
+    /// The proposed master secret is generated and persisted, but it is not
+    /// used to derive keys until it is activated. Proposing the same
+    /// generation again returns the checksum of the existing proposal, which
+    /// is loaded from local storage after a restart.
+    pub fn propose_master_secret(&self, req: &ProposeRequest) -> Result<GenerationResponse> {
+        self.propose(req.generation, None)
+    }
+
+    /// Propose a master secret generated jointly by a committee of key
+    /// manager enclaves (see `Dkg`).
+    ///
+    /// All committee members propose the same master secret, so proposing
+    /// it again is allowed, but it does not replace a different proposal.
+    pub fn propose_joint_master_secret(
+        &self,
+        generation: Generation,
+        master_secret: MasterSecret,
+    ) -> Result<GenerationResponse> {
+        self.propose(generation, Some(master_secret))
+    }
+
+    fn propose(
+        &self,
+        generation: Generation,
+        master_secret: Option<MasterSecret>,
+    ) -> Result<GenerationResponse> {
+        let mut inner = self.inner.write().unwrap();
+
+        let runtime_id = match inner.runtime_id {
+            Some(runtime_id) if inner.master_secret.is_some() => runtime_id,
+            _ => return Err(KeyManagerError::NotInitialized.into()),
+        };
+        if generation != inner.latest_generation() + 1 {
+            return Err(KeyManagerError::InvalidGeneration(generation).into());
+        }
+
+        let master_secret = match (&inner.proposal, master_secret) {
+            (Some((proposed, existing)), master_secret) if *proposed == generation => {
+                if master_secret.map_or(false, |master_secret| master_secret != *existing) {
+                    return Err(KeyManagerError::StateCorrupted.into());
+                }
+                existing.clone()
+            }
+            // A persisted master secret of the generation must never be
+            // replaced, as it may have been activated elsewhere.
+            (_, master_secret) => match (
+                Self::load_master_secret(&runtime_id, generation),
+                master_secret,
+            ) {
+                (Some(existing), Some(master_secret)) if existing != master_secret => {
+                    return Err(KeyManagerError::StateCorrupted.into());
+                }
+                (Some(existing), _) => existing,
+                (None, Some(master_secret)) => {
+                    Self::save_master_secret(&master_secret, &runtime_id, generation);
+                    master_secret
+                }
+                (None, None) => Self::generate_master_secret(&runtime_id, generation),
+            },
+        };
+        let checksum = Self::checksum_master_secret(&master_secret, &runtime_id, generation);
+        inner.proposal = Some((generation, master_secret));
+
+        Ok(GenerationResponse {
+            generation,
+            checksum,
+        })
+    }
+
+    /// Activate a master secret generation.
+    ///
+    /// The master secret is taken from the local proposal, loaded from
+    /// local storage or replicated from another key manager instance, and
+    /// must match the checksum agreed upon by the network.
+    ///
+    /// Active generations are persisted and restored by `init` after the
+    /// key manager restarts.
+    #[cfg_attr(not(target_env = "sgx"), allow(unused))]
+    pub fn activate_master_secret(
+        &self,
+        req: &ActivateRequest,
+        ctx: &mut RpcContext,
+    ) -> Result<GenerationResponse> {
+        let mut inner = self.inner.write().unwrap();
+
+        let runtime_id = match inner.runtime_id {
+            Some(runtime_id) if inner.master_secret.is_some() => runtime_id,
+            _ => return Err(KeyManagerError::NotInitialized.into()),
+        };
+        if req.generation == 0 {
+            return Err(KeyManagerError::InvalidGeneration(req.generation).into());
+        }
+        if let Some(active) = inner.generations.get(&req.generation) {
+            if active.checksum != req.checksum {
+                return Err(KeyManagerError::StateCorrupted.into());
+            }
+            return Ok(GenerationResponse {
+                generation: req.generation,
+                checksum: active.checksum.clone(),
+            });
+        }
+
+        let (master_secret, did_replicate) = match inner.proposal {
+            Some((generation, ref master_secret)) if generation == req.generation => {
+                (master_secret.clone(), false)
+            }
+            _ => match Self::load_master_secret(&runtime_id, req.generation) {
+                Some(master_secret) => (master_secret, false),
+                None => (Self::replicate_from_remote(ctx, req.generation)?, true),
+            },
+        };
+
+        let checksum = Self::checksum_master_secret(&master_secret, &runtime_id, req.generation);
+        if req.checksum != checksum {
+            return Err(KeyManagerError::StateCorrupted.into());
+        }
+
+        if did_replicate {
+            Self::save_master_secret(&master_secret, &runtime_id, req.generation);
+        }
+        inner.generations.insert(
+            req.generation,
+            ActiveGeneration {
+                master_secret,
+                checksum: checksum.clone(),
+            },
+        );
+        Self::save_active_generations(&runtime_id, inner.generations.keys().cloned().collect());
+        if let Some((generation, _)) = inner.proposal {
+            if generation <= req.generation {
+                inner.proposal = None;
+            }
+        }
+
+        Ok(GenerationResponse {
+            generation: req.generation,
+            checksum,
+        })
+    }
+
     // Replicate master secret.
-    pub fn replicate_master_secret(&self) -> Result<ReplicateResponse> {
+    pub fn replicate_master_secret(&self, req: &ReplicateRequest) -> Result<ReplicateResponse> {
         let inner = self.inner.read().unwrap();
 
-        match inner.master_secret {
-            Some(master_secret) => Ok(ReplicateResponse { master_secret }),
-            None => Err(KeyManagerError::NotInitialized.into()),
+        // A proposed master secret may be replicated, so that other key
+        // manager instances can activate it.
+        if let Some((generation, ref master_secret)) = inner.proposal {
+            if generation == req.generation {
+                return Ok(ReplicateResponse {
+                    master_secret: master_secret.clone(),
+                });
+            }
         }
+
+        Ok(ReplicateResponse {
+            master_secret: inner.get_master_secret(req.generation)?.clone(),
+        })
     }
 
-    fn load_master_secret(runtime_id: &RuntimeId) -> Option<MasterSecret> {
+    fn replicate_from_remote(ctx: &mut RpcContext, generation: Generation) -> Result<MasterSecret> {
+        let km_client = new_replication_client(ctx);
+
+        let result =
+            km_client.replicate_master_secret(IoContext::create_child(&ctx.io_ctx), generation);
+        let master_secret = Executor::with_current(|executor| executor.block_on(result))?;
+        Ok(master_secret.unwrap())
+    }
+
+    /// Untrusted local storage key of the given master secret generation.
+    fn master_secret_storage_key(generation: Generation) -> Vec<u8> {
+        let mut key = MASTER_SECRET_STORAGE_KEY.to_vec();
+        if generation > 0 {
+            // The initial generation keeps the legacy key.
+            key.extend_from_slice(format!(".{}", generation).as_bytes());
+        }
+        key
+    }
+
+    /// Additional data used when sealing the given master secret generation
+    /// (before versioned sealing was used).
+    fn master_secret_seal_ad(runtime_id: &RuntimeId, generation: Generation) -> Vec<u8> {
+        let mut ad = runtime_id.as_ref().to_vec();
+        if generation > 0 {
+            ad.extend_from_slice(&generation.to_be_bytes());
+        }
+        ad
+    }
+
+    /// Sealing context of the given master secret generation.
+    fn master_secret_seal_context(runtime_id: &RuntimeId, generation: Generation) -> Vec<u8> {
+        let mut context = MASTER_SECRET_SEAL_CONTEXT.to_vec();
+        context.extend_from_slice(&Self::master_secret_seal_ad(runtime_id, generation));
+        context
+    }
+
+    fn load_master_secret(runtime_id: &RuntimeId, generation: Generation) -> Option<MasterSecret> {
         let ciphertext = StorageContext::with_current(|_mkvs, untrusted_local| {
-            untrusted_local.get(MASTER_SECRET_STORAGE_KEY.to_vec())
+            untrusted_local.get(Self::master_secret_storage_key(generation))
         })
         .unwrap();
 
-        match ciphertext.len() {
+        let master_secret = match ciphertext.len() {
             0 => return None,
-            MASTER_SECRET_STORAGE_SIZE => (),
+            _ if seal::is_versioned(&ciphertext) => seal::unseal_cbor(
+                Keypolicy::MRENCLAVE,
+                &Self::master_secret_seal_context(runtime_id, generation),
+                &ciphertext,
+            )
+            .expect("persisted state is corrupted")?,
+            MASTER_SECRET_STORAGE_SIZE => {
+                Self::open_legacy_master_secret(&ciphertext, runtime_id, generation)
+            }
             _ => {
                 panic!("persisted state is corrupted, invalid size");
             }
+        };
+
+        // Migrate the persisted master secret to the sealing key of the
+        // current enclave version.
+        if seal::needs_reseal(&ciphertext) {
+            Self::save_master_secret(&master_secret, runtime_id, generation);
         }
 
+        Some(master_secret)
+    }
+
+    /// Decrypt a master secret persisted before versioned sealing was used.
+    fn open_legacy_master_secret(
+        ciphertext: &[u8],
+        runtime_id: &RuntimeId,
+        generation: Generation,
+    ) -> MasterSecret {
         // Split the ciphertext || tag || nonce.
         let mut nonce = [0u8; NONCE_SIZE];
         nonce.copy_from_slice(&ciphertext[32 + TAG_SIZE..]);
@@ -400,56 +808,107 @@ impl Kdf {
 
         // Decrypt the persisted master secret.
         let d2 = Self::new_d2();
-        let plaintext = d2
-            .open(&nonce, ciphertext.to_vec(), runtime_id.as_ref().to_vec())
+        let mut plaintext = d2
+            .open(
+                &nonce,
+                ciphertext.to_vec(),
+                Self::master_secret_seal_ad(runtime_id, generation),
+            )
             .expect("persisted state is corrupted");
+        let master_secret =
+            MasterSecret::from_slice(&plaintext).expect("persisted state is corrupted");
+        plaintext.zeroize();
 
-        Some(MasterSecret::from(plaintext))
+        master_secret
     }
 
-    fn save_master_secret(master_secret: &MasterSecret, runtime_id: &RuntimeId) {
-        let mut rng = OsRng {};
-
+    fn save_master_secret(
+        master_secret: &MasterSecret,
+        runtime_id: &RuntimeId,
+        generation: Generation,
+    ) {
         // Encrypt the master secret.
-        let mut nonce = [0u8; NONCE_SIZE];
-        rng.fill(&mut nonce);
-        let d2 = Self::new_d2();
-        let mut ciphertext = d2.seal(
-            &nonce,
-            master_secret.as_ref().to_vec(),
-            runtime_id.as_ref().to_vec(),
+        let ciphertext = seal::seal_cbor(
+            Keypolicy::MRENCLAVE,
+            &Self::master_secret_seal_context(runtime_id, generation),
+            master_secret,
         );
-        ciphertext.extend_from_slice(&nonce);
 
         // Persist the encrypted master secret.
         StorageContext::with_current(|_mkvs, untrusted_local| {
-            untrusted_local.insert(MASTER_SECRET_STORAGE_KEY.to_vec(), ciphertext)
+            untrusted_local.insert(Self::master_secret_storage_key(generation), ciphertext)
         })
         .expect("failed to persist master secret");
     }
 
-    fn generate_master_secret(runtime_id: &RuntimeId) -> MasterSecret {
-        let mut rng = OsRng {};
+    /// Load the active master secret generations (other than the initial
+    /// one).
+    fn load_active_generations(runtime_id: &RuntimeId) -> Vec<Generation> {
+        let ciphertext = StorageContext::with_current(|_mkvs, untrusted_local| {
+            untrusted_local.get(ACTIVE_GENERATIONS_STORAGE_KEY.to_vec())
+        })
+        .unwrap();
+
+        let generations: Vec<Generation> = seal::unseal_cbor(
+            Keypolicy::MRENCLAVE,
+            &Self::active_generations_seal_context(runtime_id),
+            &ciphertext,
+        )
+        .expect("persisted state is corrupted")
+        .unwrap_or_default();
+
+        if seal::needs_reseal(&ciphertext) {
+            Self::save_active_generations(runtime_id, generations.clone());
+        }
+
+        generations
+    }
+
+    fn save_active_generations(runtime_id: &RuntimeId, generations: Vec<Generation>) {
+        let ciphertext = seal::seal_cbor(
+            Keypolicy::MRENCLAVE,
+            &Self::active_generations_seal_context(runtime_id),
+            &generations,
+        );
+
+        StorageContext::with_current(|_mkvs, untrusted_local| {
+            untrusted_local.insert(ACTIVE_GENERATIONS_STORAGE_KEY.to_vec(), ciphertext)
+        })
+        .expect("failed to persist active generations");
+    }
 
+    fn active_generations_seal_context(runtime_id: &RuntimeId) -> Vec<u8> {
+        let mut context = ACTIVE_GENERATIONS_SEAL_CONTEXT.to_vec();
+        context.extend_from_slice(runtime_id.as_ref());
+        context
+    }
+
+    fn generate_master_secret(runtime_id: &RuntimeId, generation: Generation) -> MasterSecret {
         // TODO: Support static keying for debugging.
-        let mut master_secret = [0u8; 32];
-        rng.fill(&mut master_secret);
-        let master_secret = MasterSecret::from(master_secret.to_vec());
+        let master_secret = MasterSecret::generate();
 
-        Self::save_master_secret(&master_secret, runtime_id);
+        Self::save_master_secret(&master_secret, runtime_id, generation);
 
         master_secret
     }
 
-    fn checksum_master_secret(master_secret: &MasterSecret, runtime_id: &RuntimeId) -> Vec<u8> {
-        let mut k = [0u8; 32];
-
+    fn checksum_master_secret(
+        master_secret: &MasterSecret,
+        runtime_id: &RuntimeId,
+        generation: Generation,
+    ) -> Vec<u8> {
         // KMAC256(master_secret, kmRuntimeID, 32, "ekiden-checksum-master-secret")
-        let mut f = KMac::new_kmac256(master_secret.as_ref(), &RUNTIME_CHECKSUM_CUSTOM);
-        f.update(runtime_id.as_ref());
-        f.finalize(&mut k);
-
-        k.to_vec()
+        //
+        // Later generations also include the generation number, so that the
+        // checksum commits to it.
+        let generation_bytes = generation.to_be_bytes();
+        let mut input: Vec<&[u8]> = vec![runtime_id.as_ref()];
+        if generation > 0 {
+            input.push(&generation_bytes);
+        }
+        kmac256::<32>(master_secret.as_ref(), &RUNTIME_CHECKSUM_CUSTOM, &input)
+            .as_ref()
+            .to_vec()
     }
 
     fn new_d2() -> DeoxysII {
@@ -4,16 +4,17 @@ use anyhow::Result;
 
 use oasis_core_keymanager_api_common::*;
 use oasis_core_runtime::{
+    common::crypto::vss::Share,
     dispatcher::Initializer,
     enclave_rpc::{
         dispatcher::{Method as RpcMethod, MethodDescriptor as RpcMethodDescriptor},
         Context as RpcContext,
     },
     rak::RAK,
-    register_runtime_rpc_methods, Protocol, RpcDemux, RpcDispatcher, TxnDispatcher,
+    register_runtime_rpc_methods, Protocol, QueryRouter, RpcDemux, RpcDispatcher, TxnDispatcher,
 };
 
-use crate::{context, kdf::Kdf, policy::Policy};
+use crate::{context, dkg::Dkg, kdf::Kdf, policy::Policy};
 
 /// Initialize the Kdf.
 fn init_kdf(req: &InitRequest, ctx: &mut RpcContext) -> Result<SignedInitResponse> {
@@ -21,13 +22,45 @@ fn init_kdf(req: &InitRequest, ctx: &mut RpcContext) -> Result<SignedInitRespons
     Kdf::global().init(&req, ctx, policy_checksum)
 }
 
+/// Propose a new master secret generation.
+fn propose_master_secret(
+    req: &ProposeRequest,
+    _ctx: &mut RpcContext,
+) -> Result<GenerationResponse> {
+    Kdf::global().propose_master_secret(req)
+}
+
+/// Activate a master secret generation.
+fn activate_master_secret(
+    req: &ActivateRequest,
+    ctx: &mut RpcContext,
+) -> Result<GenerationResponse> {
+    Kdf::global().activate_master_secret(req, ctx)
+}
+
+/// Deal a random secret for jointly generating a master secret.
+fn dkg_deal(req: &DealRequest, _ctx: &mut RpcContext) -> Result<DealResponse> {
+    Dkg::global().deal(req)
+}
+
+/// Combine the shares dealt by the committee.
+fn dkg_combine(req: &CombineRequest, ctx: &mut RpcContext) -> Result<CombineResponse> {
+    Dkg::global().combine(req, ctx)
+}
+
+/// Reconstruct and propose the jointly generated master secret.
+fn dkg_reconstruct(req: &ReconstructRequest, ctx: &mut RpcContext) -> Result<GenerationResponse> {
+    Dkg::global().reconstruct(req, ctx)
+}
+
 /// Initialize a keymanager with trusted policy signers.
 pub fn new_keymanager(signers: TrustedPolicySigners) -> Box<dyn Initializer> {
     // Initializer.
     let init = move |protocol: &Arc<Protocol>,
                      _rak: &Arc<RAK>,
                      _rpc_demux: &mut RpcDemux,
-                     rpc: &mut RpcDispatcher|
+                     rpc: &mut RpcDispatcher,
+                     _query: &mut QueryRouter|
           -> Option<Box<dyn TxnDispatcher>> {
         // Initialize the set of trusted policy signers.
         set_trusted_policy_signers(signers.clone());
@@ -50,6 +83,51 @@ pub fn new_keymanager(signers: TrustedPolicySigners) -> Box<dyn Initializer> {
             ),
             true,
         );
+        rpc.add_method(
+            RpcMethod::new(
+                RpcMethodDescriptor {
+                    name: "propose_master_secret".to_string(),
+                },
+                propose_master_secret,
+            ),
+            true,
+        );
+        rpc.add_method(
+            RpcMethod::new(
+                RpcMethodDescriptor {
+                    name: "activate_master_secret".to_string(),
+                },
+                activate_master_secret,
+            ),
+            true,
+        );
+        rpc.add_method(
+            RpcMethod::new(
+                RpcMethodDescriptor {
+                    name: "dkg_deal".to_string(),
+                },
+                dkg_deal,
+            ),
+            true,
+        );
+        rpc.add_method(
+            RpcMethod::new(
+                RpcMethodDescriptor {
+                    name: "dkg_combine".to_string(),
+                },
+                dkg_combine,
+            ),
+            true,
+        );
+        rpc.add_method(
+            RpcMethod::new(
+                RpcMethodDescriptor {
+                    name: "dkg_reconstruct".to_string(),
+                },
+                dkg_reconstruct,
+            ),
+            true,
+        );
 
         let runtime_id = protocol.get_runtime_id();
         let km_proto = protocol.clone(); // Shut up the borrow checker.
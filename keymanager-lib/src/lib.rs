@@ -1,4 +1,5 @@
 pub mod context;
+pub mod dkg;
 pub mod kdf;
 pub mod keymanager;
 mod methods;
@@ -1,9 +1,9 @@
 //! Methods exported to remote clients via EnclaveRPC.
 use anyhow::Result;
 use oasis_core_keymanager_api_common::*;
-use oasis_core_runtime::enclave_rpc::Context as RpcContext;
+use oasis_core_runtime::{common::crypto::vss::Share, enclave_rpc::Context as RpcContext};
 
-use crate::{kdf::Kdf, policy::Policy};
+use crate::{dkg::Dkg, kdf::Kdf, policy::Policy};
 
 /// See `Kdf::get_or_create_keys`.
 pub fn get_or_create_keys(req: &RequestIds, ctx: &mut RpcContext) -> Result<KeyPair> {
@@ -12,7 +12,7 @@ pub fn get_or_create_keys(req: &RequestIds, ctx: &mut RpcContext) -> Result<KeyP
     if !Policy::unsafe_skip() {
         let si = ctx.session_info.as_ref();
         let si = si.ok_or(KeyManagerError::NotAuthenticated)?;
-        let their_id = &si.authenticated_avr.identity;
+        let their_id = &si.verified_quote.identity();
 
         Policy::global().may_get_or_create_keys(their_id, &req)?;
     }
@@ -27,22 +27,100 @@ pub fn get_public_key(req: &RequestIds, _ctx: &mut RpcContext) -> Result<Option<
     // No authentication, absolutely anyone is allowed to query public keys.
 
     let pk = kdf.get_public_key(req)?;
-    pk.map_or(Ok(None), |pk| Ok(Some(kdf.sign_public_key(pk)?)))
+    pk.map_or(Ok(None), |pk| {
+        Ok(Some(kdf.sign_public_key(pk, req.generation)?))
+    })
+}
+
+/// See `Kdf::get_or_create_ephemeral_keys`.
+pub fn get_or_create_ephemeral_keys(
+    req: &EphemeralKeyRequest,
+    ctx: &mut RpcContext,
+) -> Result<KeyPair> {
+    // Ephemeral keys are released under the same policy as long-term keys.
+    if !Policy::unsafe_skip() {
+        let si = ctx.session_info.as_ref();
+        let si = si.ok_or(KeyManagerError::NotAuthenticated)?;
+        let their_id = &si.verified_quote.identity();
+
+        Policy::global().may_get_or_create_keys(their_id, &req.to_request_ids())?;
+    }
+
+    Kdf::global().get_or_create_ephemeral_keys(req, current_epoch(ctx)?)
+}
+
+/// See `Kdf::get_public_ephemeral_key`.
+pub fn get_public_ephemeral_key(
+    req: &EphemeralKeyRequest,
+    ctx: &mut RpcContext,
+) -> Result<Option<SignedPublicEphemeralKey>> {
+    let kdf = Kdf::global();
+
+    // No authentication, absolutely anyone is allowed to query public keys,
+    // but only of runtimes covered by the policy, as each runtime has its own
+    // ephemeral secrets.
+    if !Policy::unsafe_skip() {
+        Policy::global().may_derive_ephemeral_keys(&req.runtime_id)?;
+    }
+
+    let pk = kdf.get_public_ephemeral_key(req, current_epoch(ctx)?)?;
+    pk.map_or(Ok(None), |pk| {
+        Ok(Some(kdf.sign_public_ephemeral_key(pk, req.epoch)?))
+    })
+}
+
+/// Current consensus epoch, which determines the ephemeral keys that can be
+/// obtained.
+fn current_epoch(ctx: &RpcContext) -> Result<EpochTime> {
+    match ctx.current_epoch() {
+        Some(info) => Ok(info.epoch),
+        None => Err(KeyManagerError::EpochUnknown.into()),
+    }
 }
 
 /// See `Kdf::replicate_master_secret`.
 pub fn replicate_master_secret(
-    _req: &ReplicateRequest,
+    req: &ReplicateRequest,
     ctx: &mut RpcContext,
 ) -> Result<ReplicateResponse> {
     // Authenticate the source enclave based on the MRSIGNER/MRNELCAVE.
     if !Policy::unsafe_skip() {
         let si = ctx.session_info.as_ref();
         let si = si.ok_or(KeyManagerError::NotAuthenticated)?;
-        let their_id = &si.authenticated_avr.identity;
+        let their_id = &si.verified_quote.identity();
+
+        Policy::global().may_replicate_master_secret(their_id)?;
+    }
+
+    Kdf::global().replicate_master_secret(req)
+}
+
+/// See `Dkg::get_dealt_share`.
+pub fn get_dealt_share(req: &DealtShareRequest, ctx: &mut RpcContext) -> Result<Share> {
+    // Shares are only released to enclaves which may replicate the master
+    // secret, as enough of them reveal it.
+    if !Policy::unsafe_skip() {
+        let si = ctx.session_info.as_ref();
+        let si = si.ok_or(KeyManagerError::NotAuthenticated)?;
+        let their_id = &si.verified_quote.identity();
+
+        Policy::global().may_replicate_master_secret(their_id)?;
+    }
+
+    Dkg::global().get_dealt_share(req)
+}
+
+/// See `Dkg::get_joint_share`.
+pub fn get_joint_share(req: &JointShareRequest, ctx: &mut RpcContext) -> Result<Share> {
+    // Shares are only released to enclaves which may replicate the master
+    // secret, as enough of them reveal it.
+    if !Policy::unsafe_skip() {
+        let si = ctx.session_info.as_ref();
+        let si = si.ok_or(KeyManagerError::NotAuthenticated)?;
+        let their_id = &si.verified_quote.identity();
 
         Policy::global().may_replicate_master_secret(their_id)?;
     }
 
-    Kdf::global().replicate_master_secret()
+    Dkg::global().get_joint_share(req)
 }
@@ -16,7 +16,7 @@ use oasis_core_runtime::{
         runtime::RuntimeId,
         sgx::{
             avr::EnclaveIdentity,
-            seal::{seal, unseal},
+            seal::{needs_reseal, seal_versioned, unseal_versioned},
         },
     },
     enclave_rpc::Context as RpcContext,
@@ -68,11 +68,8 @@ impl Policy {
 
         // If there is no existing policy, attempt to load from local storage.
         let old_policy = match inner.policy.as_ref() {
-            Some(old_policy) => old_policy.clone(),
-            None => match Self::load_policy() {
-                Some(old_policy) => old_policy,
-                None => CachedPolicy::default(),
-            },
+            Some(old_policy) => Some(old_policy.clone()),
+            None => Self::load_policy(),
         };
 
         // De-serialize the new policy, verify signatures.
@@ -80,22 +77,18 @@ impl Policy {
 
         // Ensure the new policy's runtime ID matches the current enclave's.
         let rctx = runtime_context!(ctx, KmContext);
-        if rctx.runtime_id != new_policy.runtime_id {
+        if rctx.runtime_id != new_policy.policy.id {
             return Err(KeyManagerError::PolicyInvalid.into());
         }
 
-        // Compare the new serial number with the old serial number, ensure
-        // it is greater.
-        if old_policy.serial > new_policy.serial {
-            return Err(KeyManagerError::PolicyRollback.into());
-        } else if old_policy.serial == new_policy.serial {
-            // Policy should be identical, ensure nothing has changed
-            // and just return.
-            if old_policy.checksum != new_policy.checksum {
-                return Err(KeyManagerError::PolicyChanged.into());
+        // Ensure the new policy may replace the old one, which is the case
+        // if its serial number is greater or if it is identical.
+        if let Some(old_policy) = old_policy {
+            new_policy.policy.check_update(&old_policy.policy)?;
+            if old_policy.policy.serial == new_policy.policy.serial {
+                inner.policy = Some(old_policy.clone());
+                return Ok(old_policy.checksum);
             }
-            inner.policy = Some(old_policy.clone());
-            return Ok(old_policy.checksum.clone());
         }
 
         // Persist then apply the new policy.
@@ -125,6 +118,20 @@ impl Policy {
         }
     }
 
+    /// Check if ephemeral keys may be derived for the given runtime ID,
+    /// which is the case if any enclave may query its keys.
+    pub fn may_derive_ephemeral_keys(&self, runtime_id: &RuntimeId) -> Result<()> {
+        let inner = self.inner.read().unwrap();
+        let policy = match inner.policy.as_ref() {
+            Some(policy) => policy,
+            None => return Err(KeyManagerError::InvalidAuthentication.into()),
+        };
+        match policy.may_query.contains_key(runtime_id) {
+            true => Ok(()),
+            false => Err(KeyManagerError::InvalidAuthentication.into()),
+        }
+    }
+
     /// Check if the MRENCLAVE/MRSIGNER may replicate.
     pub fn may_replicate_master_secret(&self, remote_enclave: &EnclaveIdentity) -> Result<()> {
         // Always allow replication to ourselves, if it is possible to do so in
@@ -175,14 +182,21 @@ impl Policy {
         })
         .unwrap();
 
-        unseal(Keypolicy::MRENCLAVE, &POLICY_SEAL_CONTEXT, &ciphertext).map(|plaintext| {
-            // Deserialization failures are fatal, because it is state corruption.
-            CachedPolicy::parse(&plaintext).expect("failed to deserialize persisted policy")
-        })
+        let plaintext = unseal_versioned(Keypolicy::MRENCLAVE, &POLICY_SEAL_CONTEXT, &ciphertext)
+            .expect("persisted policy is corrupted")?;
+
+        // Migrate the persisted policy to the sealing key of the current
+        // enclave version.
+        if needs_reseal(&ciphertext) {
+            Self::save_raw_policy(&plaintext);
+        }
+
+        // Deserialization failures are fatal, because it is state corruption.
+        Some(CachedPolicy::parse(&plaintext).expect("failed to deserialize persisted policy"))
     }
 
     fn save_raw_policy(raw_policy: &Vec<u8>) {
-        let ciphertext = seal(Keypolicy::MRENCLAVE, &POLICY_SEAL_CONTEXT, &raw_policy);
+        let ciphertext = seal_versioned(Keypolicy::MRENCLAVE, &POLICY_SEAL_CONTEXT, &raw_policy);
 
         // Persist the encrypted master secret.
         StorageContext::with_current(|_mkvs, untrusted_local| {
@@ -195,8 +209,7 @@ impl Policy {
 #[derive(Clone, Debug)]
 struct CachedPolicy {
     pub checksum: Vec<u8>,
-    pub serial: u32,
-    pub runtime_id: RuntimeId,
+    pub policy: PolicySGX,
     pub may_query: HashMap<RuntimeId, HashSet<EnclaveIdentity>>,
     pub may_replicate: HashSet<EnclaveIdentity>,
     pub may_replicate_from: HashSet<EnclaveIdentity>,
@@ -208,20 +221,16 @@ impl CachedPolicy {
         let untrusted_policy: SignedPolicySGX = cbor::from_slice(&raw)?;
         let policy = untrusted_policy.verify()?;
 
-        let mut cached_policy = Self::default();
-        cached_policy.serial = policy.serial;
-        cached_policy.runtime_id = policy.id;
-
         let mut sha3 = Sha3::v256();
         sha3.update(&raw);
         let mut k = [0; 32];
         sha3.finalize(&mut k);
-        cached_policy.checksum = k.to_vec();
+        let mut cached_policy = Self::new(k.to_vec(), policy.clone());
 
         // Convert the policy into a cached one.
         //
-        // TODO: Need a mock enclave identity for non-sgx builds if we want to
-        // ever test policies with such a build.
+        // Non-SGX builds only have an enclave identity with the
+        // `debug-mock-sgx` feature of the runtime.
         let enclave_identity = match EnclaveIdentity::current() {
             Some(enclave_identity) => enclave_identity,
             None => return Ok(cached_policy),
@@ -249,11 +258,10 @@ impl CachedPolicy {
         Ok(cached_policy)
     }
 
-    fn default() -> Self {
+    fn new(checksum: Vec<u8>, policy: PolicySGX) -> Self {
         CachedPolicy {
-            checksum: vec![],
-            serial: 0,
-            runtime_id: RuntimeId::default(),
+            checksum,
+            policy,
             may_query: HashMap::new(),
             may_replicate: HashSet::new(),
             may_replicate_from: HashSet::new(),
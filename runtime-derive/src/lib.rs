@@ -0,0 +1,303 @@
+//! Derive macros for the Oasis Core runtime.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields, Lit, Meta,
+    NestedMeta, Path, Type,
+};
+
+/// Name of the helper attribute.
+const ATTRIBUTE: &str = "marshal";
+
+/// Derive `storage::mkvs::marshal::Marshal` for a struct.
+///
+/// Fields are encoded in declaration order without any framing:
+///
+/// * `u8`, `u16`, `u32` and `u64` fields are encoded as fixed-width little
+///   endian integers.
+/// * `[u8; N]` fields and hashes (or any other byte array type defined via
+///   `impl_bytes!` and marked with `#[marshal(fixed)]`) are encoded as is.
+/// * `Vec<u8>` fields are encoded as a little endian length followed by the
+///   bytes. The length is a `u32` unless overridden by e.g.
+///   `#[marshal(len = "u16")]`.
+/// * Any other field must itself implement `Marshal`.
+///
+/// Unmarshaling checks all lengths against the input before reading, so
+/// truncated or malformed input results in an error instead of a panic.
+///
+/// Code inside the runtime crate itself must use
+/// `#[marshal(crate_path = "crate")]` on the struct.
+#[proc_macro_derive(Marshal, attributes(marshal))]
+pub fn derive_marshal(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_marshal(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Encoding of a single field.
+enum FieldKind {
+    /// Fixed-width little endian integer.
+    Int(Type),
+    /// Fixed-size byte array.
+    Array,
+    /// Byte array newtype (e.g., `Hash`).
+    Fixed,
+    /// Length-prefixed bytes with the given length type.
+    Bytes(Type),
+    /// Nested type implementing `Marshal`.
+    Nested,
+}
+
+struct FieldAttrs {
+    fixed: bool,
+    len: Option<Type>,
+}
+
+fn marshal_attrs(attrs: &[Attribute]) -> Result<Vec<NestedMeta>, Error> {
+    let mut result = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident(ATTRIBUTE)) {
+        match attr.parse_meta()? {
+            Meta::List(list) => result.extend(list.nested.into_iter()),
+            meta => return Err(Error::new(meta.span(), "expected #[marshal(...)]")),
+        }
+    }
+    Ok(result)
+}
+
+fn lit_str<T: syn::parse::Parse>(lit: &Lit) -> Result<T, Error> {
+    match lit {
+        Lit::Str(lit) => lit.parse(),
+        lit => Err(Error::new(lit.span(), "expected a string literal")),
+    }
+}
+
+fn container_crate_path(attrs: &[Attribute]) -> Result<Path, Error> {
+    let mut krate = syn::parse_quote!(::oasis_core_runtime);
+    for meta in marshal_attrs(attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("crate_path") => {
+                krate = lit_str(&nv.lit)?;
+            }
+            meta => return Err(Error::new(meta.span(), "unknown marshal attribute")),
+        }
+    }
+    Ok(krate)
+}
+
+fn field_attrs(attrs: &[Attribute]) -> Result<FieldAttrs, Error> {
+    let mut result = FieldAttrs {
+        fixed: false,
+        len: None,
+    };
+    for meta in marshal_attrs(attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("fixed") => {
+                result.fixed = true;
+            }
+            NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("len") => {
+                result.len = Some(lit_str(&nv.lit)?);
+            }
+            meta => return Err(Error::new(meta.span(), "unknown marshal attribute")),
+        }
+    }
+    Ok(result)
+}
+
+/// Return the last path segment of a type, if it is a plain path.
+fn type_ident(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            path.path.segments.last().map(|seg| seg.ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn is_u8(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none() && path.path.is_ident("u8"),
+        _ => false,
+    }
+}
+
+fn is_byte_vec(ty: &Type) -> bool {
+    let path = match ty {
+        Type::Path(path) if path.qself.is_none() => &path.path,
+        _ => return false,
+    };
+    let seg = match path.segments.last() {
+        Some(seg) if seg.ident == "Vec" => seg,
+        _ => return false,
+    };
+    match seg.arguments {
+        syn::PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+            match args.args.first() {
+                Some(syn::GenericArgument::Type(ty)) => is_u8(ty),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn field_kind(ty: &Type, attrs: FieldAttrs) -> Result<FieldKind, Error> {
+    if let Some(len) = attrs.len {
+        if !is_byte_vec(ty) {
+            return Err(Error::new(
+                ty.span(),
+                "#[marshal(len)] is only supported on Vec<u8> fields",
+            ));
+        }
+        return Ok(FieldKind::Bytes(len));
+    }
+    if attrs.fixed {
+        return Ok(FieldKind::Fixed);
+    }
+
+    match ty {
+        Type::Array(array) if is_u8(&array.elem) => return Ok(FieldKind::Array),
+        _ => {}
+    }
+    if is_byte_vec(ty) {
+        return Ok(FieldKind::Bytes(syn::parse_quote!(u32)));
+    }
+    match type_ident(ty).as_deref() {
+        Some("u8") | Some("u16") | Some("u32") | Some("u64") => Ok(FieldKind::Int(ty.clone())),
+        Some("Hash") => Ok(FieldKind::Fixed),
+        _ => Ok(FieldKind::Nested),
+    }
+}
+
+fn expand_marshal(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let krate = container_crate_path(&input.attrs)?;
+    let fields = match input.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "Marshal can only be derived for structs",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let marshal = quote!(#krate::storage::mkvs::marshal::Marshal);
+    let private = quote!(#krate::storage::mkvs::marshal::derive);
+
+    let mut marshal_fields = Vec::new();
+    let mut unmarshal_fields = Vec::new();
+    let members: Vec<(TokenStream2, String, &syn::Field)> = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let ident = field.ident.as_ref().expect("named field");
+                (ident.to_token_stream(), ident.to_string(), field)
+            })
+            .collect(),
+        Fields::Unnamed(fields) => fields
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let index = syn::Index::from(index);
+                (index.to_token_stream(), index.index.to_string(), field)
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    for (member, member_name, field) in members {
+        let field_name = format!("{}.{}", name, member_name);
+        let kind = field_kind(&field.ty, field_attrs(&field.attrs)?)?;
+        let (marshal_field, unmarshal_field) = match kind {
+            FieldKind::Int(ty) => (
+                quote! {
+                    result.extend_from_slice(&self.#member.to_le_bytes());
+                },
+                quote! {
+                    let mut buf = [0u8; ::core::mem::size_of::<#ty>()];
+                    buf.copy_from_slice(#private::read(
+                        data,
+                        &mut offset,
+                        ::core::mem::size_of::<#ty>(),
+                        #field_name,
+                    )?);
+                    self.#member = <#ty>::from_le_bytes(buf);
+                },
+            ),
+            FieldKind::Array => (
+                quote! {
+                    result.extend_from_slice(&self.#member[..]);
+                },
+                quote! {
+                    let len = self.#member.len();
+                    self.#member
+                        .copy_from_slice(#private::read(data, &mut offset, len, #field_name)?);
+                },
+            ),
+            FieldKind::Fixed => (
+                quote! {
+                    result.extend_from_slice(&self.#member.0[..]);
+                },
+                quote! {
+                    let len = self.#member.0.len();
+                    self.#member
+                        .0
+                        .copy_from_slice(#private::read(data, &mut offset, len, #field_name)?);
+                },
+            ),
+            FieldKind::Bytes(len_ty) => (
+                quote! {
+                    let len: #len_ty = #private::length(self.#member.len(), #field_name)?;
+                    result.extend_from_slice(&len.to_le_bytes());
+                    result.extend_from_slice(&self.#member);
+                },
+                quote! {
+                    let mut buf = [0u8; ::core::mem::size_of::<#len_ty>()];
+                    buf.copy_from_slice(#private::read(
+                        data,
+                        &mut offset,
+                        ::core::mem::size_of::<#len_ty>(),
+                        #field_name,
+                    )?);
+                    let len = <#len_ty>::from_le_bytes(buf) as usize;
+                    self.#member = #private::read(data, &mut offset, len, #field_name)?.to_vec();
+                },
+            ),
+            FieldKind::Nested => (
+                quote! {
+                    result.extend_from_slice(&#marshal::marshal_binary(&self.#member)?);
+                },
+                quote! {
+                    offset += #marshal::unmarshal_binary(&mut self.#member, &data[offset..])?;
+                },
+            ),
+        };
+        marshal_fields.push(marshal_field);
+        unmarshal_fields.push(unmarshal_field);
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #marshal for #name #ty_generics #where_clause {
+            #[allow(unused_mut)]
+            fn marshal_binary(&self) -> #private::Result<::std::vec::Vec<u8>> {
+                let mut result = ::std::vec::Vec::new();
+                #(#marshal_fields)*
+                Ok(result)
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn unmarshal_binary(&mut self, data: &[u8]) -> #private::Result<usize> {
+                let mut offset = 0usize;
+                #({ #unmarshal_fields })*
+                Ok(offset)
+            }
+        }
+    })
+}
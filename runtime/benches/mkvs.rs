@@ -0,0 +1,195 @@
+//! Benchmarks for MKVS hot paths.
+//!
+//! Run with `cargo bench --bench mkvs`. Tree sizes straddle the threshold
+//! above which leaf hashing is parallelized on commit, and read benchmarks
+//! run against a remote tree with a limited cache for each cache policy.
+use std::rc::Rc;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use io_context::Context;
+
+use oasis_core_runtime::storage::mkvs::{
+    sync::{MapReadSync, NoopReadSyncer},
+    CachePolicy, ClockPolicy, LRUPolicy, Root, SegmentedLRUPolicy, Tree,
+};
+
+const TREE_SIZES: &[usize] = &[100, 1_000, 10_000];
+
+fn gen_pairs(count: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+    (0..count)
+        .map(|i| {
+            (
+                format!("key{}", i).into_bytes(),
+                format!("value{}", i).into_bytes(),
+            )
+        })
+        .collect()
+}
+
+/// Generate a committed tree containing the given pairs, together with its
+/// root.
+fn gen_tree(pairs: &[(Vec<u8>, Vec<u8>)]) -> (Tree, Root) {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    for (key, value) in pairs {
+        tree.insert(Context::background(), key, value)
+            .expect("insert");
+    }
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let root = Root {
+        hash,
+        ..Default::default()
+    };
+
+    (tree, root)
+}
+
+fn cache_policies() -> Vec<(&'static str, Rc<dyn CachePolicy>)> {
+    vec![
+        ("lru", Rc::new(LRUPolicy)),
+        ("slru", Rc::new(SegmentedLRUPolicy)),
+        ("clock", Rc::new(ClockPolicy)),
+    ]
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for &size in TREE_SIZES {
+        let pairs = gen_pairs(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &pairs, |b, pairs| {
+            b.iter_batched(
+                || Tree::make().new(Box::new(NoopReadSyncer)),
+                |mut tree| {
+                    for (key, value) in pairs {
+                        tree.insert(Context::background(), key, value)
+                            .expect("insert");
+                    }
+                    tree
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_commit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("commit");
+    for &size in TREE_SIZES {
+        let pairs = gen_pairs(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &pairs, |b, pairs| {
+            b.iter_batched(
+                || {
+                    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+                    for (key, value) in pairs {
+                        tree.insert(Context::background(), key, value)
+                            .expect("insert");
+                    }
+                    tree
+                },
+                |mut tree| {
+                    Tree::commit(&mut tree, Context::background(), Default::default(), 0)
+                        .expect("commit")
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get");
+    for &size in TREE_SIZES {
+        let pairs = gen_pairs(size);
+        let (tree, root) = gen_tree(&pairs);
+        group.bench_with_input(BenchmarkId::new("local", size), &pairs, |b, pairs| {
+            b.iter(|| {
+                for (key, _) in pairs {
+                    tree.get(Context::background(), key).expect("get");
+                }
+            })
+        });
+
+        for (name, policy) in cache_policies() {
+            let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+            let remote = Tree::make()
+                .with_capacity(size / 4, size)
+                .with_cache_policy(policy)
+                .with_root(root)
+                .new(Box::new(rs));
+            group.bench_with_input(BenchmarkId::new(name, size), &pairs, |b, pairs| {
+                b.iter(|| {
+                    // Interleave a hot key with a scan over all keys.
+                    for (key, _) in pairs {
+                        remote.get(Context::background(), &pairs[0].0).expect("get");
+                        remote.get(Context::background(), key).expect("get");
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_mixed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mixed");
+    for &size in TREE_SIZES {
+        let pairs = gen_pairs(size);
+        let (tree, root) = gen_tree(&pairs);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &pairs, |b, pairs| {
+            b.iter_batched(
+                || {
+                    let rs =
+                        MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+                    Tree::make().with_root(root).new(Box::new(rs))
+                },
+                |mut tree| {
+                    // Read two keys, update one and remove every tenth key.
+                    for (i, (key, value)) in pairs.iter().enumerate() {
+                        let other = &pairs[(i * 7) % pairs.len()].0;
+                        tree.get(Context::background(), other).expect("get");
+                        tree.get(Context::background(), key).expect("get");
+                        if i % 10 == 0 {
+                            tree.remove(Context::background(), key).expect("remove");
+                        } else {
+                            tree.insert(Context::background(), key, value)
+                                .expect("insert");
+                        }
+                    }
+                    Tree::commit(&mut tree, Context::background(), Default::default(), 0)
+                        .expect("commit")
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_get_with_proof(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_with_proof");
+    for &size in TREE_SIZES {
+        let pairs = gen_pairs(size);
+        let (tree, _) = gen_tree(&pairs);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &pairs, |b, pairs| {
+            b.iter(|| {
+                for (key, _) in pairs.iter().step_by(10) {
+                    tree.get_with_proof(Context::background(), key)
+                        .expect("get_with_proof");
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_insert,
+    bench_commit,
+    bench_get,
+    bench_mixed,
+    bench_get_with_proof
+);
+criterion_main!(benches);
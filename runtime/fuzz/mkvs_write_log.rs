@@ -0,0 +1,11 @@
+use honggfuzz::fuzz;
+
+use oasis_core_runtime::storage::mkvs::fuzz::fuzz_write_log_decode;
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            fuzz_write_log_decode(data);
+        });
+    }
+}
@@ -221,6 +221,12 @@ macro_rules! impl_bytes {
 
             }
         }
+
+        impl $crate::common::schema::Describe for $name {
+            fn schema() -> $crate::common::schema::Schema {
+                $crate::common::schema::Schema::Bytes
+            }
+        }
     };
 }
 
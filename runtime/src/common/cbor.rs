@@ -1,9 +1,12 @@
 //! Canonical CBOR serialization/deserialization functions.
 use std::io::Write;
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 pub use serde_cbor::value::{from_value, Value};
 use serde_cbor::{self, Result};
+use thiserror::Error;
+
+use super::crypto::hash::Hash;
 
 /// Convert a value to a `Value`.
 pub fn to_value<T>(value: T) -> Value
@@ -39,3 +42,384 @@ where
 {
     serde_cbor::from_slice(slice)
 }
+
+/// Deserializes a slice to a value, rejecting any input that is not in
+/// canonical form.
+///
+/// Canonical inputs only use definite lengths, encode all integers and
+/// lengths in their shortest form and sort map keys by length and then
+/// bytewise, without duplicates. This must be used for all inputs whose
+/// decoding needs to be deterministic, e.g. because they are verified by
+/// consensus.
+pub fn from_slice_strict<'a, T>(slice: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let size = check_canonical(slice, 0)?;
+    if size != slice.len() {
+        return Err(non_canonical("trailing data"));
+    }
+    from_slice(slice)
+}
+
+/// Maximum nesting depth of arrays, maps and tags accepted by strict
+/// decoding.
+const MAX_STRICT_DEPTH: usize = 128;
+
+fn non_canonical(reason: &str) -> serde_cbor::Error {
+    serde::de::Error::custom(format!("non-canonical CBOR: {}", reason))
+}
+
+/// Read the head of a single data item, returning its major type, argument
+/// and encoded size. Non-minimal and indefinite length encodings are
+/// rejected.
+fn read_head(data: &[u8]) -> Result<(u8, u64, usize)> {
+    let initial = *data
+        .first()
+        .ok_or_else(|| non_canonical("unexpected end"))?;
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+
+    let (arg, size) = match info {
+        0..=23 => (info as u64, 1),
+        24..=27 => {
+            let len = 1 << (info - 24);
+            if data.len() < 1 + len {
+                return Err(non_canonical("unexpected end"));
+            }
+            let arg = data[1..1 + len]
+                .iter()
+                .fold(0u64, |arg, b| (arg << 8) | *b as u64);
+            (arg, 1 + len)
+        }
+        31 => return Err(non_canonical("indefinite length")),
+        _ => return Err(non_canonical("reserved additional information")),
+    };
+
+    // Floats are not integers, so they have no shorter form to check.
+    let minimal = match (major, info) {
+        (7, 25..=27) => true,
+        (7, 24) => arg >= 32,
+        (_, 24) => arg >= 24,
+        (_, 25) => arg > 0xff,
+        (_, 26) => arg > 0xffff,
+        (_, 27) => arg > 0xffff_ffff,
+        _ => true,
+    };
+    if !minimal {
+        return Err(non_canonical("non-minimal encoding"));
+    }
+
+    Ok((major, arg, size))
+}
+
+/// Check that the data item at the start of the given slice is canonical,
+/// returning its encoded size.
+fn check_canonical(data: &[u8], depth: usize) -> Result<usize> {
+    if depth > MAX_STRICT_DEPTH {
+        return Err(non_canonical("nesting too deep"));
+    }
+
+    let (major, arg, mut pos) = read_head(data)?;
+    let remaining = (data.len() - pos) as u64;
+    match major {
+        // Integers and simple values.
+        0 | 1 | 7 => {}
+        // Byte and text strings.
+        2 | 3 => {
+            if arg > remaining {
+                return Err(non_canonical("unexpected end"));
+            }
+            pos += arg as usize;
+        }
+        // Arrays.
+        4 => {
+            if arg > remaining {
+                return Err(non_canonical("unexpected end"));
+            }
+            for _ in 0..arg {
+                pos += check_canonical(&data[pos..], depth + 1)?;
+            }
+        }
+        // Maps.
+        5 => {
+            if arg > remaining / 2 {
+                return Err(non_canonical("unexpected end"));
+            }
+            let mut last_key: Option<&[u8]> = None;
+            for _ in 0..arg {
+                let key_size = check_canonical(&data[pos..], depth + 1)?;
+                let key = &data[pos..pos + key_size];
+                // Keys must be sorted by length and then bytewise, which also
+                // rules out duplicates.
+                if let Some(last_key) = last_key {
+                    if (last_key.len(), last_key) >= (key.len(), key) {
+                        return Err(non_canonical("unsorted or duplicate map keys"));
+                    }
+                }
+                last_key = Some(key);
+                pos += key_size;
+                pos += check_canonical(&data[pos..], depth + 1)?;
+            }
+        }
+        // Tags.
+        6 => {
+            pos += check_canonical(&data[pos..], depth + 1)?;
+        }
+        _ => unreachable!("major type is three bits"),
+    }
+
+    Ok(pos)
+}
+
+/// Errors returned when navigating a `Value`.
+#[derive(Error, Debug)]
+pub enum PathError {
+    #[error("cbor: field {0} not found")]
+    NotFound(String),
+    #[error("cbor: field {path} is not {expected}")]
+    TypeMismatch {
+        path: String,
+        expected: &'static str,
+    },
+    #[error("cbor: failed to decode field {path}: {source}")]
+    Decode {
+        path: String,
+        source: serde_cbor::Error,
+    },
+}
+
+/// A (possibly missing) value at a path within a `Value`.
+///
+/// Paths are dot-separated lists of map keys and array indices, e.g.
+/// `header.state_root` or `messages.0.method`. Lookups never fail, only the
+/// final accessor returns an error if any path component was not found or
+/// the value is of a different type.
+#[derive(Clone, Debug)]
+pub struct ValuePath<'a> {
+    path: String,
+    value: Option<&'a Value>,
+}
+
+impl<'a> ValuePath<'a> {
+    /// Navigate further down the given path.
+    pub fn path(self, path: &str) -> ValuePath<'a> {
+        path.split('.')
+            .fold(self, |current, segment| current.get(segment))
+    }
+
+    fn get(self, segment: &str) -> ValuePath<'a> {
+        let path = if self.path.is_empty() {
+            segment.to_owned()
+        } else {
+            format!("{}.{}", self.path, segment)
+        };
+        let value = self.value.and_then(|value| match value {
+            Value::Map(map) => map.get(&Value::Text(segment.to_owned())).or_else(|| {
+                segment
+                    .parse::<i128>()
+                    .ok()
+                    .and_then(|key| map.get(&Value::Integer(key)))
+            }),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get(index)),
+            _ => None,
+        });
+
+        ValuePath { path, value }
+    }
+
+    /// Whether a value exists at the path.
+    pub fn exists(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// The value at the path.
+    pub fn value(&self) -> std::result::Result<&'a Value, PathError> {
+        self.value
+            .ok_or_else(|| PathError::NotFound(self.path.clone()))
+    }
+
+    fn mismatch<T>(&self, expected: &'static str) -> std::result::Result<T, PathError> {
+        Err(PathError::TypeMismatch {
+            path: self.path.clone(),
+            expected,
+        })
+    }
+
+    /// The value at the path as a boolean.
+    pub fn as_bool(&self) -> std::result::Result<bool, PathError> {
+        match self.value()? {
+            Value::Bool(value) => Ok(*value),
+            _ => self.mismatch("a boolean"),
+        }
+    }
+
+    /// The value at the path as an unsigned integer.
+    pub fn as_u64(&self) -> std::result::Result<u64, PathError> {
+        match self.value()? {
+            Value::Integer(value) if *value >= 0 && *value <= u64::MAX as i128 => Ok(*value as u64),
+            _ => self.mismatch("an unsigned integer"),
+        }
+    }
+
+    /// The value at the path as a signed integer.
+    pub fn as_i64(&self) -> std::result::Result<i64, PathError> {
+        match self.value()? {
+            Value::Integer(value) if *value >= i64::MIN as i128 && *value <= i64::MAX as i128 => {
+                Ok(*value as i64)
+            }
+            _ => self.mismatch("a signed integer"),
+        }
+    }
+
+    /// The value at the path as a text string.
+    pub fn as_str(&self) -> std::result::Result<&'a str, PathError> {
+        match self.value()? {
+            Value::Text(value) => Ok(value),
+            _ => self.mismatch("a text string"),
+        }
+    }
+
+    /// The value at the path as a byte string.
+    pub fn as_bytes(&self) -> std::result::Result<&'a [u8], PathError> {
+        match self.value()? {
+            Value::Bytes(value) => Ok(value),
+            _ => self.mismatch("a byte string"),
+        }
+    }
+
+    /// The value at the path as a hash.
+    pub fn as_hash(&self) -> std::result::Result<Hash, PathError> {
+        match self.value()? {
+            Value::Bytes(value) if value.len() == Hash::len() => Ok(Hash::from(&value[..])),
+            _ => self.mismatch("a hash"),
+        }
+    }
+
+    /// The value at the path as an array.
+    pub fn as_array(&self) -> std::result::Result<&'a Vec<Value>, PathError> {
+        match self.value()? {
+            Value::Array(value) => Ok(value),
+            _ => self.mismatch("an array"),
+        }
+    }
+
+    /// Decode the value at the path into the given type.
+    pub fn decode<T: DeserializeOwned>(&self) -> std::result::Result<T, PathError> {
+        from_value(self.value()?.clone()).map_err(|source| PathError::Decode {
+            path: self.path.clone(),
+            source,
+        })
+    }
+}
+
+/// Path-based navigation of `Value`s, see `ValuePath`.
+pub trait ValueExt {
+    /// Return the value at the given path.
+    fn path(&self, path: &str) -> ValuePath<'_>;
+}
+
+impl ValueExt for Value {
+    fn path(&self, path: &str) -> ValuePath<'_> {
+        ValuePath {
+            path: String::new(),
+            value: Some(self),
+        }
+        .path(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Header {
+        round: u64,
+        state_root: Hash,
+    }
+
+    #[test]
+    fn test_value_path() {
+        let header = Header {
+            round: 42,
+            state_root: Hash::digest_bytes(b"state"),
+        };
+        let mut map = BTreeMap::new();
+        map.insert("header".to_owned(), to_value(&header));
+        map.insert(
+            "messages".to_owned(),
+            Value::Array(vec![Value::Text("foo".to_owned())]),
+        );
+        let value = to_value(&map);
+
+        assert_eq!(42, value.path("header.round").as_u64().unwrap());
+        assert_eq!(
+            header.state_root,
+            value.path("header.state_root").as_hash().unwrap()
+        );
+        assert_eq!(
+            header.state_root,
+            value.path("header").path("state_root").as_hash().unwrap()
+        );
+        assert_eq!(header, value.path("header").decode::<Header>().unwrap());
+        assert_eq!("foo", value.path("messages.0").as_str().unwrap());
+
+        assert!(!value.path("header.missing.round").exists());
+        match value.path("header.missing.round").as_u64() {
+            Err(PathError::NotFound(path)) => assert_eq!("header.missing.round", path),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match value.path("messages.1").as_str() {
+            Err(PathError::NotFound(_)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match value.path("header.round").as_hash() {
+            Err(PathError::TypeMismatch { .. }) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_from_slice_strict() {
+        let mut map = BTreeMap::new();
+        map.insert("bb".to_owned(), vec![1u64, 1000]);
+        map.insert("a".to_owned(), vec![]);
+        map.insert("c".to_owned(), vec![u64::MAX]);
+        let data = to_vec(&map);
+        let decoded: BTreeMap<String, Vec<u64>> = from_slice_strict(&data).unwrap();
+        assert_eq!(map, decoded);
+
+        // Indefinite length array.
+        assert!(from_slice::<Vec<u64>>(&[0x9f, 0x01, 0xff]).is_ok());
+        assert!(from_slice_strict::<Vec<u64>>(&[0x9f, 0x01, 0xff]).is_err());
+        // Non-minimal integer.
+        assert!(from_slice::<u64>(&[0x18, 0x01]).is_ok());
+        assert!(from_slice_strict::<u64>(&[0x18, 0x01]).is_err());
+        // Non-minimal length.
+        assert!(from_slice_strict::<Vec<u64>>(&[0x98, 0x01, 0x01]).is_err());
+        // Unsorted map keys ({"b": 1, "a": 2}).
+        let unsorted = [0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02];
+        assert!(from_slice::<BTreeMap<String, u64>>(&unsorted).is_ok());
+        assert!(from_slice_strict::<BTreeMap<String, u64>>(&unsorted).is_err());
+        // Keys sorted bytewise but not by length ({"aa": 1, "b": 2}).
+        let unsorted = [0xa2, 0x62, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02];
+        assert!(from_slice_strict::<BTreeMap<String, u64>>(&unsorted).is_err());
+        // Duplicate map keys ({"a": 1, "a": 2}).
+        let duplicate = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02];
+        assert!(from_slice_strict::<BTreeMap<String, u64>>(&duplicate).is_err());
+        // Truncated and oversized inputs.
+        assert!(from_slice_strict::<Vec<u8>>(&[0x5b, 0xff, 0xff]).is_err());
+        assert!(from_slice_strict::<Vec<u64>>(&[
+            0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+        ])
+        .is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! BLS12-381 signatures.
+//!
+//! Public keys are points in G1 and signatures are points in G2, both in
+//! their compressed encoding. Signatures use the proof-of-possession scheme
+//! from the IETF BLS signature draft, so signatures and public keys can be
+//! aggregated as long as every aggregated public key came with a valid proof
+//! of possession.
+use anyhow::Result;
+use blst::{min_pk as bls, BLST_ERROR};
+use thiserror::Error;
+
+use super::{hash::Hash, secret::Secret};
+
+/// Domain separation tag used for signatures.
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// Domain separation tag used for proofs of possession.
+const POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Size of a private key in bytes.
+pub const PRIVATE_KEY_SIZE: usize = 32;
+/// Size of a compressed public key in bytes.
+pub const PUBLIC_KEY_SIZE: usize = 48;
+/// Size of a compressed signature in bytes.
+pub const SIGNATURE_SIZE: usize = 96;
+
+impl_bytes!(
+    PublicKey,
+    PUBLIC_KEY_SIZE,
+    "A BLS12-381 public key (compressed G1 point)."
+);
+
+impl_bytes!(
+    Signature,
+    SIGNATURE_SIZE,
+    "A BLS12-381 signature (compressed G2 point)."
+);
+
+/// BLS signature error.
+#[derive(Error, Debug)]
+pub enum BlsError {
+    #[error("bls: malformed private key")]
+    MalformedPrivateKey,
+    #[error("bls: malformed public key")]
+    MalformedPublicKey,
+    #[error("bls: malformed signature")]
+    MalformedSignature,
+    #[error("bls: nothing to aggregate")]
+    EmptyAggregate,
+    #[error("bls: length mismatch")]
+    LengthMismatch,
+    #[error("bls: signature verification failed")]
+    VerificationFailed,
+}
+
+fn check(err: BLST_ERROR) -> Result<()> {
+    match err {
+        BLST_ERROR::BLST_SUCCESS => Ok(()),
+        BLST_ERROR::BLST_BAD_ENCODING | BLST_ERROR::BLST_POINT_NOT_ON_CURVE => {
+            Err(BlsError::MalformedSignature.into())
+        }
+        _ => Err(BlsError::VerificationFailed.into()),
+    }
+}
+
+/// Digest of a message signed in the given context.
+fn digest(context: &[u8], message: &[u8]) -> Hash {
+    Hash::digest_bytes_list_with_context(context, &[message])
+}
+
+/// A BLS12-381 private key.
+pub struct PrivateKey(bls::SecretKey);
+
+impl PrivateKey {
+    /// Generates a new private key.
+    pub fn generate() -> Self {
+        Self::from_seed(&Secret::<32>::generate())
+    }
+
+    /// Derives a private key from the given seed, which must contain at
+    /// least 32 bytes of entropy.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the seed is shorter than 32 bytes.
+    pub fn from_seed(seed: &Secret<32>) -> Self {
+        PrivateKey(bls::SecretKey::key_gen(seed.as_ref(), &[]).expect("seed is long enough"))
+    }
+
+    /// Derives a private key from a test key seed.
+    pub fn from_test_seed(seed: String) -> Self {
+        let seed = Hash::digest_bytes(seed.as_bytes());
+        Self::from_seed(&Secret::new(seed.0))
+    }
+
+    /// Converts this private key into bytes.
+    pub fn to_bytes(&self) -> Secret<PRIVATE_KEY_SIZE> {
+        Secret::new(self.0.to_bytes())
+    }
+
+    /// Constructs a private key from bytes returned by `to_bytes`.
+    pub fn from_bytes(bytes: &Secret<PRIVATE_KEY_SIZE>) -> Result<Self> {
+        bls::SecretKey::from_bytes(bytes.as_ref())
+            .map(PrivateKey)
+            .map_err(|_| BlsError::MalformedPrivateKey.into())
+    }
+
+    /// Returns the public key.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(self.0.sk_to_pk().compress())
+    }
+
+    /// Generates a signature over the context and message.
+    pub fn sign(&self, context: &[u8], message: &[u8]) -> Signature {
+        let digest = digest(context, message);
+        Signature(self.0.sign(digest.as_ref(), SIGNATURE_DST, &[]).compress())
+    }
+
+    /// Generates a proof of possession of the private key, which must be
+    /// checked before the public key is used in aggregate verification.
+    pub fn prove_possession(&self) -> Signature {
+        let pk = self.public_key();
+        Signature(self.0.sign(pk.as_ref(), POP_DST, &[]).compress())
+    }
+}
+
+impl PublicKey {
+    fn to_point(&self) -> Result<bls::PublicKey> {
+        bls::PublicKey::key_validate(self.as_ref()).map_err(|_| BlsError::MalformedPublicKey.into())
+    }
+
+    /// Verify a proof of possession generated by `PrivateKey::prove_possession`.
+    pub fn verify_possession(&self, proof: &Signature) -> Result<()> {
+        let pk = self.to_point()?;
+        let sig = proof.to_point()?;
+        check(sig.verify(true, self.as_ref(), POP_DST, &[], &pk, false))
+    }
+
+    /// Aggregate the given public keys.
+    ///
+    /// The result can be used to verify an aggregate signature over a single
+    /// message, but only if all public keys have a verified proof of
+    /// possession.
+    pub fn aggregate(pks: &[PublicKey]) -> Result<PublicKey> {
+        if pks.is_empty() {
+            return Err(BlsError::EmptyAggregate.into());
+        }
+        let points = pks
+            .iter()
+            .map(PublicKey::to_point)
+            .collect::<Result<Vec<_>>>()?;
+        let points: Vec<&bls::PublicKey> = points.iter().collect();
+        let aggregate = bls::AggregatePublicKey::aggregate(&points, false)
+            .map_err(|_| BlsError::MalformedPublicKey)?;
+
+        Ok(PublicKey(aggregate.to_public_key().compress()))
+    }
+}
+
+impl Signature {
+    fn to_point(&self) -> Result<bls::Signature> {
+        bls::Signature::uncompress(self.as_ref()).map_err(|_| BlsError::MalformedSignature.into())
+    }
+
+    /// Verify signature.
+    pub fn verify(&self, pk: &PublicKey, context: &[u8], message: &[u8]) -> Result<()> {
+        let pk = pk.to_point()?;
+        let sig = self.to_point()?;
+        let digest = digest(context, message);
+        check(sig.verify(true, digest.as_ref(), SIGNATURE_DST, &[], &pk, false))
+    }
+
+    /// Aggregate the given signatures.
+    pub fn aggregate(sigs: &[Signature]) -> Result<Signature> {
+        if sigs.is_empty() {
+            return Err(BlsError::EmptyAggregate.into());
+        }
+        let points = sigs
+            .iter()
+            .map(Signature::to_point)
+            .collect::<Result<Vec<_>>>()?;
+        let points: Vec<&bls::Signature> = points.iter().collect();
+        let aggregate = bls::AggregateSignature::aggregate(&points, true)
+            .map_err(|_| BlsError::MalformedSignature)?;
+
+        Ok(Signature(aggregate.to_signature().compress()))
+    }
+
+    /// Verify an aggregate signature by the given public keys over the same
+    /// context and message.
+    ///
+    /// All public keys must have a verified proof of possession, otherwise
+    /// the aggregate signature can be forged.
+    pub fn verify_aggregate(
+        &self,
+        pks: &[PublicKey],
+        context: &[u8],
+        message: &[u8],
+    ) -> Result<()> {
+        self.verify(&PublicKey::aggregate(pks)?, context, message)
+    }
+
+    /// Verify an aggregate signature where the `i`-th public key signed the
+    /// `i`-th context and message.
+    ///
+    /// As with `verify_aggregate`, all public keys must have a verified proof
+    /// of possession.
+    pub fn verify_aggregate_multi(
+        &self,
+        pks: &[PublicKey],
+        contexts: &[&[u8]],
+        messages: &[&[u8]],
+    ) -> Result<()> {
+        if pks.is_empty() {
+            return Err(BlsError::EmptyAggregate.into());
+        }
+        if contexts.len() != pks.len() || messages.len() != pks.len() {
+            return Err(BlsError::LengthMismatch.into());
+        }
+
+        let points = pks
+            .iter()
+            .map(PublicKey::to_point)
+            .collect::<Result<Vec<_>>>()?;
+        let points: Vec<&bls::PublicKey> = points.iter().collect();
+        let digests: Vec<Hash> = contexts
+            .iter()
+            .zip(messages)
+            .map(|(context, message)| digest(context, message))
+            .collect();
+        let digests: Vec<&[u8]> = digests.iter().map(|digest| digest.as_ref()).collect();
+
+        let sig = self.to_point()?;
+        check(sig.aggregate_verify(true, &digests, SIGNATURE_DST, &points, false))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::cbor;
+
+    const CONTEXT: &[u8] = b"oasis-core/test: bls";
+
+    #[test]
+    fn test_sign_verify() {
+        let sk = PrivateKey::from_test_seed("bls".to_owned());
+        let pk = sk.public_key();
+        let sig = sk.sign(CONTEXT, b"message");
+
+        sig.verify(&pk, CONTEXT, b"message").expect("verify");
+        assert!(sig.verify(&pk, CONTEXT, b"other message").is_err());
+        assert!(sig
+            .verify(&pk, b"oasis-core/test: other", b"message")
+            .is_err());
+        assert!(sig
+            .verify(&PrivateKey::generate().public_key(), CONTEXT, b"message")
+            .is_err());
+        assert!(Signature::default()
+            .verify(&pk, CONTEXT, b"message")
+            .is_err());
+
+        // Keys and signatures are deterministic.
+        let sk2 = PrivateKey::from_bytes(&sk.to_bytes()).unwrap();
+        assert_eq!(pk, sk2.public_key());
+        assert_eq!(sig, sk2.sign(CONTEXT, b"message"));
+
+        // CBOR encodings are plain byte strings.
+        let encoded = cbor::to_vec(&sig);
+        assert_eq!(2 + SIGNATURE_SIZE, encoded.len());
+        assert_eq!(sig, cbor::from_slice::<Signature>(&encoded).unwrap());
+        let encoded = cbor::to_vec(&pk);
+        assert_eq!(2 + PUBLIC_KEY_SIZE, encoded.len());
+        assert_eq!(pk, cbor::from_slice::<PublicKey>(&encoded).unwrap());
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let sks: Vec<_> = (0..4)
+            .map(|i| PrivateKey::from_test_seed(format!("bls {}", i)))
+            .collect();
+        let pks: Vec<_> = sks.iter().map(|sk| sk.public_key()).collect();
+        for (sk, pk) in sks.iter().zip(&pks) {
+            pk.verify_possession(&sk.prove_possession())
+                .expect("verify possession");
+        }
+        assert!(pks[0]
+            .verify_possession(&sks[1].prove_possession())
+            .is_err());
+        // A proof of possession is not a valid signature over the public key.
+        assert!(sks[0]
+            .prove_possession()
+            .verify(&pks[0], CONTEXT, pks[0].as_ref())
+            .is_err());
+
+        // Same message.
+        let sigs: Vec<_> = sks.iter().map(|sk| sk.sign(CONTEXT, b"header")).collect();
+        let aggregate = Signature::aggregate(&sigs).unwrap();
+        aggregate
+            .verify_aggregate(&pks, CONTEXT, b"header")
+            .expect("verify aggregate");
+        assert!(aggregate
+            .verify_aggregate(&pks[1..], CONTEXT, b"header")
+            .is_err());
+        assert!(Signature::aggregate(&[]).is_err());
+
+        // Distinct messages.
+        let messages: Vec<Vec<u8>> = (0..sks.len()).map(|i| vec![i as u8; 8]).collect();
+        let messages: Vec<&[u8]> = messages.iter().map(|m| &m[..]).collect();
+        let contexts = vec![CONTEXT; sks.len()];
+        let sigs: Vec<_> = sks
+            .iter()
+            .zip(&messages)
+            .map(|(sk, message)| sk.sign(CONTEXT, message))
+            .collect();
+        let aggregate = Signature::aggregate(&sigs).unwrap();
+        aggregate
+            .verify_aggregate_multi(&pks, &contexts, &messages)
+            .expect("verify aggregate");
+        let mut other_pks = pks.clone();
+        other_pks.swap(0, 1);
+        assert!(aggregate
+            .verify_aggregate_multi(&other_pks, &contexts, &messages)
+            .is_err());
+        assert!(aggregate
+            .verify_aggregate_multi(&pks, &contexts[1..], &messages)
+            .is_err());
+    }
+}
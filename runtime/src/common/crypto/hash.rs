@@ -13,16 +13,32 @@ impl Hash {
     }
 
     /// Compute a digest of the passed slices of bytes.
+    ///
+    /// The slices are simply concatenated, so different lists with the same
+    /// concatenation have the same digest. Use
+    /// `digest_bytes_list_with_context` when this is a problem.
     pub fn digest_bytes_list(data: &[&[u8]]) -> Hash {
-        let mut ctx = Sha512Trunc256::new();
-        for datum in data {
-            ctx.update(datum);
-        }
+        data.iter()
+            .fold(Hash::builder(), |builder, datum| builder.update(datum))
+            .finalize()
+    }
 
-        let mut result = [0u8; 32];
-        result[..].copy_from_slice(ctx.finalize().as_ref());
+    /// Compute a domain-separated digest of the passed slices of bytes.
+    ///
+    /// The context and every part are prefixed by their length as a 64-bit
+    /// little endian integer, so the digest is unambiguous.
+    pub fn digest_bytes_list_with_context(context: &[u8], data: &[&[u8]]) -> Hash {
+        data.iter()
+            .fold(
+                Hash::builder().update_prefixed(context),
+                |builder, datum| builder.update_prefixed(datum),
+            )
+            .finalize()
+    }
 
-        Hash(result)
+    /// Start computing a digest incrementally.
+    pub fn builder() -> Builder {
+        Builder::default()
     }
 
     /// Returns true if the hash is of an empty string.
@@ -40,3 +56,81 @@ impl Hash {
         ])
     }
 }
+
+/// Incremental hash builder.
+#[derive(Clone, Default)]
+pub struct Builder {
+    ctx: Sha512Trunc256,
+}
+
+impl Builder {
+    /// Append the given bytes.
+    pub fn update<T: AsRef<[u8]>>(mut self, data: T) -> Self {
+        self.ctx.update(data.as_ref());
+        self
+    }
+
+    /// Append the given bytes, prefixed by their length as a 64-bit little
+    /// endian integer.
+    pub fn update_prefixed<T: AsRef<[u8]>>(self, data: T) -> Self {
+        let data = data.as_ref();
+        self.update((data.len() as u64).to_le_bytes()).update(data)
+    }
+
+    /// Finish computing the digest.
+    pub fn finalize(self) -> Hash {
+        let mut result = [0u8; 32];
+        result[..].copy_from_slice(self.ctx.finalize().as_ref());
+
+        Hash(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hash_builder() {
+        assert_eq!(Hash::empty_hash(), Hash::builder().finalize());
+        assert_eq!(
+            Hash::digest_bytes(b"foobar"),
+            Hash::builder().update(b"foo").update(b"bar").finalize()
+        );
+        assert_eq!(
+            Hash::digest_bytes(b"foobar"),
+            Hash::digest_bytes_list(&[&b"foo"[..], &b"bar"[..]])
+        );
+    }
+
+    #[test]
+    fn test_hash_with_context() {
+        let context = b"oasis-core/test: hash";
+        let hash = Hash::digest_bytes_list_with_context(context, &[&b"foo"[..], &b"bar"[..]]);
+        assert_eq!(
+            Hash::builder()
+                .update(21u64.to_le_bytes())
+                .update(context)
+                .update(3u64.to_le_bytes())
+                .update(b"foo")
+                .update(3u64.to_le_bytes())
+                .update(b"bar")
+                .finalize(),
+            hash
+        );
+
+        // Unlike plain concatenation, splitting parts differently changes
+        // the digest.
+        assert_ne!(
+            hash,
+            Hash::digest_bytes_list_with_context(context, &[&b"foob"[..], &b"ar"[..]])
+        );
+        assert_ne!(
+            hash,
+            Hash::digest_bytes_list_with_context(
+                b"oasis-core/test: other",
+                &[&b"foo"[..], &b"bar"[..]]
+            )
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Key derivation functions.
+//!
+//! All derivations take an explicit domain separation label, so that keys
+//! derived for different purposes from the same secret are independent.
+//! Derived keys are returned as `Secret`s of the requested size.
+use anyhow::Result;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha512Trunc256;
+use sp800_185::{CShake, KMac};
+use thiserror::Error;
+
+use super::secret::Secret;
+
+/// Size of the HMAC-SHA512/256 output in bytes.
+pub const HMAC_SIZE: usize = 32;
+
+/// Key derivation errors.
+#[derive(Error, Debug)]
+pub enum KdfError {
+    #[error("kdf: invalid pseudorandom key length")]
+    InvalidKeyLength,
+}
+
+fn concat(label: &[u8], context: &[&[u8]]) -> Vec<u8> {
+    let mut result = label.to_vec();
+    for part in context {
+        result.extend_from_slice(part);
+    }
+    result
+}
+
+/// HKDF (RFC 5869) instantiated with SHA-512/256.
+pub struct Hkdf(hkdf::Hkdf<Sha512Trunc256>);
+
+impl Hkdf {
+    /// Extract a pseudorandom key from the input key material, using the
+    /// label as salt.
+    pub fn extract(label: &[u8], ikm: &[u8]) -> Self {
+        let (_, kdf) = hkdf::Hkdf::extract(Some(label), ikm);
+        Hkdf(kdf)
+    }
+
+    /// Use the given uniformly random key (e.g., a previously expanded key)
+    /// as the pseudorandom key, skipping the extract step.
+    pub fn from_prk(prk: &[u8]) -> Result<Self> {
+        hkdf::Hkdf::from_prk(prk)
+            .map(Hkdf)
+            .map_err(|_| KdfError::InvalidKeyLength.into())
+    }
+
+    /// Expand the pseudorandom key into a key of `N` bytes, using the
+    /// label followed by the context as info.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `N` is larger than 255 times the digest
+    /// size.
+    pub fn expand<const N: usize>(&self, label: &[u8], context: &[&[u8]]) -> Secret<N> {
+        let mut result = Secret::<N>::default();
+        self.0
+            .expand(&concat(label, context), result.as_mut())
+            .expect("output is short enough");
+        result
+    }
+}
+
+/// Compute HMAC-SHA512/256 over the concatenated data, truncated to `N`
+/// bytes.
+///
+/// For derivations from a secret, the secret is the key and the label is
+/// the first data item. For derivations from a non-secret label (e.g., the
+/// output of a key agreement), the label is the key.
+///
+/// # Panics
+///
+/// This function will panic if `N` is larger than `HMAC_SIZE`.
+pub fn hmac_sha512_256<const N: usize>(key: &[u8], data: &[&[u8]]) -> Secret<N> {
+    assert!(N <= HMAC_SIZE, "output is too long");
+
+    let mut mac = Hmac::<Sha512Trunc256>::new_varkey(key).expect("Hmac::new_varkey");
+    for datum in data {
+        mac.update(datum);
+    }
+
+    let mut result = Secret::<N>::default();
+    result
+        .as_mut()
+        .copy_from_slice(&mac.finalize().into_bytes()[..N]);
+    result
+}
+
+/// Compute KMAC256 (NIST SP 800-185) of `N` bytes over the concatenated
+/// data, using the label as customization string.
+pub fn kmac256<const N: usize>(key: &[u8], label: &[u8], data: &[&[u8]]) -> Secret<N> {
+    let mut kmac = KMac::new_kmac256(key, label);
+    for datum in data {
+        kmac.update(datum);
+    }
+
+    let mut result = Secret::<N>::default();
+    kmac.finalize(result.as_mut());
+    result
+}
+
+/// Compute `N` bytes of cSHAKE256 (NIST SP 800-185) output over the
+/// concatenated data, using the label as customization string.
+///
+/// Unlike KMAC, the output does not depend on its length, so a single call
+/// can derive several keys by splitting the output.
+pub fn cshake256<const N: usize>(label: &[u8], data: &[&[u8]]) -> Secret<N> {
+    let mut result = Secret::<N>::default();
+    cshake256_into(label, data, result.as_mut());
+    result
+}
+
+/// Fill the output with cSHAKE256 output, see `cshake256`.
+///
+/// This can be used when the output length is only known at runtime.
+pub fn cshake256_into(label: &[u8], data: &[&[u8]], output: &mut [u8]) {
+    // Note: The `name` parameter for cSHAKE is reserved for use by NIST.
+    let mut xof = CShake::new_cshake256(&[], label);
+    for datum in data {
+        xof.update(datum);
+    }
+    xof.finalize(output);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LABEL: &[u8] = b"oasis-core/test: kdf";
+
+    fn from_hex(data: &str) -> Vec<u8> {
+        rustc_hex::FromHex::from_hex(data).unwrap()
+    }
+
+    #[test]
+    fn test_hkdf() {
+        // Inputs from test case 1 of RFC 5869, using SHA-512/256.
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        let kdf = Hkdf::extract(&salt, &[0x0b; 22]);
+        let okm: Secret<42> = kdf.expand(&info[..4], &[&info[4..8], &info[8..]]);
+        assert_eq!(
+            from_hex("789a93e567a1861de449342b2d674c0df737fd8adce2a8e1843237c1938ac413044b496ce267a198ebe3"),
+            okm.as_ref()
+        );
+
+        // Labels and input key material separate domains.
+        let other: Secret<42> = Hkdf::extract(LABEL, &[0x0b; 22]).expand(&info, &[]);
+        assert_ne!(okm, other);
+        let other: Secret<42> = Hkdf::extract(&salt, &[0x0c; 22]).expand(&info, &[]);
+        assert_ne!(okm, other);
+
+        // Chained expansion.
+        let prk: Secret<32> = kdf.expand(LABEL, &[]);
+        let next: Secret<32> = Hkdf::from_prk(prk.as_ref()).unwrap().expand(LABEL, &[]);
+        assert_ne!(prk, next);
+        assert!(Hkdf::from_prk(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_hmac_sha512_256() {
+        // Inputs from test case 2 of RFC 4231, using SHA-512/256.
+        let mac: Secret<32> =
+            hmac_sha512_256(b"Jefe", &[&b"what do ya want "[..], &b"for nothing?"[..]]);
+        assert_eq!(
+            from_hex("6df7b24630d5ccb2ee335407081a87188c221489768fa2020513b2d593359456"),
+            mac.as_ref()
+        );
+        let truncated: Secret<16> =
+            hmac_sha512_256(b"Jefe", &[&b"what do ya want for nothing?"[..]]);
+        assert_eq!(&mac.as_ref()[..16], truncated.as_ref());
+    }
+
+    #[test]
+    fn test_kmac256() {
+        let a: Secret<32> = kmac256(b"key", LABEL, &[&b"foo"[..], &b"bar"[..]]);
+        let b: Secret<32> = kmac256(b"key", LABEL, &[&b"foobar"[..]]);
+        assert_eq!(a, b);
+        assert_ne!(a, kmac256(b"other key", LABEL, &[&b"foobar"[..]]));
+        assert_ne!(
+            a,
+            kmac256(b"key", b"oasis-core/test: other", &[&b"foobar"[..]])
+        );
+
+        // The output length is part of the input.
+        let short: Secret<16> = kmac256(b"key", LABEL, &[&b"foobar"[..]]);
+        assert_ne!(&a.as_ref()[..16], short.as_ref());
+    }
+
+    #[test]
+    fn test_cshake256() {
+        let a: Secret<64> = cshake256(LABEL, &[&b"foo"[..], &b"bar"[..]]);
+        let b: Secret<32> = cshake256(LABEL, &[&b"foobar"[..]]);
+        assert_eq!(&a.as_ref()[..32], b.as_ref());
+        assert_ne!(b, cshake256(b"oasis-core/test: other", &[&b"foobar"[..]]));
+
+        let mut c = vec![0; 48];
+        cshake256_into(LABEL, &[&b"foobar"[..]], &mut c);
+        assert_eq!(&a.as_ref()[..48], &c[..]);
+    }
+}
@@ -1,5 +1,11 @@
 //! Cryptographic primitives.
 
+pub mod bls;
 pub mod hash;
+pub mod kdf;
 pub mod mrae;
+pub mod secp256k1;
+pub mod secret;
 pub mod signature;
+pub mod vrf;
+pub mod vss;
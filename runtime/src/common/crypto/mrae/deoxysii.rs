@@ -2,34 +2,25 @@
 
 pub use super::deoxysii_rust::{DeoxysII, KEY_SIZE, NONCE_SIZE, TAG_SIZE};
 
-use super::{
-    hmac::{Hmac, Mac, NewMac},
-    sha2::Sha512Trunc256,
-    x25519_dalek,
-};
+use super::x25519_dalek;
 
 use anyhow::Result;
 use rand::rngs::OsRng;
 
-type Kdf = Hmac<Sha512Trunc256>;
+use crate::common::crypto::{kdf, secret::Secret};
+
+/// Label used to derive box keys from the shared secret.
+const BOX_KDF_LABEL: &[u8] = b"MRAE_Box_Deoxys-II-256-128";
 
 /// Derives a MRAE AEAD symmetric key suitable for use with the asymmetric
 /// box primitives from the provided X25519 public and private keys.
-fn derive_symmetric_key(public: &[u8; 32], private: &[u8; 32]) -> [u8; KEY_SIZE] {
+fn derive_symmetric_key(public: &[u8; 32], private: &[u8; 32]) -> Secret<{ KEY_SIZE }> {
     let public = x25519_dalek::PublicKey::from(public.clone());
     let private = x25519_dalek::StaticSecret::from(private.clone());
 
     let pmk = private.diffie_hellman(&public);
 
-    let mut kdf = Kdf::new_varkey(b"MRAE_Box_Deoxys-II-256-128").expect("Hmac::new_varkey");
-    kdf.update(pmk.as_bytes());
-    drop(pmk);
-
-    let mut derived_key = [0u8; KEY_SIZE];
-    let digest = kdf.finalize();
-    derived_key.copy_from_slice(&digest.into_bytes()[..KEY_SIZE]);
-
-    derived_key
+    kdf::hmac_sha512_256(BOX_KDF_LABEL, &[pmk.as_bytes()])
 }
 
 /// Generates a public/private key pair suitable for use with
@@ -57,7 +48,7 @@ pub fn box_seal(
 ) -> Result<Vec<u8>> {
     let key = derive_symmetric_key(peers_public_key, private_key);
 
-    let d2 = DeoxysII::new(&key);
+    let d2 = DeoxysII::new(key.as_bytes());
 
     Ok(d2.seal(nonce, plaintext, additional_data))
 }
@@ -76,7 +67,7 @@ pub fn box_open(
 ) -> Result<Vec<u8>> {
     let key = derive_symmetric_key(peers_public_key, private_key);
 
-    let d2 = DeoxysII::new(&key);
+    let d2 = DeoxysII::new(key.as_bytes());
 
     d2.open(nonce, ciphertext, additional_data)
         .map_err(|err| err.into())
@@ -1,8 +1,7 @@
 //! MRAE primitives.
 extern crate deoxysii as deoxysii_rust;
-extern crate hmac;
-extern crate sha2;
 extern crate x25519_dalek;
 
 pub mod deoxysii;
 pub mod nonce;
+pub mod session;
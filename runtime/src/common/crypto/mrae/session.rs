@@ -0,0 +1,231 @@
+//! Deoxys-II session box with per-message key ratcheting.
+//!
+//! Unlike `deoxysii::box_seal`, which uses the same symmetric key for every
+//! message, a session box derives a separate chain key for each direction
+//! from an X25519 key agreement and ratchets it forward after every message.
+//! Old message keys are erased, so compromising the session state does not
+//! reveal previously exchanged messages.
+//!
+//! Messages must be opened in the order they were sealed.
+use anyhow::Result;
+use byteorder::{BigEndian, ByteOrder};
+use thiserror::Error;
+
+use super::{
+    deoxysii::{DeoxysII, KEY_SIZE, NONCE_SIZE, TAG_SIZE},
+    x25519_dalek::{PublicKey, StaticSecret},
+};
+use crate::common::crypto::{kdf::Hkdf, secret::Secret};
+
+/// Salt used when extracting the session secret.
+const SESSION_SALT: &[u8] = b"oasis-core/mrae: session box";
+/// Label of the chain key used by the initiator to seal messages.
+const INITIATOR_LABEL: &[u8] = b"initiator to responder";
+/// Label of the chain key used by the responder to seal messages.
+const RESPONDER_LABEL: &[u8] = b"responder to initiator";
+/// Label used to derive a message key from a chain key.
+const MESSAGE_KEY_LABEL: &[u8] = b"message key";
+/// Label used to derive the next chain key from a chain key.
+const CHAIN_KEY_LABEL: &[u8] = b"chain key";
+
+/// Size of the message counter prefixed to sealed messages.
+const COUNTER_SIZE: usize = 8;
+
+/// Session box errors.
+#[derive(Error, Debug)]
+pub enum SessionBoxError {
+    #[error("session box: key agreement produced a low-order point")]
+    LowOrderPoint,
+    #[error("session box: malformed message")]
+    MalformedMessage,
+    #[error("session box: unexpected message counter (expected {expected} got {actual})")]
+    UnexpectedCounter { expected: u64, actual: u64 },
+    #[error("session box: message authentication failed")]
+    AuthenticationFailed,
+    #[error("session box: message counter exhausted")]
+    CounterExhausted,
+}
+
+/// One direction of a session.
+struct Ratchet {
+    chain_key: Secret<32>,
+    counter: u64,
+}
+
+impl Ratchet {
+    /// Derive the key of the current message and the ratchet for the next
+    /// message.
+    fn step(&self) -> Result<(Secret<{ KEY_SIZE }>, Ratchet)> {
+        let counter = self
+            .counter
+            .checked_add(1)
+            .ok_or(SessionBoxError::CounterExhausted)?;
+
+        let kdf = Hkdf::from_prk(self.chain_key.as_ref())?;
+        let key = kdf.expand(MESSAGE_KEY_LABEL, &[]);
+        let next = Ratchet {
+            chain_key: kdf.expand(CHAIN_KEY_LABEL, &[]),
+            counter,
+        };
+        Ok((key, next))
+    }
+
+    fn nonce(&self) -> [u8; NONCE_SIZE] {
+        let mut nonce = [0u8; NONCE_SIZE];
+        BigEndian::write_u64(&mut nonce[NONCE_SIZE - COUNTER_SIZE..], self.counter);
+        nonce
+    }
+}
+
+/// A bidirectional authenticated encryption session between two parties.
+pub struct SessionBox {
+    send: Ratchet,
+    recv: Ratchet,
+}
+
+impl SessionBox {
+    /// Establish a session between the local private key and the peer's
+    /// public key.
+    ///
+    /// Exactly one of the parties must be the initiator, as the two
+    /// directions use different keys.
+    pub fn new(
+        private_key: &Secret<32>,
+        peers_public_key: &[u8; 32],
+        initiator: bool,
+    ) -> Result<Self> {
+        let private_key = StaticSecret::from(*private_key.as_bytes());
+        let public_key = PublicKey::from(&private_key);
+        let peers_public_key = PublicKey::from(*peers_public_key);
+
+        let shared = private_key.diffie_hellman(&peers_public_key);
+        if shared.as_bytes().iter().all(|b| *b == 0) {
+            return Err(SessionBoxError::LowOrderPoint.into());
+        }
+
+        // Bind the chain keys to both public keys.
+        let (initiator_pk, responder_pk) = if initiator {
+            (public_key.as_bytes(), peers_public_key.as_bytes())
+        } else {
+            (peers_public_key.as_bytes(), public_key.as_bytes())
+        };
+        let kdf = Hkdf::extract(SESSION_SALT, shared.as_bytes());
+        let context: &[&[u8]] = &[initiator_pk, responder_pk];
+        let initiator_key = Ratchet {
+            chain_key: kdf.expand(INITIATOR_LABEL, context),
+            counter: 0,
+        };
+        let responder_key = Ratchet {
+            chain_key: kdf.expand(RESPONDER_LABEL, context),
+            counter: 0,
+        };
+
+        let (send, recv) = if initiator {
+            (initiator_key, responder_key)
+        } else {
+            (responder_key, initiator_key)
+        };
+        Ok(Self { send, recv })
+    }
+
+    /// Seal the next message with the given additional data.
+    pub fn seal(&mut self, plaintext: Vec<u8>, additional_data: Vec<u8>) -> Result<Vec<u8>> {
+        let (key, next) = self.send.step()?;
+        let d2 = DeoxysII::new(key.as_bytes());
+
+        let mut result = vec![0u8; COUNTER_SIZE];
+        BigEndian::write_u64(&mut result, self.send.counter);
+        result.extend(d2.seal(&self.send.nonce(), plaintext, additional_data));
+
+        self.send = next;
+        Ok(result)
+    }
+
+    /// Open the next message with the given additional data.
+    ///
+    /// The session is only advanced if the message is authentic.
+    pub fn open(&mut self, sealed: &[u8], additional_data: Vec<u8>) -> Result<Vec<u8>> {
+        if sealed.len() < COUNTER_SIZE + TAG_SIZE {
+            return Err(SessionBoxError::MalformedMessage.into());
+        }
+        let counter = BigEndian::read_u64(&sealed[..COUNTER_SIZE]);
+        if counter != self.recv.counter {
+            return Err(SessionBoxError::UnexpectedCounter {
+                expected: self.recv.counter,
+                actual: counter,
+            }
+            .into());
+        }
+
+        let (key, next) = self.recv.step()?;
+        let d2 = DeoxysII::new(key.as_bytes());
+        let plaintext = d2
+            .open(
+                &self.recv.nonce(),
+                sealed[COUNTER_SIZE..].to_vec(),
+                additional_data,
+            )
+            .map_err(|_| SessionBoxError::AuthenticationFailed)?;
+
+        self.recv = next;
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::crypto::mrae::deoxysii::generate_key_pair;
+
+    fn session_pair() -> (SessionBox, SessionBox) {
+        let (a_pub, a_priv) = generate_key_pair();
+        let (b_pub, b_priv) = generate_key_pair();
+
+        let a = SessionBox::new(&Secret::new(a_priv), &b_pub, true).unwrap();
+        let b = SessionBox::new(&Secret::new(b_priv), &a_pub, false).unwrap();
+        (a, b)
+    }
+
+    #[test]
+    fn test_session_box() {
+        let (mut a, mut b) = session_pair();
+
+        // The two directions use different keys.
+        let first = a.seal(b"first".to_vec(), vec![]).unwrap();
+        assert!(a.open(&first, vec![]).is_err());
+        assert_eq!(b"first".to_vec(), b.open(&first, vec![]).unwrap());
+
+        let mut sealed = Vec::new();
+        for i in 0..3u8 {
+            let msg = a.seal(vec![i; 16], b"ad".to_vec()).unwrap();
+            assert_eq!(vec![i; 16], b.open(&msg, b"ad".to_vec()).unwrap());
+            sealed.push(msg);
+        }
+        // Every message is sealed under a different key.
+        let again = a.seal(vec![0; 16], b"ad".to_vec()).unwrap();
+        assert_ne!(sealed[0][COUNTER_SIZE..], again[COUNTER_SIZE..]);
+
+        // Replayed, reordered and tampered messages are rejected without
+        // advancing the session.
+        assert!(b.open(&sealed[0], b"ad".to_vec()).is_err());
+        let mut tampered = again.clone();
+        *tampered.last_mut().unwrap() ^= 0x01;
+        assert!(b.open(&tampered, b"ad".to_vec()).is_err());
+        assert!(b.open(&again, b"other".to_vec()).is_err());
+        assert!(b.open(&again[..COUNTER_SIZE], b"ad".to_vec()).is_err());
+        assert_eq!(vec![0; 16], b.open(&again, b"ad".to_vec()).unwrap());
+
+        let reply = b.seal(b"reply".to_vec(), vec![]).unwrap();
+        assert_eq!(b"reply".to_vec(), a.open(&reply, vec![]).unwrap());
+
+        // Sessions with other peers use different keys.
+        let (_, mut c) = session_pair();
+        assert!(c.open(&first, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_session_box_low_order() {
+        let (_, a_priv) = generate_key_pair();
+        assert!(SessionBox::new(&Secret::new(a_priv), &[0; 32], true).is_err());
+    }
+}
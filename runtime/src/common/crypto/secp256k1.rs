@@ -0,0 +1,233 @@
+//! Secp256k1 ECDSA signature verification.
+//!
+//! Only verification and public key recovery are supported, as needed by
+//! runtimes validating Ethereum-compatible transactions. Messages are 32-byte
+//! digests, usually computed with `keccak256`.
+use anyhow::Result;
+use thiserror::Error;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Size of a compressed public key in bytes.
+pub const PUBLIC_KEY_SIZE: usize = 33;
+/// Size of a signature in bytes.
+pub const SIGNATURE_SIZE: usize = 64;
+/// Size of a recoverable signature in bytes.
+pub const RECOVERABLE_SIGNATURE_SIZE: usize = 65;
+/// Size of an address in bytes.
+pub const ADDRESS_SIZE: usize = 20;
+
+impl_bytes!(
+    PublicKey,
+    PUBLIC_KEY_SIZE,
+    "A secp256k1 public key (compressed point)."
+);
+
+impl_bytes!(
+    Signature,
+    SIGNATURE_SIZE,
+    "A secp256k1 ECDSA signature (r || s)."
+);
+
+impl_bytes!(
+    RecoverableSignature,
+    RECOVERABLE_SIGNATURE_SIZE,
+    "A recoverable secp256k1 ECDSA signature (r || s || v)."
+);
+
+impl_bytes!(Address, ADDRESS_SIZE, "An Ethereum-compatible address.");
+
+/// Secp256k1 error.
+#[derive(Error, Debug)]
+pub enum Secp256k1Error {
+    #[error("secp256k1: malformed public key")]
+    MalformedPublicKey,
+    #[error("secp256k1: malformed signature")]
+    MalformedSignature,
+    #[error("secp256k1: malformed recovery id")]
+    MalformedRecoveryId,
+    #[error("secp256k1: signature malleability check failed")]
+    MalleabilityError,
+    #[error("secp256k1: signature verification failed")]
+    VerificationFailed,
+}
+
+/// Compute the Keccak-256 digest of the passed slice of bytes.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+
+    let mut result = [0u8; 32];
+    hasher.finalize(&mut result);
+    result
+}
+
+fn parse_signature(data: &[u8]) -> Result<secp256k1::Signature> {
+    let sig = secp256k1::Signature::parse_slice(&data[..SIGNATURE_SIZE])
+        .map_err(|_| Secp256k1Error::MalformedSignature)?;
+
+    // Reject signatures with a high s, as (r, -s) is also valid.
+    if sig.s.is_high() {
+        return Err(Secp256k1Error::MalleabilityError.into());
+    }
+    Ok(sig)
+}
+
+impl PublicKey {
+    /// Parse a public key in either the compressed or the uncompressed
+    /// encoding.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let pk = secp256k1::PublicKey::parse_slice(data, None)
+            .map_err(|_| Secp256k1Error::MalformedPublicKey)?;
+
+        Ok(PublicKey(pk.serialize_compressed()))
+    }
+
+    fn to_point(&self) -> Result<secp256k1::PublicKey> {
+        secp256k1::PublicKey::parse_compressed(&self.0)
+            .map_err(|_| Secp256k1Error::MalformedPublicKey.into())
+    }
+
+    /// Return the uncompressed encoding of the public key.
+    pub fn to_uncompressed(&self) -> Result<[u8; 65]> {
+        Ok(self.to_point()?.serialize())
+    }
+
+    /// Derive the Ethereum-compatible address of the public key.
+    pub fn to_address(&self) -> Result<Address> {
+        Ok(Address::from_uncompressed(&self.to_uncompressed()?))
+    }
+}
+
+impl Signature {
+    /// Verify the signature over the given message digest.
+    ///
+    /// Signatures with a high s value are rejected to prevent
+    /// malleability.
+    pub fn verify(&self, pk: &PublicKey, digest: &[u8; 32]) -> Result<()> {
+        let sig = parse_signature(self.as_ref())?;
+        let message = secp256k1::Message::parse(digest);
+        if !secp256k1::verify(&message, &sig, &pk.to_point()?) {
+            return Err(Secp256k1Error::VerificationFailed.into());
+        }
+        Ok(())
+    }
+}
+
+impl RecoverableSignature {
+    /// Recover the public key which produced the signature over the given
+    /// message digest.
+    ///
+    /// The recovery id may either be 0/1 or use the legacy Ethereum 27/28
+    /// encoding. Signatures with a high s value are rejected to prevent
+    /// malleability.
+    pub fn recover(&self, digest: &[u8; 32]) -> Result<PublicKey> {
+        let sig = parse_signature(self.as_ref())?;
+        let v = match self.0[SIGNATURE_SIZE] {
+            v @ 27..=28 => v - 27,
+            v => v,
+        };
+        let recovery_id =
+            secp256k1::RecoveryId::parse(v).map_err(|_| Secp256k1Error::MalformedRecoveryId)?;
+        let message = secp256k1::Message::parse(digest);
+        let pk = secp256k1::recover(&message, &sig, &recovery_id)
+            .map_err(|_| Secp256k1Error::VerificationFailed)?;
+
+        Ok(PublicKey(pk.serialize_compressed()))
+    }
+
+    /// Return the signature without the recovery id.
+    pub fn to_signature(&self) -> Signature {
+        Signature::from(&self.0[..SIGNATURE_SIZE])
+    }
+}
+
+impl Address {
+    /// Derive the address of an uncompressed public key, which is the last
+    /// 20 bytes of the Keccak-256 digest of the key without its prefix.
+    pub fn from_uncompressed(pk: &[u8; 65]) -> Self {
+        Address::from(&keccak256(&pk[1..])[32 - ADDRESS_SIZE..])
+    }
+
+    /// Format the address as hex with an EIP-55 mixed-case checksum.
+    pub fn to_checksum_string(&self) -> String {
+        let hex = format!("{:x}", self);
+        let digest = keccak256(hex.as_bytes());
+
+        let mut result = String::with_capacity(2 + hex.len());
+        result.push_str("0x");
+        for (i, c) in hex.chars().enumerate() {
+            let nibble = (digest[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 {
+                result.push(c.to_ascii_uppercase());
+            } else {
+                result.push(c);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secret_key() -> secp256k1::SecretKey {
+        let sk = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let sk: Vec<u8> = rustc_hex::FromHex::from_hex(sk).unwrap();
+        secp256k1::SecretKey::parse_slice(&sk).unwrap()
+    }
+
+    #[test]
+    fn test_address() {
+        let pk = secp256k1::PublicKey::from_secret_key(&secret_key());
+        let uncompressed = PublicKey::parse(&pk.serialize()).unwrap();
+        let compressed = PublicKey::parse(&pk.serialize_compressed()).unwrap();
+        assert_eq!(uncompressed, compressed);
+        assert!(PublicKey::parse(&[0x05; 33]).is_err());
+
+        let address = compressed.to_address().unwrap();
+        assert_eq!(
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23",
+            address.to_checksum_string()
+        );
+
+        // Test vector from EIP-55.
+        let address: Address = "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".parse().unwrap();
+        assert_eq!(
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            address.to_checksum_string()
+        );
+    }
+
+    #[test]
+    fn test_verify_recover() {
+        let sk = secret_key();
+        let pk = PublicKey(secp256k1::PublicKey::from_secret_key(&sk).serialize_compressed());
+        let digest = keccak256(b"message");
+        let (sig, recovery_id) = secp256k1::sign(&secp256k1::Message::parse(&digest), &sk);
+
+        let signature = Signature(sig.serialize());
+        signature.verify(&pk, &digest).expect("verify");
+        assert!(signature.verify(&pk, &keccak256(b"other")).is_err());
+
+        let mut recoverable = [0u8; RECOVERABLE_SIGNATURE_SIZE];
+        recoverable[..SIGNATURE_SIZE].copy_from_slice(&sig.serialize());
+        recoverable[SIGNATURE_SIZE] = recovery_id.serialize();
+        let recoverable = RecoverableSignature(recoverable);
+        assert_eq!(signature, recoverable.to_signature());
+        assert_eq!(pk, recoverable.recover(&digest).unwrap());
+        assert_ne!(pk, recoverable.recover(&keccak256(b"other")).unwrap());
+
+        let mut legacy = recoverable;
+        legacy.0[SIGNATURE_SIZE] += 27;
+        assert_eq!(pk, legacy.recover(&digest).unwrap());
+        legacy.0[SIGNATURE_SIZE] = 4;
+        assert!(legacy.recover(&digest).is_err());
+
+        // The high-s twin of a valid signature is rejected.
+        let mut high_s = secp256k1::Signature::parse_slice(&sig.serialize()).unwrap();
+        high_s.s = -high_s.s;
+        let high_s = Signature(high_s.serialize());
+        assert!(high_s.verify(&pk, &digest).is_err());
+    }
+}
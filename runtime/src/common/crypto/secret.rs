@@ -0,0 +1,209 @@
+//! Secret key material.
+use std::fmt;
+
+use rand::{rngs::OsRng, Rng};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+/// Secret errors.
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error("secret: invalid length (expected {expected} got {actual})")]
+    InvalidLength { expected: usize, actual: usize },
+}
+
+/// A fixed-size secret (e.g., a private or symmetric key).
+///
+/// The secret is zeroized when dropped and only compared in constant time.
+/// It is deliberately not `Copy` and its `Debug` implementation does not
+/// reveal its contents, so key material does not get duplicated or logged
+/// by accident.
+pub struct Secret<const N: usize>([u8; N]);
+
+impl<const N: usize> Secret<N> {
+    /// Size of the secret in bytes.
+    pub const fn len() -> usize {
+        N
+    }
+
+    /// Create a new secret from the given bytes.
+    pub fn new(bytes: [u8; N]) -> Self {
+        Secret(bytes)
+    }
+
+    /// Generate a new random secret.
+    pub fn generate() -> Self {
+        let mut secret = Self::default();
+        OsRng {}.fill(&mut secret.0[..]);
+        secret
+    }
+
+    /// Create a new secret by copying the given bytes.
+    pub fn from_slice(data: &[u8]) -> Result<Self, SecretError> {
+        if data.len() != N {
+            return Err(SecretError::InvalidLength {
+                expected: N,
+                actual: data.len(),
+            });
+        }
+
+        let mut secret = Self::default();
+        secret.0.copy_from_slice(data);
+        Ok(secret)
+    }
+
+    /// Return a reference to the secret bytes.
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+}
+
+impl<const N: usize> Default for Secret<N> {
+    fn default() -> Self {
+        Secret([0; N])
+    }
+}
+
+impl<const N: usize> Clone for Secret<N> {
+    fn clone(&self) -> Self {
+        Secret(self.0)
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Secret<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        Secret(bytes)
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for Secret<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> AsMut<[u8]> for Secret<N> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> PartialEq for Secret<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0[..].ct_eq(&other.0[..]).into()
+    }
+}
+
+impl<const N: usize> Eq for Secret<N> {}
+
+impl<const N: usize> Drop for Secret<N> {
+    fn drop(&mut self) {
+        self.0[..].zeroize();
+    }
+}
+
+impl<const N: usize> fmt::Debug for Secret<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret<{}>(<redacted>)", N)
+    }
+}
+
+impl<const N: usize> Serialize for Secret<N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            let mut encoded = base64::encode(&self.0[..]);
+            let result = serializer.serialize_str(&encoded);
+            encoded.zeroize();
+            result
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Secret<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SecretVisitor<const N: usize>;
+
+        impl<'de, const N: usize> de::Visitor<'de> for SecretVisitor<N> {
+            type Value = Secret<N>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "{} bytes or a base64-encoded string", N)
+            }
+
+            fn visit_bytes<E>(self, data: &[u8]) -> Result<Secret<N>, E>
+            where
+                E: de::Error,
+            {
+                if data.len() != N {
+                    return Err(de::Error::invalid_length(data.len(), &self));
+                }
+                Ok(Secret::from_slice(data).expect("length was checked"))
+            }
+
+            fn visit_byte_buf<E>(self, mut data: Vec<u8>) -> Result<Secret<N>, E>
+            where
+                E: de::Error,
+            {
+                let result = self.visit_bytes(&data);
+                data.zeroize();
+                result
+            }
+
+            fn visit_str<E>(self, data: &str) -> Result<Secret<N>, E>
+            where
+                E: de::Error,
+            {
+                let mut bytes = base64::decode(data)
+                    .map_err(|_| de::Error::custom("invalid base64-encoded string"))?;
+                let result = self.visit_bytes(&bytes);
+                bytes.zeroize();
+                result
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_string(SecretVisitor::<N>)
+        } else {
+            deserializer.deserialize_bytes(SecretVisitor::<N>)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::cbor;
+
+    #[test]
+    fn test_secret() {
+        let secret = Secret::<32>::new([1; 32]);
+        assert_eq!(secret, Secret::from([1; 32]));
+        assert_ne!(secret, Secret::default());
+        assert_ne!(Secret::<32>::generate(), Secret::default());
+        assert_eq!("Secret<32>(<redacted>)", format!("{:?}", secret));
+
+        assert!(Secret::<32>::from_slice(&[1; 31]).is_err());
+        assert_eq!(secret, Secret::from_slice(&[1; 32]).unwrap());
+
+        // Secrets serialize like the byte array types they replace.
+        let encoded = cbor::to_vec(&secret);
+        assert_eq!(cbor::to_vec(&serde_bytes::Bytes::new(&[1; 32])), encoded);
+        let decoded: Secret<32> = cbor::from_slice(&encoded).unwrap();
+        assert_eq!(secret, decoded);
+        assert!(cbor::from_slice::<Secret<16>>(&encoded).is_err());
+
+        let encoded = serde_json::to_string(&secret).unwrap();
+        let decoded: Secret<32> = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(secret, decoded);
+    }
+}
@@ -1,5 +1,5 @@
 //! Signature types.
-use std::io::Cursor;
+use std::{io::Cursor, sync::Arc};
 
 use anyhow::Result;
 use byteorder::{LittleEndian, ReadBytesExt};
@@ -22,6 +22,10 @@ impl_bytes!(
 enum SignatureError {
     #[error("signature malleability check failed")]
     MalleabilityError,
+    #[error("batch verification length mismatch")]
+    BatchLengthMismatch,
+    #[error("raw signatures not supported by signer")]
+    RawSigningUnsupported,
 }
 
 static CURVE_ORDER: &'static [u64] = &[
@@ -85,6 +89,14 @@ impl Signer for PrivateKey {
 
         Ok(Signature(self.0.sign(digest.as_ref()).to_bytes()))
     }
+
+    fn sign_raw(&self, message: &[u8]) -> Result<Signature> {
+        Ok(Signature(self.0.sign(message).to_bytes()))
+    }
+
+    fn public_key(&self) -> Result<PublicKey> {
+        Ok(PrivateKey::public_key(self))
+    }
 }
 
 impl_bytes!(Signature, 64, "An Ed25519 signature.");
@@ -93,8 +105,14 @@ impl Signature {
     /// Verify signature.
     pub fn verify(&self, pk: &PublicKey, context: &[u8], message: &[u8]) -> Result<()> {
         // TODO/#2103: Replace this with Ed25519ctx.
-        let pk = ed25519_dalek::PublicKey::from_bytes(pk.as_ref()).unwrap();
         let digest = Hash::digest_bytes_list(&[context, message]);
+        self.verify_raw(pk, digest.as_ref())
+    }
+
+    /// Verify a plain Ed25519 signature over the message, see
+    /// `Signer::sign_raw`.
+    pub fn verify_raw(&self, pk: &PublicKey, message: &[u8]) -> Result<()> {
+        let pk = ed25519_dalek::PublicKey::from_bytes(pk.as_ref())?;
         let sig_slice = self.as_ref();
         let sig = ed25519_dalek::Signature::from_bytes(sig_slice).unwrap();
 
@@ -104,8 +122,53 @@ impl Signature {
             return Err(SignatureError::MalleabilityError.into());
         }
 
-        Ok(pk.verify(digest.as_ref(), &sig)?)
+        Ok(pk.verify(message, &sig)?)
+    }
+}
+
+/// Verify a batch of signatures.
+///
+/// The `i`-th signature is checked against the `i`-th context, message and
+/// public key and the call only succeeds if all signatures are valid. This
+/// is significantly faster than verifying each signature separately, but
+/// does not report which signature is invalid.
+///
+/// As batch verification uses the cofactored verification equation, it may
+/// accept some signatures with small-order components that `Signature::verify`
+/// rejects. Such signatures can only be produced deliberately.
+pub fn verify_batch(
+    contexts: &[&[u8]],
+    messages: &[&[u8]],
+    signatures: &[Signature],
+    public_keys: &[PublicKey],
+) -> Result<()> {
+    let count = signatures.len();
+    if contexts.len() != count || messages.len() != count || public_keys.len() != count {
+        return Err(SignatureError::BatchLengthMismatch.into());
+    }
+
+    // TODO/#2103: Replace this with Ed25519ctx.
+    let digests: Vec<Hash> = contexts
+        .iter()
+        .zip(messages)
+        .map(|(context, message)| Hash::digest_bytes_list(&[*context, *message]))
+        .collect();
+    let digests: Vec<&[u8]> = digests.iter().map(|digest| digest.as_ref()).collect();
+
+    let mut sigs = Vec::with_capacity(count);
+    for sig in signatures {
+        let sig_slice = sig.as_ref();
+        if !sc_minimal(&sig_slice[32..]) {
+            return Err(SignatureError::MalleabilityError.into());
+        }
+        sigs.push(ed25519_dalek::Signature::from_bytes(sig_slice)?);
     }
+    let pks = public_keys
+        .iter()
+        .map(|pk| ed25519_dalek::PublicKey::from_bytes(pk.as_ref()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(ed25519_dalek::verify_batch(&digests, &sigs, &pks)?)
 }
 
 /// A signature bundled with a public key.
@@ -118,9 +181,66 @@ pub struct SignatureBundle {
 }
 
 /// A abstract signer.
+///
+/// Besides the in-memory `PrivateKey`, signers may be backed by e.g. an HSM
+/// or the host, in which case both methods may fail.
 pub trait Signer: Send + Sync {
     /// Generates a signature over the context and message.
     fn sign(&self, context: &[u8], message: &[u8]) -> Result<Signature>;
+
+    /// Generates a plain Ed25519 signature over the message, without a
+    /// context.
+    ///
+    /// This is only meant for protocols mandating such signatures, whose
+    /// messages must then be domain separated by the protocol itself.
+    /// Signers need not support it.
+    fn sign_raw(&self, message: &[u8]) -> Result<Signature> {
+        let _ = message;
+        Err(SignatureError::RawSigningUnsupported.into())
+    }
+
+    /// Returns the public key corresponding to the signer.
+    fn public_key(&self) -> Result<PublicKey>;
+}
+
+/// A signer bound to a single signature context.
+///
+/// Handing out a context signer instead of the underlying signer limits its
+/// holder to producing signatures in one domain.
+#[derive(Clone)]
+pub struct ContextSigner {
+    signer: Arc<dyn Signer>,
+    context: Vec<u8>,
+}
+
+impl ContextSigner {
+    /// Bind the given signer to the given context.
+    pub fn new(signer: Arc<dyn Signer>, context: &[u8]) -> Self {
+        Self {
+            signer,
+            context: context.to_vec(),
+        }
+    }
+
+    /// Returns the context the signer is bound to.
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    /// Generates a signature over the message.
+    pub fn sign(&self, message: &[u8]) -> Result<Signature> {
+        self.signer.sign(&self.context, message)
+    }
+
+    /// Returns the public key corresponding to the signer.
+    pub fn public_key(&self) -> Result<PublicKey> {
+        self.signer.public_key()
+    }
+
+    /// Verify a signature produced by the signer over the message.
+    pub fn verify(&self, signature: &Signature, message: &[u8]) -> Result<()> {
+        signature.verify(&self.public_key()?, &self.context, message)
+    }
 }
 
 // Check if s < L, per RFC 8032, inspired by the Go runtime library's version
@@ -149,6 +269,8 @@ fn sc_minimal(raw_s: &[u8]) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use rustc_hex::{FromHex, ToHex};
+
     use super::*;
 
     #[test]
@@ -217,6 +339,74 @@ mod tests {
         ]))
     }
 
+    #[test]
+    fn test_verify_batch() {
+        let keys: Vec<_> = (0..8)
+            .map(|i| PrivateKey::from_test_seed(format!("batch {}", i)))
+            .collect();
+        let contexts: Vec<&[u8]> = vec![&b"oasis-core/test: batch"[..]; keys.len()];
+        let messages: Vec<Vec<u8>> = (0..keys.len()).map(|i| vec![i as u8; i]).collect();
+        let messages: Vec<&[u8]> = messages.iter().map(|m| &m[..]).collect();
+        let mut signatures: Vec<_> = keys
+            .iter()
+            .zip(&messages)
+            .map(|(key, message)| key.sign(contexts[0], message).unwrap())
+            .collect();
+        let public_keys: Vec<_> = keys.iter().map(|key| key.public_key()).collect();
+
+        verify_batch(&contexts, &messages, &signatures, &public_keys).expect("batch verification");
+        verify_batch(&[], &[], &[], &[]).expect("empty batch verification");
+        assert!(verify_batch(&contexts[1..], &messages, &signatures, &public_keys).is_err());
+
+        // A single different context or signature invalidates the batch.
+        let mut other_contexts = contexts.clone();
+        other_contexts[3] = b"oasis-core/test: other";
+        assert!(verify_batch(&other_contexts, &messages, &signatures, &public_keys).is_err());
+        signatures.swap(1, 2);
+        assert!(verify_batch(&contexts, &messages, &signatures, &public_keys).is_err());
+    }
+
+    #[test]
+    fn test_context_signer() {
+        let key = PrivateKey::from_test_seed("context signer".to_owned());
+        let public_key = key.public_key();
+        let signer = ContextSigner::new(Arc::new(key), b"oasis-core/test: context");
+        assert_eq!(public_key, signer.public_key().unwrap());
+
+        let signature = signer.sign(b"message").unwrap();
+        signer.verify(&signature, b"message").expect("verify");
+        signature
+            .verify(&public_key, b"oasis-core/test: context", b"message")
+            .expect("verify");
+        assert!(signature
+            .verify(&public_key, b"oasis-core/test: other", b"message")
+            .is_err());
+    }
+
+    #[test]
+    fn test_sign_raw() {
+        // Test vector 1 from RFC 8032.
+        let key = PrivateKey::from_bytes(
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+                .from_hex()
+                .unwrap(),
+        );
+        let public_key = key.public_key();
+        assert_eq!(
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            public_key.as_ref().to_hex::<String>()
+        );
+
+        let signature = key.sign_raw(b"").unwrap();
+        assert_eq!(
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+            signature.as_ref().to_hex::<String>()
+        );
+        signature.verify_raw(&public_key, b"").expect("verify_raw");
+        assert!(signature.verify_raw(&public_key, b"other").is_err());
+        assert!(signature.verify(&public_key, b"", b"").is_err());
+    }
+
     #[test]
     fn test_private_key_to_bytes() {
         let secret = PrivateKey::generate();
@@ -0,0 +1,248 @@
+//! Verifiable random function.
+//!
+//! This implements ECVRF-EDWARDS25519-SHA512-TAI from RFC 9381, using Ed25519
+//! keys, so the node identity key can be used to produce verifiable
+//! randomness (e.g., for leader or committee election).
+use anyhow::Result;
+use curve25519_dalek::{
+    constants::ED25519_BASEPOINT_POINT,
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    scalar::Scalar,
+};
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+
+use super::signature::{PrivateKey, PublicKey};
+
+/// Suite string of ECVRF-EDWARDS25519-SHA512-TAI.
+const SUITE: u8 = 0x03;
+/// Size of the challenge in bytes.
+const CHALLENGE_SIZE: usize = 16;
+
+/// Size of a proof in bytes.
+pub const PROOF_SIZE: usize = 80;
+/// Size of a VRF output in bytes.
+pub const OUTPUT_SIZE: usize = 64;
+
+impl_bytes!(Proof, PROOF_SIZE, "An ECVRF-Edwards25519 proof.");
+
+impl_bytes!(Output, OUTPUT_SIZE, "An ECVRF-Edwards25519 output (hash).");
+
+/// VRF error.
+#[derive(Error, Debug)]
+pub enum VrfError {
+    #[error("vrf: malformed public key")]
+    MalformedPublicKey,
+    #[error("vrf: malformed proof")]
+    MalformedProof,
+    #[error("vrf: hash to curve failed")]
+    HashToCurveFailed,
+    #[error("vrf: proof verification failed")]
+    VerificationFailed,
+}
+
+/// Decode a point, rejecting non-canonical encodings.
+fn decode_point(data: &[u8]) -> Option<EdwardsPoint> {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(data);
+    let point = CompressedEdwardsY(bytes).decompress()?;
+    if point.compress().to_bytes() != bytes {
+        return None;
+    }
+    Some(point)
+}
+
+/// ECVRF_encode_to_curve_try_and_increment.
+fn hash_to_curve(pk: &PublicKey, alpha: &[u8]) -> Result<EdwardsPoint> {
+    for ctr in 0..=255u8 {
+        let hash = Sha512::new()
+            .chain(&[SUITE, 0x01])
+            .chain(pk.as_ref())
+            .chain(alpha)
+            .chain(&[ctr, 0x00])
+            .finalize();
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&hash[..32]);
+        if let Some(point) = CompressedEdwardsY(bytes).decompress() {
+            return Ok(point.mul_by_cofactor());
+        }
+    }
+    Err(VrfError::HashToCurveFailed.into())
+}
+
+/// ECVRF_challenge_generation.
+fn challenge(points: &[&EdwardsPoint]) -> Scalar {
+    let hash = points
+        .iter()
+        .fold(Sha512::new().chain(&[SUITE, 0x02]), |hasher, point| {
+            hasher.chain(point.compress().as_bytes())
+        })
+        .chain(&[0x00])
+        .finalize();
+
+    let mut bytes = [0u8; 32];
+    bytes[..CHALLENGE_SIZE].copy_from_slice(&hash[..CHALLENGE_SIZE]);
+    Scalar::from_bytes_mod_order(bytes)
+}
+
+/// Decode a proof into (Gamma, c, s).
+fn decode_proof(proof: &Proof) -> Result<(EdwardsPoint, Scalar, Scalar)> {
+    let gamma = decode_point(&proof.0[..32]).ok_or(VrfError::MalformedProof)?;
+
+    let mut c = [0u8; 32];
+    c[..CHALLENGE_SIZE].copy_from_slice(&proof.0[32..32 + CHALLENGE_SIZE]);
+    let c = Scalar::from_bytes_mod_order(c);
+
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&proof.0[32 + CHALLENGE_SIZE..]);
+    let s = Scalar::from_canonical_bytes(s).ok_or(VrfError::MalformedProof)?;
+
+    Ok((gamma, c, s))
+}
+
+/// Generate a VRF proof for the given input.
+pub fn prove(sk: &PrivateKey, alpha: &[u8]) -> Result<Proof> {
+    // Derive the secret scalar and nonce key as in RFC 8032.
+    let hashed_sk = Sha512::digest(sk.0.secret.as_bytes());
+    let mut sk_scalar = [0u8; 32];
+    sk_scalar.copy_from_slice(&hashed_sk[..32]);
+    sk_scalar[0] &= 248;
+    sk_scalar[31] &= 127;
+    sk_scalar[31] |= 64;
+    let sk_scalar = Scalar::from_bytes_mod_order(sk_scalar);
+
+    let pk = sk.public_key();
+    let pk_point = ED25519_BASEPOINT_POINT * sk_scalar;
+    let h_point = hash_to_curve(&pk, alpha)?;
+    let gamma = h_point * sk_scalar;
+
+    // ECVRF_nonce_generation_RFC8032.
+    let mut nonce_hash = [0u8; 64];
+    nonce_hash.copy_from_slice(
+        &Sha512::new()
+            .chain(&hashed_sk[32..])
+            .chain(h_point.compress().as_bytes())
+            .finalize(),
+    );
+    let nonce = Scalar::from_bytes_mod_order_wide(&nonce_hash);
+
+    let c = challenge(&[
+        &pk_point,
+        &h_point,
+        &gamma,
+        &(ED25519_BASEPOINT_POINT * nonce),
+        &(h_point * nonce),
+    ]);
+    let s = nonce + c * sk_scalar;
+
+    let mut proof = Proof::default();
+    proof.0[..32].copy_from_slice(gamma.compress().as_bytes());
+    proof.0[32..32 + CHALLENGE_SIZE].copy_from_slice(&c.as_bytes()[..CHALLENGE_SIZE]);
+    proof.0[32 + CHALLENGE_SIZE..].copy_from_slice(s.as_bytes());
+    Ok(proof)
+}
+
+/// Compute the VRF output from a proof, without verifying it.
+///
+/// Only use this on proofs that are known to be valid, otherwise use
+/// `verify`.
+pub fn proof_to_hash(proof: &Proof) -> Result<Output> {
+    let (gamma, _, _) = decode_proof(proof)?;
+    let hash = Sha512::new()
+        .chain(&[SUITE, 0x03])
+        .chain(gamma.mul_by_cofactor().compress().as_bytes())
+        .chain(&[0x00])
+        .finalize();
+
+    Ok(Output::from(&hash[..]))
+}
+
+/// Verify a VRF proof for the given input and return the VRF output.
+pub fn verify(pk: &PublicKey, proof: &Proof, alpha: &[u8]) -> Result<Output> {
+    let pk_point = decode_point(pk.as_ref()).ok_or(VrfError::MalformedPublicKey)?;
+    if pk_point.is_small_order() {
+        return Err(VrfError::MalformedPublicKey.into());
+    }
+    let (gamma, c, s) = decode_proof(proof)?;
+
+    let h_point = hash_to_curve(pk, alpha)?;
+    let u = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-c, &pk_point, &s);
+    let v = h_point * s - gamma * c;
+    if challenge(&[&pk_point, &h_point, &gamma, &u, &v]) != c {
+        return Err(VrfError::VerificationFailed.into());
+    }
+
+    proof_to_hash(proof)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn from_hex<T: From<Vec<u8>>>(data: &str) -> T {
+        let data: Vec<u8> = rustc_hex::FromHex::from_hex(data).unwrap();
+        T::from(data)
+    }
+
+    #[test]
+    fn test_vectors() {
+        // Test vectors from RFC 9381, Appendix B.3.
+        let vectors = [
+            (
+                "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+                "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+                "",
+                "8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805",
+                "90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae",
+            ),
+            (
+                "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+                "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+                "72",
+                "f3141cd382dc42909d19ec5110469e4feae18300e94f304590abdced48aed5933bf0864a62558b3ed7f2fea45c92a465301b3bbf5e3e54ddf2d935be3b67926da3ef39226bbc355bdc9850112c8f4b02",
+                "eb4440665d3891d668e7e0fcaf587f1b4bd7fbfe99d0eb2211ccec90496310eb5e33821bc613efb94db5e5b54c70a848a0bef4553a41befc57663b56373a5031",
+            ),
+            (
+                "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+                "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+                "af82",
+                "9bc0f79119cc5604bf02d23b4caede71393cedfbb191434dd016d30177ccbf8096bb474e53895c362d8628ee9f9ea3c0e52c7a5c691b6c18c9979866568add7a2d41b00b05081ed0f58ee5e31b3a970e",
+                "645427e5d00c62a23fb703732fa5d892940935942101e456ecca7bb217c61c452118fec1219202a0edcf038bb6373241578be7217ba85a2687f7a0310b2df19f",
+            ),
+        ];
+
+        for (sk, pk, alpha, pi, beta) in vectors.iter() {
+            let sk = PrivateKey::from_bytes(from_hex(sk));
+            let pk: PublicKey = from_hex(pk);
+            let alpha: Vec<u8> = from_hex(alpha);
+            let pi: Proof = from_hex(pi);
+            let beta: Output = from_hex(beta);
+
+            assert_eq!(pk, sk.public_key());
+            assert_eq!(pi, prove(&sk, &alpha).unwrap());
+            assert_eq!(beta, proof_to_hash(&pi).unwrap());
+            assert_eq!(beta, verify(&pk, &pi, &alpha).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_verify_invalid() {
+        let sk = PrivateKey::from_test_seed("vrf".to_owned());
+        let pk = sk.public_key();
+        let proof = prove(&sk, b"epoch 1").unwrap();
+        verify(&pk, &proof, b"epoch 1").expect("verify");
+
+        assert!(verify(&pk, &proof, b"epoch 2").is_err());
+        let other = PrivateKey::from_test_seed("other".to_owned()).public_key();
+        assert!(verify(&other, &proof, b"epoch 1").is_err());
+        for i in &[0, 32, 48, 79] {
+            let mut tampered = proof;
+            tampered.0[*i] ^= 0x01;
+            assert!(verify(&pk, &tampered, b"epoch 1").is_err());
+        }
+        // The identity point is a small-order public key.
+        let mut identity = PublicKey::default();
+        identity.0[0] = 0x01;
+        assert!(verify(&identity, &proof, b"epoch 1").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+//! Pedersen verifiable secret sharing over Ristretto.
+//!
+//! A dealer splits a secret into shares, the evaluations of a random
+//! polynomial of degree `threshold - 1`, and publishes Pedersen commitments
+//! to the polynomial coefficients. The commitments allow each participant to
+//! verify its share without learning anything about the secret, and any
+//! `threshold` valid shares are enough to reconstruct it.
+//!
+//! A secret can be generated jointly by a committee without a trusted dealer:
+//! every member deals a random secret and each participant adds up the
+//! verified shares it has received (see `combine`). The joint secret is the
+//! sum of all dealt secrets, so it is not known to any single dealer.
+use anyhow::Result;
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+    traits::Identity,
+};
+use lazy_static::lazy_static;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::secret::Secret;
+
+lazy_static! {
+    /// Blinding generator of the Pedersen commitments, whose discrete
+    /// logarithm with respect to the basepoint is unknown.
+    static ref BLINDING_GENERATOR: RistrettoPoint =
+        RistrettoPoint::hash_from_bytes::<Sha512>(b"oasis-core/vss: blinding generator");
+}
+
+/// Index of a participant (starting at 1).
+pub type Index = u32;
+
+impl_bytes!(
+    Commitment,
+    32,
+    "A Pedersen commitment to a polynomial coefficient (compressed Ristretto point)."
+);
+
+/// VSS error.
+#[derive(Error, Debug)]
+pub enum VssError {
+    #[error("vss: invalid threshold {threshold} for {participants} participants")]
+    InvalidThreshold { threshold: u32, participants: u32 },
+    #[error("vss: invalid participant index {0}")]
+    InvalidIndex(Index),
+    #[error("vss: malformed commitment")]
+    MalformedCommitment,
+    #[error("vss: malformed share")]
+    MalformedShare,
+    #[error("vss: share verification failed")]
+    VerificationFailed,
+    #[error("vss: mismatched commitments")]
+    MismatchedCommitments,
+    #[error("vss: not enough shares (expected {expected} got {actual})")]
+    NotEnoughShares { expected: usize, actual: usize },
+    #[error("vss: duplicate share of participant {0}")]
+    DuplicateShare(Index),
+}
+
+/// A share of a secret.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Share {
+    /// Index of the participant holding the share.
+    pub index: Index,
+    /// Evaluation of the secret polynomial at the index.
+    pub value: Secret<32>,
+    /// Evaluation of the blinding polynomial at the index.
+    pub blinding: Secret<32>,
+}
+
+impl Share {
+    fn new(index: Index, value: Scalar, blinding: Scalar) -> Self {
+        Self {
+            index,
+            value: Secret::new(value.to_bytes()),
+            blinding: Secret::new(blinding.to_bytes()),
+        }
+    }
+
+    fn scalars(&self) -> Result<(Scalar, Scalar)> {
+        let value =
+            Scalar::from_canonical_bytes(*self.value.as_bytes()).ok_or(VssError::MalformedShare)?;
+        let blinding = Scalar::from_canonical_bytes(*self.blinding.as_bytes())
+            .ok_or(VssError::MalformedShare)?;
+        Ok((value, blinding))
+    }
+
+    /// Add another share of the same participant.
+    pub fn add(&self, other: &Share) -> Result<Share> {
+        if self.index != other.index {
+            return Err(VssError::InvalidIndex(other.index).into());
+        }
+
+        let (mut value, mut blinding) = self.scalars()?;
+        let (mut other_value, mut other_blinding) = other.scalars()?;
+        let share = Share::new(self.index, value + other_value, blinding + other_blinding);
+        value.zeroize();
+        blinding.zeroize();
+        other_value.zeroize();
+        other_blinding.zeroize();
+
+        Ok(share)
+    }
+}
+
+/// Commitments to the coefficients of a dealt polynomial, starting with the
+/// constant term.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Commitments(pub Vec<Commitment>);
+
+impl Commitments {
+    /// Number of shares required to reconstruct the secret.
+    pub fn threshold(&self) -> usize {
+        self.0.len()
+    }
+
+    fn points(&self) -> Result<Vec<RistrettoPoint>> {
+        self.0
+            .iter()
+            .map(|c| {
+                CompressedRistretto(c.0)
+                    .decompress()
+                    .ok_or_else(|| VssError::MalformedCommitment.into())
+            })
+            .collect()
+    }
+
+    /// Verify that the share is consistent with the commitments.
+    pub fn verify(&self, share: &Share) -> Result<()> {
+        if share.index == 0 {
+            return Err(VssError::InvalidIndex(share.index).into());
+        }
+
+        // Evaluate the committed polynomial at the index (Horner's method).
+        let x = Scalar::from(share.index as u64);
+        let expected = self
+            .points()?
+            .iter()
+            .rev()
+            .fold(RistrettoPoint::identity(), |acc, point| acc * x + point);
+
+        let (mut value, mut blinding) = share.scalars()?;
+        let actual = RISTRETTO_BASEPOINT_POINT * value + *BLINDING_GENERATOR * blinding;
+        value.zeroize();
+        blinding.zeroize();
+
+        if actual != expected {
+            return Err(VssError::VerificationFailed.into());
+        }
+        Ok(())
+    }
+
+    /// Add the commitments of another dealing with the same threshold.
+    pub fn add(&self, other: &Commitments) -> Result<Commitments> {
+        if self.threshold() != other.threshold() {
+            return Err(VssError::MismatchedCommitments.into());
+        }
+
+        Ok(Commitments(
+            self.points()?
+                .iter()
+                .zip(other.points()?.iter())
+                .map(|(a, b)| Commitment((a + b).compress().to_bytes()))
+                .collect(),
+        ))
+    }
+}
+
+/// Deal a random secret to the given number of participants, so that any
+/// `threshold` of them can reconstruct it.
+///
+/// Returns the commitments to the dealt polynomial and the shares of the
+/// participants, ordered by index.
+pub fn deal(threshold: u32, participants: u32) -> Result<(Commitments, Vec<Share>)> {
+    if threshold == 0 || threshold > participants {
+        return Err(VssError::InvalidThreshold {
+            threshold,
+            participants,
+        }
+        .into());
+    }
+
+    let mut rng = OsRng {};
+    let mut coefficients: Vec<(Scalar, Scalar)> = (0..threshold)
+        .map(|_| (Scalar::random(&mut rng), Scalar::random(&mut rng)))
+        .collect();
+
+    let commitments = Commitments(
+        coefficients
+            .iter()
+            .map(|(a, b)| {
+                let point = RISTRETTO_BASEPOINT_POINT * a + *BLINDING_GENERATOR * b;
+                Commitment(point.compress().to_bytes())
+            })
+            .collect(),
+    );
+
+    let shares = (1..=participants)
+        .map(|index| {
+            let x = Scalar::from(index as u64);
+            let (value, blinding) = coefficients
+                .iter()
+                .rev()
+                .fold((Scalar::zero(), Scalar::zero()), |(v, b), (ca, cb)| {
+                    (v * x + ca, b * x + cb)
+                });
+            Share::new(index, value, blinding)
+        })
+        .collect();
+
+    for (a, b) in coefficients.iter_mut() {
+        a.zeroize();
+        b.zeroize();
+    }
+
+    Ok((commitments, shares))
+}
+
+/// Verify the shares of a participant received from each dealer and combine
+/// them into the participant's share of the joint secret.
+///
+/// Returns the commitments to the joint polynomial (which are the same for
+/// all participants) and the combined share.
+pub fn combine(dealings: &[(Commitments, Share)]) -> Result<(Commitments, Share)> {
+    let (first_commitments, first_share) = match dealings.first() {
+        Some(dealing) => dealing,
+        None => {
+            return Err(VssError::NotEnoughShares {
+                expected: 1,
+                actual: 0,
+            }
+            .into())
+        }
+    };
+    first_commitments.verify(first_share)?;
+
+    let mut commitments = first_commitments.clone();
+    let mut share = first_share.clone();
+    for (dealer_commitments, dealer_share) in &dealings[1..] {
+        dealer_commitments.verify(dealer_share)?;
+        commitments = commitments.add(dealer_commitments)?;
+        share = share.add(dealer_share)?;
+    }
+
+    Ok((commitments, share))
+}
+
+/// Reconstruct a secret from at least `threshold` shares.
+///
+/// The shares should be verified beforehand, as invalid shares result in
+/// an incorrect secret.
+pub fn reconstruct(shares: &[Share], threshold: usize) -> Result<Secret<32>> {
+    if threshold == 0 || shares.len() < threshold {
+        return Err(VssError::NotEnoughShares {
+            expected: threshold,
+            actual: shares.len(),
+        }
+        .into());
+    }
+    let shares = &shares[..threshold];
+
+    let mut indices: Vec<Index> = Vec::with_capacity(threshold);
+    for share in shares {
+        if share.index == 0 {
+            return Err(VssError::InvalidIndex(share.index).into());
+        }
+        if indices.contains(&share.index) {
+            return Err(VssError::DuplicateShare(share.index).into());
+        }
+        indices.push(share.index);
+    }
+
+    // Lagrange interpolation at zero.
+    let mut secret = Scalar::zero();
+    for share in shares {
+        let xi = Scalar::from(share.index as u64);
+        let lambda = indices
+            .iter()
+            .filter(|&&index| index != share.index)
+            .map(|&index| Scalar::from(index as u64))
+            .fold(Scalar::one(), |acc, xj| acc * xj * (xj - xi).invert());
+
+        let (mut value, _) = share.scalars()?;
+        secret += lambda * value;
+        value.zeroize();
+    }
+
+    let result = Secret::new(secret.to_bytes());
+    secret.zeroize();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vss() {
+        assert!(deal(0, 3).is_err());
+        assert!(deal(4, 3).is_err());
+
+        let (commitments, shares) = deal(3, 5).unwrap();
+        assert_eq!(3, commitments.threshold());
+        assert_eq!(5, shares.len());
+        for share in &shares {
+            commitments.verify(share).unwrap();
+        }
+
+        let mut tampered = shares[0].clone();
+        tampered.index = 2;
+        assert!(commitments.verify(&tampered).is_err());
+
+        // Any threshold shares reconstruct the same secret.
+        let secret = reconstruct(&shares[..3], 3).unwrap();
+        let others = vec![shares[4].clone(), shares[1].clone(), shares[3].clone()];
+        assert_eq!(secret, reconstruct(&others, 3).unwrap());
+        assert_ne!(secret, reconstruct(&shares[..2], 2).unwrap());
+        assert!(reconstruct(&shares[..2], 3).is_err());
+        let duplicate = vec![shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(reconstruct(&duplicate, 3).is_err());
+    }
+
+    #[test]
+    fn test_vss_joint() {
+        // Three dealers, each dealing to the same three participants.
+        let dealings: Vec<(Commitments, Vec<Share>)> =
+            (0..3).map(|_| deal(2, 3).unwrap()).collect();
+
+        let joint: Vec<(Commitments, Share)> = (0..3)
+            .map(|i| {
+                let received: Vec<(Commitments, Share)> = dealings
+                    .iter()
+                    .map(|(commitments, shares)| (commitments.clone(), shares[i].clone()))
+                    .collect();
+                combine(&received).unwrap()
+            })
+            .collect();
+
+        // All participants agree on the joint commitments.
+        let commitments = &joint[0].0;
+        for (other, share) in &joint {
+            assert_eq!(commitments, other);
+            commitments.verify(share).unwrap();
+        }
+
+        // The joint secret is the sum of the dealt secrets.
+        let expected = dealings
+            .iter()
+            .map(|(_, shares)| {
+                let secret = reconstruct(shares, 2).unwrap();
+                Scalar::from_canonical_bytes(*secret.as_bytes()).unwrap()
+            })
+            .fold(Scalar::zero(), |acc, secret| acc + secret);
+        let shares: Vec<Share> = joint.into_iter().map(|(_, share)| share).collect();
+        let secret = reconstruct(&shares[1..], 2).unwrap();
+        assert_eq!(expected.to_bytes(), *secret.as_bytes());
+
+        // Shares from a dealer with a different threshold are rejected.
+        let (other_commitments, other_shares) = deal(3, 3).unwrap();
+        let received = vec![
+            (dealings[0].0.clone(), dealings[0].1[0].clone()),
+            (other_commitments, other_shares[0].clone()),
+        ];
+        assert!(combine(&received).is_err());
+    }
+}
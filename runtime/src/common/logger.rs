@@ -1,23 +1,180 @@
 //! Logging subsystem for runtimes.
-use std::sync::{Mutex, Once};
+//!
+//! Records are structured (level, module, message and fields) and filtered
+//! per module. Once the runtime host supports it, records are forwarded to
+//! the host over the runtime host protocol, otherwise they are written to
+//! standard error as JSON.
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, Once, RwLock},
+    thread,
+};
 
+use anyhow::Result;
+use crossbeam::channel;
 use lazy_static::lazy_static;
 use log::Level;
-use slog::{self, Drain};
+use serde::{Deserialize, Serialize};
+use slog::{self, Drain, KV};
 use slog_scope;
 use slog_stdlog;
 
+use super::metrics::{self, Counter};
+
+/// Maximum number of records waiting to be forwarded. Further records are
+/// written to standard error instead.
+const FORWARD_BACKLOG_SIZE: usize = 1024;
+/// Maximum number of records forwarded at once.
+const MAX_FORWARD_BATCH_SIZE: usize = 64;
+
 lazy_static! {
-    static ref LOGGER: slog::Logger = slog::Logger::root(
-        Mutex::new(slog_json::Json::default(std::io::stderr())).map(slog::Fuse),
-        o!()
-    );
+    static ref LOGGER: slog::Logger = slog::Logger::root(HostDrain::new(), o!());
+
+    /// Logging configuration, set once by `init_logger_with_config`.
+    static ref CONFIG: RwLock<LoggerConfig> = RwLock::new(LoggerConfig::default());
+
+    /// Queue of records to forward, if forwarding is enabled.
+    static ref FORWARDER: RwLock<Option<channel::Sender<LogRecord>>> = RwLock::new(None);
 
     /// Initializes the global logger once.
     static ref INIT_GLOBAL_LOGGER: Once = Once::new();
 
     /// Prevents the global logger from being dropped.
     static ref GLOBAL_LOGGER_SCOPE_GUARD: Mutex<Option<slog_scope::GlobalLoggerGuard>> = Mutex::new(None);
+
+    static ref DROPPED_RECORDS: Arc<Counter> = metrics::registry().counter(
+        "oasis_runtime_log_records_dropped",
+        "Number of log records dropped as they could not be forwarded to the host.",
+    );
+}
+
+/// Logging configuration.
+#[derive(Clone, Debug)]
+pub struct LoggerConfig {
+    /// Minimum level of logged records.
+    pub level: Level,
+    /// Minimum level of logged records per module, overriding `level`.
+    ///
+    /// An entry for a module also applies to its submodules, e.g., an entry
+    /// for `runtime` applies to `runtime/protocol`. The longest matching
+    /// entry is used.
+    pub module_levels: BTreeMap<String, Level>,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            level: Level::Info,
+            module_levels: BTreeMap::new(),
+        }
+    }
+}
+
+impl LoggerConfig {
+    /// Return the minimum level of logged records for the given module.
+    pub fn level_for(&self, module: &str) -> Level {
+        self.module_levels
+            .iter()
+            .filter(|(prefix, _)| {
+                module == prefix.as_str()
+                    || (module.starts_with(prefix.as_str())
+                        && module[prefix.len()..].starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    /// Return the most verbose level logged for any module.
+    fn max_level(&self) -> Level {
+        self.module_levels
+            .values()
+            .fold(self.level, |max, level| std::cmp::max(max, *level))
+    }
+}
+
+/// A structured log record.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// Level, one of `trace`, `debug`, `info`, `warn`, `error`.
+    pub level: String,
+    /// Module which emitted the record.
+    pub module: String,
+    /// Message.
+    pub msg: String,
+    /// Additional key/value pairs.
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+fn to_log_level(level: slog::Level) -> Level {
+    match level {
+        slog::Level::Critical | slog::Level::Error => Level::Error,
+        slog::Level::Warning => Level::Warn,
+        slog::Level::Info => Level::Info,
+        slog::Level::Debug => Level::Debug,
+        slog::Level::Trace => Level::Trace,
+    }
+}
+
+/// Collects the key/value pairs of a record.
+#[derive(Default)]
+struct FieldCollector(BTreeMap<String, String>);
+
+impl slog::Serializer for FieldCollector {
+    fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.insert(key.to_string(), val.to_string());
+        Ok(())
+    }
+}
+
+/// Drain which filters records and either forwards them or writes them to
+/// standard error.
+struct HostDrain {
+    stderr: Mutex<slog_json::Json<std::io::Stderr>>,
+}
+
+impl HostDrain {
+    fn new() -> Self {
+        Self {
+            stderr: Mutex::new(slog_json::Json::default(std::io::stderr())),
+        }
+    }
+}
+
+impl Drain for HostDrain {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(&self, record: &slog::Record, values: &slog::OwnedKVList) -> Result<(), slog::Never> {
+        let mut fields = FieldCollector::default();
+        let _ = values.serialize(record, &mut fields);
+        let _ = record.kv().serialize(record, &mut fields);
+        let mut fields = fields.0;
+        let module = fields.remove("module").unwrap_or_default();
+
+        let level = to_log_level(record.level());
+        if level > CONFIG.read().unwrap().level_for(&module) {
+            return Ok(());
+        }
+
+        if let Some(forwarder) = FORWARDER.read().unwrap().as_ref() {
+            let record = LogRecord {
+                level: level.to_string().to_lowercase(),
+                module,
+                msg: record.msg().to_string(),
+                fields,
+            };
+            if forwarder.try_send(record).is_ok() {
+                return Ok(());
+            }
+            // Fall back to standard error when the backlog is full.
+        }
+
+        let _ = self.stderr.lock().unwrap().log(record, values);
+        Ok(())
+    }
 }
 
 /// Get the logger.
@@ -27,12 +184,78 @@ pub fn get_logger(module: &'static str) -> slog::Logger {
 
 /// Initialize the global slog_stdlog adapter to allow logging with the log crate (instead of slog).
 pub fn init_logger(level: Level) {
+    init_logger_with_config(LoggerConfig {
+        level,
+        ..Default::default()
+    })
+}
+
+/// Initialize logging with the given configuration.
+///
+/// See `init_logger`.
+pub fn init_logger_with_config(config: LoggerConfig) {
     INIT_GLOBAL_LOGGER.call_once(|| {
+        let max_level = config.max_level();
+        *CONFIG.write().unwrap() = config;
+
         let global_logger = LOGGER.new(o!("module" => "global"));
         GLOBAL_LOGGER_SCOPE_GUARD
             .lock()
             .unwrap()
             .get_or_insert(slog_scope::set_global_logger(global_logger));
-        let _log_guard = slog_stdlog::init_with_level(level).unwrap();
+        let _log_guard = slog_stdlog::init_with_level(max_level).unwrap();
+    });
+}
+
+/// Forward all further log records to the given sink (e.g., the runtime
+/// host) instead of writing them to standard error.
+///
+/// Records are forwarded in batches from a separate thread. If the sink
+/// fails, forwarding stops so that new records are written to standard
+/// error, and the records which were not forwarded are dropped and counted
+/// in the `oasis_runtime_log_records_dropped` metric.
+pub fn forward_records<F>(sink: F)
+where
+    F: Fn(Vec<LogRecord>) -> Result<()> + Send + 'static,
+{
+    let (tx, rx) = channel::bounded(FORWARD_BACKLOG_SIZE);
+    *FORWARDER.write().unwrap() = Some(tx);
+
+    thread::spawn(move || {
+        while let Ok(record) = rx.recv() {
+            let mut records = vec![record];
+            records.extend(rx.try_iter().take(MAX_FORWARD_BATCH_SIZE - 1));
+            let count = records.len();
+            if sink(records).is_err() {
+                FORWARDER.write().unwrap().take();
+                DROPPED_RECORDS.inc_by((count + rx.try_iter().count()) as u64);
+                return;
+            }
+        }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_level_for() {
+        let mut config = LoggerConfig::default();
+        assert_eq!(Level::Info, config.level_for("runtime/protocol"));
+
+        config
+            .module_levels
+            .insert("runtime".to_owned(), Level::Warn);
+        config
+            .module_levels
+            .insert("runtime/storage".to_owned(), Level::Trace);
+        assert_eq!(Level::Warn, config.level_for("runtime"));
+        assert_eq!(Level::Warn, config.level_for("runtime/protocol"));
+        assert_eq!(Level::Trace, config.level_for("runtime/storage"));
+        assert_eq!(Level::Trace, config.level_for("runtime/storage/mkvs"));
+        assert_eq!(Level::Info, config.level_for("runtimes"));
+        assert_eq!(Level::Info, config.level_for("global"));
+        assert_eq!(Level::Trace, config.max_level());
+    }
+}
@@ -0,0 +1,330 @@
+//! Lightweight metrics registry.
+//!
+//! Metrics are registered in a global registry, which the runtime host can
+//! scrape over the runtime host protocol.
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+/// Default histogram buckets, suitable for latencies in seconds.
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::default();
+}
+
+/// Return the global metrics registry.
+pub fn registry() -> &'static Registry {
+    &REGISTRY
+}
+
+/// A monotonically increasing counter.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    /// Increment the counter by one.
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    /// Increment the counter by the given amount.
+    pub fn inc_by(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Current value of the counter.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that can go up and down.
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    /// Set the gauge to the given value.
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    /// Increment the gauge by one.
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decrement the gauge by one.
+    pub fn dec(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Current value of the gauge.
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+struct HistogramState {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// A histogram of observed values.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    state: Mutex<HistogramState>,
+}
+
+impl Histogram {
+    /// Create a new histogram with the given (sorted) bucket upper bounds.
+    pub fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            state: Mutex::new(HistogramState {
+                counts: vec![0; bounds.len()],
+                sum: 0.0,
+                count: 0,
+            }),
+        }
+    }
+
+    /// Record an observed value.
+    pub fn observe(&self, value: f64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            state.counts[bucket] += 1;
+        }
+        state.sum += value;
+        state.count += 1;
+    }
+
+    fn snapshot(&self) -> MetricValue {
+        let state = self.state.lock().unwrap();
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .zip(&state.counts)
+            .map(|(bound, count)| {
+                cumulative += count;
+                HistogramBucket {
+                    upper_bound: *bound,
+                    count: cumulative,
+                }
+            })
+            .collect();
+
+        MetricValue::Histogram {
+            buckets,
+            sum: state.sum,
+            count: state.count,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Metric {
+    Counter(Arc<Counter>),
+    Gauge(Arc<Gauge>),
+    Histogram(Arc<Histogram>),
+}
+
+/// A registry of named metrics.
+#[derive(Debug, Default)]
+pub struct Registry {
+    metrics: RwLock<BTreeMap<String, (String, Metric)>>,
+}
+
+impl Registry {
+    fn get_or_register<F>(&self, name: &str, help: &str, create: F) -> Metric
+    where
+        F: FnOnce() -> Metric,
+    {
+        if let Some((_, metric)) = self.metrics.read().unwrap().get(name) {
+            return metric.clone();
+        }
+        self.metrics
+            .write()
+            .unwrap()
+            .entry(name.to_owned())
+            .or_insert_with(|| (help.to_owned(), create()))
+            .1
+            .clone()
+    }
+
+    /// Return the counter with the given name, registering it if needed.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if a metric of a different type is registered
+    /// under the same name.
+    pub fn counter(&self, name: &str, help: &str) -> Arc<Counter> {
+        match self.get_or_register(name, help, || Metric::Counter(Default::default())) {
+            Metric::Counter(counter) => counter,
+            _ => panic!("metric {} is not a counter", name),
+        }
+    }
+
+    /// Return the gauge with the given name, registering it if needed.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if a metric of a different type is registered
+    /// under the same name.
+    pub fn gauge(&self, name: &str, help: &str) -> Arc<Gauge> {
+        match self.get_or_register(name, help, || Metric::Gauge(Default::default())) {
+            Metric::Gauge(gauge) => gauge,
+            _ => panic!("metric {} is not a gauge", name),
+        }
+    }
+
+    /// Return the histogram with the given name, registering it with the
+    /// given bucket upper bounds if needed.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if a metric of a different type is registered
+    /// under the same name.
+    pub fn histogram(&self, name: &str, help: &str, bounds: &[f64]) -> Arc<Histogram> {
+        match self.get_or_register(name, help, || {
+            Metric::Histogram(Arc::new(Histogram::new(bounds)))
+        }) {
+            Metric::Histogram(histogram) => histogram,
+            _ => panic!("metric {} is not a histogram", name),
+        }
+    }
+
+    /// Return the current values of all metrics, ordered by name.
+    pub fn gather(&self) -> Vec<MetricFamily> {
+        self.metrics
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, (help, metric))| MetricFamily {
+                name: name.clone(),
+                help: help.clone(),
+                value: match metric {
+                    Metric::Counter(counter) => MetricValue::Counter {
+                        value: counter.get(),
+                    },
+                    Metric::Gauge(gauge) => MetricValue::Gauge { value: gauge.get() },
+                    Metric::Histogram(histogram) => histogram.snapshot(),
+                },
+            })
+            .collect()
+    }
+}
+
+/// A histogram bucket.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    /// Upper bound of the bucket (inclusive).
+    pub upper_bound: f64,
+    /// Number of observations less than or equal to the upper bound.
+    pub count: u64,
+}
+
+/// The value of a metric.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MetricValue {
+    Counter {
+        value: u64,
+    },
+    Gauge {
+        value: i64,
+    },
+    Histogram {
+        buckets: Vec<HistogramBucket>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+/// A snapshot of a named metric.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MetricFamily {
+    pub name: String,
+    pub help: String,
+    pub value: MetricValue,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::cbor;
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::default();
+        let counter = registry.counter("test_counter", "A counter.");
+        counter.inc();
+        registry.counter("test_counter", "A counter.").inc_by(2);
+        let gauge = registry.gauge("test_gauge", "A gauge.");
+        gauge.set(5);
+        gauge.dec();
+        let histogram = registry.histogram("test_histogram", "A histogram.", &[1.0, 2.0]);
+        for value in &[0.5, 1.0, 1.5, 3.0] {
+            histogram.observe(*value);
+        }
+
+        let metrics = registry.gather();
+        assert_eq!(
+            vec![
+                MetricFamily {
+                    name: "test_counter".to_owned(),
+                    help: "A counter.".to_owned(),
+                    value: MetricValue::Counter { value: 3 },
+                },
+                MetricFamily {
+                    name: "test_gauge".to_owned(),
+                    help: "A gauge.".to_owned(),
+                    value: MetricValue::Gauge { value: 4 },
+                },
+                MetricFamily {
+                    name: "test_histogram".to_owned(),
+                    help: "A histogram.".to_owned(),
+                    value: MetricValue::Histogram {
+                        buckets: vec![
+                            HistogramBucket {
+                                upper_bound: 1.0,
+                                count: 2,
+                            },
+                            HistogramBucket {
+                                upper_bound: 2.0,
+                                count: 3,
+                            },
+                        ],
+                        sum: 6.0,
+                        count: 4,
+                    },
+                },
+            ],
+            metrics
+        );
+
+        let encoded = cbor::to_vec(&metrics);
+        let decoded: Vec<MetricFamily> = cbor::from_slice(&encoded).unwrap();
+        assert_eq!(metrics, decoded);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_registry_type_mismatch() {
+        let registry = Registry::default();
+        registry.counter("test_metric", "A counter.");
+        registry.gauge("test_metric", "A gauge.");
+    }
+}
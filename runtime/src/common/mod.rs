@@ -6,9 +6,15 @@ pub mod cbor;
 pub mod crypto;
 pub mod key_format;
 pub mod logger;
+pub mod metrics;
+pub mod protobuf;
+pub mod quantity;
 pub mod registry;
 pub mod roothash;
 pub mod runtime;
+pub mod schema;
 pub mod sgx;
+pub mod staking;
 pub mod time;
 pub mod version;
+pub mod x509;
@@ -0,0 +1,272 @@
+//! Protocol Buffers wire format encoding/decoding functions.
+//!
+//! Only the wire format is supported, so messages are encoded and decoded
+//! field by field by the code using them. This is sufficient for the few
+//! protobuf messages exchanged with other protocols, e.g. Tendermint.
+use anyhow::Result;
+use thiserror::Error;
+
+/// Protobuf decoding error.
+#[derive(Error, Debug)]
+pub enum ProtobufError {
+    #[error("protobuf: truncated message")]
+    Truncated,
+    #[error("protobuf: malformed varint")]
+    MalformedVarint,
+    #[error("protobuf: unsupported wire type {0}")]
+    UnsupportedWireType(u64),
+    #[error("protobuf: invalid field number")]
+    InvalidField,
+    #[error("protobuf: unexpected wire type for field {0}")]
+    UnexpectedWireType(u32),
+}
+
+/// Wire type of varint fields.
+const WIRE_VARINT: u64 = 0;
+/// Wire type of 64-bit fields.
+const WIRE_FIXED64: u64 = 1;
+/// Wire type of length-delimited fields.
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+/// Wire type of 32-bit fields.
+const WIRE_FIXED32: u64 = 5;
+
+/// Append the varint encoding of the given value.
+pub fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Decode a varint at the start of the given slice, returning the value and
+/// the size of its encoding.
+pub fn decode_varint(data: &[u8]) -> Result<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    if data.len() < 10 {
+        return Err(ProtobufError::Truncated.into());
+    }
+    Err(ProtobufError::MalformedVarint.into())
+}
+
+/// Encoder for a protobuf message.
+///
+/// Fields are written in the order of the calls, which must be the order
+/// of their field numbers for the encoding to be canonical. All fields are
+/// written even if they have their default value, so callers encoding
+/// proto3 messages must skip such fields themselves.
+#[derive(Clone, Debug, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    /// Create a new encoder for an empty message.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key(&mut self, field: u32, wire_type: u64) {
+        encode_varint(&mut self.buf, (u64::from(field) << 3) | wire_type);
+    }
+
+    /// Write a varint field (e.g., `int64`, `uint64`, `bool` or an enum).
+    pub fn varint(mut self, field: u32, value: u64) -> Self {
+        self.key(field, WIRE_VARINT);
+        encode_varint(&mut self.buf, value);
+        self
+    }
+
+    /// Write a `sfixed64` field.
+    pub fn sfixed64(mut self, field: u32, value: i64) -> Self {
+        self.key(field, WIRE_FIXED64);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Write a length-delimited field (e.g., `bytes` or `string`).
+    pub fn bytes(mut self, field: u32, value: &[u8]) -> Self {
+        self.key(field, WIRE_LENGTH_DELIMITED);
+        encode_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    /// Write an embedded message field.
+    pub fn message(self, field: u32, message: Encoder) -> Self {
+        self.bytes(field, &message.buf)
+    }
+
+    /// Return the encoded message.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Return the encoded message prefixed by its varint-encoded length.
+    pub fn into_length_delimited(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.buf.len() + 10);
+        encode_varint(&mut buf, self.buf.len() as u64);
+        buf.extend_from_slice(&self.buf);
+        buf
+    }
+}
+
+/// Value of a decoded field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    /// Return the value of a varint field.
+    pub fn as_varint(&self, field: u32) -> Result<u64> {
+        match self {
+            Value::Varint(value) => Ok(*value),
+            _ => Err(ProtobufError::UnexpectedWireType(field).into()),
+        }
+    }
+
+    /// Return the value of a `sfixed64` field.
+    pub fn as_sfixed64(&self, field: u32) -> Result<i64> {
+        match self {
+            Value::Fixed64(value) => Ok(*value as i64),
+            _ => Err(ProtobufError::UnexpectedWireType(field).into()),
+        }
+    }
+
+    /// Return the value of a length-delimited field.
+    pub fn as_bytes(&self, field: u32) -> Result<&'a [u8]> {
+        match self {
+            Value::Bytes(value) => Ok(value),
+            _ => Err(ProtobufError::UnexpectedWireType(field).into()),
+        }
+    }
+}
+
+/// Decode the fields of a message in the order they are encoded.
+///
+/// Unknown fields are returned as well so callers can skip them, as is
+/// required for protobuf messages.
+pub fn decode(mut data: &[u8]) -> Result<Vec<(u32, Value)>> {
+    let mut fields = Vec::new();
+    while !data.is_empty() {
+        let (key, size) = decode_varint(data)?;
+        data = &data[size..];
+        let field = key >> 3;
+        if field == 0 || field > u64::from(u32::MAX >> 3) {
+            return Err(ProtobufError::InvalidField.into());
+        }
+
+        let value = match key & 0x7 {
+            WIRE_VARINT => {
+                let (value, size) = decode_varint(data)?;
+                data = &data[size..];
+                Value::Varint(value)
+            }
+            WIRE_FIXED64 => {
+                if data.len() < 8 {
+                    return Err(ProtobufError::Truncated.into());
+                }
+                let mut value = [0u8; 8];
+                value.copy_from_slice(&data[..8]);
+                data = &data[8..];
+                Value::Fixed64(u64::from_le_bytes(value))
+            }
+            WIRE_LENGTH_DELIMITED => {
+                let (len, size) = decode_varint(data)?;
+                data = &data[size..];
+                if (data.len() as u64) < len {
+                    return Err(ProtobufError::Truncated.into());
+                }
+                let (value, rest) = data.split_at(len as usize);
+                data = rest;
+                Value::Bytes(value)
+            }
+            WIRE_FIXED32 => {
+                if data.len() < 4 {
+                    return Err(ProtobufError::Truncated.into());
+                }
+                let mut value = [0u8; 4];
+                value.copy_from_slice(&data[..4]);
+                data = &data[4..];
+                Value::Fixed32(u32::from_le_bytes(value))
+            }
+            wire_type => return Err(ProtobufError::UnsupportedWireType(wire_type).into()),
+        };
+        fields.push((field as u32, value));
+    }
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        let cases: Vec<(u64, &[u8])> = vec![
+            (0, &[0x00]),
+            (1, &[0x01]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (300, &[0xac, 0x02]),
+            (
+                u64::MAX,
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            ),
+        ];
+        for (value, encoded) in cases {
+            let mut buf = Vec::new();
+            encode_varint(&mut buf, value);
+            assert_eq!(encoded, &buf[..]);
+            assert_eq!((value, encoded.len()), decode_varint(encoded).unwrap());
+        }
+
+        assert!(decode_varint(&[0x80]).is_err());
+        assert!(decode_varint(&[0xff; 11]).is_err());
+    }
+
+    #[test]
+    fn test_message() {
+        // Test vector from the protobuf encoding guide: field 1 = 150,
+        // field 2 = "testing".
+        let encoded = Encoder::new()
+            .varint(1, 150)
+            .bytes(2, b"testing")
+            .sfixed64(3, -2)
+            .message(4, Encoder::new().varint(1, 1))
+            .into_vec();
+        assert_eq!(
+            &[0x08, 0x96, 0x01, 0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g'],
+            &encoded[..12]
+        );
+
+        let fields = decode(&encoded).unwrap();
+        assert_eq!(4, fields.len());
+        assert_eq!((1, Value::Varint(150)), fields[0]);
+        assert_eq!(b"testing", fields[1].1.as_bytes(2).unwrap());
+        assert_eq!(-2, fields[2].1.as_sfixed64(3).unwrap());
+        assert!(fields[2].1.as_varint(3).is_err());
+        let embedded = decode(fields[3].1.as_bytes(4).unwrap()).unwrap();
+        assert_eq!(vec![(1, Value::Varint(1))], embedded);
+
+        let delimited = Encoder::new().varint(1, 150).into_length_delimited();
+        assert_eq!(vec![0x03, 0x08, 0x96, 0x01], delimited);
+
+        // Truncated messages are rejected.
+        assert!(decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(decode(&[0x12, 0x07, b't']).is_err());
+        // Field number zero is invalid.
+        assert!(decode(&[0x00, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Arbitrary precision unsigned integer quantities.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/common/quantity.
+//!
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A non-negative quantity (e.g., an amount of tokens).
+///
+/// Quantities are encoded as big-endian byte strings without leading zeroes.
+/// Only quantities which fit into 128 bits are supported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Quantity(pub u128);
+
+impl Quantity {
+    /// Checked addition.
+    pub fn checked_add(&self, other: &Quantity) -> Option<Quantity> {
+        self.0.checked_add(other.0).map(Quantity)
+    }
+
+    /// Checked subtraction.
+    pub fn checked_sub(&self, other: &Quantity) -> Option<Quantity> {
+        self.0.checked_sub(other.0).map(Quantity)
+    }
+
+    /// Whether the quantity is zero.
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<u64> for Quantity {
+    fn from(value: u64) -> Self {
+        Quantity(value as u128)
+    }
+}
+
+impl From<u128> for Quantity {
+    fn from(value: u128) -> Self {
+        Quantity(value)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes = self.0.to_be_bytes();
+        let leading = (self.0.leading_zeros() / 8) as usize;
+        serializer.serialize_bytes(&bytes[leading..])
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BytesVisitor;
+
+        impl<'de> de::Visitor<'de> for BytesVisitor {
+            type Value = Quantity;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a big-endian quantity of at most 16 bytes")
+            }
+
+            fn visit_bytes<E>(self, data: &[u8]) -> Result<Quantity, E>
+            where
+                E: de::Error,
+            {
+                let start = data.iter().position(|b| *b != 0).unwrap_or(data.len());
+                let data = &data[start..];
+                if data.len() > 16 {
+                    return Err(E::invalid_length(data.len(), &self));
+                }
+                let mut bytes = [0u8; 16];
+                bytes[16 - data.len()..].copy_from_slice(data);
+                Ok(Quantity(u128::from_be_bytes(bytes)))
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustc_hex::ToHex;
+
+    use super::*;
+    use crate::common::cbor;
+
+    #[test]
+    fn test_serialization() {
+        let cases: Vec<(u128, &str)> = vec![
+            (0, "40"),
+            (1, "4101"),
+            (10, "410a"),
+            (100, "4164"),
+            (1000, "4203e8"),
+            (1_000_000, "430f4240"),
+            (u64::max_value() as u128, "48ffffffffffffffff"),
+        ];
+        for (value, expected) in cases {
+            let enc = cbor::to_vec(&Quantity(value));
+            assert_eq!(enc.to_hex::<String>(), expected);
+            let dec: Quantity = cbor::from_slice(&enc).expect("decode");
+            assert_eq!(dec, Quantity(value));
+        }
+
+        // Leading zeroes are accepted, too large quantities are not.
+        let dec: Quantity = cbor::from_slice(&[0x43, 0x00, 0x00, 0x01]).expect("decode");
+        assert_eq!(dec, Quantity(1));
+        let mut too_large = vec![0x51];
+        too_large.extend_from_slice(&[0xff; 17]);
+        assert!(cbor::from_slice::<Quantity>(&too_large).is_err());
+    }
+}
@@ -6,9 +6,14 @@
 //!
 use super::{
     super::storage::mkvs::WriteLog,
-    crypto::{hash, signature::SignatureBundle},
+    crypto::{
+        hash,
+        signature::{PublicKey, SignatureBundle},
+    },
+    roothash::Namespace,
 };
 use serde::{Deserialize, Serialize};
+use serde_repr::*;
 
 /// Runtime genesis information that is used to initialize runtime state in the first block.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -29,3 +34,65 @@ pub struct RuntimeGenesis {
     /// Runtime round in the genesis.
     pub round: u64,
 }
+
+/// Runtime kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u32)]
+pub enum RuntimeKind {
+    /// Invalid runtime that should never be explicitly set.
+    Invalid = 0,
+    /// Generic compute runtime.
+    Compute = 1,
+    /// Key manager runtime.
+    KeyManager = 2,
+}
+
+impl Default for RuntimeKind {
+    fn default() -> Self {
+        RuntimeKind::Invalid
+    }
+}
+
+/// TEE hardware implementation.
+///
+/// # Note
+///
+/// This should be kept in sync with go/common/node/node.go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum TEEHardware {
+    /// Non-TEE implementation.
+    Invalid = 0,
+    /// Intel SGX TEE implementation.
+    IntelSGX = 1,
+}
+
+impl Default for TEEHardware {
+    fn default() -> Self {
+        TEEHardware::Invalid
+    }
+}
+
+/// Runtime descriptor.
+///
+/// Only the fields needed by runtimes are decoded, the scheduling, storage,
+/// admission and staking parameters are not.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Runtime {
+    /// Structure version.
+    #[serde(default)]
+    pub v: u16,
+    /// Globally unique long term identifier of the runtime.
+    pub id: Namespace,
+    /// Public key identifying the entity running the runtime.
+    pub entity_id: PublicKey,
+    /// Runtime genesis information.
+    pub genesis: RuntimeGenesis,
+    /// Type of runtime.
+    pub kind: RuntimeKind,
+    /// Runtime's TEE hardware requirements.
+    pub tee_hardware: TEEHardware,
+    /// Key manager runtime ID for this runtime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_manager: Option<Namespace>,
+}
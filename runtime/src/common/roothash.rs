@@ -10,6 +10,8 @@ use serde_repr::*;
 use super::{
     cbor,
     crypto::{hash::Hash, signature::SignatureBundle},
+    quantity::Quantity,
+    staking,
 };
 
 /// Runtime block.
@@ -52,8 +54,106 @@ impl Default for HeaderType {
 }
 
 /// Roothash message.
+///
+/// Messages are executed by the consensus layer once the block which contains
+/// them is finalized, and the results of their execution are delivered to the
+/// runtime in the following round (see `MessageEvent`).
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum Message {}
+pub enum Message {
+    /// A staking operation on behalf of the runtime account.
+    #[serde(rename = "staking")]
+    Staking {
+        /// Structure version.
+        #[serde(default)]
+        v: u16,
+        /// Staking operation.
+        #[serde(flatten)]
+        msg: StakingMessage,
+    },
+}
+
+impl Message {
+    /// A message transferring tokens from the runtime account.
+    pub fn staking_transfer(to: staking::Address, amount: Quantity) -> Self {
+        Message::Staking {
+            v: 0,
+            msg: StakingMessage::Transfer(staking::Transfer { to, amount }),
+        }
+    }
+
+    /// A message withdrawing tokens to the runtime account, using an
+    /// allowance given to the runtime account.
+    pub fn staking_withdraw(from: staking::Address, amount: Quantity) -> Self {
+        Message::Staking {
+            v: 0,
+            msg: StakingMessage::Withdraw(staking::Withdraw { from, amount }),
+        }
+    }
+}
+
+/// Staking operation performed by a roothash message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StakingMessage {
+    #[serde(rename = "transfer")]
+    Transfer(staking::Transfer),
+    #[serde(rename = "withdraw")]
+    Withdraw(staking::Withdraw),
+}
+
+/// Result of executing a roothash message.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MessageEvent {
+    /// Module of the error, if any.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub module: String,
+    /// Code of the error, if any.
+    #[serde(default)]
+    pub code: u32,
+    /// Index of the message in the block which contains it.
+    #[serde(default)]
+    pub index: u32,
+}
+
+impl MessageEvent {
+    /// Whether the message was executed successfully.
+    pub fn is_success(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// A message sent from the consensus layer to the runtime.
+///
+/// Incoming messages are queued by the consensus layer and processed by the
+/// runtime in queue order. Any fee and tokens attached to a message have
+/// already been moved to the runtime account when the message was queued.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IncomingMessage {
+    /// Sequence number of the message in the queue.
+    pub id: u64,
+    /// Address of the account which submitted the message.
+    pub caller: staking::Address,
+    /// Runtime-specific tag of the message.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub tag: u64,
+    /// Fee paid to the runtime for processing the message.
+    #[serde(default, skip_serializing_if = "Quantity::is_zero")]
+    pub fee: Quantity,
+    /// Tokens deposited to the runtime with the message.
+    #[serde(default, skip_serializing_if = "Quantity::is_zero")]
+    pub tokens: Quantity,
+    /// Runtime-specific payload of the message.
+    #[serde(default, with = "serde_bytes", skip_serializing_if = "Vec::is_empty")]
+    pub data: Vec<u8>,
+}
+
+/// Returns the hash of the given processed incoming messages.
+pub fn in_messages_hash(msgs: &[IncomingMessage]) -> Hash {
+    Hash::digest_bytes(&cbor::to_vec(&msgs))
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
 
 /// Block header.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -110,6 +210,12 @@ pub struct ComputeResultsHeader {
     /// Messages sent from this batch.
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub messages: Vec<Message>,
+    /// Hash of the processed incoming messages, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_msgs_hash: Option<Hash>,
+    /// Number of processed incoming messages.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub in_msgs_count: u32,
 }
 
 impl ComputeResultsHeader {
@@ -121,6 +227,8 @@ impl ComputeResultsHeader {
 
 #[cfg(test)]
 mod tests {
+    use rustc_hex::ToHex;
+
     use super::*;
 
     #[test]
@@ -164,10 +272,32 @@ mod tests {
             io_root: Some(Hash::empty_hash()),
             state_root: Some(Hash::empty_hash()),
             messages: Vec::new(),
+            ..Default::default()
         };
         assert_eq!(
             populated.encoded_hash(),
             Hash::from("374021bcba44f1014d0d9919e876a1ecd7fe5ec1a92ecf9c8b313cd4976fbc01")
         );
     }
+
+    #[test]
+    fn test_message_serialization() {
+        // NOTE: These cases MUST be synced with go/roothash/api/block/message_test.go.
+        let cases = vec![
+            (
+                Message::staking_transfer(Default::default(), Quantity(1000)),
+                "a1677374616b696e67a2617600687472616e73666572a262746f5500000000000000000000000000000000000000000066616d6f756e744203e8",
+            ),
+            (
+                Message::staking_withdraw(Default::default(), Quantity(1000)),
+                "a1677374616b696e67a2617600687769746864726177a26466726f6d5500000000000000000000000000000000000000000066616d6f756e744203e8",
+            ),
+        ];
+        for (message, expected) in cases {
+            let enc = cbor::to_vec(&message);
+            assert_eq!(enc.to_hex::<String>(), expected);
+            let dec: Message = cbor::from_slice(&enc).expect("decode");
+            assert_eq!(dec, message);
+        }
+    }
 }
@@ -0,0 +1,192 @@
+//! Schemas of CBOR-encoded values.
+//!
+//! Runtime methods describe the expected CBOR encoding of their arguments
+//! and outputs using schemas, which are reported to the host so that tooling
+//! can generate clients for any runtime.
+use std::{any::type_name, collections::BTreeMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::cbor;
+
+/// Schema of a CBOR-encoded value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Schema {
+    /// Any value.
+    Any,
+    /// The null value.
+    Null,
+    Bool,
+    Unsigned,
+    Signed,
+    Float,
+    Bytes,
+    Text,
+    /// Either null or a value of the given schema.
+    Optional(Box<Schema>),
+    /// An array of values of the given schema.
+    Array(Box<Schema>),
+    /// A map with keys and values of the given schemas.
+    Map {
+        key: Box<Schema>,
+        value: Box<Schema>,
+    },
+    /// A fixed-size array of values of the given schemas.
+    Tuple(Vec<Schema>),
+    /// A map of named fields.
+    Struct(BTreeMap<String, Schema>),
+    /// A value of a named type whose schema is not described.
+    Named(String),
+}
+
+impl Schema {
+    /// Schema of the given type.
+    pub fn of<T: Describe>() -> Self {
+        T::schema()
+    }
+
+    /// Schema referring to the given type by name only.
+    pub fn named<T: ?Sized>() -> Self {
+        Schema::Named(type_name::<T>().to_owned())
+    }
+
+    /// Schema of a map of the given named fields.
+    pub fn fields<I, N>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = (N, Schema)>,
+        N: Into<String>,
+    {
+        Schema::Struct(
+            fields
+                .into_iter()
+                .map(|(name, schema)| (name.into(), schema))
+                .collect(),
+        )
+    }
+}
+
+/// A type with a known schema.
+pub trait Describe {
+    /// Schema of the CBOR encoding of the type.
+    fn schema() -> Schema;
+}
+
+macro_rules! impl_describe {
+    ($schema:ident, $($ty:ty),*) => {
+        $(
+            impl Describe for $ty {
+                fn schema() -> Schema {
+                    Schema::$schema
+                }
+            }
+        )*
+    };
+}
+
+impl_describe!(Null, ());
+impl_describe!(Bool, bool);
+impl_describe!(Unsigned, u8, u16, u32, u64, u128, usize);
+impl_describe!(Signed, i8, i16, i32, i64, i128, isize);
+impl_describe!(Float, f32, f64);
+impl_describe!(Text, String, str);
+impl_describe!(Any, cbor::Value);
+
+impl Describe for serde_bytes::ByteBuf {
+    fn schema() -> Schema {
+        Schema::Bytes
+    }
+}
+
+impl<T: Describe> Describe for Option<T> {
+    fn schema() -> Schema {
+        Schema::Optional(Box::new(T::schema()))
+    }
+}
+
+impl<T: Describe> Describe for Vec<T> {
+    fn schema() -> Schema {
+        Schema::Array(Box::new(T::schema()))
+    }
+}
+
+impl<K: Describe, V: Describe> Describe for BTreeMap<K, V> {
+    fn schema() -> Schema {
+        Schema::Map {
+            key: Box::new(K::schema()),
+            value: Box::new(V::schema()),
+        }
+    }
+}
+
+macro_rules! impl_describe_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: Describe),+> Describe for ($($name,)+) {
+            fn schema() -> Schema {
+                Schema::Tuple(vec![$($name::schema()),+])
+            }
+        }
+    };
+}
+
+impl_describe_tuple!(A);
+impl_describe_tuple!(A, B);
+impl_describe_tuple!(A, B, C);
+impl_describe_tuple!(A, B, C, D);
+
+/// Description of a runtime method.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MethodInfo {
+    /// Method name.
+    pub name: String,
+    /// Schema of the method arguments.
+    pub args: Schema,
+    /// Schema of the method output.
+    pub output: Schema,
+}
+
+impl MethodInfo {
+    /// Create a new method description which refers to the argument and
+    /// output types by name only.
+    pub fn new<Args, Output>(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            args: Schema::named::<Args>(),
+            output: Schema::named::<Output>(),
+        }
+    }
+
+    /// Set the schemas of the method arguments and output.
+    pub fn with_schema(&mut self, args: Schema, output: Schema) -> &mut Self {
+        self.args = args;
+        self.output = output;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(
+            Schema::Map {
+                key: Box::new(Schema::Text),
+                value: Box::new(Schema::Optional(Box::new(Schema::Array(Box::new(
+                    Schema::Unsigned
+                ))))),
+            },
+            Schema::of::<BTreeMap<String, Option<Vec<u8>>>>()
+        );
+        assert_eq!(
+            Schema::Tuple(vec![Schema::Bool, Schema::Bytes, Schema::Any]),
+            Schema::of::<(bool, serde_bytes::ByteBuf, cbor::Value)>()
+        );
+        assert_eq!(Schema::Named("u64".to_owned()), Schema::named::<u64>());
+
+        let schema = Schema::fields(vec![("key", Schema::Bytes), ("value", Schema::Signed)]);
+        let encoded = cbor::to_vec(&schema);
+        let decoded: Schema = cbor::from_slice(&encoded).unwrap();
+        assert_eq!(schema, decoded);
+    }
+}
@@ -13,6 +13,7 @@ use sgx_isa::{AttributesFlags, Report};
 use thiserror::Error;
 use webpki;
 
+use super::{QuotePolicy, TCBLevel, TeeEvidence, VerifiedQuote};
 use crate::common::time::{insecure_posix_time, update_insecure_posix_time};
 
 /// AVR verification error.
@@ -182,12 +183,19 @@ pub struct AVR {
     pub certificate_chain: Vec<u8>,
 }
 
+impl TeeEvidence for AVR {
+    fn verify(&self, policy: &QuotePolicy) -> Result<VerifiedQuote> {
+        Ok(verify(self, policy)?.into())
+    }
+}
+
 /// Authenticated information obtained from validating an AVR.
 #[derive(Debug, Clone)]
 pub struct AuthenticatedAVR {
     pub report_data: Vec<u8>,
     // TODO: add other av report/quote body/report fields we want to give the consumer
     pub identity: EnclaveIdentity,
+    pub tcb_level: TCBLevel,
     pub timestamp: i64,
     pub nonce: String,
 }
@@ -239,10 +247,9 @@ impl ParsedAVR {
     }
 }
 
-/// Verify attestation report.
-pub fn verify(avr: &AVR) -> Result<AuthenticatedAVR> {
+/// Verify attestation report under the given quote policy.
+pub fn verify(avr: &AVR, policy: &QuotePolicy) -> Result<AuthenticatedAVR> {
     let unsafe_skip_avr_verification = option_env!("OASIS_UNSAFE_SKIP_AVR_VERIFY").is_some();
-    let strict_avr_verification = option_env!("OASIS_STRICT_AVR_VERIFY").is_some();
 
     // Get the time.
     let timestamp_now = insecure_posix_time();
@@ -265,23 +272,17 @@ pub fn verify(avr: &AVR) -> Result<AuthenticatedAVR> {
     if !timestamp_is_fresh(timestamp_now, timestamp) {
         return Err(AVRError::TimestampOutOfRange.into());
     }
+    policy.check_age(timestamp_now, timestamp)?;
 
     let nonce = avr_body.nonce()?;
 
     let quote_status = avr_body.isv_enclave_quote_status()?;
-    match quote_status.as_str() {
-        "OK" => {}
-        "GROUP_OUT_OF_DATE"
-        | "CONFIGURATION_NEEDED"
-        | "SW_HARDENING_NEEDED"
-        | "CONFIGURATION_AND_SW_HARDENING_NEEDED" => {
-            if strict_avr_verification {
-                return Err(AVRError::QuoteStatusInvalid {
-                    status: quote_status.to_owned(),
-                }
-                .into());
-            }
-        }
+    let tcb_level = match quote_status.as_str() {
+        "OK" => TCBLevel::UpToDate,
+        "GROUP_OUT_OF_DATE" => TCBLevel::OutOfDate,
+        "CONFIGURATION_NEEDED" => TCBLevel::ConfigurationNeeded,
+        "SW_HARDENING_NEEDED" => TCBLevel::SWHardeningNeeded,
+        "CONFIGURATION_AND_SW_HARDENING_NEEDED" => TCBLevel::ConfigurationAndSWHardeningNeeded,
         _ => {
             return Err(AVRError::QuoteStatusInvalid {
                 status: quote_status.to_owned(),
@@ -289,6 +290,7 @@ pub fn verify(avr: &AVR) -> Result<AuthenticatedAVR> {
             .into());
         }
     };
+    policy.check_tcb_level(tcb_level)?;
 
     let quote_body = avr_body.isv_enclave_quote_body()?;
     let quote_body = match base64::decode(&quote_body) {
@@ -323,6 +325,7 @@ pub fn verify(avr: &AVR) -> Result<AuthenticatedAVR> {
             mr_enclave: MrEnclave::from(quote_body.report_body.mrenclave.to_vec()),
             mr_signer: MrSigner::from(quote_body.report_body.mrsigner.to_vec()),
         },
+        tcb_level,
         timestamp,
         nonce: nonce.to_string(),
     })
@@ -385,10 +388,13 @@ fn validate_decoded_avr_signature(
     Ok(cert.verify_signature(IAS_SIG_ALGS[0], message, &signature)?)
 }
 
+/// Period (in seconds) for which a cached AVR is considered "fresh".
+pub(crate) const TIMESTAMP_FRESHNESS_PERIOD: i64 = 60 * 60 * 24;
+
 /// Return true iff the (POXIX) timestamp is considered "fresh" for the purposes
 /// of a cached AVR, given the current time.
 pub(crate) fn timestamp_is_fresh(now: i64, timestamp: i64) -> bool {
-    (now - timestamp).abs() < 60 * 60 * 24
+    (now - timestamp).abs() < TIMESTAMP_FRESHNESS_PERIOD
 }
 
 /// Enclave identity.
@@ -416,9 +422,13 @@ impl EnclaveIdentity {
             })
         }
 
-        // TODO: There should be a mechanism for setting mock values for
-        // the purpose of testing.
-        #[cfg(not(target_env = "sgx"))]
+        // Non-SGX builds with mock attestation use the mock identity.
+        #[cfg(all(not(target_env = "sgx"), feature = "debug-mock-sgx"))]
+        {
+            Some(super::mock::identity())
+        }
+
+        #[cfg(all(not(target_env = "sgx"), not(feature = "debug-mock-sgx")))]
         None
     }
 
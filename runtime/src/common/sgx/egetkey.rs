@@ -1,10 +1,11 @@
 //! SGX per-CPU package sealing key accessor.
 
 use sgx_isa::Keypolicy;
-use sp800_185::KMac;
+
+use crate::common::crypto::kdf::kmac256;
 
 #[cfg(target_env = "sgx")]
-use sgx_isa::{Keyname, Keyrequest};
+use sgx_isa::{Keyname, Keyrequest, Report};
 #[cfg(target_env = "sgx")]
 use tiny_keccak::{Hasher, Sha3};
 
@@ -18,11 +19,12 @@ const MOCK_KDF_CUSTOM: &[u8] = b"Ekiden Extract Test SGX Seal Key";
 const SEAL_KDF_CUSTOM: &[u8] = b"Ekiden Expand SGX Seal Key";
 
 #[cfg(target_env = "sgx")]
-fn egetkey_impl(key_policy: Keypolicy, context: &[u8]) -> [u8; 16] {
+fn egetkey_impl(key_policy: Keypolicy, context: &[u8], isv_svn: u16) -> [u8; 16] {
     let mut req = Keyrequest::default();
 
     req.keyname = Keyname::Seal as u16;
     req.keypolicy = key_policy;
+    req.isvsvn = isv_svn;
 
     let mut sha3 = Sha3::v256();
     sha3.update(context);
@@ -41,19 +43,33 @@ fn egetkey_impl(key_policy: Keypolicy, context: &[u8]) -> [u8; 16] {
 }
 
 #[cfg(not(target_env = "sgx"))]
-fn egetkey_impl(key_policy: Keypolicy, context: &[u8]) -> [u8; 16] {
-    let mut k = [0u8; 16];
-
+fn egetkey_impl(key_policy: Keypolicy, context: &[u8], isv_svn: u16) -> [u8; 16] {
     // Deterministically generate a test master key from the context.
-    let mut kdf = match key_policy {
-        Keypolicy::MRENCLAVE => KMac::new_kmac256(MOCK_MRENCLAVE_KEY, MOCK_KDF_CUSTOM),
-        Keypolicy::MRSIGNER => KMac::new_kmac256(MOCK_MRSIGNER_KEY, MOCK_KDF_CUSTOM),
+    let key = match key_policy {
+        Keypolicy::MRENCLAVE => MOCK_MRENCLAVE_KEY,
+        Keypolicy::MRSIGNER => MOCK_MRSIGNER_KEY,
         _ => panic!("Invalid key_policy"),
     };
-    kdf.update(&context);
-    kdf.finalize(&mut k);
 
-    k
+    match isv_svn {
+        0 => *kmac256::<16>(key, MOCK_KDF_CUSTOM, &[context]).as_bytes(),
+        _ => *kmac256::<16>(key, MOCK_KDF_CUSTOM, &[context, &isv_svn.to_be_bytes()]).as_bytes(),
+    }
+}
+
+/// Return the security version number (ISV SVN) of the current enclave.
+///
+/// Non-SGX builds always have a security version number of zero.
+pub fn current_isv_svn() -> u16 {
+    #[cfg(target_env = "sgx")]
+    {
+        Report::for_self().isvsvn
+    }
+
+    #[cfg(not(target_env = "sgx"))]
+    {
+        0
+    }
 }
 
 /// egetkey returns a 256 bit key suitable for sealing secrets to the
@@ -63,19 +79,28 @@ fn egetkey_impl(key_policy: Keypolicy, context: &[u8]) -> [u8; 16] {
 /// Note: The key can also be used for other things (eg: as an X25519
 /// private key).
 pub fn egetkey(key_policy: Keypolicy, context: &[u8]) -> [u8; 32] {
-    let mut k = [0u8; 32];
+    egetkey_for_svn(key_policy, context, 0)
+}
 
+/// egetkey_for_svn returns a sealing key like `egetkey`, but derived for the
+/// given security version number (ISV SVN).
+///
+/// Keys can only be derived for versions up to the version of the current
+/// enclave (see `current_isv_svn`), so secrets sealed by an enclave can be
+/// unsealed by later, but not earlier, versions of it.
+///
+/// # Panics
+///
+/// In SGX builds, this function will panic if the version is later than
+/// the version of the current enclave.
+pub fn egetkey_for_svn(key_policy: Keypolicy, context: &[u8], isv_svn: u16) -> [u8; 32] {
     // Obtain the per-CPU package SGX sealing key, with the requested
     // policy.
-    let master_secret = egetkey_impl(key_policy, context);
+    let master_secret = egetkey_impl(key_policy, context, isv_svn);
 
     // Expand the 128 bit EGETKEY result into a 256 bit key, suitable
     // for use with our MRAE primitives.
-    let mut kdf = KMac::new_kmac256(&master_secret, SEAL_KDF_CUSTOM);
-    kdf.update(&context);
-    kdf.finalize(&mut k);
-
-    k
+    *kmac256::<32>(&master_secret, SEAL_KDF_CUSTOM, &[context]).as_bytes()
 }
 
 #[cfg(test)]
@@ -99,5 +124,9 @@ mod tests {
         // Ensure determinism.
         let aa_key = egetkey(Keypolicy::MRENCLAVE, b"Context A");
         assert!(a_key == aa_key);
+
+        // Ensure the version does something.
+        assert!(a_key == egetkey_for_svn(Keypolicy::MRENCLAVE, b"Context A", 0));
+        assert!(a_key != egetkey_for_svn(Keypolicy::MRENCLAVE, b"Context A", 1));
     }
 }
@@ -0,0 +1,187 @@
+//! Mock attestation for local development without SGX hardware.
+//!
+//! Mock quotes contain a report body (with the same layout as in SGX reports)
+//! signed by a well-known development key instead of a quoting enclave, so
+//! anyone is able to forge them. They are only accepted by runtimes built
+//! with the `debug-mock-sgx` feature, which must never be used in production.
+//!
+//! Non-SGX runtimes built with the feature attest their RAK using mock
+//! quotes, so that the enclave RPC and attestation stack can be exercised
+//! without SGX hardware.
+use std::{env, str::FromStr};
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sgx_isa::{AttributesFlags, Report};
+use thiserror::Error;
+
+use super::{
+    avr::{timestamp_is_fresh, EnclaveIdentity, MrEnclave, MrSigner},
+    pcs::{decode_report, REPORT_BODY_SIZE},
+    QuotePolicy, TCBLevel, TeeEvidence, VerifiedQuote,
+};
+use crate::common::{
+    crypto::{
+        hash::Hash,
+        signature::{PrivateKey, Signature, Signer},
+    },
+    time::insecure_posix_time,
+};
+
+/// Mock quote signature context.
+const MOCK_QUOTE_SIGNATURE_CONTEXT: &[u8] = b"oasis-core/sgx: mock quote";
+
+lazy_static! {
+    // Development key signing mock quotes.
+    static ref MOCK_QUOTE_SIGNER: PrivateKey =
+        PrivateKey::from_test_seed("oasis-core/sgx: mock attestation".to_owned());
+}
+
+/// Mock attestation error.
+#[derive(Error, Debug)]
+enum MockError {
+    #[error("mock attestation is disabled")]
+    Disabled,
+    #[error("mock quote signature verification failed")]
+    SignatureInvalid,
+    #[error("timestamp differs by more than 1 day")]
+    TimestampOutOfRange,
+    #[error("malformed report body")]
+    MalformedReportBody,
+}
+
+/// A mock quote, signed by the development key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockQuote {
+    /// Report body of the attested enclave.
+    #[serde(with = "serde_bytes")]
+    pub report_body: Vec<u8>,
+    /// Time of the attestation.
+    pub timestamp: i64,
+    /// Signature of the report body and timestamp by the development key.
+    pub signature: Signature,
+}
+
+impl MockQuote {
+    fn signed_message(report_body: &[u8], timestamp: i64) -> Vec<u8> {
+        [report_body, &timestamp.to_be_bytes()].concat()
+    }
+}
+
+impl TeeEvidence for MockQuote {
+    fn verify(&self, policy: &QuotePolicy) -> Result<VerifiedQuote> {
+        verify(self, policy)
+    }
+}
+
+/// Enclave identity of mock enclaves.
+///
+/// The MRENCLAVE can be set with the `OASIS_MOCK_SGX_MRENCLAVE` environment
+/// variable (hex-encoded), so that different runtimes can be told apart.
+pub fn identity() -> EnclaveIdentity {
+    let mr_enclave = env::var("OASIS_MOCK_SGX_MRENCLAVE")
+        .ok()
+        .and_then(|mr_enclave| MrEnclave::from_str(&mr_enclave).ok())
+        .unwrap_or_else(|| MrEnclave(Hash::digest_bytes(b"oasis-core/sgx: mock MRENCLAVE").0));
+    let mr_signer = MrSigner(Hash::digest_bytes(MOCK_QUOTE_SIGNER.public_key().as_ref()).0);
+
+    EnclaveIdentity {
+        mr_enclave,
+        mr_signer,
+    }
+}
+
+/// Generate a mock quote of the given enclave at the given time.
+pub fn generate(identity: &EnclaveIdentity, report_data: &[u8; 64], timestamp: i64) -> MockQuote {
+    let mut report = Report::try_copy_from(&[0; Report::UNPADDED_SIZE]).unwrap();
+    report.mrenclave = identity.mr_enclave.0;
+    report.mrsigner = identity.mr_signer.0;
+    report.attributes.flags = AttributesFlags::INIT | AttributesFlags::DEBUG;
+    report.reportdata = *report_data;
+    let report: &[u8] = report.as_ref();
+    let report_body = report[..REPORT_BODY_SIZE].to_vec();
+
+    let signature = MOCK_QUOTE_SIGNER
+        .sign(
+            MOCK_QUOTE_SIGNATURE_CONTEXT,
+            &MockQuote::signed_message(&report_body, timestamp),
+        )
+        .unwrap();
+
+    MockQuote {
+        report_body,
+        timestamp,
+        signature,
+    }
+}
+
+/// Verify a mock quote under the given quote policy.
+///
+/// Mock quotes are always rejected unless the `debug-mock-sgx` feature is
+/// enabled.
+pub fn verify(quote: &MockQuote, policy: &QuotePolicy) -> Result<VerifiedQuote> {
+    if !cfg!(feature = "debug-mock-sgx") {
+        return Err(MockError::Disabled.into());
+    }
+
+    if quote.report_body.len() != REPORT_BODY_SIZE {
+        return Err(MockError::MalformedReportBody.into());
+    }
+
+    quote
+        .signature
+        .verify(
+            &MOCK_QUOTE_SIGNER.public_key(),
+            MOCK_QUOTE_SIGNATURE_CONTEXT,
+            &MockQuote::signed_message(&quote.report_body, quote.timestamp),
+        )
+        .map_err(|_| MockError::SignatureInvalid)?;
+
+    let now = insecure_posix_time();
+    if !timestamp_is_fresh(now, quote.timestamp) {
+        return Err(MockError::TimestampOutOfRange.into());
+    }
+    policy.check_age(now, quote.timestamp)?;
+    policy.check_tcb_level(TCBLevel::UpToDate)?;
+
+    let report = decode_report(&quote.report_body)?;
+
+    Ok(VerifiedQuote {
+        report_data: report.reportdata.to_vec(),
+        mr_enclave: MrEnclave(report.mrenclave),
+        mr_signer: MrSigner(report.mrsigner),
+        tcb_level: TCBLevel::UpToDate,
+        timestamp: quote.timestamp,
+        valid_until: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mock_quote() {
+        let identity = identity();
+        let now = insecure_posix_time();
+        let quote = generate(&identity, &[0x42; 64], now);
+        let policy = QuotePolicy::default();
+
+        if !cfg!(feature = "debug-mock-sgx") {
+            assert!(verify(&quote, &policy).is_err(), "mock quotes are rejected");
+            return;
+        }
+
+        let verified = verify(&quote, &policy).unwrap();
+        assert_eq!(identity, verified.identity());
+        assert_eq!(vec![0x42; 64], verified.report_data);
+
+        let mut tampered = quote.clone();
+        tampered.report_body[64] ^= 0x01;
+        assert!(verify(&tampered, &policy).is_err());
+
+        let stale = generate(&identity, &[0x42; 64], now - 2 * 24 * 60 * 60);
+        assert!(verify(&stale, &policy).is_err());
+    }
+}
@@ -1,5 +1,208 @@
 //! SGX-specific functionality.
+use std::{collections::BTreeSet, sync::RwLock};
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 pub mod avr;
 pub mod egetkey;
+pub mod mock;
+pub mod pcs;
 pub mod seal;
+
+use self::avr::{EnclaveIdentity, MrEnclave, MrSigner};
+
+lazy_static! {
+    // Quote policy of the runtime.
+    static ref QUOTE_POLICY: RwLock<QuotePolicy> = RwLock::new(QuotePolicy::default());
+}
+
+/// Quote policy error.
+#[derive(Error, Debug)]
+enum QuotePolicyError {
+    #[error("quote is too old")]
+    QuoteTooOld,
+    #[error("rejecting TCB level ({0:?})")]
+    TCBLevelRejected(TCBLevel),
+    #[error("TCB evaluation data number {0} is too low")]
+    TCBEvaluationDataNumberTooLow(u32),
+    #[error("platform {0:x} is blocked")]
+    PlatformBlocked(pcs::FMSPC),
+}
+
+/// Evidence produced by a TEE, attesting the identity of the code running
+/// in it.
+pub trait TeeEvidence {
+    /// Verify the evidence under the given quote policy.
+    fn verify(&self, policy: &QuotePolicy) -> Result<VerifiedQuote>;
+}
+
+/// Remote attestation of an enclave (or of another kind of TEE).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Quote {
+    /// EPID attestation verification report, signed by IAS.
+    #[serde(rename = "avr")]
+    IAS(avr::AVR),
+    /// DCAP quote, verified with collateral from the PCS.
+    #[serde(rename = "pcs")]
+    PCS(pcs::QuoteBundle),
+    /// Mock quote for development without SGX hardware, only accepted with
+    /// the `debug-mock-sgx` feature.
+    #[serde(rename = "mock")]
+    Mock(mock::MockQuote),
+}
+
+impl Quote {
+    /// Verify the remote attestation under the quote policy of the runtime.
+    pub fn verify(&self) -> Result<VerifiedQuote> {
+        self.verify_with_policy(&QuotePolicy::global())
+    }
+
+    /// Verify the remote attestation under the given quote policy.
+    pub fn verify_with_policy(&self, policy: &QuotePolicy) -> Result<VerifiedQuote> {
+        self.evidence().verify(policy)
+    }
+
+    /// Evidence contained in the remote attestation.
+    pub fn evidence(&self) -> &dyn TeeEvidence {
+        match self {
+            Quote::IAS(avr) => avr,
+            Quote::PCS(bundle) => bundle,
+            Quote::Mock(quote) => quote,
+        }
+    }
+}
+
+/// Policy for accepting remote attestations, configured per runtime by the
+/// host.
+///
+/// As the host is not trusted, the policy can only restrict which quotes are
+/// accepted. In particular, strict builds (`OASIS_STRICT_AVR_VERIFY`) never
+/// accept platforms which are not up to date, and AVRs older than a day are
+/// always rejected.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotePolicy {
+    /// Maximum age of a quote in seconds. As DCAP quotes are not timestamped,
+    /// their age is the age of the collateral (its issue date).
+    #[serde(default)]
+    pub max_quote_age: Option<u64>,
+    /// Accepted TCB levels. If empty, all TCB levels are accepted.
+    #[serde(default)]
+    pub allowed_tcb_levels: BTreeSet<TCBLevel>,
+    /// Minimum TCB evaluation data number of the DCAP collateral, which
+    /// allows rejecting platforms that are not up to date with respect to a
+    /// TCB recovery, even if the collateral has not been updated yet.
+    #[serde(default)]
+    pub min_tcb_evaluation_data_number: u32,
+    /// Platforms whose DCAP quotes are rejected.
+    #[serde(default)]
+    pub blocked_fmspcs: BTreeSet<pcs::FMSPC>,
+}
+
+impl QuotePolicy {
+    /// Quote policy of the runtime.
+    pub fn global() -> QuotePolicy {
+        QUOTE_POLICY.read().unwrap().clone()
+    }
+
+    /// Configure the quote policy of the runtime.
+    pub fn set_global(policy: QuotePolicy) {
+        *QUOTE_POLICY.write().unwrap() = policy;
+    }
+
+    fn check_age(&self, now: i64, issued_at: i64) -> Result<()> {
+        let age = now.saturating_sub(issued_at);
+        match self.max_quote_age {
+            Some(max_age) if age > 0 && age as u64 > max_age => {
+                Err(QuotePolicyError::QuoteTooOld.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_tcb_level(&self, tcb_level: TCBLevel) -> Result<()> {
+        // Unless strict verification is enabled, TCB levels other than up to
+        // date are accepted by default.
+        let strict_verification = option_env!("OASIS_STRICT_AVR_VERIFY").is_some();
+        if (strict_verification && tcb_level != TCBLevel::UpToDate)
+            || (!self.allowed_tcb_levels.is_empty()
+                && !self.allowed_tcb_levels.contains(&tcb_level))
+        {
+            return Err(QuotePolicyError::TCBLevelRejected(tcb_level).into());
+        }
+        Ok(())
+    }
+
+    fn check_tcb_evaluation_data_number(&self, number: u32) -> Result<()> {
+        if number < self.min_tcb_evaluation_data_number {
+            return Err(QuotePolicyError::TCBEvaluationDataNumberTooLow(number).into());
+        }
+        Ok(())
+    }
+
+    fn check_fmspc(&self, fmspc: &pcs::FMSPC) -> Result<()> {
+        if self.blocked_fmspcs.contains(fmspc) {
+            return Err(QuotePolicyError::PlatformBlocked(*fmspc).into());
+        }
+        Ok(())
+    }
+}
+
+/// TCB level of an attested platform, ordered from the most secure one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TCBLevel {
+    /// The platform is up to date.
+    UpToDate,
+    /// The platform is up to date, but software mitigations are required.
+    SWHardeningNeeded,
+    /// The platform is up to date, but its configuration is vulnerable.
+    ConfigurationNeeded,
+    /// The platform is up to date, but software mitigations are required and
+    /// its configuration is vulnerable.
+    ConfigurationAndSWHardeningNeeded,
+    /// The platform needs a microcode or firmware update.
+    OutOfDate,
+    /// The platform needs a microcode or firmware update and its
+    /// configuration is vulnerable.
+    OutOfDateConfigurationNeeded,
+}
+
+/// Authenticated information obtained from verifying a remote attestation.
+#[derive(Debug, Clone)]
+pub struct VerifiedQuote {
+    pub report_data: Vec<u8>,
+    pub mr_enclave: MrEnclave,
+    pub mr_signer: MrSigner,
+    pub tcb_level: TCBLevel,
+    /// Time of the attestation (the AVR timestamp for EPID, and the time of
+    /// verification for DCAP).
+    pub timestamp: i64,
+    /// Time after which the collateral (or certificates) used to verify the
+    /// attestation are no longer valid, if any.
+    pub valid_until: Option<i64>,
+}
+
+impl VerifiedQuote {
+    /// Identity of the attested enclave.
+    pub fn identity(&self) -> EnclaveIdentity {
+        EnclaveIdentity {
+            mr_enclave: self.mr_enclave.clone(),
+            mr_signer: self.mr_signer.clone(),
+        }
+    }
+}
+
+impl From<avr::AuthenticatedAVR> for VerifiedQuote {
+    fn from(avr: avr::AuthenticatedAVR) -> Self {
+        Self {
+            report_data: avr.report_data,
+            mr_enclave: avr.identity.mr_enclave,
+            mr_signer: avr.identity.mr_signer,
+            tcb_level: avr.tcb_level,
+            timestamp: avr.timestamp,
+            valid_until: None,
+        }
+    }
+}
@@ -0,0 +1,843 @@
+//! Intel SGX DCAP (ECDSA) quote verification.
+//!
+//! Unlike EPID quotes, which are verified by IAS, DCAP quotes are verified
+//! locally based on collateral provided by the Intel Provisioning
+//! Certification Service (PCS):
+//!
+//! * The quote contains the PCK certificate chain of the platform, leading
+//!   to the Intel SGX Root CA. The PCK certificate signs the report of the
+//!   quoting enclave, which in turn binds the attestation key signing the
+//!   report of the attested enclave.
+//! * The TCB info of the platform (FMSPC) determines the TCB level of the
+//!   platform based on the TCB components of its PCK certificate.
+//! * The QE identity determines whether the quoting enclave is genuine and
+//!   up to date.
+//!
+//! Note that certificate revocation lists are not checked, revoked platforms
+//! are only rejected based on the TCB info.
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use chrono::prelude::*;
+use pem::parse_many;
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+use rustc_hex::FromHex;
+use serde::{Deserialize, Serialize};
+use sgx_isa::{AttributesFlags, Report};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::{
+    avr::{MrEnclave, MrSigner},
+    QuotePolicy, TCBLevel, TeeEvidence, VerifiedQuote,
+};
+use crate::common::{
+    time::insecure_posix_time,
+    x509::{Certificate, DerReader, DER_TAG_OCTET_STRING, DER_TAG_OID, DER_TAG_SEQUENCE},
+};
+
+/// DCAP quote verification error.
+#[derive(Error, Debug)]
+enum PCSError {
+    #[error("malformed quote")]
+    MalformedQuote,
+    #[error("unsupported quote (version {version} attestation key type {key_type})")]
+    UnsupportedQuote { version: u16, key_type: u16 },
+    #[error("unsupported QE vendor")]
+    UnsupportedQEVendor,
+    #[error("unsupported certification data type {0}")]
+    UnsupportedCertificationData(u16),
+    #[error("failed to parse report body")]
+    MalformedReportBody,
+    #[error("unable to find any certificates")]
+    NoCertificates,
+    #[error("malformed certificate PEM")]
+    MalformedCertificatePEM,
+    #[error("malformed certificate")]
+    MalformedCertificate,
+    #[error("PCK certificate did not contain the SGX extension")]
+    MissingSGXExtension,
+    #[error("QE report signature verification failed")]
+    QEReportSignatureInvalid,
+    #[error("QE report does not bind the attestation key")]
+    AttestationKeyMismatch,
+    #[error("ISV report signature verification failed")]
+    ISVReportSignatureInvalid,
+    #[error("collateral signature verification failed")]
+    CollateralSignatureInvalid,
+    #[error("malformed collateral")]
+    MalformedCollateral,
+    #[error("unsupported collateral version {0}")]
+    UnsupportedCollateral(u32),
+    #[error("collateral has expired")]
+    CollateralExpired,
+    #[error("TCB info does not match the PCK certificate")]
+    TCBInfoMismatch,
+    #[error("QE report does not match the QE identity")]
+    QEIdentityMismatch,
+    #[error("no matching TCB level")]
+    TCBLevelNotFound,
+    #[error("TCB level is revoked")]
+    TCBRevoked,
+    #[error("debug enclaves not allowed")]
+    DebugEnclave,
+    #[error("production enclaves not allowed")]
+    ProductionEnclave,
+}
+
+// Quote constants.
+const QUOTE_HEADER_SIZE: usize = 48;
+pub(super) const REPORT_BODY_SIZE: usize = 384;
+const ECDSA_P256_SIGNATURE_SIZE: usize = 64;
+const ECDSA_P256_PUBLIC_KEY_SIZE: usize = 64;
+const QUOTE_VERSION: u16 = 3;
+const ATTESTATION_KEY_TYPE_ECDSA_P256: u16 = 2;
+const CERTIFICATION_DATA_PCK_CERTIFICATE_CHAIN: u16 = 5;
+const QE_VENDOR_ID_INTEL: [u8; 16] = [
+    0x93, 0x9a, 0x72, 0x33, 0xf7, 0x9c, 0x4c, 0xa9, 0x94, 0x0a, 0x0d, 0xb3, 0x95, 0x7f, 0x06, 0x07,
+];
+
+// Certificate constants.
+const PEM_CERTIFICATE_LABEL: &str = "CERTIFICATE";
+/// SGX extension of PCK certificates (1.2.840.113741.1.13.1).
+const SGX_EXTENSION_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x01, 0x0d, 0x01];
+const SGX_EXTENSION_TCB: u8 = 2;
+const SGX_EXTENSION_PCE_ID: u8 = 3;
+const SGX_EXTENSION_FMSPC: u8 = 4;
+const SGX_TCB_COMPONENTS: usize = 16;
+const SGX_TCB_PCE_SVN: u8 = 17;
+
+static PCS_SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[&webpki::ECDSA_P256_SHA256];
+
+static PCS_ANCHORS: [webpki::TrustAnchor<'static>; 1] = [
+    // Derived via webpki::trust_anchor_util::generate_code_for_trust_anchors.
+    //
+    // -----BEGIN CERTIFICATE-----
+    // MIICjzCCAjSgAwIBAgIUImUM1lqdNInzg7SVUr9QGzknBqwwCgYIKoZIzj0EAwIw
+    // aDEaMBgGA1UEAwwRSW50ZWwgU0dYIFJvb3QgQ0ExGjAYBgNVBAoMEUludGVsIENv
+    // cnBvcmF0aW9uMRQwEgYDVQQHDAtTYW50YSBDbGFyYTELMAkGA1UECAwCQ0ExCzAJ
+    // BgNVBAYTAlVTMB4XDTE4MDUyMTEwNDUxMFoXDTQ5MTIzMTIzNTk1OVowaDEaMBgG
+    // A1UEAwwRSW50ZWwgU0dYIFJvb3QgQ0ExGjAYBgNVBAoMEUludGVsIENvcnBvcmF0
+    // aW9uMRQwEgYDVQQHDAtTYW50YSBDbGFyYTELMAkGA1UECAwCQ0ExCzAJBgNVBAYT
+    // AlVTMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEC6nEwMDIYZOj/iPWsCzaEKi7
+    // 1OiOSLRFhWGjbnBVJfVnkY4u3IjkDYYL0MxO4mqsyYjlBalTVYxFP2sJBK5zlKOB
+    // uzCBuDAfBgNVHSMEGDAWgBQiZQzWWp00ifODtJVSv1AbOScGrDBSBgNVHR8ESzBJ
+    // MEegRaBDhkFodHRwczovL2NlcnRpZmljYXRlcy50cnVzdGVkc2VydmljZXMuaW50
+    // ZWwuY29tL0ludGVsU0dYUm9vdENBLmRlcjAdBgNVHQ4EFgQUImUM1lqdNInzg7SV
+    // Ur9QGzknBqwwDgYDVR0PAQH/BAQDAgEGMBIGA1UdEwEB/wQIMAYBAf8CAQEwCgYI
+    // KoZIzj0EAwIDSQAwRgIhAOW/5QkR+S9CiSDcNoowLuPRLsWGf/Yi7GSX94BgwTwg
+    // AiEA4J0lrHoMs+Xo5o/sX6O9QWxHRAvZUGOdRQ7cvqRXaqI=
+    // -----END CERTIFICATE-----
+    webpki::TrustAnchor {
+        subject: &[
+            49, 26, 48, 24, 6, 3, 85, 4, 3, 12, 17, 73, 110, 116, 101, 108, 32, 83, 71, 88, 32, 82,
+            111, 111, 116, 32, 67, 65, 49, 26, 48, 24, 6, 3, 85, 4, 10, 12, 17, 73, 110, 116, 101,
+            108, 32, 67, 111, 114, 112, 111, 114, 97, 116, 105, 111, 110, 49, 20, 48, 18, 6, 3, 85,
+            4, 7, 12, 11, 83, 97, 110, 116, 97, 32, 67, 108, 97, 114, 97, 49, 11, 48, 9, 6, 3, 85,
+            4, 8, 12, 2, 67, 65, 49, 11, 48, 9, 6, 3, 85, 4, 6, 19, 2, 85, 83,
+        ],
+        spki: &[
+            48, 19, 6, 7, 42, 134, 72, 206, 61, 2, 1, 6, 8, 42, 134, 72, 206, 61, 3, 1, 7, 3, 66,
+            0, 4, 11, 169, 196, 192, 192, 200, 97, 147, 163, 254, 35, 214, 176, 44, 218, 16, 168,
+            187, 212, 232, 142, 72, 180, 69, 133, 97, 163, 110, 112, 85, 37, 245, 103, 145, 142,
+            46, 220, 136, 228, 13, 134, 11, 208, 204, 78, 226, 106, 172, 201, 136, 229, 5, 169, 83,
+            85, 140, 69, 63, 107, 9, 4, 174, 115, 148,
+        ],
+        name_constraints: None,
+    },
+];
+
+impl_bytes!(
+    FMSPC,
+    6,
+    "Family-Model-Stepping-Platform-CustomSKU of an SGX platform."
+);
+
+/// A DCAP quote bundled with the collateral required to verify it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteBundle {
+    /// Quote generated by the quoting enclave (version 3, ECDSA P-256).
+    #[serde(with = "serde_bytes")]
+    pub quote: Vec<u8>,
+    /// Collateral of the platform the quote has been generated on.
+    pub tcb: TCBBundle,
+}
+
+impl TeeEvidence for QuoteBundle {
+    fn verify(&self, policy: &QuotePolicy) -> Result<VerifiedQuote> {
+        verify(self, policy)
+    }
+}
+
+/// Collateral obtained from the PCS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TCBBundle {
+    /// TCB info of the platform FMSPC.
+    pub tcb_info: SignedCollateral,
+    /// Identity of the quoting enclave.
+    pub qe_identity: SignedCollateral,
+    /// PEM-encoded TCB signing certificate chain (optionally URL-encoded,
+    /// as returned by the PCS).
+    #[serde(with = "serde_bytes")]
+    pub certificates: Vec<u8>,
+}
+
+/// Collateral signed by the TCB signing key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedCollateral {
+    /// Signed JSON body (the `tcbInfo` or `enclaveIdentity` object exactly
+    /// as returned by the PCS).
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+    /// Raw ECDSA P-256 signature of the body (r || s).
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl SignedCollateral {
+    /// Verify the signature of the collateral and decode it.
+    fn open<T: serde::de::DeserializeOwned>(&self, public_key: &[u8]) -> Result<T> {
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, public_key)
+            .verify(&self.body, &self.signature)
+            .map_err(|_| PCSError::CollateralSignatureInvalid)?;
+        serde_json::from_slice(&self.body).map_err(|_| PCSError::MalformedCollateral.into())
+    }
+}
+
+/// Status of a TCB level, as defined by the collateral.
+#[derive(Clone, Copy, Debug, Deserialize)]
+enum TCBStatus {
+    UpToDate,
+    SWHardeningNeeded,
+    ConfigurationNeeded,
+    ConfigurationAndSWHardeningNeeded,
+    OutOfDate,
+    OutOfDateConfigurationNeeded,
+    Revoked,
+}
+
+impl TCBStatus {
+    fn level(self) -> Result<TCBLevel> {
+        match self {
+            TCBStatus::UpToDate => Ok(TCBLevel::UpToDate),
+            TCBStatus::SWHardeningNeeded => Ok(TCBLevel::SWHardeningNeeded),
+            TCBStatus::ConfigurationNeeded => Ok(TCBLevel::ConfigurationNeeded),
+            TCBStatus::ConfigurationAndSWHardeningNeeded => {
+                Ok(TCBLevel::ConfigurationAndSWHardeningNeeded)
+            }
+            TCBStatus::OutOfDate => Ok(TCBLevel::OutOfDate),
+            TCBStatus::OutOfDateConfigurationNeeded => Ok(TCBLevel::OutOfDateConfigurationNeeded),
+            TCBStatus::Revoked => Err(PCSError::TCBRevoked.into()),
+        }
+    }
+}
+
+/// TCB info of a platform (version 2).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TCBInfo {
+    version: u32,
+    issue_date: String,
+    next_update: String,
+    fmspc: String,
+    pce_id: String,
+    tcb_evaluation_data_number: u32,
+    /// TCB levels, ordered from the latest one.
+    tcb_levels: Vec<TCBInfoLevel>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TCBInfoLevel {
+    /// Minimum SVN of each TCB component (`sgxtcbcompNNsvn`) and of the
+    /// PCE (`pcesvn`).
+    tcb: BTreeMap<String, u16>,
+    tcb_status: TCBStatus,
+}
+
+impl TCBInfo {
+    /// Determine the TCB level of the platform with the given PCK
+    /// certificate.
+    fn level(&self, pck: &PCKCertificate, now: i64) -> Result<TCBLevel> {
+        if self.version != 2 {
+            return Err(PCSError::UnsupportedCollateral(self.version).into());
+        }
+        check_next_update(&self.next_update, now)?;
+        if decode_hex(&self.fmspc)? != pck.fmspc.as_ref() || decode_hex(&self.pce_id)? != pck.pce_id
+        {
+            return Err(PCSError::TCBInfoMismatch.into());
+        }
+
+        for level in &self.tcb_levels {
+            if level.is_satisfied_by(pck)? {
+                return level.tcb_status.level();
+            }
+        }
+        Err(PCSError::TCBLevelNotFound.into())
+    }
+}
+
+impl TCBInfoLevel {
+    fn is_satisfied_by(&self, pck: &PCKCertificate) -> Result<bool> {
+        for (i, &svn) in pck.tcb_components.iter().enumerate() {
+            if svn < self.svn(&format!("sgxtcbcomp{:02}svn", i + 1))? {
+                return Ok(false);
+            }
+        }
+        Ok(pck.pce_svn >= self.svn("pcesvn")?)
+    }
+
+    fn svn(&self, component: &str) -> Result<u16> {
+        match self.tcb.get(component) {
+            Some(&svn) => Ok(svn),
+            None => Err(PCSError::MalformedCollateral.into()),
+        }
+    }
+}
+
+/// Identity of the quoting enclave (version 2).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QEIdentity {
+    id: String,
+    version: u32,
+    issue_date: String,
+    next_update: String,
+    tcb_evaluation_data_number: u32,
+    miscselect: String,
+    miscselect_mask: String,
+    attributes: String,
+    attributes_mask: String,
+    mrsigner: String,
+    isvprodid: u16,
+    /// TCB levels, ordered from the latest one.
+    tcb_levels: Vec<QEIdentityLevel>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QEIdentityLevel {
+    tcb: QEIdentityTCB,
+    tcb_status: TCBStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct QEIdentityTCB {
+    isvsvn: u16,
+}
+
+impl QEIdentity {
+    /// Verify the report of the quoting enclave and determine its TCB level.
+    fn level(&self, qe_report: &Report, now: i64) -> Result<TCBLevel> {
+        if self.id != "QE" || self.version != 2 {
+            return Err(PCSError::UnsupportedCollateral(self.version).into());
+        }
+        check_next_update(&self.next_update, now)?;
+
+        let mrsigner =
+            MrSigner::from_str(&self.mrsigner).map_err(|_| PCSError::MalformedCollateral)?;
+        if MrSigner(qe_report.mrsigner) != mrsigner || qe_report.isvprodid != self.isvprodid {
+            return Err(PCSError::QEIdentityMismatch.into());
+        }
+
+        let miscselect = qe_report.miscselect.bits().to_le_bytes();
+        let mut attributes = [0u8; 16];
+        attributes[..8].copy_from_slice(&qe_report.attributes.flags.bits().to_le_bytes());
+        attributes[8..].copy_from_slice(&qe_report.attributes.xfrm.to_le_bytes());
+        if !masked_eq(
+            &miscselect,
+            &decode_hex(&self.miscselect)?,
+            &decode_hex(&self.miscselect_mask)?,
+        ) || !masked_eq(
+            &attributes,
+            &decode_hex(&self.attributes)?,
+            &decode_hex(&self.attributes_mask)?,
+        ) {
+            return Err(PCSError::QEIdentityMismatch.into());
+        }
+
+        for level in &self.tcb_levels {
+            if qe_report.isvsvn >= level.tcb.isvsvn {
+                return level.tcb_status.level();
+            }
+        }
+        Err(PCSError::TCBLevelNotFound.into())
+    }
+}
+
+fn check_next_update(next_update: &str, now: i64) -> Result<()> {
+    if now > parse_date(next_update)? {
+        return Err(PCSError::CollateralExpired.into());
+    }
+    Ok(())
+}
+
+fn parse_date(date: &str) -> Result<i64> {
+    Ok(DateTime::parse_from_rfc3339(date)
+        .map_err(|_| PCSError::MalformedCollateral)?
+        .timestamp())
+}
+
+fn decode_hex(data: &str) -> Result<Vec<u8>> {
+    data.from_hex()
+        .map_err(|_| PCSError::MalformedCollateral.into())
+}
+
+fn masked_eq(actual: &[u8], expected: &[u8], mask: &[u8]) -> bool {
+    actual.len() == expected.len()
+        && actual.len() == mask.len()
+        && actual
+            .iter()
+            .zip(expected)
+            .zip(mask)
+            .all(|((a, e), m)| a & m == e & m)
+}
+
+/// Parsed (but not yet verified) quote.
+struct ParsedQuote<'a> {
+    /// Part of the quote signed by the attestation key (header and report).
+    signed: &'a [u8],
+    report_body: Report,
+    report_signature: &'a [u8],
+    attestation_key: &'a [u8],
+    qe_report_body: &'a [u8],
+    qe_report: Report,
+    qe_report_signature: &'a [u8],
+    qe_authentication_data: &'a [u8],
+    certification_data: &'a [u8],
+}
+
+impl<'a> ParsedQuote<'a> {
+    fn parse(quote: &'a [u8]) -> Result<Self> {
+        let mut data = quote;
+
+        // Header.
+        let header = take(&mut data, QUOTE_HEADER_SIZE)?;
+        let version = LittleEndian::read_u16(&header[0..2]);
+        let key_type = LittleEndian::read_u16(&header[2..4]);
+        if version != QUOTE_VERSION || key_type != ATTESTATION_KEY_TYPE_ECDSA_P256 {
+            return Err(PCSError::UnsupportedQuote { version, key_type }.into());
+        }
+        if header[12..28] != QE_VENDOR_ID_INTEL {
+            return Err(PCSError::UnsupportedQEVendor.into());
+        }
+
+        // Report body.
+        let report_body = decode_report(take(&mut data, REPORT_BODY_SIZE)?)?;
+        let signed = &quote[..QUOTE_HEADER_SIZE + REPORT_BODY_SIZE];
+
+        // Signature data.
+        let signature_data_len = LittleEndian::read_u32(take(&mut data, 4)?) as usize;
+        let mut signature_data = take(&mut data, signature_data_len)?;
+        if !data.is_empty() {
+            return Err(PCSError::MalformedQuote.into());
+        }
+        let data = &mut signature_data;
+
+        let report_signature = take(data, ECDSA_P256_SIGNATURE_SIZE)?;
+        let attestation_key = take(data, ECDSA_P256_PUBLIC_KEY_SIZE)?;
+        let qe_report_body = take(data, REPORT_BODY_SIZE)?;
+        let qe_report = decode_report(qe_report_body)?;
+        let qe_report_signature = take(data, ECDSA_P256_SIGNATURE_SIZE)?;
+        let qe_authentication_data_len = LittleEndian::read_u16(take(data, 2)?) as usize;
+        let qe_authentication_data = take(data, qe_authentication_data_len)?;
+        let certification_data_type = LittleEndian::read_u16(take(data, 2)?);
+        let certification_data_len = LittleEndian::read_u32(take(data, 4)?) as usize;
+        let certification_data = take(data, certification_data_len)?;
+        if certification_data_type != CERTIFICATION_DATA_PCK_CERTIFICATE_CHAIN {
+            return Err(PCSError::UnsupportedCertificationData(certification_data_type).into());
+        }
+
+        Ok(Self {
+            signed,
+            report_body,
+            report_signature,
+            attestation_key,
+            qe_report_body,
+            qe_report,
+            qe_report_signature,
+            qe_authentication_data,
+            certification_data,
+        })
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(PCSError::MalformedQuote.into());
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+pub(super) fn decode_report(body: &[u8]) -> Result<Report> {
+    let mut report = vec![0; Report::UNPADDED_SIZE];
+    report[..body.len()].copy_from_slice(body);
+    Report::try_copy_from(&report).ok_or_else(|| PCSError::MalformedReportBody.into())
+}
+
+/// Platform information contained in a PCK certificate.
+#[derive(Debug, Default)]
+struct PCKCertificate {
+    public_key: Vec<u8>,
+    fmspc: FMSPC,
+    pce_id: Vec<u8>,
+    tcb_components: [u16; SGX_TCB_COMPONENTS],
+    pce_svn: u16,
+}
+
+impl PCKCertificate {
+    fn parse(der: &[u8]) -> Result<Self> {
+        let certificate = Certificate::parse(der)?;
+        let extension = match certificate.extensions.get(SGX_EXTENSION_OID) {
+            Some(extension) => extension,
+            None => return Err(PCSError::MissingSGXExtension.into()),
+        };
+
+        let mut pck = PCKCertificate {
+            public_key: certificate.public_key.to_vec(),
+            ..Default::default()
+        };
+        let (mut has_tcb, mut has_pce_id, mut has_fmspc) = (false, false, false);
+        let mut items = DerReader::new(DerReader::new(extension).read(DER_TAG_SEQUENCE)?);
+        while !items.is_empty() {
+            let mut item = DerReader::new(items.read(DER_TAG_SEQUENCE)?);
+            match sgx_extension_id(item.read(DER_TAG_OID)?) {
+                Some([SGX_EXTENSION_TCB]) => {
+                    pck.parse_tcb(item.read(DER_TAG_SEQUENCE)?)?;
+                    has_tcb = true;
+                }
+                Some([SGX_EXTENSION_PCE_ID]) => {
+                    pck.pce_id = item.read(DER_TAG_OCTET_STRING)?.to_vec();
+                    has_pce_id = true;
+                }
+                Some([SGX_EXTENSION_FMSPC]) => {
+                    let fmspc = item.read(DER_TAG_OCTET_STRING)?;
+                    if fmspc.len() != FMSPC::len() {
+                        return Err(PCSError::MalformedCertificate.into());
+                    }
+                    pck.fmspc = FMSPC::from(fmspc);
+                    has_fmspc = true;
+                }
+                _ => {}
+            }
+        }
+        if !has_tcb || !has_pce_id || !has_fmspc {
+            return Err(PCSError::MissingSGXExtension.into());
+        }
+
+        Ok(pck)
+    }
+
+    fn parse_tcb(&mut self, tcb: &[u8]) -> Result<()> {
+        let mut components = 0;
+        let mut has_pce_svn = false;
+        let mut items = DerReader::new(tcb);
+        while !items.is_empty() {
+            let mut item = DerReader::new(items.read(DER_TAG_SEQUENCE)?);
+            match sgx_extension_id(item.read(DER_TAG_OID)?) {
+                Some([SGX_EXTENSION_TCB, index])
+                    if *index >= 1 && *index as usize <= SGX_TCB_COMPONENTS =>
+                {
+                    self.tcb_components[*index as usize - 1] = item.read_u16()?;
+                    components += 1;
+                }
+                Some([SGX_EXTENSION_TCB, SGX_TCB_PCE_SVN]) => {
+                    self.pce_svn = item.read_u16()?;
+                    has_pce_svn = true;
+                }
+                _ => {}
+            }
+        }
+        if components != SGX_TCB_COMPONENTS || !has_pce_svn {
+            return Err(PCSError::MissingSGXExtension.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Return the trailing arcs of an OID in the SGX extension namespace.
+fn sgx_extension_id(oid: &[u8]) -> Option<&[u8]> {
+    if oid.starts_with(SGX_EXTENSION_OID) {
+        Some(&oid[SGX_EXTENSION_OID.len()..])
+    } else {
+        None
+    }
+}
+
+fn decode_certificate_chain(pem: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let raw_pem = percent_encoding::percent_decode(pem).decode_utf8()?;
+    let mut chain = Vec::new();
+    for pem in parse_many(&raw_pem.as_bytes()) {
+        if pem.tag != PEM_CERTIFICATE_LABEL {
+            return Err(PCSError::MalformedCertificatePEM.into());
+        }
+        chain.push(pem.contents);
+    }
+    if chain.is_empty() {
+        return Err(PCSError::NoCertificates.into());
+    }
+
+    Ok(chain)
+}
+
+fn validate_certificate_chain(
+    anchors: &[webpki::TrustAnchor],
+    chain: &[Vec<u8>],
+    now: i64,
+) -> Result<()> {
+    let (leaf, intermediates) = chain.split_first().ok_or(PCSError::NoCertificates)?;
+    let intermediates: Vec<_> = intermediates.iter().map(|der| &der[..]).collect();
+    let time = webpki::Time::from_seconds_since_unix_epoch(now as u64);
+
+    webpki::EndEntityCert::from(leaf)
+        .and_then(|cert| {
+            cert.verify_is_valid_tls_server_cert(
+                PCS_SIG_ALGS,
+                &webpki::TLSServerTrustAnchors(anchors),
+                &intermediates,
+                time,
+            )
+        })
+        .map_err(|err| anyhow!("failed to validate certificate chain: {:?}", err))
+}
+
+fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, public_key)
+        .verify(message, signature)
+        .is_ok()
+}
+
+/// Verify a DCAP quote and its collateral under the given quote policy.
+pub fn verify(bundle: &QuoteBundle, policy: &QuotePolicy) -> Result<VerifiedQuote> {
+    verify_at(bundle, policy, &PCS_ANCHORS, insecure_posix_time())
+}
+
+fn verify_at(
+    bundle: &QuoteBundle,
+    policy: &QuotePolicy,
+    anchors: &[webpki::TrustAnchor],
+    now: i64,
+) -> Result<VerifiedQuote> {
+    let quote = ParsedQuote::parse(&bundle.quote)?;
+
+    // Verify the PCK certificate chain and the QE report signed by it.
+    let pck_chain = decode_certificate_chain(quote.certification_data)?;
+    validate_certificate_chain(anchors, &pck_chain, now)?;
+    let pck = PCKCertificate::parse(&pck_chain[0])?;
+    policy.check_fmspc(&pck.fmspc)?;
+    if !verify_signature(
+        &pck.public_key,
+        quote.qe_report_body,
+        quote.qe_report_signature,
+    ) {
+        return Err(PCSError::QEReportSignatureInvalid.into());
+    }
+
+    // Verify that the QE report binds the attestation key, and the report
+    // signed by it: SHA256(attestation_key || qe_authentication_data).
+    let mut hasher = Sha256::new();
+    hasher.update(quote.attestation_key);
+    hasher.update(quote.qe_authentication_data);
+    let binding = hasher.finalize();
+    if quote.qe_report.reportdata[..32] != binding[..]
+        || quote.qe_report.reportdata[32..].iter().any(|&b| b != 0)
+    {
+        return Err(PCSError::AttestationKeyMismatch.into());
+    }
+    let attestation_key = [&[0x04][..], quote.attestation_key].concat();
+    if !verify_signature(&attestation_key, quote.signed, quote.report_signature) {
+        return Err(PCSError::ISVReportSignatureInvalid.into());
+    }
+
+    // Verify the collateral and determine the TCB level.
+    let tcb_chain = decode_certificate_chain(&bundle.tcb.certificates)?;
+    validate_certificate_chain(anchors, &tcb_chain, now)?;
+    let tcb_signing_key = Certificate::parse(&tcb_chain[0])?.public_key;
+    let tcb_info: TCBInfo = bundle.tcb.tcb_info.open(tcb_signing_key)?;
+    let qe_identity: QEIdentity = bundle.tcb.qe_identity.open(tcb_signing_key)?;
+    let tcb_level = tcb_info
+        .level(&pck, now)?
+        .max(qe_identity.level(&quote.qe_report, now)?);
+    policy.check_tcb_level(tcb_level)?;
+    policy.check_tcb_evaluation_data_number(
+        tcb_info
+            .tcb_evaluation_data_number
+            .min(qe_identity.tcb_evaluation_data_number),
+    )?;
+    policy.check_age(
+        now,
+        parse_date(&tcb_info.issue_date)?.min(parse_date(&qe_identity.issue_date)?),
+    )?;
+
+    // Disallow debug enclaves, if we are in production environment and disallow production enclaves,
+    // if we are in debug environment.
+    let is_debug = quote
+        .report_body
+        .attributes
+        .flags
+        .contains(AttributesFlags::DEBUG);
+    let allow_debug = option_env!("OASIS_UNSAFE_ALLOW_DEBUG_ENCLAVES").is_some();
+    if is_debug && !allow_debug {
+        return Err(PCSError::DebugEnclave.into());
+    } else if !is_debug && allow_debug {
+        return Err(PCSError::ProductionEnclave.into());
+    }
+
+    Ok(VerifiedQuote {
+        report_data: quote.report_body.reportdata.to_vec(),
+        mr_enclave: MrEnclave(quote.report_body.mrenclave),
+        mr_signer: MrSigner(quote.report_body.mrsigner),
+        tcb_level,
+        timestamp: now,
+        valid_until: Some(
+            parse_date(&tcb_info.next_update)?.min(parse_date(&qe_identity.next_update)?),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Quote and collateral signed by a test root CA, with the same structure
+    // as the ones provided by Intel.
+    const QUOTE: &[u8] = include_bytes!("../../../testdata/pcs_quote.bin");
+    const TCB_INFO: &[u8] = include_bytes!("../../../testdata/pcs_tcb_info.json");
+    const TCB_INFO_SIG: &[u8] = include_bytes!("../../../testdata/pcs_tcb_info.sig");
+    const QE_IDENTITY: &[u8] = include_bytes!("../../../testdata/pcs_qe_identity.json");
+    const QE_IDENTITY_SIG: &[u8] = include_bytes!("../../../testdata/pcs_qe_identity.sig");
+    const TCB_CERTIFICATES: &[u8] = include_bytes!("../../../testdata/pcs_tcb_certificates.pem");
+    const TEST_ROOT_CA: &[u8] = include_bytes!("../../../testdata/pcs_test_root_ca.der");
+    const NOW: i64 = 1640995200; // 2022-01-01T00:00:00
+
+    fn bundle() -> QuoteBundle {
+        QuoteBundle {
+            quote: QUOTE.to_vec(),
+            tcb: TCBBundle {
+                tcb_info: SignedCollateral {
+                    body: TCB_INFO.to_vec(),
+                    signature: TCB_INFO_SIG.to_vec(),
+                },
+                qe_identity: SignedCollateral {
+                    body: QE_IDENTITY.to_vec(),
+                    signature: QE_IDENTITY_SIG.to_vec(),
+                },
+                certificates: TCB_CERTIFICATES.to_vec(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_verify_quote() {
+        let anchors = [webpki::trust_anchor_util::cert_der_as_trust_anchor(TEST_ROOT_CA).unwrap()];
+        let policy = QuotePolicy::default();
+
+        // Positive test.
+        let verified = verify_at(&bundle(), &policy, &anchors, NOW).unwrap();
+        assert_eq!(verified.mr_enclave, MrEnclave([0x11; 32]));
+        assert_eq!(verified.mr_signer, MrSigner([0x22; 32]));
+        assert_eq!(verified.tcb_level, TCBLevel::SWHardeningNeeded);
+        assert!(verified
+            .report_data
+            .starts_with(b"oasis-core/test: report data"));
+        assert_eq!(verified.valid_until, Some(2493072000)); // 2049-01-01T00:00:00
+
+        // Untrusted root.
+        assert!(verify_at(&bundle(), &policy, &PCS_ANCHORS, NOW).is_err());
+
+        // Expired certificates and collateral.
+        assert!(verify_at(&bundle(), &policy, &anchors, 2524608000).is_err());
+
+        // Tampered report body.
+        let mut tampered = bundle();
+        tampered.quote[QUOTE_HEADER_SIZE + 64] ^= 0x01;
+        assert!(verify_at(&tampered, &policy, &anchors, NOW).is_err());
+
+        // Tampered QE report.
+        let mut tampered = bundle();
+        let qe_report_offset = QUOTE_HEADER_SIZE + REPORT_BODY_SIZE + 4 + 128;
+        tampered.quote[qe_report_offset + 256] ^= 0x01;
+        assert!(verify_at(&tampered, &policy, &anchors, NOW).is_err());
+
+        // Tampered collateral.
+        let mut tampered = bundle();
+        tampered.tcb.tcb_info.body[10] ^= 0x01;
+        assert!(verify_at(&tampered, &policy, &anchors, NOW).is_err());
+
+        // Truncated quote.
+        let mut tampered = bundle();
+        tampered.quote.truncate(QUOTE.len() - 1);
+        assert!(verify_at(&tampered, &policy, &anchors, NOW).is_err());
+    }
+
+    #[test]
+    fn test_quote_policy() {
+        let anchors = [webpki::trust_anchor_util::cert_der_as_trust_anchor(TEST_ROOT_CA).unwrap()];
+        let verify = |policy: &QuotePolicy| verify_at(&bundle(), policy, &anchors, NOW);
+
+        // The collateral has been issued on 2021-06-01.
+        let mut policy = QuotePolicy {
+            max_quote_age: Some(365 * 24 * 60 * 60),
+            ..Default::default()
+        };
+        assert!(verify(&policy).is_ok());
+        policy.max_quote_age = Some(24 * 60 * 60);
+        assert!(verify(&policy).is_err(), "collateral too old");
+
+        let mut policy = QuotePolicy::default();
+        policy.allowed_tcb_levels.insert(TCBLevel::UpToDate);
+        assert!(verify(&policy).is_err(), "TCB level not allowed");
+        policy
+            .allowed_tcb_levels
+            .insert(TCBLevel::SWHardeningNeeded);
+        assert!(verify(&policy).is_ok());
+
+        let mut policy = QuotePolicy {
+            min_tcb_evaluation_data_number: 11,
+            ..Default::default()
+        };
+        assert!(verify(&policy).is_ok());
+        policy.min_tcb_evaluation_data_number = 12;
+        assert!(
+            verify(&policy).is_err(),
+            "TCB evaluation data number too low"
+        );
+
+        let mut policy = QuotePolicy::default();
+        policy
+            .blocked_fmspcs
+            .insert(FMSPC::from_str("00906ea10001").unwrap());
+        assert!(verify(&policy).is_ok());
+        policy
+            .blocked_fmspcs
+            .insert(FMSPC::from_str("00906ea10000").unwrap());
+        assert!(verify(&policy).is_err(), "platform blocked");
+    }
+
+    #[test]
+    fn test_tcb_level() {
+        let tcb_info: TCBInfo = serde_json::from_slice(TCB_INFO).unwrap();
+        let mut pck = PCKCertificate {
+            fmspc: FMSPC([0x00, 0x90, 0x6e, 0xa1, 0x00, 0x00]),
+            pce_id: vec![0x00, 0x00],
+            tcb_components: [6, 6, 2, 2, 4, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            pce_svn: 11,
+            ..Default::default()
+        };
+        assert_eq!(TCBLevel::UpToDate, tcb_info.level(&pck, NOW).unwrap());
+
+        pck.pce_svn = 10;
+        assert_eq!(TCBLevel::OutOfDate, tcb_info.level(&pck, NOW).unwrap());
+
+        pck.tcb_components[0] = 1;
+        assert!(tcb_info.level(&pck, NOW).is_err(), "revoked");
+
+        pck.tcb_components[0] = 6;
+        pck.fmspc.0[0] = 0x01;
+        assert!(tcb_info.level(&pck, NOW).is_err(), "other platform");
+    }
+}
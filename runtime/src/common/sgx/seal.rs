@@ -1,13 +1,67 @@
 //! Wrappers for sealing secrets to the enclave in cold storage.
+//!
+//! Secrets sealed with `seal_versioned` (or `seal_cbor`) carry a versioned
+//! header, which records the key policy and the security version (ISV SVN)
+//! of the sealing enclave. This allows later versions of the enclave to
+//! unseal them and to migrate them to their own sealing key (see `reseal`).
+use anyhow::Result;
 use rand::{rngs::OsRng, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sgx_isa::Keypolicy;
+use thiserror::Error;
 use zeroize::Zeroize;
 
 use crate::common::{
+    cbor,
     crypto::mrae::deoxysii::{DeoxysII, NONCE_SIZE, TAG_SIZE},
-    sgx::egetkey::egetkey,
+    sgx::egetkey::{current_isv_svn, egetkey_for_svn},
 };
 
+/// Prefix of versioned sealed payloads.
+const VERSIONED_MAGIC: &[u8] = b"EkSl";
+/// Current version of the sealed payload format.
+const VERSION: u16 = 1;
+
+/// Sealing error.
+#[derive(Error, Debug)]
+pub enum SealError {
+    #[error("seal: ciphertext is corrupted")]
+    Corrupted,
+    #[error("seal: unsupported version {0}")]
+    UnsupportedVersion(u16),
+    #[error("seal: unexpected key policy")]
+    UnexpectedKeyPolicy,
+    #[error("seal: sealed by a later enclave version ({isv_svn} > {current})")]
+    LaterEnclaveVersion { isv_svn: u16, current: u16 },
+}
+
+/// Versioned sealed payload.
+#[derive(Serialize, Deserialize)]
+struct SealedPayload {
+    version: u16,
+    key_policy: u16,
+    /// Security version of the sealing enclave, whose sealing key is used.
+    isv_svn: u16,
+    #[serde(with = "serde_bytes")]
+    nonce: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    ciphertext: Vec<u8>,
+}
+
+impl SealedPayload {
+    /// Additional data authenticating the header.
+    fn additional_data(&self) -> Vec<u8> {
+        cbor::to_vec(&(self.version, self.key_policy, self.isv_svn))
+    }
+
+    fn decode(sealed: &[u8]) -> Option<Self> {
+        if !sealed.starts_with(VERSIONED_MAGIC) {
+            return None;
+        }
+        cbor::from_slice(&sealed[VERSIONED_MAGIC.len()..]).ok()
+    }
+}
+
 /// Seal a secret to the enclave.
 ///
 /// The `context` field is a domain separation tag.
@@ -17,7 +71,7 @@ pub fn seal(key_policy: Keypolicy, context: &[u8], data: &[u8]) -> Vec<u8> {
     // Encrypt the raw policy.
     let mut nonce = [0u8; NONCE_SIZE];
     rng.fill(&mut nonce);
-    let d2 = new_d2(key_policy, context);
+    let d2 = new_d2(key_policy, context, 0);
     let mut ciphertext = d2.seal(&nonce, data.to_vec(), vec![]);
     ciphertext.extend_from_slice(&nonce);
 
@@ -33,11 +87,172 @@ pub fn seal(key_policy: Keypolicy, context: &[u8], data: &[u8]) -> Vec<u8> {
 /// All parsing and authentication errors of the ciphertext are fatal and
 /// will result in a panic.
 pub fn unseal(key_policy: Keypolicy, context: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
-    let ct_len = ciphertext.len();
-    if ct_len == 0 {
+    if ciphertext.is_empty() {
         return None;
-    } else if ct_len < TAG_SIZE + NONCE_SIZE {
-        panic!("ciphertext is corrupted, invalid size");
+    }
+
+    Some(open_legacy(key_policy, context, ciphertext).expect("ciphertext is corrupted"))
+}
+
+/// Seal a secret to the enclave, with a versioned header.
+///
+/// The secret is sealed with the sealing key of the current enclave version.
+/// The `context` field is a domain separation tag.
+pub fn seal_versioned(key_policy: Keypolicy, context: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut rng = OsRng {};
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    rng.fill(&mut nonce);
+    let mut payload = SealedPayload {
+        version: VERSION,
+        key_policy: key_policy.bits(),
+        isv_svn: current_isv_svn(),
+        nonce: nonce.to_vec(),
+        ciphertext: vec![],
+    };
+    let d2 = new_d2(key_policy, context, payload.isv_svn);
+    payload.ciphertext = d2.seal(&nonce, data.to_vec(), payload.additional_data());
+
+    let mut sealed = VERSIONED_MAGIC.to_vec();
+    sealed.extend_from_slice(&cbor::to_vec(&payload));
+    sealed
+}
+
+/// Unseal a secret sealed by `seal_versioned` with the given key policy,
+/// by the current or an earlier version of the enclave.
+///
+/// Secrets sealed by `seal` with the same key policy and context are also
+/// accepted, so that existing secrets can still be unsealed after switching
+/// to versioned sealing. Returns `None` if the ciphertext is empty.
+pub fn unseal_versioned(
+    key_policy: Keypolicy,
+    context: &[u8],
+    sealed: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    if sealed.is_empty() {
+        return Ok(None);
+    }
+    if !sealed.starts_with(VERSIONED_MAGIC) {
+        return Ok(Some(open_legacy(key_policy, context, sealed)?));
+    }
+
+    let payload = SealedPayload::decode(sealed).ok_or(SealError::Corrupted)?;
+    if payload.version != VERSION {
+        return Err(SealError::UnsupportedVersion(payload.version).into());
+    }
+    if payload.key_policy != key_policy.bits() {
+        return Err(SealError::UnexpectedKeyPolicy.into());
+    }
+    let current = current_isv_svn();
+    if payload.isv_svn > current {
+        return Err(SealError::LaterEnclaveVersion {
+            isv_svn: payload.isv_svn,
+            current,
+        }
+        .into());
+    }
+    if payload.nonce.len() != NONCE_SIZE {
+        return Err(SealError::Corrupted.into());
+    }
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&payload.nonce);
+    let d2 = new_d2(key_policy, context, payload.isv_svn);
+    let plaintext = d2
+        .open(
+            &nonce,
+            payload.ciphertext.clone(),
+            payload.additional_data(),
+        )
+        .map_err(|_| SealError::Corrupted)?;
+
+    Ok(Some(plaintext))
+}
+
+/// Seal a CBOR-serializable value to the enclave, with a versioned header.
+///
+/// See `seal_versioned`.
+pub fn seal_cbor<T: Serialize>(key_policy: Keypolicy, context: &[u8], value: &T) -> Vec<u8> {
+    let mut plaintext = cbor::to_vec(value);
+    let sealed = seal_versioned(key_policy, context, &plaintext);
+    plaintext.zeroize();
+
+    sealed
+}
+
+/// Unseal a value sealed by `seal_cbor`.
+///
+/// See `unseal_versioned`.
+pub fn unseal_cbor<T: DeserializeOwned>(
+    key_policy: Keypolicy,
+    context: &[u8],
+    sealed: &[u8],
+) -> Result<Option<T>> {
+    match unseal_versioned(key_policy, context, sealed)? {
+        Some(mut plaintext) => {
+            let value = cbor::from_slice(&plaintext);
+            plaintext.zeroize();
+            Ok(Some(value?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Check whether the sealed secret has a versioned header, as opposed to
+/// secrets sealed with `seal` or with a custom format.
+pub fn is_versioned(sealed: &[u8]) -> bool {
+    sealed.starts_with(VERSIONED_MAGIC)
+}
+
+/// Check whether a sealed secret should be migrated with `reseal`, because
+/// it has not been sealed with a versioned header by the current enclave
+/// version.
+pub fn needs_reseal(sealed: &[u8]) -> bool {
+    if sealed.is_empty() {
+        return false;
+    }
+    match SealedPayload::decode(sealed) {
+        Some(payload) => payload.version != VERSION || payload.isv_svn < current_isv_svn(),
+        None => true,
+    }
+}
+
+/// Migrate a sealed secret to the given key policy and to the sealing key
+/// of the current enclave version.
+///
+/// This is used when upgrading an enclave:
+///
+/// * Secrets sealed with the `MRSIGNER` policy can be unsealed by later
+///   versions of the enclave, which reseal them from `MRSIGNER` to
+///   `MRSIGNER` to rotate them to their own sealing key.
+///
+/// * Secrets sealed with the `MRENCLAVE` policy can only be unsealed by the
+///   same enclave, so before the upgrade the old version must reseal them
+///   from `MRENCLAVE` to `MRSIGNER`, and the new version then reseals them
+///   from `MRSIGNER` back to `MRENCLAVE`.
+///
+/// Returns `None` if the ciphertext is empty.
+pub fn reseal(
+    from: Keypolicy,
+    to: Keypolicy,
+    context: &[u8],
+    sealed: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    match unseal_versioned(from, context, sealed)? {
+        Some(mut plaintext) => {
+            let resealed = seal_versioned(to, context, &plaintext);
+            plaintext.zeroize();
+            Ok(Some(resealed))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Open a secret sealed by `seal`.
+fn open_legacy(key_policy: Keypolicy, context: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let ct_len = ciphertext.len();
+    if ct_len < TAG_SIZE + NONCE_SIZE {
+        return Err(SealError::Corrupted.into());
     }
     let ct_len = ct_len - NONCE_SIZE;
 
@@ -46,16 +261,16 @@ pub fn unseal(key_policy: Keypolicy, context: &[u8], ciphertext: &[u8]) -> Optio
     nonce.copy_from_slice(&ciphertext[ct_len..]);
     let ciphertext = &ciphertext[..ct_len];
 
-    let d2 = new_d2(key_policy, context);
+    let d2 = new_d2(key_policy, context, 0);
     let plaintext = d2
         .open(&nonce, ciphertext.to_vec(), vec![])
-        .expect("ciphertext is corrupted");
+        .map_err(|_| SealError::Corrupted)?;
 
-    Some(plaintext)
+    Ok(plaintext)
 }
 
-fn new_d2(key_policy: Keypolicy, context: &[u8]) -> DeoxysII {
-    let mut seal_key = egetkey(key_policy, context);
+fn new_d2(key_policy: Keypolicy, context: &[u8], isv_svn: u16) -> DeoxysII {
+    let mut seal_key = egetkey_for_svn(key_policy, context, isv_svn);
     let d2 = DeoxysII::new(&seal_key);
     seal_key.zeroize();
 
@@ -82,6 +297,75 @@ mod tests {
         assert_eq!(unsealed_c, None);
     }
 
+    #[test]
+    fn test_seal_versioned() {
+        let sealed = seal_cbor(Keypolicy::MRENCLAVE, b"MRENCLAVE", &42u64);
+        assert!(!needs_reseal(&sealed));
+        let unsealed: Option<u64> =
+            unseal_cbor(Keypolicy::MRENCLAVE, b"MRENCLAVE", &sealed).unwrap();
+        assert_eq!(unsealed, Some(42));
+
+        // Test incorrect key policy, context and ciphertext.
+        assert!(unseal_versioned(Keypolicy::MRSIGNER, b"MRENCLAVE", &sealed).is_err());
+        assert!(unseal_versioned(Keypolicy::MRENCLAVE, b"MRSIGNER", &sealed).is_err());
+        let mut payload = SealedPayload::decode(&sealed).unwrap();
+        payload.ciphertext[0] = payload.ciphertext[0].wrapping_add(1);
+        let mut corrupted = VERSIONED_MAGIC.to_vec();
+        corrupted.extend_from_slice(&cbor::to_vec(&payload));
+        assert!(unseal_versioned(Keypolicy::MRENCLAVE, b"MRENCLAVE", &corrupted).is_err());
+
+        // Test payloads sealed by a later enclave version.
+        let mut payload = SealedPayload::decode(&sealed).unwrap();
+        payload.isv_svn = current_isv_svn() + 1;
+        let mut later = VERSIONED_MAGIC.to_vec();
+        later.extend_from_slice(&cbor::to_vec(&payload));
+        assert!(unseal_versioned(Keypolicy::MRENCLAVE, b"MRENCLAVE", &later).is_err());
+
+        // Test zero-length ciphertext.
+        assert!(unseal_versioned(Keypolicy::MRENCLAVE, b"MRENCLAVE", b"")
+            .unwrap()
+            .is_none());
+        assert!(!needs_reseal(b""));
+    }
+
+    #[test]
+    fn test_reseal() {
+        // Legacy payloads can be unsealed and migrated.
+        let legacy = seal(Keypolicy::MRENCLAVE, b"MRENCLAVE", b"Mr. Enclave");
+        assert!(needs_reseal(&legacy));
+        assert_eq!(
+            unseal_versioned(Keypolicy::MRENCLAVE, b"MRENCLAVE", &legacy).unwrap(),
+            Some(b"Mr. Enclave".to_vec())
+        );
+
+        // Migrate from MRENCLAVE to MRSIGNER and back.
+        let exported = reseal(
+            Keypolicy::MRENCLAVE,
+            Keypolicy::MRSIGNER,
+            b"MRENCLAVE",
+            &legacy,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(!needs_reseal(&exported));
+        assert_eq!(
+            unseal_versioned(Keypolicy::MRSIGNER, b"MRENCLAVE", &exported).unwrap(),
+            Some(b"Mr. Enclave".to_vec())
+        );
+        let imported = reseal(
+            Keypolicy::MRSIGNER,
+            Keypolicy::MRENCLAVE,
+            b"MRENCLAVE",
+            &exported,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            unseal_versioned(Keypolicy::MRENCLAVE, b"MRENCLAVE", &imported).unwrap(),
+            Some(b"Mr. Enclave".to_vec())
+        );
+    }
+
     #[test]
     #[should_panic]
     fn test_incorrect_context() {
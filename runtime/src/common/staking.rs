@@ -0,0 +1,141 @@
+//! Staking structures.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/staking/api.
+//!
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    crypto::{hash::Hash, signature::PublicKey},
+    quantity::Quantity,
+    roothash::Namespace,
+};
+
+/// Context of v0 staking account addresses.
+const ADDRESS_V0_CONTEXT: &[u8] = b"oasis-core/address: staking";
+/// Context of v0 runtime account addresses.
+const ADDRESS_RUNTIME_V0_CONTEXT: &[u8] = b"oasis-core/address: runtime";
+/// Version of staking account addresses.
+const ADDRESS_V0_VERSION: u8 = 0;
+
+impl_bytes!(Address, 21, "A staking account address.");
+
+impl Address {
+    /// Address of the staking account controlled by the given public key.
+    pub fn from_pk(pk: &PublicKey) -> Self {
+        Self::from_context_data(ADDRESS_V0_CONTEXT, pk.as_ref())
+    }
+
+    /// Address of the staking account of the runtime with the given ID.
+    ///
+    /// The runtime account is controlled by the runtime through the staking
+    /// messages it emits.
+    pub fn from_runtime_id(id: &Namespace) -> Self {
+        Self::from_context_data(ADDRESS_RUNTIME_V0_CONTEXT, id.as_ref())
+    }
+
+    fn from_context_data(context: &[u8], data: &[u8]) -> Self {
+        let hash = Hash::digest_bytes_list(&[context, &[ADDRESS_V0_VERSION], data]);
+        let mut address = [0u8; 21];
+        address[0] = ADDRESS_V0_VERSION;
+        address[1..].copy_from_slice(&hash.as_ref()[..20]);
+        Address(address)
+    }
+}
+
+/// A transfer of tokens from the sending account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Transfer {
+    /// Destination account.
+    pub to: Address,
+    /// Amount of tokens to transfer.
+    pub amount: Quantity,
+}
+
+/// A withdrawal of tokens to the sending account, using an allowance given
+/// to it by the source account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Withdraw {
+    /// Source account.
+    pub from: Address,
+    /// Amount of tokens to withdraw.
+    pub amount: Quantity,
+}
+
+/// A share pool of an escrow account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SharePool {
+    /// Total amount of tokens in the pool.
+    #[serde(default)]
+    pub balance: Quantity,
+    /// Total number of shares in the pool.
+    #[serde(default)]
+    pub total_shares: Quantity,
+}
+
+/// A general (non-escrow) account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GeneralAccount {
+    /// Balance of the account.
+    #[serde(default)]
+    pub balance: Quantity,
+    /// Nonce of the account.
+    #[serde(default)]
+    pub nonce: u64,
+    /// Amounts which other accounts may withdraw from the account.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub allowances: BTreeMap<Address, Quantity>,
+}
+
+/// An escrow account.
+///
+/// The commission schedule and stake accumulator are not decoded.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EscrowAccount {
+    /// Active escrowed tokens.
+    #[serde(default)]
+    pub active: SharePool,
+    /// Debonding escrowed tokens.
+    #[serde(default)]
+    pub debonding: SharePool,
+}
+
+/// A staking account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Account {
+    /// General account.
+    #[serde(default)]
+    pub general: GeneralAccount,
+    /// Escrow account.
+    #[serde(default)]
+    pub escrow: EscrowAccount,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_address_from_pk() {
+        let pk =
+            PublicKey::from("badadd1e55ffffffffffffffffffffffffffffffffffffffffffffffffffffff");
+        assert_eq!(
+            Address::from_pk(&pk),
+            Address::from("00c8006304c3316c1835a4569ff7fc933e9f1cc4de")
+        );
+    }
+
+    #[test]
+    fn test_address_from_runtime_id() {
+        // NOTE: This address MUST be synced with go/staking/api/address_test.go.
+        let id =
+            Namespace::from("8000000000000000000000000000000000000000000000000000000000000000");
+        assert_eq!(
+            Address::from_runtime_id(&id),
+            Address::from("001a1b0eef616533bec4de188b53cc89cd1381cd33")
+        );
+    }
+}
@@ -1,9 +1,12 @@
 /// Protocol and runtime versioning.
+use std::fmt;
 
 // NOTE: This should be kept in sync with go/common/version/version.go.
 
 /// A protocol or runtime version.
-#[derive(Clone, Copy, Debug)]
+///
+/// Versions are ordered by their major, minor and patch segments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Version {
     major: u16,
     minor: u16,
@@ -23,13 +26,34 @@ macro_rules! version_from_cargo {
 
 impl Version {
     /// Creates a new version with given major, minor, and patch segments.
-    pub fn new(major: u16, minor: u16, patch: u16) -> Version {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Version {
         Version {
             major: major,
             minor: minor,
             patch: patch,
         }
     }
+
+    /// Return the major segment of the version.
+    pub fn major(&self) -> u16 {
+        self.major
+    }
+
+    /// Return the minor segment of the version.
+    pub fn minor(&self) -> u16 {
+        self.minor
+    }
+
+    /// Return the patch segment of the version.
+    pub fn patch(&self) -> u16 {
+        self.patch
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
 }
 
 // Returns the version as a platform-dependent u64.
@@ -55,6 +79,6 @@ impl From<u64> for Version {
 // the worker host.
 pub const PROTOCOL_VERSION: Version = Version {
     major: 1,
-    minor: 0,
+    minor: 17,
     patch: 0,
 };
@@ -0,0 +1,181 @@
+//! Minimal DER and X.509 certificate parsing, sufficient for verifying the
+//! certificate chains of remote attestations.
+use std::{collections::BTreeMap, str};
+
+use anyhow::Result;
+use byteorder::{BigEndian, ByteOrder};
+use chrono::NaiveDateTime;
+use thiserror::Error;
+
+pub(crate) const DER_TAG_BOOLEAN: u8 = 0x01;
+pub(crate) const DER_TAG_INTEGER: u8 = 0x02;
+pub(crate) const DER_TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const DER_TAG_OCTET_STRING: u8 = 0x04;
+pub(crate) const DER_TAG_OID: u8 = 0x06;
+pub(crate) const DER_TAG_UTC_TIME: u8 = 0x17;
+pub(crate) const DER_TAG_GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const DER_TAG_SEQUENCE: u8 = 0x30;
+pub(crate) const DER_TAG_VERSION: u8 = 0xa0;
+pub(crate) const DER_TAG_ISSUER_UID: u8 = 0x81;
+pub(crate) const DER_TAG_SUBJECT_UID: u8 = 0x82;
+pub(crate) const DER_TAG_EXTENSIONS: u8 = 0xa3;
+
+/// Certificate parsing error.
+#[derive(Error, Debug)]
+enum X509Error {
+    #[error("malformed certificate")]
+    MalformedCertificate,
+}
+
+/// Minimal DER reader.
+pub(crate) struct DerReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> DerReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.data.first().cloned()
+    }
+
+    /// Read the next element with the given tag and return its contents.
+    pub(crate) fn read(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let (header_len, len) = match self.data {
+            [t, len, ..] if *t == tag && *len < 0x80 => (2, *len as usize),
+            [t, 0x81, len, ..] if *t == tag => (3, *len as usize),
+            [t, 0x82, len0, len1, ..] if *t == tag => {
+                (4, BigEndian::read_u16(&[*len0, *len1]) as usize)
+            }
+            _ => return Err(X509Error::MalformedCertificate.into()),
+        };
+        if self.data.len() < header_len + len {
+            return Err(X509Error::MalformedCertificate.into());
+        }
+        let contents = &self.data[header_len..header_len + len];
+        self.data = &self.data[header_len + len..];
+        Ok(contents)
+    }
+
+    /// Read the next element with the given tag and return its encoding.
+    pub(crate) fn read_raw(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let data = self.data;
+        self.read(tag)?;
+        Ok(&data[..data.len() - self.data.len()])
+    }
+
+    /// Read the next element if it has the given tag.
+    pub(crate) fn read_optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>> {
+        match self.peek() {
+            Some(t) if t == tag => Ok(Some(self.read(tag)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Read an unsigned integer.
+    pub(crate) fn read_u16(&mut self) -> Result<u16> {
+        match self.read(DER_TAG_INTEGER)? {
+            [value] if *value < 0x80 => Ok(*value as u16),
+            [0, value] if *value >= 0x80 => Ok(*value as u16),
+            [value0, value1] if *value0 > 0 && *value0 < 0x80 => {
+                Ok(BigEndian::read_u16(&[*value0, *value1]))
+            }
+            _ => Err(X509Error::MalformedCertificate.into()),
+        }
+    }
+
+    /// Read a UTC or generalized time, as a POSIX timestamp.
+    pub(crate) fn read_time(&mut self) -> Result<i64> {
+        let (time, format) = match self.peek() {
+            Some(DER_TAG_UTC_TIME) => (self.read(DER_TAG_UTC_TIME)?, "%y%m%d%H%M%SZ"),
+            Some(DER_TAG_GENERALIZED_TIME) => {
+                (self.read(DER_TAG_GENERALIZED_TIME)?, "%Y%m%d%H%M%SZ")
+            }
+            _ => return Err(X509Error::MalformedCertificate.into()),
+        };
+        let time = str::from_utf8(time).map_err(|_| X509Error::MalformedCertificate)?;
+        let time = NaiveDateTime::parse_from_str(time, format)
+            .map_err(|_| X509Error::MalformedCertificate)?;
+        Ok(time.timestamp())
+    }
+}
+
+/// Parsed certificate contents.
+///
+/// Parsing does not verify anything, the certificate must either be verified
+/// beforehand or by the caller (using the signed TBS certificate).
+pub(crate) struct Certificate<'a> {
+    /// Encoded TBS certificate, covered by the signature.
+    pub tbs: &'a [u8],
+    /// OID of the signature algorithm.
+    pub signature_algorithm: &'a [u8],
+    /// Signature of the TBS certificate by the issuer.
+    pub signature: &'a [u8],
+    /// Start of the validity period (POSIX timestamp).
+    pub not_before: i64,
+    /// End of the validity period (POSIX timestamp).
+    pub not_after: i64,
+    /// Encoded public key (uncompressed for EC keys, PKCS#1 for RSA keys).
+    pub public_key: &'a [u8],
+    /// Extension values, by OID.
+    pub extensions: BTreeMap<&'a [u8], &'a [u8]>,
+}
+
+impl<'a> Certificate<'a> {
+    pub(crate) fn parse(der: &'a [u8]) -> Result<Self> {
+        let mut certificate = DerReader::new(DerReader::new(der).read(DER_TAG_SEQUENCE)?);
+        let tbs = certificate.read_raw(DER_TAG_SEQUENCE)?;
+        let signature_algorithm =
+            DerReader::new(certificate.read(DER_TAG_SEQUENCE)?).read(DER_TAG_OID)?;
+        let signature = match certificate.read(DER_TAG_BIT_STRING)? {
+            [0, signature @ ..] => signature,
+            _ => return Err(X509Error::MalformedCertificate.into()),
+        };
+
+        let mut contents = DerReader::new(DerReader::new(tbs).read(DER_TAG_SEQUENCE)?);
+        contents.read_optional(DER_TAG_VERSION)?;
+        contents.read(DER_TAG_INTEGER)?; // Serial number.
+        contents.read(DER_TAG_SEQUENCE)?; // Signature algorithm.
+        contents.read(DER_TAG_SEQUENCE)?; // Issuer.
+        let mut validity = DerReader::new(contents.read(DER_TAG_SEQUENCE)?);
+        let not_before = validity.read_time()?;
+        let not_after = validity.read_time()?;
+        contents.read(DER_TAG_SEQUENCE)?; // Subject.
+
+        let mut spki = DerReader::new(contents.read(DER_TAG_SEQUENCE)?);
+        spki.read(DER_TAG_SEQUENCE)?; // Algorithm.
+        let public_key = match spki.read(DER_TAG_BIT_STRING)? {
+            [0, public_key @ ..] => public_key,
+            _ => return Err(X509Error::MalformedCertificate.into()),
+        };
+
+        contents.read_optional(DER_TAG_ISSUER_UID)?;
+        contents.read_optional(DER_TAG_SUBJECT_UID)?;
+        let mut extensions = BTreeMap::new();
+        if let Some(data) = contents.read_optional(DER_TAG_EXTENSIONS)? {
+            let mut data = DerReader::new(DerReader::new(data).read(DER_TAG_SEQUENCE)?);
+            while !data.is_empty() {
+                let mut extension = DerReader::new(data.read(DER_TAG_SEQUENCE)?);
+                let oid = extension.read(DER_TAG_OID)?;
+                extension.read_optional(DER_TAG_BOOLEAN)?; // Critical.
+                extensions.insert(oid, extension.read(DER_TAG_OCTET_STRING)?);
+            }
+        }
+
+        Ok(Self {
+            tbs,
+            signature_algorithm,
+            signature,
+            not_before,
+            not_after,
+            public_key,
+            extensions,
+        })
+    }
+}
@@ -0,0 +1,106 @@
+//! Consensus epoch tracking.
+//!
+//! The host notifies the runtime of every consensus epoch transition, so that
+//! components which must act on epoch transitions (e.g., the key manager) can
+//! wait for them instead of polling the consensus state.
+use std::sync::{Condvar, Mutex};
+
+/// A consensus epoch number.
+pub type EpochTime = u64;
+
+/// A consensus epoch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EpochInfo {
+    /// Epoch number.
+    pub epoch: EpochTime,
+    /// Consensus block height at the start of the epoch.
+    pub height: i64,
+    /// Consensus entropy beacon at the start of the epoch.
+    pub beacon: Vec<u8>,
+}
+
+/// Tracker of the current consensus epoch, as notified by the host.
+#[derive(Debug, Default)]
+pub struct EpochTracker {
+    current: Mutex<Option<EpochInfo>>,
+    updated: Condvar,
+}
+
+impl EpochTracker {
+    /// Create a new epoch tracker which does not know the current epoch yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the current epoch, if the host has notified the runtime of any.
+    pub fn current(&self) -> Option<EpochInfo> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Update the current epoch.
+    ///
+    /// Updates to epochs before the current epoch are ignored. Returns whether
+    /// the current epoch has been updated.
+    pub(crate) fn update(&self, info: EpochInfo) -> bool {
+        let mut current = self.current.lock().unwrap();
+        if current
+            .as_ref()
+            .map_or(false, |current| current.epoch > info.epoch)
+        {
+            return false;
+        }
+        *current = Some(info);
+        self.updated.notify_all();
+        true
+    }
+
+    /// Block the calling thread until the current epoch is at least the given
+    /// epoch and return the current epoch.
+    pub fn wait_epoch(&self, epoch: EpochTime) -> EpochInfo {
+        let mut current = self.current.lock().unwrap();
+        loop {
+            match *current {
+                Some(ref info) if info.epoch >= epoch => return info.clone(),
+                _ => current = self.updated.wait(current).unwrap(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::*;
+
+    fn epoch_info(epoch: EpochTime) -> EpochInfo {
+        EpochInfo {
+            epoch,
+            height: 100 * epoch as i64,
+            beacon: vec![epoch as u8; 32],
+        }
+    }
+
+    #[test]
+    fn test_epoch_tracker() {
+        let tracker = Arc::new(EpochTracker::new());
+        assert_eq!(tracker.current(), None);
+
+        assert!(tracker.update(epoch_info(2)));
+        assert_eq!(tracker.current(), Some(epoch_info(2)));
+        assert_eq!(tracker.wait_epoch(1), epoch_info(2));
+
+        // Earlier epochs are ignored.
+        assert!(!tracker.update(epoch_info(1)));
+        assert_eq!(tracker.current(), Some(epoch_info(2)));
+
+        // Waiting for a future epoch blocks until it is reached.
+        let waiter = {
+            let tracker = tracker.clone();
+            thread::spawn(move || tracker.wait_epoch(4))
+        };
+        assert!(tracker.update(epoch_info(3)));
+        assert!(tracker.update(epoch_info(5)));
+        assert_eq!(waiter.join().unwrap(), epoch_info(5));
+    }
+}
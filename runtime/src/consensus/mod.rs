@@ -0,0 +1,22 @@
+//! Consensus layer structures.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/consensus/api.
+//!
+use serde::{Deserialize, Serialize};
+
+pub mod epoch;
+pub mod state;
+pub mod tendermint;
+pub mod verifier;
+
+/// A light consensus block suitable for syncing light clients.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightBlock {
+    /// Block height.
+    pub height: i64,
+    /// Consensus backend specific light block.
+    #[serde(with = "serde_bytes")]
+    pub meta: Vec<u8>,
+}
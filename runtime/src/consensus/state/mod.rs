@@ -0,0 +1,257 @@
+//! Verified consensus state.
+//!
+//! The consensus state is a Merklized key-value store whose root is committed
+//! by the consensus block headers. The state is read through the host, and
+//! every proof returned by the host is verified against the state root of a
+//! header verified by the light client verifier, so the decoded state can be
+//! relied upon without trusting the host.
+use std::sync::Arc;
+
+use anyhow::Result;
+use io_context::Context;
+use serde::de::DeserializeOwned;
+
+use super::{tendermint::Header, verifier::HostVerifier};
+use crate::{
+    common::{cbor, roothash::Namespace},
+    protocol::Protocol,
+    storage::mkvs::{
+        sync::{HostReadSyncer, ReadSync},
+        ReadOnlyTree, Root, Tree,
+    },
+    types::HostStorageEndpoint,
+};
+
+pub mod registry;
+pub mod roothash;
+pub mod staking;
+
+/// Consensus state at a given height.
+pub struct ConsensusState {
+    height: i64,
+    mkvs: ReadOnlyTree,
+}
+
+impl ConsensusState {
+    /// Consensus state committed by the given header, fetched with the given
+    /// read syncer.
+    ///
+    /// The header must have been verified, as proofs are only verified against
+    /// its state root.
+    pub fn new(header: &Header, read_syncer: Box<dyn ReadSync>) -> Result<Self> {
+        // The root must match the one the host stores the consensus state
+        // under. The consensus state lives in the zero namespace, and the
+        // header at a given height commits the state after executing the
+        // previous block, stored under the previous height as its version
+        // (see `doCommit` in go/consensus/tendermint/abci/state.go and
+        // `NewBlock` in go/consensus/tendermint/api/api.go). The root type is
+        // omitted as the consensus state roots are not typed.
+        let root = Root {
+            namespace: Namespace::default(),
+            version: (header.height - 1) as u64,
+            hash: header.state_root()?,
+            ..Default::default()
+        };
+
+        Ok(Self {
+            height: header.height,
+            mkvs: Tree::open_readonly(read_syncer, root),
+        })
+    }
+
+    /// Consensus state committed by the header at the given height (or the
+    /// latest header with `HEIGHT_LATEST`), verified and fetched from the
+    /// host.
+    ///
+    /// Fails with a `StaleState` error if the state is older than allowed by
+    /// the verifier's staleness policy.
+    pub fn from_verifier(
+        ctx: Context,
+        verifier: &HostVerifier,
+        height: i64,
+        protocol: Arc<Protocol>,
+    ) -> Result<Self> {
+        let header = verifier.sync_fresh(ctx, &protocol, height)?;
+        let read_syncer =
+            HostReadSyncer::new_with_endpoint(protocol, HostStorageEndpoint::Consensus);
+
+        Self::new(&header, Box::new(read_syncer))
+    }
+
+    /// Height of the header committing the state.
+    pub fn height(&self) -> i64 {
+        self.height
+    }
+
+    /// Fetch and decode the value of the given key, if any.
+    fn get<T: DeserializeOwned>(&self, ctx: Context, key: &[u8]) -> Result<Option<T>> {
+        match self.mkvs.get(ctx, key)? {
+            Some(value) => Ok(Some(cbor::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use std::{
+        any::Any,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{
+        common::crypto::hash::Hash,
+        consensus::{
+            tendermint::BlockId,
+            verifier::tests::{keys, light_block, verifier},
+            LightBlock,
+        },
+        storage::mkvs::{
+            sync::{
+                GetPrefixesRequest, GetRequest, IterateRequest, MapReadSync, NoopReadSyncer,
+                ProofResponse,
+            },
+            RootType,
+        },
+    };
+
+    /// A read syncer recording the roots of the requests.
+    struct RecordingReadSync {
+        rs: MapReadSync,
+        roots: Arc<Mutex<Vec<Root>>>,
+    }
+
+    impl ReadSync for RecordingReadSync {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+            self.roots.lock().unwrap().push(request.tree.root);
+            self.rs.sync_get(ctx, request)
+        }
+
+        fn sync_get_prefixes(
+            &mut self,
+            ctx: Context,
+            request: GetPrefixesRequest,
+        ) -> Result<ProofResponse> {
+            self.roots.lock().unwrap().push(request.tree.root);
+            self.rs.sync_get_prefixes(ctx, request)
+        }
+
+        fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+            self.roots.lock().unwrap().push(request.tree.root);
+            self.rs.sync_iterate(ctx, request)
+        }
+    }
+
+    /// Commit a tree with the given items in the given version.
+    fn commit(items: &[(Vec<u8>, Vec<u8>)], version: u64) -> (Tree, Hash) {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for (key, value) in items {
+            tree.insert(Context::background(), key, value)
+                .expect("insert");
+        }
+        let (_, hash) = Tree::commit(
+            &mut tree,
+            Context::background(),
+            Default::default(),
+            version,
+        )
+        .expect("commit");
+        (tree, hash)
+    }
+
+    /// Consensus state with the given items, as committed by a header with
+    /// the given state root (the root of the items if not given).
+    pub fn state_with_root(items: &[(Vec<u8>, Vec<u8>)], root: Option<Hash>) -> ConsensusState {
+        let (tree, hash) = commit(items, 41);
+        let read_syncer = MapReadSync::from_tree(Context::background(), &tree).expect("export");
+        let header = Header {
+            height: 42,
+            app_hash: root.unwrap_or(hash).as_ref().to_vec(),
+            ..Default::default()
+        };
+        ConsensusState::new(&header, Box::new(read_syncer)).unwrap()
+    }
+
+    /// Consensus state with the given items.
+    pub fn state(items: &[(Vec<u8>, Vec<u8>)]) -> ConsensusState {
+        state_with_root(items, None)
+    }
+
+    #[test]
+    fn test_get() {
+        let items = vec![(b"key".to_vec(), cbor::to_vec(&42u64))];
+        let state = state(&items);
+        assert_eq!(state.height(), 42);
+        let value: Option<u64> = state.get(Context::background(), b"key").unwrap();
+        assert_eq!(value, Some(42));
+        let value: Option<u64> = state.get(Context::background(), b"missing").unwrap();
+        assert_eq!(value, None);
+
+        // State which is not committed by the header is rejected.
+        let (_, other_root) = commit(&[(b"key".to_vec(), cbor::to_vec(&43u64))], 41);
+        let state = state_with_root(&items, Some(other_root));
+        assert!(state.get::<u64>(Context::background(), b"key").is_err());
+    }
+
+    #[test]
+    fn test_go_state_root() {
+        // Test vector from the Go side (go/storage/mkvs/tree_test.go,
+        // testCommitNoPersist), where both items end up committed in the
+        // state root of version 42.
+        let items = vec![
+            (b"this key".to_vec(), b"should not be persisted".to_vec()),
+            (b"but now".to_vec(), b"we will persist everything".to_vec()),
+        ];
+        let (tree, hash) = commit(&items, 42);
+        assert_eq!(
+            format!("{:?}", hash),
+            "b27dda2f4f86f9dfa6168696a35ad03c3fddfe721d80d102e5b3c82abfa6a638"
+        );
+
+        // The state root is committed by a verified light block.
+        let validators = keys("validator", 4);
+        let block = light_block(43, hash, &validators, &validators, BlockId::default());
+        let block = LightBlock {
+            height: 43,
+            meta: block.encode(),
+        };
+        let verifier = verifier(&block);
+        let header = verifier.verified_header(43).unwrap();
+
+        let roots = Arc::new(Mutex::new(Vec::new()));
+        let read_syncer = RecordingReadSync {
+            rs: MapReadSync::from_tree(Context::background(), &tree).expect("export"),
+            roots: roots.clone(),
+        };
+        let state = ConsensusState::new(header, Box::new(read_syncer)).unwrap();
+        for (key, value) in &items {
+            let fetched = state.mkvs.get(Context::background(), key).unwrap();
+            assert_eq!(fetched.as_ref(), Some(value));
+        }
+
+        // The state is fetched from the root the Go side stores it under.
+        let roots = roots.lock().unwrap();
+        assert!(!roots.is_empty());
+        for root in roots.iter() {
+            assert_eq!(root.namespace, Namespace::default());
+            assert_eq!(root.version, 42);
+            assert_eq!(root.root_type, RootType::Invalid);
+            assert_eq!(root.hash, hash);
+        }
+
+        // An empty application state hash commits the empty state.
+        let block = light_block(42, hash, &validators, &validators, BlockId::default());
+        let mut header = block.signed_header.header;
+        header.app_hash = vec![];
+        let state = ConsensusState::new(&header, Box::new(NoopReadSyncer)).unwrap();
+        assert_eq!(
+            state.mkvs.get(Context::background(), b"but now").unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Registry consensus state.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/consensus/tendermint/apps/registry/state.
+//!
+use anyhow::Result;
+use io_context::Context;
+use serde::{Deserialize, Serialize};
+
+use super::ConsensusState;
+use crate::{
+    common::{
+        cbor,
+        crypto::{hash::Hash, signature::SignatureBundle},
+        registry::Runtime,
+        roothash::Namespace,
+    },
+    storage::mkvs::keyformat::KeyFormat,
+};
+
+crate::key_format!(
+    /// Signed runtime descriptors, keyed by the hash of the runtime ID.
+    struct SignedRuntimeKeyFormat(0x13) {
+        id_hash: Hash,
+    }
+);
+
+/// A signed runtime descriptor.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct SignedRuntime {
+    /// CBOR-encoded runtime descriptor.
+    #[serde(with = "serde_bytes")]
+    untrusted_raw_value: Vec<u8>,
+    /// Signature of the entity registering the runtime.
+    signature: SignatureBundle,
+}
+
+impl ConsensusState {
+    /// Descriptor of the (non-suspended) runtime with the given ID, if any.
+    ///
+    /// The entity signature is not verified again, as the descriptor has been
+    /// accepted by the consensus layer.
+    pub fn runtime(&self, ctx: Context, id: &Namespace) -> Result<Option<Runtime>> {
+        let key = SignedRuntimeKeyFormat {
+            id_hash: Hash::digest_bytes(id.as_ref()),
+        }
+        .encode();
+        match self.get::<SignedRuntime>(ctx, &key)? {
+            Some(signed) => Ok(Some(cbor::from_slice(&signed.untrusted_raw_value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::test::state, *};
+    use crate::common::registry::{RuntimeKind, TEEHardware};
+
+    #[test]
+    fn test_runtime() {
+        let id = Namespace::from(Hash::digest_bytes(b"runtime").as_ref());
+        let runtime = Runtime {
+            v: 1,
+            id,
+            kind: RuntimeKind::Compute,
+            tee_hardware: TEEHardware::IntelSGX,
+            ..Default::default()
+        };
+        let signed = SignedRuntime {
+            untrusted_raw_value: cbor::to_vec(&runtime),
+            ..Default::default()
+        };
+        let state = state(&[(
+            SignedRuntimeKeyFormat {
+                id_hash: Hash::digest_bytes(id.as_ref()),
+            }
+            .encode(),
+            cbor::to_vec(&signed),
+        )]);
+
+        assert_eq!(
+            state.runtime(Context::background(), &id).unwrap(),
+            Some(runtime)
+        );
+        assert_eq!(
+            state
+                .runtime(Context::background(), &Namespace::default())
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Roothash consensus state.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/consensus/tendermint/apps/roothash/state.
+//!
+use anyhow::Result;
+use io_context::Context;
+use serde::{Deserialize, Serialize};
+
+use super::ConsensusState;
+use crate::{
+    common::{
+        crypto::hash::Hash,
+        registry::Runtime,
+        roothash::{AnnotatedBlock, Block, Namespace},
+    },
+    storage::mkvs::keyformat::KeyFormat,
+};
+
+crate::key_format!(
+    /// Per-runtime roothash state, keyed by the hash of the runtime ID.
+    struct RuntimeKeyFormat(0x20) {
+        id_hash: Hash,
+    }
+);
+
+/// Per-runtime roothash state.
+///
+/// The executor commitment pool is not decoded.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeState {
+    /// Runtime descriptor.
+    pub runtime: Runtime,
+    /// Whether the runtime is suspended.
+    #[serde(default)]
+    pub suspended: bool,
+    /// Genesis block of the runtime.
+    pub genesis_block: Block,
+    /// Latest block of the runtime.
+    pub current_block: Block,
+    /// Consensus height at which the latest block was produced.
+    pub current_block_height: i64,
+}
+
+impl ConsensusState {
+    /// Roothash state of the runtime with the given ID, if any.
+    pub fn runtime_state(&self, ctx: Context, id: &Namespace) -> Result<Option<RuntimeState>> {
+        let key = RuntimeKeyFormat {
+            id_hash: Hash::digest_bytes(id.as_ref()),
+        }
+        .encode();
+        self.get(ctx, &key)
+    }
+
+    /// Latest block of the runtime with the given ID, if any.
+    pub fn latest_block(&self, ctx: Context, id: &Namespace) -> Result<Option<AnnotatedBlock>> {
+        Ok(self.runtime_state(ctx, id)?.map(|state| AnnotatedBlock {
+            consensus_height: state.current_block_height,
+            block: state.current_block,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::test::state, *};
+    use crate::common::{cbor, roothash::Header};
+
+    #[test]
+    fn test_latest_block() {
+        let id = Namespace::from(Hash::digest_bytes(b"runtime").as_ref());
+        let block = Block {
+            header: Header {
+                namespace: id,
+                round: 10,
+                ..Default::default()
+            },
+        };
+        let runtime_state = RuntimeState {
+            current_block: block.clone(),
+            current_block_height: 40,
+            ..Default::default()
+        };
+        let state = state(&[(
+            RuntimeKeyFormat {
+                id_hash: Hash::digest_bytes(id.as_ref()),
+            }
+            .encode(),
+            cbor::to_vec(&runtime_state),
+        )]);
+
+        assert_eq!(
+            state.runtime_state(Context::background(), &id).unwrap(),
+            Some(runtime_state)
+        );
+        assert_eq!(
+            state.latest_block(Context::background(), &id).unwrap(),
+            Some(AnnotatedBlock {
+                consensus_height: 40,
+                block,
+            })
+        );
+        assert_eq!(
+            state
+                .latest_block(Context::background(), &Namespace::default())
+                .unwrap(),
+            None
+        );
+    }
+}
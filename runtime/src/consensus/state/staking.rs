@@ -0,0 +1,114 @@
+//! Staking consensus state.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/consensus/tendermint/apps/staking/state.
+//!
+use anyhow::Result;
+use io_context::Context;
+
+use super::ConsensusState;
+use crate::{
+    common::{
+        quantity::Quantity,
+        staking::{Account, Address},
+    },
+    storage::mkvs::keyformat::{KeyFormat, KeyFormatAtom},
+};
+
+impl KeyFormatAtom for Address {
+    fn size() -> usize {
+        Address::len()
+    }
+
+    fn encode_atom(self) -> Vec<u8> {
+        self.as_ref().to_vec()
+    }
+
+    fn decode_atom(data: &[u8]) -> Self {
+        data.into()
+    }
+}
+
+crate::key_format!(
+    /// Accounts, keyed by address.
+    struct AccountKeyFormat(0x50) {
+        address: Address,
+    }
+);
+
+/// Key of the total token supply.
+const TOTAL_SUPPLY_KEY: &[u8] = &[0x51];
+/// Key of the common pool balance.
+const COMMON_POOL_KEY: &[u8] = &[0x52];
+
+impl ConsensusState {
+    /// Staking account with the given address.
+    ///
+    /// Accounts which do not exist are returned as empty accounts.
+    pub fn account(&self, ctx: Context, address: &Address) -> Result<Account> {
+        let key = AccountKeyFormat { address: *address }.encode();
+        Ok(self.get(ctx, &key)?.unwrap_or_default())
+    }
+
+    /// Total token supply.
+    pub fn total_supply(&self, ctx: Context) -> Result<Quantity> {
+        Ok(self.get(ctx, TOTAL_SUPPLY_KEY)?.unwrap_or_default())
+    }
+
+    /// Balance of the common pool.
+    pub fn common_pool(&self, ctx: Context) -> Result<Quantity> {
+        Ok(self.get(ctx, COMMON_POOL_KEY)?.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::test::state, *};
+    use crate::common::{cbor, crypto::signature::PublicKey, staking::GeneralAccount};
+
+    #[test]
+    fn test_account() {
+        let address = Address::from_pk(&PublicKey::from(
+            "badadd1e55ffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        ));
+        let other = Address::from_pk(&PublicKey::from(
+            "badbadadd1e55fffffffffffffffffffffffffffffffffffffffffffffffffff",
+        ));
+        let account = Account {
+            general: GeneralAccount {
+                balance: Quantity::from(1000u64),
+                nonce: 7,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let state = state(&[
+            (
+                AccountKeyFormat { address }.encode(),
+                cbor::to_vec(&account),
+            ),
+            (
+                TOTAL_SUPPLY_KEY.to_vec(),
+                cbor::to_vec(&Quantity::from(10_000u64)),
+            ),
+        ]);
+
+        assert_eq!(
+            state.account(Context::background(), &address).unwrap(),
+            account
+        );
+        assert_eq!(
+            state.account(Context::background(), &other).unwrap(),
+            Account::default()
+        );
+        assert_eq!(
+            state.total_supply(Context::background()).unwrap(),
+            Quantity::from(10_000u64)
+        );
+        assert_eq!(
+            state.common_pool(Context::background()).unwrap(),
+            Quantity::default()
+        );
+    }
+}
@@ -0,0 +1,965 @@
+//! Tendermint light blocks.
+//!
+//! With the Tendermint consensus backend, the metadata of a light block is a
+//! protobuf-encoded Tendermint light block, i.e. a signed header together
+//! with the validator set which signed it. This module decodes such light
+//! blocks and computes the header and validator set hashes and the vote sign
+//! bytes needed to verify them, the same way as Tendermint 0.34 does.
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::common::{
+    crypto::{hash::Hash, signature::PublicKey},
+    protobuf::{self, Encoder, Value},
+};
+
+/// Size of Tendermint hashes (SHA-256).
+pub const HASH_SIZE: usize = 32;
+/// Size of Tendermint validator addresses.
+pub const ADDRESS_SIZE: usize = 20;
+/// Maximum length of a chain identifier.
+const MAX_CHAIN_ID_LENGTH: usize = 50;
+/// Maximum size of a commit signature.
+const MAX_SIGNATURE_SIZE: usize = 64;
+/// Signed message type of precommit votes.
+const PRECOMMIT_TYPE: u64 = 2;
+
+/// Tendermint decoding error.
+#[derive(Error, Debug)]
+pub enum TendermintError {
+    #[error("tendermint: malformed {0}")]
+    Malformed(&'static str),
+    #[error("tendermint: unsupported public key type")]
+    UnsupportedPublicKey,
+}
+
+/// SHA-256 digest of the given data.
+fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(data).to_vec()
+}
+
+/// Merkle root of the given items, as computed by Tendermint (RFC 6962).
+pub fn merkle_root<T: AsRef<[u8]>>(items: &[T]) -> Vec<u8> {
+    match items.len() {
+        0 => sha256(&[]),
+        1 => sha256(&[&[0x00], items[0].as_ref()].concat()),
+        n => {
+            // Split at the largest power of two smaller than the number of items.
+            let split = n.next_power_of_two() / 2;
+            let left = merkle_root(&items[..split]);
+            let right = merkle_root(&items[split..]);
+            sha256(&[&[0x01], &left[..], &right[..]].concat())
+        }
+    }
+}
+
+/// Write a varint field unless it has the default value, as proto3 does.
+fn varint(encoder: Encoder, field: u32, value: u64) -> Encoder {
+    if value == 0 {
+        return encoder;
+    }
+    encoder.varint(field, value)
+}
+
+/// Write a length-delimited field unless it is empty, as proto3 does.
+fn bytes(encoder: Encoder, field: u32, value: &[u8]) -> Encoder {
+    if value.is_empty() {
+        return encoder;
+    }
+    encoder.bytes(field, value)
+}
+
+/// Check that the given hash is either empty or a valid hash.
+fn validate_hash(hash: &[u8], what: &'static str) -> Result<(), TendermintError> {
+    if !hash.is_empty() && hash.len() != HASH_SIZE {
+        return Err(TendermintError::Malformed(what));
+    }
+    Ok(())
+}
+
+/// A protobuf timestamp.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    /// Seconds since the UNIX epoch.
+    pub seconds: i64,
+    /// Non-negative fraction of a second, in nanoseconds.
+    pub nanos: i32,
+}
+
+impl Timestamp {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut ts = Self::default();
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => ts.seconds = value.as_varint(field)? as i64,
+                2 => ts.nanos = value.as_varint(field)? as i32,
+                _ => {}
+            }
+        }
+        if ts.nanos < 0 || ts.nanos >= 1_000_000_000 {
+            return Err(TendermintError::Malformed("timestamp").into());
+        }
+        Ok(ts)
+    }
+
+    fn encode(&self) -> Encoder {
+        let encoder = varint(Encoder::new(), 1, self.seconds as u64);
+        varint(encoder, 2, self.nanos as u64)
+    }
+}
+
+/// Header of the parts of a block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PartSetHeader {
+    /// Number of parts.
+    pub total: u32,
+    /// Merkle root of the parts.
+    pub hash: Vec<u8>,
+}
+
+impl PartSetHeader {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut header = Self::default();
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => header.total = value.as_varint(field)? as u32,
+                2 => header.hash = value.as_bytes(field)?.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(header)
+    }
+
+    fn encode(&self) -> Encoder {
+        let encoder = varint(Encoder::new(), 1, u64::from(self.total));
+        bytes(encoder, 2, &self.hash)
+    }
+}
+
+/// Identifier of a block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockId {
+    /// Hash of the block header.
+    pub hash: Vec<u8>,
+    /// Header of the parts of the block.
+    pub part_set_header: PartSetHeader,
+}
+
+impl BlockId {
+    /// Whether this is the identifier of no block (e.g., of the block before
+    /// the first block).
+    pub fn is_zero(&self) -> bool {
+        self.hash.is_empty() && self.part_set_header == PartSetHeader::default()
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut id = Self::default();
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => id.hash = value.as_bytes(field)?.to_vec(),
+                2 => id.part_set_header = PartSetHeader::decode(value.as_bytes(field)?)?,
+                _ => {}
+            }
+        }
+        Ok(id)
+    }
+
+    /// Encode the block identifier. This is also its canonical encoding in
+    /// votes, as both have the same fields.
+    fn encode(&self) -> Encoder {
+        bytes(Encoder::new(), 1, &self.hash).message(2, self.part_set_header.encode())
+    }
+
+    fn validate_basic(&self) -> Result<(), TendermintError> {
+        validate_hash(&self.hash, "block id hash")?;
+        validate_hash(&self.part_set_header.hash, "part set header hash")
+    }
+}
+
+/// Tendermint block header.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    /// Block protocol version.
+    pub version_block: u64,
+    /// Application protocol version.
+    pub version_app: u64,
+    /// Chain identifier.
+    pub chain_id: String,
+    /// Block height.
+    pub height: i64,
+    /// Block time.
+    pub time: Timestamp,
+    /// Identifier of the previous block.
+    pub last_block_id: BlockId,
+    /// Hash of the commit of the previous block.
+    pub last_commit_hash: Vec<u8>,
+    /// Hash of the transactions in the block.
+    pub data_hash: Vec<u8>,
+    /// Hash of the validator set committing this block.
+    pub validators_hash: Vec<u8>,
+    /// Hash of the validator set committing the next block.
+    pub next_validators_hash: Vec<u8>,
+    /// Hash of the consensus parameters.
+    pub consensus_hash: Vec<u8>,
+    /// Application state hash after executing the previous block.
+    pub app_hash: Vec<u8>,
+    /// Hash of the results of executing the transactions of the previous
+    /// block.
+    pub last_results_hash: Vec<u8>,
+    /// Hash of the evidence in the block.
+    pub evidence_hash: Vec<u8>,
+    /// Address of the block proposer.
+    pub proposer_address: Vec<u8>,
+}
+
+impl Header {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut header = Self::default();
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => {
+                    for (field, value) in protobuf::decode(value.as_bytes(field)?)? {
+                        match field {
+                            1 => header.version_block = value.as_varint(field)?,
+                            2 => header.version_app = value.as_varint(field)?,
+                            _ => {}
+                        }
+                    }
+                }
+                2 => {
+                    header.chain_id = String::from_utf8(value.as_bytes(field)?.to_vec())
+                        .map_err(|_| TendermintError::Malformed("chain id"))?
+                }
+                3 => header.height = value.as_varint(field)? as i64,
+                4 => header.time = Timestamp::decode(value.as_bytes(field)?)?,
+                5 => header.last_block_id = BlockId::decode(value.as_bytes(field)?)?,
+                6 => header.last_commit_hash = value.as_bytes(field)?.to_vec(),
+                7 => header.data_hash = value.as_bytes(field)?.to_vec(),
+                8 => header.validators_hash = value.as_bytes(field)?.to_vec(),
+                9 => header.next_validators_hash = value.as_bytes(field)?.to_vec(),
+                10 => header.consensus_hash = value.as_bytes(field)?.to_vec(),
+                11 => header.app_hash = value.as_bytes(field)?.to_vec(),
+                12 => header.last_results_hash = value.as_bytes(field)?.to_vec(),
+                13 => header.evidence_hash = value.as_bytes(field)?.to_vec(),
+                14 => header.proposer_address = value.as_bytes(field)?.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(header)
+    }
+
+    fn encode_version(&self) -> Encoder {
+        let encoder = varint(Encoder::new(), 1, self.version_block);
+        varint(encoder, 2, self.version_app)
+    }
+
+    fn encode(&self) -> Encoder {
+        let mut encoder = Encoder::new().message(1, self.encode_version());
+        encoder = bytes(encoder, 2, self.chain_id.as_bytes());
+        encoder = varint(encoder, 3, self.height as u64);
+        encoder = encoder
+            .message(4, self.time.encode())
+            .message(5, self.last_block_id.encode());
+        for (field, hash) in (6..).zip(self.hashes().iter()) {
+            encoder = bytes(encoder, field, hash);
+        }
+        encoder
+    }
+
+    /// Hashes and the proposer address, in field order.
+    fn hashes(&self) -> [&[u8]; 9] {
+        [
+            &self.last_commit_hash,
+            &self.data_hash,
+            &self.validators_hash,
+            &self.next_validators_hash,
+            &self.consensus_hash,
+            &self.app_hash,
+            &self.last_results_hash,
+            &self.evidence_hash,
+            &self.proposer_address,
+        ]
+    }
+
+    /// Hash of the header, which identifies the block.
+    ///
+    /// This is the Merkle root of the encoded header fields, where scalar
+    /// fields are encoded as protobuf wrapper values.
+    pub fn hash(&self) -> Vec<u8> {
+        let mut items = vec![
+            self.encode_version().into_vec(),
+            bytes(Encoder::new(), 1, self.chain_id.as_bytes()).into_vec(),
+            varint(Encoder::new(), 1, self.height as u64).into_vec(),
+            self.time.encode().into_vec(),
+            self.last_block_id.encode().into_vec(),
+        ];
+        items.extend(
+            self.hashes()
+                .iter()
+                .map(|hash| bytes(Encoder::new(), 1, hash).into_vec()),
+        );
+        merkle_root(&items)
+    }
+
+    /// Consensus state root committed by the header.
+    ///
+    /// The application state hash of the Oasis consensus application is the
+    /// root hash of the consensus state after executing the previous block.
+    /// An empty application state hash commits the empty state, as in
+    /// `NewBlock` of the Go consensus backend.
+    pub fn state_root(&self) -> Result<Hash> {
+        if self.app_hash.is_empty() {
+            return Ok(Hash::empty_hash());
+        }
+        if self.app_hash.len() != Hash::len() {
+            return Err(TendermintError::Malformed("application state hash").into());
+        }
+        Ok(Hash::from(&self.app_hash[..]))
+    }
+
+    fn validate_basic(&self) -> Result<(), TendermintError> {
+        if self.chain_id.len() > MAX_CHAIN_ID_LENGTH {
+            return Err(TendermintError::Malformed("chain id"));
+        }
+        if self.height <= 0 {
+            return Err(TendermintError::Malformed("header height"));
+        }
+        self.last_block_id.validate_basic()?;
+        validate_hash(&self.last_commit_hash, "last commit hash")?;
+        validate_hash(&self.data_hash, "data hash")?;
+        validate_hash(&self.evidence_hash, "evidence hash")?;
+        validate_hash(&self.last_results_hash, "last results hash")?;
+        validate_hash(&self.consensus_hash, "consensus hash")?;
+        if self.validators_hash.len() != HASH_SIZE {
+            return Err(TendermintError::Malformed("validators hash"));
+        }
+        if self.next_validators_hash.len() != HASH_SIZE {
+            return Err(TendermintError::Malformed("next validators hash"));
+        }
+        if self.proposer_address.len() != ADDRESS_SIZE {
+            return Err(TendermintError::Malformed("proposer address"));
+        }
+        Ok(())
+    }
+}
+
+/// Vote of a validator recorded in a commit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockIdFlag {
+    /// The validator did not vote.
+    Absent = 1,
+    /// The validator voted for the committed block.
+    Commit = 2,
+    /// The validator voted for no block.
+    Nil = 3,
+}
+
+impl Default for BlockIdFlag {
+    fn default() -> Self {
+        BlockIdFlag::Absent
+    }
+}
+
+/// Signature of a validator in a commit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommitSig {
+    /// Vote of the validator.
+    pub block_id_flag: BlockIdFlag,
+    /// Address of the validator.
+    pub validator_address: Vec<u8>,
+    /// Time of the vote.
+    pub timestamp: Timestamp,
+    /// Signature of the vote.
+    pub signature: Vec<u8>,
+}
+
+impl CommitSig {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut sig = Self::default();
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => {
+                    sig.block_id_flag = match value.as_varint(field)? {
+                        1 => BlockIdFlag::Absent,
+                        2 => BlockIdFlag::Commit,
+                        3 => BlockIdFlag::Nil,
+                        _ => return Err(TendermintError::Malformed("block id flag").into()),
+                    }
+                }
+                2 => sig.validator_address = value.as_bytes(field)?.to_vec(),
+                3 => sig.timestamp = Timestamp::decode(value.as_bytes(field)?)?,
+                4 => sig.signature = value.as_bytes(field)?.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(sig)
+    }
+
+    fn encode(&self) -> Encoder {
+        let mut encoder = Encoder::new().varint(1, self.block_id_flag as u64);
+        encoder = bytes(encoder, 2, &self.validator_address);
+        encoder = encoder.message(3, self.timestamp.encode());
+        bytes(encoder, 4, &self.signature)
+    }
+
+    fn validate_basic(&self) -> Result<(), TendermintError> {
+        match self.block_id_flag {
+            BlockIdFlag::Absent => {
+                if !self.validator_address.is_empty()
+                    || self.timestamp != Timestamp::default()
+                    || !self.signature.is_empty()
+                {
+                    return Err(TendermintError::Malformed("absent commit signature"));
+                }
+            }
+            BlockIdFlag::Commit | BlockIdFlag::Nil => {
+                if self.validator_address.len() != ADDRESS_SIZE
+                    || self.signature.is_empty()
+                    || self.signature.len() > MAX_SIGNATURE_SIZE
+                {
+                    return Err(TendermintError::Malformed("commit signature"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Commit of a block by the validators.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Commit {
+    /// Height of the committed block.
+    pub height: i64,
+    /// Consensus round in which the block was committed.
+    pub round: i32,
+    /// Identifier of the committed block.
+    pub block_id: BlockId,
+    /// Signatures of the validators, in validator set order.
+    pub signatures: Vec<CommitSig>,
+}
+
+impl Commit {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut commit = Self::default();
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => commit.height = value.as_varint(field)? as i64,
+                2 => commit.round = value.as_varint(field)? as i32,
+                3 => commit.block_id = BlockId::decode(value.as_bytes(field)?)?,
+                4 => commit
+                    .signatures
+                    .push(CommitSig::decode(value.as_bytes(field)?)?),
+                _ => {}
+            }
+        }
+        Ok(commit)
+    }
+
+    fn encode(&self) -> Encoder {
+        let mut encoder = varint(Encoder::new(), 1, self.height as u64);
+        encoder = varint(encoder, 2, self.round as i64 as u64);
+        encoder = encoder.message(3, self.block_id.encode());
+        for sig in &self.signatures {
+            encoder = encoder.message(4, sig.encode());
+        }
+        encoder
+    }
+
+    /// Bytes signed by the validator with the given index in the commit,
+    /// i.e. its canonical precommit vote.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no signature with the given index.
+    pub fn vote_sign_bytes(&self, chain_id: &str, index: usize) -> Vec<u8> {
+        let sig = &self.signatures[index];
+        let mut encoder = Encoder::new().varint(1, PRECOMMIT_TYPE);
+        if self.height != 0 {
+            encoder = encoder.sfixed64(2, self.height);
+        }
+        if self.round != 0 {
+            encoder = encoder.sfixed64(3, i64::from(self.round));
+        }
+        // Votes for no block do not include a block identifier.
+        if sig.block_id_flag == BlockIdFlag::Commit && !self.block_id.is_zero() {
+            encoder = encoder.message(4, self.block_id.encode());
+        }
+        encoder = encoder.message(5, sig.timestamp.encode());
+        bytes(encoder, 6, chain_id.as_bytes()).into_length_delimited()
+    }
+
+    fn validate_basic(&self) -> Result<(), TendermintError> {
+        if self.height < 0 || self.round < 0 {
+            return Err(TendermintError::Malformed("commit"));
+        }
+        if self.height >= 1 {
+            if self.block_id.is_zero() || self.signatures.is_empty() {
+                return Err(TendermintError::Malformed("commit"));
+            }
+            self.block_id.validate_basic()?;
+            for sig in &self.signatures {
+                sig.validate_basic()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A consensus validator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Validator {
+    /// Address of the validator.
+    pub address: Vec<u8>,
+    /// Consensus public key of the validator.
+    pub public_key: PublicKey,
+    /// Voting power of the validator.
+    pub voting_power: i64,
+    /// Priority of the validator in proposer selection.
+    pub proposer_priority: i64,
+}
+
+impl Validator {
+    /// Create a new validator with the given public key and voting power.
+    pub fn new(public_key: PublicKey, voting_power: i64) -> Self {
+        Self {
+            address: address(&public_key),
+            public_key,
+            voting_power,
+            proposer_priority: 0,
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut validator = Self::default();
+        let mut public_key = None;
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => validator.address = value.as_bytes(field)?.to_vec(),
+                2 => public_key = Some(decode_public_key(value.as_bytes(field)?)?),
+                3 => validator.voting_power = value.as_varint(field)? as i64,
+                4 => validator.proposer_priority = value.as_varint(field)? as i64,
+                _ => {}
+            }
+        }
+        validator.public_key = public_key.ok_or(TendermintError::UnsupportedPublicKey)?;
+        Ok(validator)
+    }
+
+    fn encode_public_key(&self) -> Encoder {
+        Encoder::new().bytes(1, self.public_key.as_ref())
+    }
+
+    fn encode(&self) -> Encoder {
+        let mut encoder = bytes(Encoder::new(), 1, &self.address);
+        encoder = encoder.message(2, self.encode_public_key());
+        encoder = varint(encoder, 3, self.voting_power as u64);
+        varint(encoder, 4, self.proposer_priority as u64)
+    }
+
+    fn validate_basic(&self) -> Result<(), TendermintError> {
+        if self.voting_power < 0 {
+            return Err(TendermintError::Malformed("validator voting power"));
+        }
+        if self.address != address(&self.public_key) {
+            return Err(TendermintError::Malformed("validator address"));
+        }
+        Ok(())
+    }
+}
+
+/// Address of the validator with the given public key.
+pub fn address(public_key: &PublicKey) -> Vec<u8> {
+    sha256(public_key.as_ref())[..ADDRESS_SIZE].to_vec()
+}
+
+/// Decode a Tendermint public key, of which only Ed25519 keys are supported.
+fn decode_public_key(data: &[u8]) -> Result<PublicKey> {
+    for (field, value) in protobuf::decode(data)? {
+        if let (1, Value::Bytes(key)) = (field, value) {
+            if key.len() != PublicKey::len() {
+                return Err(TendermintError::Malformed("public key").into());
+            }
+            return Ok(PublicKey::from(key));
+        }
+    }
+    Err(TendermintError::UnsupportedPublicKey.into())
+}
+
+/// A consensus validator set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidatorSet {
+    /// Validators, in order of decreasing voting power.
+    pub validators: Vec<Validator>,
+    /// Proposer of the block.
+    pub proposer: Option<Validator>,
+    /// Total voting power of the validators.
+    pub total_voting_power: i64,
+}
+
+impl ValidatorSet {
+    /// Create a new validator set with the given validators.
+    pub fn new(validators: Vec<Validator>) -> Self {
+        let total_voting_power = validators.iter().map(|v| v.voting_power).sum();
+        Self {
+            proposer: validators.first().cloned(),
+            validators,
+            total_voting_power,
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut set = Self::default();
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => set
+                    .validators
+                    .push(Validator::decode(value.as_bytes(field)?)?),
+                2 => set.proposer = Some(Validator::decode(value.as_bytes(field)?)?),
+                3 => set.total_voting_power = value.as_varint(field)? as i64,
+                _ => {}
+            }
+        }
+        Ok(set)
+    }
+
+    fn encode(&self) -> Encoder {
+        let mut encoder = Encoder::new();
+        for validator in &self.validators {
+            encoder = encoder.message(1, validator.encode());
+        }
+        if let Some(proposer) = &self.proposer {
+            encoder = encoder.message(2, proposer.encode());
+        }
+        varint(encoder, 3, self.total_voting_power as u64)
+    }
+
+    /// Hash of the validator set, as committed by headers.
+    ///
+    /// This is the Merkle root of the public keys and voting powers of the
+    /// validators.
+    pub fn hash(&self) -> Vec<u8> {
+        let items: Vec<Vec<u8>> = self
+            .validators
+            .iter()
+            .map(|validator| {
+                let encoder = Encoder::new().message(1, validator.encode_public_key());
+                varint(encoder, 2, validator.voting_power as u64).into_vec()
+            })
+            .collect();
+        merkle_root(&items)
+    }
+
+    /// Validator with the given address and its index, if any.
+    pub fn get_by_address(&self, address: &[u8]) -> Option<(usize, &Validator)> {
+        self.validators
+            .iter()
+            .enumerate()
+            .find(|(_, validator)| validator.address == address)
+    }
+
+    fn validate_basic(&self) -> Result<(), TendermintError> {
+        if self.validators.is_empty() {
+            return Err(TendermintError::Malformed("empty validator set"));
+        }
+        for validator in &self.validators {
+            validator.validate_basic()?;
+        }
+        Ok(())
+    }
+}
+
+/// A block header together with its commit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignedHeader {
+    /// Block header.
+    pub header: Header,
+    /// Commit of the header.
+    pub commit: Commit,
+}
+
+/// A Tendermint light block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LightBlock {
+    /// Signed block header.
+    pub signed_header: SignedHeader,
+    /// Validator set which signed the header.
+    pub validator_set: ValidatorSet,
+}
+
+impl LightBlock {
+    /// Decode a protobuf-encoded light block and check that it is well-formed
+    /// (but not that it is valid).
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut signed_header = None;
+        let mut validator_set = None;
+        for (field, value) in protobuf::decode(data)? {
+            match field {
+                1 => {
+                    let mut header = None;
+                    let mut commit = None;
+                    for (field, value) in protobuf::decode(value.as_bytes(field)?)? {
+                        match field {
+                            1 => header = Some(Header::decode(value.as_bytes(field)?)?),
+                            2 => commit = Some(Commit::decode(value.as_bytes(field)?)?),
+                            _ => {}
+                        }
+                    }
+                    signed_header = Some(SignedHeader {
+                        header: header.ok_or(TendermintError::Malformed("missing header"))?,
+                        commit: commit.ok_or(TendermintError::Malformed("missing commit"))?,
+                    });
+                }
+                2 => validator_set = Some(ValidatorSet::decode(value.as_bytes(field)?)?),
+                _ => {}
+            }
+        }
+
+        let block = Self {
+            signed_header: signed_header
+                .ok_or(TendermintError::Malformed("missing signed header"))?,
+            validator_set: validator_set
+                .ok_or(TendermintError::Malformed("missing validator set"))?,
+        };
+        block.validate_basic()?;
+        Ok(block)
+    }
+
+    /// Encode the light block.
+    pub fn encode(&self) -> Vec<u8> {
+        let signed_header = Encoder::new()
+            .message(1, self.signed_header.header.encode())
+            .message(2, self.signed_header.commit.encode());
+        Encoder::new()
+            .message(1, signed_header)
+            .message(2, self.validator_set.encode())
+            .into_vec()
+    }
+
+    fn validate_basic(&self) -> Result<(), TendermintError> {
+        let SignedHeader { header, commit } = &self.signed_header;
+        header.validate_basic()?;
+        commit.validate_basic()?;
+        self.validator_set.validate_basic()?;
+        if commit.height != header.height {
+            return Err(TendermintError::Malformed("commit height"));
+        }
+        if commit.signatures.len() != self.validator_set.validators.len() {
+            return Err(TendermintError::Malformed("commit signature count"));
+        }
+        Ok(())
+    }
+}
+
+/// Result of executing a transaction, as committed by the results hash of
+/// the next header.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxResult {
+    /// Result code (zero on success).
+    pub code: u32,
+    /// Result data.
+    pub data: Vec<u8>,
+    /// Gas requested by the transaction.
+    pub gas_wanted: i64,
+    /// Gas used by the transaction.
+    pub gas_used: i64,
+}
+
+/// Hash of the results of executing the transactions of a block.
+pub fn results_hash(results: &[TxResult]) -> Vec<u8> {
+    let items: Vec<Vec<u8>> = results
+        .iter()
+        .map(|result| {
+            let mut encoder = varint(Encoder::new(), 1, u64::from(result.code));
+            encoder = bytes(encoder, 2, &result.data);
+            encoder = varint(encoder, 5, result.gas_wanted as u64);
+            varint(encoder, 6, result.gas_used as u64).into_vec()
+        })
+        .collect();
+    merkle_root(&items)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rustc_hex::ToHex;
+
+    #[test]
+    fn test_merkle_root() {
+        // Test vectors from RFC 6962 implementations.
+        assert_eq!(
+            merkle_root::<&[u8]>(&[]).to_hex::<String>(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            merkle_root(&[b""]).to_hex::<String>(),
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+        assert_eq!(
+            merkle_root(&[b"L123456"]).to_hex::<String>(),
+            "395aa064aa4c29f7010acfe3f25db9485bbd4b91897b6ad7ad547639252b4d56"
+        );
+
+        // Items are split at the largest power of two smaller than their count.
+        let items: Vec<Vec<u8>> = (0u8..5).map(|i| vec![i]).collect();
+        let node =
+            |left: Vec<u8>, right: Vec<u8>| sha256(&[&[0x01], &left[..], &right[..]].concat());
+        let expected = node(
+            node(
+                node(merkle_root(&items[0..1]), merkle_root(&items[1..2])),
+                node(merkle_root(&items[2..3]), merkle_root(&items[3..4])),
+            ),
+            merkle_root(&items[4..5]),
+        );
+        assert_eq!(merkle_root(&items), expected);
+    }
+
+    #[test]
+    fn test_header_hash() {
+        // Test vector from Tendermint (types/block_test.go, TestHeaderHash).
+        let mut header = Header {
+            version_block: 1,
+            version_app: 2,
+            chain_id: "chainId".to_string(),
+            height: 3,
+            time: Timestamp {
+                seconds: 1570983284, // 2019-10-13T16:14:44Z
+                nanos: 0,
+            },
+            last_block_id: BlockId {
+                hash: vec![0; HASH_SIZE],
+                part_set_header: PartSetHeader {
+                    total: 6,
+                    hash: vec![0; HASH_SIZE],
+                },
+            },
+            last_commit_hash: sha256(b"last_commit_hash"),
+            data_hash: sha256(b"data_hash"),
+            validators_hash: sha256(b"validators_hash"),
+            next_validators_hash: sha256(b"next_validators_hash"),
+            consensus_hash: sha256(b"consensus_hash"),
+            app_hash: sha256(b"app_hash"),
+            last_results_hash: sha256(b"last_results_hash"),
+            evidence_hash: sha256(b"evidence_hash"),
+            proposer_address: sha256(b"proposer_address")[..ADDRESS_SIZE].to_vec(),
+        };
+        assert_eq!(
+            header.hash().to_hex::<String>(),
+            "f740121f553b5418c3efbd343c2dbfe9e007bb67b0d020a0741374bab65242a4"
+        );
+
+        // The header survives an encoding round trip.
+        let decoded = Header::decode(&header.encode().into_vec()).unwrap();
+        assert_eq!(decoded, header);
+        header.validate_basic().unwrap();
+        assert_eq!(
+            header.state_root().unwrap().as_ref(),
+            &sha256(b"app_hash")[..]
+        );
+
+        // An empty application state hash commits the empty state.
+        header.app_hash = vec![];
+        assert_eq!(header.state_root().unwrap(), Hash::empty_hash());
+        header.app_hash = vec![0; 20];
+        assert!(header.state_root().is_err());
+    }
+
+    #[test]
+    fn test_vote_sign_bytes() {
+        // Test vectors from Tendermint (types/vote_test.go,
+        // TestVoteSignBytesTestVectors), for a precommit vote at height 1
+        // and round 1 at the zero time.
+        let mut commit = Commit {
+            height: 1,
+            round: 1,
+            block_id: BlockId::default(),
+            signatures: vec![CommitSig {
+                block_id_flag: BlockIdFlag::Nil,
+                timestamp: Timestamp {
+                    seconds: -62135596800,
+                    nanos: 0,
+                },
+                ..Default::default()
+            }],
+        };
+        let expected = vec![
+            0x21, 0x08, 0x02, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0x01,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2a, 0x0b, 0x08, 0x80, 0x92, 0xb8, 0xc3,
+            0x98, 0xfe, 0xff, 0xff, 0xff, 0x01,
+        ];
+        assert_eq!(commit.vote_sign_bytes("", 0), expected);
+
+        // The chain identifier is appended.
+        let mut with_chain_id = expected.clone();
+        with_chain_id[0] = 0x30;
+        with_chain_id.extend_from_slice(b"\x32\x0dtest_chain_id");
+        assert_eq!(commit.vote_sign_bytes("test_chain_id", 0), with_chain_id);
+
+        // Votes for the committed block include its canonical identifier.
+        commit.block_id = BlockId {
+            hash: vec![1; HASH_SIZE],
+            part_set_header: PartSetHeader {
+                total: 1,
+                hash: vec![2; HASH_SIZE],
+            },
+        };
+        assert_eq!(commit.vote_sign_bytes("", 0), expected);
+        commit.signatures[0].block_id_flag = BlockIdFlag::Commit;
+        let bytes = commit.vote_sign_bytes("", 0);
+        assert_eq!(bytes[0] as usize, bytes.len() - 1);
+        assert_eq!(&bytes[21..25], &[0x22, 0x48, 0x0a, 0x20]);
+    }
+
+    #[test]
+    fn test_light_block() {
+        let validators: Vec<_> = (0..3u8)
+            .map(|i| Validator::new(PublicKey::from(vec![i; 32]), 10))
+            .collect();
+        let validator_set = ValidatorSet::new(validators);
+        let header = Header {
+            chain_id: "test".to_string(),
+            height: 2,
+            validators_hash: validator_set.hash(),
+            next_validators_hash: validator_set.hash(),
+            proposer_address: validator_set.validators[0].address.clone(),
+            ..Default::default()
+        };
+        let commit = Commit {
+            height: 2,
+            round: 0,
+            block_id: BlockId {
+                hash: header.hash(),
+                part_set_header: PartSetHeader::default(),
+            },
+            signatures: validator_set
+                .validators
+                .iter()
+                .map(|validator| CommitSig {
+                    block_id_flag: BlockIdFlag::Commit,
+                    validator_address: validator.address.clone(),
+                    timestamp: Timestamp::default(),
+                    signature: vec![0; 64],
+                })
+                .collect(),
+        };
+        let block = LightBlock {
+            signed_header: SignedHeader { header, commit },
+            validator_set,
+        };
+
+        let encoded = block.encode();
+        assert_eq!(LightBlock::decode(&encoded).unwrap(), block);
+
+        // Malformed blocks are rejected.
+        assert!(LightBlock::decode(&encoded[..encoded.len() - 1]).is_err());
+        let mut malformed = block.clone();
+        malformed.signed_header.commit.height = 3;
+        assert!(LightBlock::decode(&malformed.encode()).is_err());
+        let mut malformed = block.clone();
+        malformed.signed_header.commit.signatures.pop();
+        assert!(LightBlock::decode(&malformed.encode()).is_err());
+        let mut malformed = block;
+        malformed.validator_set.validators[0].address = vec![0; ADDRESS_SIZE];
+        assert!(LightBlock::decode(&malformed.encode()).is_err());
+    }
+}
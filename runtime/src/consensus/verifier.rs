@@ -0,0 +1,1140 @@
+//! Consensus light client verification.
+//!
+//! The verifier follows the consensus layer from a trust root configured at
+//! runtime initialization and verifies the light blocks provided by the
+//! (untrusted) host the same way a Tendermint light client does. Every header
+//! must be committed by more than 2/3 of the voting power of its validator
+//! set, and that validator set must either be the one announced by the latest
+//! trusted header (adjacent verification) or enough voting power of the latest
+//! trusted validator set must have signed the commit as well (skipping
+//! verification). Verified headers then authenticate the consensus state root
+//! and the transaction results at their height.
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use io_context::Context;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    tendermint::{
+        self, results_hash, BlockIdFlag, Commit, Header, SignedHeader, Timestamp, TxResult,
+        ValidatorSet,
+    },
+    LightBlock,
+};
+use crate::{
+    common::{
+        crypto::{
+            hash::Hash,
+            signature::{PublicKey, Signature},
+        },
+        time::insecure_posix_time,
+    },
+    protocol::{Protocol, ProtocolError},
+    types::Body,
+};
+
+/// Height with which the latest light block is requested from the host.
+pub const HEIGHT_LATEST: i64 = 0;
+
+/// Default trusting period (two weeks, in seconds).
+const DEFAULT_TRUSTING_PERIOD: i64 = 14 * 24 * 60 * 60;
+/// Default maximum clock drift (seconds).
+const DEFAULT_MAX_CLOCK_DRIFT: i64 = 10;
+/// Maximum number of verified headers kept by the verifier.
+const MAX_VERIFIED_HEADERS: usize = 128;
+
+/// Light block verification error.
+#[derive(Error, Debug)]
+pub enum VerificationError {
+    #[error("malformed light block: {0}")]
+    MalformedLightBlock(String),
+    #[error("chain id mismatch")]
+    ChainIdMismatch,
+    #[error("header does not match the trust root")]
+    TrustRootMismatch,
+    #[error("validator set hash mismatch")]
+    ValidatorsHashMismatch,
+    #[error("next validator set hash mismatch")]
+    NextValidatorsHashMismatch,
+    #[error("commit does not match the header")]
+    CommitMismatch,
+    #[error("height {height} is not after the trusted height {trusted}")]
+    NonIncreasingHeight { height: i64, trusted: i64 },
+    #[error("header time is not after the trusted header time")]
+    NonIncreasingTime,
+    #[error("header time is too far in the future")]
+    HeaderFromFuture,
+    #[error("trusted header expired at {0}")]
+    TrustedHeaderExpired(i64),
+    #[error("duplicate commit signature")]
+    DuplicateSignature,
+    #[error("commit signature verification failed")]
+    InvalidSignature,
+    #[error("insufficient voting power signed the commit ({signed} of {total})")]
+    InsufficientVotingPower { signed: i64, total: i64 },
+    #[error("insufficient trusted voting power signed the commit ({signed} of {total})")]
+    InsufficientTrustedVotingPower { signed: i64, total: i64 },
+    #[error("voting power overflow")]
+    VotingPowerOverflow,
+    #[error("header at height {0} is not verified")]
+    HeaderNotVerified(i64),
+    #[error("state root mismatch")]
+    StateRootMismatch,
+    #[error("results hash mismatch")]
+    ResultsHashMismatch,
+    #[error("consensus state at height {height} is stale (latest trusted height {trusted_height}, time {trusted_time})")]
+    StaleState {
+        height: i64,
+        trusted_height: i64,
+        trusted_time: i64,
+    },
+}
+
+/// Decode the Tendermint light block contained in a light block.
+fn decode_light_block(block: &LightBlock) -> Result<tendermint::LightBlock, VerificationError> {
+    let decoded = tendermint::LightBlock::decode(&block.meta)
+        .map_err(|err| VerificationError::MalformedLightBlock(err.to_string()))?;
+    if decoded.signed_header.header.height != block.height {
+        return Err(VerificationError::MalformedLightBlock(
+            "height mismatch".to_string(),
+        ));
+    }
+    Ok(decoded)
+}
+
+/// Check that the validator set and commit of a light block belong to its
+/// header.
+fn validate_light_block(
+    block: &tendermint::LightBlock,
+    chain_id: &str,
+) -> Result<(), VerificationError> {
+    let SignedHeader { header, commit } = &block.signed_header;
+    if header.chain_id != chain_id {
+        return Err(VerificationError::ChainIdMismatch);
+    }
+    if block.validator_set.hash() != header.validators_hash {
+        return Err(VerificationError::ValidatorsHashMismatch);
+    }
+    if commit.height != header.height || commit.block_id.hash != header.hash() {
+        return Err(VerificationError::CommitMismatch);
+    }
+    Ok(())
+}
+
+/// Total voting power of a validator set.
+fn total_voting_power(validators: &ValidatorSet) -> Result<i64, VerificationError> {
+    validators
+        .validators
+        .iter()
+        .try_fold(0i64, |total, validator| {
+            total
+                .checked_add(validator.voting_power)
+                .ok_or(VerificationError::VotingPowerOverflow)
+        })
+}
+
+/// Verify the signature of the validator with the given index in the commit.
+fn verify_commit_signature(
+    chain_id: &str,
+    commit: &Commit,
+    index: usize,
+    public_key: &PublicKey,
+) -> Result<(), VerificationError> {
+    let signature = &commit.signatures[index].signature;
+    if signature.len() != Signature::len() {
+        return Err(VerificationError::InvalidSignature);
+    }
+    Signature::from(&signature[..])
+        .verify_raw(public_key, &commit.vote_sign_bytes(chain_id, index))
+        .map_err(|_| VerificationError::InvalidSignature)
+}
+
+/// Fraction of the trusted voting power which must sign a header for
+/// skipping verification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrustThreshold {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl TrustThreshold {
+    /// Whether the signed voting power exceeds the threshold.
+    fn exceeded(&self, signed: i64, total: i64) -> bool {
+        (signed as i128) * (self.denominator as i128) > (total as i128) * (self.numerator as i128)
+    }
+}
+
+impl Default for TrustThreshold {
+    fn default() -> Self {
+        Self {
+            numerator: 1,
+            denominator: 3,
+        }
+    }
+}
+
+/// Fraction of the voting power which must commit every header.
+const COMMIT_THRESHOLD: TrustThreshold = TrustThreshold {
+    numerator: 2,
+    denominator: 3,
+};
+
+/// Verify that more than 2/3 of the voting power of the validator set signed
+/// the commit.
+///
+/// Signatures in the commit are matched to validators by their index.
+fn verify_commit(
+    chain_id: &str,
+    validators: &ValidatorSet,
+    commit: &Commit,
+) -> Result<(), VerificationError> {
+    if commit.signatures.len() != validators.validators.len() {
+        return Err(VerificationError::CommitMismatch);
+    }
+
+    let total = total_voting_power(validators)?;
+    let mut signed = 0i64;
+    for (index, (sig, validator)) in commit
+        .signatures
+        .iter()
+        .zip(validators.validators.iter())
+        .enumerate()
+    {
+        if sig.block_id_flag != BlockIdFlag::Commit {
+            continue;
+        }
+        if sig.validator_address != validator.address {
+            return Err(VerificationError::CommitMismatch);
+        }
+        verify_commit_signature(chain_id, commit, index, &validator.public_key)?;
+        signed = signed
+            .checked_add(validator.voting_power)
+            .ok_or(VerificationError::VotingPowerOverflow)?;
+        if COMMIT_THRESHOLD.exceeded(signed, total) {
+            return Ok(());
+        }
+    }
+
+    Err(VerificationError::InsufficientVotingPower { signed, total })
+}
+
+/// Verify that more than the trust threshold of the voting power of a
+/// trusted validator set signed the commit.
+///
+/// Signatures in the commit are matched to validators by their address, as
+/// the trusted validator set may differ from the one committing the header.
+fn verify_commit_trusting(
+    chain_id: &str,
+    validators: &ValidatorSet,
+    commit: &Commit,
+    threshold: TrustThreshold,
+) -> Result<(), VerificationError> {
+    let total = total_voting_power(validators)?;
+    let mut signed = 0i64;
+    let mut seen = HashSet::new();
+    for (index, sig) in commit.signatures.iter().enumerate() {
+        if sig.block_id_flag != BlockIdFlag::Commit {
+            continue;
+        }
+        let (validator_index, validator) = match validators.get_by_address(&sig.validator_address) {
+            Some(validator) => validator,
+            None => continue,
+        };
+        if !seen.insert(validator_index) {
+            return Err(VerificationError::DuplicateSignature);
+        }
+        verify_commit_signature(chain_id, commit, index, &validator.public_key)?;
+        signed = signed
+            .checked_add(validator.voting_power)
+            .ok_or(VerificationError::VotingPowerOverflow)?;
+        if threshold.exceeded(signed, total) {
+            return Ok(());
+        }
+    }
+
+    Err(VerificationError::InsufficientTrustedVotingPower { signed, total })
+}
+
+/// Trust root of the verifier, configured at runtime initialization.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustRoot {
+    /// Chain identifier.
+    pub chain_id: String,
+    /// Height of the trusted header.
+    pub height: i64,
+    /// Hash of the trusted header.
+    pub hash: Hash,
+}
+
+/// Bounds on how stale the consensus state used by the runtime may be.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StalenessPolicy {
+    /// Maximum number of blocks by which the state may lag behind the latest
+    /// trusted header.
+    pub max_blocks: Option<i64>,
+    /// Maximum age (in seconds) of the latest trusted header, relative to the
+    /// local clock. This bounds how far behind the consensus layer the host
+    /// can keep the runtime by withholding newer light blocks.
+    pub max_age: Option<i64>,
+}
+
+/// Verifier options.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Period (in seconds) after its time for which a header is trusted.
+    ///
+    /// This must be shorter than the unbonding period, so that validators
+    /// of a trusted header can still be slashed for signing conflicting
+    /// headers.
+    pub trusting_period: i64,
+    /// Maximum time (in seconds) by which headers may be ahead of the local
+    /// clock.
+    pub max_clock_drift: i64,
+    /// Trust threshold for skipping verification.
+    pub trust_threshold: TrustThreshold,
+    /// Bounds on the staleness of the consensus state, unbounded by default.
+    pub staleness: StalenessPolicy,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            trusting_period: DEFAULT_TRUSTING_PERIOD,
+            max_clock_drift: DEFAULT_MAX_CLOCK_DRIFT,
+            trust_threshold: TrustThreshold::default(),
+            staleness: StalenessPolicy::default(),
+        }
+    }
+}
+
+/// Consensus light client verifier.
+pub struct Verifier {
+    options: Options,
+    chain_id: String,
+    /// Latest trusted header.
+    trusted: Header,
+    /// Validator set committing the latest trusted header.
+    trusted_validators: ValidatorSet,
+    /// Recently verified headers by height.
+    headers: BTreeMap<i64, Header>,
+}
+
+impl Verifier {
+    /// Create a verifier trusting the light block at the trust root.
+    pub fn new(options: Options, root: &TrustRoot, block: &LightBlock) -> Result<Self> {
+        Self::new_at(options, root, block, insecure_posix_time())
+    }
+
+    fn new_at(options: Options, root: &TrustRoot, block: &LightBlock, now: i64) -> Result<Self> {
+        let block = decode_light_block(block)?;
+        validate_light_block(&block, &root.chain_id)?;
+        let header = &block.signed_header.header;
+        if header.height != root.height || header.hash() != root.hash.as_ref() {
+            return Err(VerificationError::TrustRootMismatch.into());
+        }
+        verify_commit(
+            &root.chain_id,
+            &block.validator_set,
+            &block.signed_header.commit,
+        )?;
+
+        let mut verifier = Self {
+            options,
+            chain_id: root.chain_id.clone(),
+            trusted: header.clone(),
+            trusted_validators: block.validator_set,
+            headers: BTreeMap::new(),
+        };
+        verifier.check_trusted(now)?;
+        verifier.headers.insert(
+            block.signed_header.header.height,
+            block.signed_header.header,
+        );
+
+        Ok(verifier)
+    }
+
+    /// Latest trusted header.
+    pub fn trusted_header(&self) -> &Header {
+        &self.trusted
+    }
+
+    /// Height of the latest trusted header.
+    pub fn trusted_height(&self) -> i64 {
+        self.trusted.height
+    }
+
+    /// Time (POSIX time) of the latest trusted header.
+    pub fn trusted_time(&self) -> i64 {
+        self.trusted.time.seconds
+    }
+
+    /// Check that the consensus state at the given height is fresh enough
+    /// according to the staleness policy, returning a `StaleState` error
+    /// otherwise.
+    pub fn check_fresh(&self, height: i64) -> Result<()> {
+        self.check_fresh_at(height, insecure_posix_time())
+    }
+
+    fn check_fresh_at(&self, height: i64, now: i64) -> Result<()> {
+        let policy = &self.options.staleness;
+        let lagging = policy.max_blocks.map_or(false, |max_blocks| {
+            self.trusted.height - height > max_blocks
+        });
+        let expired = policy
+            .max_age
+            .map_or(false, |max_age| now - self.trusted_time() > max_age);
+        if lagging || expired {
+            return Err(VerificationError::StaleState {
+                height,
+                trusted_height: self.trusted.height,
+                trusted_time: self.trusted_time(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Verify a light block against the latest trusted header and make it
+    /// the latest trusted header.
+    pub fn verify(&mut self, block: &LightBlock) -> Result<()> {
+        self.verify_at(block, insecure_posix_time())
+    }
+
+    fn verify_at(&mut self, block: &LightBlock, now: i64) -> Result<()> {
+        let block = decode_light_block(block)?;
+        validate_light_block(&block, &self.chain_id)?;
+        let SignedHeader { header, commit } = &block.signed_header;
+
+        // Already verified headers are accepted again, as long as they match.
+        if header.height <= self.trusted.height {
+            return match self.headers.get(&header.height) {
+                Some(verified) if verified == header => Ok(()),
+                _ => Err(VerificationError::NonIncreasingHeight {
+                    height: header.height,
+                    trusted: self.trusted.height,
+                }
+                .into()),
+            };
+        }
+
+        self.check_trusted(now)?;
+        if header.time <= self.trusted.time {
+            return Err(VerificationError::NonIncreasingTime.into());
+        }
+        if header.time > Self::latest_time(now, self.options.max_clock_drift) {
+            return Err(VerificationError::HeaderFromFuture.into());
+        }
+
+        if header.height == self.trusted.height + 1 {
+            // Adjacent headers must be committed by the announced validators.
+            if header.validators_hash != self.trusted.next_validators_hash {
+                return Err(VerificationError::NextValidatorsHashMismatch.into());
+            }
+        } else {
+            // Enough of the trusted validators must vouch for other headers.
+            verify_commit_trusting(
+                &self.chain_id,
+                &self.trusted_validators,
+                commit,
+                self.options.trust_threshold,
+            )?;
+        }
+        verify_commit(&self.chain_id, &block.validator_set, commit)?;
+
+        self.trusted = header.clone();
+        self.trusted_validators = block.validator_set;
+        self.headers.insert(
+            block.signed_header.header.height,
+            block.signed_header.header,
+        );
+        while self.headers.len() > MAX_VERIFIED_HEADERS {
+            let oldest = *self.headers.keys().next().unwrap();
+            self.headers.remove(&oldest);
+        }
+
+        Ok(())
+    }
+
+    /// Verify the light block at the given height, fetching it and any
+    /// intermediate light blocks needed to bridge validator set changes
+    /// (by bisection) with the given function, and return its header.
+    ///
+    /// With `HEIGHT_LATEST`, the latest light block returned by the fetch
+    /// function is verified.
+    pub fn sync<F>(&mut self, height: i64, fetch: F) -> Result<&Header>
+    where
+        F: FnMut(i64) -> Result<LightBlock>,
+    {
+        self.sync_at(height, fetch, insecure_posix_time())
+    }
+
+    fn sync_at<F>(&mut self, height: i64, mut fetch: F, now: i64) -> Result<&Header>
+    where
+        F: FnMut(i64) -> Result<LightBlock>,
+    {
+        if self.headers.contains_key(&height) {
+            return Ok(&self.headers[&height]);
+        }
+
+        let mut fetch = |height| -> Result<LightBlock> {
+            let block = fetch(height)?;
+            if height != HEIGHT_LATEST && block.height != height {
+                return Err(VerificationError::MalformedLightBlock(
+                    "unexpected height".to_string(),
+                )
+                .into());
+            }
+            Ok(block)
+        };
+
+        let target = fetch(height)?;
+        let height = target.height;
+        if self.headers.contains_key(&height) {
+            // The latest light block may already be trusted.
+            return Ok(&self.headers[&height]);
+        }
+        let mut pending = vec![target];
+        while let Some(block) = pending.last() {
+            let err = match self.verify_at(block, now) {
+                Ok(()) => {
+                    pending.pop();
+                    continue;
+                }
+                Err(err) => err,
+            };
+            match err.downcast_ref::<VerificationError>() {
+                Some(VerificationError::InsufficientTrustedVotingPower { .. })
+                    if block.height > self.trusted.height + 1 =>
+                {
+                    let pivot = self.trusted.height + (block.height - self.trusted.height) / 2;
+                    pending.push(fetch(pivot)?);
+                }
+                _ => return Err(err),
+            }
+        }
+
+        Ok(self.verified_header(height)?)
+    }
+
+    /// Verified header at the given height.
+    ///
+    /// Only a limited number of recently verified headers is kept.
+    pub fn verified_header(&self, height: i64) -> Result<&Header> {
+        self.headers
+            .get(&height)
+            .ok_or_else(|| VerificationError::HeaderNotVerified(height).into())
+    }
+
+    /// Verify that the given consensus state root is the one committed by
+    /// the verified header at the given height.
+    pub fn verify_state_root(&self, height: i64, state_root: &Hash) -> Result<()> {
+        if &self.verified_header(height)?.state_root()? != state_root {
+            return Err(VerificationError::StateRootMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Verify that the given transaction results of the block preceding the
+    /// given height are the ones committed by the verified header at the given
+    /// height.
+    pub fn verify_results(&self, height: i64, results: &[TxResult]) -> Result<()> {
+        if self.verified_header(height)?.last_results_hash != results_hash(results) {
+            return Err(VerificationError::ResultsHashMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Latest header time accepted at the given local time.
+    fn latest_time(now: i64, max_clock_drift: i64) -> Timestamp {
+        Timestamp {
+            seconds: now + max_clock_drift,
+            nanos: 0,
+        }
+    }
+
+    fn check_trusted(&self, now: i64) -> Result<(), VerificationError> {
+        let expires_at = self.trusted_time() + self.options.trusting_period;
+        if now >= expires_at {
+            return Err(VerificationError::TrustedHeaderExpired(expires_at));
+        }
+        if self.trusted.time > Self::latest_time(now, self.options.max_clock_drift) {
+            return Err(VerificationError::HeaderFromFuture);
+        }
+        Ok(())
+    }
+}
+
+/// Consensus verifier configuration.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// Trust root of the verifier.
+    pub trust_root: TrustRoot,
+    /// Verifier options.
+    pub options: Options,
+}
+
+/// Consensus light client verifier which fetches light blocks from the host.
+///
+/// The verifier starts from the trust root when it is first used.
+pub struct HostVerifier {
+    config: Config,
+    verifier: Mutex<Option<Verifier>>,
+}
+
+impl HostVerifier {
+    /// Create a new verifier with the given configuration.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            verifier: Mutex::new(None),
+        }
+    }
+
+    /// Verify the header at the given height (or the latest header with
+    /// `HEIGHT_LATEST`) with light blocks fetched from the host, and return
+    /// it.
+    pub fn sync(&self, ctx: Context, protocol: &Protocol, height: i64) -> Result<Header> {
+        let ctx = ctx.freeze();
+        self.sync_at(
+            height,
+            |height| fetch_light_block(&ctx, protocol, height),
+            insecure_posix_time(),
+        )
+    }
+
+    fn sync_at<F>(&self, height: i64, mut fetch: F, now: i64) -> Result<Header>
+    where
+        F: FnMut(i64) -> Result<LightBlock>,
+    {
+        let mut verifier = self.verifier.lock().unwrap();
+        if verifier.is_none() {
+            let root = &self.config.trust_root;
+            let block = fetch(root.height)?;
+            *verifier = Some(Verifier::new_at(
+                self.config.options.clone(),
+                root,
+                &block,
+                now,
+            )?);
+        }
+        let header = verifier.as_mut().unwrap().sync_at(height, fetch, now)?;
+
+        Ok(header.clone())
+    }
+
+    /// Verify the header at the given height like `sync`, and check that the
+    /// consensus state it commits is fresh enough according to the staleness
+    /// policy, returning a `StaleState` error otherwise.
+    pub fn sync_fresh(&self, ctx: Context, protocol: &Protocol, height: i64) -> Result<Header> {
+        let ctx = ctx.freeze();
+        self.sync_fresh_at(
+            height,
+            |height| fetch_light_block(&ctx, protocol, height),
+            insecure_posix_time(),
+        )
+    }
+
+    fn sync_fresh_at<F>(&self, height: i64, fetch: F, now: i64) -> Result<Header>
+    where
+        F: FnMut(i64) -> Result<LightBlock>,
+    {
+        let header = self.sync_at(height, fetch, now)?;
+        let verifier = self.verifier.lock().unwrap();
+        verifier
+            .as_ref()
+            .unwrap()
+            .check_fresh_at(header.height, now)?;
+
+        Ok(header)
+    }
+
+    /// Latest trusted header, if the verifier has been used.
+    pub fn trusted_header(&self) -> Option<Header> {
+        self.verifier
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|verifier| verifier.trusted_header().clone())
+    }
+}
+
+/// Fetch the light block at the given height from the host.
+fn fetch_light_block(ctx: &Arc<Context>, protocol: &Protocol, height: i64) -> Result<LightBlock> {
+    match protocol.make_request(
+        Context::create_child(ctx),
+        Body::HostFetchConsensusBlockRequest { height },
+    ) {
+        Ok(Body::HostFetchConsensusBlockResponse { block }) => Ok(block),
+        Ok(_) => Err(ProtocolError::InvalidResponse.into()),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::common::crypto::signature::{PrivateKey, Signer};
+    use tendermint::{address, BlockId, CommitSig, PartSetHeader, Validator};
+
+    pub const CHAIN_ID: &str = "oasis-core/test: chain";
+    pub const GENESIS_TIME: i64 = 1640995200; // 2022-01-01T00:00:00
+
+    pub fn keys(seed: &str, count: usize) -> Vec<PrivateKey> {
+        (0..count)
+            .map(|i| PrivateKey::from_test_seed(format!("{} {}", seed, i)))
+            .collect()
+    }
+
+    fn validator_set(keys: &[PrivateKey]) -> ValidatorSet {
+        ValidatorSet::new(
+            keys.iter()
+                .map(|key| Validator::new(key.public_key(), 10))
+                .collect(),
+        )
+    }
+
+    fn state_root(height: i64) -> Hash {
+        Hash::digest_bytes(&height.to_le_bytes())
+    }
+
+    fn results(height: i64) -> Vec<TxResult> {
+        vec![TxResult {
+            data: height.to_le_bytes().to_vec(),
+            ..Default::default()
+        }]
+    }
+
+    /// Sign a commit with the validators at the given indices.
+    fn sign(commit: &mut Commit, keys: &[PrivateKey], signers: &[usize]) {
+        for (index, key) in keys.iter().enumerate() {
+            if !signers.contains(&index) {
+                commit.signatures[index] = CommitSig::default();
+                continue;
+            }
+            let message = commit.vote_sign_bytes(CHAIN_ID, index);
+            let signature = key.sign_raw(&message).unwrap();
+            commit.signatures[index].signature = signature.as_ref().to_vec();
+        }
+    }
+
+    /// Produce a signed Tendermint light block at the given height, with the
+    /// given state root.
+    pub fn light_block(
+        height: i64,
+        app_hash: Hash,
+        keys: &[PrivateKey],
+        next_keys: &[PrivateKey],
+        last_block_id: BlockId,
+    ) -> tendermint::LightBlock {
+        let validator_set = validator_set(keys);
+        let header = Header {
+            version_block: 11,
+            chain_id: CHAIN_ID.to_string(),
+            height,
+            time: Timestamp {
+                seconds: GENESIS_TIME + height * 6,
+                nanos: 0,
+            },
+            last_block_id,
+            validators_hash: validator_set.hash(),
+            next_validators_hash: self::validator_set(next_keys).hash(),
+            app_hash: app_hash.as_ref().to_vec(),
+            last_results_hash: results_hash(&results(height - 1)),
+            proposer_address: validator_set.validators[0].address.clone(),
+            ..Default::default()
+        };
+        let mut commit = Commit {
+            height,
+            round: 0,
+            block_id: BlockId {
+                hash: header.hash(),
+                part_set_header: PartSetHeader {
+                    total: 1,
+                    hash: vec![height as u8; tendermint::HASH_SIZE],
+                },
+            },
+            signatures: keys
+                .iter()
+                .map(|key| CommitSig {
+                    block_id_flag: BlockIdFlag::Commit,
+                    validator_address: address(&key.public_key()),
+                    timestamp: header.time,
+                    signature: vec![],
+                })
+                .collect(),
+        };
+        sign(&mut commit, keys, &(0..keys.len()).collect::<Vec<_>>());
+
+        tendermint::LightBlock {
+            signed_header: SignedHeader { header, commit },
+            validator_set,
+        }
+    }
+
+    /// Produce a chain of light blocks, using the validator set for each
+    /// height returned by the given function.
+    fn chain<'a>(length: i64, validators: impl Fn(i64) -> &'a [PrivateKey]) -> Vec<LightBlock> {
+        let mut blocks = Vec::new();
+        let mut last_block_id = BlockId::default();
+        for height in 1..=length {
+            let block = light_block(
+                height,
+                state_root(height),
+                validators(height),
+                validators(height + 1),
+                last_block_id,
+            );
+            last_block_id = block.signed_header.commit.block_id.clone();
+            blocks.push(LightBlock {
+                height,
+                meta: block.encode(),
+            });
+        }
+        blocks
+    }
+
+    /// Verifier trusting the given light block.
+    pub fn verifier(block: &LightBlock) -> Verifier {
+        Verifier::new_at(Options::default(), &root(block), block, GENESIS_TIME + 3600).unwrap()
+    }
+
+    pub fn root(block: &LightBlock) -> TrustRoot {
+        let block = decode_light_block(block).unwrap();
+        TrustRoot {
+            chain_id: CHAIN_ID.to_string(),
+            height: block.signed_header.header.height,
+            hash: Hash::from(block.signed_header.header.hash()),
+        }
+    }
+
+    /// Modify a light block, optionally signing its commit again with the
+    /// validators at the given indices.
+    fn tamper(
+        block: &LightBlock,
+        keys: &[PrivateKey],
+        signers: Option<&[usize]>,
+        f: impl FnOnce(&mut tendermint::LightBlock),
+    ) -> LightBlock {
+        let mut decoded = decode_light_block(block).unwrap();
+        f(&mut decoded);
+        if let Some(signers) = signers {
+            let header_hash = decoded.signed_header.header.hash();
+            let commit = &mut decoded.signed_header.commit;
+            commit.block_id.hash = header_hash;
+            sign(commit, keys, signers);
+        }
+        LightBlock {
+            height: block.height,
+            meta: decoded.encode(),
+        }
+    }
+
+    #[test]
+    fn test_verify_adjacent_and_skipping() {
+        let validators = keys("validator", 4);
+        let blocks = chain(20, |_| &validators[..]);
+        let now = GENESIS_TIME + 3600;
+
+        let mut verifier =
+            Verifier::new_at(Options::default(), &root(&blocks[0]), &blocks[0], now).unwrap();
+        verifier.verify_at(&blocks[1], now).expect("adjacent");
+        verifier.verify_at(&blocks[9], now).expect("skipping");
+        assert_eq!(verifier.trusted_height(), 10);
+        verifier
+            .verify_at(&blocks[9], now)
+            .expect("already verified");
+        assert!(verifier.verify_at(&blocks[4], now).is_err(), "not verified");
+
+        verifier
+            .verify_state_root(10, &state_root(10))
+            .expect("state root");
+        assert!(verifier.verify_state_root(10, &Hash::empty_hash()).is_err());
+        verifier.verify_results(10, &results(9)).expect("results");
+        assert!(verifier.verify_results(10, &results(10)).is_err());
+        assert!(verifier.verify_state_root(5, &Hash::empty_hash()).is_err());
+    }
+
+    #[test]
+    fn test_check_fresh() {
+        let validators = keys("validator", 4);
+        let blocks = chain(20, |_| &validators[..]);
+        let now = GENESIS_TIME + 120;
+        let options = Options {
+            staleness: StalenessPolicy {
+                max_blocks: Some(5),
+                max_age: Some(60),
+            },
+            ..Default::default()
+        };
+
+        let mut verifier = Verifier::new_at(options, &root(&blocks[0]), &blocks[0], now).unwrap();
+        verifier.verify_at(&blocks[14], now).expect("skipping");
+        verifier.check_fresh_at(15, now).expect("latest state");
+        verifier
+            .check_fresh_at(10, now)
+            .expect("state within bound");
+
+        // State lagging too far behind the latest trusted header is stale.
+        let err = verifier.check_fresh_at(9, now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::StaleState {
+                height: 9,
+                trusted_height: 15,
+                ..
+            })
+        ));
+
+        // Everything is stale once the latest trusted header is too old.
+        assert!(verifier
+            .check_fresh_at(15, GENESIS_TIME + 15 * 6 + 61)
+            .is_err());
+
+        // Without a policy, state is never stale.
+        let verifier =
+            Verifier::new_at(Options::default(), &root(&blocks[0]), &blocks[0], now).unwrap();
+        verifier
+            .check_fresh_at(-100, now + 3600)
+            .expect("unbounded");
+    }
+
+    #[test]
+    fn test_host_verifier_staleness() {
+        let validators = keys("validator", 4);
+        let blocks = chain(20, |_| &validators[..]);
+        let now = GENESIS_TIME + 20 * 6 + 10;
+        let verifier = HostVerifier::new(Config {
+            trust_root: root(&blocks[0]),
+            options: Options {
+                staleness: StalenessPolicy {
+                    max_blocks: Some(5),
+                    max_age: Some(60),
+                },
+                ..Default::default()
+            },
+        });
+        let fetch = |latest: i64| {
+            let blocks = &blocks;
+            move |height: i64| match height {
+                HEIGHT_LATEST => Ok(blocks[(latest - 1) as usize].clone()),
+                height => Ok(blocks[(height - 1) as usize].clone()),
+            }
+        };
+
+        for height in &[14, 15] {
+            verifier
+                .sync_fresh_at(*height, fetch(20), now)
+                .expect("fresh state");
+        }
+        let header = verifier
+            .sync_fresh_at(HEIGHT_LATEST, fetch(20), now)
+            .expect("latest state");
+        assert_eq!(header.height, 20);
+        verifier
+            .sync_fresh_at(15, fetch(20), now)
+            .expect("state within bound");
+
+        // State lagging too far behind the latest trusted header is stale,
+        // even though its header is verified.
+        let err = verifier.sync_fresh_at(14, fetch(20), now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::StaleState {
+                height: 14,
+                trusted_height: 20,
+                ..
+            })
+        ));
+        verifier.sync_at(14, fetch(20), now).expect("verified");
+
+        // A host withholding newer light blocks is detected once the latest
+        // trusted header is too old.
+        let later = now + 60;
+        let err = verifier
+            .sync_fresh_at(HEIGHT_LATEST, fetch(20), later)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::StaleState { height: 20, .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_trust_root() {
+        let validators = keys("validator", 4);
+        let blocks = chain(2, |_| &validators[..]);
+        let now = GENESIS_TIME + 3600;
+
+        assert!(Verifier::new_at(Options::default(), &root(&blocks[0]), &blocks[1], now).is_err());
+        let expired = GENESIS_TIME + DEFAULT_TRUSTING_PERIOD + 6;
+        assert!(
+            Verifier::new_at(Options::default(), &root(&blocks[0]), &blocks[0], expired).is_err()
+        );
+        let mut other = root(&blocks[0]);
+        other.chain_id = "oasis-core/test: other chain".to_string();
+        assert!(Verifier::new_at(Options::default(), &other, &blocks[0], now).is_err());
+    }
+
+    #[test]
+    fn test_verify_invalid() {
+        let validators = keys("validator", 4);
+        let blocks = chain(10, |_| &validators[..]);
+        let now = GENESIS_TIME + 3600;
+        let mut verifier =
+            Verifier::new_at(Options::default(), &root(&blocks[0]), &blocks[0], now).unwrap();
+        let all = [0, 1, 2, 3];
+
+        // Tampered header.
+        let tampered = tamper(&blocks[1], &validators, None, |block| {
+            block.signed_header.header.app_hash = vec![0; 32];
+        });
+        assert!(verifier.verify_at(&tampered, now).is_err());
+
+        // Tampered header signed by the wrong chain.
+        let tampered = tamper(&blocks[1], &validators, Some(&all), |block| {
+            block.signed_header.header.chain_id = "oasis-core/test: other chain".to_string();
+        });
+        assert!(verifier.verify_at(&tampered, now).is_err());
+
+        // Invalid signature.
+        let tampered = tamper(&blocks[1], &validators, None, |block| {
+            let signatures = &mut block.signed_header.commit.signatures;
+            signatures[0].signature = signatures[1].signature.clone();
+        });
+        let err = verifier.verify_at(&tampered, now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::InvalidSignature)
+        ));
+
+        // Duplicate signature of a trusted validator.
+        let tampered = tamper(&blocks[9], &validators, None, |block| {
+            let signatures = &mut block.signed_header.commit.signatures;
+            signatures[1] = signatures[0].clone();
+        });
+        let err = verifier.verify_at(&tampered, now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::DuplicateSignature)
+        ));
+
+        // Not enough voting power (2/3 exactly is not enough).
+        let tampered = tamper(&blocks[1], &validators, Some(&[0, 2]), |_| {});
+        let err = verifier.verify_at(&tampered, now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::InsufficientVotingPower {
+                signed: 20,
+                total: 40
+            })
+        ));
+        let quorum = tamper(&blocks[1], &validators, Some(&[0, 1, 3]), |_| {});
+        verifier.verify_at(&quorum, now).expect("quorum");
+
+        // Header from the future.
+        assert!(verifier.verify_at(&blocks[9], GENESIS_TIME).is_err());
+
+        // Expired trusted header.
+        let expired = GENESIS_TIME + DEFAULT_TRUSTING_PERIOD + 12;
+        assert!(verifier.verify_at(&blocks[9], expired).is_err());
+
+        // Malformed light blocks.
+        let mut malformed = blocks[9].clone();
+        malformed.meta.truncate(10);
+        assert!(verifier.verify_at(&malformed, now).is_err());
+        let mut malformed = blocks[9].clone();
+        malformed.height = 11;
+        assert!(verifier.verify_at(&malformed, now).is_err());
+    }
+
+    #[test]
+    fn test_sync_bisection() {
+        // The validator set is replaced completely every 4 blocks, so skipping
+        // verification only succeeds within each period.
+        let validators: Vec<_> = (0..9)
+            .map(|i| keys(&format!("validators {}", i), 4))
+            .collect();
+        let blocks = chain(32, |height| &validators[((height - 1) / 4) as usize][..]);
+        let now = GENESIS_TIME + 3600;
+
+        let mut verifier =
+            Verifier::new_at(Options::default(), &root(&blocks[0]), &blocks[0], now).unwrap();
+        let err = verifier.verify_at(&blocks[31], now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::InsufficientTrustedVotingPower { .. })
+        ));
+
+        let mut fetched = Vec::new();
+        let header = verifier
+            .sync_at(
+                32,
+                |height| {
+                    fetched.push(height);
+                    Ok(blocks[(height - 1) as usize].clone())
+                },
+                now,
+            )
+            .expect("sync")
+            .clone();
+        assert_eq!(header.height, 32);
+        assert_eq!(verifier.trusted_height(), 32);
+        assert!(fetched.len() < 32, "bisection skips headers");
+
+        // Light blocks at other heights than requested are rejected.
+        let mut verifier =
+            Verifier::new_at(Options::default(), &root(&blocks[0]), &blocks[0], now).unwrap();
+        assert!(verifier
+            .sync_at(32, |_| Ok(blocks[30].clone()), now)
+            .is_err());
+        let tampered = tamper(&blocks[31], &validators[7], Some(&[]), |_| {});
+        assert!(verifier.sync_at(32, |_| Ok(tampered.clone()), now).is_err());
+        assert_eq!(verifier.trusted_height(), 1);
+    }
+
+    #[test]
+    fn test_host_verifier() {
+        let validators = keys("validator", 4);
+        let blocks = chain(20, |_| &validators[..]);
+        let now = GENESIS_TIME + 3600;
+        let fetch = |height: i64| match height {
+            HEIGHT_LATEST => Ok(blocks[19].clone()),
+            height => Ok(blocks[(height - 1) as usize].clone()),
+        };
+
+        let verifier = HostVerifier::new(Config {
+            trust_root: root(&blocks[4]),
+            options: Options::default(),
+        });
+        assert!(verifier.trusted_header().is_none());
+
+        // The verifier starts from the trust root.
+        let header = verifier.sync_at(10, fetch, now).unwrap();
+        assert_eq!(header.height, 10);
+        assert_eq!(verifier.trusted_header().unwrap().height, 10);
+        let header = verifier.sync_at(5, fetch, now).unwrap();
+        assert_eq!(header.height, 5);
+
+        let header = verifier.sync_at(HEIGHT_LATEST, fetch, now).unwrap();
+        assert_eq!(header.height, 20);
+        assert_eq!(verifier.trusted_header().unwrap().height, 20);
+        let header = verifier.sync_at(HEIGHT_LATEST, fetch, now).unwrap();
+        assert_eq!(header.height, 20);
+
+        // Trust roots not matching the light block are rejected.
+        let mut trust_root = root(&blocks[4]);
+        trust_root.hash = Hash::empty_hash();
+        let verifier = HostVerifier::new(Config {
+            trust_root,
+            options: Options::default(),
+        });
+        assert!(verifier.sync_at(10, fetch, now).is_err());
+        assert!(verifier.trusted_header().is_none());
+    }
+}
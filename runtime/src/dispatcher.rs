@@ -7,11 +7,13 @@ use std::{
         Arc, Condvar, Mutex,
     },
     thread,
+    time::Instant,
 };
 
 use anyhow::{anyhow, Result};
 use crossbeam::channel;
 use io_context::Context;
+use lazy_static::lazy_static;
 use slog::Logger;
 
 use crate::{
@@ -22,7 +24,15 @@ use crate::{
             signature::{Signature, Signer},
         },
         logger::get_logger,
-        roothash::{Block, ComputeResultsHeader, COMPUTE_RESULTS_HEADER_CONTEXT},
+        metrics::{self, Histogram, DEFAULT_BUCKETS},
+        roothash::{
+            in_messages_hash, Block, ComputeResultsHeader, IncomingMessage, MessageEvent,
+            COMPUTE_RESULTS_HEADER_CONTEXT,
+        },
+    },
+    consensus::{
+        epoch::EpochTracker,
+        verifier::{Config as VerifierConfig, HostVerifier},
     },
     enclave_rpc::{
         demux::Demux as RpcDemux,
@@ -31,26 +41,42 @@ use crate::{
         Context as RpcContext,
     },
     protocol::{Protocol, ProtocolUntrustedLocalStorage},
+    query::{self, Context as QueryContext, QueryRouter},
     rak::RAK,
     storage::{
         mkvs::{
             sync::{HostReadSyncer, NoopReadSyncer},
-            Root, Tree,
+            Root, RootType, Tree,
         },
-        StorageContext,
+        StorageContext, MKVS,
     },
     transaction::{
-        dispatcher::{Dispatcher as TxnDispatcher, NoopDispatcher as TxnNoopDispatcher},
+        dispatcher::{
+            BatchResult, Dispatcher as TxnDispatcher, NoopDispatcher as TxnNoopDispatcher,
+        },
         tree::Tree as TxnTree,
-        types::TxnBatch,
+        types::{CheckTxMetadata, TxnBatch},
         Context as TxnContext,
     },
-    types::{Body, ComputedBatch},
+    types::{Body, ComputedBatch, ProtocolVersion, RuntimeIntrospection},
 };
 
 /// Maximum amount of requests that can be in the dispatcher queue.
 const BACKLOG_SIZE: usize = 10;
 
+lazy_static! {
+    static ref EXECUTE_BATCH_LATENCY: Arc<Histogram> = metrics::registry().histogram(
+        "oasis_runtime_execute_batch_latency_seconds",
+        "Time taken to execute a transaction batch.",
+        DEFAULT_BUCKETS,
+    );
+    static ref CHECK_BATCH_LATENCY: Arc<Histogram> = metrics::registry().histogram(
+        "oasis_runtime_check_batch_latency_seconds",
+        "Time taken to check a transaction batch.",
+        DEFAULT_BUCKETS,
+    );
+}
+
 /// Interface for dispatcher initializers.
 pub trait Initializer: Send + Sync {
     /// Initializes the dispatcher(s).
@@ -60,6 +86,7 @@ pub trait Initializer: Send + Sync {
         rak: &Arc<RAK>,
         rpc_demux: &mut RpcDemux,
         rpc_dispatcher: &mut RpcDispatcher,
+        query_router: &mut QueryRouter,
     ) -> Option<Box<dyn TxnDispatcher>>;
 }
 
@@ -70,6 +97,7 @@ where
             &Arc<RAK>,
             &mut RpcDemux,
             &mut RpcDispatcher,
+            &mut QueryRouter,
         ) -> Option<Box<dyn TxnDispatcher>>
         + Send
         + Sync,
@@ -80,8 +108,9 @@ where
         rak: &Arc<RAK>,
         rpc_demux: &mut RpcDemux,
         rpc_dispatcher: &mut RpcDispatcher,
+        query_router: &mut QueryRouter,
     ) -> Option<Box<dyn TxnDispatcher>> {
-        (*self)(protocol, rak, rpc_demux, rpc_dispatcher)
+        (*self)(protocol, rak, rpc_demux, rpc_dispatcher, query_router)
     }
 }
 
@@ -112,11 +141,17 @@ pub struct Dispatcher {
     protocol_cond: Condvar,
     rak: Arc<RAK>,
     abort_batch: Arc<AtomicBool>,
+    epoch_tracker: Arc<EpochTracker>,
+    consensus_verifier: Option<Arc<HostVerifier>>,
 }
 
 impl Dispatcher {
     /// Create a new runtime call dispatcher.
-    pub fn new(initializer: Box<dyn Initializer>, rak: Arc<RAK>) -> Arc<Self> {
+    pub fn new(
+        initializer: Box<dyn Initializer>,
+        rak: Arc<RAK>,
+        consensus_verifier: Option<VerifierConfig>,
+    ) -> Arc<Self> {
         let (tx, rx) = channel::bounded(BACKLOG_SIZE);
         let (abort_tx, abort_rx) = channel::bounded(1);
 
@@ -129,6 +164,9 @@ impl Dispatcher {
             protocol_cond: Condvar::new(),
             rak,
             abort_batch: Arc::new(AtomicBool::new(false)),
+            epoch_tracker: Arc::new(EpochTracker::new()),
+            consensus_verifier: consensus_verifier
+                .map(|config| Arc::new(HostVerifier::new(config))),
         });
 
         let d = dispatcher.clone();
@@ -147,6 +185,16 @@ impl Dispatcher {
         self.protocol_cond.notify_one();
     }
 
+    /// Return the tracker of the current consensus epoch.
+    pub fn epoch_tracker(&self) -> Arc<EpochTracker> {
+        self.epoch_tracker.clone()
+    }
+
+    /// Return the consensus light client verifier, if configured.
+    pub fn consensus_verifier(&self) -> Option<Arc<HostVerifier>> {
+        self.consensus_verifier.clone()
+    }
+
     /// Queue a new request to be dispatched.
     pub fn queue_request(&self, ctx: Context, id: u64, body: Body) -> Result<()> {
         self.queue_tx.try_send((ctx, id, body))?;
@@ -183,9 +231,14 @@ impl Dispatcher {
         info!(self.logger, "Starting the runtime dispatcher");
         let mut rpc_demux = RpcDemux::new(self.rak.clone());
         let mut rpc_dispatcher = RpcDispatcher::new();
-        let mut txn_dispatcher: Box<dyn TxnDispatcher> = if let Some(txn) =
-            initializer.init(&protocol, &self.rak, &mut rpc_demux, &mut rpc_dispatcher)
-        {
+        let mut query_router = QueryRouter::new();
+        let mut txn_dispatcher: Box<dyn TxnDispatcher> = if let Some(txn) = initializer.init(
+            &protocol,
+            &self.rak,
+            &mut rpc_demux,
+            &mut rpc_dispatcher,
+            &mut query_router,
+        ) {
             txn
         } else {
             Box::new(TxnNoopDispatcher::new())
@@ -193,9 +246,10 @@ impl Dispatcher {
         txn_dispatcher.set_abort_batch_flag(self.abort_batch.clone());
 
         // Create common MKVS to use as a cache as long as the root stays the same. Use separate
-        // caches for executing and checking transactions.
+        // caches for executing and checking transactions, and for queries.
         let mut cache = Cache::new(protocol.clone());
         let mut cache_check = Cache::new(protocol.clone());
+        let mut cache_query = Cache::new(protocol.clone());
 
         'dispatch: loop {
             // Check if abort was requested and if so, signal that the batch
@@ -230,9 +284,13 @@ impl Dispatcher {
                         io_root,
                         inputs,
                         block,
+                        beacon,
+                        message_results,
+                        in_msgs,
                     },
                 )) => {
                     // Transaction execution.
+                    let start = Instant::now();
                     self.dispatch_txn(
                         &mut cache,
                         &mut txn_dispatcher,
@@ -242,11 +300,16 @@ impl Dispatcher {
                         io_root,
                         inputs,
                         block,
+                        beacon,
+                        message_results,
+                        in_msgs,
                         false,
                     );
+                    EXECUTE_BATCH_LATENCY.observe(start.elapsed().as_secs_f64());
                 }
                 Ok((ctx, id, Body::RuntimeCheckTxBatchRequest { inputs, block })) => {
                     // Transaction check.
+                    let start = Instant::now();
                     self.dispatch_txn(
                         &mut cache_check,
                         &mut txn_dispatcher,
@@ -256,8 +319,37 @@ impl Dispatcher {
                         Hash::default(),
                         inputs,
                         block,
+                        Vec::new(),
+                        Vec::new(),
+                        Vec::new(),
                         true,
                     );
+                    CHECK_BATCH_LATENCY.observe(start.elapsed().as_secs_f64());
+                }
+                Ok((
+                    ctx,
+                    id,
+                    Body::RuntimeQueryRequest {
+                        method,
+                        args,
+                        block,
+                    },
+                )) => {
+                    // Query.
+                    self.dispatch_query(
+                        &mut cache_query,
+                        &query_router,
+                        &protocol,
+                        ctx,
+                        id,
+                        method,
+                        args,
+                        block,
+                    );
+                }
+                Ok((_ctx, id, Body::RuntimeIntrospectionRequest {})) => {
+                    // Introspection.
+                    self.dispatch_introspection(&*txn_dispatcher, &query_router, &protocol, id);
                 }
                 Ok((ctx, id, Body::RuntimeKeyManagerPolicyUpdateRequest { signed_policy_raw })) => {
                     // KeyManager policy update local RPC call.
@@ -300,6 +392,9 @@ impl Dispatcher {
         io_root: Hash,
         mut inputs: TxnBatch,
         block: Block,
+        beacon: Vec<u8>,
+        message_results: Vec<MessageEvent>,
+        in_msgs: Vec<IncomingMessage>,
         check_only: bool,
     ) {
         debug!(self.logger, "Received transaction batch request";
@@ -313,14 +408,21 @@ impl Dispatcher {
         cache.maybe_replace(Root {
             namespace: block.header.namespace,
             version: block.header.round,
+            root_type: RootType::State,
             hash: block.header.state_root,
+            ..Default::default()
         });
 
         let untrusted_local = Arc::new(ProtocolUntrustedLocalStorage::new(
             Context::create_child(&ctx),
             protocol.clone(),
         ));
-        let txn_ctx = TxnContext::new(ctx.clone(), &block.header, check_only);
+        let mut txn_ctx = TxnContext::new(ctx.clone(), &block.header, check_only);
+        if !beacon.is_empty() {
+            txn_ctx.set_beacon(&beacon);
+        }
+        txn_ctx.set_message_results(message_results);
+        txn_ctx.set_incoming_messages(in_msgs.clone());
         match StorageContext::enter(&mut cache.mkvs, untrusted_local.clone(), || {
             txn_dispatcher.dispatch_batch(&inputs, txn_ctx)
         }) {
@@ -337,13 +439,38 @@ impl Dispatcher {
                     )
                     .unwrap();
             }
-            Ok((mut outputs, mut tags, messages)) => {
+            Ok(BatchResult {
+                mut outputs,
+                mut tags,
+                mut events,
+                messages,
+                in_msgs_count,
+                stats,
+            }) => {
                 if check_only {
                     debug!(self.logger, "Transaction batch check complete");
 
+                    // Older hosts do not know about transaction metadata.
+                    let meta = if protocol.get_protocol_version() >= ProtocolVersion::V1_5 {
+                        Some(
+                            outputs
+                                .iter()
+                                .map(|output| CheckTxMetadata::from_output(output))
+                                .collect(),
+                        )
+                    } else {
+                        None
+                    };
+
                     // Send the result back.
                     protocol
-                        .send_response(id, Body::RuntimeCheckTxBatchResponse { results: outputs })
+                        .send_response(
+                            id,
+                            Body::RuntimeCheckTxBatchResponse {
+                                results: outputs,
+                                meta,
+                            },
+                        )
                         .unwrap();
                 } else {
                     // Finalize state.
@@ -366,7 +493,9 @@ impl Dispatcher {
                         Root {
                             namespace: block.header.namespace,
                             version: block.header.round + 1,
+                            root_type: RootType::IO,
                             hash: Hash::empty_hash(),
+                            ..Default::default()
                         },
                     );
                     let mut hashes = Vec::new();
@@ -391,12 +520,18 @@ impl Dispatcher {
                 );
                     }
 
-                    for (tx_hash, (output, tags)) in
-                        hashes.drain(..).zip(outputs.drain(..).zip(tags.drain(..)))
+                    // Dispatchers are not required to return events.
+                    events.resize_with(hashes.len(), Default::default);
+                    for (tx_hash, ((output, tags), events)) in hashes
+                        .drain(..)
+                        .zip(outputs.drain(..).zip(tags.drain(..)).zip(events.drain(..)))
                     {
                         txn_tree
                             .add_output(Context::create_child(&ctx), tx_hash, output, tags)
                             .expect("add transaction must succeed");
+                        txn_tree
+                            .add_events(Context::create_child(&ctx), tx_hash, events)
+                            .expect("add events must succeed");
                     }
 
                     let (io_write_log, io_root) = txn_tree
@@ -409,6 +544,11 @@ impl Dispatcher {
                         io_root: Some(io_root),
                         state_root: Some(new_state_root),
                         messages,
+                        in_msgs_hash: match in_msgs_count {
+                            0 => None,
+                            count => Some(in_messages_hash(&in_msgs[..count as usize])),
+                        },
+                        in_msgs_count,
                     };
 
                     debug!(self.logger, "Transaction batch execution complete";
@@ -425,11 +565,26 @@ impl Dispatcher {
                         Signature::default()
                     };
 
+                    debug!(self.logger, "Transaction batch scheduling statistics";
+                        "partitions" => stats.partitions,
+                        "max_partition_size" => stats.max_partition_size,
+                        "reordered" => stats.reordered,
+                    );
+
+                    // Older hosts do not know about scheduling statistics.
+                    let protocol_version = protocol.get_protocol_version();
+                    let schedule_stats = if protocol_version >= ProtocolVersion::V1_4 {
+                        Some(stats)
+                    } else {
+                        None
+                    };
+
                     let result = ComputedBatch {
                         header,
                         io_write_log,
                         state_write_log,
                         rak_sig,
+                        schedule_stats,
                     };
 
                     // Send the result back.
@@ -507,10 +662,15 @@ impl Dispatcher {
                         Context::create_child(&ctx),
                         protocol.clone(),
                     ));
-                    let rpc_ctx = RpcContext::new(ctx.clone(), self.rak.clone(), session_info);
-                    let response =
+                    let rpc_ctx = RpcContext::new(
+                        ctx.clone(),
+                        self.rak.clone(),
+                        session_info,
+                        self.epoch_tracker.clone(),
+                    );
+                    let (response, stream) =
                         StorageContext::enter(&mut mkvs, untrusted_local.clone(), || {
-                            rpc_dispatcher.dispatch(req, rpc_ctx)
+                            rpc_dispatcher.dispatch_streaming(req, rpc_ctx)
                         });
                     let response = RpcMessage::Response(response);
 
@@ -518,8 +678,12 @@ impl Dispatcher {
 
                     debug!(self.logger, "RPC call dispatch complete");
 
+                    // Any previous response stream of the session is replaced.
                     let mut buffer = vec![];
-                    match rpc_demux.write_message(session_id, response, &mut buffer) {
+                    match rpc_demux
+                        .set_stream(session_id, stream)
+                        .and_then(|_| rpc_demux.write_message(session_id, response, &mut buffer))
+                    {
                         Ok(_) => {
                             // Transmit response.
                             protocol_response = Body::RuntimeRPCCallResponse { response: buffer };
@@ -534,6 +698,41 @@ impl Dispatcher {
                         }
                     }
                 }
+                RpcMessage::StreamNext { seq } => {
+                    // Next chunk of the response stream, which is produced in
+                    // a storage context like the request itself.
+                    let ctx = ctx.freeze();
+                    let mut mkvs = Tree::make().new(Box::new(NoopReadSyncer));
+                    let untrusted_local = Arc::new(ProtocolUntrustedLocalStorage::new(
+                        Context::create_child(&ctx),
+                        protocol.clone(),
+                    ));
+                    let mut buffer = vec![];
+                    let result = StorageContext::enter(&mut mkvs, untrusted_local.clone(), || {
+                        rpc_demux.next_stream_response(session_id, seq)
+                    })
+                    .and_then(|response| {
+                        rpc_demux.write_message(
+                            session_id,
+                            RpcMessage::Response(response),
+                            &mut buffer,
+                        )
+                    });
+                    match result {
+                        Ok(_) => {
+                            // Transmit response.
+                            protocol_response = Body::RuntimeRPCCallResponse { response: buffer };
+                        }
+                        Err(error) => {
+                            error!(self.logger, "Error while streaming response"; "err" => %error);
+                            protocol_response = Body::Error {
+                                module: "".to_owned(), // XXX: Error codes.
+                                code: 0,               // XXX: Error codes.
+                                message: format!("{}", error),
+                            };
+                        }
+                    }
+                }
                 RpcMessage::Close => {
                     // Session close.
                     let mut buffer = vec![];
@@ -588,7 +787,12 @@ impl Dispatcher {
             Context::create_child(&ctx),
             protocol.clone(),
         ));
-        let rpc_ctx = RpcContext::new(ctx.clone(), self.rak.clone(), None);
+        let rpc_ctx = RpcContext::new(
+            ctx.clone(),
+            self.rak.clone(),
+            None,
+            self.epoch_tracker.clone(),
+        );
         let response = StorageContext::enter(&mut mkvs, untrusted_local.clone(), || {
             rpc_dispatcher.dispatch_local(req, rpc_ctx)
         });
@@ -604,6 +808,84 @@ impl Dispatcher {
         protocol.send_response(id, protocol_response).unwrap();
     }
 
+    fn dispatch_query(
+        &self,
+        cache: &mut Cache,
+        query_router: &QueryRouter,
+        protocol: &Arc<Protocol>,
+        ctx: Context,
+        id: u64,
+        method: String,
+        args: cbor::Value,
+        block: Block,
+    ) {
+        debug!(self.logger, "Received query request";
+            "method" => &method,
+            "state_root" => ?block.header.state_root,
+            "round" => block.header.round,
+        );
+
+        // Query the state at the given block.
+        let ctx = ctx.freeze();
+        cache.maybe_replace(Root {
+            namespace: block.header.namespace,
+            version: block.header.round,
+            root_type: RootType::State,
+            hash: block.header.state_root,
+            ..Default::default()
+        });
+        let query_ctx = QueryContext {
+            io_ctx: ctx,
+            header: &block.header,
+            state: &*cache.mkvs,
+            consensus: self
+                .consensus_verifier
+                .as_deref()
+                .map(|verifier| (verifier, protocol)),
+        };
+        let response = match query_router.dispatch(&method, args, &query_ctx) {
+            Ok(data) => {
+                debug!(self.logger, "Query dispatch complete");
+                Body::RuntimeQueryResponse { data }
+            }
+            Err(error) => {
+                debug!(self.logger, "Query failed"; "err" => %error);
+                Body::Error {
+                    module: query::MODULE_NAME.to_owned(),
+                    code: error.code(),
+                    message: format!("{}", error),
+                }
+            }
+        };
+
+        protocol.send_response(id, response).unwrap();
+    }
+
+    fn dispatch_introspection(
+        &self,
+        txn_dispatcher: &dyn TxnDispatcher,
+        query_router: &QueryRouter,
+        protocol: &Arc<Protocol>,
+        id: u64,
+    ) {
+        debug!(self.logger, "Received introspection request");
+
+        #[allow(unused_mut)]
+        let mut features = txn_dispatcher.features();
+        #[cfg(target_env = "sgx")]
+        features.push("sgx".to_owned());
+
+        let info = RuntimeIntrospection {
+            methods: txn_dispatcher.methods(),
+            queries: query_router.methods(),
+            features,
+        };
+
+        protocol
+            .send_response(id, Body::RuntimeIntrospectionResponse { info })
+            .unwrap();
+    }
+
     fn handle_km_policy_update(
         &self,
         rpc_dispatcher: &mut RpcDispatcher,
@@ -624,7 +906,7 @@ impl Dispatcher {
 
 struct Cache {
     protocol: Arc<Protocol>,
-    mkvs: Tree,
+    mkvs: Box<dyn MKVS>,
     root: Root,
 }
 
@@ -637,12 +919,15 @@ impl Cache {
         }
     }
 
-    fn new_tree(protocol: &Arc<Protocol>, root: Root) -> Tree {
+    fn new_tree(protocol: &Arc<Protocol>, root: Root) -> Box<dyn MKVS> {
         let read_syncer = HostReadSyncer::new(protocol.clone());
-        Tree::make()
-            .with_capacity(100_000, 10_000_000)
-            .with_root(root)
-            .new(Box::new(read_syncer))
+        Box::new(
+            Tree::make()
+                .with_capacity(100_000, 10_000_000)
+                .with_root_type(RootType::State)
+                .with_root(root)
+                .new(Box::new(read_syncer)),
+        )
     }
 
     fn maybe_replace(&mut self, root: Root) {
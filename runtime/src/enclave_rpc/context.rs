@@ -4,7 +4,10 @@ use std::{any::Any, sync::Arc};
 use io_context::Context as IoContext;
 
 use super::session::SessionInfo;
-use crate::rak::RAK;
+use crate::{
+    consensus::epoch::{EpochInfo, EpochTime, EpochTracker},
+    rak::RAK,
+};
 
 struct NoRuntimeContext;
 
@@ -16,6 +19,8 @@ pub struct Context {
     pub rak: Arc<RAK>,
     /// Information about the session the RPC call was delivered over.
     pub session_info: Option<Arc<SessionInfo>>,
+    /// Tracker of the current consensus epoch.
+    pub epoch_tracker: Arc<EpochTracker>,
     /// Runtime-specific context.
     pub runtime: Box<dyn Any>,
 }
@@ -26,12 +31,29 @@ impl Context {
         io_ctx: Arc<IoContext>,
         rak: Arc<RAK>,
         session_info: Option<Arc<SessionInfo>>,
+        epoch_tracker: Arc<EpochTracker>,
     ) -> Self {
         Self {
             io_ctx,
             rak,
             session_info,
+            epoch_tracker,
             runtime: Box::new(NoRuntimeContext),
         }
     }
+
+    /// Return the current consensus epoch, its start height and beacon, if
+    /// known.
+    pub fn current_epoch(&self) -> Option<EpochInfo> {
+        self.epoch_tracker.current()
+    }
+
+    /// Block until the consensus epoch is at least the given epoch and return
+    /// the current epoch.
+    ///
+    /// This blocks the RPC dispatcher, so it should only be used for epochs
+    /// which are about to start.
+    pub fn wait_epoch(&self, epoch: EpochTime) -> EpochInfo {
+        self.epoch_tracker.wait_epoch(epoch)
+    }
 }
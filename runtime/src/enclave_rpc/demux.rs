@@ -2,14 +2,20 @@
 use std::{collections::HashMap, io::Write, sync::Arc, time::SystemTime};
 
 use anyhow::Result;
+use lazy_static::lazy_static;
 use thiserror::Error;
 
 use super::{
     session::{Builder, Session, SessionInfo},
-    types::{Frame, Message, SessionID},
+    stream::{ResponseStream, StreamError},
+    types::{Body, Frame, Message, Response, SessionID},
 };
 use crate::{
-    common::{cbor, time::insecure_posix_system_time},
+    common::{
+        cbor,
+        metrics::{self, Gauge},
+        time::insecure_posix_system_time,
+    },
     rak::RAK,
 };
 
@@ -22,6 +28,13 @@ const DEFAULT_STALE_SESSION_TIMEOUT_SECS: u64 = 60;
 /// STALE_SESSIONS_CHECK_TIMEOUT_SECS seconds between checks.
 const STALE_SESSIONS_CHECK_TIMEOUT_SECS: u64 = 10;
 
+lazy_static! {
+    static ref SESSIONS: Arc<Gauge> = metrics::registry().gauge(
+        "oasis_runtime_rpc_sessions",
+        "Number of open EnclaveRPC sessions.",
+    );
+}
+
 /// Demux error.
 #[derive(Error, Debug)]
 enum DemuxError {
@@ -45,6 +58,8 @@ pub struct Demux {
 struct EnrichedSession {
     session: Session,
     last_process_frame_time: SystemTime,
+    /// Response stream of the last request (if any).
+    stream: Option<ResponseStream>,
 }
 
 impl Demux {
@@ -82,10 +97,15 @@ impl Demux {
                     .as_secs()
                     < stale_session_timeout
             });
+            self.update_session_count();
         }
         self.last_stale_sessions_purge = now;
     }
 
+    fn update_session_count(&self) {
+        SESSIONS.set(self.sessions.len() as i64);
+    }
+
     /// Process an incoming frame.
     pub fn process_frame<W: Write>(
         &mut self,
@@ -117,6 +137,7 @@ impl Demux {
                 // In case there is an error, drop the session.
                 Err(error) => {
                     self.sessions.remove(&id);
+                    self.update_session_count();
                     Err(error)
                 }
             }
@@ -150,8 +171,10 @@ impl Demux {
                     EnrichedSession {
                         session: session,
                         last_process_frame_time: insecure_posix_system_time(),
+                        stream: None,
                     },
                 );
+                self.update_session_count();
 
                 Ok(result)
             } else {
@@ -178,9 +201,36 @@ impl Demux {
         }
     }
 
+    /// Set the response stream of the session, replacing any previous one.
+    pub fn set_stream(&mut self, id: SessionID, stream: Option<ResponseStream>) -> Result<()> {
+        match self.sessions.get_mut(&id) {
+            Some(enriched_session) => {
+                enriched_session.stream = stream;
+                Ok(())
+            }
+            None => Err(DemuxError::SessionNotFound { session: id }.into()),
+        }
+    }
+
+    /// Produce the response carrying the chunk with the given sequence number
+    /// of the session's response stream.
+    pub fn next_stream_response(&mut self, id: SessionID, seq: u64) -> Result<Response> {
+        match self.sessions.get_mut(&id) {
+            Some(enriched_session) => match enriched_session.stream {
+                Some(ref mut stream) => Ok(stream.next_response(seq)),
+                None => Ok(Response {
+                    body: Body::Error(format!("{}", StreamError::NoActiveStream)),
+                }),
+            },
+            None => Err(DemuxError::SessionNotFound { session: id }.into()),
+        }
+    }
+
     /// Close the session and generate a response.
     pub fn close<W: Write>(&mut self, id: SessionID, mut writer: W) -> Result<()> {
-        match self.sessions.remove(&id) {
+        let session = self.sessions.remove(&id);
+        self.update_session_count();
+        match session {
             Some(mut enriched_session) => {
                 // Responses don't need framing as they are linked at the
                 // runtime IPC protocol.
@@ -1,5 +1,5 @@
 //! RPC dispatcher.
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
 use serde::{de::DeserializeOwned, Serialize};
@@ -7,6 +7,8 @@ use thiserror::Error;
 
 use super::{
     context::Context,
+    policy::AccessControl,
+    stream::{ResponseStream, StreamItems},
     types::{Body, Request, Response},
 };
 use crate::common::cbor;
@@ -16,6 +18,8 @@ use crate::common::cbor;
 enum DispatchError {
     #[error("method not found: {method:?}")]
     MethodNotFound { method: String },
+    #[error("streaming method called without a session: {method:?}")]
+    StreamingUnsupported { method: String },
 }
 
 /// Custom context initializer.
@@ -57,6 +61,26 @@ where
     }
 }
 
+/// Handler for a streaming RPC method.
+pub trait StreamingMethodHandler<Rq, Item> {
+    /// Invoke the method implementation and return the items of the
+    /// response stream.
+    ///
+    /// The items are produced lazily as they are requested by the client.
+    fn handle(&self, request: &Rq, ctx: &mut Context) -> Result<StreamItems<Item>>;
+}
+
+impl<Rq, Item, F> StreamingMethodHandler<Rq, Item> for F
+where
+    Rq: 'static,
+    Item: 'static,
+    F: Fn(&Rq, &mut Context) -> Result<StreamItems<Item>> + 'static,
+{
+    fn handle(&self, request: &Rq, ctx: &mut Context) -> Result<StreamItems<Item>> {
+        (*self)(&request, ctx)
+    }
+}
+
 /// Dispatcher for a RPC method.
 pub trait MethodHandlerDispatch {
     /// Get method descriptor.
@@ -64,6 +88,16 @@ pub trait MethodHandlerDispatch {
 
     /// Dispatch request.
     fn dispatch(&self, request: Request, ctx: &mut Context) -> Result<Response>;
+
+    /// Dispatch request, also returning the response stream in case of a
+    /// streaming method.
+    fn dispatch_streaming(
+        &self,
+        request: Request,
+        ctx: &mut Context,
+    ) -> Result<(Response, Option<ResponseStream>)> {
+        Ok((self.dispatch(request, ctx)?, None))
+    }
 }
 
 struct MethodHandlerDispatchImpl<Rq, Rsp> {
@@ -92,6 +126,42 @@ where
     }
 }
 
+struct StreamingMethodHandlerDispatchImpl<Rq, Item> {
+    /// Method descriptor.
+    descriptor: MethodDescriptor,
+    /// Method handler.
+    handler: Box<dyn StreamingMethodHandler<Rq, Item>>,
+}
+
+impl<Rq, Item> MethodHandlerDispatch for StreamingMethodHandlerDispatchImpl<Rq, Item>
+where
+    Rq: DeserializeOwned + 'static,
+    Item: Serialize + 'static,
+{
+    fn get_descriptor(&self) -> &MethodDescriptor {
+        &self.descriptor
+    }
+
+    fn dispatch(&self, request: Request, _ctx: &mut Context) -> Result<Response> {
+        Err(DispatchError::StreamingUnsupported {
+            method: request.method,
+        }
+        .into())
+    }
+
+    fn dispatch_streaming(
+        &self,
+        request: Request,
+        ctx: &mut Context,
+    ) -> Result<(Response, Option<ResponseStream>)> {
+        let request = cbor::from_value(request.args)?;
+        let items = self.handler.handle(&request, ctx)?;
+        let mut stream = ResponseStream::new(Box::new(items.map(|item| item.map(cbor::to_value))));
+
+        Ok((stream.next_response(0), Some(stream)))
+    }
+}
+
 /// RPC method dispatcher implementation.
 pub struct Method {
     /// Method dispatcher.
@@ -114,6 +184,24 @@ impl Method {
         }
     }
 
+    /// Create a new enclave streaming method descriptor.
+    ///
+    /// Streaming methods can only be called over a session (see
+    /// `Dispatcher::dispatch_streaming`).
+    pub fn new_streaming<Rq, Item, Handler>(method: MethodDescriptor, handler: Handler) -> Self
+    where
+        Rq: DeserializeOwned + 'static,
+        Item: Serialize + 'static,
+        Handler: StreamingMethodHandler<Rq, Item> + 'static,
+    {
+        Method {
+            dispatcher: Box::new(StreamingMethodHandlerDispatchImpl {
+                descriptor: method,
+                handler: Box::new(handler),
+            }),
+        }
+    }
+
     /// Return method name.
     pub fn get_name(&self) -> &String {
         &self.dispatcher.get_descriptor().name
@@ -123,6 +211,16 @@ impl Method {
     pub fn dispatch(&self, request: Request, ctx: &mut Context) -> Result<Response> {
         self.dispatcher.dispatch(request, ctx)
     }
+
+    /// Dispatch a request, also returning the response stream in case of a
+    /// streaming method.
+    pub fn dispatch_streaming(
+        &self,
+        request: Request,
+        ctx: &mut Context,
+    ) -> Result<(Response, Option<ResponseStream>)> {
+        self.dispatcher.dispatch_streaming(request, ctx)
+    }
 }
 
 /// Key manager policy update handler callback.
@@ -138,6 +236,8 @@ pub struct Dispatcher {
     km_policy_handler: Option<Box<KeyManagerPolicyHandler>>,
     /// Registered context initializer.
     ctx_initializer: Option<Box<dyn ContextInitializer>>,
+    /// Access control for (non-local) RPC methods.
+    access_control: Option<Arc<AccessControl>>,
 }
 
 impl Dispatcher {
@@ -148,6 +248,7 @@ impl Dispatcher {
             local_methods: HashMap::new(),
             km_policy_handler: None,
             ctx_initializer: None,
+            access_control: None,
         }
     }
 
@@ -167,26 +268,61 @@ impl Dispatcher {
         self.ctx_initializer = Some(Box::new(initializer));
     }
 
+    /// Configure access control for (non-local) RPC methods.
+    ///
+    /// Calls are dispatched only if they are allowed by the current policy
+    /// of the access control, which can be updated while the dispatcher is
+    /// in use. If left unspecified, all calls are allowed.
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
     /// Dispatch request.
     pub fn dispatch(&self, request: Request, mut ctx: Context) -> Response {
         if let Some(ref ctx_init) = self.ctx_initializer {
             ctx_init.init(&mut ctx);
         }
 
-        match self.dispatch_fallible(request, &mut ctx, false) {
-            Ok(response) => response,
+        match self.dispatch_fallible(request, &mut ctx, false, false) {
+            Ok((response, _)) => response,
             Err(error) => Response {
                 body: Body::Error(format!("{}", error)),
             },
         }
     }
 
+    /// Dispatch request, also returning the response stream in case of a
+    /// streaming method.
+    ///
+    /// The response carries the first chunk of the stream, further chunks
+    /// should be produced from the returned stream as they are requested.
+    pub fn dispatch_streaming(
+        &self,
+        request: Request,
+        mut ctx: Context,
+    ) -> (Response, Option<ResponseStream>) {
+        if let Some(ref ctx_init) = self.ctx_initializer {
+            ctx_init.init(&mut ctx);
+        }
+
+        match self.dispatch_fallible(request, &mut ctx, false, true) {
+            Ok(result) => result,
+            Err(error) => (
+                Response {
+                    body: Body::Error(format!("{}", error)),
+                },
+                None,
+            ),
+        }
+    }
+
     fn dispatch_fallible(
         &self,
         request: Request,
         ctx: &mut Context,
         is_local: bool,
-    ) -> Result<Response> {
+        is_streaming: bool,
+    ) -> Result<(Response, Option<ResponseStream>)> {
         let vtbl = match is_local {
             false => &self.methods,
             true => &self.local_methods,
@@ -196,8 +332,15 @@ impl Dispatcher {
             ctx_init.init(ctx);
         }
 
+        if !is_local {
+            if let Some(ref access_control) = self.access_control {
+                access_control.check(&request, ctx)?;
+            }
+        }
+
         match vtbl.get(&request.method) {
-            Some(dispatcher) => dispatcher.dispatch(request, ctx),
+            Some(dispatcher) if is_streaming => dispatcher.dispatch_streaming(request, ctx),
+            Some(dispatcher) => Ok((dispatcher.dispatch(request, ctx)?, None)),
             None => Err(DispatchError::MethodNotFound {
                 method: request.method,
             }
@@ -211,8 +354,8 @@ impl Dispatcher {
             ctx_init.init(&mut ctx);
         }
 
-        match self.dispatch_fallible(request, &mut ctx, true) {
-            Ok(response) => response,
+        match self.dispatch_fallible(request, &mut ctx, true, false) {
+            Ok((response, _)) => response,
             Err(error) => Response {
                 body: Body::Error(format!("{}", error)),
             },
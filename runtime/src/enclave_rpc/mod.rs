@@ -4,7 +4,9 @@ pub mod context;
 pub mod demux;
 pub mod dispatcher;
 pub mod macros;
+pub mod policy;
 pub mod session;
+pub mod stream;
 pub mod types;
 
 // Re-exports.
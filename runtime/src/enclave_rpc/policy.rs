@@ -0,0 +1,412 @@
+//! Per-method access control for RPC calls.
+//!
+//! An access policy maps caller enclave identities (MRENCLAVE/MRSIGNER) and
+//! the runtimes on whose behalf they call to the sets of methods they may
+//! call. Policies are signed by a set of trusted signers and can be updated
+//! at any time without restarting the enclave. Once access control is
+//! configured on a dispatcher, all calls not explicitly allowed by the
+//! current policy are denied.
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::RwLock,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{context::Context, types::Request};
+use crate::common::{
+    cbor,
+    crypto::signature::{PublicKey, SignatureBundle},
+    runtime::RuntimeId,
+    sgx::avr::{EnclaveIdentity, MrEnclave, MrSigner},
+};
+
+/// Signature context used for signing access policies.
+pub const POLICY_SIGNATURE_CONTEXT: &[u8] = b"oasis-core/enclave-rpc: access policy";
+
+/// Method name matching all methods.
+pub const ANY_METHOD: &str = "*";
+
+/// Access control errors.
+#[derive(Error, Debug)]
+pub enum PolicyError {
+    #[error("access denied to method {method:?}")]
+    AccessDenied { method: String },
+    #[error("policy is for a different runtime")]
+    InvalidRuntime,
+    #[error("policy failed signature verification")]
+    InvalidSignature,
+    #[error("policy has insufficient signatures")]
+    InsufficientSignatures,
+    #[error("policy rollback (serial {new} older than {current})")]
+    Rollback { current: u32, new: u32 },
+    #[error("policy alteration without serial increment")]
+    Changed,
+}
+
+/// An access control rule.
+///
+/// A rule matches a call if all of its (set) conditions match.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccessRule {
+    /// MRENCLAVE of the caller (any if not set).
+    #[serde(default)]
+    pub mr_enclave: Option<MrEnclave>,
+    /// MRSIGNER of the caller (any if not set).
+    #[serde(default)]
+    pub mr_signer: Option<MrSigner>,
+    /// Runtimes on whose behalf the caller may call (any if not set).
+    #[serde(default)]
+    pub runtime_ids: Option<BTreeSet<RuntimeId>>,
+    /// Methods which may be called, where `ANY_METHOD` allows all methods.
+    pub methods: BTreeSet<String>,
+}
+
+impl AccessRule {
+    fn allows(
+        &self,
+        method: &str,
+        caller: Option<&EnclaveIdentity>,
+        runtime_id: Option<&RuntimeId>,
+    ) -> bool {
+        if let Some(ref mr_enclave) = self.mr_enclave {
+            if caller.map(|caller| &caller.mr_enclave) != Some(mr_enclave) {
+                return false;
+            }
+        }
+        if let Some(ref mr_signer) = self.mr_signer {
+            if caller.map(|caller| &caller.mr_signer) != Some(mr_signer) {
+                return false;
+            }
+        }
+        if let Some(ref runtime_ids) = self.runtime_ids {
+            match runtime_id {
+                Some(runtime_id) if runtime_ids.contains(runtime_id) => {}
+                _ => return false,
+            }
+        }
+
+        self.methods.contains(method) || self.methods.contains(ANY_METHOD)
+    }
+}
+
+/// An access policy.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccessPolicy {
+    /// Serial number of the policy, which must increase with every update.
+    pub serial: u32,
+    /// Runtime the policy applies to.
+    pub id: RuntimeId,
+    /// Rules, any of which may allow a call.
+    pub rules: Vec<AccessRule>,
+}
+
+impl AccessPolicy {
+    /// Check whether the given caller may call the given method on behalf
+    /// of the given runtime.
+    pub fn allows(
+        &self,
+        method: &str,
+        caller: Option<&EnclaveIdentity>,
+        runtime_id: Option<&RuntimeId>,
+    ) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.allows(method, caller, runtime_id))
+    }
+}
+
+/// A signed access policy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedAccessPolicy {
+    pub policy: AccessPolicy,
+    pub signatures: Vec<SignatureBundle>,
+}
+
+impl SignedAccessPolicy {
+    /// Verify the signatures and return the policy if enough of them were
+    /// made by the given trusted signers.
+    pub fn verify(&self, signers: &HashSet<PublicKey>, threshold: usize) -> Result<&AccessPolicy> {
+        let raw_policy = cbor::to_vec(&self.policy);
+        let mut trusted = HashSet::new();
+        for bundle in &self.signatures {
+            let public_key = bundle
+                .public_key
+                .as_ref()
+                .ok_or(PolicyError::InvalidSignature)?;
+            bundle
+                .signature
+                .verify(public_key, POLICY_SIGNATURE_CONTEXT, &raw_policy)
+                .map_err(|_| PolicyError::InvalidSignature)?;
+            if signers.contains(public_key) {
+                trusted.insert(public_key);
+            }
+        }
+        if trusted.len() < threshold {
+            return Err(PolicyError::InsufficientSignatures.into());
+        }
+
+        Ok(&self.policy)
+    }
+}
+
+/// Resolver of the runtime on whose behalf a call is made.
+pub trait RuntimeResolver: Send + Sync {
+    /// Return the runtime on whose behalf the call is made, if known.
+    fn resolve(&self, request: &Request, ctx: &Context) -> Option<RuntimeId>;
+}
+
+impl<F> RuntimeResolver for F
+where
+    F: Fn(&Request, &Context) -> Option<RuntimeId> + Send + Sync,
+{
+    fn resolve(&self, request: &Request, ctx: &Context) -> Option<RuntimeId> {
+        (*self)(request, ctx)
+    }
+}
+
+/// Access control by a (reloadable) signed access policy.
+pub struct AccessControl {
+    runtime_id: RuntimeId,
+    signers: HashSet<PublicKey>,
+    threshold: usize,
+    runtime_resolver: Option<Box<dyn RuntimeResolver>>,
+    policy: RwLock<Option<AccessPolicy>>,
+}
+
+impl AccessControl {
+    /// Create a new access control for the given runtime, accepting policies
+    /// signed by at least `threshold` of the given signers.
+    ///
+    /// Until a policy is loaded, all calls are denied.
+    pub fn new(runtime_id: RuntimeId, signers: HashSet<PublicKey>, threshold: usize) -> Self {
+        Self {
+            runtime_id,
+            signers,
+            threshold,
+            runtime_resolver: None,
+            policy: RwLock::new(None),
+        }
+    }
+
+    /// Configure the resolver of the runtime on whose behalf calls are
+    /// made, used by rules restricting runtimes.
+    ///
+    /// If left unspecified, calls are never made on behalf of any runtime.
+    pub fn set_runtime_resolver<R>(&mut self, resolver: R)
+    where
+        R: RuntimeResolver + 'static,
+    {
+        self.runtime_resolver = Some(Box::new(resolver));
+    }
+
+    /// Load (or update) the policy from the given signed policy document.
+    ///
+    /// The policy must be for the access control's runtime and its serial
+    /// number must be greater than the serial of the current policy, unless
+    /// the policy is unchanged.
+    pub fn update(&self, signed_policy_raw: &[u8]) -> Result<()> {
+        let signed_policy: SignedAccessPolicy = cbor::from_slice(signed_policy_raw)?;
+        let new_policy = signed_policy.verify(&self.signers, self.threshold)?;
+        if new_policy.id != self.runtime_id {
+            return Err(PolicyError::InvalidRuntime.into());
+        }
+
+        let mut policy = self.policy.write().unwrap();
+        if let Some(ref current) = *policy {
+            if current.serial > new_policy.serial {
+                return Err(PolicyError::Rollback {
+                    current: current.serial,
+                    new: new_policy.serial,
+                }
+                .into());
+            } else if current.serial == new_policy.serial && current != new_policy {
+                return Err(PolicyError::Changed.into());
+            }
+        }
+        *policy = Some(new_policy.clone());
+
+        Ok(())
+    }
+
+    /// Return the serial number of the current policy, if any.
+    pub fn serial(&self) -> Option<u32> {
+        self.policy
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|policy| policy.serial)
+    }
+
+    /// Check whether the given call is allowed by the current policy.
+    pub fn check(&self, request: &Request, ctx: &Context) -> Result<()> {
+        let caller = ctx
+            .session_info
+            .as_ref()
+            .map(|info| info.verified_quote.identity());
+        let runtime_id = self
+            .runtime_resolver
+            .as_ref()
+            .and_then(|resolver| resolver.resolve(request, ctx));
+
+        match *self.policy.read().unwrap() {
+            Some(ref policy)
+                if policy.allows(&request.method, caller.as_ref(), runtime_id.as_ref()) =>
+            {
+                Ok(())
+            }
+            _ => Err(PolicyError::AccessDenied {
+                method: request.method.clone(),
+            }
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use io_context::Context as IoContext;
+
+    use super::*;
+    use crate::{
+        common::crypto::signature::{PrivateKey, Signer},
+        consensus::epoch::EpochTracker,
+        enclave_rpc::{
+            dispatcher::{Dispatcher, Method, MethodDescriptor},
+            types::Body,
+        },
+        rak::RAK,
+    };
+
+    fn sign(policy: AccessPolicy, signers: &[&PrivateKey]) -> Vec<u8> {
+        let raw_policy = cbor::to_vec(&policy);
+        let signatures = signers
+            .iter()
+            .map(|signer| SignatureBundle {
+                public_key: Some(signer.public_key()),
+                signature: signer.sign(POLICY_SIGNATURE_CONTEXT, &raw_policy).unwrap(),
+            })
+            .collect();
+        cbor::to_vec(&SignedAccessPolicy { policy, signatures })
+    }
+
+    fn request(method: &str) -> Request {
+        Request {
+            method: method.to_owned(),
+            args: cbor::to_value(42u64),
+        }
+    }
+
+    #[test]
+    fn test_access_control() {
+        let runtime_id = RuntimeId::default();
+        let other_runtime_id = RuntimeId::from(vec![1; 32]);
+        let signer_a = PrivateKey::generate();
+        let signer_b = PrivateKey::generate();
+        let untrusted = PrivateKey::generate();
+        let signers = vec![signer_a.public_key(), signer_b.public_key()]
+            .into_iter()
+            .collect();
+        let mut access_control = AccessControl::new(runtime_id, signers, 2);
+        access_control.set_runtime_resolver(|request: &Request, _ctx: &Context| {
+            match request.method.as_str() {
+                "other" => Some(RuntimeId::from(vec![1; 32])),
+                _ => None,
+            }
+        });
+        let access_control = Arc::new(access_control);
+
+        let mut dispatcher = Dispatcher::new();
+        for name in &["allowed", "denied", "other"] {
+            dispatcher.add_method(
+                Method::new(
+                    MethodDescriptor {
+                        name: name.to_string(),
+                    },
+                    |args: &u64, _ctx: &mut Context| -> Result<u64> { Ok(*args) },
+                ),
+                false,
+            );
+        }
+        dispatcher.set_access_control(access_control.clone());
+        let rak = Arc::new(RAK::new());
+        let is_allowed = |method: &str| {
+            let ctx = Context::new(
+                IoContext::background().freeze(),
+                rak.clone(),
+                None,
+                Arc::new(EpochTracker::new()),
+            );
+            match dispatcher.dispatch(request(method), ctx).body {
+                Body::Success(_) => true,
+                Body::Error(_) => false,
+                body => panic!("unexpected response body: {:?}", body),
+            }
+        };
+
+        // Everything is denied until a policy is loaded.
+        assert!(!is_allowed("allowed"));
+
+        let policy = AccessPolicy {
+            serial: 1,
+            id: runtime_id,
+            rules: vec![
+                AccessRule {
+                    methods: vec!["allowed".to_owned()].into_iter().collect(),
+                    ..Default::default()
+                },
+                AccessRule {
+                    runtime_ids: Some(vec![other_runtime_id].into_iter().collect()),
+                    methods: vec![ANY_METHOD.to_owned()].into_iter().collect(),
+                    ..Default::default()
+                },
+                AccessRule {
+                    mr_enclave: Some(MrEnclave::default()),
+                    methods: vec!["denied".to_owned()].into_iter().collect(),
+                    ..Default::default()
+                },
+            ],
+        };
+        assert!(access_control
+            .update(&sign(policy.clone(), &[&signer_a]))
+            .is_err());
+        assert!(access_control
+            .update(&sign(policy.clone(), &[&signer_a, &untrusted]))
+            .is_err());
+        access_control
+            .update(&sign(policy.clone(), &[&signer_a, &signer_b]))
+            .unwrap();
+        assert_eq!(Some(1), access_control.serial());
+        assert!(is_allowed("allowed"));
+        assert!(!is_allowed("denied"));
+        assert!(is_allowed("other"));
+
+        // Policies must be for the same runtime and must not be rolled back.
+        let mut wrong_runtime = policy.clone();
+        wrong_runtime.serial = 2;
+        wrong_runtime.id = other_runtime_id;
+        assert!(access_control
+            .update(&sign(wrong_runtime, &[&signer_a, &signer_b]))
+            .is_err());
+        let mut changed = policy.clone();
+        changed.rules.clear();
+        assert!(access_control
+            .update(&sign(changed.clone(), &[&signer_a, &signer_b]))
+            .is_err());
+
+        // Reload the policy without restarting.
+        changed.serial = 2;
+        access_control
+            .update(&sign(changed, &[&signer_a, &signer_b]))
+            .unwrap();
+        assert!(!is_allowed("allowed"));
+        assert!(access_control
+            .update(&sign(policy, &[&signer_a, &signer_b]))
+            .is_err());
+        assert_eq!(Some(2), access_control.serial());
+    }
+}
@@ -3,23 +3,61 @@ use std::{collections::HashSet, io::Write, mem, sync::Arc};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use sgx_isa::Keypolicy;
 use snow;
 use thiserror::Error;
+use zeroize::Zeroize;
 
 use super::types::Message;
 use crate::{
     common::{
         cbor,
-        crypto::signature::{PublicKey, Signature, Signer},
-        sgx::avr,
+        crypto::{
+            kdf::kmac256,
+            secret::Secret,
+            signature::{PublicKey, Signature, Signer},
+        },
+        protobuf,
+        sgx::{
+            avr,
+            seal::{seal_cbor, unseal_cbor},
+            Quote, VerifiedQuote,
+        },
     },
     rak::RAK,
 };
 
 /// Noise protocol pattern.
 const NOISE_PATTERN: &'static str = "Noise_XX_25519_ChaChaPoly_SHA256";
+/// Noise protocol pattern of session resumption handshakes.
+const NOISE_RESUMPTION_PATTERN: &str = "Noise_NNpsk0_25519_ChaChaPoly_SHA256";
+/// Maximum size of a Noise protocol message.
+const NOISE_MAX_MESSAGE_LEN: usize = 65535;
+/// Session resumption secret derivation label.
+const RESUMPTION_SECRET_LABEL: &[u8] = b"oasis-core/enclave-rpc: session resumption";
 /// RAK signature session binding context.
 const RAK_SESSION_BINDING_CONTEXT: [u8; 8] = *b"EkRakRpc";
+/// RAK signature session transcript binding context.
+const RAK_TRANSCRIPT_BINDING_CONTEXT: [u8; 8] = *b"EkRakHsh";
+/// Session state sealing context.
+const SESSION_STATE_SEAL_CONTEXT: &[u8] = b"oasis-core/enclave-rpc: session state";
+/// Prefix of the static public key signed by the libp2p identity key.
+const LIBP2P_STATIC_KEY_PREFIX: &[u8] = b"noise-libp2p-static-key:";
+/// libp2p key type of Ed25519 identity keys.
+const LIBP2P_KEY_TYPE_ED25519: u64 = 1;
+/// `NoiseHandshakePayload.identity_key` field number.
+const LIBP2P_PAYLOAD_IDENTITY_KEY: u32 = 1;
+/// `NoiseHandshakePayload.identity_sig` field number.
+const LIBP2P_PAYLOAD_IDENTITY_SIG: u32 = 2;
+/// `NoiseHandshakePayload.extensions` field number.
+const LIBP2P_PAYLOAD_EXTENSIONS: u32 = 4;
+/// `PublicKey.Type` field number.
+const LIBP2P_PUBLIC_KEY_TYPE: u32 = 1;
+/// `PublicKey.Data` field number.
+const LIBP2P_PUBLIC_KEY_DATA: u32 = 2;
+/// `NoiseExtensions` field number of the remote attestation, outside of the
+/// range used by the libp2p specification.
+const LIBP2P_EXTENSION_QUOTE: u32 = 1000;
 
 /// Session-related error.
 #[derive(Error, Debug)]
@@ -32,18 +70,150 @@ enum SessionError {
     Closed,
     #[error("mismatched enclave identity")]
     MismatchedEnclaveIdentity,
+    #[error("unsupported handshake binding")]
+    UnsupportedBinding,
+    #[error("local attestation not available")]
+    LocalAttestationUnavailable,
+    #[error("remote attestation required")]
+    RemoteAttestationRequired,
+    #[error("invalid identity payload")]
+    InvalidPayload,
+}
+
+/// Format of the identity payloads of the handshake messages.
+///
+/// The format is not announced in the handshake, so both parties must be
+/// configured to use the same format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PayloadFormat {
+    /// CBOR-encoded `RAKBinding`, bound into the handshake as selected by the
+    /// initiator (see `HandshakeBinding`).
+    RAKBinding,
+    /// `NoiseHandshakePayload` as defined by the libp2p Noise specification.
+    ///
+    /// RAK is used as the Ed25519 identity key, signing the prefixed static
+    /// public key, and the remote attestation is carried in an extension
+    /// which other libp2p implementations ignore. The first handshake message
+    /// has an empty payload, so the binding is always `StaticKey`.
+    ///
+    /// Handshakes interoperate with libp2p peers using Ed25519 identities,
+    /// which are reported by `Session::remote_identity`. Framing of the
+    /// handshake messages and the transport messages (CBOR-encoded
+    /// `Message`s) are left to the caller.
+    Libp2p,
+}
+
+impl Default for PayloadFormat {
+    fn default() -> Self {
+        PayloadFormat::RAKBinding
+    }
+}
+
+/// How the enclave identity (RAK binding) is bound into the handshake.
+///
+/// The binding is selected by the initiator of a session and announced in
+/// the payload of the first handshake message, which the responder follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandshakeBinding {
+    /// RAK signs the session's static public key.
+    StaticKey,
+    /// RAK signs the handshake hash of the handshake messages preceding the
+    /// binding together with the session's static public key.
+    ///
+    /// This binds the enclave identity to the specific handshake (as in the
+    /// standard analyses of identity-bound Noise XX), so that a binding cannot
+    /// be replayed in another handshake using the same static key.
+    Transcript,
+}
+
+impl Default for HandshakeBinding {
+    fn default() -> Self {
+        HandshakeBinding::StaticKey
+    }
+}
+
+impl HandshakeBinding {
+    /// Payload of the first handshake message announcing the binding.
+    ///
+    /// The payload is empty for `StaticKey` for compatibility with peers
+    /// which do not support selecting the binding.
+    fn to_payload(self) -> Vec<u8> {
+        match self {
+            HandshakeBinding::StaticKey => vec![],
+            binding => cbor::to_vec(&binding),
+        }
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self> {
+        if payload.is_empty() {
+            return Ok(HandshakeBinding::StaticKey);
+        }
+        cbor::from_slice(payload).map_err(|_| SessionError::UnsupportedBinding.into())
+    }
+
+    /// Context and message signed by RAK to bind the given static public key.
+    fn signed_message(self, handshake_hash: &[u8], static_pub: &[u8]) -> (&'static [u8], Vec<u8>) {
+        match self {
+            HandshakeBinding::StaticKey => (&RAK_SESSION_BINDING_CONTEXT[..], static_pub.to_vec()),
+            HandshakeBinding::Transcript => (
+                &RAK_TRANSCRIPT_BINDING_CONTEXT[..],
+                [handshake_hash, static_pub].concat(),
+            ),
+        }
+    }
 }
 
 /// Information about a session.
 pub struct SessionInfo {
     pub rak_binding: RAKBinding,
-    pub authenticated_avr: avr::AuthenticatedAVR,
+    pub verified_quote: VerifiedQuote,
+}
+
+/// Authenticated remote party of a session.
+#[derive(Clone)]
+struct Remote {
+    static_key: Vec<u8>,
+    /// Handshake hash to which the remote RAK binding is bound.
+    handshake_hash: Vec<u8>,
+    /// Identity payload of the remote party.
+    payload: Vec<u8>,
+}
+
+/// Transport state of an established session.
+struct Transport {
+    state: snow::TransportState,
+    /// Secret for resuming the session (see `SessionState`).
+    resumption_secret: Secret<32>,
+    remote: Remote,
+}
+
+impl Transport {
+    fn new(mut state: snow::HandshakeState, remote: Remote) -> Result<Self> {
+        // Both parties derive the resumption secret from the keys of the
+        // session.
+        let (initiator_key, responder_key) = state.dangerously_get_raw_split();
+        let mut keys = [initiator_key, responder_key].concat();
+        let resumption_secret = kmac256(
+            &keys,
+            RESUMPTION_SECRET_LABEL,
+            &[state.get_handshake_hash()],
+        );
+        keys.zeroize();
+
+        Ok(Self {
+            state: state.into_transport_mode()?,
+            resumption_secret,
+            remote,
+        })
+    }
 }
 
 enum State {
     Handshake1(snow::HandshakeState),
     Handshake2(snow::HandshakeState),
-    Transport(snow::TransportState),
+    Resumption1(snow::HandshakeState, Remote),
+    Resumption2(snow::HandshakeState, Remote),
+    Transport(Transport),
     Closed,
 }
 
@@ -52,25 +222,39 @@ pub struct Session {
     local_static_pub: Vec<u8>,
     rak: Option<Arc<RAK>>,
     remote_enclaves: Option<HashSet<avr::EnclaveIdentity>>,
+    binding: HandshakeBinding,
+    format: PayloadFormat,
+    mutual_attestation: bool,
+    static_key_fallback: bool,
     info: Option<Arc<SessionInfo>>,
+    remote_identity: Option<PublicKey>,
     state: State,
     buf: Vec<u8>,
 }
 
 impl Session {
     fn new(
-        handshake_state: snow::HandshakeState,
+        state: State,
         local_static_pub: Vec<u8>,
         rak: Option<Arc<RAK>>,
         remote_enclaves: Option<HashSet<avr::EnclaveIdentity>>,
+        binding: HandshakeBinding,
+        format: PayloadFormat,
+        mutual_attestation: bool,
+        static_key_fallback: bool,
     ) -> Self {
         Self {
             local_static_pub,
             rak,
             remote_enclaves,
+            binding,
+            format,
+            mutual_attestation,
+            static_key_fallback,
             info: None,
-            state: State::Handshake1(handshake_state),
-            buf: vec![0u8; 65535],
+            remote_identity: None,
+            state,
+            buf: vec![0u8; NOISE_MAX_MESSAGE_LEN],
         }
     }
 
@@ -93,51 +277,115 @@ impl Session {
                     if !data.is_empty() {
                         return Err(SessionError::InvalidInput.into());
                     }
+                    self.check_binding()?;
 
                     // -> e
-                    let len = state.write_message(&[], &mut self.buf)?;
+                    let len = state.write_message(&self.binding.to_payload(), &mut self.buf)?;
                     writer.write_all(&self.buf[..len])?;
                 } else {
                     // <- e
-                    state.read_message(&data, &mut self.buf)?;
+                    let len = state.read_message(&data, &mut self.buf)?;
+                    self.binding = HandshakeBinding::from_payload(&self.buf[..len])?;
+                    if self.format == PayloadFormat::Libp2p
+                        && self.binding != HandshakeBinding::StaticKey
+                    {
+                        return Err(SessionError::UnsupportedBinding.into());
+                    }
+                    self.check_binding()?;
 
                     // -> e, ee, s, es
-                    let len = state.write_message(&self.get_rak_binding(), &mut self.buf)?;
+                    let payload = self.get_payload(state.get_handshake_hash())?;
+                    let len = state.write_message(&payload, &mut self.buf)?;
                     writer.write_all(&self.buf[..len])?;
                 }
 
                 self.state = State::Handshake2(state);
             }
             State::Handshake2(mut state) => {
+                // The remote RAK binding is bound to the handshake hash
+                // before the message carrying it.
+                let remote_handshake_hash = state.get_handshake_hash().to_vec();
                 if state.is_initiator() {
                     // <- e, ee, s, es
                     let len = state.read_message(&data, &mut self.buf)?;
+                    let remote_payload = self.buf[..len].to_vec();
                     let remote_static = state
                         .get_remote_static()
-                        .expect("dh exchange just happened");
-                    self.info = self.verify_rak_binding(&self.buf[..len], remote_static)?;
+                        .expect("dh exchange just happened")
+                        .to_vec();
+                    self.verify_payload(&remote_payload, &remote_static, &remote_handshake_hash)?;
 
                     // -> s, se
-                    let len = state.write_message(&self.get_rak_binding(), &mut self.buf)?;
+                    let payload = self.get_payload(state.get_handshake_hash())?;
+                    let len = state.write_message(&payload, &mut self.buf)?;
                     writer.write_all(&self.buf[..len])?;
+
+                    // Move into transport mode.
+                    self.state = State::Transport(Transport::new(
+                        state,
+                        Remote {
+                            static_key: remote_static,
+                            handshake_hash: remote_handshake_hash,
+                            payload: remote_payload,
+                        },
+                    )?);
                 } else {
                     // <- s, se
                     let len = state.read_message(&data, &mut self.buf)?;
+                    let remote_payload = self.buf[..len].to_vec();
                     let remote_static = state
                         .get_remote_static()
-                        .expect("dh exchange just happened");
-                    self.info = self.verify_rak_binding(&self.buf[..len], remote_static)?;
+                        .expect("dh exchange just happened")
+                        .to_vec();
+                    self.verify_payload(&remote_payload, &remote_static, &remote_handshake_hash)?;
+
+                    // Move into transport mode.
+                    self.state = State::Transport(Transport::new(
+                        state,
+                        Remote {
+                            static_key: remote_static,
+                            handshake_hash: remote_handshake_hash,
+                            payload: remote_payload,
+                        },
+                    )?);
                 }
+            }
+            State::Resumption1(mut state, remote) => {
+                // Initiator only sends in this state.
+                if !data.is_empty() {
+                    return Err(SessionError::InvalidInput.into());
+                }
+
+                // -> psk, e
+                let len = state.write_message(&[], &mut self.buf)?;
+                writer.write_all(&self.buf[..len])?;
+
+                self.state = State::Resumption2(state, remote);
+            }
+            State::Resumption2(mut state, remote) => {
+                // <- e, ee
+                state.read_message(&data, &mut self.buf)?;
 
                 // Move into transport mode.
-                self.state = State::Transport(state.into_transport_mode()?);
+                self.state = State::Transport(Transport::new(state, remote)?);
             }
-            State::Transport(mut state) => {
+            State::Transport(mut transport) => {
                 // TODO: Restore session in case of errors.
-                let len = state.read_message(&data, &mut self.buf)?;
+                let len = match transport.state.read_message(&data, &mut self.buf) {
+                    Ok(len) => len,
+                    Err(error) if !transport.state.is_initiator() => {
+                        // The initiator may be resuming the session.
+                        let transport = self
+                            .respond_resumption(transport, &data, writer)
+                            .map_err(|_| error)?;
+                        self.state = State::Transport(transport);
+                        return Ok(None);
+                    }
+                    Err(error) => return Err(error.into()),
+                };
                 let msg = cbor::from_slice(&self.buf[..len])?;
 
-                self.state = State::Transport(state);
+                self.state = State::Transport(transport);
                 return Ok(Some(msg));
             }
             State::Closed => {
@@ -153,9 +401,9 @@ impl Session {
     /// The `writer` will be used for protocol message output which should
     /// be transmitted to the remote session counterpart.
     pub fn write_message<W: Write>(&mut self, msg: Message, mut writer: W) -> Result<()> {
-        if let State::Transport(ref mut state) = self.state {
+        if let State::Transport(ref mut transport) = self.state {
             let msg = cbor::to_vec(&msg);
-            let len = state.write_message(&msg, &mut self.buf)?;
+            let len = transport.state.write_message(&msg, &mut self.buf)?;
             writer.write_all(&self.buf[..len])?;
 
             Ok(())
@@ -164,6 +412,52 @@ impl Session {
         }
     }
 
+    /// Export the state of an established session, so that it can be
+    /// resumed later using `Builder::build_resumed`.
+    ///
+    /// Each resumption of the session changes its state, so it must be
+    /// exported again after the session has been resumed.
+    pub fn export_state(&self) -> Result<SessionState> {
+        if let State::Transport(ref transport) = self.state {
+            Ok(SessionState {
+                resumption_secret: transport.resumption_secret.clone(),
+                remote_static: transport.remote.static_key.clone(),
+                rak_binding: self.info.as_ref().map(|info| info.rak_binding.clone()),
+                binding: self.binding,
+                remote_handshake_hash: transport.remote.handshake_hash.clone(),
+                payload_format: self.format,
+                remote_payload: match self.format {
+                    PayloadFormat::RAKBinding => vec![],
+                    PayloadFormat::Libp2p => transport.remote.payload.clone(),
+                },
+            })
+        } else {
+            Err(SessionError::InvalidState.into())
+        }
+    }
+
+    /// Respond to a resumption handshake of the initiator, which derives new
+    /// transport keys from the resumption secret and fresh ephemeral keys.
+    fn respond_resumption<W: Write>(
+        &mut self,
+        transport: Transport,
+        data: &[u8],
+        mut writer: W,
+    ) -> Result<Transport> {
+        let mut state = snow::Builder::new(NOISE_RESUMPTION_PATTERN.parse().unwrap())
+            .psk(0, transport.resumption_secret.as_ref())
+            .build_responder()?;
+
+        // <- psk, e
+        state.read_message(data, &mut self.buf)?;
+
+        // -> e, ee
+        let len = state.write_message(&[], &mut self.buf)?;
+        writer.write_all(&self.buf[..len])?;
+
+        Transport::new(state, transport.remote)
+    }
+
     /// Mark the session as closed.
     ///
     /// After the session is closed it can no longer be used to transmit
@@ -172,67 +466,203 @@ impl Session {
         self.state = State::Closed;
     }
 
-    fn get_rak_binding(&self) -> Vec<u8> {
+    /// Handshake binding of the session.
+    pub fn handshake_binding(&self) -> HandshakeBinding {
+        self.binding
+    }
+
+    /// Identity payload format of the session.
+    pub fn payload_format(&self) -> PayloadFormat {
+        self.format
+    }
+
+    /// Mutual attestation requires both parties to bind their attestation to
+    /// the session transcript (or to the static key, if falling back to it is
+    /// allowed), which the libp2p payload format does not support.
+    fn check_binding(&self) -> Result<()> {
+        if !self.mutual_attestation {
+            return Ok(());
+        }
+        match (self.format, self.binding) {
+            (PayloadFormat::Libp2p, _) => Err(SessionError::UnsupportedBinding.into()),
+            (_, HandshakeBinding::Transcript) => Ok(()),
+            (_, HandshakeBinding::StaticKey) if self.static_key_fallback => Ok(()),
+            _ => Err(SessionError::UnsupportedBinding.into()),
+        }
+    }
+
+    fn get_payload(&self, handshake_hash: &[u8]) -> Result<Vec<u8>> {
+        match self.format {
+            PayloadFormat::RAKBinding => self.get_rak_binding(handshake_hash),
+            PayloadFormat::Libp2p => self.get_libp2p_payload(),
+        }
+    }
+
+    fn get_libp2p_payload(&self) -> Result<Vec<u8>> {
+        let rak = match self.rak {
+            Some(ref rak) if rak.public_key().is_some() => rak,
+            _ => return self.missing_rak_binding(),
+        };
+
+        let rak_pub = rak.public_key().expect("rak is configured");
+        let identity_key = protobuf::Encoder::new()
+            .varint(LIBP2P_PUBLIC_KEY_TYPE, LIBP2P_KEY_TYPE_ED25519)
+            .bytes(LIBP2P_PUBLIC_KEY_DATA, rak_pub.as_ref());
+        let identity_sig =
+            rak.sign_raw(&[LIBP2P_STATIC_KEY_PREFIX, &self.local_static_pub].concat())?;
+        let mut payload = protobuf::Encoder::new()
+            .message(LIBP2P_PAYLOAD_IDENTITY_KEY, identity_key)
+            .bytes(LIBP2P_PAYLOAD_IDENTITY_SIG, identity_sig.as_ref());
+        if let Some(quote) = rak.quote() {
+            let extensions =
+                protobuf::Encoder::new().bytes(LIBP2P_EXTENSION_QUOTE, &cbor::to_vec(&*quote));
+            payload = payload.message(LIBP2P_PAYLOAD_EXTENSIONS, extensions);
+        }
+
+        Ok(payload.into_vec())
+    }
+
+    fn get_rak_binding(&self, handshake_hash: &[u8]) -> Result<Vec<u8>> {
         match self.rak {
             Some(ref rak) => {
-                if rak.public_key().is_none() || rak.avr().is_none() {
-                    return vec![];
+                if rak.public_key().is_none() || rak.quote().is_none() {
+                    return self.missing_rak_binding();
                 }
 
                 let rak_pub = rak.public_key().expect("rak is configured").clone();
-                let avr = rak.avr().expect("avr is configured").clone();
+                let quote = rak.quote().expect("quote is configured").clone();
+                let (context, message) = self
+                    .binding
+                    .signed_message(handshake_hash, &self.local_static_pub);
                 let rak_binding = RAKBinding {
-                    avr: (*avr).clone(),
+                    quote: (*quote).clone(),
                     rak_pub,
-                    binding: rak
-                        .sign(&RAK_SESSION_BINDING_CONTEXT, &self.local_static_pub)
-                        .unwrap(),
+                    binding: rak.sign(context, &message)?,
                 };
 
-                cbor::to_vec(&rak_binding)
+                Ok(cbor::to_vec(&rak_binding))
             }
-            None => vec![],
+            None => self.missing_rak_binding(),
+        }
+    }
+
+    fn missing_rak_binding(&self) -> Result<Vec<u8>> {
+        if self.mutual_attestation {
+            return Err(SessionError::LocalAttestationUnavailable.into());
         }
+        Ok(vec![])
     }
 
-    fn verify_rak_binding(
-        &self,
-        rak_binding: &[u8],
+    /// Verify the identity payload of the remote party, updating the
+    /// session information and remote identity.
+    fn verify_payload(
+        &mut self,
+        payload: &[u8],
         remote_static: &[u8],
-    ) -> Result<Option<Arc<SessionInfo>>> {
-        if rak_binding.is_empty() {
+        remote_handshake_hash: &[u8],
+    ) -> Result<()> {
+        if payload.is_empty() {
             // If enclave identity verification is required and no RAK binding
             // has been provided, we must abort the session.
             if self.remote_enclaves.is_some() {
                 return Err(SessionError::MismatchedEnclaveIdentity.into());
             }
-            return Ok(None);
+            if self.mutual_attestation {
+                return Err(SessionError::RemoteAttestationRequired.into());
+            }
+            self.info = None;
+            self.remote_identity = None;
+            return Ok(());
         }
 
-        let rak_binding: RAKBinding = cbor::from_slice(rak_binding)?;
-        let authenticated_avr = avr::verify(&rak_binding.avr)?;
+        let info = match self.format {
+            PayloadFormat::RAKBinding => {
+                let rak_binding: RAKBinding = cbor::from_slice(payload)?;
+
+                // Verify remote static key binding.
+                let (context, message) = self
+                    .binding
+                    .signed_message(remote_handshake_hash, remote_static);
+                if let Err(err) =
+                    rak_binding
+                        .binding
+                        .verify(&rak_binding.rak_pub, context, &message)
+                {
+                    // Peers which do not support selecting the binding ignore
+                    // the announced binding and bind to the static key.
+                    if !self.static_key_fallback || self.binding == HandshakeBinding::StaticKey {
+                        return Err(err);
+                    }
+                    let (context, message) = HandshakeBinding::StaticKey
+                        .signed_message(remote_handshake_hash, remote_static);
+                    rak_binding
+                        .binding
+                        .verify(&rak_binding.rak_pub, context, &message)?;
+                    self.binding = HandshakeBinding::StaticKey;
+                }
+
+                Some(self.verify_rak_binding(rak_binding)?)
+            }
+            PayloadFormat::Libp2p => {
+                let (identity_key, identity_sig, quote) = decode_libp2p_payload(payload)?;
+
+                // Verify remote static key signature.
+                identity_sig.verify_raw(
+                    &identity_key,
+                    &[LIBP2P_STATIC_KEY_PREFIX, remote_static].concat(),
+                )?;
+
+                match quote {
+                    Some(quote) => Some(self.verify_rak_binding(RAKBinding {
+                        quote,
+                        rak_pub: identity_key,
+                        binding: identity_sig,
+                    })?),
+                    None => {
+                        // Unattested peers have no enclave identity.
+                        if self.remote_enclaves.is_some() {
+                            return Err(SessionError::MismatchedEnclaveIdentity.into());
+                        }
+                        self.info = None;
+                        self.remote_identity = Some(identity_key);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        self.remote_identity = info.as_ref().map(|info| info.rak_binding.rak_pub.clone());
+        self.info = info;
+        Ok(())
+    }
+
+    /// Verify the remote attestation of a RAK binding whose static key
+    /// binding has already been verified.
+    fn verify_rak_binding(&self, rak_binding: RAKBinding) -> Result<Arc<SessionInfo>> {
+        let verified_quote = rak_binding.quote.verify()?;
 
         // Verify MRENCLAVE/MRSIGNER.
         if let Some(ref remote_enclaves) = self.remote_enclaves {
-            if !remote_enclaves.contains(&authenticated_avr.identity) {
+            if !remote_enclaves.contains(&verified_quote.identity()) {
                 return Err(SessionError::MismatchedEnclaveIdentity.into());
             }
         }
 
         // Verify RAK binding.
-        RAK::verify_binding(&authenticated_avr, &rak_binding.rak_pub)?;
-
-        // Verify remote static key binding.
-        rak_binding.binding.verify(
-            &rak_binding.rak_pub,
-            &RAK_SESSION_BINDING_CONTEXT,
-            remote_static,
-        )?;
+        RAK::verify_binding(&verified_quote, &rak_binding.rak_pub)?;
 
-        Ok(Some(Arc::new(SessionInfo {
+        Ok(Arc::new(SessionInfo {
             rak_binding,
-            authenticated_avr,
-        })))
+            verified_quote,
+        }))
+    }
+
+    /// Authenticated identity key of the remote party, if it provided one.
+    ///
+    /// This is the remote RAK for attested parties, and the libp2p identity
+    /// key for unattested parties using the libp2p payload format.
+    pub fn remote_identity(&self) -> Option<PublicKey> {
+        self.remote_identity.clone()
     }
 
     /// Session information.
@@ -251,28 +681,135 @@ impl Session {
     }
 }
 
+/// Decode a libp2p `NoiseHandshakePayload` into the identity key, the
+/// identity signature and the remote attestation (if any).
+fn decode_libp2p_payload(payload: &[u8]) -> Result<(PublicKey, Signature, Option<Quote>)> {
+    let mut identity_key = None;
+    let mut identity_sig = None;
+    let mut quote = None;
+    for (field, value) in protobuf::decode(payload)? {
+        match field {
+            LIBP2P_PAYLOAD_IDENTITY_KEY => {
+                identity_key = Some(decode_libp2p_public_key(value.as_bytes(field)?)?);
+            }
+            LIBP2P_PAYLOAD_IDENTITY_SIG => {
+                let sig = value.as_bytes(field)?;
+                if sig.len() != Signature::len() {
+                    return Err(SessionError::InvalidPayload.into());
+                }
+                identity_sig = Some(Signature::from(sig));
+            }
+            LIBP2P_PAYLOAD_EXTENSIONS => {
+                // Embedded messages may be split across multiple fields.
+                for (field, value) in protobuf::decode(value.as_bytes(field)?)? {
+                    if field == LIBP2P_EXTENSION_QUOTE {
+                        quote = Some(cbor::from_slice(value.as_bytes(field)?)?);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match (identity_key, identity_sig) {
+        (Some(identity_key), Some(identity_sig)) => Ok((identity_key, identity_sig, quote)),
+        _ => Err(SessionError::InvalidPayload.into()),
+    }
+}
+
+/// Decode a libp2p `PublicKey`, which must be an Ed25519 key.
+fn decode_libp2p_public_key(data: &[u8]) -> Result<PublicKey> {
+    let mut key_type = None;
+    let mut key = None;
+    for (field, value) in protobuf::decode(data)? {
+        match field {
+            LIBP2P_PUBLIC_KEY_TYPE => key_type = Some(value.as_varint(field)?),
+            LIBP2P_PUBLIC_KEY_DATA => key = Some(value.as_bytes(field)?),
+            _ => {}
+        }
+    }
+
+    match (key_type, key) {
+        (Some(LIBP2P_KEY_TYPE_ED25519), Some(key)) if key.len() == PublicKey::len() => {
+            Ok(PublicKey::from(key))
+        }
+        _ => Err(SessionError::InvalidPayload.into()),
+    }
+}
+
 /// Binding of the session's static public key to a remote attestation
-/// verification report through the use of the remote attestation key.
+/// through the use of the remote attestation key.
 ///
 /// The signature chain is as follows:
 ///
-/// * `avr` contains the remote attestation verification report which
-///   binds RAK to the remote attestation.
+/// * `quote` contains the remote attestation (either an attestation
+///   verification report or a DCAP quote) which binds RAK to the enclave.
 /// * `rak_pub` contains the public part of RAK.
 /// * `binding` is signed by `rak_pub` and binds the session's static
-///   public key to RAK.
+///   public key (and possibly the handshake, see `HandshakeBinding`)
+///   to RAK. For the libp2p payload format, this is the libp2p identity
+///   signature.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RAKBinding {
-    pub avr: avr::AVR,
+    /// Remote attestation, encoded as an `avr` or `pcs` field (so that
+    /// bindings containing an AVR remain compatible).
+    #[serde(flatten)]
+    pub quote: Quote,
     pub rak_pub: PublicKey,
     pub binding: Signature,
 }
 
+/// Exported state of an established session.
+///
+/// The state contains the resumption secret of the session, so it must only
+/// leave the enclave sealed (see `SessionState::seal`). It does not contain
+/// the transport keys, as a resumed session first performs a resumption
+/// handshake with the remote party, deriving fresh transport keys from the
+/// resumption secret and new ephemeral keys. Rolling back the sealed state
+/// therefore cannot cause nonces to be reused. Each resumption replaces the
+/// resumption secret of both parties, so a session can only be resumed from
+/// its latest exported state.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionState {
+    resumption_secret: Secret<32>,
+    #[serde(with = "serde_bytes")]
+    remote_static: Vec<u8>,
+    rak_binding: Option<RAKBinding>,
+    #[serde(default)]
+    binding: HandshakeBinding,
+    #[serde(default, with = "serde_bytes")]
+    remote_handshake_hash: Vec<u8>,
+    #[serde(default)]
+    payload_format: PayloadFormat,
+    /// Identity payload of the remote party, for the libp2p payload format.
+    #[serde(default, with = "serde_bytes")]
+    remote_payload: Vec<u8>,
+}
+
+impl SessionState {
+    /// Seal the session state to the enclave identity.
+    pub fn seal(&self) -> Vec<u8> {
+        seal_cbor(Keypolicy::MRENCLAVE, SESSION_STATE_SEAL_CONTEXT, self)
+    }
+
+    /// Unseal session state previously sealed by `seal`.
+    ///
+    /// Returns `None` if the ciphertext is empty, and an error if it is
+    /// corrupted or has been sealed by a different enclave.
+    pub fn unseal(ciphertext: &[u8]) -> Result<Option<Self>> {
+        unseal_cbor(Keypolicy::MRENCLAVE, SESSION_STATE_SEAL_CONTEXT, ciphertext)
+    }
+}
+
 /// Session builder.
 #[derive(Clone)]
 pub struct Builder {
     rak: Option<Arc<RAK>>,
     remote_enclaves: Option<HashSet<avr::EnclaveIdentity>>,
+    binding: HandshakeBinding,
+    format: PayloadFormat,
+    mutual_attestation: bool,
+    static_key_fallback: bool,
 }
 
 impl Builder {
@@ -281,6 +818,10 @@ impl Builder {
         Self {
             rak: None,
             remote_enclaves: None,
+            binding: HandshakeBinding::default(),
+            format: PayloadFormat::default(),
+            mutual_attestation: false,
+            static_key_fallback: false,
         }
     }
 
@@ -301,6 +842,47 @@ impl Builder {
         self
     }
 
+    /// Configure how the RAK binding is bound into the handshake.
+    ///
+    /// This only affects initiator sessions, as responder sessions use the
+    /// binding selected by the initiator.
+    pub fn handshake_binding(mut self, binding: HandshakeBinding) -> Self {
+        self.binding = binding;
+        self
+    }
+
+    /// Configure the format of the identity payloads, which must match the
+    /// format used by the remote party.
+    pub fn payload_format(mut self, format: PayloadFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Require both parties to attest themselves, binding their attestation
+    /// to the session transcript.
+    ///
+    /// Initiator sessions use the `Transcript` handshake binding, and
+    /// responder sessions reject initiators using any other binding. The
+    /// handshake fails if either party does not provide a (valid) RAK
+    /// binding, which prevents attestations provided for other sessions from
+    /// being relayed.
+    pub fn mutual_attestation(mut self, enabled: bool) -> Self {
+        self.mutual_attestation = enabled;
+        self
+    }
+
+    /// Accept remote parties which only support the `StaticKey` binding.
+    ///
+    /// Such parties ignore the binding announced by the initiator, so an
+    /// initiator session falls back to the `StaticKey` binding if the RAK
+    /// binding of the responder only verifies for it, and binds its own RAK
+    /// in the same way. This also holds for sessions requiring mutual
+    /// attestation, in which case both parties must still attest themselves.
+    pub fn static_key_fallback(mut self, enabled: bool) -> Self {
+        self.static_key_fallback = enabled;
+        self
+    }
+
     fn build<'a>(
         mut self,
     ) -> (
@@ -319,21 +901,473 @@ impl Builder {
 
     /// Build initiator session.
     pub fn build_initiator(self) -> Session {
+        let mutual_attestation = self.mutual_attestation;
+        let static_key_fallback = self.static_key_fallback;
+        let format = self.format;
+        let binding = match (format, mutual_attestation) {
+            (PayloadFormat::Libp2p, _) => HandshakeBinding::StaticKey,
+            (_, true) => HandshakeBinding::Transcript,
+            (_, false) => self.binding,
+        };
         let (builder, keypair, rak, enclaves) = self.build();
         let session = builder
             .local_private_key(&keypair.private)
             .build_initiator()
             .unwrap();
-        Session::new(session, keypair.public, rak, enclaves)
+        Session::new(
+            State::Handshake1(session),
+            keypair.public,
+            rak,
+            enclaves,
+            binding,
+            format,
+            mutual_attestation,
+            static_key_fallback,
+        )
     }
 
     /// Build responder session.
     pub fn build_responder(self) -> Session {
+        let binding = self.binding;
+        let format = self.format;
+        let mutual_attestation = self.mutual_attestation;
+        let static_key_fallback = self.static_key_fallback;
         let (builder, keypair, rak, enclaves) = self.build();
         let session = builder
             .local_private_key(&keypair.private)
             .build_responder()
             .unwrap();
-        Session::new(session, keypair.public, rak, enclaves)
+        Session::new(
+            State::Handshake1(session),
+            keypair.public,
+            rak,
+            enclaves,
+            binding,
+            format,
+            mutual_attestation,
+            static_key_fallback,
+        )
+    }
+
+    /// Build an initiator session resuming the given exported session state.
+    ///
+    /// The remote party's identity payload is verified again against the
+    /// configured remote enclave identities and mutual attestation
+    /// requirement. The payload format is that of the resumed session.
+    ///
+    /// Like a new session, the resumed session must complete a (resumption)
+    /// handshake before it can be used, which requires the remote party to
+    /// still have the session.
+    pub fn build_resumed(mut self, state: SessionState) -> Result<Session> {
+        let remote_payload = match state.payload_format {
+            PayloadFormat::RAKBinding => state
+                .rak_binding
+                .map(|rak_binding| cbor::to_vec(&rak_binding))
+                .unwrap_or_default(),
+            PayloadFormat::Libp2p => state.remote_payload,
+        };
+        let remote = Remote {
+            static_key: state.remote_static,
+            handshake_hash: state.remote_handshake_hash,
+            payload: remote_payload,
+        };
+        let noise = snow::Builder::new(NOISE_RESUMPTION_PATTERN.parse().unwrap())
+            .psk(0, state.resumption_secret.as_ref())
+            .build_initiator()?;
+        let mut session = Session::new(
+            State::Closed,
+            vec![],
+            self.rak.take(),
+            self.remote_enclaves.take(),
+            state.binding,
+            state.payload_format,
+            self.mutual_attestation,
+            self.static_key_fallback,
+        );
+        session.check_binding()?;
+        session.verify_payload(&remote.payload, &remote.static_key, &remote.handshake_hash)?;
+        session.state = State::Resumption1(noise, remote);
+
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        common::crypto::signature::PrivateKey,
+        enclave_rpc::types::{Body, Response},
+    };
+
+    fn message(value: u64) -> Message {
+        Message::Response(Response {
+            body: Body::Success(cbor::to_value(value)),
+        })
+    }
+
+    fn transfer(from: &mut Session, to: &mut Session, value: u64) -> Result<()> {
+        let mut buffer = vec![];
+        from.write_message(message(value), &mut buffer)?;
+        match to.process_data(buffer, vec![])? {
+            Some(Message::Response(Response {
+                body: Body::Success(received),
+            })) => assert_eq!(value, cbor::from_value::<u64>(received).unwrap()),
+            _ => panic!("expected response message"),
+        }
+        Ok(())
+    }
+
+    fn handshake(initiator: &mut Session, responder: &mut Session) {
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        let mut reply = vec![];
+        responder.process_data(buffer, &mut reply).unwrap();
+        let mut buffer = vec![];
+        initiator.process_data(reply, &mut buffer).unwrap();
+        responder.process_data(buffer, vec![]).unwrap();
+        assert!(initiator.is_connected());
+        assert!(responder.is_connected());
+    }
+
+    fn resume(initiator: &mut Session, responder: &mut Session) {
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        let mut reply = vec![];
+        assert!(responder
+            .process_data(buffer, &mut reply)
+            .unwrap()
+            .is_none());
+        initiator.process_data(reply, vec![]).unwrap();
+        assert!(initiator.is_connected());
+    }
+
+    #[test]
+    fn test_handshake_binding() {
+        let mut initiator = Builder::new()
+            .handshake_binding(HandshakeBinding::Transcript)
+            .build_initiator();
+        let mut responder = Builder::new().build_responder();
+        handshake(&mut initiator, &mut responder);
+        assert_eq!(HandshakeBinding::Transcript, responder.handshake_binding());
+        transfer(&mut initiator, &mut responder, 1).unwrap();
+        transfer(&mut responder, &mut initiator, 2).unwrap();
+
+        // Each party's binding is bound to a different handshake hash.
+        let state = initiator.export_state().unwrap();
+        let remote_state = responder.export_state().unwrap();
+        assert_eq!(HandshakeBinding::Transcript, state.binding);
+        assert_eq!(32, state.remote_handshake_hash.len());
+        assert_ne!(
+            state.remote_handshake_hash,
+            remote_state.remote_handshake_hash
+        );
+
+        let mut initiator = Builder::new().build_initiator();
+        let mut responder = Builder::new().build_responder();
+        handshake(&mut initiator, &mut responder);
+        assert_eq!(HandshakeBinding::StaticKey, responder.handshake_binding());
+
+        // The binding must be known to the responder.
+        let mut responder = Builder::new().build_responder();
+        let noise_builder = snow::Builder::new(NOISE_PATTERN.parse().unwrap());
+        let keypair = noise_builder.generate_keypair().unwrap();
+        let mut noise = noise_builder
+            .local_private_key(&keypair.private)
+            .build_initiator()
+            .unwrap();
+        let mut buffer = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+        let len = noise.write_message(b"unknown", &mut buffer).unwrap();
+        assert!(responder
+            .process_data(buffer[..len].to_vec(), vec![])
+            .is_err());
+    }
+
+    #[test]
+    fn test_mutual_attestation() {
+        // Responders requiring mutual attestation reject other bindings.
+        let mut initiator = Builder::new().build_initiator();
+        let mut responder = Builder::new().mutual_attestation(true).build_responder();
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        assert!(responder.process_data(buffer, vec![]).is_err());
+
+        // Initiators requiring mutual attestation use the transcript binding
+        // and reject responders which do not attest themselves.
+        let mut initiator = Builder::new().mutual_attestation(true).build_initiator();
+        let mut responder = Builder::new().build_responder();
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        let mut reply = vec![];
+        responder.process_data(buffer, &mut reply).unwrap();
+        assert_eq!(HandshakeBinding::Transcript, responder.handshake_binding());
+        assert!(initiator.process_data(reply, vec![]).is_err());
+
+        // Sessions requiring mutual attestation fail if they cannot attest
+        // themselves.
+        let mut initiator = Builder::new()
+            .handshake_binding(HandshakeBinding::Transcript)
+            .build_initiator();
+        let mut responder = Builder::new().mutual_attestation(true).build_responder();
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        assert!(responder.process_data(buffer, vec![]).is_err());
+
+        // Responders falling back to the static key binding accept initiators
+        // which do not announce a binding, but still require attestation.
+        let mut initiator = Builder::new().build_initiator();
+        let mut responder = Builder::new()
+            .mutual_attestation(true)
+            .static_key_fallback(true)
+            .build_responder();
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        let err = responder.process_data(buffer, vec![]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SessionError>(),
+            Some(SessionError::LocalAttestationUnavailable)
+        ));
+
+        // Resumed sessions are subject to the same requirements.
+        let mut initiator = Builder::new().build_initiator();
+        let mut responder = Builder::new().build_responder();
+        handshake(&mut initiator, &mut responder);
+        let state = initiator.export_state().unwrap();
+        assert!(Builder::new()
+            .mutual_attestation(true)
+            .build_resumed(state)
+            .is_err());
+    }
+
+    /// RAK with a signer but without a remote attestation.
+    fn unattested_rak() -> (Arc<RAK>, PublicKey) {
+        let key = PrivateKey::generate();
+        let public_key = key.public_key();
+        let rak = Arc::new(RAK::new());
+        rak.set_signer(Arc::new(key));
+        (rak, public_key)
+    }
+
+    /// Encode a libp2p handshake payload as specified, with the fields in
+    /// the order used by libp2p implementations.
+    fn libp2p_payload(key: &PrivateKey, static_pub: &[u8]) -> Vec<u8> {
+        let sig = key
+            .sign_raw(&[&b"noise-libp2p-static-key:"[..], static_pub].concat())
+            .unwrap();
+        [
+            &[0x0a, 0x24, 0x08, 0x01, 0x12, 0x20][..],
+            key.public_key().as_ref(),
+            &[0x12, 0x40][..],
+            sig.as_ref(),
+        ]
+        .concat()
+    }
+
+    /// Check a libp2p handshake payload as specified.
+    fn check_libp2p_payload(payload: &[u8], identity: &PublicKey, static_pub: &[u8]) {
+        assert_eq!(104, payload.len());
+        assert_eq!(&[0x0a, 0x24, 0x08, 0x01, 0x12, 0x20], &payload[..6]);
+        assert_eq!(identity.as_ref(), &payload[6..38]);
+        assert_eq!(&[0x12, 0x40], &payload[38..40]);
+        Signature::from(&payload[40..])
+            .verify_raw(
+                identity,
+                &[&b"noise-libp2p-static-key:"[..], static_pub].concat(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_libp2p_payload() {
+        let (initiator_rak, initiator_key) = unattested_rak();
+        let (responder_rak, responder_key) = unattested_rak();
+        let mut initiator = Builder::new()
+            .local_rak(initiator_rak.clone())
+            .payload_format(PayloadFormat::Libp2p)
+            .build_initiator();
+        let mut responder = Builder::new()
+            .local_rak(responder_rak)
+            .payload_format(PayloadFormat::Libp2p)
+            .build_responder();
+        handshake(&mut initiator, &mut responder);
+        assert_eq!(PayloadFormat::Libp2p, responder.payload_format());
+        assert_eq!(Some(responder_key.clone()), initiator.remote_identity());
+        assert_eq!(Some(initiator_key), responder.remote_identity());
+        assert!(initiator.session_info().is_none());
+        transfer(&mut initiator, &mut responder, 1).unwrap();
+        transfer(&mut responder, &mut initiator, 2).unwrap();
+
+        // Resumed sessions verify the identity payload again.
+        let state = initiator.export_state().unwrap();
+        assert!(Builder::new()
+            .remote_enclaves(Some(HashSet::new()))
+            .build_resumed(state.clone())
+            .is_err());
+        let mut initiator = Builder::new().build_resumed(state).unwrap();
+        assert_eq!(PayloadFormat::Libp2p, initiator.payload_format());
+        assert_eq!(Some(responder_key), initiator.remote_identity());
+        resume(&mut initiator, &mut responder);
+        transfer(&mut initiator, &mut responder, 3).unwrap();
+
+        // Unattested parties are rejected if enclave identities are required.
+        let mut initiator = Builder::new()
+            .local_rak(initiator_rak.clone())
+            .payload_format(PayloadFormat::Libp2p)
+            .build_initiator();
+        let mut responder = Builder::new()
+            .payload_format(PayloadFormat::Libp2p)
+            .remote_enclaves(Some(HashSet::new()))
+            .build_responder();
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        let mut reply = vec![];
+        responder.process_data(buffer, &mut reply).unwrap();
+        let mut buffer = vec![];
+        initiator.process_data(reply, &mut buffer).unwrap();
+        assert!(responder.process_data(buffer, vec![]).is_err());
+
+        // Other bindings and mutual attestation are not supported.
+        let mut initiator = Builder::new()
+            .handshake_binding(HandshakeBinding::Transcript)
+            .build_initiator();
+        let mut responder = Builder::new()
+            .payload_format(PayloadFormat::Libp2p)
+            .build_responder();
+        let mut buffer = vec![];
+        initiator.process_data(vec![], &mut buffer).unwrap();
+        assert!(responder.process_data(buffer, vec![]).is_err());
+        let mut initiator = Builder::new()
+            .local_rak(initiator_rak)
+            .payload_format(PayloadFormat::Libp2p)
+            .mutual_attestation(true)
+            .build_initiator();
+        assert!(initiator.process_data(vec![], vec![]).is_err());
+    }
+
+    #[test]
+    fn test_libp2p_interop() {
+        let noise_builder = || snow::Builder::new(NOISE_PATTERN.parse().unwrap());
+        let mut buf = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+        let peer_key = PrivateKey::generate();
+        let (rak, rak_pub) = unattested_rak();
+
+        // Session as initiator, libp2p peer as responder.
+        let keypair = noise_builder().generate_keypair().unwrap();
+        let mut peer = noise_builder()
+            .local_private_key(&keypair.private)
+            .build_responder()
+            .unwrap();
+        let mut session = Builder::new()
+            .local_rak(rak.clone())
+            .payload_format(PayloadFormat::Libp2p)
+            .build_initiator();
+
+        let mut msg1 = vec![];
+        session.process_data(vec![], &mut msg1).unwrap();
+        assert_eq!(0, peer.read_message(&msg1, &mut buf).unwrap());
+        let len = peer
+            .write_message(&libp2p_payload(&peer_key, &keypair.public), &mut buf)
+            .unwrap();
+        let mut msg3 = vec![];
+        session
+            .process_data(buf[..len].to_vec(), &mut msg3)
+            .unwrap();
+        let len = peer.read_message(&msg3, &mut buf).unwrap();
+        check_libp2p_payload(&buf[..len], &rak_pub, peer.get_remote_static().unwrap());
+        assert!(session.is_connected());
+        assert_eq!(Some(peer_key.public_key()), session.remote_identity());
+
+        // Transport messages use the keys of the standard split.
+        let mut peer = peer.into_transport_mode().unwrap();
+        let len = peer
+            .write_message(&cbor::to_vec(&message(1)), &mut buf)
+            .unwrap();
+        assert!(session
+            .process_data(buf[..len].to_vec(), vec![])
+            .unwrap()
+            .is_some());
+
+        // Session as responder, libp2p peer as initiator.
+        let keypair = noise_builder().generate_keypair().unwrap();
+        let mut peer = noise_builder()
+            .local_private_key(&keypair.private)
+            .build_initiator()
+            .unwrap();
+        let mut session = Builder::new()
+            .local_rak(rak.clone())
+            .payload_format(PayloadFormat::Libp2p)
+            .build_responder();
+
+        let len = peer.write_message(&[], &mut buf).unwrap();
+        let mut msg2 = vec![];
+        session
+            .process_data(buf[..len].to_vec(), &mut msg2)
+            .unwrap();
+        let len = peer.read_message(&msg2, &mut buf).unwrap();
+        check_libp2p_payload(&buf[..len], &rak_pub, peer.get_remote_static().unwrap());
+        let len = peer
+            .write_message(&libp2p_payload(&peer_key, &keypair.public), &mut buf)
+            .unwrap();
+        session.process_data(buf[..len].to_vec(), vec![]).unwrap();
+        assert!(session.is_connected());
+        assert_eq!(Some(peer_key.public_key()), session.remote_identity());
+
+        // Signatures over another static key are rejected.
+        let keypair = noise_builder().generate_keypair().unwrap();
+        let mut peer = noise_builder()
+            .local_private_key(&keypair.private)
+            .build_responder()
+            .unwrap();
+        let mut session = Builder::new()
+            .payload_format(PayloadFormat::Libp2p)
+            .build_initiator();
+
+        let mut msg1 = vec![];
+        session.process_data(vec![], &mut msg1).unwrap();
+        peer.read_message(&msg1, &mut buf).unwrap();
+        let len = peer
+            .write_message(&libp2p_payload(&peer_key, &[0u8; 32]), &mut buf)
+            .unwrap();
+        assert!(session.process_data(buf[..len].to_vec(), vec![]).is_err());
+    }
+
+    #[test]
+    fn test_session_resumption() {
+        let mut initiator = Builder::new().build_initiator();
+        let mut responder = Builder::new().build_responder();
+        assert!(initiator.export_state().is_err());
+
+        // Perform the handshake.
+        handshake(&mut initiator, &mut responder);
+
+        transfer(&mut initiator, &mut responder, 1).unwrap();
+        transfer(&mut responder, &mut initiator, 2).unwrap();
+
+        // Resume the initiator from its sealed state.
+        let sealed = initiator.export_state().unwrap().seal();
+        drop(initiator);
+        let state = SessionState::unseal(&sealed).unwrap().unwrap();
+        let mut initiator = Builder::new().build_resumed(state).unwrap();
+        assert!(!initiator.is_connected());
+        resume(&mut initiator, &mut responder);
+        transfer(&mut initiator, &mut responder, 3).unwrap();
+        transfer(&mut responder, &mut initiator, 4).unwrap();
+
+        // The session can be resumed again from its new state.
+        let state = initiator.export_state().unwrap();
+        let mut initiator = Builder::new().build_resumed(state).unwrap();
+        resume(&mut initiator, &mut responder);
+        transfer(&mut initiator, &mut responder, 5).unwrap();
+        transfer(&mut responder, &mut initiator, 6).unwrap();
+
+        // Stale (e.g., rolled back) state is rejected by the remote party, as
+        // the resumption secret has changed.
+        let stale = SessionState::unseal(&sealed).unwrap().unwrap();
+        let mut stale = Builder::new().build_resumed(stale).unwrap();
+        let mut buffer = vec![];
+        stale.process_data(vec![], &mut buffer).unwrap();
+        assert!(responder.process_data(buffer, vec![]).is_err());
+
+        assert!(SessionState::unseal(&[]).unwrap().is_none());
     }
 }
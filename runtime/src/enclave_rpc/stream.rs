@@ -0,0 +1,140 @@
+//! Server-streaming responses.
+//!
+//! A streaming method returns a sequence of items instead of a single
+//! response. The items are produced lazily, one chunk at a time as they are
+//! requested by the client (see `types::StreamChunk`), so arbitrarily large
+//! responses can be returned without buffering them in the enclave.
+use anyhow::Result;
+use thiserror::Error;
+
+use super::types::{Body, Response, StreamChunk};
+use crate::common::cbor::Value;
+
+/// Stream error.
+#[derive(Error, Debug)]
+pub enum StreamError {
+    #[error("no active response stream")]
+    NoActiveStream,
+    #[error("unexpected stream chunk requested (expected {expected} got {seq})")]
+    UnexpectedSequence { expected: u64, seq: u64 },
+    #[error("response stream has ended")]
+    Ended,
+}
+
+/// Items of a response stream.
+pub type StreamItems<T = Value> = Box<dyn Iterator<Item = Result<T>> + Send>;
+
+/// Response stream of a streaming method.
+pub struct ResponseStream {
+    /// Remaining items of the stream.
+    items: StreamItems,
+    /// Sequence number of the next chunk.
+    next_seq: u64,
+    /// Last produced chunk, kept for retransmission.
+    last: Option<StreamChunk>,
+    /// Flag indicating whether producing an item has failed.
+    failed: bool,
+}
+
+impl ResponseStream {
+    /// Create a new response stream from the given items.
+    pub fn new(items: StreamItems) -> Self {
+        Self {
+            items,
+            next_seq: 0,
+            last: None,
+            failed: false,
+        }
+    }
+
+    /// Produce the chunk with the given sequence number.
+    ///
+    /// Only the next chunk can be produced, or the last chunk retransmitted
+    /// in case it has been lost in transit.
+    pub fn next_chunk(&mut self, seq: u64) -> Result<StreamChunk> {
+        if self.failed {
+            return Err(StreamError::Ended.into());
+        }
+        if let Some(ref last) = self.last {
+            if last.seq == seq {
+                return Ok(last.clone());
+            }
+            if last.is_end() {
+                return Err(StreamError::Ended.into());
+            }
+        }
+        if seq != self.next_seq {
+            return Err(StreamError::UnexpectedSequence {
+                expected: self.next_seq,
+                seq,
+            }
+            .into());
+        }
+
+        let item = match self.items.next().transpose() {
+            Ok(item) => item,
+            Err(error) => {
+                self.failed = true;
+                return Err(error);
+            }
+        };
+        let chunk = StreamChunk { seq, item };
+        self.next_seq += 1;
+        self.last = Some(chunk.clone());
+
+        Ok(chunk)
+    }
+
+    /// Produce the response carrying the chunk with the given sequence number.
+    pub fn next_response(&mut self, seq: u64) -> Response {
+        match self.next_chunk(seq) {
+            Ok(chunk) => Response {
+                body: Body::Chunk(chunk),
+            },
+            Err(error) => Response {
+                body: Body::Error(format!("{}", error)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::common::cbor;
+
+    #[test]
+    fn test_response_stream() {
+        let items = vec![Ok(cbor::to_value(1u64)), Ok(cbor::to_value(2u64))];
+        let mut stream = ResponseStream::new(Box::new(items.into_iter()));
+
+        assert!(
+            stream.next_chunk(1).is_err(),
+            "chunks must be requested in order"
+        );
+        let chunk = stream.next_chunk(0).unwrap();
+        assert_eq!(Some(cbor::to_value(1u64)), chunk.item);
+        let chunk = stream.next_chunk(1).unwrap();
+        assert_eq!(Some(cbor::to_value(2u64)), chunk.item);
+        // Retransmission.
+        let chunk = stream.next_chunk(1).unwrap();
+        assert_eq!(Some(cbor::to_value(2u64)), chunk.item);
+        assert!(stream.next_chunk(0).is_err(), "only the last chunk is kept");
+
+        let chunk = stream.next_chunk(2).unwrap();
+        assert!(chunk.is_end());
+        assert!(stream.next_chunk(2).unwrap().is_end());
+        assert!(stream.next_chunk(3).is_err(), "stream has ended");
+
+        let items = vec![Ok(cbor::to_value(1u64)), Err(anyhow!("failed"))];
+        let mut stream = ResponseStream::new(Box::new(items.into_iter()));
+        assert!(stream.next_chunk(0).is_ok());
+        match stream.next_response(1).body {
+            Body::Error(error) => assert_eq!("failed", error),
+            body => panic!("unexpected response body: {:?}", body),
+        }
+        assert!(stream.next_chunk(1).is_err(), "stream has failed");
+    }
+}
@@ -47,21 +47,50 @@ pub struct Error {
     pub message: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Body {
     Success(Value),
     Error(String),
+    /// A chunk of a streamed response.
+    Chunk(StreamChunk),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A chunk of a streamed response.
+///
+/// Chunks of a stream are numbered sequentially starting at zero, with the
+/// first chunk being sent in response to the request itself. Each further
+/// chunk must be requested by the client with a `StreamNext` message, so the
+/// server never produces more than the client is ready to consume.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamChunk {
+    /// Sequence number of the chunk.
+    pub seq: u64,
+    /// Item of the stream or `None` if this is the end of the stream.
+    pub item: Option<Value>,
+}
+
+impl StreamChunk {
+    /// Return whether the chunk marks the end of the stream.
+    pub fn is_end(&self) -> bool {
+        self.item.is_none()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Response {
     pub body: Body,
 }
 
 /// Protocol message.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Message {
     Request(Request),
     Response(Response),
     Close,
+    /// Request for the chunk with the given sequence number of the response
+    /// stream of the last request. Requesting the previous chunk again
+    /// retransmits it.
+    StreamNext {
+        seq: u64,
+    },
 }
@@ -0,0 +1,180 @@
+//! Attested status of the runtime identity.
+//!
+//! Remote attestations of the runtime attestation key expire, AVRs after a
+//! day and DCAP quotes once their collateral is due for an update. This keeps
+//! track of when the current attestation expires, asks the host to refresh it
+//! ahead of time and exposes the status of the attestation, so that the node
+//! can alert before the runtime loses its attested status.
+use std::{sync::RwLock, thread, time::Duration};
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+    logger::get_logger, sgx::avr::TIMESTAMP_FRESHNESS_PERIOD, time::insecure_posix_time,
+};
+
+/// How long before the attestation expires a refresh is requested (seconds).
+const REFRESH_MARGIN: i64 = 6 * 60 * 60;
+/// Minimum time between refresh requests (seconds).
+const REFRESH_RETRY_INTERVAL: i64 = 10 * 60;
+/// Interval at which the refresh worker checks whether a refresh is due.
+const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // Attestation tracker of the runtime.
+    static ref TRACKER: RwLock<Tracker> = RwLock::new(Tracker::default());
+}
+
+/// Attested status of the runtime identity.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationStatus {
+    /// Whether the runtime attestation key has an attestation which has not
+    /// expired yet.
+    pub attested: bool,
+    /// Time of the current attestation (POSIX timestamp).
+    pub attested_at: Option<i64>,
+    /// Time at which the current attestation expires (POSIX timestamp).
+    pub expires_at: Option<i64>,
+    /// Time from which a refresh is requested from the host (POSIX
+    /// timestamp).
+    pub refresh_at: Option<i64>,
+    /// Error of the last refresh request, if it failed.
+    pub last_refresh_error: Option<String>,
+}
+
+#[derive(Default)]
+struct Tracker {
+    attested_at: Option<i64>,
+    expires_at: Option<i64>,
+    // Whether the runtime has been attested before, in which case losing the
+    // attestation also requires a refresh.
+    was_attested: bool,
+    last_refresh_request: Option<i64>,
+    last_refresh_error: Option<String>,
+}
+
+impl Tracker {
+    fn status(&self, now: i64) -> AttestationStatus {
+        AttestationStatus {
+            attested: self.expires_at.map_or(false, |expires_at| now < expires_at),
+            attested_at: self.attested_at,
+            expires_at: self.expires_at,
+            refresh_at: self
+                .expires_at
+                .map(|expires_at| expires_at - REFRESH_MARGIN),
+            last_refresh_error: self.last_refresh_error.clone(),
+        }
+    }
+
+    fn refresh_due(&self, now: i64) -> bool {
+        if let Some(last_refresh_request) = self.last_refresh_request {
+            if now - last_refresh_request < REFRESH_RETRY_INTERVAL {
+                return false;
+            }
+        }
+
+        match self.expires_at {
+            Some(expires_at) => now >= expires_at - REFRESH_MARGIN,
+            None => self.was_attested,
+        }
+    }
+}
+
+/// Attested status of the runtime identity.
+pub fn attestation_status() -> AttestationStatus {
+    TRACKER.read().unwrap().status(insecure_posix_time())
+}
+
+/// Record a new attestation of the runtime identity, made at the given time
+/// and verified with collateral valid until the given time (if limited).
+#[cfg_attr(
+    not(any(target_env = "sgx", feature = "debug-mock-sgx")),
+    allow(dead_code)
+)]
+pub(crate) fn record_attestation(attested_at: i64, valid_until: Option<i64>) {
+    let mut expires_at = attested_at + TIMESTAMP_FRESHNESS_PERIOD;
+    if let Some(valid_until) = valid_until {
+        expires_at = expires_at.min(valid_until);
+    }
+
+    let mut tracker = TRACKER.write().unwrap();
+    tracker.attested_at = Some(attested_at);
+    tracker.expires_at = Some(expires_at);
+    tracker.was_attested = true;
+}
+
+/// Record that the attestation of the runtime identity has been discarded.
+pub(crate) fn clear_attestation() {
+    let mut tracker = TRACKER.write().unwrap();
+    tracker.attested_at = None;
+    tracker.expires_at = None;
+}
+
+/// Request refreshes of the attestation of the runtime identity using the
+/// given function (e.g., through the runtime host) before it expires.
+///
+/// Refreshes are requested from a separate thread. Failed requests are
+/// retried, at most once every ten minutes.
+#[cfg_attr(not(target_env = "sgx"), allow(unused))]
+pub(crate) fn schedule_refresh<F>(refresh: F)
+where
+    F: Fn() -> Result<()> + Send + 'static,
+{
+    let logger = get_logger("runtime/identity");
+
+    thread::spawn(move || loop {
+        thread::sleep(REFRESH_CHECK_INTERVAL);
+
+        let now = insecure_posix_time();
+        {
+            let mut tracker = TRACKER.write().unwrap();
+            if !tracker.refresh_due(now) {
+                continue;
+            }
+            tracker.last_refresh_request = Some(now);
+        }
+
+        info!(logger, "Requesting attestation refresh";
+            "expires_at" => ?TRACKER.read().unwrap().expires_at,
+        );
+        let result = refresh();
+        if let Err(ref error) = result {
+            warn!(logger, "Failed to request attestation refresh"; "err" => %error);
+        }
+        TRACKER.write().unwrap().last_refresh_error = result.err().map(|err| err.to_string());
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_refresh_due() {
+        let now = 1640995200; // 2022-01-01T00:00:00
+        let mut tracker = Tracker::default();
+        assert!(!tracker.refresh_due(now), "never attested");
+        assert!(!tracker.status(now).attested);
+
+        tracker.attested_at = Some(now);
+        tracker.expires_at = Some(now + TIMESTAMP_FRESHNESS_PERIOD);
+        tracker.was_attested = true;
+        assert!(tracker.status(now).attested);
+        assert!(!tracker.refresh_due(now));
+        let refresh_at = tracker.status(now).refresh_at.unwrap();
+        assert!(tracker.refresh_due(refresh_at));
+
+        tracker.last_refresh_request = Some(refresh_at);
+        assert!(!tracker.refresh_due(refresh_at + 1), "throttled");
+        assert!(tracker.refresh_due(refresh_at + REFRESH_RETRY_INTERVAL));
+
+        let expired = now + TIMESTAMP_FRESHNESS_PERIOD;
+        assert!(!tracker.status(expired).attested);
+
+        tracker.attested_at = None;
+        tracker.expires_at = None;
+        assert!(tracker.refresh_due(expired), "attestation discarded");
+    }
+}
@@ -1,24 +1,48 @@
 //! Runtime initialization.
 use crate::{
     common::{
-        logger::{get_logger, init_logger},
+        logger::{get_logger, init_logger_with_config, LoggerConfig},
         version::Version,
     },
+    consensus::verifier::Config as VerifierConfig,
     dispatcher::{Dispatcher, Initializer},
-    protocol::{Protocol, Stream},
+    protocol::{Protocol, ProtocolConfig, Stream},
     rak::RAK,
 };
 
-use log;
 use std::{env, sync::Arc};
 
+/// Runtime configuration.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// Runtime host protocol configuration.
+    pub protocol: ProtocolConfig,
+    /// Logging configuration.
+    pub logger: LoggerConfig,
+    /// Consensus light client verifier configuration, if the runtime verifies
+    /// the consensus layer.
+    ///
+    /// The staleness policy of its options bounds how old the consensus state
+    /// made available to queries may be.
+    pub consensus_verifier: Option<VerifierConfig>,
+}
+
 /// Starts the runtime.
 pub fn start_runtime(initializer: Box<dyn Initializer>, version: Version) {
+    start_runtime_with_config(initializer, version, Config::default())
+}
+
+/// Starts the runtime with the given configuration.
+pub fn start_runtime_with_config(
+    initializer: Box<dyn Initializer>,
+    version: Version,
+    config: Config,
+) {
     // Output backtraces.
     env::set_var("RUST_BACKTRACE", "1");
 
     // Initialize logging.
-    init_logger(log::Level::Info);
+    init_logger_with_config(config.logger);
     let logger = get_logger("runtime");
     info!(logger, "Runtime is starting");
 
@@ -26,7 +50,7 @@ pub fn start_runtime(initializer: Box<dyn Initializer>, version: Version) {
     let rak = Arc::new(RAK::new());
 
     // Initialize the dispatcher.
-    let dispatcher = Dispatcher::new(initializer, rak.clone());
+    let dispatcher = Dispatcher::new(initializer, rak.clone(), config.consensus_verifier);
 
     info!(logger, "Establishing connection with the worker host");
 
@@ -55,6 +79,7 @@ pub fn start_runtime(initializer: Box<dyn Initializer>, version: Version) {
         rak.clone(),
         dispatcher.clone(),
         version,
+        config.protocol,
     ));
 
     protocol.start();
@@ -13,6 +13,7 @@
 #![feature(test)]
 #![feature(box_into_pin)]
 #![feature(arbitrary_self_types)]
+#![feature(min_const_generics)]
 
 #[macro_use]
 extern crate slog;
@@ -49,12 +50,15 @@ use sgx_isa::{AttributesFlags, Report};
 
 #[macro_use]
 pub mod common;
+pub mod consensus;
 pub mod dispatcher;
 pub mod enclave_rpc;
 pub mod executor;
+pub mod identity;
 pub mod init;
 pub mod macros;
 pub mod protocol;
+pub mod query;
 pub mod rak;
 pub mod storage;
 pub mod tracing;
@@ -100,6 +104,9 @@ lazy_static! {
             let fortanix_mrsigner = MrSigner::from("9affcfae47b848ec2caf1c49b4b283531e1cc425f93582b36806e52a43d78d1a");
             let maybe_secure = maybe_secure && (enclave_identity.mr_signer != fortanix_mrsigner);
 
+            // Mock attestation MUST NOT be enabled.
+            let maybe_secure = maybe_secure && !cfg!(feature = "debug-mock-sgx");
+
             maybe_secure
         };
 
@@ -121,7 +128,8 @@ pub struct BuildInfo {
 // Re-exports.
 pub use self::{
     enclave_rpc::{demux::Demux as RpcDemux, dispatcher::Dispatcher as RpcDispatcher},
-    init::start_runtime,
-    protocol::Protocol,
+    init::{start_runtime, start_runtime_with_config, Config as RuntimeConfig},
+    protocol::{Protocol, ProtocolConfig},
+    query::QueryRouter,
     transaction::dispatcher::{Dispatcher as TxnDispatcher, MethodDispatcher as TxnMethDispatcher},
 };
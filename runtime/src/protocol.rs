@@ -1,27 +1,39 @@
 //! Runtime side of the worker-host protocol.
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io::{BufReader, BufWriter, Read, Write},
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc, Mutex,
     },
+    thread,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crossbeam::channel;
 use io_context::Context;
+use serde_bytes::ByteBuf;
 use slog::Logger;
 use thiserror::Error;
 
 use crate::{
-    common::{cbor, logger::get_logger, runtime::RuntimeId, version::Version},
+    common::{
+        cbor,
+        logger::{self, get_logger},
+        metrics,
+        runtime::RuntimeId,
+        sgx::QuotePolicy,
+        version::Version,
+    },
+    consensus::{epoch::EpochInfo, verifier::HostVerifier},
     dispatcher::Dispatcher,
+    identity,
     rak::RAK,
     storage::KeyValue,
     tracing,
-    types::{Body, Message, MessageType},
+    types::{Body, LocalStorageItem, Message, MessageHeader, MessageType, ProtocolVersion},
     BUILD_INFO,
 };
 
@@ -30,13 +42,24 @@ pub type Stream = ::std::os::unix::net::UnixStream;
 #[cfg(target_env = "sgx")]
 pub type Stream = ::std::net::TcpStream;
 
-/// Maximum message size.
-const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024; // 16MiB
+/// Default maximum message size.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024; // 16MiB
+/// Default maximum size of incoming requests being handled at once.
+const DEFAULT_MAX_IN_FLIGHT_BYTES: usize = 64 * 1024 * 1024; // 64MiB
+/// Maximum number of requests kept in flight by `Protocol::make_requests`.
+pub const MAX_IN_FLIGHT_REQUESTS: usize = 32;
+/// Interval at which requests waiting for a response check whether their
+/// context has been cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+type PendingRequests = Arc<Mutex<HashMap<u64, channel::Sender<Body>>>>;
 
 #[derive(Error, Debug)]
 pub enum ProtocolError {
-    #[error("message too large")]
-    MessageTooLarge,
+    #[error("message too large ({size} bytes, maximum is {max} bytes)")]
+    MessageTooLarge { size: usize, max: usize },
+    #[error("too many requests in flight ({size} bytes, limit is {max} bytes)")]
+    InFlightLimitExceeded { size: usize, max: usize },
     #[error("method not supported")]
     MethodNotSupported,
     #[error("invalid response")]
@@ -46,12 +69,107 @@ pub enum ProtocolError {
     AttestationRequired,
     #[error("runtime id not set")]
     RuntimeIDNotSet,
+    #[error("incompatible host protocol version (host: {host} runtime: {runtime})")]
+    IncompatibleVersion { host: Version, runtime: Version },
+    #[error("message not supported by host (requires protocol version {0})")]
+    UnsupportedByHost(Version),
+    #[error("unsupported message: {0}")]
+    UnsupportedMessage(String),
+    #[error("request cancelled")]
+    RequestCancelled,
+    #[error("connection to host closed")]
+    ConnectionClosed,
+}
+
+/// Runtime host protocol configuration.
+#[derive(Clone, Debug)]
+pub struct ProtocolConfig {
+    /// Maximum size of a single message in bytes.
+    ///
+    /// Larger incoming messages are rejected before any memory is allocated
+    /// for them, and terminate the connection as the host is misbehaving.
+    pub max_message_size: usize,
+    /// Maximum total size in bytes of the incoming requests queued for or
+    /// being handled by the dispatcher.
+    ///
+    /// Requests which would exceed the limit are failed immediately, so that
+    /// the host must wait for responses before sending more. A single request
+    /// is always accepted when no other requests are in flight.
+    pub max_in_flight_bytes: usize,
+}
+
+impl Default for ProtocolConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_in_flight_bytes: DEFAULT_MAX_IN_FLIGHT_BYTES,
+        }
+    }
+}
+
+/// Incoming requests which are handled asynchronously by the dispatcher.
+#[derive(Default)]
+struct InFlightRequests {
+    /// Total size of the in-flight requests.
+    bytes: usize,
+    /// Sizes of the in-flight requests by request identifier.
+    sizes: HashMap<u64, usize>,
+}
+
+/// An outgoing request waiting for its response from the worker host.
+///
+/// Dropping the request before the response arrives cancels it, in which
+/// case the response is discarded when it arrives.
+pub struct PendingResponse {
+    id: u64,
+    ctx: Context,
+    rx: channel::Receiver<Body>,
+    pending_requests: PendingRequests,
+}
+
+impl PendingResponse {
+    /// Identifier of the request.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Wait for the response.
+    ///
+    /// Returns `ProtocolError::RequestCancelled` if the request context is
+    /// cancelled or its deadline passes before the response arrives.
+    pub fn wait(self) -> Result<Body> {
+        loop {
+            match self.rx.recv_timeout(CANCEL_POLL_INTERVAL) {
+                Ok(Body::Error { message, .. }) => return Err(anyhow!("{}", message)),
+                Ok(body) => return Ok(body),
+                Err(channel::RecvTimeoutError::Timeout) => {
+                    if self.ctx.done().is_some() {
+                        return Err(ProtocolError::RequestCancelled.into());
+                    }
+                }
+                Err(channel::RecvTimeoutError::Disconnected) => {
+                    return Err(ProtocolError::ConnectionClosed.into())
+                }
+            }
+        }
+    }
+
+    /// Cancel the request without waiting for the response.
+    pub fn cancel(self) {}
+}
+
+impl Drop for PendingResponse {
+    fn drop(&mut self) {
+        self.pending_requests.lock().unwrap().remove(&self.id);
+    }
 }
 
 /// Runtime part of the runtime host protocol.
 pub struct Protocol {
     /// Logger.
     logger: Logger,
+    /// Protocol configuration.
+    config: ProtocolConfig,
     /// Runtime attestation key.
     #[cfg_attr(not(target_env = "sgx"), allow(unused))]
     rak: Arc<RAK>,
@@ -64,11 +182,15 @@ pub struct Protocol {
     /// Outgoing request identifier generator.
     last_request_id: AtomicUsize,
     /// Pending outgoing requests.
-    pending_out_requests: Mutex<HashMap<u64, channel::Sender<Body>>>,
+    pending_out_requests: PendingRequests,
+    /// Incoming requests being handled by the dispatcher.
+    in_flight_requests: Mutex<InFlightRequests>,
     /// Runtime identifier.
     runtime_id: Mutex<Option<RuntimeId>>,
     /// Runtime version.
     runtime_version: Version,
+    /// Protocol version negotiated with the host.
+    protocol_version: Mutex<ProtocolVersion>,
 }
 
 impl Protocol {
@@ -78,19 +200,23 @@ impl Protocol {
         rak: Arc<RAK>,
         dispatcher: Arc<Dispatcher>,
         runtime_version: Version,
+        config: ProtocolConfig,
     ) -> Self {
         let logger = get_logger("runtime/protocol");
 
         Self {
             logger,
+            config,
             rak,
             dispatcher,
             outgoing_mutex: Mutex::new(()),
             stream,
             last_request_id: AtomicUsize::new(0),
-            pending_out_requests: Mutex::new(HashMap::new()),
+            pending_out_requests: Arc::new(Mutex::new(HashMap::new())),
+            in_flight_requests: Mutex::new(InFlightRequests::default()),
             runtime_id: Mutex::new(None),
             runtime_version: runtime_version,
+            protocol_version: Mutex::new(ProtocolVersion::V1_0),
         }
     }
 
@@ -106,6 +232,20 @@ impl Protocol {
             .expect("runtime_id should be set")
     }
 
+    /// Return the protocol version negotiated with the host.
+    ///
+    /// Before the host has sent its runtime info request, this is the
+    /// initial protocol version.
+    pub fn get_protocol_version(&self) -> ProtocolVersion {
+        *self.protocol_version.lock().unwrap()
+    }
+
+    /// Return the consensus light client verifier, if the runtime has been
+    /// configured with one.
+    pub fn consensus_verifier(&self) -> Option<Arc<HostVerifier>> {
+        self.dispatcher.consensus_verifier()
+    }
+
     /// Start the protocol handler loop.
     pub fn start(self: &Arc<Protocol>) {
         info!(self.logger, "Starting protocol handler");
@@ -121,11 +261,57 @@ impl Protocol {
             }
         }
 
+        // Fail any requests still waiting for a response.
+        self.pending_out_requests.lock().unwrap().clear();
+
         info!(self.logger, "Protocol handler is terminating");
     }
 
     /// Make a new request to the worker host and wait for the response.
+    ///
+    /// Returns `ProtocolError::UnsupportedByHost` if the host does not
+    /// support the request.
     pub fn make_request(&self, ctx: Context, body: Body) -> Result<Body> {
+        self.send_request(ctx, body)?.wait()
+    }
+
+    /// Make several requests to the worker host and wait for all of the
+    /// responses, which are returned in request order.
+    ///
+    /// Up to `MAX_IN_FLIGHT_REQUESTS` requests are in flight at once, so the
+    /// host may handle them concurrently. Fails on the first failed request
+    /// and cancels the remaining ones.
+    pub fn make_requests(&self, ctx: Context, bodies: Vec<Body>) -> Result<Vec<Body>> {
+        let ctx = ctx.freeze();
+        let mut responses = Vec::with_capacity(bodies.len());
+        let mut in_flight = VecDeque::with_capacity(MAX_IN_FLIGHT_REQUESTS);
+        for body in bodies {
+            if in_flight.len() == MAX_IN_FLIGHT_REQUESTS {
+                let pending: PendingResponse = in_flight.pop_front().unwrap();
+                responses.push(pending.wait()?);
+            }
+            in_flight.push_back(self.send_request(Context::create_child(&ctx), body)?);
+        }
+        for pending in in_flight {
+            responses.push(pending.wait()?);
+        }
+
+        Ok(responses)
+    }
+
+    /// Send a new request to the worker host without waiting for the
+    /// response.
+    ///
+    /// Returns `ProtocolError::UnsupportedByHost` if the host does not
+    /// support the request.
+    pub fn send_request(&self, ctx: Context, body: Body) -> Result<PendingResponse> {
+        if !self.get_protocol_version().supports(&body) {
+            return Err(ProtocolError::UnsupportedByHost(body.min_protocol_version().0).into());
+        }
+        if ctx.done().is_some() {
+            return Err(ProtocolError::RequestCancelled.into());
+        }
+
         let id = self.last_request_id.fetch_add(1, Ordering::SeqCst) as u64;
         let span_context = tracing::get_span_context(&ctx).unwrap_or(&vec![]).clone();
         let message = Message {
@@ -141,18 +327,23 @@ impl Protocol {
             let mut pending_requests = self.pending_out_requests.lock().unwrap();
             pending_requests.insert(id, tx);
         }
+        let pending = PendingResponse {
+            id,
+            ctx,
+            rx,
+            pending_requests: self.pending_out_requests.clone(),
+        };
 
-        // Write message to stream and wait for the response.
+        // Write message to stream, the response is delivered by the protocol
+        // handler loop.
         self.encode_message(message)?;
 
-        match rx.recv()? {
-            Body::Error { message, .. } => Err(anyhow!("{}", message)),
-            body => Ok(body),
-        }
+        Ok(pending)
     }
 
     /// Send an async response to a previous request back to the worker host.
     pub fn send_response(&self, id: u64, body: Body) -> Result<()> {
+        self.release_in_flight(id);
         self.encode_message(Message {
             id,
             body,
@@ -161,17 +352,21 @@ impl Protocol {
         })
     }
 
-    fn decode_message<R: Read>(&self, mut reader: R) -> Result<Message> {
+    fn read_message<R: Read>(&self, mut reader: R) -> Result<Vec<u8>> {
         let length = reader.read_u32::<BigEndian>()? as usize;
-        if length > MAX_MESSAGE_SIZE {
-            return Err(ProtocolError::MessageTooLarge.into());
+        if length > self.config.max_message_size {
+            return Err(ProtocolError::MessageTooLarge {
+                size: length,
+                max: self.config.max_message_size,
+            }
+            .into());
         }
 
         // TODO: Avoid allocations.
         let mut buffer = vec![0; length];
         reader.read_exact(&mut buffer)?;
 
-        Ok(cbor::from_slice(&buffer)?)
+        Ok(buffer)
     }
 
     fn encode_message(&self, message: Message) -> Result<()> {
@@ -179,8 +374,12 @@ impl Protocol {
         let mut writer = BufWriter::new(&self.stream);
 
         let buffer = cbor::to_vec(&message);
-        if buffer.len() > MAX_MESSAGE_SIZE {
-            return Err(ProtocolError::MessageTooLarge.into());
+        if buffer.len() > self.config.max_message_size {
+            return Err(ProtocolError::MessageTooLarge {
+                size: buffer.len(),
+                max: self.config.max_message_size,
+            }
+            .into());
         }
 
         writer.write_u32::<BigEndian>(buffer.len() as u32)?;
@@ -190,7 +389,11 @@ impl Protocol {
     }
 
     fn handle_message<R: Read>(self: &Arc<Protocol>, reader: R) -> Result<()> {
-        let message = self.decode_message(reader)?;
+        let buffer = self.read_message(reader)?;
+        let message: Message = match cbor::from_slice(&buffer) {
+            Ok(message) => message,
+            Err(error) => return self.handle_unsupported_message(&buffer, error.to_string()),
+        };
 
         match message.message_type {
             MessageType::Request => {
@@ -199,7 +402,7 @@ impl Protocol {
                 let mut ctx = Context::background();
                 tracing::add_span_context(&mut ctx, message.span_context);
 
-                let body = match self.handle_request(ctx, id, message.body) {
+                let body = match self.handle_request(ctx, id, message.body, buffer.len()) {
                     Ok(Some(result)) => result,
                     Ok(None) => {
                         // A message will be sent later by another thread so there
@@ -223,21 +426,7 @@ impl Protocol {
             }
             MessageType::Response => {
                 // Response to our request.
-                let response_sender = {
-                    let mut pending_requests = self.pending_out_requests.lock().unwrap();
-                    pending_requests.remove(&message.id)
-                };
-
-                match response_sender {
-                    Some(response_sender) => {
-                        if let Err(error) = response_sender.try_send(message.body) {
-                            warn!(self.logger, "Unable to deliver response to local handler"; "err" => %error);
-                        }
-                    }
-                    None => {
-                        warn!(self.logger, "Received response message for unknown request"; "msg_id" => message.id);
-                    }
-                }
+                self.deliver_response(message.id, message.body);
             }
             _ => warn!(self.logger, "Received a malformed message"),
         }
@@ -245,14 +434,104 @@ impl Protocol {
         Ok(())
     }
 
+    /// Handle a message whose body cannot be decoded, e.g. because it was
+    /// introduced in a later version of the protocol, by failing only the
+    /// affected request.
+    fn handle_unsupported_message(&self, buffer: &[u8], error: String) -> Result<()> {
+        // Messages without a valid header are fatal.
+        let header: MessageHeader = cbor::from_slice(buffer)?;
+        warn!(self.logger, "Received unsupported message";
+            "msg_id" => header.id,
+            "err" => %error,
+        );
+
+        let body = Body::Error {
+            module: "".to_owned(), // XXX: Error codes.
+            code: 0,               // XXX: Error codes.
+            message: format!("{}", ProtocolError::UnsupportedMessage(error)),
+        };
+        match header.message_type {
+            MessageType::Request => self.send_response(header.id, body)?,
+            MessageType::Response => self.deliver_response(header.id, body),
+            _ => warn!(self.logger, "Received a malformed message"),
+        }
+
+        Ok(())
+    }
+
+    fn deliver_response(&self, id: u64, body: Body) {
+        let response_sender = {
+            let mut pending_requests = self.pending_out_requests.lock().unwrap();
+            pending_requests.remove(&id)
+        };
+
+        match response_sender {
+            Some(response_sender) => {
+                if let Err(error) = response_sender.try_send(body) {
+                    warn!(self.logger, "Unable to deliver response to local handler"; "err" => %error);
+                }
+            }
+            None => {
+                // The request may have been cancelled while waiting.
+                warn!(self.logger, "Received response message for unknown or cancelled request"; "msg_id" => id);
+            }
+        }
+    }
+
     fn handle_request(
         self: &Arc<Protocol>,
         ctx: Context,
         id: u64,
         request: Body,
+        size: usize,
     ) -> Result<Option<Body>> {
         match request {
-            Body::RuntimeInfoRequest { runtime_id } => {
+            Body::RuntimeInfoRequest {
+                runtime_id,
+                host_protocol_version,
+            } => {
+                // Negotiate the protocol version.
+                let host_protocol_version = host_protocol_version.map(Version::from);
+                let protocol_version = ProtocolVersion::negotiate(host_protocol_version)
+                    .ok_or_else(|| ProtocolError::IncompatibleVersion {
+                        host: host_protocol_version.unwrap_or(ProtocolVersion::V1_0.0),
+                        runtime: BUILD_INFO.protocol_version,
+                    })?;
+                info!(self.logger, "Negotiated runtime host protocol version";
+                    "version" => %protocol_version.0,
+                );
+                *self.protocol_version.lock().unwrap() = protocol_version;
+
+                // Forward log records to the host, if supported.
+                if protocol_version >= ProtocolVersion::V1_2 {
+                    let protocol = self.clone();
+                    logger::forward_records(move |records| {
+                        match protocol
+                            .make_request(Context::background(), Body::HostLogRequest { records })?
+                        {
+                            Body::HostLogResponse {} => Ok(()),
+                            _ => Err(ProtocolError::InvalidResponse.into()),
+                        }
+                    });
+                }
+
+                // Request attestation refreshes from the host, if supported.
+                #[cfg(target_env = "sgx")]
+                {
+                    if protocol_version >= ProtocolVersion::V1_10 {
+                        let protocol = self.clone();
+                        identity::schedule_refresh(move || {
+                            match protocol.make_request(
+                                Context::background(),
+                                Body::HostAttestationRefreshRequest {},
+                            )? {
+                                Body::HostAttestationRefreshResponse {} => Ok(()),
+                                _ => Err(ProtocolError::InvalidResponse.into()),
+                            }
+                        });
+                    }
+                }
+
                 // Store the passed Runtime ID.
                 *self.runtime_id.lock().unwrap() = Some(runtime_id);
 
@@ -264,6 +543,9 @@ impl Protocol {
                 }))
             }
             Body::RuntimePingRequest {} => Ok(Some(Body::Empty {})),
+            Body::RuntimeMetricsRequest {} => Ok(Some(Body::RuntimeMetricsResponse {
+                metrics: metrics::registry().gather(),
+            })),
             Body::RuntimeShutdownRequest {} => {
                 info!(self.logger, "Received worker shutdown request");
                 Err(ProtocolError::MethodNotSupported.into())
@@ -271,9 +553,27 @@ impl Protocol {
             req @ Body::RuntimeAbortRequest {} => {
                 info!(self.logger, "Received worker abort request");
                 self.can_handle_runtime_requests()?;
-                self.dispatcher.abort_and_wait(ctx, id, req)?;
-                info!(self.logger, "Handled worker abort request");
-                Ok(Some(Body::RuntimeAbortResponse {}))
+
+                // Wait for the abort in a separate thread, as the batch being
+                // aborted may be waiting for responses to its own requests.
+                let protocol = self.clone();
+                thread::spawn(move || {
+                    let body = match protocol.dispatcher.abort_and_wait(ctx, id, req) {
+                        Ok(()) => {
+                            info!(protocol.logger, "Handled worker abort request");
+                            Body::RuntimeAbortResponse {}
+                        }
+                        Err(error) => Body::Error {
+                            module: "".to_owned(), // XXX: Error codes.
+                            code: 0,               // XXX: Error codes.
+                            message: format!("{}", error),
+                        },
+                    };
+                    if let Err(error) = protocol.send_response(id, body) {
+                        error!(protocol.logger, "Failed to send abort response"; "err" => %error);
+                    }
+                });
+                Ok(None)
             }
             #[cfg(target_env = "sgx")]
             Body::RuntimeCapabilityTEERakInitRequest { target_info } => {
@@ -308,31 +608,52 @@ impl Protocol {
                 self.rak.set_avr(avr)?;
                 Ok(Some(Body::RuntimeCapabilityTEERakAvrResponse {}))
             }
-            req @ Body::RuntimeRPCCallRequest { .. } => {
-                self.can_handle_runtime_requests()?;
-                self.dispatcher.queue_request(ctx, id, req)?;
-                Ok(None)
+            #[cfg(target_env = "sgx")]
+            Body::RuntimeCapabilityTEERakQuoteRequest { quote } => {
+                info!(
+                    self.logger,
+                    "Configuring DCAP quote for the runtime attestation key binding"
+                );
+                self.rak.set_quote(quote)?;
+                Ok(Some(Body::RuntimeCapabilityTEERakQuoteResponse {}))
             }
-            req @ Body::RuntimeLocalRPCCallRequest { .. } => {
-                self.can_handle_runtime_requests()?;
-                self.dispatcher.queue_request(ctx, id, req)?;
-                Ok(None)
+            Body::RuntimeCapabilityTEEQuotePolicyUpdateRequest { policy } => {
+                info!(self.logger, "Configuring the quote policy"; "policy" => ?policy);
+                QuotePolicy::set_global(policy);
+                Ok(Some(Body::RuntimeCapabilityTEEQuotePolicyUpdateResponse {}))
             }
-            req @ Body::RuntimeCheckTxBatchRequest { .. } => {
-                self.can_handle_runtime_requests()?;
-                self.dispatcher.queue_request(ctx, id, req)?;
-                Ok(None)
+            Body::RuntimeConsensusEpochUpdateRequest {
+                epoch,
+                height,
+                beacon,
+            } => {
+                debug!(self.logger, "Received consensus epoch update";
+                    "epoch" => epoch,
+                    "height" => height,
+                );
+                self.dispatcher.epoch_tracker().update(EpochInfo {
+                    epoch,
+                    height,
+                    beacon,
+                });
+                Ok(Some(Body::RuntimeConsensusEpochUpdateResponse {}))
+            }
+            Body::RuntimeAttestationStatusRequest {} => {
+                Ok(Some(Body::RuntimeAttestationStatusResponse {
+                    status: identity::attestation_status(),
+                }))
             }
+            req @ Body::RuntimeRPCCallRequest { .. } => self.queue_request(ctx, id, req, size),
+            req @ Body::RuntimeLocalRPCCallRequest { .. } => self.queue_request(ctx, id, req, size),
+            req @ Body::RuntimeCheckTxBatchRequest { .. } => self.queue_request(ctx, id, req, size),
             req @ Body::RuntimeExecuteTxBatchRequest { .. } => {
-                self.can_handle_runtime_requests()?;
-                self.dispatcher.queue_request(ctx, id, req)?;
-                Ok(None)
+                self.queue_request(ctx, id, req, size)
             }
+            req @ Body::RuntimeQueryRequest { .. } => self.queue_request(ctx, id, req, size),
+            req @ Body::RuntimeIntrospectionRequest {} => self.queue_request(ctx, id, req, size),
             req @ Body::RuntimeKeyManagerPolicyUpdateRequest { .. } => {
                 info!(self.logger, "Received key manager policy update request");
-                self.can_handle_runtime_requests()?;
-                self.dispatcher.queue_request(ctx, id, req)?;
-                Ok(None)
+                self.queue_request(ctx, id, req, size)
             }
             req => {
                 warn!(self.logger, "Received unsupported request"; "req" => format!("{:?}", req));
@@ -341,6 +662,46 @@ impl Protocol {
         }
     }
 
+    /// Queue a request to be handled by the dispatcher, which sends the
+    /// response once done.
+    fn queue_request(
+        &self,
+        ctx: Context,
+        id: u64,
+        request: Body,
+        size: usize,
+    ) -> Result<Option<Body>> {
+        self.can_handle_runtime_requests()?;
+        self.reserve_in_flight(id, size)?;
+        if let Err(error) = self.dispatcher.queue_request(ctx, id, request) {
+            self.release_in_flight(id);
+            return Err(error);
+        }
+        Ok(None)
+    }
+
+    fn reserve_in_flight(&self, id: u64, size: usize) -> Result<()> {
+        let mut in_flight = self.in_flight_requests.lock().unwrap();
+        let bytes = in_flight.bytes.saturating_add(size);
+        if in_flight.bytes > 0 && bytes > self.config.max_in_flight_bytes {
+            return Err(ProtocolError::InFlightLimitExceeded {
+                size: bytes,
+                max: self.config.max_in_flight_bytes,
+            }
+            .into());
+        }
+        in_flight.bytes = bytes;
+        in_flight.sizes.insert(id, size);
+        Ok(())
+    }
+
+    fn release_in_flight(&self, id: u64) {
+        let mut in_flight = self.in_flight_requests.lock().unwrap();
+        if let Some(size) = in_flight.sizes.remove(&id) {
+            in_flight.bytes -= size;
+        }
+    }
+
     fn can_handle_runtime_requests(&self) -> Result<()> {
         if self.runtime_id.lock().unwrap().is_none() {
             return Err(ProtocolError::RuntimeIDNotSet.into());
@@ -348,7 +709,7 @@ impl Protocol {
 
         #[cfg(target_env = "sgx")]
         {
-            if self.rak.avr().is_none() {
+            if self.rak.quote().is_none() {
                 return Err(ProtocolError::AttestationRequired.into());
             }
         }
@@ -404,4 +765,65 @@ impl KeyValue for ProtocolUntrustedLocalStorage {
             Err(error) => Err(error),
         }
     }
+
+    fn get_batch(&self, keys: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>> {
+        if self.protocol.get_protocol_version() < ProtocolVersion::V1_1 {
+            // Older hosts do not support batches, fetch each key separately.
+            let ctx = Context::create_child(&self.ctx);
+            let requests = keys
+                .into_iter()
+                .map(|key| Body::HostLocalStorageGetRequest { key })
+                .collect();
+            return self
+                .protocol
+                .make_requests(ctx, requests)?
+                .into_iter()
+                .map(|response| match response {
+                    Body::HostLocalStorageGetResponse { value } => Ok(value),
+                    _ => Err(ProtocolError::InvalidResponse.into()),
+                })
+                .collect();
+        }
+
+        let ctx = Context::create_child(&self.ctx);
+        let count = keys.len();
+        let keys = keys.into_iter().map(ByteBuf::from).collect();
+
+        match self
+            .protocol
+            .make_request(ctx, Body::HostLocalStorageGetBatchRequest { keys })
+        {
+            Ok(Body::HostLocalStorageGetBatchResponse { values }) if values.len() == count => {
+                Ok(values.into_iter().map(ByteBuf::into_vec).collect())
+            }
+            Ok(_) => Err(ProtocolError::InvalidResponse.into()),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn insert_batch(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        if self.protocol.get_protocol_version() < ProtocolVersion::V1_1 {
+            // Older hosts do not support batches, store each key separately
+            // (which is not atomic).
+            for (key, value) in items {
+                self.insert(key, value)?;
+            }
+            return Ok(());
+        }
+
+        let ctx = Context::create_child(&self.ctx);
+        let items = items
+            .into_iter()
+            .map(|(key, value)| LocalStorageItem { key, value })
+            .collect();
+
+        match self
+            .protocol
+            .make_request(ctx, Body::HostLocalStorageSetBatchRequest { items })
+        {
+            Ok(Body::HostLocalStorageSetBatchResponse {}) => Ok(()),
+            Ok(_) => Err(ProtocolError::InvalidResponse.into()),
+            Err(error) => Err(error),
+        }
+    }
 }
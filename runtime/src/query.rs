@@ -0,0 +1,276 @@
+//! Runtime query dispatcher.
+//!
+//! Queries are read-only calls which are executed against the runtime state
+//! at a specific round, outside of any transaction. Query methods are
+//! registered by name (e.g., `accounts.Balance`) on a `QueryRouter`, which
+//! decodes their arguments and reports typed errors to the host.
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use io_context::Context as IoContext;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use crate::{
+    common::{cbor, roothash::Header, schema::MethodInfo},
+    consensus::{state::ConsensusState, verifier::HostVerifier},
+    protocol::Protocol,
+    storage::MKVS,
+};
+
+/// Name of the error module of query errors.
+pub const MODULE_NAME: &str = "runtime/query";
+
+/// Query errors.
+#[derive(Error, Debug)]
+pub enum QueryError {
+    #[error("query: method not found: {0}")]
+    MethodNotFound(String),
+    #[error("query: malformed arguments: {0}")]
+    MalformedArguments(String),
+    #[error("query: {0}")]
+    Failed(String),
+    #[error("query: consensus state not available")]
+    ConsensusNotAvailable,
+}
+
+impl QueryError {
+    /// Error code of the error, unique within `MODULE_NAME`.
+    pub fn code(&self) -> u32 {
+        match self {
+            QueryError::MethodNotFound(_) => 1,
+            QueryError::MalformedArguments(_) => 2,
+            QueryError::Failed(_) => 3,
+            QueryError::ConsensusNotAvailable => 4,
+        }
+    }
+}
+
+/// Query context.
+pub struct Context<'a> {
+    /// I/O context.
+    pub io_ctx: Arc<IoContext>,
+    /// The header of the block whose state is queried.
+    pub header: &'a Header,
+    /// Read-only state of the runtime at the queried round.
+    pub state: &'a dyn MKVS,
+    /// Consensus light client verifier and the protocol instance used to
+    /// fetch the consensus state, if the runtime verifies the consensus
+    /// layer.
+    pub consensus: Option<(&'a HostVerifier, &'a Arc<Protocol>)>,
+}
+
+impl<'a> Context<'a> {
+    /// Verified consensus state at the given height (or the latest height
+    /// with `HEIGHT_LATEST`).
+    ///
+    /// Fails with a `StaleState` error if the state is older than allowed by
+    /// the staleness policy configured at runtime initialization.
+    pub fn consensus_state(&self, height: i64) -> Result<ConsensusState> {
+        let (verifier, protocol) = self.consensus.ok_or(QueryError::ConsensusNotAvailable)?;
+        ConsensusState::from_verifier(
+            IoContext::create_child(&self.io_ctx),
+            verifier,
+            height,
+            protocol.clone(),
+        )
+    }
+}
+
+/// Handler for a query method.
+pub trait QueryHandler<Args, Output> {
+    /// Invoke the method implementation and return a response.
+    fn handle(&self, args: &Args, ctx: &Context) -> Result<Output>;
+}
+
+impl<Args, Output, F> QueryHandler<Args, Output> for F
+where
+    Args: 'static,
+    Output: 'static,
+    F: Fn(&Args, &Context) -> Result<Output> + 'static,
+{
+    fn handle(&self, args: &Args, ctx: &Context) -> Result<Output> {
+        (*self)(args, ctx)
+    }
+}
+
+trait QueryHandlerDispatch {
+    /// Dispatches the given raw arguments.
+    fn dispatch(&self, args: cbor::Value, ctx: &Context) -> Result<cbor::Value, QueryError>;
+}
+
+struct QueryHandlerDispatchImpl<Args, Output> {
+    handler: Box<dyn QueryHandler<Args, Output>>,
+}
+
+impl<Args, Output> QueryHandlerDispatch for QueryHandlerDispatchImpl<Args, Output>
+where
+    Args: DeserializeOwned + 'static,
+    Output: Serialize + 'static,
+{
+    fn dispatch(&self, args: cbor::Value, ctx: &Context) -> Result<cbor::Value, QueryError> {
+        let args = cbor::from_value(args)
+            .map_err(|error| QueryError::MalformedArguments(format!("{}", error)))?;
+        let response = self.handler.handle(&args, ctx).map_err(|error| {
+            match error.downcast::<QueryError>() {
+                Ok(error) => error,
+                Err(error) => QueryError::Failed(format!("{}", error)),
+            }
+        })?;
+
+        Ok(cbor::to_value(response))
+    }
+}
+
+struct QueryMethod {
+    info: MethodInfo,
+    dispatcher: Box<dyn QueryHandlerDispatch>,
+}
+
+/// Router of queries to the registered query methods.
+pub struct QueryRouter {
+    methods: HashMap<String, QueryMethod>,
+}
+
+impl QueryRouter {
+    /// Create a new query router without any methods.
+    pub fn new() -> Self {
+        Self {
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Register a new query method under the given name, replacing any
+    /// method previously registered under the same name.
+    ///
+    /// Returns the description of the method, which can be used to set the
+    /// schemas of its arguments and output (see `MethodInfo::with_schema`).
+    pub fn register<Args, Output, Handler>(
+        &mut self,
+        method: &str,
+        handler: Handler,
+    ) -> &mut MethodInfo
+    where
+        Args: DeserializeOwned + 'static,
+        Output: Serialize + 'static,
+        Handler: QueryHandler<Args, Output> + 'static,
+    {
+        self.methods.insert(
+            method.to_owned(),
+            QueryMethod {
+                info: MethodInfo::new::<Args, Output>(method),
+                dispatcher: Box::new(QueryHandlerDispatchImpl {
+                    handler: Box::new(handler),
+                }),
+            },
+        );
+        &mut self.methods.get_mut(method).unwrap().info
+    }
+
+    /// Return whether no query methods have been registered.
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+
+    /// Return descriptions of the registered query methods, ordered by name.
+    pub fn methods(&self) -> Vec<MethodInfo> {
+        let mut methods: Vec<_> = self
+            .methods
+            .values()
+            .map(|method| method.info.clone())
+            .collect();
+        methods.sort_by(|a, b| a.name.cmp(&b.name));
+        methods
+    }
+
+    /// Dispatches a query to the method registered under the given name.
+    ///
+    /// Errors returned by the method are reported as `QueryError::Failed`
+    /// unless the method itself returned a `QueryError`.
+    pub fn dispatch(
+        &self,
+        method: &str,
+        args: cbor::Value,
+        ctx: &Context,
+    ) -> Result<cbor::Value, QueryError> {
+        match self.methods.get(method) {
+            Some(method) => method.dispatcher.dispatch(args, ctx),
+            None => Err(QueryError::MethodNotFound(method.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::{
+        common::schema::Schema,
+        consensus::verifier::HEIGHT_LATEST,
+        storage::mkvs::{sync::NoopReadSyncer, Tree},
+    };
+
+    #[test]
+    fn test_query_router() {
+        let mut router = QueryRouter::new();
+        assert!(router.is_empty());
+        router
+            .register(
+                "kv.Get",
+                |key: &String, ctx: &Context| -> Result<Option<Vec<u8>>> {
+                    Ok(ctx
+                        .state
+                        .get(IoContext::create_child(&ctx.io_ctx), key.as_bytes()))
+                },
+            )
+            .with_schema(Schema::Text, Schema::of::<Option<serde_bytes::ByteBuf>>());
+        router.register("consensus.Height", |_: &(), ctx: &Context| -> Result<i64> {
+            Ok(ctx.consensus_state(HEIGHT_LATEST)?.height())
+        });
+        router.register("kv.Fail", |key: &String, _ctx: &Context| -> Result<()> {
+            match key.as_str() {
+                "typed" => Err(QueryError::MalformedArguments("bad key".to_owned()).into()),
+                _ => Err(anyhow!("failed")),
+            }
+        });
+
+        let methods = router.methods();
+        assert_eq!(
+            vec!["consensus.Height", "kv.Fail", "kv.Get"],
+            methods.iter().map(|m| m.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(Schema::named::<String>(), methods[1].args);
+        assert_eq!(Schema::Text, methods[2].args);
+        assert_eq!(Schema::Optional(Box::new(Schema::Bytes)), methods[2].output);
+
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        tree.insert(IoContext::background(), b"foo", b"bar")
+            .unwrap();
+        let header = Header::default();
+        let ctx = Context {
+            io_ctx: IoContext::background().freeze(),
+            header: &header,
+            state: &tree,
+            consensus: None,
+        };
+
+        let result = router
+            .dispatch("kv.Get", cbor::to_value("foo"), &ctx)
+            .unwrap();
+        assert_eq!(
+            Some(b"bar".to_vec()),
+            cbor::from_value::<Option<Vec<u8>>>(result).unwrap()
+        );
+
+        let code = |method: &str, args: cbor::Value| {
+            router.dispatch(method, args, &ctx).unwrap_err().code()
+        };
+        assert_eq!(1, code("kv.Missing", cbor::to_value("foo")));
+        assert_eq!(2, code("kv.Get", cbor::to_value(42)));
+        assert_eq!(2, code("kv.Fail", cbor::to_value("typed")));
+        assert_eq!(3, code("kv.Fail", cbor::to_value("foo")));
+        // Without a consensus verifier, the consensus state is not available.
+        assert_eq!(4, code("consensus.Height", cbor::to_value(())));
+    }
+}
@@ -7,12 +7,22 @@ use thiserror::Error;
 
 #[cfg_attr(not(target_env = "sgx"), allow(unused))]
 use crate::common::crypto::hash::Hash;
-use crate::common::{
-    crypto::signature::{PrivateKey, PublicKey, Signature, Signer},
-    sgx::avr,
-    time::insecure_posix_time,
+use crate::{
+    common::{
+        crypto::signature::{PublicKey, Signature, Signer},
+        sgx::{avr, Quote, VerifiedQuote},
+        time::insecure_posix_time,
+    },
+    identity,
 };
 
+#[cfg(all(not(target_env = "sgx"), feature = "debug-mock-sgx"))]
+use crate::common::{crypto::signature::PrivateKey, sgx::mock};
+#[cfg(target_env = "sgx")]
+use crate::common::{
+    crypto::signature::PrivateKey,
+    sgx::{pcs, QuotePolicy},
+};
 #[cfg(target_env = "sgx")]
 use base64;
 #[cfg(target_env = "sgx")]
@@ -50,9 +60,9 @@ enum AVRError {
 }
 
 struct Inner {
-    private_key: Option<PrivateKey>,
-    avr: Option<Arc<avr::AVR>>,
-    avr_timestamp: Option<i64>,
+    signer: Option<Arc<dyn Signer>>,
+    quote: Option<Arc<Quote>>,
+    quote_timestamp: Option<i64>,
     #[allow(unused)]
     enclave_identity: Option<avr::EnclaveIdentity>,
     #[allow(unused)]
@@ -66,8 +76,8 @@ struct Inner {
 /// The runtime attestation key (RAK) represents the identity of the enclave
 /// and can be used to sign remote attestations. Its purpose is to avoid
 /// round trips to IAS for each verification as the verifier can instead
-/// verify the RAK signature and the signature on the provided AVR which
-/// RAK to the enclave.
+/// verify the RAK signature and the provided remote attestation (an AVR or
+/// a DCAP quote) which binds RAK to the enclave.
 pub struct RAK {
     inner: RwLock<Inner>,
 }
@@ -75,11 +85,17 @@ pub struct RAK {
 impl RAK {
     /// Create an uninitialized runtime attestation key instance.
     pub fn new() -> Self {
+        // Mock attestation does not require initialization by the host.
+        #[cfg(all(not(target_env = "sgx"), feature = "debug-mock-sgx"))]
+        let signer: Option<Arc<dyn Signer>> = Some(Arc::new(PrivateKey::generate()));
+        #[cfg(not(all(not(target_env = "sgx"), feature = "debug-mock-sgx")))]
+        let signer = None;
+
         Self {
             inner: RwLock::new(Inner {
-                private_key: None,
-                avr: None,
-                avr_timestamp: None,
+                signer,
+                quote: None,
+                quote_timestamp: None,
                 enclave_identity: avr::EnclaveIdentity::current(),
                 target_info: None,
                 nonce: None,
@@ -135,8 +151,8 @@ impl RAK {
         inner.target_info = Some(target_info);
 
         // Generate the ephemeral RAK iff one is not set.
-        if inner.private_key.is_none() {
-            inner.private_key = Some(PrivateKey::generate())
+        if inner.signer.is_none() {
+            inner.signer = Some(Arc::new(PrivateKey::generate()))
         }
 
         Ok(())
@@ -196,22 +212,7 @@ impl RAK {
         }
         inner.nonce = None;
 
-        let authenticated_avr = avr::verify(&avr)?;
-
-        // Verify that the AVR's enclave identity matches our own.
-        let enclave_identity = inner
-            .enclave_identity
-            .as_ref()
-            .expect("Enclave identity must be configured");
-        if authenticated_avr.identity.mr_enclave != enclave_identity.mr_enclave {
-            return Err(AVRError::MrEnclaveMismatch.into());
-        }
-        if authenticated_avr.identity.mr_signer != enclave_identity.mr_signer {
-            return Err(AVRError::MrSignerMismatch.into());
-        }
-
-        // Verify that the AVR has H(RAK) in report body.
-        Self::verify_binding(&authenticated_avr, &rak_pub)?;
+        let authenticated_avr = avr::verify(&avr, &QuotePolicy::global())?;
 
         // Cross check the unchecked nonce with the post validation one.
         // Technically a waste of CPU cycles, doesn't hurt anything.
@@ -224,58 +225,162 @@ impl RAK {
             return Err(AVRError::NonceMismatch.into());
         }
 
-        // If there is an existing AVR that is dated more recently than
+        Self::set_verified_quote(
+            &mut inner,
+            Quote::IAS(avr),
+            authenticated_avr.into(),
+            &rak_pub,
+        )
+    }
+
+    /// Configure the DCAP quote for RAK, generated from the last report
+    /// returned by `init_report`.
+    #[cfg(target_env = "sgx")]
+    pub(crate) fn set_quote(&self, quote: pcs::QuoteBundle) -> Result<()> {
+        let rak_pub = self.public_key().expect("RAK must be configured");
+
+        let mut inner = self.inner.write().unwrap();
+
+        // As with AVRs, a nonce is only usable once even if verification
+        // fails, and its absence means we aren't in the process of attesting.
+        let expected_nonce = match inner.nonce.take() {
+            Some(nonce) => nonce,
+            None => return Err(AVRError::NonceMismatch.into()),
+        };
+
+        let verified_quote = pcs::verify(&quote, &QuotePolicy::global())?;
+
+        // Verify that the quote's report contains the nonce.
+        if expected_nonce.as_bytes() != &verified_quote.report_data[32..64] {
+            return Err(AVRError::NonceMismatch.into());
+        }
+
+        Self::set_verified_quote(&mut inner, Quote::PCS(quote), verified_quote, &rak_pub)
+    }
+
+    #[cfg(target_env = "sgx")]
+    fn set_verified_quote(
+        inner: &mut Inner,
+        quote: Quote,
+        verified_quote: VerifiedQuote,
+        rak_pub: &PublicKey,
+    ) -> Result<()> {
+        // Verify that the quote's enclave identity matches our own.
+        let enclave_identity = inner
+            .enclave_identity
+            .as_ref()
+            .expect("Enclave identity must be configured");
+        if verified_quote.mr_enclave != enclave_identity.mr_enclave {
+            return Err(AVRError::MrEnclaveMismatch.into());
+        }
+        if verified_quote.mr_signer != enclave_identity.mr_signer {
+            return Err(AVRError::MrSignerMismatch.into());
+        }
+
+        // Verify that the quote has H(RAK) in report body.
+        Self::verify_binding(&verified_quote, rak_pub)?;
+
+        // If there is an existing quote that is dated more recently than
         // the one being set, silently ignore the update.
-        if inner.avr.is_some() {
-            let existing_timestamp = inner.avr_timestamp.unwrap();
-            if existing_timestamp > authenticated_avr.timestamp {
+        if let Some(existing_timestamp) = inner.quote_timestamp {
+            if existing_timestamp > verified_quote.timestamp {
                 return Ok(());
             }
         }
 
-        inner.avr = Some(Arc::new(avr));
-        inner.avr_timestamp = Some(authenticated_avr.timestamp);
+        inner.quote = Some(Arc::new(quote));
+        inner.quote_timestamp = Some(verified_quote.timestamp);
+        identity::record_attestation(verified_quote.timestamp, verified_quote.valid_until);
         Ok(())
     }
 
+    /// Attest RAK using a mock quote.
+    #[cfg(all(not(target_env = "sgx"), feature = "debug-mock-sgx"))]
+    fn set_mock_quote(inner: &mut Inner, now: i64) {
+        let rak_pub = match inner.signer.as_ref().and_then(|s| s.public_key().ok()) {
+            Some(rak_pub) => rak_pub,
+            None => return,
+        };
+        let enclave_identity = match inner.enclave_identity {
+            Some(ref enclave_identity) => enclave_identity,
+            None => return,
+        };
+
+        let mut report_data = [0; 64];
+        report_data[0..32].copy_from_slice(Self::report_body_for_rak(&rak_pub).as_ref());
+        let quote = mock::generate(enclave_identity, &report_data, now);
+
+        inner.quote = Some(Arc::new(Quote::Mock(quote)));
+        inner.quote_timestamp = Some(now);
+        identity::record_attestation(now, None);
+    }
+
+    /// Use the given signer as the RAK instead of generating an ephemeral
+    /// in-memory key.
+    ///
+    /// This allows the runtime identity to be backed by e.g. an HSM or the
+    /// host. Any existing attestation is discarded, as it is bound to the
+    /// previous key. Note that a signer backed by anything outside of the
+    /// enclave weakens the guarantees provided by remote attestation.
+    pub fn set_signer(&self, signer: Arc<dyn Signer>) {
+        let mut inner = self.inner.write().unwrap();
+        inner.signer = Some(signer);
+        inner.quote = None;
+        inner.quote_timestamp = None;
+        inner.nonce = None;
+        identity::clear_attestation();
+    }
+
     /// Public part of RAK.
     ///
     /// This method may return `None` in the case where the enclave is not
     /// running on SGX hardware.
     pub fn public_key(&self) -> Option<PublicKey> {
         let inner = self.inner.read().unwrap();
-        inner.private_key.as_ref().map(|pk| pk.public_key())
+        inner
+            .signer
+            .as_ref()
+            .and_then(|signer| signer.public_key().ok())
     }
 
-    /// Attestation verification report for RAK.
+    /// Remote attestation of RAK.
     ///
-    /// This method may return `None` in case AVR has not yet been set from
-    /// the outside, or if the AVR has expired.
-    pub fn avr(&self) -> Option<Arc<avr::AVR>> {
+    /// This method may return `None` in case the attestation has not yet
+    /// been set from the outside, or if it has expired. With the
+    /// `debug-mock-sgx` feature, non-SGX builds return a fresh mock quote
+    /// instead.
+    pub fn quote(&self) -> Option<Arc<Quote>> {
         let now = insecure_posix_time();
 
-        // Enforce AVR expiration.
+        // Enforce attestation expiration.
         let mut inner = self.inner.write().unwrap();
-        if inner.avr.is_some() {
-            let timestamp = inner.avr_timestamp.unwrap();
+        if inner.quote.is_some() {
+            let timestamp = inner.quote_timestamp.unwrap();
             if !avr::timestamp_is_fresh(now, timestamp) {
-                // Reset the AVR.
-                inner.avr = None;
-                inner.avr_timestamp = None;
+                // Reset the attestation.
+                inner.quote = None;
+                inner.quote_timestamp = None;
+                identity::clear_attestation();
+            }
+        }
 
-                return None;
+        // Mock quotes are generated on demand.
+        #[cfg(all(not(target_env = "sgx"), feature = "debug-mock-sgx"))]
+        {
+            if inner.quote.is_none() {
+                Self::set_mock_quote(&mut inner, now);
             }
         }
 
-        inner.avr.clone()
+        inner.quote.clone()
     }
 
     /// Verify a provided RAK binding.
-    pub fn verify_binding(avr: &avr::AuthenticatedAVR, rak: &PublicKey) -> Result<()> {
-        if avr.report_data.len() < 32 {
+    pub fn verify_binding(quote: &VerifiedQuote, rak: &PublicKey) -> Result<()> {
+        if quote.report_data.len() < 32 {
             return Err(RAKError::MalformedReportData.into());
         }
-        if Self::report_body_for_rak(rak).as_ref() != &avr.report_data[..32] {
+        if Self::report_body_for_rak(rak).as_ref() != &quote.report_data[..32] {
             return Err(RAKError::BindingMismatch.into());
         }
 
@@ -287,9 +392,22 @@ impl Signer for RAK {
     /// Generate a RAK signature with the private key over the context and message.
     fn sign(&self, context: &[u8], message: &[u8]) -> Result<Signature> {
         let inner = self.inner.read().unwrap();
-        match inner.private_key {
-            Some(ref key) => Ok(key.sign(context, message)?),
+        match inner.signer {
+            Some(ref signer) => Ok(signer.sign(context, message)?),
+            None => Err(RAKError::NotConfigured.into()),
+        }
+    }
+
+    /// Generate a plain RAK signature with the private key over the message.
+    fn sign_raw(&self, message: &[u8]) -> Result<Signature> {
+        let inner = self.inner.read().unwrap();
+        match inner.signer {
+            Some(ref signer) => Ok(signer.sign_raw(message)?),
             None => Err(RAKError::NotConfigured.into()),
         }
     }
+
+    fn public_key(&self) -> Result<PublicKey> {
+        RAK::public_key(self).ok_or_else(|| RAKError::NotConfigured.into())
+    }
 }
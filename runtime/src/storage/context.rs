@@ -1,44 +1,108 @@
 //! Thread-local storage context.
 //!
 //! The storage context is a convenient way to share CAS and MKVS
-//! implementations across the current thread.
-use std::{cell::RefCell, sync::Arc};
+//! implementations across the current thread. A `StorageContextHandle` can
+//! be used to enter an existing storage context on other threads.
+use std::{
+    cell::RefCell,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 use super::{KeyValue, MKVS};
 
+struct MKVSPtr(*mut dyn MKVS);
+
+// Safety: MKVS implementations are Send and all access to them is
+// serialized by the mutex in Ctx.
+unsafe impl Send for MKVSPtr {}
+
+/// An entered storage context, shared by all threads it has been entered on.
 struct Ctx {
-    mkvs: *mut dyn MKVS,
+    /// Pointer to the MKVS, cleared once the context is exited.
+    mkvs: Mutex<Option<MKVSPtr>>,
     untrusted_local: Arc<dyn KeyValue>,
 }
 
 thread_local! {
-    static CTX: RefCell<Option<Ctx>> = RefCell::new(None);
+    static CTX: RefCell<Vec<Arc<Ctx>>> = RefCell::new(Vec::new());
 }
 
-struct CtxGuard;
+struct CtxGuard {
+    owned: bool,
+}
 
 impl CtxGuard {
-    fn new<M>(mkvs: &mut M, untrusted_local: Arc<dyn KeyValue>) -> Self
-    where
-        M: MKVS + 'static,
-    {
-        CTX.with(|ctx| {
-            assert!(ctx.borrow().is_none(), "nested enter is not allowed");
-            ctx.borrow_mut().replace(Ctx {
-                mkvs,
-                untrusted_local,
-            });
+    fn new(
+        mkvs: &mut (dyn MKVS + 'static),
+        untrusted_local: Arc<dyn KeyValue>,
+        nested: bool,
+    ) -> Self {
+        let ctx = Arc::new(Ctx {
+            mkvs: Mutex::new(Some(MKVSPtr(mkvs))),
+            untrusted_local,
+        });
+        Self::push(ctx, nested, true)
+    }
+
+    fn push(ctx: Arc<Ctx>, nested: bool, owned: bool) -> Self {
+        CTX.with(|local| {
+            let mut local = local.borrow_mut();
+            assert!(nested || local.is_empty(), "nested enter is not allowed");
+            local.push(ctx);
         });
 
-        CtxGuard
+        CtxGuard { owned }
     }
 }
 
 impl Drop for CtxGuard {
     fn drop(&mut self) {
-        CTX.with(|local| {
-            drop(local.borrow_mut().take());
-        });
+        let ctx = CTX.with(|local| local.borrow_mut().pop());
+        if let (true, Some(ctx)) = (self.owned, ctx) {
+            // Wait for other threads to stop using the MKVS and prevent any
+            // further use through handles.
+            *ctx.mkvs.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        }
+    }
+}
+
+/// A handle to an entered storage context.
+///
+/// The handle can be sent to other threads, e.g. ones running async tasks,
+/// and entered there. Access to the MKVS is serialized between all threads
+/// the context has been entered on. Exiting the context on the thread that
+/// originally entered it waits until no other thread is using the MKVS, and
+/// afterwards the handle can no longer be entered.
+#[derive(Clone)]
+pub struct StorageContextHandle {
+    ctx: Arc<Ctx>,
+}
+
+impl StorageContextHandle {
+    /// Return whether the storage context has not yet been exited.
+    pub fn is_valid(&self) -> bool {
+        self.ctx
+            .mkvs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// Enter the storage context on the current thread, overriding any
+    /// context that has already been entered until the closure returns.
+    ///
+    /// Returns `None` without running the closure if the storage context has
+    /// already been exited.
+    pub fn enter<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce() -> R,
+    {
+        if !self.is_valid() {
+            return None;
+        }
+
+        let _guard = CtxGuard::push(self.ctx.clone(), true, false);
+        Some(f())
     }
 }
 
@@ -47,15 +111,56 @@ pub struct StorageContext;
 
 impl StorageContext {
     /// Enter the storage context.
-    pub fn enter<M, F, R>(mkvs: &mut M, untrusted_local: Arc<dyn KeyValue>, f: F) -> R
+    ///
+    /// Any MKVS implementation may be used, including boxed trait objects.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the storage context has already been entered, use
+    /// `StorageContext::enter_nested` to override an entered context.
+    pub fn enter<F, R>(
+        mkvs: &mut (dyn MKVS + 'static),
+        untrusted_local: Arc<dyn KeyValue>,
+        f: F,
+    ) -> R
     where
-        M: MKVS + 'static,
         F: FnOnce() -> R,
     {
-        let _guard = CtxGuard::new(mkvs, untrusted_local);
+        let _guard = CtxGuard::new(mkvs, untrusted_local, false);
         f()
     }
 
+    /// Enter the storage context, overriding any context that has already
+    /// been entered until the closure returns.
+    ///
+    /// This can be used to temporarily run a sub-call against a different
+    /// MKVS, e.g. an overlay tree. The previous context is restored once the
+    /// closure returns (or panics).
+    pub fn enter_nested<F, R>(
+        mkvs: &mut (dyn MKVS + 'static),
+        untrusted_local: Arc<dyn KeyValue>,
+        f: F,
+    ) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let _guard = CtxGuard::new(mkvs, untrusted_local, true);
+        f()
+    }
+
+    /// Return whether the storage context has been entered on the current
+    /// thread.
+    pub fn is_entered() -> bool {
+        CTX.with(|ctx| !ctx.borrow().is_empty())
+    }
+
+    /// Return a handle to the thread-local storage context if it has been
+    /// entered.
+    pub fn handle() -> Option<StorageContextHandle> {
+        CTX.with(|local| local.borrow().last().cloned())
+            .map(|ctx| StorageContextHandle { ctx })
+    }
+
     /// Run a closure with the thread-local storage context.
     ///
     /// # Panics
@@ -65,12 +170,128 @@ impl StorageContext {
     where
         F: FnOnce(&mut dyn MKVS, &Arc<dyn KeyValue>) -> R,
     {
-        CTX.with(|ctx| {
-            let ctx = ctx.borrow();
-            let ctx_ref = ctx.as_ref().expect("must only be called while entered");
-            let mkvs_ref = unsafe { ctx_ref.mkvs.as_mut().expect("pointer is never null") };
+        Self::try_with_current(f).expect("must only be called while entered")
+    }
+
+    /// Run a closure with the thread-local storage context if it has been
+    /// entered, returning `None` otherwise.
+    ///
+    /// The closure must not access the same storage context again, as it has
+    /// exclusive access to the MKVS while it runs.
+    pub fn try_with_current<F, R>(f: F) -> Option<R>
+    where
+        F: FnOnce(&mut dyn MKVS, &Arc<dyn KeyValue>) -> R,
+    {
+        // Do not hold the borrow while running the closure, so that it may
+        // enter a nested context.
+        let ctx = CTX.with(|local| local.borrow().last().cloned())?;
+        let mkvs = ctx.mkvs.lock().unwrap_or_else(PoisonError::into_inner);
+        let mkvs_ref = unsafe { mkvs.as_ref()?.0.as_mut().expect("pointer is never null") };
+
+        Some(f(mkvs_ref, &ctx.untrusted_local))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use anyhow::{anyhow, Result};
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::{sync::NoopReadSyncer, Tree};
+
+    struct NoopKeyValue;
+
+    impl KeyValue for NoopKeyValue {
+        fn get(&self, _key: Vec<u8>) -> Result<Vec<u8>> {
+            Err(anyhow!("not found"))
+        }
+
+        fn insert(&self, _key: Vec<u8>, _value: Vec<u8>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn current_get(key: &[u8]) -> Option<Vec<u8>> {
+        StorageContext::with_current(|mkvs, _| mkvs.get(Context::background(), key))
+    }
+
+    #[test]
+    fn test_enter_nested() {
+        let untrusted_local: Arc<dyn KeyValue> = Arc::new(NoopKeyValue);
+        let mut outer = Tree::make().new(Box::new(NoopReadSyncer));
+        outer
+            .insert(Context::background(), b"key", b"outer")
+            .unwrap();
+        let mut inner = Tree::make().new(Box::new(NoopReadSyncer));
+        inner
+            .insert(Context::background(), b"key", b"inner")
+            .unwrap();
 
-            f(mkvs_ref, &ctx_ref.untrusted_local)
-        })
+        StorageContext::enter(&mut outer, untrusted_local.clone(), || {
+            assert_eq!(Some(b"outer".to_vec()), current_get(b"key"));
+
+            StorageContext::with_current(|_, untrusted_local| {
+                StorageContext::enter_nested(&mut inner, untrusted_local.clone(), || {
+                    assert_eq!(Some(b"inner".to_vec()), current_get(b"key"));
+                });
+            });
+
+            // The previous context is restored.
+            assert_eq!(Some(b"outer".to_vec()), current_get(b"key"));
+        });
+    }
+
+    #[test]
+    fn test_try_with_current() {
+        assert!(!StorageContext::is_entered());
+        assert_eq!(None, StorageContext::try_with_current(|_, _| ()));
+
+        let untrusted_local: Arc<dyn KeyValue> = Arc::new(NoopKeyValue);
+        let mut mkvs = Tree::make().new(Box::new(NoopReadSyncer));
+        StorageContext::enter(&mut mkvs, untrusted_local, || {
+            assert!(StorageContext::is_entered());
+            assert_eq!(Some(()), StorageContext::try_with_current(|_, _| ()));
+        });
+        assert!(!StorageContext::is_entered());
+    }
+
+    #[test]
+    fn test_handle() {
+        assert!(StorageContext::handle().is_none());
+
+        let untrusted_local: Arc<dyn KeyValue> = Arc::new(NoopKeyValue);
+        let mut mkvs = Tree::make().new(Box::new(NoopReadSyncer));
+        mkvs.insert(Context::background(), b"key", b"value")
+            .unwrap();
+
+        let handle = StorageContext::enter(&mut mkvs, untrusted_local, || {
+            let handle = StorageContext::handle().expect("entered");
+            let other = handle.clone();
+            let value = thread::spawn(move || other.enter(|| current_get(b"key")))
+                .join()
+                .expect("join");
+            assert_eq!(Some(Some(b"value".to_vec())), value);
+
+            handle
+        });
+
+        // Handles can not be entered once the context has been exited.
+        assert!(!handle.is_valid());
+        assert_eq!(None, handle.enter(|| ()));
+    }
+
+    #[test]
+    #[should_panic(expected = "nested enter is not allowed")]
+    fn test_enter_twice() {
+        let untrusted_local: Arc<dyn KeyValue> = Arc::new(NoopKeyValue);
+        let mut outer = Tree::make().new(Box::new(NoopReadSyncer));
+        let mut inner = Tree::make().new(Box::new(NoopReadSyncer));
+
+        StorageContext::enter(&mut outer, untrusted_local.clone(), || {
+            StorageContext::enter(&mut inner, untrusted_local.clone(), || {});
+        });
     }
 }
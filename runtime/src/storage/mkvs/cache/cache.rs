@@ -1,4 +1,4 @@
-use std::{any::Any, ptr::NonNull, sync::Arc};
+use std::{any::Any, ptr::NonNull, rc::Rc, sync::Arc};
 
 use anyhow::Result;
 use io_context::Context;
@@ -10,10 +10,37 @@ use crate::storage::mkvs::{cache::lru_cache::CacheItemBox, sync::*, tree::*};
 pub struct CacheStats {
     /// Count of internal nodes held by the cache.
     pub internal_node_count: usize,
-    /// Total size of values held by the cache.
+    /// Total size of internal nodes held by the cache, in bytes.
+    pub internal_node_size: usize,
+    /// Total size of leaf nodes held by the cache, in bytes.
     pub leaf_value_size: usize,
+    /// Number of node dereferences served from the cache.
+    pub hit_count: u64,
+    /// Number of node dereferences that required a remote sync.
+    pub miss_count: u64,
+    /// Number of nodes evicted to make space for other nodes.
+    pub eviction_count: u64,
+    /// Number of remote syncs performed via the read syncer.
+    pub sync_fetch_count: u64,
 }
 
+/// Events reported to cache hooks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheEvent {
+    /// A node dereference was served from the cache.
+    Hit,
+    /// A node dereference required a remote sync.
+    Miss,
+    /// A node was evicted to make space for other nodes.
+    Eviction,
+    /// A remote sync was performed via the read syncer.
+    SyncFetch,
+}
+
+/// Callback invoked by the cache for each cache event, e.g. to export cache
+/// metrics.
+pub type CacheHook = Rc<dyn Fn(CacheEvent)>;
+
 /// Used to fetch proofs from a remote tree via the ReadSyncer interface.
 pub trait ReadSyncFetcher {
     /// Fetch proof.
@@ -108,6 +135,13 @@ pub trait Cache {
     // due to it becoming dirty.
     fn rollback_node(&mut self, ptr: NodePtrRef, kind: NodeKind);
 
+    /// Pin a node, making it ineligible for eviction.
+    fn pin_node(&mut self, ptr: NodePtrRef);
+
+    /// Unpin a node. The node must be committed again for it to become
+    /// eligible for eviction.
+    fn unpin_node(&mut self, ptr: NodePtrRef);
+
     /// Mark the current LRU queue positions as the ones before any nodes are
     /// visited. Any new nodes committed into the cache after this is called
     /// will be inserted after the marked position.
@@ -131,7 +165,7 @@ where
     fn get_cache_extra(&self) -> CacheExtra<Item>;
     /// Set the item's caching hint.
     fn set_cache_extra(&mut self, new_val: CacheExtra<Item>);
-    /// Return the size, in bytes, of the item when cached.
+    /// Return the estimated memory used by the item when cached, in bytes.
     fn get_cached_size(&self) -> usize;
 }
 
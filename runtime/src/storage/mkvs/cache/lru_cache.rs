@@ -1,11 +1,33 @@
-use std::{any::Any, cell::RefCell, pin::Pin, ptr::NonNull, rc::Rc, sync::Arc};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    pin::Pin,
+    ptr::NonNull,
+    rc::Rc,
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
 use intrusive_collections::{IntrusivePointer, LinkedList, LinkedListLink};
 use io_context::Context;
+use lazy_static::lazy_static;
 use thiserror::Error;
 
-use crate::storage::mkvs::{cache::*, sync::*, tree::*};
+use crate::{
+    common::metrics::{self, Counter},
+    storage::mkvs::{cache::*, sync::*, tree::*},
+};
+
+lazy_static! {
+    static ref CACHE_HITS: Arc<Counter> = metrics::registry().counter(
+        "oasis_runtime_mkvs_cache_hits",
+        "Number of MKVS tree cache hits.",
+    );
+    static ref CACHE_MISSES: Arc<Counter> = metrics::registry().counter(
+        "oasis_runtime_mkvs_cache_misses",
+        "Number of MKVS tree cache misses.",
+    );
+}
 
 #[derive(Error, Debug)]
 #[error("mkvs: tried to remove locked node")]
@@ -14,6 +36,8 @@ struct RemoveLockedError;
 #[derive(Clone, Default)]
 pub struct CacheItemBox<Item: CacheItem + Default> {
     item: Rc<RefCell<Item>>,
+    size: usize,
+    state: Cell<PolicyState>,
     link: LinkedListLink,
 }
 
@@ -41,21 +65,38 @@ where
     V: CacheItem + Default,
 {
     pub list: LinkedList<CacheItemAdapter<V>>,
+    pub count: usize,
     pub size: usize,
     pub capacity: usize,
+    pub count_capacity: bool,
     pub mark: CacheExtra<V>,
+    pub policy: Rc<dyn CachePolicy>,
 }
 
 impl<V> LRUList<V>
 where
     V: CacheItem + Default,
 {
-    pub fn new(capacity: usize) -> LRUList<V> {
+    /// Construct a new list holding items up to the given capacity, which
+    /// is either a number of items (if `count_capacity` is set) or a total
+    /// size in bytes.
+    pub fn new(capacity: usize, count_capacity: bool, policy: Rc<dyn CachePolicy>) -> LRUList<V> {
         LRUList {
             list: LinkedList::new(CacheItemAdapter::new()),
+            count: 0,
             size: 0,
-            capacity: capacity,
+            capacity,
+            count_capacity,
             mark: None,
+            policy,
+        }
+    }
+
+    fn used(&self) -> usize {
+        if self.count_capacity {
+            self.count
+        } else {
+            self.size
         }
     }
 
@@ -72,9 +113,15 @@ where
     fn add(&mut self, val: Rc<RefCell<V>>) {
         let mut val_ref = val.borrow_mut();
         if val_ref.get_cache_extra().is_none() {
-            self.size += val_ref.get_cached_size();
+            // Remember the size the item was accounted with, as the item may
+            // be modified while still in the cache.
+            let size = val_ref.get_cached_size();
+            self.count += 1;
+            self.size += size;
             let mut item_box = Box::pin(CacheItemBox {
                 item: val.clone(),
+                size,
+                state: Cell::new(PolicyState::default()),
                 link: LinkedListLink::new(),
             });
             val_ref.set_cache_extra(NonNull::new(&mut *item_box));
@@ -96,8 +143,15 @@ where
             None => false,
             Some(non_null) => {
                 let mut item_cursor = unsafe { self.list.cursor_mut_from_ptr(non_null.as_ptr()) };
-                let removed_box = item_cursor.remove().unwrap();
-                self.list.push_front(removed_box);
+                let item_box = item_cursor.get().unwrap();
+                let mut state = item_box.state.get();
+                let move_to_front = self.policy.on_use(&mut state);
+                item_box.state.set(state);
+
+                if move_to_front {
+                    let removed_box = item_cursor.remove().unwrap();
+                    self.list.push_front(removed_box);
+                }
                 true
             }
         }
@@ -118,9 +172,9 @@ where
                 match item_cursor.remove() {
                     None => false,
                     Some(item_box) => {
-                        let mut val = item_box.item.borrow_mut();
-                        val.set_cache_extra(None);
-                        self.size -= val.get_cached_size();
+                        item_box.item.borrow_mut().set_cache_extra(None);
+                        self.count -= 1;
+                        self.size -= item_box.size;
                         true
                     }
                 }
@@ -135,14 +189,30 @@ where
     ) -> Result<Vec<Rc<RefCell<V>>>, RemoveLockedError> {
         let mut evicted: Vec<Rc<RefCell<V>>> = Vec::new();
         if self.capacity > 0 {
-            let target_size = val.borrow().get_cached_size();
-            while !self.list.is_empty() && self.size + target_size > self.capacity {
+            let target = if self.count_capacity {
+                1
+            } else {
+                val.borrow().get_cached_size()
+            };
+            while !self.list.is_empty() && self.used() + target > self.capacity {
                 let back = (*self.list.back().get().unwrap()).item.clone();
                 if let Some(locked_val) = locked_val {
                     if back.as_ptr() == locked_val.as_ptr() {
                         return Err(RemoveLockedError);
                     }
                 }
+
+                let back_box = self.list.back().get().unwrap();
+                let mut state = back_box.state.get();
+                let evict = self.policy.on_evict(&mut state);
+                back_box.state.set(state);
+                if !evict {
+                    // Give the item another chance.
+                    let reprieved_box = self.list.back_mut().remove().unwrap();
+                    self.list.push_front(reprieved_box);
+                    continue;
+                }
+
                 if self.remove(back.clone()) {
                     evicted.push(back);
                 }
@@ -152,7 +222,10 @@ where
     }
 }
 
-/// Cache implementation with a simple LRU eviction strategy.
+/// Cache implementation with an LRU-based eviction strategy.
+///
+/// The exact eviction behavior is determined by the configured cache policy,
+/// which defaults to plain LRU.
 pub struct LRUCache {
     read_syncer: Box<dyn ReadSync>,
 
@@ -161,6 +234,12 @@ pub struct LRUCache {
 
     lru_leaf: LRUList<NodePointer>,
     lru_internal: LRUList<NodePointer>,
+
+    hit_count: u64,
+    miss_count: u64,
+    eviction_count: u64,
+    sync_fetch_count: u64,
+    hook: Option<CacheHook>,
 }
 
 impl LRUCache {
@@ -168,16 +247,19 @@ impl LRUCache {
     ///
     /// * `node_capacity` is the maximum number of internal nodes held by the
     ///   cache before eviction.
-    /// * `value_capacity` is the total size, in bytes, of values held
-    ///   by the cache before eviction.
+    /// * `value_capacity` is the total size, in bytes, of leaf nodes held
+    ///   by the cache before eviction. Each leaf node is accounted with its
+    ///   key, value and a fixed per-node overhead (`NODE_OVERHEAD`).
     /// * `read_syncer` is the read syncer used as backing for the cache.
+    /// * `policy` decides which nodes and values are evicted first.
     pub fn new(
         node_capacity: usize,
         value_capacity: usize,
         read_syncer: Box<dyn ReadSync>,
+        policy: Rc<dyn CachePolicy>,
     ) -> Box<LRUCache> {
         Box::new(LRUCache {
-            read_syncer: read_syncer,
+            read_syncer,
 
             pending_root: Rc::new(RefCell::new(NodePointer {
                 node: None,
@@ -185,11 +267,40 @@ impl LRUCache {
             })),
             sync_root: Root::default(),
 
-            lru_leaf: LRUList::new(value_capacity),
-            lru_internal: LRUList::new(node_capacity),
+            lru_leaf: LRUList::new(value_capacity, false, policy.clone()),
+            lru_internal: LRUList::new(node_capacity, true, policy),
+
+            hit_count: 0,
+            miss_count: 0,
+            eviction_count: 0,
+            sync_fetch_count: 0,
+            hook: None,
         })
     }
 
+    /// Set the hook invoked for each cache event.
+    pub fn set_hook(&mut self, hook: Option<CacheHook>) {
+        self.hook = hook;
+    }
+
+    fn record_event(&mut self, event: CacheEvent) {
+        match event {
+            CacheEvent::Hit => {
+                self.hit_count += 1;
+                CACHE_HITS.inc();
+            }
+            CacheEvent::Miss => {
+                self.miss_count += 1;
+                CACHE_MISSES.inc();
+            }
+            CacheEvent::Eviction => self.eviction_count += 1,
+            CacheEvent::SyncFetch => self.sync_fetch_count += 1,
+        }
+        if let Some(ref hook) = self.hook {
+            hook(event);
+        }
+    }
+
     fn new_internal_node_ptr(&mut self, node: Option<NodeRef>) -> NodePtrRef {
         Rc::new(RefCell::new(NodePointer {
             node: node,
@@ -212,7 +323,7 @@ impl LRUCache {
         if !ptr.borrow().clean {
             panic!("mkvs: commit_node called on dirty node");
         }
-        if ptr.borrow().node.is_none() {
+        if ptr.borrow().node.is_none() || ptr.borrow().pinned {
             return Ok(());
         }
         if self.use_node(ptr.clone()) {
@@ -225,6 +336,7 @@ impl LRUCache {
                     .lru_internal
                     .evict_for_val(ptr.clone(), locked_ptr.clone())?;
                 for node in evicted {
+                    self.record_event(CacheEvent::Eviction);
                     self.try_remove_node(node.clone(), locked_ptr.clone())?;
                 }
                 self.lru_internal.add(ptr.clone());
@@ -234,6 +346,7 @@ impl LRUCache {
                     .lru_leaf
                     .evict_for_val(ptr.clone(), locked_ptr.clone())?;
                 for node in evicted {
+                    self.record_event(CacheEvent::Eviction);
                     self.try_remove_node(node.clone(), locked_ptr.clone())?;
                 }
                 self.lru_leaf.add(ptr.clone());
@@ -354,8 +467,13 @@ impl Cache for LRUCache {
 
     fn stats(&self) -> CacheStats {
         CacheStats {
-            internal_node_count: self.lru_internal.size,
+            internal_node_count: self.lru_internal.count,
+            internal_node_size: self.lru_internal.size,
             leaf_value_size: self.lru_leaf.size,
+            hit_count: self.hit_count,
+            miss_count: self.miss_count,
+            eviction_count: self.eviction_count,
+            sync_fetch_count: self.sync_fetch_count,
         }
     }
 
@@ -438,6 +556,7 @@ impl Cache for LRUCache {
                 drop(ptr);
                 self.remove_node(ptr_ref.clone());
             } else {
+                self.record_event(CacheEvent::Hit);
                 return Ok(Some(node.clone()));
             }
         } else {
@@ -449,6 +568,7 @@ impl Cache for LRUCache {
 
         // Node not available locally, fetch from read syncer.
         if let Some(fetcher) = fetcher {
+            self.record_event(CacheEvent::Miss);
             self.remote_sync(ctx, ptr_ref.clone(), fetcher)?;
         } else {
             return Err(anyhow!(
@@ -471,12 +591,16 @@ impl Cache for LRUCache {
         ptr: NodePtrRef,
         fetcher: F,
     ) -> Result<()> {
-        let proof = fetcher.fetch(
-            Context::create_child(&ctx),
-            self.sync_root,
-            ptr.clone(),
-            &mut self.read_syncer,
-        )?;
+        check_cancelled(ctx)?;
+        self.record_event(CacheEvent::SyncFetch);
+        let proof = fetcher
+            .fetch(
+                Context::create_child(&ctx),
+                self.sync_root,
+                ptr.clone(),
+                &mut self.read_syncer,
+            )
+            .map_err(|source| Error::SyncFailed { source })?;
 
         // The proof can be for one of two hashes: i) it is either for ptr.Hash in case
         // all the nodes are only contained in the subtree below ptr, or ii) it is for
@@ -487,15 +611,21 @@ impl Cache for LRUCache {
         } else if proof.untrusted_root == self.sync_root.hash {
             (self.pending_root.clone(), self.sync_root.hash)
         } else {
-            return Err(anyhow!(
-                "mkvs: got proof for unexpected root ({:?})",
-                proof.untrusted_root
-            ));
+            return Err(Error::RootMismatch {
+                expected: self.sync_root.hash,
+                got: proof.untrusted_root,
+            }
+            .into());
         };
 
         // Verify proof.
         let pv = ProofVerifier;
-        let subtree = pv.verify_proof(Context::create_child(&ctx), expected_root, &proof)?;
+        let subtree = pv.verify_proof_with(
+            Context::create_child(&ctx),
+            expected_root,
+            &proof,
+            self.sync_root.hash_algorithm.hasher(),
+        )?;
 
         // Merge resulting nodes.
         let mut merged_nodes: Vec<NodePtrRef> = Vec::new();
@@ -545,6 +675,19 @@ impl Cache for LRUCache {
         ptr.borrow_mut().set_cache_extra(None);
     }
 
+    fn pin_node(&mut self, ptr: NodePtrRef) {
+        match classify_noderef!(? ptr.borrow().node) {
+            NodeKind::Internal => self.lru_internal.remove(ptr.clone()),
+            NodeKind::Leaf => self.lru_leaf.remove(ptr.clone()),
+            NodeKind::None => false,
+        };
+        ptr.borrow_mut().pinned = true;
+    }
+
+    fn unpin_node(&mut self, ptr: NodePtrRef) {
+        ptr.borrow_mut().pinned = false;
+    }
+
     fn mark_position(&mut self) {
         self.lru_internal.mark();
         self.lru_leaf.mark();
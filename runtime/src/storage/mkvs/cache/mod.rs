@@ -1,5 +1,7 @@
 mod cache;
 mod lru_cache;
+mod policy;
 
 pub use cache::*;
 pub use lru_cache::*;
+pub use policy::*;
@@ -0,0 +1,115 @@
+/// Per-item state maintained by the cache on behalf of a cache policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PolicyState {
+    /// Whether the item has been used since it was last considered for eviction.
+    pub referenced: bool,
+    /// Number of times the item has been used since it was added or last demoted.
+    pub hits: u32,
+}
+
+/// Eviction policy used by the in-memory tree cache.
+///
+/// Cached items are kept in a queue ordered by recency. Items are added at the
+/// front of the queue and eviction candidates are taken from the back. The
+/// policy decides how item use affects the queue and whether eviction
+/// candidates get another chance.
+pub trait CachePolicy {
+    /// Called when a cached item is used. Returns true if the item should be
+    /// moved to the front of the queue.
+    fn on_use(&self, state: &mut PolicyState) -> bool;
+
+    /// Called when a cached item is the next eviction candidate. Returns true
+    /// if the item should be evicted or false if it should instead be moved
+    /// to the front of the queue.
+    ///
+    /// Implementations must update the state so that the item is eventually
+    /// evicted if it is not used again.
+    fn on_evict(&self, state: &mut PolicyState) -> bool;
+}
+
+/// Plain least-recently-used eviction.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LRUPolicy;
+
+impl CachePolicy for LRUPolicy {
+    fn on_use(&self, _state: &mut PolicyState) -> bool {
+        true
+    }
+
+    fn on_evict(&self, _state: &mut PolicyState) -> bool {
+        true
+    }
+}
+
+/// Segmented LRU eviction.
+///
+/// Items start out in the probationary segment and are promoted to the
+/// protected segment once they are used after being added. Protected items
+/// that reach the back of the queue are demoted to the probationary segment
+/// instead of being evicted, so keys that are used repeatedly survive scans
+/// over keys that are only used once.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SegmentedLRUPolicy;
+
+impl CachePolicy for SegmentedLRUPolicy {
+    fn on_use(&self, state: &mut PolicyState) -> bool {
+        state.hits = state.hits.saturating_add(1);
+        true
+    }
+
+    fn on_evict(&self, state: &mut PolicyState) -> bool {
+        if state.hits > 0 {
+            state.hits = 0;
+            return false;
+        }
+        true
+    }
+}
+
+/// Clock (second-chance) eviction.
+///
+/// Using an item only sets its reference bit instead of reordering the queue,
+/// which makes use cheaper than with LRU. Referenced eviction candidates have
+/// their bit cleared and are given another pass through the queue.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ClockPolicy;
+
+impl CachePolicy for ClockPolicy {
+    fn on_use(&self, state: &mut PolicyState) -> bool {
+        state.referenced = true;
+        false
+    }
+
+    fn on_evict(&self, state: &mut PolicyState) -> bool {
+        if state.referenced {
+            state.referenced = false;
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cache_policies() {
+        let mut state = PolicyState::default();
+        assert!(LRUPolicy.on_use(&mut state));
+        assert!(LRUPolicy.on_evict(&mut state));
+
+        let mut state = PolicyState::default();
+        assert!(SegmentedLRUPolicy.on_evict(&mut state));
+        assert!(SegmentedLRUPolicy.on_use(&mut state));
+        assert!(SegmentedLRUPolicy.on_use(&mut state));
+        assert!(!SegmentedLRUPolicy.on_evict(&mut state));
+        assert!(SegmentedLRUPolicy.on_evict(&mut state));
+
+        let mut state = PolicyState::default();
+        assert!(ClockPolicy.on_evict(&mut state));
+        assert!(!ClockPolicy.on_use(&mut state));
+        assert!(!ClockPolicy.on_evict(&mut state));
+        assert!(ClockPolicy.on_evict(&mut state));
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use io_context::Context;
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{
+        checkpoint::{Chunk, Metadata, CHECKPOINT_VERSION},
+        Error, Root, Tree,
+    },
+};
+
+/// Split a committed tree into checkpoint chunks.
+///
+/// Keys are assigned to chunks in iteration order, and a chunk is closed as
+/// soon as adding the next key and value would make them exceed `chunk_size`
+/// bytes. A chunk always contains at least one key. Each chunk carries a proof
+/// for the lookup paths of all of its keys, so the chunks together contain all
+/// nodes of the tree.
+///
+/// The tree must not have any uncommitted changes.
+pub fn create_checkpoint(
+    ctx: Context,
+    tree: &Tree,
+    root: Root,
+    chunk_size: usize,
+) -> Result<(Metadata, Vec<Chunk>)> {
+    let ctx = ctx.freeze();
+    let mut chunks = Vec::new();
+    let mut keys: Vec<Vec<u8>> = Vec::new();
+    let mut size = 0;
+
+    let mut it = tree.iter(Context::create_child(&ctx));
+    it.rewind();
+    for (key, value) in it.by_ref() {
+        let item_size = key.len() + value.len();
+        if !keys.is_empty() && size + item_size > chunk_size {
+            chunks.push(create_chunk(&ctx, tree, root, &keys)?);
+            keys.clear();
+            size = 0;
+        }
+        keys.push(key);
+        size += item_size;
+    }
+    if let Some(error) = it.error() {
+        return Err(anyhow!(
+            "checkpoint: failed to iterate over tree: {}",
+            error
+        ));
+    }
+    if !keys.is_empty() {
+        chunks.push(create_chunk(&ctx, tree, root, &keys)?);
+    } else if chunks.is_empty() && !root.hash.is_empty() {
+        // An empty tree does not need any chunks, but only for the empty root.
+        return Err(Error::RootMismatch {
+            expected: root.hash,
+            got: Hash::empty_hash(),
+        }
+        .into());
+    }
+
+    let metadata = Metadata {
+        version: CHECKPOINT_VERSION,
+        root,
+        chunks: chunks.iter().map(Chunk::encoded_hash).collect(),
+    };
+
+    Ok((metadata, chunks))
+}
+
+fn create_chunk(ctx: &Arc<Context>, tree: &Tree, root: Root, keys: &[Vec<u8>]) -> Result<Chunk> {
+    let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_slice()).collect();
+    let proof = tree.get_proof_multi(Context::create_child(ctx), &keys)?;
+    if proof.untrusted_root != root.hash {
+        return Err(Error::RootMismatch {
+            expected: root.hash,
+            got: proof.untrusted_root,
+        }
+        .into());
+    }
+
+    Ok(Chunk { proof })
+}
@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+use crate::common::crypto::hash::Hash;
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("checkpoint: unsupported version {0}")]
+    UnsupportedVersion(u16),
+    #[error("checkpoint: chunk index {0} out of range")]
+    ChunkIndexOutOfRange(usize),
+    #[error("checkpoint: corrupted chunk (expected: {expected:?} got: {got:?})")]
+    CorruptedChunk { expected: Hash, got: Hash },
+    #[error("checkpoint: corrupted node {0:?}")]
+    CorruptedNode(Hash),
+    #[error("checkpoint: restore not yet complete")]
+    RestoreIncomplete,
+    #[error("checkpoint: restored tree is missing node {0:?}")]
+    MissingNode(Hash),
+}
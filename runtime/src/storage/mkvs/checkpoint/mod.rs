@@ -0,0 +1,200 @@
+//! Tree checkpoints.
+//!
+//! A checkpoint splits a committed tree into chunks, each of which is a proof
+//! rooted at the tree root covering the lookup paths of a contiguous range of
+//! keys. Chunks are addressed by the hash of their encoding, so a restorer that
+//! only knows the checkpoint metadata can verify every chunk it receives from
+//! an untrusted source.
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{cbor, crypto::hash::Hash},
+    storage::mkvs::{sync::Proof, Root},
+};
+
+mod chunker;
+mod errors;
+mod restorer;
+
+pub use chunker::*;
+pub use errors::*;
+pub use restorer::*;
+
+/// Current version of the checkpoint format.
+pub const CHECKPOINT_VERSION: u16 = 1;
+
+/// Checkpoint metadata.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Version of the checkpoint format.
+    pub version: u16,
+    /// The root the checkpoint was created for.
+    pub root: Root,
+    /// Hashes of all chunks, in order.
+    pub chunks: Vec<Hash>,
+}
+
+/// A single checkpoint chunk.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Proof containing the nodes of the chunk.
+    pub proof: Proof,
+}
+
+impl Chunk {
+    /// Returns a hash of an encoded chunk.
+    pub fn encoded_hash(&self) -> Hash {
+        Hash::digest_bytes(&cbor::to_vec(&self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{stream, Future};
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::{sync::NoopReadSyncer, Tree};
+
+    fn make_tree() -> (Tree, Root) {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..100 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        let (_, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        let root = Root {
+            hash,
+            ..Default::default()
+        };
+
+        (tree, root)
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let (tree, root) = make_tree();
+        let (metadata, chunks) =
+            create_checkpoint(Context::background(), &tree, root, 256).expect("create_checkpoint");
+        assert_eq!(root, metadata.root);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.len(), metadata.chunks.len());
+
+        // Chunk creation is deterministic.
+        let (other_metadata, _) =
+            create_checkpoint(Context::background(), &tree, root, 256).expect("create_checkpoint");
+        assert_eq!(metadata, other_metadata);
+
+        // Corrupted chunks are rejected.
+        let mut restorer = ChunkRestorer::new(metadata.clone()).expect("new");
+        let mut corrupted = chunks[0].clone();
+        corrupted.proof.entries.pop();
+        assert!(restorer
+            .restore_chunk(Context::background(), 0, &corrupted)
+            .is_err());
+        assert!(restorer
+            .restore_chunk(Context::background(), 1, &chunks[0])
+            .is_err());
+        assert!(restorer
+            .restore_chunk(Context::background(), chunks.len(), &chunks[0])
+            .is_err());
+
+        // Chunks may be restored in any order.
+        for (index, chunk) in chunks.iter().enumerate().rev() {
+            assert!(!restorer.is_done());
+            let done = restorer
+                .restore_chunk(Context::background(), index, chunk)
+                .expect("restore_chunk");
+            assert_eq!(index == 0, done);
+        }
+        let restored = restorer.finish().expect("finish");
+        for i in 0..100 {
+            let value = restored
+                .get(Context::background(), format!("key {}", i).as_bytes())
+                .expect("get");
+            assert_eq!(Some(format!("value {}", i).into_bytes()), value);
+        }
+
+        // Restores must be complete.
+        let mut restorer = ChunkRestorer::new(metadata).expect("new");
+        restorer
+            .restore_chunk(Context::background(), 0, &chunks[0])
+            .expect("restore_chunk");
+        assert!(restorer.finish().is_err());
+
+        // Empty trees do not need any chunks.
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        let (_, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        let root = Root {
+            hash,
+            ..Default::default()
+        };
+        let (metadata, chunks) =
+            create_checkpoint(Context::background(), &tree, root, 256).expect("create_checkpoint");
+        assert!(chunks.is_empty());
+        let restorer = ChunkRestorer::new(metadata).expect("new");
+        assert!(restorer.is_done());
+        restorer.finish().expect("finish");
+    }
+
+    #[test]
+    fn test_checkpoint_stream_resume() {
+        let (tree, root) = make_tree();
+        let (metadata, chunks) =
+            create_checkpoint(Context::background(), &tree, root, 256).expect("create_checkpoint");
+        let total = chunks.len();
+
+        // Restore every other chunk from a stream.
+        let restorer = ChunkRestorer::new(metadata).expect("new");
+        let first: Vec<_> = chunks.iter().cloned().enumerate().step_by(2).collect();
+        let restorer = restorer
+            .restore_stream(Context::background(), stream::iter_ok(first))
+            .wait()
+            .expect("restore_stream");
+        let verified = (total + 1) / 2;
+        assert_eq!(
+            RestoreProgress { verified, total },
+            restorer.restore_progress()
+        );
+        assert_eq!(total - verified, restorer.pending_chunks().len());
+
+        // Corrupted nodes are rejected when resuming.
+        let manifest = restorer.manifest();
+        assert_eq!(verified, manifest.restored.len());
+        let nodes = restorer.nodes().clone();
+        let mut corrupted = nodes.clone();
+        let mut hashes = nodes.keys();
+        let (first, second) = (hashes.next().unwrap(), hashes.next().unwrap());
+        corrupted.insert(*first, nodes[second].clone());
+        assert!(ChunkRestorer::resume(manifest.clone(), corrupted).is_err());
+
+        // Resume from the manifest and the restored nodes.
+        let restorer = ChunkRestorer::resume(manifest, nodes).expect("resume");
+        assert_eq!(verified, restorer.restore_progress().verified);
+
+        let rest: Vec<_> = restorer
+            .pending_chunks()
+            .into_iter()
+            .rev()
+            .map(|index| (index, chunks[index].clone()))
+            .collect();
+        let restorer = restorer
+            .restore_stream(Context::background(), stream::iter_ok(rest))
+            .wait()
+            .expect("restore_stream");
+        assert!(restorer.is_done());
+        let restored = restorer.finish().expect("finish");
+        for i in 0..100 {
+            let value = restored
+                .get(Context::background(), format!("key {}", i).as_bytes())
+                .expect("get");
+            assert_eq!(Some(format!("value {}", i).into_bytes()), value);
+        }
+    }
+}
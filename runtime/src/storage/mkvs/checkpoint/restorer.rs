@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use anyhow::{Error, Result};
+use futures::{Future, Stream};
+use io_context::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{checkpoint::*, marshal::*, sync::*, tree::*},
+};
+
+/// Progress of a checkpoint restore.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RestoreProgress {
+    /// Number of chunks that have been verified and restored.
+    pub verified: usize,
+    /// Total number of chunks in the checkpoint.
+    pub total: usize,
+}
+
+/// A record of the chunks restored so far, allowing an interrupted restore
+/// to be resumed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Metadata of the checkpoint being restored.
+    pub metadata: Metadata,
+    /// Indices of the chunks that have been restored, in ascending order.
+    pub restored: Vec<u64>,
+}
+
+/// Restores a tree from checkpoint chunks.
+///
+/// Every chunk is checked against the hash listed in the checkpoint metadata
+/// and its proof is verified against the checkpoint root before any of its
+/// nodes are accepted. Chunks may be restored in any order.
+pub struct ChunkRestorer {
+    metadata: Metadata,
+    restored: Vec<bool>,
+    remaining: usize,
+    nodes: HashMap<Hash, Vec<u8>>,
+}
+
+impl ChunkRestorer {
+    /// Construct a new restorer for the checkpoint with the given metadata.
+    pub fn new(metadata: Metadata) -> Result<Self> {
+        if metadata.version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(metadata.version).into());
+        }
+        let restored = vec![false; metadata.chunks.len()];
+
+        Ok(Self {
+            metadata,
+            remaining: restored.len(),
+            restored,
+            nodes: HashMap::new(),
+        })
+    }
+
+    /// Resume an interrupted restore.
+    ///
+    /// The nodes are those previously returned by `nodes` and may come from
+    /// untrusted storage, as every node is checked against its hash and only
+    /// nodes reachable from the checkpoint root are ever used.
+    pub fn resume(manifest: Manifest, nodes: HashMap<Hash, Vec<u8>>) -> Result<Self> {
+        let mut restorer = Self::new(manifest.metadata)?;
+        let hasher = restorer.metadata.root.hash_algorithm.hasher();
+        for (hash, data) in &nodes {
+            let mut node = NodeBox::default();
+            node.unmarshal_binary(data)?;
+            node.update_decoded_hash_with(hasher);
+            if node.get_hash() != *hash {
+                return Err(CheckpointError::CorruptedNode(*hash).into());
+            }
+        }
+        restorer.nodes = nodes;
+
+        for index in manifest.restored {
+            let index = index as usize;
+            if index >= restorer.restored.len() {
+                return Err(CheckpointError::ChunkIndexOutOfRange(index).into());
+            }
+            if !restorer.restored[index] {
+                restorer.restored[index] = true;
+                restorer.remaining -= 1;
+            }
+        }
+
+        Ok(restorer)
+    }
+
+    /// Return the metadata of the checkpoint being restored.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Whether all chunks have been restored.
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Return the number of verified chunks out of all chunks.
+    pub fn restore_progress(&self) -> RestoreProgress {
+        RestoreProgress {
+            verified: self.restored.len() - self.remaining,
+            total: self.restored.len(),
+        }
+    }
+
+    /// Return the indices of all chunks which have not been restored yet.
+    pub fn pending_chunks(&self) -> Vec<usize> {
+        self.restored
+            .iter()
+            .enumerate()
+            .filter(|(_, &restored)| !restored)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Return a manifest of the restore, which together with the restored
+    /// nodes allows the restore to be resumed.
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            metadata: self.metadata.clone(),
+            restored: self
+                .restored
+                .iter()
+                .enumerate()
+                .filter(|(_, &restored)| restored)
+                .map(|(index, _)| index as u64)
+                .collect(),
+        }
+    }
+
+    /// Return the verified nodes restored so far, keyed by node hash.
+    pub fn nodes(&self) -> &HashMap<Hash, Vec<u8>> {
+        &self.nodes
+    }
+
+    /// Verify and restore the chunk with the given index.
+    ///
+    /// Returns whether all chunks have been restored. Restoring the same chunk
+    /// more than once has no effect.
+    pub fn restore_chunk(&mut self, ctx: Context, index: usize, chunk: &Chunk) -> Result<bool> {
+        let expected = *self
+            .metadata
+            .chunks
+            .get(index)
+            .ok_or(CheckpointError::ChunkIndexOutOfRange(index))?;
+        let got = chunk.encoded_hash();
+        if got != expected {
+            return Err(CheckpointError::CorruptedChunk { expected, got }.into());
+        }
+        if self.restored[index] {
+            return Ok(self.is_done());
+        }
+
+        let subtree = ProofVerifier.verify_proof_with(
+            ctx,
+            self.metadata.root.hash,
+            &chunk.proof,
+            self.metadata.root.hash_algorithm.hasher(),
+        )?;
+        collect_nodes(&subtree, &mut self.nodes)?;
+        self.restored[index] = true;
+        self.remaining -= 1;
+
+        Ok(self.is_done())
+    }
+
+    /// Restore chunks from a stream of chunks and their indices.
+    ///
+    /// Chunks may arrive in any order. The returned future resolves to the
+    /// restorer once the stream ends, or fails on the first invalid chunk.
+    pub fn restore_stream<S>(
+        self,
+        ctx: Context,
+        chunks: S,
+    ) -> impl Future<Item = Self, Error = Error>
+    where
+        S: Stream<Item = (usize, Chunk), Error = Error>,
+    {
+        let ctx = ctx.freeze();
+        chunks.fold(self, move |mut restorer, (index, chunk)| {
+            restorer
+                .restore_chunk(Context::create_child(&ctx), index, &chunk)
+                .map(|_| restorer)
+        })
+    }
+
+    /// Finish the restore and return the restored tree.
+    ///
+    /// The restored tree is backed by the verified nodes of all chunks and
+    /// restoring fails if any nodes of the tree are missing.
+    pub fn finish(self) -> Result<Tree> {
+        if !self.is_done() {
+            return Err(CheckpointError::RestoreIncomplete.into());
+        }
+        check_complete(&self.nodes, self.metadata.root.hash)?;
+
+        Ok(Tree::make()
+            .with_root(self.metadata.root)
+            .new(Box::new(MapReadSync::new(self.nodes))))
+    }
+}
+
+/// Collect all full nodes of a verified subtree in their full binary encoding.
+fn collect_nodes(ptr: &NodePtrRef, nodes: &mut HashMap<Hash, Vec<u8>>) -> Result<()> {
+    let ptr = ptr.borrow();
+    let node_ref = match ptr.node {
+        Some(ref node_ref) => node_ref.clone(),
+        None => return Ok(()),
+    };
+    nodes.insert(ptr.hash, node_ref.borrow().marshal_binary()?);
+
+    if let NodeBox::Internal(ref n) = *node_ref.borrow() {
+        collect_nodes(&n.left, nodes)?;
+        collect_nodes(&n.right, nodes)?;
+    }
+    Ok(())
+}
+
+/// Check that all nodes reachable from the given root are available.
+fn check_complete(nodes: &HashMap<Hash, Vec<u8>>, hash: Hash) -> Result<()> {
+    if hash.is_empty() {
+        return Ok(());
+    }
+    let data = nodes.get(&hash).ok_or(CheckpointError::MissingNode(hash))?;
+
+    let mut node = NodeBox::default();
+    node.unmarshal_binary(data)?;
+    if let NodeBox::Internal(ref n) = node {
+        check_complete(nodes, n.left.borrow().hash)?;
+        check_complete(nodes, n.right.borrow().hash)?;
+    }
+    Ok(())
+}
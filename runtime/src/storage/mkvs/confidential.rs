@@ -0,0 +1,207 @@
+//! Confidential MKVS wrapper.
+use anyhow::Result;
+use io_context::Context;
+
+use crate::{
+    common::{
+        crypto::{
+            hash::Hash,
+            kdf::hmac_sha512_256 as prf,
+            mrae::deoxysii::{DeoxysII, KEY_SIZE, NONCE_SIZE, TAG_SIZE},
+            secret::Secret,
+        },
+        roothash::Namespace,
+    },
+    storage::mkvs::{Prefix, WriteLog, MKVS},
+};
+
+const KEY_CONTEXT: &[u8] = b"oasis-core/mkvs: confidential key";
+const VALUE_CONTEXT: &[u8] = b"oasis-core/mkvs: confidential value";
+const NONCE_CONTEXT: &[u8] = b"oasis-core/mkvs: confidential nonce";
+
+/// An MKVS wrapper which encrypts all keys and values before storing them
+/// in the underlying MKVS.
+///
+/// Keys are replaced by a keyed PRF of the key, so they can still be looked
+/// up but no longer iterated over in order, and key prefixes are not
+/// preserved. Values are encrypted with Deoxys-II using the encrypted key as
+/// additional data and a nonce derived from the key and the version of the
+/// tree the value is written at, followed by the nonce.
+///
+/// All keys are derived from a single state key provided by the runtime.
+pub struct ConfidentialTree<M: MKVS> {
+    inner: M,
+    key_key: Secret<32>,
+    value_key: Secret<{ KEY_SIZE }>,
+    nonce_key: Secret<32>,
+    version: u64,
+}
+
+impl<M: MKVS> ConfidentialTree<M> {
+    /// Create a new confidential wrapper around the given MKVS, writing
+    /// values at the given version.
+    pub fn new(inner: M, state_key: &[u8; KEY_SIZE], version: u64) -> Self {
+        Self {
+            inner,
+            key_key: prf(state_key, &[KEY_CONTEXT]),
+            value_key: prf(state_key, &[VALUE_CONTEXT]),
+            nonce_key: prf(state_key, &[NONCE_CONTEXT]),
+            version,
+        }
+    }
+
+    /// Return a reference to the underlying MKVS.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Set the version at which values are written.
+    pub fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+
+    /// Return the key under which the given key is stored in the underlying
+    /// MKVS.
+    pub fn encrypt_key(&self, key: &[u8]) -> Vec<u8> {
+        prf::<32>(self.key_key.as_ref(), &[key]).as_ref().to_vec()
+    }
+
+    fn encrypt_value(&self, key: &[u8], encrypted_key: &[u8], value: &[u8]) -> Vec<u8> {
+        let nonce: Secret<{ NONCE_SIZE }> = prf(
+            self.nonce_key.as_ref(),
+            &[&self.version.to_be_bytes()[..], key],
+        );
+        let nonce = *nonce.as_bytes();
+
+        let d2 = DeoxysII::new(self.value_key.as_bytes());
+        let mut ciphertext = d2.seal(&nonce, value.to_vec(), encrypted_key.to_vec());
+        ciphertext.extend_from_slice(&nonce);
+        ciphertext
+    }
+
+    /// Decrypt a value stored in the underlying MKVS.
+    ///
+    /// # Panics
+    ///
+    /// Authentication failures are fatal, because they mean that the state
+    /// is corrupted.
+    fn decrypt_value(&self, encrypted_key: &[u8], ciphertext: Vec<u8>) -> Vec<u8> {
+        let ct_len = ciphertext.len();
+        if ct_len < TAG_SIZE + NONCE_SIZE {
+            panic!("confidential state is corrupted, invalid size");
+        }
+        let ct_len = ct_len - NONCE_SIZE;
+
+        // Split the ciphertext || tag || nonce.
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&ciphertext[ct_len..]);
+        let mut ciphertext = ciphertext;
+        ciphertext.truncate(ct_len);
+
+        let d2 = DeoxysII::new(self.value_key.as_bytes());
+        d2.open(&nonce, ciphertext, encrypted_key.to_vec())
+            .expect("confidential state is corrupted")
+    }
+}
+
+impl<M: MKVS> MKVS for ConfidentialTree<M> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        let encrypted_key = self.encrypt_key(key);
+        self.inner
+            .get(ctx, &encrypted_key)
+            .map(|value| self.decrypt_value(&encrypted_key, value))
+    }
+
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        self.inner.cache_contains_key(ctx, &self.encrypt_key(key))
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        let encrypted_key = self.encrypt_key(key);
+        let encrypted_value = self.encrypt_value(key, &encrypted_key, value);
+        self.inner
+            .insert(ctx, &encrypted_key, &encrypted_value)
+            .map(|value| self.decrypt_value(&encrypted_key, value))
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        let encrypted_key = self.encrypt_key(key);
+        self.inner
+            .remove(ctx, &encrypted_key)
+            .map(|value| self.decrypt_value(&encrypted_key, value))
+    }
+
+    /// Prefixes are not preserved by key encryption, so nothing is
+    /// prefetched.
+    fn prefetch_prefixes(&self, _ctx: Context, _prefixes: &Vec<Prefix>, _limit: u16) {}
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        self.inner.commit(ctx, namespace, version)
+    }
+
+    fn rollback(&mut self) {
+        self.inner.rollback()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::mkvs::{sync::NoopReadSyncer, Tree};
+
+    #[test]
+    fn test_confidential_tree() {
+        let tree = Tree::make().new(Box::new(NoopReadSyncer));
+        let mut tree = ConfidentialTree::new(tree, &[7u8; KEY_SIZE], 1);
+
+        assert_eq!(None, tree.insert(Context::background(), b"key", b"value"));
+        assert_eq!(
+            Some(b"value".to_vec()),
+            tree.get(Context::background(), b"key")
+        );
+
+        // Neither the key nor the value are stored in plain text.
+        assert_eq!(
+            None,
+            tree.inner().get(Context::background(), b"key").unwrap()
+        );
+        let encrypted_key = tree.encrypt_key(b"key");
+        let encrypted_value = tree
+            .inner()
+            .get(Context::background(), &encrypted_key)
+            .unwrap()
+            .expect("encrypted value");
+        assert_ne!(b"value".to_vec(), encrypted_value);
+
+        // Encryption is deterministic for a given version.
+        assert_eq!(
+            Some(b"value".to_vec()),
+            tree.insert(Context::background(), b"key", b"value")
+        );
+        assert_eq!(
+            Some(encrypted_value.clone()),
+            tree.inner()
+                .get(Context::background(), &encrypted_key)
+                .unwrap()
+        );
+        tree.set_version(2);
+        tree.insert(Context::background(), b"key", b"value");
+        assert_ne!(
+            Some(encrypted_value),
+            tree.inner()
+                .get(Context::background(), &encrypted_key)
+                .unwrap()
+        );
+
+        assert_eq!(
+            Some(b"value".to_vec()),
+            tree.remove(Context::background(), b"key")
+        );
+        assert_eq!(None, tree.get(Context::background(), b"key"));
+    }
+}
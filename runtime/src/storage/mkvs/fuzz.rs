@@ -0,0 +1,70 @@
+//! Fuzzing entry points.
+//!
+//! Each entry point decodes arbitrary input and panics if doing so violates
+//! any invariant, so that fuzzers only need to feed them data. Decoding must
+//! also not allocate memory out of proportion to the input size, so fuzzers
+//! should be run with a memory limit (e.g. honggfuzz's `--rlimit_rss`).
+use io_context::Context;
+
+use crate::{
+    common::cbor,
+    storage::mkvs::{
+        marshal::Marshal,
+        sync::{Proof, ProofVerifier},
+        NodeBox, WriteLog,
+    },
+};
+
+/// Unmarshal a node from arbitrary data.
+///
+/// A successfully unmarshalled node must not be larger than the data it was
+/// decoded from, and marshalling it must be stable.
+pub fn fuzz_unmarshal_node(data: &[u8]) {
+    let mut node = NodeBox::default();
+    let size = match node.unmarshal_binary(data) {
+        Ok(size) => size,
+        Err(_) => return,
+    };
+    assert!(size <= data.len(), "consumed more data than available");
+
+    let encoded = node.marshal_binary().expect("marshal decoded node");
+    let mut decoded = NodeBox::default();
+    let decoded_size = decoded
+        .unmarshal_binary(&encoded)
+        .expect("unmarshal marshalled node");
+    assert_eq!(
+        encoded.len(),
+        decoded_size,
+        "marshalled node has trailing data"
+    );
+    assert_eq!(
+        encoded,
+        decoded.marshal_binary().expect("marshal decoded node"),
+        "marshalling is not stable"
+    );
+}
+
+/// Decode a proof from arbitrary data and verify it.
+pub fn fuzz_proof_decode(data: &[u8]) {
+    let proof: Proof = match cbor::from_slice(data) {
+        Ok(proof) => proof,
+        Err(_) => return,
+    };
+
+    let pv = ProofVerifier;
+    let _ = pv.verify_proof(Context::background(), proof.untrusted_root, &proof);
+}
+
+/// Decode a write log from arbitrary data.
+///
+/// Encoding a successfully decoded write log must round-trip.
+pub fn fuzz_write_log_decode(data: &[u8]) {
+    let write_log: WriteLog = match cbor::from_slice(data) {
+        Ok(write_log) => write_log,
+        Err(_) => return,
+    };
+
+    let encoded = cbor::to_vec(&write_log);
+    let decoded: WriteLog = cbor::from_slice(&encoded).expect("decode encoded write log");
+    assert_eq!(write_log, decoded, "write log encoding does not round-trip");
+}
@@ -0,0 +1,203 @@
+//! Typed key formats.
+//!
+//! A key consists of a prefix byte identifying its format followed by typed
+//! fields. Each field is encoded with a fixed width and in big-endian byte
+//! order, so that iterating over keys in the tree visits them in the order of
+//! their field values and any leading fields can be used as an iteration
+//! prefix.
+//!
+//! Key formats are most easily defined via the `key_format!` macro:
+//!
+//! ```ignore
+//! key_format!(
+//!     /// Account balances, keyed by account and asset.
+//!     pub struct BalanceKeyFormat('B' as u8) {
+//!         pub account: Hash,
+//!         pub asset: u32,
+//!     }
+//! );
+//! ```
+use crate::common::crypto::hash::Hash;
+
+pub use crate::common::key_format::KeyFormat;
+
+/// A key format field with a fixed-width, order-preserving encoding.
+pub trait KeyFormatAtom {
+    /// Size of the encoded field.
+    fn size() -> usize;
+
+    /// Encode the field.
+    fn encode_atom(self) -> Vec<u8>;
+
+    /// Decode the field from data of exactly `size` bytes.
+    fn decode_atom(data: &[u8]) -> Self
+    where
+        Self: Sized;
+}
+
+macro_rules! impl_key_format_atom_for_uint {
+    ($ty:ty) => {
+        impl KeyFormatAtom for $ty {
+            fn size() -> usize {
+                std::mem::size_of::<$ty>()
+            }
+
+            fn encode_atom(self) -> Vec<u8> {
+                self.to_be_bytes().to_vec()
+            }
+
+            fn decode_atom(data: &[u8]) -> Self {
+                let mut bytes = [0u8; std::mem::size_of::<$ty>()];
+                bytes.copy_from_slice(data);
+                <$ty>::from_be_bytes(bytes)
+            }
+        }
+    };
+}
+
+impl_key_format_atom_for_uint!(u8);
+impl_key_format_atom_for_uint!(u16);
+impl_key_format_atom_for_uint!(u32);
+impl_key_format_atom_for_uint!(u64);
+
+impl KeyFormatAtom for Hash {
+    fn size() -> usize {
+        Hash::len()
+    }
+
+    fn encode_atom(self) -> Vec<u8> {
+        self.as_ref().to_vec()
+    }
+
+    fn decode_atom(data: &[u8]) -> Self {
+        data.into()
+    }
+}
+
+/// Define a key format struct whose fields are all `KeyFormatAtom`s, together
+/// with its `KeyFormat` implementation.
+#[macro_export]
+macro_rules! key_format {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident($prefix:expr) {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident : $ty:ty),+ $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $ty),+
+        }
+
+        impl $crate::storage::mkvs::keyformat::KeyFormat for $name {
+            fn prefix() -> u8 {
+                $prefix
+            }
+
+            fn size() -> usize {
+                [$(<$ty as $crate::storage::mkvs::keyformat::KeyFormatAtom>::size()),+]
+                    .iter()
+                    .sum()
+            }
+
+            fn encode_atoms(self, atoms: &mut Vec<Vec<u8>>) {
+                $(
+                    atoms.push(
+                        $crate::storage::mkvs::keyformat::KeyFormatAtom::encode_atom(self.$field),
+                    );
+                )+
+            }
+
+            fn decode_atoms(data: &[u8]) -> Self {
+                let mut offset = 0;
+                $(
+                    let size = <$ty as $crate::storage::mkvs::keyformat::KeyFormatAtom>::size();
+                    let $field = <$ty as $crate::storage::mkvs::keyformat::KeyFormatAtom>::decode_atom(
+                        &data[offset..offset + size],
+                    );
+                    offset += size;
+                )+
+                let _ = offset;
+
+                Self { $($field),+ }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+    use rustc_hex::ToHex;
+
+    use super::*;
+    use crate::storage::mkvs::{sync::NoopReadSyncer, Tree};
+
+    key_format!(
+        #[derive(Debug, PartialEq)]
+        struct TestKeyFormat('K' as u8) {
+            id: u64,
+            hash: Hash,
+            kind: u8,
+        }
+    );
+
+    #[test]
+    fn test_key_format_macro() {
+        assert_eq!(8 + 32 + 1, TestKeyFormat::size());
+
+        let enc = TestKeyFormat {
+            id: 0x0102,
+            hash: Hash::empty_hash(),
+            kind: 7,
+        }
+        .encode();
+        assert_eq!(
+            enc.to_hex::<String>(),
+            "4b0000000000000102c672b8d1ef56ed28ab87c3622c5114069bdd3ad7b8f9737498d0c01ecef0967a07"
+        );
+        assert_eq!(
+            Some(TestKeyFormat {
+                id: 0x0102,
+                hash: Hash::empty_hash(),
+                kind: 7,
+            }),
+            TestKeyFormat::decode(&enc)
+        );
+
+        // Partial encoding.
+        let enc = TestKeyFormat {
+            id: 0x0102,
+            hash: Hash::empty_hash(),
+            kind: 7,
+        }
+        .encode_partial(1);
+        assert_eq!(enc.to_hex::<String>(), "4b0000000000000102");
+    }
+
+    #[test]
+    fn test_key_format_ordering() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        let ids = [1000u64, 1, 256, 2, u64::max_value()];
+        for &id in &ids {
+            let key = TestKeyFormat {
+                id,
+                hash: Hash::empty_hash(),
+                kind: 0,
+            }
+            .encode();
+            tree.insert(Context::background(), &key, b"value")
+                .expect("insert");
+        }
+
+        // Keys are iterated over in the order of their field values.
+        let mut it = tree.iter(Context::background());
+        it.rewind();
+        let iterated: Vec<u64> = it
+            .map(|(key, _)| TestKeyFormat::decode(&key).expect("decode").id)
+            .collect();
+        let mut sorted = ids.to_vec();
+        sorted.sort();
+        assert_eq!(sorted, iterated);
+    }
+}
@@ -3,6 +3,18 @@ use std::io::Cursor;
 use anyhow::{anyhow, Result};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
+pub use oasis_core_runtime_derive::Marshal;
+
+/// Node serialization mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarshalMode {
+    /// Full encoding, internal nodes include hashes of their children.
+    Full,
+    /// Compact encoding, internal nodes omit their children which are
+    /// expected to be encoded separately (e.g., as subsequent proof entries).
+    Compact,
+}
+
 /// The `Marshal` trait is used for marshaling and unmarshaling MKVS trees.
 pub trait Marshal {
     /// Marshal the object into a binary form and return it as a new vector.
@@ -64,3 +76,103 @@ impl Marshal for u64 {
         }
     }
 }
+
+/// Helpers used by code generated by `#[derive(Marshal)]`.
+#[doc(hidden)]
+pub mod derive {
+    use std::convert::TryFrom;
+
+    use anyhow::anyhow;
+    pub use anyhow::Result;
+
+    /// Read `len` bytes of the given field at `offset`, advancing it.
+    pub fn read<'a>(
+        data: &'a [u8],
+        offset: &mut usize,
+        len: usize,
+        field: &str,
+    ) -> Result<&'a [u8]> {
+        let end = offset
+            .checked_add(len)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| anyhow!("mkvs: malformed {}", field))?;
+        let result = &data[*offset..end];
+        *offset = end;
+        Ok(result)
+    }
+
+    /// Convert the length of the given field into its length prefix.
+    pub fn length<T: TryFrom<usize>>(len: usize, field: &str) -> Result<T> {
+        T::try_from(len).map_err(|_| anyhow!("mkvs: {} too large", field))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::crypto::hash::Hash;
+
+    #[derive(Debug, Default, PartialEq, Marshal)]
+    #[marshal(crate_path = "crate")]
+    struct Inner(u8, [u8; 2]);
+
+    #[derive(Debug, Default, PartialEq, Marshal)]
+    #[marshal(crate_path = "crate")]
+    struct Record {
+        version: u64,
+        hash: Hash,
+        #[marshal(len = "u16")]
+        key: Vec<u8>,
+        value: Vec<u8>,
+        inner: Inner,
+    }
+
+    #[test]
+    fn test_derive_marshal() {
+        let record = Record {
+            version: 0x0102,
+            hash: Hash::digest_bytes(b"foo"),
+            key: b"key".to_vec(),
+            value: b"value".to_vec(),
+            inner: Inner(7, [8, 9]),
+        };
+
+        let data = record.marshal_binary().expect("marshal");
+        let mut expected = vec![0x02, 0x01, 0, 0, 0, 0, 0, 0];
+        expected.extend_from_slice(record.hash.as_ref());
+        expected.extend_from_slice(&[3, 0]);
+        expected.extend_from_slice(b"key");
+        expected.extend_from_slice(&[5, 0, 0, 0]);
+        expected.extend_from_slice(b"value");
+        expected.extend_from_slice(&[7, 8, 9]);
+        assert_eq!(expected, data);
+
+        // Trailing data is not consumed.
+        let mut input = data.clone();
+        input.push(0xff);
+        let mut decoded = Record::default();
+        let size = decoded.unmarshal_binary(&input).expect("unmarshal");
+        assert_eq!(data.len(), size);
+        assert_eq!(record, decoded);
+
+        // Truncated input is rejected at every length.
+        for len in 0..data.len() {
+            let mut decoded = Record::default();
+            assert!(decoded.unmarshal_binary(&data[..len]).is_err());
+        }
+
+        // Length prefixes larger than the input are rejected.
+        let mut input = data.clone();
+        input[40] = 0xff;
+        input[41] = 0xff;
+        let mut decoded = Record::default();
+        assert!(decoded.unmarshal_binary(&input).is_err());
+
+        // Fields too large for their length prefix can not be marshaled.
+        let record = Record {
+            key: vec![0; 0x10000],
+            ..Default::default()
+        };
+        assert!(record.marshal_binary().is_err());
+    }
+}
@@ -12,14 +12,30 @@ use crate::common::{crypto::hash::Hash, roothash::Namespace};
 #[macro_use]
 mod tree;
 mod cache;
+pub mod checkpoint;
+mod confidential;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 #[cfg(test)]
 mod interop;
+pub mod keyformat;
 pub mod marshal;
+pub mod proof;
 pub mod sync;
 #[cfg(test)]
 mod tests;
+mod tracker;
+mod write_log;
 
-pub use tree::{Depth, Key, NodeBox, Root, Tree};
+pub use cache::{CachePolicy, ClockPolicy, LRUPolicy, SegmentedLRUPolicy};
+pub use confidential::ConfidentialTree;
+pub use tracker::{Access, AccessKind, AccessTracker};
+pub use tree::{
+    Depth, Error, Format, Key, MultiVersionTree, NodeBox, NodeTestVector, OverlayTree,
+    OverlayTreeIterator, ProofTestVector, ReadOnlyTree, Root, RootType, TestVectors, Tree,
+    UnmarshalLimits, WriteLogEntryTestVector, WriteLogTestVector, SUBTREE_EXPORT_VERSION,
+};
+pub use write_log::WriteLogExt;
 
 /// The type of entry in the log.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -155,11 +171,134 @@ pub trait MKVS: Send + Sync {
     fn rollback(&mut self);
 }
 
+impl<T: MKVS + ?Sized> MKVS for Box<T> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        T::get(self, ctx, key)
+    }
+
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        T::cache_contains_key(self, ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        T::insert(self, ctx, key, value)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        T::remove(self, ctx, key)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) {
+        T::prefetch_prefixes(self, ctx, prefixes, limit)
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        T::commit(self, ctx, namespace, version)
+    }
+
+    fn rollback(&mut self) {
+        T::rollback(self)
+    }
+}
+
+/// An iterator over the keys and values of a merklized key-value store.
+pub trait MKVSIterator: Iterator<Item = (Vec<u8>, Vec<u8>)> {
+    /// Sets the number of next elements to prefetch.
+    fn set_prefetch(&mut self, prefetch: usize);
+
+    /// Return whether the iterator is valid.
+    fn is_valid(&self) -> bool;
+
+    /// Return the error that occurred during iteration if any.
+    fn error(&self) -> &Option<anyhow::Error>;
+
+    /// Move the iterator to the first key in the tree.
+    fn rewind(&mut self);
+
+    /// Moves the iterator either at the given key or at the next larger key.
+    fn seek(&mut self, key: &[u8]);
+
+    /// Return the key at the current iterator position if any.
+    fn key(&self) -> Option<&Vec<u8>>;
+
+    /// Return the value at the current iterator position if any.
+    fn value(&self) -> Option<&Vec<u8>>;
+}
+
+/// Merklized key-value store which propagates errors instead of panicking.
+pub trait FallibleMKVS {
+    /// Fetch entry with given key.
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Check if the local MKVS cache contains the given key.
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool;
+
+    /// Update entry with given key, returning the previous value if any.
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Remove entry with given key, returning the previous value if any.
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Populate the in-memory tree with nodes for keys starting with given prefixes.
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()>;
+
+    /// Returns an iterator over the store.
+    fn iter(&self, ctx: Context) -> Box<dyn MKVSIterator + '_>;
+
+    /// Commit all database changes to the underlying store.
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)>;
+}
+
+impl<T: FallibleMKVS + ?Sized> FallibleMKVS for &mut T {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        <T as FallibleMKVS>::get(self, ctx, key)
+    }
+
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        <T as FallibleMKVS>::cache_contains_key(self, ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        <T as FallibleMKVS>::insert(self, ctx, key, value)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        <T as FallibleMKVS>::remove(self, ctx, key)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
+        <T as FallibleMKVS>::prefetch_prefixes(self, ctx, prefixes, limit)
+    }
+
+    fn iter(&self, ctx: Context) -> Box<dyn MKVSIterator + '_> {
+        <T as FallibleMKVS>::iter(self, ctx)
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        <T as FallibleMKVS>::commit(self, ctx, namespace, version)
+    }
+}
+
 #[cfg(test)]
 mod _tests {
     use super::*;
 
-    use crate::common::cbor;
+    use crate::{common::cbor, storage::mkvs::sync::NoopReadSyncer};
 
     #[test]
     fn test_write_log_serialization() {
@@ -173,4 +312,19 @@ mod _tests {
 
         assert_eq!(write_log, deserialized);
     }
+
+    #[test]
+    fn test_boxed_mkvs() {
+        fn insert(mkvs: &mut dyn MKVS, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+            mkvs.insert(Context::background(), key, value)
+        }
+
+        let mut mkvs: Box<dyn MKVS> = Box::new(Tree::make().new(Box::new(NoopReadSyncer)));
+        assert_eq!(None, insert(&mut mkvs, b"foo", b"bar"));
+        assert_eq!(Some(b"bar".to_vec()), insert(&mut mkvs, b"foo", b"baz"));
+        assert_eq!(
+            Some(b"baz".to_vec()),
+            mkvs.get(Context::background(), b"foo")
+        );
+    }
 }
@@ -0,0 +1,248 @@
+//! Stateless verification of MKVS proofs.
+use anyhow::{anyhow, Result};
+use io_context::Context;
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{sync::ProofVerifier, tree::*},
+};
+
+pub use crate::storage::mkvs::sync::{Proof, RawProofEntry};
+
+/// A verifier for inclusion and non-inclusion proofs of keys.
+///
+/// In contrast to a `Tree` backed by a verifying read syncer, the verifier
+/// does not need any cache or other state and can be used to check proofs
+/// obtained from untrusted sources against an independently obtained root.
+#[derive(Default)]
+pub struct Verifier {
+    hash_algorithm: HashAlgorithm,
+}
+
+impl Verifier {
+    /// Construct a verifier for proofs of trees whose nodes are hashed using
+    /// the given digest algorithm (e.g., the one recorded in their `Root`).
+    pub fn new(hash_algorithm: HashAlgorithm) -> Self {
+        Self { hash_algorithm }
+    }
+
+    /// Verify a proof for the given key against the given root hash.
+    ///
+    /// On success, returns the value of the key if the proof is an inclusion
+    /// proof or `None` if the proof shows that the key does not exist.
+    pub fn verify(
+        &self,
+        ctx: Context,
+        root: Hash,
+        key: &[u8],
+        proof: &Proof,
+    ) -> Result<Option<Vec<u8>>> {
+        let root_ptr =
+            ProofVerifier.verify_proof_with(ctx, root, proof, self.hash_algorithm.hasher())?;
+        Self::lookup(root_ptr, 0, &key.to_vec())
+    }
+
+    /// Verify a proof for multiple keys (e.g., one returned by
+    /// `Tree::get_multi_with_proof`) against the given root hash.
+    ///
+    /// On success, returns the values of the keys in the order they were
+    /// given, with `None` for keys that the proof shows do not exist. Fails
+    /// if the proof does not cover all of the keys.
+    pub fn verify_multi(
+        &self,
+        ctx: Context,
+        root: Hash,
+        keys: &[&[u8]],
+        proof: &Proof,
+    ) -> Result<Vec<Option<Vec<u8>>>> {
+        let root_ptr =
+            ProofVerifier.verify_proof_with(ctx, root, proof, self.hash_algorithm.hasher())?;
+        keys.iter()
+            .map(|key| Self::lookup(root_ptr.clone(), 0, &key.to_vec()))
+            .collect()
+    }
+
+    fn lookup(ptr: NodePtrRef, bit_depth: Depth, key: &Key) -> Result<Option<Value>> {
+        if ptr.borrow().is_null() {
+            // Reached a nil node, there is nothing here.
+            return Ok(None);
+        }
+        let node_ref = match ptr.borrow().node {
+            Some(ref node_ref) => node_ref.clone(),
+            None => return Err(anyhow!("verifier: proof does not cover the key")),
+        };
+
+        let node = node_ref.borrow();
+        match *node {
+            NodeBox::Internal(ref n) => {
+                let bit_length = bit_depth + n.label_bit_length;
+
+                // Does lookup key end here? Look into LeafNode.
+                if key.bit_length() == bit_length {
+                    return Self::lookup(n.leaf_node.clone(), bit_length, key);
+                }
+
+                // Lookup key is too short for the current label. It's not stored.
+                if key.bit_length() < bit_length {
+                    return Ok(None);
+                }
+
+                // Continue recursively based on a bit value.
+                if key.get_bit(bit_length) {
+                    Self::lookup(n.right.clone(), bit_length, key)
+                } else {
+                    Self::lookup(n.left.clone(), bit_length, key)
+                }
+            }
+            NodeBox::Leaf(ref n) => {
+                // Reached a leaf node, check if key matches.
+                if n.key == *key {
+                    Ok(Some(n.value.clone()))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::sync::NoopReadSyncer;
+
+    #[test]
+    fn test_verifier() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..100 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        let (_, root) = tree
+            .commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+
+        let verifier = Verifier::default();
+
+        // Inclusion proof.
+        let (_, proof) = tree
+            .get_with_proof(Context::background(), b"key 42")
+            .expect("get_with_proof");
+        let value = verifier
+            .verify(Context::background(), root, b"key 42", &proof)
+            .expect("inclusion proof should verify");
+        assert_eq!(Some(b"value 42".to_vec()), value);
+
+        // A proof for one key does not cover unrelated keys.
+        let result = verifier.verify(Context::background(), root, b"key 7", &proof);
+        assert!(result.is_err(), "proof should not cover unrelated key");
+
+        // Non-inclusion proof.
+        let (_, proof) = tree
+            .get_with_proof(Context::background(), b"key 420")
+            .expect("get_with_proof");
+        let value = verifier
+            .verify(Context::background(), root, b"key 420", &proof)
+            .expect("non-inclusion proof should verify");
+        assert_eq!(None, value);
+
+        // Proof for a different root.
+        let bogus_root = Hash::digest_bytes(b"i am a bogus hash");
+        let result = verifier.verify(Context::background(), bogus_root, b"key 42", &proof);
+        assert!(result.is_err(), "proof for a different root should fail");
+    }
+
+    #[test]
+    fn test_verifier_multi() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..100 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        let (_, root) = tree
+            .commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+
+        let keys: Vec<&[u8]> = vec![
+            &b"key 42"[..],
+            &b"key 420"[..],
+            &b"key 1"[..],
+            &b"key 99"[..],
+            &b"key 42"[..],
+        ];
+        let (values, proof) = tree
+            .get_multi_with_proof(Context::background(), &keys)
+            .expect("get_multi_with_proof");
+        let expected = vec![
+            Some(b"value 42".to_vec()),
+            None,
+            Some(b"value 1".to_vec()),
+            Some(b"value 99".to_vec()),
+            Some(b"value 42".to_vec()),
+        ];
+        assert_eq!(expected, values);
+
+        let verifier = Verifier::default();
+        let verified = verifier
+            .verify_multi(Context::background(), root, &keys, &proof)
+            .expect("multiproof should verify");
+        assert_eq!(expected, verified);
+
+        // Shared nodes are only included once.
+        let individual: usize = keys[..4]
+            .iter()
+            .map(|key| {
+                let (_, proof) = tree
+                    .get_with_proof(Context::background(), key)
+                    .expect("get_with_proof");
+                proof.entries.len()
+            })
+            .sum();
+        assert!(proof.entries.len() < individual);
+
+        // A multiproof does not cover unrelated keys.
+        let result = verifier.verify_multi(
+            Context::background(),
+            root,
+            &[&b"key 42"[..], &b"key 7"[..]],
+            &proof,
+        );
+        assert!(result.is_err(), "proof should not cover unrelated key");
+
+        // Proof for a different root.
+        let bogus_root = Hash::digest_bytes(b"i am a bogus hash");
+        let result = verifier.verify_multi(Context::background(), bogus_root, &keys, &proof);
+        assert!(result.is_err(), "proof for a different root should fail");
+
+        // Keys diverging from the tree inside a node label.
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        tree.insert(Context::background(), b"aa", b"first")
+            .expect("insert");
+        tree.insert(Context::background(), b"ab", b"second")
+            .expect("insert");
+        let (_, root) = tree
+            .commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+
+        let keys: Vec<&[u8]> = vec![&b"ba"[..], &b"a"[..], &b"ab"[..]];
+        let (values, proof) = tree
+            .get_multi_with_proof(Context::background(), &keys)
+            .expect("get_multi_with_proof");
+        let expected = vec![None, None, Some(b"second".to_vec())];
+        assert_eq!(expected, values);
+        let verified = verifier
+            .verify_multi(Context::background(), root, &keys, &proof)
+            .expect("multiproof should verify");
+        assert_eq!(expected, verified);
+    }
+}
@@ -0,0 +1,246 @@
+use std::{any::Any, cell::RefCell, rc::Rc};
+
+use anyhow::{Error, Result};
+use futures::{future, Async, Future, Poll};
+use io_context::Context;
+use serde::Serialize;
+
+use crate::{common::cbor, storage::mkvs::sync::*};
+
+/// A future resolving to a proof response.
+pub type ProofFuture = Box<dyn Future<Item = ProofResponse, Error = Error>>;
+
+/// An asynchronous variant of the `ReadSync` interface.
+///
+/// This allows read syncers to start multiple requests without waiting for
+/// each response in turn.
+pub trait AsyncReadSync {
+    /// Return `self` as an `Any` object, useful for downcasting.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Fetch a single key and returns the corresponding proof.
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> ProofFuture;
+
+    /// Fetch all keys under the given prefixes and returns the corresponding proofs.
+    fn sync_get_prefixes(&mut self, ctx: Context, request: GetPrefixesRequest) -> ProofFuture;
+
+    /// Seek to a given key and then fetch the specified number of following items
+    /// based on key iteration order.
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> ProofFuture;
+}
+
+/// An adapter exposing a synchronous read syncer via the asynchronous interface.
+///
+/// Requests are performed immediately and the returned futures are already
+/// resolved.
+pub struct AsyncReadSyncAdapter {
+    rs: Box<dyn ReadSync>,
+}
+
+impl AsyncReadSyncAdapter {
+    /// Construct a new adapter for the given read syncer.
+    pub fn new(rs: Box<dyn ReadSync>) -> Self {
+        Self { rs }
+    }
+}
+
+impl AsyncReadSync for AsyncReadSyncAdapter {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> ProofFuture {
+        Box::new(future::result(self.rs.sync_get(ctx, request)))
+    }
+
+    fn sync_get_prefixes(&mut self, ctx: Context, request: GetPrefixesRequest) -> ProofFuture {
+        Box::new(future::result(self.rs.sync_get_prefixes(ctx, request)))
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> ProofFuture {
+        Box::new(future::result(self.rs.sync_iterate(ctx, request)))
+    }
+}
+
+/// A read syncer driving an asynchronous read syncer, e.g. so that it can be
+/// used as backing for a `Tree`.
+///
+/// Each request blocks the current thread until the future returned by the
+/// asynchronous read syncer resolves, so the futures must not depend on the
+/// current thread to make progress.
+pub struct BlockingReadSync {
+    rs: Box<dyn AsyncReadSync>,
+}
+
+impl BlockingReadSync {
+    /// Construct a new instance, driving the given asynchronous read syncer.
+    pub fn new(rs: Box<dyn AsyncReadSync>) -> Self {
+        Self { rs }
+    }
+
+    /// Return the asynchronous read syncer driven by this instance.
+    pub fn inner(&self) -> &dyn AsyncReadSync {
+        &*self.rs
+    }
+}
+
+impl ReadSync for BlockingReadSync {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        self.rs.sync_get(ctx, request).wait()
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        self.rs.sync_get_prefixes(ctx, request).wait()
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        self.rs.sync_iterate(ctx, request).wait()
+    }
+}
+
+/// State shared between a deferring read syncer and the fetchers driving it.
+struct DeferringState {
+    rs: Box<dyn AsyncReadSync>,
+    defer: bool,
+    pending: Option<(Vec<u8>, ProofFuture)>,
+    ready: Option<(Vec<u8>, Result<ProofResponse>)>,
+}
+
+/// A read syncer driving an asynchronous read syncer, used as backing for
+/// trees constructed via `Options::new_async`.
+///
+/// Requests made while an `AsyncFetcher` polls an operation are deferred:
+/// the request fails and its future is handed to the fetcher, which retries
+/// the operation once the future resolves, answering the same request with
+/// the received response. All other requests block until the future
+/// resolves, like with `BlockingReadSync`.
+#[derive(Clone)]
+pub(crate) struct DeferringReadSync {
+    state: Rc<RefCell<DeferringState>>,
+}
+
+impl DeferringReadSync {
+    /// Construct a new instance, driving the given asynchronous read syncer.
+    pub(crate) fn new(rs: Box<dyn AsyncReadSync>) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(DeferringState {
+                rs,
+                defer: false,
+                pending: None,
+                ready: None,
+            })),
+        }
+    }
+
+    /// Whether the operation being polled has deferred a request.
+    pub(crate) fn is_deferred(&self) -> bool {
+        self.state.borrow().pending.is_some()
+    }
+
+    fn request<R, F>(&self, ctx: Context, request: R, start: F) -> Result<ProofResponse>
+    where
+        R: Serialize,
+        F: FnOnce(&mut dyn AsyncReadSync, Context, R) -> ProofFuture,
+    {
+        let id = cbor::to_vec(&request);
+        let mut state = self.state.borrow_mut();
+        if state
+            .ready
+            .as_ref()
+            .map_or(false, |(ready, _)| *ready == id)
+        {
+            return state.ready.take().expect("response is ready").1;
+        }
+
+        let fetch = start(&mut *state.rs, ctx, request);
+        if !state.defer {
+            drop(state);
+            return fetch.wait();
+        }
+        state.pending = Some((id, fetch));
+        Err(SyncerError::Deferred.into())
+    }
+}
+
+impl ReadSync for DeferringReadSync {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        self.request(ctx, request, |rs, ctx, request| rs.sync_get(ctx, request))
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        self.request(ctx, request, |rs, ctx, request| {
+            rs.sync_get_prefixes(ctx, request)
+        })
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        self.request(ctx, request, |rs, ctx, request| {
+            rs.sync_iterate(ctx, request)
+        })
+    }
+}
+
+/// Drives a tree operation, waiting for the fetches it defers without
+/// blocking and retrying it until it completes.
+pub(crate) struct AsyncFetcher {
+    rs: Option<DeferringReadSync>,
+    fetch: Option<(Vec<u8>, ProofFuture)>,
+}
+
+impl AsyncFetcher {
+    /// Construct a new fetcher for the given deferring read syncer. Without
+    /// one, operations are simply run to completion.
+    pub(crate) fn new(rs: Option<DeferringReadSync>) -> Self {
+        Self { rs, fetch: None }
+    }
+
+    /// Poll the given operation, which is retried after each deferred fetch
+    /// and must therefore not have any side effects when a fetch fails.
+    pub(crate) fn poll<T, F>(&mut self, mut op: F) -> Poll<T, Error>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let rs = match self.rs {
+            Some(ref rs) => rs,
+            None => return op().map(Async::Ready),
+        };
+
+        loop {
+            if let Some((_, ref mut fetch)) = self.fetch {
+                let rsp = match fetch.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(rsp)) => Ok(rsp),
+                    Err(err) => Err(err),
+                };
+                let (id, _) = self.fetch.take().expect("fetch in progress");
+                rs.state.borrow_mut().ready = Some((id, rsp));
+            }
+
+            rs.state.borrow_mut().defer = true;
+            let result = op();
+            let mut state = rs.state.borrow_mut();
+            state.defer = false;
+            state.ready = None;
+            match state.pending.take() {
+                Some(fetch) => self.fetch = Some(fetch),
+                None => return result.map(Async::Ready),
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Result};
+
+use crate::storage::mkvs::sync::*;
+
+/// Reassembles a proof response from partial proof responses.
+#[derive(Default)]
+pub struct ProofAssembler {
+    proof: Option<Proof>,
+    chunks: u32,
+}
+
+impl ProofAssembler {
+    /// Construct a new, empty proof assembler.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of chunks added so far.
+    pub fn chunks(&self) -> u32 {
+        self.chunks
+    }
+
+    /// Add the next chunk of the response.
+    ///
+    /// Returns the full response once the last chunk has been added.
+    pub fn add(&mut self, chunk: PartialProofResponse) -> Result<Option<ProofResponse>> {
+        let mut chunk_proof = chunk.proof;
+        match self.proof {
+            None => self.proof = Some(chunk_proof),
+            Some(ref mut proof) => {
+                if proof.untrusted_root != chunk_proof.untrusted_root {
+                    return Err(anyhow!(
+                        "mkvs: got proof chunk for unexpected root ({:?})",
+                        chunk_proof.untrusted_root
+                    ));
+                }
+                proof.entries.append(&mut chunk_proof.entries);
+            }
+        }
+        self.chunks += 1;
+
+        if chunk.more {
+            return Ok(None);
+        }
+        Ok(self.proof.take().map(|proof| ProofResponse { proof }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::crypto::hash::Hash;
+
+    #[test]
+    fn test_proof_assembler() {
+        let response = ProofResponse {
+            proof: Proof {
+                untrusted_root: Hash::digest_bytes(b"root"),
+                entries: (0..10u8)
+                    .map(|i| Some(RawProofEntry(vec![i])))
+                    .chain(std::iter::once(None))
+                    .collect(),
+            },
+        };
+
+        for &max_entries in &[1, 3, 11, 100] {
+            let chunks = response.clone().into_chunks(max_entries);
+            assert_eq!((11 + max_entries - 1) / max_entries, chunks.len());
+
+            let mut assembler = ProofAssembler::new();
+            let mut result = None;
+            for chunk in chunks {
+                assert!(result.is_none(), "no chunks should follow the last one");
+                result = assembler.add(chunk).expect("add");
+            }
+            assert_eq!(Some(response.clone()), result);
+        }
+
+        // Empty proofs are sent as a single chunk.
+        let chunks = ProofResponse::default().into_chunks(10);
+        assert_eq!(1, chunks.len());
+        let result = ProofAssembler::new().add(chunks[0].clone()).expect("add");
+        assert_eq!(Some(ProofResponse::default()), result);
+
+        // Chunks for different roots must not be mixed.
+        let mut chunks = response.clone().into_chunks(5);
+        chunks[1].proof.untrusted_root = Hash::digest_bytes(b"other root");
+        let mut assembler = ProofAssembler::new();
+        assembler.add(chunks[0].clone()).expect("add");
+        assert!(assembler.add(chunks[1].clone()).is_err());
+    }
+}
@@ -4,4 +4,8 @@ use thiserror::Error;
 pub enum SyncerError {
     #[error("mkvs: method not supported")]
     Unsupported,
+    #[error("mkvs: proof contains unrequested node")]
+    UnrequestedNode,
+    #[error("mkvs: request deferred to the asynchronous read syncer")]
+    Deferred,
 }
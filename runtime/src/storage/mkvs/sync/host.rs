@@ -1,39 +1,119 @@
-use std::{any::Any, sync::Arc};
+use std::{any::Any, collections::VecDeque, sync::Arc};
 
 use anyhow::Result;
 use io_context::Context;
 
 use crate::{
-    protocol::{Protocol, ProtocolError},
-    storage::mkvs::sync::*,
-    types::{Body, StorageSyncRequest, StorageSyncResponse},
+    protocol::{PendingResponse, Protocol, ProtocolError, MAX_IN_FLIGHT_REQUESTS},
+    storage::mkvs::{sync::*, tree::check_cancelled},
+    types::{Body, HostStorageEndpoint, ProtocolVersion, StorageSyncRequest, StorageSyncResponse},
 };
 
 /// A proxy read syncer which forwards calls to the runtime host.
 pub struct HostReadSyncer {
     protocol: Arc<Protocol>,
+    endpoint: HostStorageEndpoint,
 }
 
 impl HostReadSyncer {
-    /// Construct a new host proxy instance.
+    /// Construct a new host proxy instance for the runtime storage.
     pub fn new(protocol: Arc<Protocol>) -> HostReadSyncer {
-        HostReadSyncer { protocol: protocol }
+        Self::new_with_endpoint(protocol, HostStorageEndpoint::Runtime)
     }
 
-    fn make_request_with_proof(
+    /// Construct a new host proxy instance for the given storage.
+    pub fn new_with_endpoint(
+        protocol: Arc<Protocol>,
+        endpoint: HostStorageEndpoint,
+    ) -> HostReadSyncer {
+        HostReadSyncer { protocol, endpoint }
+    }
+
+    /// Fetch proofs for several get requests at once.
+    ///
+    /// Up to `MAX_IN_FLIGHT_REQUESTS` requests are in flight at once, so the
+    /// round trips to the host overlap. Responses are returned in request
+    /// order.
+    pub fn sync_get_many(
         &self,
         ctx: Context,
+        requests: Vec<GetRequest>,
+    ) -> Result<Vec<ProofResponse>> {
+        let ctx = ctx.freeze();
+        let mut responses = Vec::with_capacity(requests.len());
+        let mut in_flight = VecDeque::with_capacity(MAX_IN_FLIGHT_REQUESTS);
+        for request in requests {
+            if in_flight.len() == MAX_IN_FLIGHT_REQUESTS {
+                let pending: PendingResponse = in_flight.pop_front().unwrap();
+                responses.push(self.wait_with_proof(&ctx, pending)?);
+            }
+            in_flight.push_back(self.send_request(&ctx, StorageSyncRequest::SyncGet(request))?);
+        }
+        for pending in in_flight {
+            responses.push(self.wait_with_proof(&ctx, pending)?);
+        }
+
+        Ok(responses)
+    }
+
+    fn send_request(
+        &self,
+        ctx: &Arc<Context>,
         request: StorageSyncRequest,
+    ) -> Result<PendingResponse> {
+        check_cancelled(ctx)?;
+        self.protocol.send_request(
+            Context::create_child(ctx),
+            Body::HostStorageSyncRequest {
+                request,
+                endpoint: self.endpoint,
+            },
+        )
+    }
+
+    fn wait_with_proof(
+        &self,
+        ctx: &Arc<Context>,
+        pending: PendingResponse,
     ) -> Result<ProofResponse> {
-        let request = Body::HostStorageSyncRequest { request };
-        match self.protocol.make_request(ctx, request) {
-            Ok(Body::HostStorageSyncResponse {
-                response: StorageSyncResponse::ProofResponse(response),
-            }) => Ok(response),
-            Ok(_) => Err(ProtocolError::InvalidResponse.into()),
-            Err(error) => Err(error),
+        let id = pending.id();
+        let mut assembler = ProofAssembler::new();
+        let mut pending = pending;
+        loop {
+            let chunk = match pending.wait() {
+                Ok(Body::HostStorageSyncResponse {
+                    response: StorageSyncResponse::ProofResponse(response),
+                }) if assembler.chunks() == 0 => return Ok(response),
+                Ok(Body::HostStorageSyncResponse {
+                    response: StorageSyncResponse::PartialProofResponse(chunk),
+                }) => chunk,
+                Ok(_) => return Err(ProtocolError::InvalidResponse.into()),
+                Err(error) => return Err(error),
+            };
+
+            // Large responses are split into chunks, request the rest.
+            if let Some(response) = assembler.add(chunk)? {
+                return Ok(response);
+            }
+            pending = self.send_request(
+                ctx,
+                StorageSyncRequest::SyncNextChunk(NextChunkRequest {
+                    request: id,
+                    chunk: assembler.chunks(),
+                }),
+            )?;
         }
     }
+
+    fn make_request_with_proof(
+        &self,
+        ctx: Context,
+        request: StorageSyncRequest,
+    ) -> Result<ProofResponse> {
+        let ctx = ctx.freeze();
+        let pending = self.send_request(&ctx, request)?;
+        self.wait_with_proof(&ctx, pending)
+    }
 }
 
 impl ReadSync for HostReadSyncer {
@@ -45,6 +125,26 @@ impl ReadSync for HostReadSyncer {
         self.make_request_with_proof(ctx, StorageSyncRequest::SyncGet(request))
     }
 
+    fn sync_get_multi(&mut self, ctx: Context, request: GetMultiRequest) -> Result<ProofResponse> {
+        if self.protocol.get_protocol_version() < ProtocolVersion::V1_17 {
+            // Older hosts do not support batches, overlap the round trips for
+            // all keys instead.
+            let responses = self.sync_get_many(ctx, request.into_get_requests())?;
+            return ProofResponse::merge(responses);
+        }
+
+        let ctx = ctx.freeze();
+        let count = request.keys.len();
+        let pending = self.send_request(&ctx, StorageSyncRequest::SyncGetMulti(request))?;
+        match pending.wait() {
+            Ok(Body::HostStorageSyncResponse {
+                response: StorageSyncResponse::ProofResponses(responses),
+            }) if responses.len() == count => ProofResponse::merge(responses),
+            Ok(_) => Err(ProtocolError::InvalidResponse.into()),
+            Err(error) => Err(error),
+        }
+    }
+
     fn sync_get_prefixes(
         &mut self,
         ctx: Context,
@@ -0,0 +1,341 @@
+use std::{any::Any, collections::HashMap};
+
+use anyhow::{anyhow, Result};
+use io_context::Context;
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{marshal::*, sync::*, tree::*},
+};
+
+use super::verifying::Requested;
+
+/// Position of a node within the tree.
+struct NodePosition {
+    ptr: NodePtrRef,
+    /// Path to the node, excluding its label.
+    path: Key,
+    bit_depth: Depth,
+    /// Length of the path identifying the node, including the bit selecting
+    /// it in its parent.
+    length: Depth,
+}
+
+impl NodePosition {
+    fn root(hash: Hash) -> Self {
+        Self {
+            ptr: NodePointer::hash_ptr(hash),
+            path: Key::new(),
+            bit_depth: 0,
+            length: 0,
+        }
+    }
+
+    fn children(&self, node: &InternalNode) -> (Self, Self) {
+        let path = self
+            .path
+            .merge(self.bit_depth, &node.label, node.label_bit_length);
+        let bit_length = self.bit_depth + node.label_bit_length;
+        let child = |ptr: &NodePtrRef, bit| Self {
+            ptr: ptr.clone(),
+            path: path.append_bit(bit_length, bit),
+            bit_depth: bit_length,
+            length: bit_length + 1,
+        };
+
+        (child(&node.left, false), child(&node.right, true))
+    }
+}
+
+/// State of a proof being built.
+struct ProofState<'a> {
+    rs: &'a MapReadSync,
+    requested: &'a Requested<'a>,
+    /// Number of leaf nodes that may still be included.
+    remaining: usize,
+    proof: ProofBuilder,
+}
+
+impl<'a> ProofState<'a> {
+    fn build(&mut self, pos: NodePosition, parent_covered: bool) -> Result<()> {
+        if pos.ptr.borrow().is_null() {
+            return self.proof.push(None);
+        }
+
+        let covered = self.requested.covers(&pos.path, pos.length, parent_covered);
+        let included = covered || (parent_covered && self.requested.include_siblings());
+        if !included || self.remaining == 0 {
+            return self.proof.push_hash(&pos.ptr);
+        }
+
+        let node = self.rs.load(&pos.ptr)?;
+        self.proof.push_node(&node)?;
+        match node {
+            NodeBox::Internal(ref n) => {
+                let (left, right) = pos.children(n);
+                self.build(left, covered)?;
+                self.build(right, covered)
+            }
+            NodeBox::Leaf(_) => {
+                if covered {
+                    self.remaining = self.remaining.saturating_sub(1);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A read syncer serving requests from an in-memory map of nodes.
+///
+/// Nodes are stored in their full binary encoding, keyed by node hash, e.g.
+/// as returned by `Tree::export_nodes`. This allows testing tree logic that
+/// depends on a remote tree without going through the host protocol.
+#[derive(Default)]
+pub struct MapReadSync {
+    nodes: HashMap<Hash, Vec<u8>>,
+    max_proof_size: usize,
+}
+
+impl MapReadSync {
+    /// Construct a new read syncer serving the given nodes.
+    pub fn new(nodes: HashMap<Hash, Vec<u8>>) -> Self {
+        Self {
+            nodes,
+            max_proof_size: 0,
+        }
+    }
+
+    /// Construct a new read syncer serving all nodes of the given tree.
+    ///
+    /// The tree must not have any uncommitted changes.
+    pub fn from_tree(ctx: Context, tree: &Tree) -> Result<Self> {
+        Ok(Self::new(tree.export_nodes(ctx)?))
+    }
+
+    /// Add all nodes of the given tree.
+    ///
+    /// The tree must not have any uncommitted changes.
+    pub fn add_tree(&mut self, ctx: Context, tree: &Tree) -> Result<()> {
+        self.nodes.extend(tree.export_nodes(ctx)?);
+        Ok(())
+    }
+
+    /// Add a single node in its full binary encoding.
+    pub fn insert_node(&mut self, hash: Hash, data: Vec<u8>) {
+        self.nodes.insert(hash, data);
+    }
+
+    /// Set the maximum estimated size of the proofs served, in bytes.
+    ///
+    /// Requests which would result in a larger proof fail with
+    /// `Error::ProofTooLarge`. If set to 0, the size is not limited.
+    pub fn set_max_proof_size(&mut self, max_proof_size: usize) {
+        self.max_proof_size = max_proof_size;
+    }
+
+    /// Return the nodes served by this read syncer.
+    pub fn nodes(&self) -> &HashMap<Hash, Vec<u8>> {
+        &self.nodes
+    }
+
+    fn load(&self, ptr: &NodePtrRef) -> Result<NodeBox> {
+        let hash = ptr.borrow().hash;
+        let data = self
+            .nodes
+            .get(&hash)
+            .ok_or_else(|| anyhow!("mkvs: node not found ({:?})", hash))?;
+
+        let mut node = NodeBox::default();
+        node.unmarshal_binary(data)?;
+        Ok(node)
+    }
+
+    /// Find the node with the given hash among the nodes included by the request.
+    ///
+    /// Also returns whether the parent of the found node is covered by the request.
+    fn locate(
+        &self,
+        requested: &Requested,
+        pos: NodePosition,
+        parent_covered: bool,
+        hash: Hash,
+    ) -> Result<Option<(NodePosition, bool)>> {
+        if pos.ptr.borrow().is_null() {
+            return Ok(None);
+        }
+        let covered = requested.covers(&pos.path, pos.length, parent_covered);
+        if !covered && !(parent_covered && requested.include_siblings()) {
+            return Ok(None);
+        }
+        if pos.ptr.borrow().hash == hash {
+            return Ok(Some((pos, parent_covered)));
+        }
+        if !covered {
+            return Ok(None);
+        }
+
+        match self.load(&pos.ptr)? {
+            NodeBox::Internal(ref n) => {
+                let (left, right) = pos.children(n);
+                match self.locate(requested, left, true, hash)? {
+                    Some(found) => Ok(Some(found)),
+                    None => self.locate(requested, right, true, hash),
+                }
+            }
+            NodeBox::Leaf(_) => Ok(None),
+        }
+    }
+
+    /// Build a proof containing only the node with the given hash.
+    fn prove_node(&self, hash: Hash) -> Result<ProofResponse> {
+        let node = self.load(&NodePointer::hash_ptr(hash))?;
+        let mut proof = ProofBuilder::new(self.max_proof_size);
+        proof.push_node(&node)?;
+        if let NodeBox::Internal(ref n) = node {
+            proof.push_hash(&n.left)?;
+            proof.push_hash(&n.right)?;
+        }
+
+        Ok(ProofResponse {
+            proof: proof.build(hash),
+        })
+    }
+
+    /// Build a proof for the part of the tree covered by the request.
+    ///
+    /// The proof is rooted at the requested position if it is included by the
+    /// request and at the tree root otherwise. At most `limit` covered leaf
+    /// nodes are included.
+    ///
+    /// As nodes can move within a locally modified tree, a requested position
+    /// which is not included by the request is served on its own.
+    fn prove(&self, tree: &TreeID, requested: Requested, limit: usize) -> Result<ProofResponse> {
+        let start = if tree.position != tree.root.hash {
+            self.locate(
+                &requested,
+                NodePosition::root(tree.root.hash),
+                false,
+                tree.position,
+            )?
+        } else {
+            None
+        };
+        let (untrusted_root, start, parent_covered) = match start {
+            Some((start, parent_covered)) => (tree.position, start, parent_covered),
+            None if tree.position != tree.root.hash && self.nodes.contains_key(&tree.position) => {
+                return self.prove_node(tree.position);
+            }
+            None => (tree.root.hash, NodePosition::root(tree.root.hash), false),
+        };
+
+        let mut state = ProofState {
+            rs: self,
+            requested: &requested,
+            remaining: limit,
+            proof: ProofBuilder::new(self.max_proof_size),
+        };
+        state.build(start, parent_covered)?;
+
+        Ok(ProofResponse {
+            proof: state.proof.build(untrusted_root),
+        })
+    }
+}
+
+impl ReadSync for MapReadSync {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, _ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        let requested = Requested::Get {
+            key: &request.key,
+            include_siblings: request.include_siblings,
+        };
+        self.prove(&request.tree, requested, usize::MAX)
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        _ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        let limit = match request.limit {
+            0 => usize::MAX,
+            limit => limit as usize,
+        };
+        self.prove(&request.tree, Requested::Prefixes(&request.prefixes), limit)
+    }
+
+    fn sync_iterate(&mut self, _ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        // Always include the first key following the requested one.
+        let limit = request.prefetch as usize + 1;
+        self.prove(&request.tree, Requested::Iterate(&request.key), limit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+
+    #[test]
+    fn test_map_read_sync() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..100 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        let (_, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+        let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+        let root = Root {
+            hash,
+            ..Default::default()
+        };
+
+        // Lookups.
+        let remote = Tree::make()
+            .with_root(root)
+            .new(Box::new(VerifyingReadSync::new(Box::new(rs))));
+        for i in 0..100 {
+            let value = remote
+                .get(Context::background(), format!("key {}", i).as_bytes())
+                .expect("get");
+            assert_eq!(Some(format!("value {}", i).into_bytes()), value);
+        }
+        assert_eq!(
+            None,
+            remote.get(Context::background(), b"key 100").expect("get")
+        );
+
+        // Iteration.
+        let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+        let remote = Tree::make()
+            .with_capacity(0, 0)
+            .with_root(root)
+            .new(Box::new(VerifyingReadSync::new(Box::new(rs))));
+        let mut it = remote.iter(Context::background());
+        it.set_prefetch(10);
+        it.rewind();
+        assert_eq!(100, it.count());
+
+        // Proof size budget.
+        let mut rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+        rs.set_max_proof_size(64);
+        let remote = Tree::make().with_root(root).new(Box::new(rs));
+        assert!(remote.get(Context::background(), b"key 0").is_err());
+
+        // Missing nodes.
+        let rs = MapReadSync::default();
+        let remote = Tree::make().with_root(root).new(Box::new(rs));
+        assert!(remote.get(Context::background(), b"key 0").is_err());
+    }
+}
@@ -1,19 +1,27 @@
 //! The read-only tree sync interface.
+mod async_read_sync;
+mod chunk;
 mod errors;
 mod host;
+mod map;
 mod merge;
 mod noop;
 mod proof;
 mod stats;
 mod sync;
+mod verifying;
 
+pub use async_read_sync::*;
+pub use chunk::*;
 pub use errors::*;
 pub use host::*;
+pub use map::*;
 pub use merge::*;
 pub use noop::*;
 pub use proof::*;
 pub use stats::*;
 pub use sync::*;
+pub use verifying::*;
 
 #[cfg(test)]
 mod test;
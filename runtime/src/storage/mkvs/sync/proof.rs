@@ -1,4 +1,7 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    cmp,
+    ops::{Deref, DerefMut},
+};
 
 use anyhow::{anyhow, Result};
 use arbitrary::Arbitrary;
@@ -8,7 +11,7 @@ use serde_bytes;
 
 use crate::{
     common::crypto::hash::Hash,
-    storage::mkvs::{marshal::Marshal, tree::*},
+    storage::mkvs::{marshal::MarshalMode, tree::*},
 };
 
 /// Proof entry type for full nodes.
@@ -63,40 +66,274 @@ pub struct Proof {
     pub entries: Vec<Option<RawProofEntry>>,
 }
 
+impl Proof {
+    /// Merge another proof for the same root into this one, so that the
+    /// merged proof includes all nodes included in either proof.
+    ///
+    /// The proofs are not verified while merging, so the merged proof must
+    /// still be verified as usual.
+    pub fn merge(&self, other: &Proof) -> Result<Proof> {
+        if self.untrusted_root != other.untrusted_root {
+            return Err(Error::RootMismatch {
+                expected: self.untrusted_root,
+                got: other.untrusted_root,
+            }
+            .into());
+        }
+
+        let mut entries = Vec::with_capacity(cmp::max(self.entries.len(), other.entries.len()));
+        let (idx, other_idx) = merge_entries(&self.entries, 0, &other.entries, 0, &mut entries)?;
+        if idx != self.entries.len() || other_idx != other.entries.len() {
+            return Err(anyhow!("merger: unused entries in proof"));
+        }
+
+        Ok(Proof {
+            untrusted_root: self.untrusted_root,
+            entries,
+        })
+    }
+}
+
+/// Check whether an entry is a full internal node, which is followed by the
+/// entries of its children.
+fn is_internal_entry(entry: &Option<RawProofEntry>) -> bool {
+    match entry {
+        Some(entry) => {
+            entry.len() > 1 && entry[0] == PROOF_ENTRY_FULL && entry[1] == NodeKind::Internal as u8
+        }
+        None => false,
+    }
+}
+
+/// Check whether an entry is a full node.
+fn is_full_entry(entry: &Option<RawProofEntry>) -> bool {
+    match entry {
+        Some(entry) => !entry.is_empty() && entry[0] == PROOF_ENTRY_FULL,
+        None => false,
+    }
+}
+
+/// Return the index of the first entry following the subtree at the given index.
+fn skip_subtree(entries: &[Option<RawProofEntry>], idx: usize) -> Result<usize> {
+    match entries.get(idx) {
+        Some(entry) if is_internal_entry(entry) => {
+            let idx = skip_subtree(entries, idx + 1)?;
+            skip_subtree(entries, idx)
+        }
+        Some(_) => Ok(idx + 1),
+        None => Err(anyhow!("merger: malformed proof")),
+    }
+}
+
+/// Merge the subtrees at the given indices, returning the indices of the first
+/// entries following them.
+fn merge_entries(
+    a: &[Option<RawProofEntry>],
+    a_idx: usize,
+    b: &[Option<RawProofEntry>],
+    b_idx: usize,
+    merged: &mut Vec<Option<RawProofEntry>>,
+) -> Result<(usize, usize)> {
+    let (a_entry, b_entry) = match (a.get(a_idx), b.get(b_idx)) {
+        (Some(a_entry), Some(b_entry)) => (a_entry, b_entry),
+        _ => return Err(anyhow!("merger: malformed proof")),
+    };
+
+    match (is_full_entry(a_entry), is_full_entry(b_entry)) {
+        (true, true) => {
+            merged.push(a_entry.clone());
+            if !is_internal_entry(a_entry) {
+                return Ok((a_idx + 1, b_idx + 1));
+            }
+            // Merge left and right children.
+            let (a_idx, b_idx) = merge_entries(a, a_idx + 1, b, b_idx + 1, merged)?;
+            merge_entries(a, a_idx, b, b_idx, merged)
+        }
+        (true, false) => {
+            let a_end = skip_subtree(a, a_idx)?;
+            merged.extend_from_slice(&a[a_idx..a_end]);
+            Ok((a_end, b_idx + 1))
+        }
+        (false, true) => {
+            let b_end = skip_subtree(b, b_idx)?;
+            merged.extend_from_slice(&b[b_idx..b_end]);
+            Ok((a_idx + 1, b_end))
+        }
+        (false, false) => {
+            merged.push(a_entry.clone());
+            Ok((a_idx + 1, b_idx + 1))
+        }
+    }
+}
+
+/// Encode a node as a full proof entry.
+///
+/// Internal nodes use the compact encoding (without child hashes) as their
+/// children are expected to follow as separate entries.
+pub(crate) fn full_proof_entry(node: &NodeBox) -> Result<RawProofEntry> {
+    let mut data = node.marshal_binary_mode(MarshalMode::Compact)?;
+
+    let mut entry = Vec::with_capacity(1 + data.len());
+    entry.push(PROOF_ENTRY_FULL);
+    entry.append(&mut data);
+    Ok(entry.into())
+}
+
+/// Encode a node pointer as a subtree hash proof entry.
+pub(crate) fn hash_proof_entry(ptr: &NodePtrRef) -> Option<RawProofEntry> {
+    let ptr = ptr.borrow();
+    if ptr.is_null() {
+        return None;
+    }
+
+    let mut entry = Vec::with_capacity(1 + Hash::len());
+    entry.push(PROOF_ENTRY_HASH);
+    entry.extend_from_slice(ptr.hash.as_ref());
+    Some(entry.into())
+}
+
+/// Size of the CBOR header of a data item with the given length.
+fn cbor_header_size(len: usize) -> usize {
+    match len {
+        0..=23 => 1,
+        24..=0xff => 2,
+        0x100..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Size of the CBOR encoding of a proof without any entries.
+fn cbor_proof_overhead() -> usize {
+    // Map header, the untrusted root and the entries array key.
+    1 + cbor_header_size(14) + 14 + cbor_header_size(Hash::len()) + Hash::len() + 1 + 7
+}
+
+/// A builder for proofs which keeps track of their size.
+///
+/// If a maximum size is set, adding an entry which would cause the encoded
+/// proof to exceed it fails with `Error::ProofTooLarge`, so that servers can
+/// bound the size of their responses.
+#[derive(Default)]
+pub struct ProofBuilder {
+    entries: Vec<Option<RawProofEntry>>,
+    entries_size: usize,
+    max_size: usize,
+}
+
+impl ProofBuilder {
+    /// Create a new proof builder.
+    ///
+    /// * `max_size` is the maximum estimated size of the encoded proof, in
+    ///   bytes. If set to 0, the size is not limited.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            ..Default::default()
+        }
+    }
+
+    /// Return the estimated size of the CBOR-encoded proof, in bytes.
+    pub fn estimated_size(&self) -> usize {
+        cbor_proof_overhead() + cbor_header_size(self.entries.len()) + self.entries_size
+    }
+
+    /// Append an entry in pre-order traversal.
+    pub fn push(&mut self, entry: Option<RawProofEntry>) -> Result<()> {
+        self.entries_size += match entry {
+            Some(ref entry) => cbor_header_size(entry.len()) + entry.len(),
+            None => 1,
+        };
+        self.entries.push(entry);
+
+        let size = self.estimated_size();
+        if self.max_size > 0 && size > self.max_size {
+            return Err(Error::ProofTooLarge {
+                size,
+                max: self.max_size,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Append a full node entry.
+    pub fn push_node(&mut self, node: &NodeBox) -> Result<()> {
+        self.push(Some(full_proof_entry(node)?))
+    }
+
+    /// Append a subtree hash entry for the given node pointer.
+    pub fn push_hash(&mut self, ptr: &NodePtrRef) -> Result<()> {
+        self.push(hash_proof_entry(ptr))
+    }
+
+    /// Return the proof for the given root.
+    pub fn build(self, untrusted_root: Hash) -> Proof {
+        Proof {
+            untrusted_root,
+            entries: self.entries,
+        }
+    }
+}
+
 /// A proof verifier enables verifying proofs returned by the ReadSyncer API.
 pub struct ProofVerifier;
 
 impl ProofVerifier {
     /// Verify a proof and generate an in-memory subtree representing the
     /// nodes which are included in the proof.
-    pub fn verify_proof(&self, _ctx: Context, root: Hash, proof: &Proof) -> Result<NodePtrRef> {
+    ///
+    /// Nodes are hashed using SHA-512/256, see `verify_proof_with` for trees
+    /// using other digest algorithms.
+    pub fn verify_proof(&self, ctx: Context, root: Hash, proof: &Proof) -> Result<NodePtrRef> {
+        self.verify_proof_with(ctx, root, proof, HashAlgorithm::default().hasher())
+    }
+
+    /// Verify a proof for a tree whose nodes are hashed using the given node
+    /// hasher and generate an in-memory subtree representing the nodes which
+    /// are included in the proof.
+    pub fn verify_proof_with(
+        &self,
+        _ctx: Context,
+        root: Hash,
+        proof: &Proof,
+        hasher: &dyn NodeHasher,
+    ) -> Result<NodePtrRef> {
         // Sanity check that the proof is for the correct root (as otherwise it
         // makes no sense to verify the proof).
         if proof.untrusted_root != root {
-            return Err(anyhow!(
-                "verifier: got proof for unexpected root (expected: {:?} got {:?})",
-                root,
-                proof.untrusted_root,
-            ));
+            return Err(Error::RootMismatch {
+                expected: root,
+                got: proof.untrusted_root,
+            }
+            .into());
         }
         if proof.entries.is_empty() {
             return Err(anyhow!("verifier: empty proof"));
         }
 
-        let (_, root_node) = self._verify_proof(proof, 0)?;
+        let (idx, root_node) = self._verify_proof(proof, 0, hasher)?;
+        if idx != proof.entries.len() {
+            return Err(anyhow!("verifier: unused entries in proof"));
+        }
         let root_hash = root_node.borrow().hash;
         if root_hash != root {
-            return Err(anyhow!(
-                "verifier: bad root (expected: {:?} got {:?})",
-                root,
-                root_hash,
-            ));
+            return Err(Error::RootMismatch {
+                expected: root,
+                got: root_hash,
+            }
+            .into());
         }
 
         Ok(root_node)
     }
 
-    fn _verify_proof(&self, proof: &Proof, idx: usize) -> Result<(usize, NodePtrRef)> {
+    fn _verify_proof(
+        &self,
+        proof: &Proof,
+        idx: usize,
+        hasher: &dyn NodeHasher,
+    ) -> Result<(usize, NodePtrRef)> {
         if idx >= proof.entries.len() {
             return Err(anyhow!("verifier: malformed proof"));
         }
@@ -112,24 +349,27 @@ impl ProofVerifier {
             PROOF_ENTRY_FULL => {
                 // Full node.
                 let mut node = NodeBox::default();
-                node.unmarshal_binary(&entry[1..])?;
+                let size = node.unmarshal_binary_mode(&entry[1..], MarshalMode::Compact)?;
+                if size != entry.len() - 1 {
+                    return Err(anyhow!("verifier: malformed full node entry"));
+                }
 
                 // For internal nodes, also decode children.
                 let mut pos = idx + 1;
                 if let NodeBox::Internal(ref mut nd) = node {
                     // Left.
-                    let result = self._verify_proof(&proof, pos)?;
+                    let result = self._verify_proof(&proof, pos, hasher)?;
                     pos = result.0;
                     nd.left = result.1;
                     // Right.
-                    let result = self._verify_proof(&proof, pos)?;
+                    let result = self._verify_proof(&proof, pos, hasher)?;
                     pos = result.0;
                     nd.right = result.1;
-
-                    // Recompute hash as hashes were not recomputed for compact encoding.
-                    nd.update_hash();
                 }
 
+                // Recompute hash as hashes were not recomputed for compact encoding.
+                node.update_decoded_hash_with(hasher);
+
                 Ok((pos, NodePointer::from_node(node)))
             }
             PROOF_ENTRY_HASH => {
@@ -154,10 +394,119 @@ mod test {
     use base64;
     use io_context::Context;
 
-    use crate::common::cbor;
+    use crate::{
+        common::cbor,
+        storage::mkvs::sync::{MapReadSync, NoopReadSyncer},
+    };
 
     use super::*;
 
+    #[test]
+    fn test_proof_builder() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..100 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        let (_, root) = tree
+            .commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+        let (_, proof) = tree
+            .get_with_proof(Context::background(), b"key 42")
+            .expect("get_with_proof");
+
+        // The estimate is exact for the entries contained in proofs.
+        let mut builder = ProofBuilder::default();
+        assert_eq!(
+            cbor::to_vec(&Proof::default()).len(),
+            builder.estimated_size()
+        );
+        for entry in &proof.entries {
+            builder.push(entry.clone()).expect("push");
+        }
+        let size = builder.estimated_size();
+        assert_eq!(proof, builder.build(root));
+        assert_eq!(cbor::to_vec(&proof).len(), size);
+
+        // Budgets are enforced.
+        let mut builder = ProofBuilder::new(size - 1);
+        let result: Result<()> = proof
+            .entries
+            .iter()
+            .try_for_each(|entry| builder.push(entry.clone()));
+        match result.unwrap_err().downcast_ref::<Error>() {
+            Some(Error::ProofTooLarge { max, .. }) => assert_eq!(size - 1, *max),
+            err => panic!("unexpected error: {:?}", err),
+        }
+
+        let tree = Tree::make()
+            .with_max_proof_size(size)
+            .with_root(Root {
+                hash: root,
+                ..Default::default()
+            })
+            .new(Box::new(
+                MapReadSync::from_tree(Context::background(), &tree).unwrap(),
+            ));
+        tree.get_with_proof(Context::background(), b"key 42")
+            .expect("proof within budget");
+        let keys: Vec<&[u8]> = vec![&b"key 1"[..], &b"key 42"[..]];
+        let result = tree.get_multi_with_proof(Context::background(), &keys);
+        match result.unwrap_err().downcast_ref::<Error>() {
+            Some(Error::ProofTooLarge { max, .. }) => assert_eq!(size, *max),
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_proof_merge() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..100 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        let (_, root) = tree
+            .commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+
+        let (_, proof_1) = tree
+            .get_with_proof(Context::background(), b"key 1")
+            .expect("get_with_proof");
+        let (_, proof_42) = tree
+            .get_with_proof(Context::background(), b"key 42")
+            .expect("get_with_proof");
+        let keys: Vec<&[u8]> = vec![&b"key 1"[..], &b"key 42"[..]];
+        let (_, proof) = tree
+            .get_multi_with_proof(Context::background(), &keys)
+            .expect("get_multi_with_proof");
+
+        // Merging results in the same proof as fetching both keys at once.
+        assert_eq!(proof, proof_1.merge(&proof_42).expect("merge"));
+        assert_eq!(proof, proof_42.merge(&proof_1).expect("merge"));
+        assert_eq!(proof_1, proof_1.merge(&proof_1).expect("merge"));
+        ProofVerifier
+            .verify_proof(Context::background(), root, &proof)
+            .expect("verify_proof");
+
+        // Proofs for different roots cannot be merged.
+        let mut other = proof_42.clone();
+        other.untrusted_root = Hash::digest_bytes(b"i am a bogus hash");
+        assert!(proof_1.merge(&other).is_err());
+
+        // Malformed proofs cannot be merged.
+        let mut truncated = proof_42.clone();
+        truncated.entries.truncate(1);
+        assert!(proof_1.merge(&truncated).is_err());
+    }
+
     #[test]
     fn test_proof() {
         // Test vector generated by Go.
@@ -233,6 +582,15 @@ mod test {
             "verify proof should fail with invalid proof"
         );
 
+        // Extra elements.
+        let mut corrupted = proof.clone();
+        corrupted.entries.push(None);
+        let result = pv.verify_proof(Context::background(), root_hash, &corrupted);
+        assert!(
+            result.is_err(),
+            "verify proof should fail with unused entries"
+        );
+
         // Missing elements.
         let mut corrupted = proof.clone();
         corrupted.entries.truncate(3);
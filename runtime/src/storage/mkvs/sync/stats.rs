@@ -9,6 +9,8 @@ use crate::storage::mkvs::sync::*;
 pub struct StatsCollector {
     /// Count of `sync_get` calls made to the underlying read syncer.
     pub sync_get_count: usize,
+    /// Count of `sync_get_multi` calls made to the underlying read syncer.
+    pub sync_get_multi_count: usize,
     /// Count of `sync_get_prefixes` calls made to the underlying read syncer.
     pub sync_get_prefixes_count: usize,
     /// Count of `sync_iterate` calls made to the underlying read syncer.
@@ -22,6 +24,7 @@ impl StatsCollector {
     pub fn new(rs: Box<dyn ReadSync>) -> StatsCollector {
         StatsCollector {
             sync_get_count: 0,
+            sync_get_multi_count: 0,
             sync_get_prefixes_count: 0,
             sync_iterate_count: 0,
             rs: rs,
@@ -39,6 +42,11 @@ impl ReadSync for StatsCollector {
         self.rs.sync_get(ctx, request)
     }
 
+    fn sync_get_multi(&mut self, ctx: Context, request: GetMultiRequest) -> Result<ProofResponse> {
+        self.sync_get_multi_count += 1;
+        self.rs.sync_get_multi(ctx, request)
+    }
+
     fn sync_get_prefixes(
         &mut self,
         ctx: Context,
@@ -1,9 +1,9 @@
 use std::any::Any;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use io_context::Context;
 use serde::{Deserialize, Serialize};
-use serde_bytes;
+use serde_bytes::{self, ByteBuf};
 
 use crate::{
     common::crypto::hash::Hash,
@@ -20,6 +20,11 @@ pub struct TreeID {
     /// The caller's position in the tree structure to allow
     /// returning partial proofs if possible.
     pub position: Hash,
+    /// The path leading to the caller's position and the bit depth of the
+    /// position, if known. It is only used locally to check partial proofs
+    /// and is never sent to the backend.
+    #[serde(skip)]
+    pub position_path: Option<(Key, Depth)>,
 }
 
 /// Request for the SyncGet operation.
@@ -32,6 +37,28 @@ pub struct GetRequest {
     pub include_siblings: bool,
 }
 
+/// Request for the SyncGetMulti operation.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GetMultiRequest {
+    pub tree: TreeID,
+    pub keys: Vec<ByteBuf>,
+}
+
+impl GetMultiRequest {
+    /// Split the request into a get request for each key.
+    pub fn into_get_requests(self) -> Vec<GetRequest> {
+        let tree = self.tree;
+        self.keys
+            .into_iter()
+            .map(|key| GetRequest {
+                tree: tree.clone(),
+                key: key.into_vec(),
+                include_siblings: false,
+            })
+            .collect()
+    }
+}
+
 /// Request for the SyncGetPrefixes operation.
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct GetPrefixesRequest {
@@ -55,6 +82,72 @@ pub struct ProofResponse {
     pub proof: Proof,
 }
 
+/// A chunk of a proof response that is too large to fit into a single message.
+///
+/// The entries of all chunks, in order, form the entries of the full proof.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartialProofResponse {
+    pub proof: Proof,
+    /// Whether more chunks follow this one.
+    pub more: bool,
+}
+
+/// Request for the next chunk of a partial proof response.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NextChunkRequest {
+    /// Identifier of the runtime host protocol request whose response is
+    /// being continued, as several chunked responses may be pending at once.
+    pub request: u64,
+    /// Index of the requested chunk, starting at 1 for the chunk following
+    /// the initial response.
+    pub chunk: u32,
+}
+
+impl ProofResponse {
+    /// Merge responses for the same root into a single response including
+    /// all nodes included in any of them.
+    pub fn merge(responses: Vec<ProofResponse>) -> Result<ProofResponse> {
+        let mut responses = responses.into_iter();
+        let mut proof = match responses.next() {
+            Some(response) => response.proof,
+            None => return Err(anyhow!("merger: no responses to merge")),
+        };
+        for response in responses {
+            proof = proof.merge(&response.proof)?;
+        }
+        Ok(ProofResponse { proof })
+    }
+
+    /// Split the response into chunks of at most `max_entries` proof entries.
+    pub fn into_chunks(self, max_entries: usize) -> Vec<PartialProofResponse> {
+        let untrusted_root = self.proof.untrusted_root;
+        let mut chunks: Vec<PartialProofResponse> = self
+            .proof
+            .entries
+            .chunks(std::cmp::max(max_entries, 1))
+            .map(|entries| PartialProofResponse {
+                proof: Proof {
+                    untrusted_root,
+                    entries: entries.to_vec(),
+                },
+                more: true,
+            })
+            .collect();
+
+        match chunks.last_mut() {
+            Some(last) => last.more = false,
+            None => chunks.push(PartialProofResponse {
+                proof: Proof {
+                    untrusted_root,
+                    entries: vec![],
+                },
+                more: false,
+            }),
+        }
+        chunks
+    }
+}
+
 /// ReadSync is the interface for synchronizing the in-memory cache
 /// with another (potentially untrusted) MKVS.
 pub trait ReadSync {
@@ -64,6 +157,20 @@ pub trait ReadSync {
     /// Fetch a single key and returns the corresponding proof.
     fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse>;
 
+    /// Fetch multiple keys and return a single proof for all of them.
+    ///
+    /// The default implementation fetches the keys one by one and merges the
+    /// proofs. Read syncers which can batch requests should override it.
+    fn sync_get_multi(&mut self, ctx: Context, request: GetMultiRequest) -> Result<ProofResponse> {
+        let ctx = ctx.freeze();
+        let responses = request
+            .into_get_requests()
+            .into_iter()
+            .map(|request| self.sync_get(Context::create_child(&ctx), request))
+            .collect::<Result<Vec<_>>>()?;
+        ProofResponse::merge(responses)
+    }
+
     /// Fetch all keys under the given prefixes and returns the corresponding proofs.
     fn sync_get_prefixes(
         &mut self,
@@ -1,10 +1,15 @@
+use std::{any::Any, cell::Cell, rc::Rc};
+
+use anyhow::Result;
+use futures::{future, task, Async, Future, Stream};
 use io_context::Context;
 
 use crate::storage::mkvs::{
+    cache::Cache,
     interop::{Driver, ProtocolServer},
     sync::*,
     tree::*,
-    LogEntry,
+    LogEntry, Prefix,
 };
 
 #[test]
@@ -54,3 +59,314 @@ fn test_nil_pointers() {
         .insert(Context::background(), b"insert", b"key")
         .expect("insert");
 }
+
+#[test]
+fn test_async_read_sync() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    let write_log = vec![
+        LogEntry::new(b"foo", b"bar"),
+        LogEntry::new(b"carrot", b"stick"),
+        LogEntry::new(b"ping", b"pong"),
+    ];
+    for entry in write_log.iter() {
+        tree.insert(
+            Context::background(),
+            &entry.key,
+            &entry.value.as_ref().unwrap(),
+        )
+        .expect("insert");
+    }
+    let (_, root) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, root, Default::default(), 0);
+
+    let async_rs = AsyncReadSyncAdapter::new(server.read_sync());
+    let remote = Tree::make()
+        .with_root(Root {
+            hash: root,
+            ..Default::default()
+        })
+        .new(Box::new(BlockingReadSync::new(Box::new(async_rs))));
+
+    for entry in write_log.iter() {
+        let value = remote.get(Context::background(), &entry.key).expect("get");
+        assert_eq!(entry.value, value);
+    }
+    assert_eq!(
+        None,
+        remote.get(Context::background(), b"moo").expect("get")
+    );
+}
+
+/// An asynchronous read syncer whose responses only become available after
+/// the returned futures have been polled once, tracking how many requests
+/// are in flight.
+struct DelayedReadSync {
+    rs: Box<dyn ReadSync>,
+    in_flight: Rc<Cell<usize>>,
+    max_in_flight: Rc<Cell<usize>>,
+}
+
+impl DelayedReadSync {
+    fn delay(&self, rsp: Result<ProofResponse>) -> ProofFuture {
+        let in_flight = self.in_flight.clone();
+        in_flight.set(in_flight.get() + 1);
+        self.max_in_flight
+            .set(self.max_in_flight.get().max(in_flight.get()));
+
+        let mut rsp = Some(rsp);
+        let mut polled = false;
+        Box::new(future::poll_fn(move || {
+            if !polled {
+                polled = true;
+                task::current().notify();
+                return Ok(Async::NotReady);
+            }
+            in_flight.set(in_flight.get() - 1);
+            rsp.take()
+                .expect("polled after completion")
+                .map(Async::Ready)
+        }))
+    }
+}
+
+impl AsyncReadSync for DelayedReadSync {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> ProofFuture {
+        let rsp = self.rs.sync_get(ctx, request);
+        self.delay(rsp)
+    }
+
+    fn sync_get_prefixes(&mut self, ctx: Context, request: GetPrefixesRequest) -> ProofFuture {
+        let rsp = self.rs.sync_get_prefixes(ctx, request);
+        self.delay(rsp)
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> ProofFuture {
+        let rsp = self.rs.sync_iterate(ctx, request);
+        self.delay(rsp)
+    }
+}
+
+#[test]
+fn test_async_tree() {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    let mut items = Vec::new();
+    for i in 0..100 {
+        let key = format!("key {:02}", i).into_bytes();
+        let value = format!("value {:02}", i).into_bytes();
+        tree.insert(Context::background(), &key, &value)
+            .expect("insert");
+        items.push((key, value));
+    }
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let root = Root {
+        hash,
+        ..Default::default()
+    };
+
+    let in_flight = Rc::new(Cell::new(0));
+    let max_in_flight = Rc::new(Cell::new(0));
+    let new_remote = || {
+        Tree::make()
+            .with_root(root)
+            .new_async(Box::new(DelayedReadSync {
+                rs: Box::new(
+                    MapReadSync::from_tree(Context::background(), &tree).expect("from_tree"),
+                ),
+                in_flight: in_flight.clone(),
+                max_in_flight: max_in_flight.clone(),
+            }))
+    };
+
+    // Lookups wait for their fetches without blocking each other.
+    let remote = new_remote();
+    let (first, second) = remote
+        .get_async(Context::background(), b"key 01")
+        .join(remote.get_async(Context::background(), b"key 42"))
+        .wait()
+        .expect("get_async");
+    assert_eq!(Some(b"value 01".to_vec()), first);
+    assert_eq!(Some(b"value 42".to_vec()), second);
+    assert_eq!(2, max_in_flight.get());
+    assert_eq!(0, in_flight.get());
+    assert_eq!(
+        None,
+        remote
+            .get_async(Context::background(), b"moo")
+            .wait()
+            .expect("get_async")
+    );
+
+    // Blocking lookups on the same tree are still supported.
+    assert_eq!(
+        Some(b"value 77".to_vec()),
+        remote.get(Context::background(), b"key 77").expect("get")
+    );
+
+    // Iteration waits for fetches in the same way.
+    let remote = new_remote();
+    let stats = remote.cache_stats();
+    let fetched: Vec<_> = remote
+        .iter_async(Context::background(), b"key 10", 5)
+        .collect()
+        .wait()
+        .expect("iter_async");
+    assert_eq!(items[10..].to_vec(), fetched);
+    assert!(remote.cache_stats().sync_fetch_count > stats.sync_fetch_count);
+    assert_eq!(0, in_flight.get());
+
+    // Trees without an asynchronous read syncer are supported as well.
+    let fetched: Vec<_> = tree
+        .iter_async(Context::background(), b"", 0)
+        .collect()
+        .wait()
+        .expect("iter_async");
+    assert_eq!(items, fetched);
+    assert_eq!(
+        Some(b"value 99".to_vec()),
+        tree.get_async(Context::background(), b"key 99")
+            .wait()
+            .expect("get_async")
+    );
+}
+
+/// A read syncer answering all requests with the full tree.
+struct OversharingReadSyncer {
+    rs: Box<dyn ReadSync>,
+}
+
+impl ReadSync for OversharingReadSyncer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        self.rs.sync_get_prefixes(
+            ctx,
+            GetPrefixesRequest {
+                tree: request.tree,
+                prefixes: vec![Prefix::from(vec![])],
+                limit: 100,
+            },
+        )
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        self.rs.sync_get_prefixes(ctx, request)
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        self.rs.sync_iterate(ctx, request)
+    }
+}
+
+#[test]
+fn test_verifying_read_sync() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    let write_log = vec![
+        LogEntry::new(b"foo", b"bar"),
+        LogEntry::new(b"carrot", b"stick"),
+        LogEntry::new(b"ping", b"pong"),
+        LogEntry::new(b"moo", b"boo"),
+    ];
+    for entry in write_log.iter() {
+        tree.insert(
+            Context::background(),
+            &entry.key,
+            &entry.value.as_ref().unwrap(),
+        )
+        .expect("insert");
+    }
+    let (_, root) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, root, Default::default(), 0);
+    let root = Root {
+        hash: root,
+        ..Default::default()
+    };
+
+    // Proofs from an honest read syncer should be accepted.
+    let remote = Tree::make()
+        .with_root(root)
+        .new(Box::new(VerifyingReadSync::new(server.read_sync())));
+    for entry in write_log.iter() {
+        let value = remote.get(Context::background(), &entry.key).expect("get");
+        assert_eq!(entry.value, value);
+    }
+    let mut it = remote.iter(Context::background());
+    it.rewind();
+    assert_eq!(write_log.len(), it.count());
+
+    // Proofs including nodes that were not requested should be rejected.
+    let mut rs = VerifyingReadSync::new(Box::new(OversharingReadSyncer {
+        rs: server.read_sync(),
+    }));
+    let err = rs
+        .sync_get(
+            Context::background(),
+            GetRequest {
+                tree: TreeID {
+                    root,
+                    position: root.hash,
+                    ..Default::default()
+                },
+                key: b"foo".to_vec(),
+                include_siblings: false,
+            },
+        )
+        .expect_err("sync_get should fail");
+    assert!(matches!(
+        err.downcast_ref::<SyncerError>(),
+        Some(SyncerError::UnrequestedNode)
+    ));
+
+    // The same holds for proofs rooted at the requested position. The left
+    // child of the root holds all keys except "ping".
+    let position = match *tree
+        .cache
+        .borrow()
+        .get_pending_root()
+        .borrow()
+        .node
+        .as_ref()
+        .unwrap()
+        .borrow()
+    {
+        NodeBox::Internal(ref n) => n.left.borrow().hash,
+        _ => panic!("root should be an internal node"),
+    };
+    let mut rs = VerifyingReadSync::new(Box::new(OversharingReadSyncer {
+        rs: Box::new(MapReadSync::from_tree(Context::background(), &tree).expect("from_tree")),
+    }));
+    let err = rs
+        .sync_get(
+            Context::background(),
+            GetRequest {
+                tree: TreeID {
+                    root,
+                    position,
+                    position_path: Some((b"foo".to_vec(), 3)),
+                },
+                key: b"foo".to_vec(),
+                include_siblings: false,
+            },
+        )
+        .expect_err("sync_get should fail");
+    assert!(matches!(
+        err.downcast_ref::<SyncerError>(),
+        Some(SyncerError::UnrequestedNode)
+    ));
+}
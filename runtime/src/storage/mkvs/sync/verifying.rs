@@ -0,0 +1,265 @@
+use std::{any::Any, cmp};
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::storage::mkvs::{sync::*, tree::*, Prefix};
+
+/// The part of the tree that a request asks for.
+pub(super) enum Requested<'a> {
+    /// Nodes on the lookup path of a key.
+    Get {
+        key: &'a Key,
+        include_siblings: bool,
+    },
+    /// Nodes on the path to or below any of the prefixes.
+    Prefixes(&'a [Prefix]),
+    /// Nodes on the path to a key and any nodes following it.
+    Iterate(&'a Key),
+}
+
+impl<'a> Requested<'a> {
+    /// Whether the node at the given position, identified by the first
+    /// `bit_length` bits of `path`, is covered by the request.
+    ///
+    /// `parent_covered` must be set if the parent of the node is covered.
+    pub(super) fn covers(&self, path: &Key, bit_length: Depth, parent_covered: bool) -> bool {
+        match self {
+            Requested::Get { key, .. } => {
+                // Lookups only follow the bit selecting each child and do not
+                // compare node labels, so neither does the lookup path.
+                if bit_length == 0 {
+                    return true;
+                }
+                let bit = bit_length - 1;
+                parent_covered && bit < key.bit_length() && path.get_bit(bit) == key.get_bit(bit)
+            }
+            Requested::Prefixes(prefixes) => prefixes.iter().any(|prefix| {
+                let prefix: &Key = prefix;
+                let prefix_length = prefix.bit_length();
+                path.common_prefix_len(bit_length, prefix, prefix_length)
+                    >= cmp::min(bit_length, prefix_length)
+            }),
+            Requested::Iterate(key) => {
+                let key_length = key.bit_length();
+                let common = path.common_prefix_len(bit_length, key, key_length);
+                // Either on the path to the key or following it in iteration order.
+                common >= cmp::min(bit_length, key_length) || path.get_bit(common)
+            }
+        }
+    }
+
+    pub(super) fn include_siblings(&self) -> bool {
+        match self {
+            Requested::Get {
+                include_siblings, ..
+            } => *include_siblings,
+            _ => false,
+        }
+    }
+}
+
+/// A proxy read syncer which verifies all proofs returned by the underlying
+/// read syncer before passing them on.
+///
+/// A proof is only accepted if it is a valid proof for either the requested
+/// tree root or the requested position, and it is rejected if it contains any
+/// full nodes outside the part of the tree covered by the request. This
+/// prevents an untrusted read syncer from making the cache hold nodes that
+/// were never asked for.
+///
+/// Proofs rooted at the requested position are only accepted if the request
+/// carries the path leading to the position, so that the proof can be checked
+/// from the correct depth.
+pub struct VerifyingReadSync {
+    rs: Box<dyn ReadSync>,
+}
+
+impl VerifyingReadSync {
+    /// Construct a new instance, proxying to the given backing read syncer.
+    pub fn new(rs: Box<dyn ReadSync>) -> Self {
+        Self { rs }
+    }
+
+    fn verify(
+        &self,
+        ctx: Context,
+        tree: &TreeID,
+        requested: Requested,
+        rsp: ProofResponse,
+    ) -> Result<ProofResponse> {
+        let proof = &rsp.proof;
+        let expected_root = if proof.untrusted_root == tree.root.hash {
+            tree.root.hash
+        } else if proof.untrusted_root == tree.position && tree.position_path.is_some() {
+            tree.position
+        } else {
+            return Err(Error::RootMismatch {
+                expected: tree.root.hash,
+                got: proof.untrusted_root,
+            }
+            .into());
+        };
+
+        let subtree = ProofVerifier.verify_proof_with(
+            ctx,
+            expected_root,
+            proof,
+            tree.root.hash_algorithm.hasher(),
+        )?;
+        match tree.position_path {
+            Some((ref path, bit_depth)) if expected_root != tree.root.hash => {
+                check_requested_position(&requested, subtree, path, bit_depth)?
+            }
+            _ => check_requested(&requested, subtree, &Key::new(), 0, 0, false)?,
+        }
+
+        Ok(rsp)
+    }
+}
+
+/// Check that all full nodes in a verified subtree rooted at the requested
+/// position are covered by the request.
+///
+/// The node at the position was requested by its hash, so only its children
+/// are checked, assuming that the path leading to the position is covered.
+fn check_requested_position(
+    requested: &Requested,
+    ptr: NodePtrRef,
+    path: &Key,
+    bit_depth: Depth,
+) -> Result<()> {
+    let node_ref = ptr.borrow().node.clone();
+    let node_ref = match node_ref {
+        Some(node_ref) => node_ref,
+        None => return Ok(()),
+    };
+    let node = node_ref.borrow();
+    let n = match *node {
+        NodeBox::Internal(ref n) => n,
+        NodeBox::Leaf(_) => return Ok(()),
+    };
+    if path.bit_length() < bit_depth {
+        return Err(SyncerError::UnrequestedNode.into());
+    }
+
+    // Apart from the root, the label of an internal node starts with the bit
+    // selecting it in its parent.
+    let (path, _) = path.split(bit_depth, bit_depth);
+    let covered = n.label_bit_length > 0
+        && requested.covers(
+            &path.append_bit(bit_depth, n.label.get_bit(0)),
+            bit_depth + 1,
+            true,
+        );
+
+    let bit_length = bit_depth + n.label_bit_length;
+    let path = path.merge(bit_depth, &n.label, n.label_bit_length);
+    check_requested(
+        requested,
+        n.left.clone(),
+        &path.append_bit(bit_length, false),
+        bit_length,
+        bit_length + 1,
+        covered,
+    )?;
+    check_requested(
+        requested,
+        n.right.clone(),
+        &path.append_bit(bit_length, true),
+        bit_length,
+        bit_length + 1,
+        covered,
+    )
+}
+
+/// Check that all full nodes in a verified subtree are covered by the request.
+///
+/// The position of a node is the path to its parent followed by the bit
+/// selecting it, so nodes are checked independently of their own labels.
+fn check_requested(
+    requested: &Requested,
+    ptr: NodePtrRef,
+    path: &Key,
+    bit_depth: Depth,
+    position_length: Depth,
+    parent_covered: bool,
+) -> Result<()> {
+    let node_ref = ptr.borrow().node.clone();
+    let node_ref = match node_ref {
+        Some(node_ref) => node_ref,
+        // Nil pointers and subtree hashes are always allowed.
+        None => return Ok(()),
+    };
+
+    let covered = requested.covers(path, position_length, parent_covered);
+    if !covered && !(parent_covered && requested.include_siblings()) {
+        return Err(SyncerError::UnrequestedNode.into());
+    }
+
+    let (left, right, path, bit_length) = match *node_ref.borrow() {
+        NodeBox::Internal(ref n) => (
+            n.left.clone(),
+            n.right.clone(),
+            path.merge(bit_depth, &n.label, n.label_bit_length),
+            bit_depth + n.label_bit_length,
+        ),
+        NodeBox::Leaf(_) => return Ok(()),
+    };
+    check_requested(
+        requested,
+        left,
+        &path.append_bit(bit_length, false),
+        bit_length,
+        bit_length + 1,
+        covered,
+    )?;
+    check_requested(
+        requested,
+        right,
+        &path.append_bit(bit_length, true),
+        bit_length,
+        bit_length + 1,
+        covered,
+    )
+}
+
+impl ReadSync for VerifyingReadSync {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        let ctx = ctx.freeze();
+        let rsp = self
+            .rs
+            .sync_get(Context::create_child(&ctx), request.clone())?;
+        let requested = Requested::Get {
+            key: &request.key,
+            include_siblings: request.include_siblings,
+        };
+        self.verify(Context::create_child(&ctx), &request.tree, requested, rsp)
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        let ctx = ctx.freeze();
+        let rsp = self
+            .rs
+            .sync_get_prefixes(Context::create_child(&ctx), request.clone())?;
+        let requested = Requested::Prefixes(&request.prefixes);
+        self.verify(Context::create_child(&ctx), &request.tree, requested, rsp)
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        let ctx = ctx.freeze();
+        let rsp = self
+            .rs
+            .sync_iterate(Context::create_child(&ctx), request.clone())?;
+        let requested = Requested::Iterate(&request.key);
+        self.verify(Context::create_child(&ctx), &request.tree, requested, rsp)
+    }
+}
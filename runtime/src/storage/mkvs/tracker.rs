@@ -0,0 +1,204 @@
+//! MKVS access tracking.
+use std::{collections::BTreeSet, sync::Mutex};
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::{
+    common::{crypto::hash::Hash, roothash::Namespace},
+    storage::mkvs::{Prefix, WriteLog, MKVS},
+    transaction::rwset::{CoarsenedSet, ReadWriteSet},
+};
+
+/// The type of a key access.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A single recorded key access.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Access {
+    /// The type of the access.
+    pub kind: AccessKind,
+    /// The key that was accessed.
+    pub key: Vec<u8>,
+    /// Size of the value that was read or written (zero if the key did not
+    /// exist or was removed).
+    pub value_size: usize,
+}
+
+impl Access {
+    /// Total number of bytes accessed, including the key.
+    pub fn size(&self) -> usize {
+        self.key.len() + self.value_size
+    }
+}
+
+/// An MKVS wrapper which records every key that is read or written.
+///
+/// The recorded accesses can be retrieved after executing a transaction,
+/// e.g. to account for storage gas or to derive the read/write set of the
+/// transaction.
+pub struct AccessTracker<M: MKVS> {
+    inner: M,
+    accesses: Mutex<Vec<Access>>,
+}
+
+impl<M: MKVS> AccessTracker<M> {
+    /// Create a new access tracker around the given MKVS.
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            accesses: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Return a reference to the underlying MKVS.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Return the underlying MKVS.
+    pub fn into_inner(self) -> M {
+        self.inner
+    }
+
+    fn record(&self, kind: AccessKind, key: &[u8], value_size: usize) {
+        self.accesses.lock().unwrap().push(Access {
+            kind,
+            key: key.to_vec(),
+            value_size,
+        });
+    }
+
+    /// Return all accesses recorded so far, in order.
+    pub fn accesses(&self) -> Vec<Access> {
+        self.accesses.lock().unwrap().clone()
+    }
+
+    /// Return all accesses recorded so far, in order, and start recording
+    /// anew.
+    pub fn take_accesses(&self) -> Vec<Access> {
+        self.accesses.lock().unwrap().drain(..).collect()
+    }
+
+    /// Derive the read/write set of all accesses recorded so far.
+    ///
+    /// Keys are coarsened to prefixes of `granularity` bytes, unless it is
+    /// zero in which case the full keys are used.
+    pub fn read_write_set(&self, granularity: u16) -> ReadWriteSet {
+        let coarsen = |key: &[u8]| match granularity as usize {
+            0 => key.to_vec(),
+            size => key[..size.min(key.len())].to_vec(),
+        };
+
+        let mut read_set = BTreeSet::new();
+        let mut write_set = BTreeSet::new();
+        for access in self.accesses.lock().unwrap().iter() {
+            match access.kind {
+                AccessKind::Read => read_set.insert(coarsen(&access.key)),
+                AccessKind::Write => write_set.insert(coarsen(&access.key)),
+            };
+        }
+        let into_set =
+            |set: BTreeSet<Vec<u8>>| -> CoarsenedSet { set.into_iter().map(Into::into).collect() };
+
+        ReadWriteSet {
+            granularity,
+            read_set: into_set(read_set),
+            write_set: into_set(write_set),
+        }
+    }
+}
+
+impl<M: MKVS> MKVS for AccessTracker<M> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.inner.get(ctx, key);
+        self.record(
+            AccessKind::Read,
+            key,
+            value.as_ref().map_or(0, |value| value.len()),
+        );
+        value
+    }
+
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        self.inner.cache_contains_key(ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        self.record(AccessKind::Write, key, value.len());
+        self.inner.insert(ctx, key, value)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        self.record(AccessKind::Write, key, 0);
+        self.inner.remove(ctx, key)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) {
+        self.inner.prefetch_prefixes(ctx, prefixes, limit)
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        self.inner.commit(ctx, namespace, version)
+    }
+
+    fn rollback(&mut self) {
+        self.inner.rollback()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        storage::mkvs::{sync::NoopReadSyncer, Tree},
+        transaction::rwset::CoarsenedKey,
+    };
+
+    #[test]
+    fn test_access_tracker() {
+        let tree = Tree::make().new(Box::new(NoopReadSyncer));
+        let mut tracker = AccessTracker::new(tree);
+
+        tracker.insert(Context::background(), b"foo/1", b"value");
+        tracker.get(Context::background(), b"foo/1");
+        tracker.get(Context::background(), b"bar/1");
+        tracker.remove(Context::background(), b"foo/2");
+
+        let accesses = tracker.accesses();
+        assert_eq!(
+            vec![
+                (AccessKind::Write, b"foo/1".to_vec(), 10),
+                (AccessKind::Read, b"foo/1".to_vec(), 10),
+                (AccessKind::Read, b"bar/1".to_vec(), 5),
+                (AccessKind::Write, b"foo/2".to_vec(), 5),
+            ],
+            accesses
+                .iter()
+                .map(|access| (access.kind, access.key.clone(), access.size()))
+                .collect::<Vec<_>>()
+        );
+
+        let rw_set = tracker.read_write_set(3);
+        assert_eq!(
+            vec![
+                CoarsenedKey::from(b"bar".to_vec()),
+                CoarsenedKey::from(b"foo".to_vec())
+            ],
+            rw_set.read_set
+        );
+        assert_eq!(vec![CoarsenedKey::from(b"foo".to_vec())], rw_set.write_set);
+
+        assert_eq!(4, tracker.take_accesses().len());
+        assert!(tracker.accesses().is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{cell::Ref, sync::Arc};
 
 use anyhow::Result;
 use io_context::Context;
@@ -8,38 +8,115 @@ use crate::{
     storage::mkvs::{cache::*, tree::*, LogEntry, WriteLog},
 };
 
+/// Minimum number of dirty leaf nodes for leaf hashing to be parallelized.
+const PARALLEL_COMMIT_THRESHOLD: usize = 1024;
+/// Number of threads used for parallel leaf hashing.
+const PARALLEL_COMMIT_THREADS: usize = 4;
+
+/// A pending modification of a single key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingChange {
+    /// The modified key.
+    pub key: Key,
+    /// The value of the key before any of the pending modifications.
+    pub old_value: Option<Value>,
+    /// The value of the key after the pending modifications.
+    pub new_value: Option<Value>,
+}
+
 impl Tree {
+    /// Return the modifications that will be written on the next commit,
+    /// sorted by key.
+    ///
+    /// The returned changes correspond to the entries of the write log
+    /// returned by `commit`.
+    pub fn pending_changes(&self) -> Vec<PendingChange> {
+        self.pending_write_log
+            .values()
+            .filter(|entry| entry.value.is_some() || entry.existed)
+            .map(|entry| PendingChange {
+                key: entry.key.clone(),
+                old_value: entry.previous.clone(),
+                new_value: entry.value.clone(),
+            })
+            .collect()
+    }
+
     /// Commit tree updates to the underlying database and return
     /// the write log and new merkle root.
+    ///
+    /// This is the same as `commit_with_log`.
     pub fn commit(
         &mut self,
         ctx: Context,
         namespace: Namespace,
         version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        self.commit_with_log(ctx, namespace, version)
+    }
+
+    /// Commit tree updates to the underlying database and return the write
+    /// log and new merkle root.
+    ///
+    /// The write log is sorted by key and is assembled while committing, from
+    /// the dirty leaf nodes visited by the same traversal that computes the
+    /// new root hash. Only removed keys, which have no leaf nodes left, are
+    /// taken from the pending modifications. Keys that were inserted and
+    /// removed again without existing before, set to the value they already
+    /// had, or restored by rolling back to a snapshot are omitted as they do
+    /// not change the tree.
+    pub fn commit_with_log(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
     ) -> Result<(WriteLog, Hash)> {
         let ctx = ctx.freeze();
         let mut update_list: UpdateList<LRUCache> = UpdateList::new();
         let pending_root = self.cache.borrow().get_pending_root();
-        let new_hash = _commit(&ctx, pending_root.clone(), &mut update_list, Some(version))?;
+        let hasher = self.hash_algorithm.hasher();
+        let leaves_hashed = hash_dirty_leaves(&pending_root, Some(version), hasher);
+        let mut log: WriteLog = Vec::new();
+        let new_hash = _commit_inner(
+            &ctx,
+            pending_root.clone(),
+            &mut update_list,
+            Some(version),
+            hasher,
+            leaves_hashed,
+            Some(&mut log),
+        )?;
 
         update_list.commit(&mut self.cache.borrow_mut());
+        self.repin_prefixes(&ctx)?;
 
-        let mut log: WriteLog = Vec::new();
-        for (_, entry) in self.pending_write_log.iter() {
-            // Skip all entries that do not exist after all the updates and
-            // did not exist before.
-            if entry.value.is_none() && !entry.existed {
-                continue;
-            }
-            log.push(LogEntry {
+        // Rolling back to a snapshot re-creates leaf nodes for keys that end
+        // up unchanged, so only keep dirty leaves with pending modifications.
+        // The traversal visits leaf nodes in key order, so only the removed
+        // keys need to be merged in.
+        let pending_write_log = &self.pending_write_log;
+        log.retain(|entry| pending_write_log.contains_key(&entry.key));
+        let removed: Vec<LogEntry> = self
+            .pending_write_log
+            .values()
+            .filter(|entry| entry.value.is_none() && entry.existed)
+            .map(|entry| LogEntry {
                 key: entry.key.clone(),
-                value: entry.value.clone(),
-            });
+                value: None,
+            })
+            .collect();
+        if !removed.is_empty() {
+            log.extend(removed);
+            log.sort_by(|a, b| a.key.cmp(&b.key));
         }
+
         self.pending_write_log.clear();
+        self.invalidate_snapshots();
         self.cache.borrow_mut().set_sync_root(Root {
             namespace,
             version,
+            root_type: self.root_type,
+            hash_algorithm: self.hash_algorithm,
             hash: new_hash,
         });
 
@@ -47,15 +124,136 @@ impl Tree {
     }
 }
 
+/// Collect all dirty leaf nodes in the given subtree.
+fn collect_dirty_leaves(ptr: &NodePtrRef, leaves: &mut Vec<NodeRef>) {
+    if ptr.borrow().clean {
+        return;
+    }
+
+    let node_ref = match ptr.borrow().node {
+        Some(ref node_ref) => node_ref.clone(),
+        None => return,
+    };
+    if node_ref.borrow().is_clean() {
+        return;
+    }
+
+    let children = match *node_ref.borrow() {
+        NodeBox::Internal(ref n) => Some((n.leaf_node.clone(), n.left.clone(), n.right.clone())),
+        NodeBox::Leaf(_) => None,
+    };
+    match children {
+        Some((leaf_node, left, right)) => {
+            collect_dirty_leaves(&leaf_node, leaves);
+            collect_dirty_leaves(&left, leaves);
+            collect_dirty_leaves(&right, leaves);
+        }
+        None => leaves.push(node_ref),
+    }
+}
+
+/// Update the version and hash of all dirty leaf nodes in the given subtree,
+/// spreading the hashing over multiple threads.
+///
+/// Returns false without doing anything if there are too few dirty leaf nodes
+/// for parallel hashing to be worthwhile or if running inside an enclave,
+/// where the number of available threads is limited.
+fn hash_dirty_leaves(ptr: &NodePtrRef, version: Option<u64>, hasher: &dyn NodeHasher) -> bool {
+    if cfg!(target_env = "sgx") {
+        return false;
+    }
+
+    let mut leaves = Vec::new();
+    collect_dirty_leaves(ptr, &mut leaves);
+    if leaves.len() < PARALLEL_COMMIT_THRESHOLD {
+        return false;
+    }
+
+    if let Some(version) = version {
+        for node_ref in &leaves {
+            noderef_as_mut!(node_ref, Leaf).version = version;
+        }
+    }
+
+    // Nodes are not thread-safe, so only borrowed views of the leaf contents
+    // are shared with the worker threads.
+    let guards: Vec<Ref<NodeBox>> = leaves.iter().map(|node_ref| node_ref.borrow()).collect();
+    let views: Vec<LeafNodeRef> = guards
+        .iter()
+        .map(|guard| match **guard {
+            NodeBox::Leaf(ref n) => LeafNodeRef {
+                version: n.version,
+                key: &n.key,
+                value: &n.value,
+            },
+            _ => unreachable!("collected node is leaf node"),
+        })
+        .collect();
+
+    let chunk_size = (views.len() + PARALLEL_COMMIT_THREADS - 1) / PARALLEL_COMMIT_THREADS;
+    let hashes: Vec<Hash> = crossbeam::scope(|scope| {
+        let handles: Vec<_> = views
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move |_| {
+                    chunk
+                        .iter()
+                        .map(|view| view.get_hash_with(hasher))
+                        .collect::<Vec<Hash>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("leaf hashing should not panic"))
+            .collect()
+    })
+    .expect("leaf hashing should not panic");
+
+    drop(views);
+    drop(guards);
+    for (node_ref, hash) in leaves.iter().zip(hashes) {
+        noderef_as_mut!(node_ref, Leaf).hash = hash;
+    }
+
+    true
+}
+
 pub fn _commit<C: Cache>(
     ctx: &Arc<Context>,
     ptr: NodePtrRef,
     update_list: &mut UpdateList<C>,
     version: Option<u64>,
+) -> Result<Hash> {
+    _commit_inner(
+        ctx,
+        ptr,
+        update_list,
+        version,
+        &Sha512_256Hasher,
+        false,
+        None,
+    )
+}
+
+/// Commit the given subtree. If `leaves_hashed` is set, dirty leaf nodes are
+/// assumed to already have their version and hash updated. If a write log is
+/// given, the keys and values of dirty leaf nodes are appended to it in key
+/// order.
+fn _commit_inner<C: Cache>(
+    ctx: &Arc<Context>,
+    ptr: NodePtrRef,
+    update_list: &mut UpdateList<C>,
+    version: Option<u64>,
+    hasher: &dyn NodeHasher,
+    leaves_hashed: bool,
+    mut log: Option<&mut WriteLog>,
 ) -> Result<Hash> {
     if ptr.borrow().clean {
         return Ok(ptr.borrow().hash);
     }
+    check_cancelled(ctx)?;
 
     match classify_noderef!(? ptr.borrow().node) {
         NodeKind::None => {
@@ -70,20 +268,53 @@ pub fn _commit<C: Cache>(
                 let int_left = noderef_as!(some_node_ref, Internal).left.clone();
                 let int_right = noderef_as!(some_node_ref, Internal).right.clone();
 
-                _commit(ctx, int_leaf_node.clone(), update_list, version)?;
-                _commit(ctx, int_left.clone(), update_list, version)?;
-                _commit(ctx, int_right.clone(), update_list, version)?;
+                _commit_inner(
+                    ctx,
+                    int_leaf_node.clone(),
+                    update_list,
+                    version,
+                    hasher,
+                    leaves_hashed,
+                    log.as_deref_mut(),
+                )?;
+                _commit_inner(
+                    ctx,
+                    int_left.clone(),
+                    update_list,
+                    version,
+                    hasher,
+                    leaves_hashed,
+                    log.as_deref_mut(),
+                )?;
+                _commit_inner(
+                    ctx,
+                    int_right.clone(),
+                    update_list,
+                    version,
+                    hasher,
+                    leaves_hashed,
+                    log.as_deref_mut(),
+                )?;
 
                 if let Some(version) = version {
                     noderef_as_mut!(some_node_ref, Internal).version = version;
                 }
-                some_node_ref.borrow_mut().update_hash();
+                some_node_ref.borrow_mut().update_hash_with(hasher);
                 ptr.borrow_mut().hash = some_node_ref.borrow().get_hash();
 
                 let closure_node_ref = some_node_ref.clone();
                 update_list.push(Box::new(move |_| {
                     noderef_as_mut!(closure_node_ref, Internal).clean = true
                 }));
+                // Make clean children eligible for eviction again, they may have
+                // been kept resident while the internal node was dirty.
+                update_list.push(Box::new(move |cache| {
+                    for child in &[&int_leaf_node, &int_left, &int_right] {
+                        if child.borrow().get_cache_extra().is_none() {
+                            cache.commit_node((*child).clone());
+                        }
+                    }
+                }));
             }
         }
         NodeKind::Leaf => {
@@ -91,11 +322,19 @@ pub fn _commit<C: Cache>(
             if node_ref.borrow().is_clean() {
                 ptr.borrow_mut().hash = node_ref.borrow().get_hash();
             } else {
-                if let Some(version) = version {
-                    noderef_as_mut!(node_ref, Leaf).version = version;
+                if !leaves_hashed {
+                    if let Some(version) = version {
+                        noderef_as_mut!(node_ref, Leaf).version = version;
+                    }
+                    node_ref.borrow_mut().update_hash_with(hasher);
                 }
-                node_ref.borrow_mut().update_hash();
                 ptr.borrow_mut().hash = node_ref.borrow().get_hash();
+                if let Some(log) = log {
+                    log.push(LogEntry {
+                        key: noderef_as!(node_ref, Leaf).key.clone(),
+                        value: Some(noderef_as!(node_ref, Leaf).value.clone()),
+                    });
+                }
 
                 let closure_node_ref = node_ref.clone();
                 update_list.push(Box::new(move |_| {
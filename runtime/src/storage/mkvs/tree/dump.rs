@@ -0,0 +1,128 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use io_context::Context;
+use rustc_hex::ToHex;
+use serde_json::{json, Value};
+
+use crate::storage::mkvs::{cache::*, tree::*};
+
+/// Output format of a tree dump.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// A Graphviz DOT graph.
+    Dot,
+    /// A JSON document with nested nodes.
+    Json,
+}
+
+impl Tree {
+    /// Dump the structure of the tree for debugging.
+    ///
+    /// All nodes are listed with their hashes, versions and labels, and leaf
+    /// nodes also with their keys. Only the local cache is walked, so nodes
+    /// which are not cached are listed by hash and are never fetched. The
+    /// output only depends on the cached nodes, which makes it possible to
+    /// compare the dumps of two trees.
+    pub fn dump(&self, _ctx: Context, format: Format) -> Result<String> {
+        let pending_root = self.cache.borrow().get_pending_root();
+        match format {
+            Format::Dot => {
+                let mut out = String::from("digraph mkvs {\n");
+                dump_dot(&mut out, &pending_root, &mut 0)?;
+                out.push_str("}\n");
+                Ok(out)
+            }
+            Format::Json => Ok(serde_json::to_string_pretty(&dump_json(&pending_root))?),
+        }
+    }
+}
+
+/// Append the DOT representation of the given subtree, returning the
+/// identifier of its root.
+fn dump_dot(out: &mut String, ptr: &NodePtrRef, next_id: &mut usize) -> Result<String> {
+    let id = format!("n{}", next_id);
+    *next_id += 1;
+
+    let ptr = ptr.borrow();
+    let node_ref = match ptr.node {
+        Some(ref node_ref) => node_ref.clone(),
+        None if ptr.is_null() => {
+            writeln!(out, "  {} [shape=point];", id)?;
+            return Ok(id);
+        }
+        None => {
+            writeln!(out, "  {} [label=\"{}\", style=dashed];", id, ptr.hash)?;
+            return Ok(id);
+        }
+    };
+
+    let node = node_ref.borrow();
+    match *node {
+        NodeBox::Internal(ref n) => {
+            writeln!(
+                out,
+                "  {} [label=\"{}{}\\nversion {}\\nlabel {}/{}\"];",
+                id,
+                n.hash,
+                if n.clean { "" } else { " (dirty)" },
+                n.version,
+                n.label.to_hex::<String>(),
+                n.label_bit_length,
+            )?;
+            if !n.leaf_node.borrow().is_null() || n.leaf_node.borrow().node.is_some() {
+                let leaf_id = dump_dot(out, &n.leaf_node, next_id)?;
+                writeln!(out, "  {} -> {} [style=dotted];", id, leaf_id)?;
+            }
+            let left_id = dump_dot(out, &n.left, next_id)?;
+            writeln!(out, "  {} -> {} [label=\"0\"];", id, left_id)?;
+            let right_id = dump_dot(out, &n.right, next_id)?;
+            writeln!(out, "  {} -> {} [label=\"1\"];", id, right_id)?;
+        }
+        NodeBox::Leaf(ref n) => {
+            writeln!(
+                out,
+                "  {} [shape=box, label=\"{}{}\\nversion {}\\nkey {}\"];",
+                id,
+                n.hash,
+                if n.clean { "" } else { " (dirty)" },
+                n.version,
+                n.key.to_hex::<String>(),
+            )?;
+        }
+    }
+    Ok(id)
+}
+
+/// Build the JSON representation of the given subtree.
+fn dump_json(ptr: &NodePtrRef) -> Value {
+    let ptr = ptr.borrow();
+    let node_ref = match ptr.node {
+        Some(ref node_ref) => node_ref.clone(),
+        None if ptr.is_null() => return Value::Null,
+        None => return json!({ "hash": format!("{:x}", ptr.hash) }),
+    };
+
+    let node = node_ref.borrow();
+    match *node {
+        NodeBox::Internal(ref n) => json!({
+            "kind": "internal",
+            "hash": format!("{:x}", n.hash),
+            "clean": n.clean,
+            "version": n.version,
+            "label": n.label.to_hex::<String>(),
+            "label_bit_length": n.label_bit_length,
+            "leaf_node": dump_json(&n.leaf_node),
+            "left": dump_json(&n.left),
+            "right": dump_json(&n.right),
+        }),
+        NodeBox::Leaf(ref n) => json!({
+            "kind": "leaf",
+            "hash": format!("{:x}", n.hash),
+            "clean": n.clean,
+            "version": n.version,
+            "key": n.key.to_hex::<String>(),
+            "value_size": n.value.len(),
+        }),
+    }
+}
@@ -1,9 +1,56 @@
+use anyhow::Result;
+use io_context::Context;
 use thiserror::Error;
 
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::tree::{HashAlgorithm, RootType},
+};
+
+/// Errors returned by tree operations.
+///
+/// Tree operations return `anyhow::Result`, so callers can match on these by
+/// downcasting the returned error.
 #[derive(Error, Debug)]
-pub enum TreeError {
+pub enum Error {
     #[error("mkvs: malformed node")]
     MalformedNode,
     #[error("mkvs: malformed key")]
     MalformedKey,
+    #[error("mkvs: tree has uncommitted changes")]
+    UncommittedChanges,
+    #[error("mkvs: invalid snapshot")]
+    InvalidSnapshot,
+    #[error("mkvs: key too large ({size} > {max} bytes)")]
+    KeyTooLarge { size: usize, max: usize },
+    #[error("mkvs: value too large ({size} > {max} bytes)")]
+    ValueTooLarge { size: usize, max: usize },
+    #[error("mkvs: proof too large ({size} > {max} bytes)")]
+    ProofTooLarge { size: usize, max: usize },
+    #[error("mkvs: sync failed: {source}")]
+    SyncFailed { source: anyhow::Error },
+    #[error("mkvs: got proof for unexpected root (expected: {expected:?} got: {got:?})")]
+    RootMismatch { expected: Hash, got: Hash },
+    #[error("mkvs: operation cancelled")]
+    Cancelled,
+    #[error("mkvs: root type mismatch (expected: {expected:?} got: {got:?})")]
+    RootTypeMismatch { expected: RootType, got: RootType },
+    #[error("mkvs: hash algorithm mismatch (expected: {expected:?} got: {got:?})")]
+    HashAlgorithmMismatch {
+        expected: HashAlgorithm,
+        got: HashAlgorithm,
+    },
+    #[error("mkvs: malformed subtree export")]
+    MalformedExport,
+    #[error("mkvs: unsupported subtree export version {0}")]
+    UnsupportedExportVersion(u8),
+}
+
+/// Return `Error::Cancelled` if the given context is done, i.e. if it has
+/// been cancelled or its deadline has passed.
+pub(crate) fn check_cancelled(ctx: &Context) -> Result<()> {
+    if ctx.done().is_some() {
+        return Err(Error::Cancelled.into());
+    }
+    Ok(())
 }
@@ -0,0 +1,212 @@
+use std::{cmp, collections::HashMap, mem::size_of, sync::Arc};
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{cache::*, marshal::*, tree::*},
+};
+
+use super::iterator::FetcherSyncIterate;
+
+/// Version of the subtree export format.
+pub const SUBTREE_EXPORT_VERSION: u8 = 1;
+
+impl Tree {
+    /// Return all nodes of the tree in their full binary encoding, keyed by
+    /// node hash.
+    ///
+    /// Leaf nodes belonging to internal nodes are embedded in the encoding of
+    /// the internal node and are not returned separately. Any parts of the
+    /// tree that are not in the local cache will be fetched from the read
+    /// syncer. The tree must not have any uncommitted changes.
+    pub fn export_nodes(&self, ctx: Context) -> Result<HashMap<Hash, Vec<u8>>> {
+        let ctx = ctx.freeze();
+        let pending_root = self.cache.borrow().get_pending_root();
+        if !self.pending_write_log.is_empty() || !pending_root.borrow().clean {
+            return Err(Error::UncommittedChanges.into());
+        }
+
+        let mut nodes = HashMap::new();
+        self._export_nodes(&ctx, &mut nodes, pending_root, Key::new(), 0)?;
+
+        Ok(nodes)
+    }
+
+    fn _export_nodes(
+        &self,
+        ctx: &Arc<Context>,
+        nodes: &mut HashMap<Hash, Vec<u8>>,
+        ptr: NodePtrRef,
+        path: Key,
+        bit_depth: Depth,
+    ) -> Result<()> {
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr.clone(),
+            Some(FetcherSyncIterate::new(&path, &path, bit_depth, 0)),
+        )?;
+        let node_ref = match node_ref {
+            Some(node_ref) => node_ref,
+            None => return Ok(()),
+        };
+        nodes.insert(ptr.borrow().hash, node_ref.borrow().marshal_binary()?);
+
+        let (left, right, path, bit_length) = match *node_ref.borrow() {
+            NodeBox::Internal(ref n) => (
+                n.left.clone(),
+                n.right.clone(),
+                path.merge(bit_depth, &n.label, n.label_bit_length),
+                bit_depth + n.label_bit_length,
+            ),
+            NodeBox::Leaf(_) => return Ok(()),
+        };
+        self._export_nodes(
+            ctx,
+            nodes,
+            left,
+            path.append_bit(bit_length, false),
+            bit_length,
+        )?;
+        self._export_nodes(
+            ctx,
+            nodes,
+            right,
+            path.append_bit(bit_length, true),
+            bit_length,
+        )
+    }
+
+    /// Export all leaf nodes with keys starting with the given prefix.
+    ///
+    /// The export starts with a format version byte followed by the leaf
+    /// nodes in key order, each in its binary encoding prefixed by its
+    /// length. Only leaf nodes are exported as the internal nodes depend on
+    /// the rest of the tree. Any parts of the tree that are not in the local
+    /// cache will be fetched from the read syncer. The tree must not have any
+    /// uncommitted changes.
+    pub fn export_subtree(&self, ctx: Context, prefix: &[u8]) -> Result<Vec<u8>> {
+        let ctx = ctx.freeze();
+        let pending_root = self.cache.borrow().get_pending_root();
+        if !self.pending_write_log.is_empty() || !pending_root.borrow().clean {
+            return Err(Error::UncommittedChanges.into());
+        }
+
+        let mut data = vec![SUBTREE_EXPORT_VERSION];
+        let prefix = prefix.to_vec();
+        self._export_subtree(&ctx, &mut data, pending_root, Key::new(), 0, &prefix)?;
+
+        Ok(data)
+    }
+
+    fn _export_subtree(
+        &self,
+        ctx: &Arc<Context>,
+        data: &mut Vec<u8>,
+        ptr: NodePtrRef,
+        path: Key,
+        bit_depth: Depth,
+        prefix: &Key,
+    ) -> Result<()> {
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr,
+            Some(FetcherSyncIterate::new(&path, &path, bit_depth, 0)),
+        )?;
+        let node_ref = match node_ref {
+            Some(node_ref) => node_ref,
+            None => return Ok(()),
+        };
+
+        let (leaf_node, left, right, path, bit_length) = match *node_ref.borrow() {
+            NodeBox::Internal(ref n) => (
+                n.leaf_node.clone(),
+                n.left.clone(),
+                n.right.clone(),
+                path.merge(bit_depth, &n.label, n.label_bit_length),
+                bit_depth + n.label_bit_length,
+            ),
+            NodeBox::Leaf(ref n) => return export_leaf(data, n, prefix),
+        };
+
+        let leaf_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            leaf_node,
+            Some(FetcherSyncIterate::new(&path, &path, bit_length, 0)),
+        )?;
+        if let Some(leaf_ref) = leaf_ref {
+            export_leaf(data, noderef_as!(leaf_ref, Leaf), prefix)?;
+        }
+
+        // Only descend into subtrees which may contain keys with the prefix.
+        let prefix_length = prefix.bit_length();
+        let covers = |path: &Key| {
+            path.common_prefix_len(bit_length + 1, prefix, prefix_length)
+                >= cmp::min(bit_length + 1, prefix_length)
+        };
+        let left_path = path.append_bit(bit_length, false);
+        if covers(&left_path) {
+            self._export_subtree(ctx, data, left, left_path, bit_length, prefix)?;
+        }
+        let right_path = path.append_bit(bit_length, true);
+        if covers(&right_path) {
+            self._export_subtree(ctx, data, right, right_path, bit_length, prefix)?;
+        }
+
+        Ok(())
+    }
+
+    /// Insert all leaf nodes contained in a subtree export produced by
+    /// `export_subtree`.
+    ///
+    /// The export is decoded in full before any keys are inserted, so
+    /// malformed exports leave the tree unchanged. The changes are not
+    /// committed.
+    pub fn import_subtree(&mut self, ctx: Context, data: &[u8]) -> Result<()> {
+        let ctx = ctx.freeze();
+        match data.first() {
+            Some(&SUBTREE_EXPORT_VERSION) => {}
+            Some(&version) => return Err(Error::UnsupportedExportVersion(version).into()),
+            None => return Err(Error::MalformedExport.into()),
+        }
+
+        let mut leaves = Vec::new();
+        let mut pos = 1;
+        while pos < data.len() {
+            if pos + size_of::<u32>() > data.len() {
+                return Err(Error::MalformedExport.into());
+            }
+            let mut length = 0u32;
+            pos += length.unmarshal_binary(&data[pos..])?;
+            let end = pos + length as usize;
+            if end > data.len() {
+                return Err(Error::MalformedExport.into());
+            }
+
+            let (leaf, size) = LeafNode::unmarshal_borrowed(&data[pos..end])?;
+            if size != length as usize {
+                return Err(Error::MalformedExport.into());
+            }
+            leaves.push(leaf);
+            pos = end;
+        }
+
+        for leaf in leaves {
+            self.insert(Context::create_child(&ctx), leaf.key, leaf.value)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn export_leaf(data: &mut Vec<u8>, leaf: &LeafNode, prefix: &Key) -> Result<()> {
+    if !leaf.key.starts_with(prefix) {
+        return Ok(());
+    }
+
+    let mut encoded = leaf.marshal_binary()?;
+    data.append(&mut (encoded.len() as u32).marshal_binary()?);
+    data.append(&mut encoded);
+    Ok(())
+}
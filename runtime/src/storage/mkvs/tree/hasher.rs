@@ -0,0 +1,115 @@
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use tiny_keccak::{Hasher, Sha3};
+
+use crate::common::crypto::hash::Hash;
+
+/// Digest algorithm used for hashing tree nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum HashAlgorithm {
+    /// SHA-512/256, used by all trees stored by the Oasis storage backends.
+    Sha512_256 = 0,
+    /// SHA3-256 (FIPS 202).
+    Sha3_256 = 1,
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> Self {
+        HashAlgorithm::Sha512_256
+    }
+}
+
+impl HashAlgorithm {
+    /// Whether this is the default algorithm (SHA-512/256).
+    pub fn is_default(&self) -> bool {
+        *self == HashAlgorithm::Sha512_256
+    }
+
+    /// Return the node hasher implementing this algorithm.
+    pub fn hasher(&self) -> &'static dyn NodeHasher {
+        match self {
+            HashAlgorithm::Sha512_256 => &Sha512_256Hasher,
+            HashAlgorithm::Sha3_256 => &Sha3_256Hasher,
+        }
+    }
+}
+
+/// Hash function used to compute node hashes.
+pub trait NodeHasher: Send + Sync {
+    /// The digest algorithm implemented by this hasher.
+    fn algorithm(&self) -> HashAlgorithm;
+
+    /// Compute a digest of the concatenation of the passed slices of bytes.
+    ///
+    /// Note that nil nodes are always represented by `Hash::empty_hash()`,
+    /// independent of the hasher in use.
+    fn digest_bytes_list(&self, data: &[&[u8]]) -> Hash;
+}
+
+/// The default node hasher, using SHA-512/256.
+pub struct Sha512_256Hasher;
+
+impl NodeHasher for Sha512_256Hasher {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Sha512_256
+    }
+
+    fn digest_bytes_list(&self, data: &[&[u8]]) -> Hash {
+        Hash::digest_bytes_list(data)
+    }
+}
+
+/// A node hasher using SHA3-256.
+pub struct Sha3_256Hasher;
+
+impl NodeHasher for Sha3_256Hasher {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Sha3_256
+    }
+
+    fn digest_bytes_list(&self, data: &[&[u8]]) -> Hash {
+        let mut sha3 = Sha3::v256();
+        for datum in data {
+            sha3.update(datum);
+        }
+
+        let mut result = [0u8; 32];
+        sha3.finalize(&mut result);
+
+        Hash(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_node_hashers() {
+        let hasher = HashAlgorithm::default().hasher();
+        assert_eq!(HashAlgorithm::Sha512_256, hasher.algorithm());
+        assert_eq!(Hash::empty_hash(), hasher.digest_bytes_list(&[]));
+        assert_eq!(
+            Hash::digest_bytes(b"foobar"),
+            hasher.digest_bytes_list(&[b"foo".as_ref(), b"bar".as_ref()])
+        );
+
+        let hasher = HashAlgorithm::Sha3_256.hasher();
+        assert_eq!(HashAlgorithm::Sha3_256, hasher.algorithm());
+        // SHA3-256 of an empty string.
+        assert_eq!(
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a",
+            format!("{:x}", hasher.digest_bytes_list(&[]))
+        );
+        assert_eq!(
+            hasher.digest_bytes_list(&[b"foobar".as_ref()]),
+            hasher.digest_bytes_list(&[b"foo".as_ref(), b"bar".as_ref()])
+        );
+        assert_ne!(
+            HashAlgorithm::Sha512_256
+                .hasher()
+                .digest_bytes_list(&[b"foo".as_ref()]),
+            hasher.digest_bytes_list(&[b"foo".as_ref()])
+        );
+    }
+}
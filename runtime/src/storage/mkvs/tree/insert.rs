@@ -11,6 +11,99 @@ impl Tree {
     /// Insert a key/value pair into the tree.
     pub fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
         let ctx = ctx.freeze();
+        self.check_size_limits(key, value)?;
+        self.insert_one(&ctx, key, value)
+    }
+
+    /// Insert a batch of key/value pairs into the tree.
+    ///
+    /// The batch is sorted by key and inserted in a single descent, splitting
+    /// the batch at each internal node so that every node on the affected
+    /// paths is visited and marked dirty only once. If the same key appears
+    /// multiple times, the last occurrence wins. Returns the previous value for
+    /// each item, in the order the items were given.
+    pub fn insert_batch(
+        &mut self,
+        ctx: Context,
+        items: &[(Key, Vec<u8>)],
+    ) -> Result<Vec<Option<Vec<u8>>>> {
+        let ctx = ctx.freeze();
+
+        // Check all items first, so that the batch is either applied fully or not at all.
+        for (key, value) in items {
+            self.check_size_limits(key, value)?;
+        }
+
+        // Stable sort, so items with the same key retain their relative order.
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        order.sort_by(|a, b| items[*a].0.cmp(&items[*b].0));
+
+        // Split the sorted items into runs of the same key, the last item of
+        // each run is inserted.
+        let mut runs: Vec<&[usize]> = Vec::new();
+        let mut i = 0;
+        while i < order.len() {
+            let mut j = i + 1;
+            while j < order.len() && items[order[j]].0 == items[order[i]].0 {
+                j += 1;
+            }
+            runs.push(&order[i..j]);
+            i = j;
+        }
+        let batch: Vec<(Key, Value)> = runs
+            .iter()
+            .map(|run| items[run[run.len() - 1]].clone())
+            .collect();
+
+        let pending_root = self.cache.borrow().get_pending_root();
+
+        // Remember where the paths from root to target nodes end (will end).
+        self.cache.borrow_mut().mark_position();
+
+        let (new_root, old_vals) = self._insert_batch(&ctx, pending_root, 0, &batch, 0)?;
+        self.cache.borrow_mut().set_pending_root(new_root);
+
+        let mut previous = vec![None; items.len()];
+        for ((run, item), old_val) in runs.iter().zip(batch.iter()).zip(old_vals) {
+            self.record_insert(&item.0, &item.1, &old_val);
+
+            // Report the value each item would have observed if the batch had
+            // been applied sequentially in the given order.
+            let mut prev = old_val;
+            for idx in run.iter() {
+                previous[*idx] = prev;
+                prev = Some(items[*idx].1.clone());
+            }
+        }
+
+        Ok(previous)
+    }
+
+    fn check_size_limits(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        if self.max_key_size > 0 && key.len() > self.max_key_size {
+            return Err(Error::KeyTooLarge {
+                size: key.len(),
+                max: self.max_key_size,
+            }
+            .into());
+        }
+        if self.max_value_size > 0 && value.len() > self.max_value_size {
+            return Err(Error::ValueTooLarge {
+                size: value.len(),
+                max: self.max_value_size,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn insert_one(
+        &mut self,
+        ctx: &Arc<Context>,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
         let pending_root = self.cache.borrow().get_pending_root();
         let boxed_key = key.to_vec();
         let boxed_val = value.to_vec();
@@ -18,27 +111,237 @@ impl Tree {
         // Remember where the path from root to target node ends (will end).
         self.cache.borrow_mut().mark_position();
 
-        let (new_root, old_val) =
-            self._insert(&ctx, pending_root, 0, &boxed_key, boxed_val.clone(), 0)?;
-        let existed = old_val != None;
-        match self.pending_write_log.get_mut(&boxed_key) {
+        let (new_root, old_val) = self._insert(ctx, pending_root, 0, &boxed_key, boxed_val, 0)?;
+        self.cache.borrow_mut().set_pending_root(new_root);
+        self.record_insert(key, value, &old_val);
+
+        Ok(old_val)
+    }
+
+    /// Record an insertion in the pending write log and the undo log.
+    fn record_insert(&mut self, key: &[u8], value: &[u8], old_val: &Option<Value>) {
+        match self.pending_write_log.get_mut(key) {
             None => {
                 self.pending_write_log.insert(
-                    boxed_key,
+                    key.to_vec(),
                     PendingLogEntry {
                         key: key.to_vec(),
-                        value: Some(boxed_val.clone()),
-                        existed: existed,
+                        value: Some(value.to_vec()),
+                        existed: old_val.is_some(),
+                        previous: old_val.clone(),
                     },
                 );
             }
             Some(ref mut entry) => {
-                entry.value = Some(boxed_val.clone());
+                entry.value = Some(value.to_vec());
             }
         };
-        self.cache.borrow_mut().set_pending_root(new_root.clone());
+        self.record_undo(key, old_val);
+    }
 
-        Ok(old_val)
+    /// Insert a sorted batch of distinct keys into the subtree rooted at the
+    /// given pointer. Returns the new subtree root and the previous value of
+    /// each key in the batch.
+    fn _insert_batch(
+        &mut self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        bit_depth: Depth,
+        batch: &[(Key, Value)],
+        depth: Depth,
+    ) -> Result<(NodePtrRef, Vec<Option<Value>>)> {
+        match batch.len() {
+            0 => return Ok((ptr, Vec::new())),
+            1 => {
+                let (key, val) = &batch[0];
+                let (ptr, old_val) = self._insert(ctx, ptr, bit_depth, key, val.clone(), depth)?;
+                return Ok((ptr, vec![old_val]));
+            }
+            _ => {}
+        }
+
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr.clone(),
+            Some(FetcherSyncGet::new(&batch[0].0, bit_depth, false)),
+        )?;
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => self.build_batch(ctx, None, bit_depth, batch, depth),
+            NodeKind::Leaf => {
+                let key = noderef_as!(node_ref.unwrap(), Leaf).key.clone();
+                self.build_batch(ctx, Some((key, ptr)), bit_depth, batch, depth)
+            }
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                let label = noderef_as!(node_ref, Internal).label.clone();
+                let label_bit_length = noderef_as!(node_ref, Internal).label_bit_length;
+
+                // Find the part of the label matched by all keys of the batch.
+                let cp_len = batch
+                    .iter()
+                    .map(|(key, _)| {
+                        let (_, key_remainder) = key.split(bit_depth, key.bit_length());
+                        label.common_prefix_len(
+                            label_bit_length,
+                            &key_remainder,
+                            key.bit_length() - bit_depth,
+                        )
+                    })
+                    .min()
+                    .unwrap_or(label_bit_length);
+
+                if cp_len < label_bit_length {
+                    // Some keys mismatch the label at position cp_len. Split the
+                    // edge and insert the batch below the new internal node.
+                    let (left, right);
+                    if let NodeBox::Internal(ref mut n) = *node_ref.borrow_mut() {
+                        let label_split = n.label.split(cp_len, n.label_bit_length);
+                        n.label = label_split.1;
+                        n.label_bit_length = n.label_bit_length - cp_len;
+                        n.clean = false;
+                        ptr.borrow_mut().clean = false;
+                        // No longer eligible for eviction as it is dirty. The leaf
+                        // node cannot be refetched via a dirty node either.
+                        let mut cache = self.cache.borrow_mut();
+                        cache.rollback_node(ptr.clone(), NodeKind::Internal);
+                        cache.rollback_node(n.leaf_node.clone(), NodeKind::Leaf);
+
+                        if n.label.get_bit(0) {
+                            left = NodePointer::null_ptr();
+                            right = ptr;
+                        } else {
+                            left = ptr;
+                            right = NodePointer::null_ptr();
+                        }
+                    } else {
+                        return Err(anyhow!(
+                            "insert.rs: unknown internal node_ref {:?}",
+                            node_ref
+                        ));
+                    }
+
+                    let label_prefix = label.split(cp_len, label_bit_length).0;
+                    let new_internal = self.cache.borrow_mut().new_internal_node(
+                        &label_prefix,
+                        cp_len,
+                        NodePointer::null_ptr(),
+                        left,
+                        right,
+                    );
+                    return self._insert_batch(ctx, new_internal, bit_depth, batch, depth);
+                }
+
+                // All keys match the node's label. Split the batch into the
+                // key ending at this node and the keys going left and right.
+                let bit_depth = bit_depth + label_bit_length;
+                let (leaf_batch, left_batch, right_batch) = split_batch(batch, bit_depth);
+                let leaf_node = noderef_as!(node_ref, Internal).leaf_node.clone();
+                let left = noderef_as!(node_ref, Internal).left.clone();
+                let right = noderef_as!(node_ref, Internal).right.clone();
+
+                let (leaf_node, mut previous) =
+                    self._insert_batch(ctx, leaf_node, bit_depth, leaf_batch, depth)?;
+                let (left, left_previous) =
+                    self._insert_batch(ctx, left, bit_depth, left_batch, depth + 1)?;
+                let (right, right_previous) =
+                    self._insert_batch(ctx, right, bit_depth, right_batch, depth + 1)?;
+                previous.extend(left_previous);
+                previous.extend(right_previous);
+
+                if let NodeBox::Internal(ref mut n) = *node_ref.borrow_mut() {
+                    n.leaf_node = leaf_node;
+                    n.left = left;
+                    n.right = right;
+
+                    if !n.leaf_node.borrow().clean
+                        || !n.left.borrow().clean
+                        || !n.right.borrow().clean
+                    {
+                        n.clean = false;
+                        ptr.borrow_mut().clean = false;
+                        // No longer eligible for eviction as it is dirty. The
+                        // leaf node cannot be refetched via a dirty node either.
+                        let mut cache = self.cache.borrow_mut();
+                        cache.rollback_node(ptr.clone(), NodeKind::Internal);
+                        cache.rollback_node(n.leaf_node.clone(), NodeKind::Leaf);
+                    }
+                }
+
+                Ok((ptr, previous))
+            }
+        }
+    }
+
+    /// Build a new subtree holding a sorted batch of at least two distinct
+    /// keys and the key of an existing leaf node, if any.
+    fn build_batch(
+        &mut self,
+        ctx: &Arc<Context>,
+        existing: Option<(Key, NodePtrRef)>,
+        bit_depth: Depth,
+        batch: &[(Key, Value)],
+        depth: Depth,
+    ) -> Result<(NodePtrRef, Vec<Option<Value>>)> {
+        // The common prefix of all keys is the common prefix of the smallest
+        // and the largest key.
+        let mut first = &batch[0].0;
+        let mut last = &batch[batch.len() - 1].0;
+        if let Some((ref key, _)) = existing {
+            first = first.min(key);
+            last = last.max(key);
+        }
+        let (_, first_remainder) = first.split(bit_depth, first.bit_length());
+        let (_, last_remainder) = last.split(bit_depth, last.bit_length());
+        let cp_len = first_remainder.common_prefix_len(
+            first.bit_length() - bit_depth,
+            &last_remainder,
+            last.bit_length() - bit_depth,
+        );
+        let label_prefix = first_remainder
+            .split(cp_len, first_remainder.bit_length())
+            .0;
+
+        let bit_depth = bit_depth + cp_len;
+        let (leaf_batch, left_batch, right_batch) = split_batch(batch, bit_depth);
+
+        // The existing leaf node is kept at its position in the new subtree.
+        let (mut leaf_node, mut left, mut right) = (
+            NodePointer::null_ptr(),
+            NodePointer::null_ptr(),
+            NodePointer::null_ptr(),
+        );
+        if let Some((key, ptr)) = existing {
+            if key.bit_length() == bit_depth {
+                // The leaf node cannot be refetched via the new dirty internal node.
+                self.cache
+                    .borrow_mut()
+                    .rollback_node(ptr.clone(), NodeKind::Leaf);
+                leaf_node = ptr;
+            } else if key.get_bit(bit_depth) {
+                right = ptr;
+            } else {
+                left = ptr;
+            }
+        }
+
+        let (leaf_node, mut previous) =
+            self._insert_batch(ctx, leaf_node, bit_depth, leaf_batch, depth)?;
+        let (left, left_previous) =
+            self._insert_batch(ctx, left, bit_depth, left_batch, depth + 1)?;
+        let (right, right_previous) =
+            self._insert_batch(ctx, right, bit_depth, right_batch, depth + 1)?;
+        previous.extend(left_previous);
+        previous.extend(right_previous);
+
+        let new_internal = self.cache.borrow_mut().new_internal_node(
+            &label_prefix,
+            cp_len,
+            leaf_node,
+            left,
+            right,
+        );
+        Ok((new_internal, previous))
     }
 
     fn _insert(
@@ -53,7 +356,7 @@ impl Tree {
         let node_ref = self.cache.borrow_mut().deref_node_ptr(
             ctx,
             ptr.clone(),
-            Some(FetcherSyncGet::new(key, false)),
+            Some(FetcherSyncGet::new(key, bit_depth, false)),
         )?;
 
         let (_, key_remainder) = key.split(bit_depth, key.bit_length());
@@ -118,10 +421,11 @@ impl Tree {
                         {
                             n.clean = false;
                             ptr.borrow_mut().clean = false;
-                            // No longer eligible for eviction as it is dirty.
-                            self.cache
-                                .borrow_mut()
-                                .rollback_node(ptr.clone(), NodeKind::Internal);
+                            // No longer eligible for eviction as it is dirty. The
+                            // leaf node cannot be refetched via a dirty node either.
+                            let mut cache = self.cache.borrow_mut();
+                            cache.rollback_node(ptr.clone(), NodeKind::Internal);
+                            cache.rollback_node(n.leaf_node.clone(), NodeKind::Leaf);
                         }
 
                         return Ok((ptr, r.1));
@@ -135,10 +439,12 @@ impl Tree {
                     n.label_bit_length = n.label_bit_length - cp_len;
                     n.clean = false;
                     ptr.borrow_mut().clean = false;
-                    // No longer eligible for eviction as it is dirty.
-                    self.cache
-                        .borrow_mut()
-                        .rollback_node(ptr.clone(), NodeKind::Internal);
+                    // No longer eligible for eviction as it is dirty. The leaf
+                    // node cannot be refetched via a dirty node either.
+                    let mut cache = self.cache.borrow_mut();
+                    cache.rollback_node(ptr.clone(), NodeKind::Internal);
+                    cache.rollback_node(n.leaf_node.clone(), NodeKind::Leaf);
+                    drop(cache);
 
                     let new_leaf = self.cache.borrow_mut().new_leaf_node(key, val);
                     if key.bit_length() - bit_depth == cp_len {
@@ -226,7 +532,11 @@ impl Tree {
                             right = NodePointer::null_ptr();
                         }
                     } else if n.key.bit_length() - bit_depth == cp_len {
-                        // Label is a prefix of the inserted key.
+                        // Label is a prefix of the inserted key. The leaf node
+                        // cannot be refetched via the new dirty internal node.
+                        self.cache
+                            .borrow_mut()
+                            .rollback_node(ptr.clone(), NodeKind::Leaf);
                         leaf_node = ptr;
                         if key_remainder.get_bit(cp_len) {
                             left = NodePointer::null_ptr();
@@ -261,3 +571,28 @@ impl Tree {
         }
     }
 }
+
+/// Split a sorted batch of keys sharing their first `bit_depth` bits into the
+/// key ending at that depth (if any) and the keys continuing with a zero and
+/// with a one bit.
+fn split_batch(
+    batch: &[(Key, Value)],
+    bit_depth: Depth,
+) -> (&[(Key, Value)], &[(Key, Value)], &[(Key, Value)]) {
+    // A key ending at the given depth is a prefix of all other keys and sorts first.
+    let leaf_end = match batch.first() {
+        Some((key, _)) if key.bit_length() == bit_depth => 1,
+        _ => 0,
+    };
+    let left_end = leaf_end
+        + batch[leaf_end..]
+            .iter()
+            .take_while(|(key, _)| !key.get_bit(bit_depth))
+            .count();
+
+    (
+        &batch[..leaf_end],
+        &batch[leaf_end..left_end],
+        &batch[left_end..],
+    )
+}
@@ -2,18 +2,28 @@
 use std::{collections::VecDeque, fmt, iter::Iterator, mem::replace, sync::Arc};
 
 use anyhow::{Error, Result};
+use futures::{try_ready, Async, Poll, Stream};
 use io_context::Context;
 
-use crate::storage::mkvs::{cache::*, sync::*, tree::*};
+use crate::storage::mkvs::{cache::*, sync::*, tree::*, MKVSIterator};
 
 pub(super) struct FetcherSyncIterate<'a> {
     key: &'a Key,
+    path: &'a Key,
+    bit_depth: Depth,
     prefetch: usize,
 }
 
 impl<'a> FetcherSyncIterate<'a> {
-    pub(super) fn new(key: &'a Key, prefetch: usize) -> Self {
-        Self { key, prefetch }
+    /// Create a fetcher for nodes at the given path and bit depth, iterating
+    /// from the given key.
+    pub(super) fn new(key: &'a Key, path: &'a Key, bit_depth: Depth, prefetch: usize) -> Self {
+        Self {
+            key,
+            path,
+            bit_depth,
+            prefetch,
+        }
     }
 }
 
@@ -31,6 +41,7 @@ impl<'a> ReadSyncFetcher for FetcherSyncIterate<'a> {
                 tree: TreeID {
                     root,
                     position: ptr.borrow().hash,
+                    position_path: Some((self.path.clone(), self.bit_depth)),
                 },
                 key: self.key.clone(),
                 prefetch: self.prefetch as u16,
@@ -41,7 +52,7 @@ impl<'a> ReadSyncFetcher for FetcherSyncIterate<'a> {
 }
 
 /// Visit state of a node.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 enum VisitState {
     Before,
     At,
@@ -51,6 +62,7 @@ enum VisitState {
 
 /// Atom in the current iterator path. Can be used to resume iteration
 /// from a given position.
+#[derive(Clone)]
 struct PathAtom {
     ptr: NodePtrRef,
     bit_depth: Depth,
@@ -109,6 +121,16 @@ impl<'tree> TreeIterator<'tree> {
         self.key.is_some()
     }
 
+    /// Return the key at the current iterator position if any.
+    pub fn key(&self) -> Option<&Key> {
+        self.key.as_ref()
+    }
+
+    /// Return the value at the current iterator position if any.
+    pub fn value(&self) -> Option<&Vec<u8>> {
+        self.value.as_ref()
+    }
+
     /// Return the error that occurred during iteration if any.
     pub fn error(&self) -> &Option<Error> {
         &self.error
@@ -135,8 +157,10 @@ impl<'tree> TreeIterator<'tree> {
             key.to_vec(),
             VisitState::Before,
         ) {
-            self.error = Some(error);
             self.reset();
+            if !self.tree.is_fetch_deferred() {
+                self.error = Some(error);
+            }
         }
     }
 
@@ -161,12 +185,26 @@ impl<'tree> TreeIterator<'tree> {
             // Try to proceed with the current node. If we don't succeed, proceed to the
             // next node.
             let key = self.key.take().expect("iterator is valid");
+            let value = self.value.take();
             self.reset();
-            if let Err(error) =
-                self._next(atom.ptr, atom.bit_depth, atom.path, key.clone(), atom.state)
-            {
-                self.error = Some(error);
+            if let Err(error) = self._next(
+                atom.ptr.clone(),
+                atom.bit_depth,
+                atom.path.clone(),
+                key.clone(),
+                atom.state.clone(),
+            ) {
                 self.reset();
+                if self.tree.is_fetch_deferred() {
+                    // Restore the position so the step can be retried once the
+                    // fetch completes.
+                    remainder.push_front(atom);
+                    self.pos = remainder;
+                    self.key = Some(key);
+                    self.value = value;
+                } else {
+                    self.error = Some(error);
+                }
                 return;
             }
             if self.key.is_some() {
@@ -192,10 +230,16 @@ impl<'tree> TreeIterator<'tree> {
         mut key: Key,
         mut state: VisitState,
     ) -> Result<()> {
+        check_cancelled(&self.ctx)?;
         let node_ref = self.tree.cache.borrow_mut().deref_node_ptr(
             &self.ctx,
             ptr.clone(),
-            Some(FetcherSyncIterate::new(&key, self.prefetch)),
+            Some(FetcherSyncIterate::new(
+                &key,
+                &path,
+                bit_depth,
+                self.prefetch,
+            )),
         )?;
 
         match classify_noderef!(?node_ref) {
@@ -341,11 +385,175 @@ impl<'tree> Iterator for TreeIterator<'tree> {
     }
 }
 
+/// Asynchronous tree iterator, see `Tree::iter_async`.
+pub struct AsyncTreeIterator<'tree> {
+    inner: TreeIterator<'tree>,
+    fetcher: AsyncFetcher,
+    start: Option<Key>,
+}
+
+impl<'tree> Stream for AsyncTreeIterator<'tree> {
+    type Item = (Vec<u8>, Vec<u8>);
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let inner = &mut self.inner;
+        match self.start {
+            Some(ref start) => try_ready!(self.fetcher.poll(|| {
+                inner.seek(start);
+                Ok(())
+            })),
+            None => try_ready!(self.fetcher.poll(|| {
+                inner.next();
+                Ok(())
+            })),
+        }
+        self.start = None;
+
+        if let Some(error) = self.inner.error.take() {
+            return Err(error);
+        }
+        Ok(Async::Ready(self.inner.key.clone().map(|key| {
+            (key, self.inner.value.clone().expect("iterator is valid"))
+        })))
+    }
+}
+
+impl<'tree> MKVSIterator for TreeIterator<'tree> {
+    fn set_prefetch(&mut self, prefetch: usize) {
+        TreeIterator::set_prefetch(self, prefetch)
+    }
+
+    fn is_valid(&self) -> bool {
+        TreeIterator::is_valid(self)
+    }
+
+    fn error(&self) -> &Option<Error> {
+        TreeIterator::error(self)
+    }
+
+    fn rewind(&mut self) {
+        TreeIterator::rewind(self)
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        TreeIterator::seek(self, key)
+    }
+
+    fn key(&self) -> Option<&Vec<u8>> {
+        TreeIterator::key(self)
+    }
+
+    fn value(&self) -> Option<&Vec<u8>> {
+        TreeIterator::value(self)
+    }
+}
+
+/// Tree iterator restricted to keys sharing a common prefix.
+pub struct PrefixIterator<'tree> {
+    inner: TreeIterator<'tree>,
+    prefix: Key,
+}
+
+impl<'tree> PrefixIterator<'tree> {
+    /// Return the error that occurred during iteration if any.
+    pub fn error(&self) -> &Option<Error> {
+        self.inner.error()
+    }
+}
+
+impl<'tree> Iterator for PrefixIterator<'tree> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let prefix = &self.prefix;
+        if !self
+            .inner
+            .key()
+            .map_or(false, |key| key.starts_with(prefix))
+        {
+            return None;
+        }
+        Iterator::next(&mut self.inner)
+    }
+}
+
 impl Tree {
     /// Returns an iterator over the tree.
     pub fn iter(&self, ctx: Context) -> TreeIterator {
         TreeIterator::new(ctx, self)
     }
+
+    /// Returns an iterator over the tree, positioned either at the given key
+    /// or at the next larger key.
+    pub fn iter_from(&self, ctx: Context, key: &[u8]) -> TreeIterator {
+        let mut it = TreeIterator::new(ctx, self);
+        it.seek(key);
+        it
+    }
+
+    /// Returns a stream of the key/value pairs of the tree in key order,
+    /// starting either at the given key or at the next larger key.
+    ///
+    /// For trees constructed via `Options::new_async`, the stream waits for
+    /// any needed fetches from the asynchronous read syncer without blocking
+    /// the current thread. Up to `prefetch` following items are fetched
+    /// together with each missing item.
+    pub fn iter_async(&self, ctx: Context, key: &[u8], prefetch: usize) -> AsyncTreeIterator {
+        let mut inner = TreeIterator::new(ctx, self);
+        inner.set_prefetch(prefetch);
+        AsyncTreeIterator {
+            inner,
+            fetcher: self.async_fetcher(),
+            start: Some(key.to_vec()),
+        }
+    }
+
+    /// Returns an iterator over all keys starting with the given prefix.
+    ///
+    /// Iteration starts by seeking to the prefix so only the subtrees that
+    /// may contain keys under the prefix are fetched from the read syncer.
+    pub fn prefix_iter(&self, ctx: Context, prefix: &[u8]) -> PrefixIterator {
+        PrefixIterator {
+            inner: self.iter_from(ctx, prefix),
+            prefix: prefix.to_vec(),
+        }
+    }
+
+    /// Returns key/value pairs with keys in the range `[start, end)` in key
+    /// order. If `end` is `None` the range is unbounded from above. At most
+    /// `limit` pairs are returned, unless `limit` is zero.
+    pub fn get_range(
+        &self,
+        ctx: Context,
+        start: &[u8],
+        end: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut it = TreeIterator::new(ctx, self);
+        if limit > 0 {
+            it.set_prefetch(limit.min(u16::MAX as usize));
+        }
+        it.seek(start);
+
+        let mut items = Vec::new();
+        while limit == 0 || items.len() < limit {
+            let in_range = match (it.key(), end) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(key), Some(end)) => key.as_slice() < end,
+            };
+            if !in_range {
+                break;
+            }
+            items.push(Iterator::next(&mut it).expect("iterator is valid"));
+        }
+
+        match it.error.take() {
+            Some(error) => Err(error),
+            None => Ok(items),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -530,6 +738,103 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_iterator_from() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+
+        let items = vec![
+            (b"key 1".to_vec(), b"one".to_vec()),
+            (b"key 3".to_vec(), b"three".to_vec()),
+            (b"key 5".to_vec(), b"five".to_vec()),
+        ];
+        for (key, value) in items.iter() {
+            tree.insert(Context::background(), key, value).unwrap();
+        }
+
+        let it = tree.iter_from(Context::background(), b"key 2");
+        assert!(it.is_valid(), "iterator should be valid after seek");
+        assert_eq!(
+            Some(&items[1].0),
+            it.key(),
+            "iterator should be at next key"
+        );
+        assert_eq!(Some(&items[1].1), it.value(), "iterator should have value");
+        let rest: Vec<(Vec<u8>, Vec<u8>)> = it.collect();
+        assert_eq!(items[1..].to_vec(), rest, "iterator should resume from key");
+
+        let it = tree.iter_from(Context::background(), b"key 6");
+        assert!(!it.is_valid(), "iterator should be invalid past the end");
+        assert!(it.key().is_none(), "iterator should not have a key");
+    }
+
+    #[test]
+    fn test_iterator_prefix_and_range() {
+        let server = ProtocolServer::new();
+
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+
+        let items = vec![
+            (b"a".to_vec(), b"a".to_vec()),
+            (b"key 1".to_vec(), b"one".to_vec()),
+            (b"key 2".to_vec(), b"two".to_vec()),
+            (b"key 5".to_vec(), b"five".to_vec()),
+            (b"other".to_vec(), b"other".to_vec()),
+        ];
+        for (key, value) in items.iter() {
+            tree.insert(Context::background(), key, value).unwrap();
+        }
+
+        let check = |tree: &Tree| {
+            let found: Vec<(Vec<u8>, Vec<u8>)> =
+                tree.prefix_iter(Context::background(), b"key").collect();
+            assert_eq!(
+                items[1..4].to_vec(),
+                found,
+                "prefix_iter should return prefixed keys"
+            );
+
+            let found: Vec<(Vec<u8>, Vec<u8>)> =
+                tree.prefix_iter(Context::background(), b"none").collect();
+            assert!(found.is_empty(), "prefix_iter should return nothing");
+
+            let found = tree
+                .get_range(Context::background(), b"key 1", Some(b"key 5"), 0)
+                .expect("get_range");
+            assert_eq!(items[1..3].to_vec(), found, "get_range should exclude end");
+
+            let found = tree
+                .get_range(Context::background(), b"b", None, 2)
+                .expect("get_range");
+            assert_eq!(
+                items[1..3].to_vec(),
+                found,
+                "get_range should respect limit"
+            );
+
+            let found = tree
+                .get_range(Context::background(), b"", None, 0)
+                .expect("get_range");
+            assert_eq!(items, found, "get_range should return all items");
+        };
+
+        // Direct.
+        check(&tree);
+
+        // Remote.
+        let (write_log, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        server.apply(&write_log, hash, Default::default(), 0);
+
+        let remote_tree = Tree::make()
+            .with_capacity(0, 0)
+            .with_root(Root {
+                hash,
+                ..Default::default()
+            })
+            .new(server.read_sync());
+        check(&remote_tree);
+    }
+
     #[test]
     fn test_iterator_eviction() {
         let server = ProtocolServer::new();
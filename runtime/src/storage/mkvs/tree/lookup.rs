@@ -1,19 +1,28 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 use anyhow::Result;
+use futures::{future, Future};
 use io_context::Context;
+use serde_bytes::ByteBuf;
 
 use crate::storage::mkvs::{cache::*, sync::*, tree::*};
 
 pub(super) struct FetcherSyncGet<'a> {
     key: &'a Key,
+    bit_depth: Depth,
     include_siblings: bool,
 }
 
 impl<'a> FetcherSyncGet<'a> {
-    pub(super) fn new(key: &'a Key, include_siblings: bool) -> Self {
+    /// Create a fetcher for nodes at the given bit depth on the lookup path
+    /// of the given key.
+    pub(super) fn new(key: &'a Key, bit_depth: Depth, include_siblings: bool) -> Self {
         Self {
             key,
+            bit_depth,
             include_siblings,
         }
     }
@@ -33,6 +42,8 @@ impl<'a> ReadSyncFetcher for FetcherSyncGet<'a> {
                 tree: TreeID {
                     root,
                     position: ptr.borrow().hash,
+                    // Lookups only follow the bits of the key.
+                    position_path: Some((self.key.clone(), self.bit_depth)),
                 },
                 key: self.key.clone(),
                 include_siblings: self.include_siblings,
@@ -42,12 +53,105 @@ impl<'a> ReadSyncFetcher for FetcherSyncGet<'a> {
     }
 }
 
+struct FetcherSyncGetMulti<'a> {
+    keys: &'a [ByteBuf],
+}
+
+impl<'a> ReadSyncFetcher for FetcherSyncGetMulti<'a> {
+    fn fetch(
+        &self,
+        ctx: Context,
+        root: Root,
+        ptr: NodePtrRef,
+        rs: &mut Box<dyn ReadSync>,
+    ) -> Result<Proof> {
+        let rsp = rs.sync_get_multi(
+            ctx,
+            GetMultiRequest {
+                tree: TreeID {
+                    root,
+                    position: ptr.borrow().hash,
+                    ..Default::default()
+                },
+                keys: self.keys.to_vec(),
+            },
+        )?;
+        Ok(rsp.proof)
+    }
+}
+
 impl Tree {
     /// Get an existing key.
     pub fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
         self._get_top(ctx, key, false)
     }
 
+    /// Get an existing key, without blocking on fetches from the read syncer.
+    ///
+    /// For trees constructed via `Options::new_async`, the returned future
+    /// waits for any needed fetches from the asynchronous read syncer, so
+    /// that several lookups and other work can proceed while the fetches
+    /// are in progress. For other trees, the lookup is performed as with
+    /// `get` when the future is first polled.
+    pub fn get_async<'a>(
+        &'a self,
+        ctx: Context,
+        key: &[u8],
+    ) -> Box<dyn Future<Item = Option<Vec<u8>>, Error = anyhow::Error> + 'a> {
+        let ctx = ctx.freeze();
+        let key = key.to_vec();
+        let mut fetcher = self.async_fetcher();
+        Box::new(future::poll_fn(move || {
+            fetcher.poll(|| self._get_top(Context::create_child(&ctx), &key, false))
+        }))
+    }
+
+    /// Get multiple existing keys.
+    ///
+    /// Keys that cannot be resolved from the local cache are deduplicated and
+    /// fetched from the read syncer with a single `sync_get_multi` request
+    /// instead of one `sync_get` per key. Returns the values in the order the
+    /// keys were given.
+    pub fn get_multi(&self, ctx: Context, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        let ctx = ctx.freeze();
+
+        let missing: BTreeSet<&[u8]> = keys
+            .iter()
+            .cloned()
+            .filter(|key| {
+                self._get_top(Context::create_child(&ctx), key, true)
+                    .is_err()
+            })
+            .collect();
+        // Fetched nodes can only be merged into a clean tree, otherwise the keys
+        // are fetched individually below.
+        let pending_root = self.cache.borrow().get_pending_root();
+        if !missing.is_empty() && pending_root.borrow().clean {
+            let keys: Vec<ByteBuf> = missing.iter().map(|key| ByteBuf::from(*key)).collect();
+            self.cache.borrow_mut().remote_sync(
+                &ctx,
+                pending_root,
+                FetcherSyncGetMulti { keys: &keys },
+            )?;
+        }
+
+        let mut resolved: BTreeMap<&[u8], Option<Vec<u8>>> = BTreeMap::new();
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            let value = match resolved.get(key) {
+                Some(value) => value.clone(),
+                None => {
+                    let value = self._get_top(Context::create_child(&ctx), key, false)?;
+                    resolved.insert(*key, value.clone());
+                    value
+                }
+            };
+            values.push(value);
+        }
+
+        Ok(values)
+    }
+
     /// Check if the key exists in the local cache.
     pub fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
         match self._get_top(ctx, key, true) {
@@ -57,6 +161,234 @@ impl Tree {
         }
     }
 
+    /// Get an existing key together with a proof of its inclusion (or
+    /// non-inclusion) in the tree.
+    ///
+    /// The proof is rooted at the current root so the tree must not have any
+    /// uncommitted changes.
+    pub fn get_with_proof(&self, ctx: Context, key: &[u8]) -> Result<(Option<Vec<u8>>, Proof)> {
+        let ctx = ctx.freeze();
+        let boxed_key = key.to_vec();
+        let pending_root = self.cache.borrow().get_pending_root();
+        if !self.pending_write_log.is_empty() || !pending_root.borrow().clean {
+            return Err(Error::UncommittedChanges.into());
+        }
+        let root_hash = pending_root.borrow().hash;
+
+        // Remember where the path from root to target node ends (will end).
+        self.cache.borrow_mut().mark_position();
+
+        let mut proof = ProofBuilder::new(self.max_proof_size);
+        let value = self._get_with_proof(&ctx, pending_root, 0, &boxed_key, &mut proof)?;
+
+        Ok((value, proof.build(root_hash)))
+    }
+
+    fn _get_with_proof(
+        &self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        bit_depth: Depth,
+        key: &Key,
+        proof: &mut ProofBuilder,
+    ) -> Result<Option<Value>> {
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr,
+            Some(FetcherSyncGet::new(key, bit_depth, false)),
+        )?;
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => {
+                // Reached a nil node, there is nothing here.
+                proof.push(None)?;
+                Ok(None)
+            }
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                let (leaf_node, left, right, bit_length) = match *node_ref.borrow() {
+                    NodeBox::Internal(ref n) => (
+                        n.leaf_node.clone(),
+                        n.left.clone(),
+                        n.right.clone(),
+                        bit_depth + n.label_bit_length,
+                    ),
+                    _ => unreachable!("node kind is internal node"),
+                };
+
+                // Make sure the leaf node is available as it is embedded in the
+                // encoding of the internal node.
+                let leaf_ref = self.cache.borrow_mut().deref_node_ptr(
+                    ctx,
+                    leaf_node,
+                    Some(FetcherSyncGet::new(key, bit_length, false)),
+                )?;
+                proof.push_node(&*node_ref.borrow())?;
+
+                // Does lookup key end here? Look into LeafNode.
+                if key.bit_length() == bit_length {
+                    proof.push_hash(&left)?;
+                    proof.push_hash(&right)?;
+
+                    return match leaf_ref {
+                        Some(leaf_ref) => {
+                            let value = noderef_as!(leaf_ref, Leaf).value.clone();
+                            Ok(Some(value))
+                        }
+                        None => Ok(None),
+                    };
+                }
+
+                // Lookup key is too short for the current label. It's not stored.
+                if key.bit_length() < bit_length {
+                    proof.push_hash(&left)?;
+                    proof.push_hash(&right)?;
+
+                    return Ok(None);
+                }
+
+                // Continue recursively based on a bit value.
+                if key.get_bit(bit_length) {
+                    proof.push_hash(&left)?;
+                    self._get_with_proof(ctx, right, bit_length, key, proof)
+                } else {
+                    let value = self._get_with_proof(ctx, left, bit_length, key, proof)?;
+                    proof.push_hash(&right)?;
+                    Ok(value)
+                }
+            }
+            NodeKind::Leaf => {
+                // Reached a leaf node, check if key matches.
+                let node_ref = node_ref.unwrap();
+                proof.push_node(&*node_ref.borrow())?;
+
+                if noderef_as!(node_ref, Leaf).key == *key {
+                    return Ok(Some(noderef_as!(node_ref, Leaf).value.clone()));
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Get multiple existing keys together with a single proof of their
+    /// inclusion (or non-inclusion) in the tree.
+    ///
+    /// Nodes shared by the lookup paths of several keys are only included
+    /// once, so the proof is usually much smaller than the individual proofs
+    /// combined. Returns the values in the order the keys were given.
+    ///
+    /// The proof is rooted at the current root so the tree must not have any
+    /// uncommitted changes.
+    pub fn get_multi_with_proof(
+        &self,
+        ctx: Context,
+        keys: &[&[u8]],
+    ) -> Result<(Vec<Option<Vec<u8>>>, Proof)> {
+        let ctx = ctx.freeze();
+        let proof = self.get_proof_multi(Context::create_child(&ctx), keys)?;
+
+        // All nodes on the lookup paths are now cached.
+        let values = keys
+            .iter()
+            .map(|key| self._get_top(Context::create_child(&ctx), key, false))
+            .collect::<Result<_>>()?;
+
+        Ok((values, proof))
+    }
+
+    /// Build a single proof covering the lookup paths of all given keys.
+    ///
+    /// The proof is rooted at the current root so the tree must not have any
+    /// uncommitted changes.
+    pub(crate) fn get_proof_multi(&self, ctx: Context, keys: &[&[u8]]) -> Result<Proof> {
+        let ctx = ctx.freeze();
+        let mut boxed_keys: Vec<Key> = keys.iter().map(|key| key.to_vec()).collect();
+        // Duplicate keys do not change the proof.
+        boxed_keys.sort();
+        boxed_keys.dedup();
+        let pending_root = self.cache.borrow().get_pending_root();
+        if !self.pending_write_log.is_empty() || !pending_root.borrow().clean {
+            return Err(Error::UncommittedChanges.into());
+        }
+        let root_hash = pending_root.borrow().hash;
+
+        // Remember where the path from root to target nodes ends (will end).
+        self.cache.borrow_mut().mark_position();
+
+        let mut proof = ProofBuilder::new(self.max_proof_size);
+        let keys: Vec<&Key> = boxed_keys.iter().collect();
+        self._get_proof_multi(&ctx, pending_root, 0, &keys, &mut proof)?;
+
+        Ok(proof.build(root_hash))
+    }
+
+    fn _get_proof_multi(
+        &self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        bit_depth: Depth,
+        keys: &[&Key],
+        proof: &mut ProofBuilder,
+    ) -> Result<()> {
+        // None of the lookup paths pass through this node.
+        if keys.is_empty() {
+            proof.push_hash(&ptr)?;
+            return Ok(());
+        }
+
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr,
+            Some(FetcherSyncGet::new(keys[0], bit_depth, false)),
+        )?;
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => {
+                // Reached a nil node, there is nothing here.
+                proof.push(None)?;
+                Ok(())
+            }
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                let (leaf_node, left, right, bit_length) = match *node_ref.borrow() {
+                    NodeBox::Internal(ref n) => (
+                        n.leaf_node.clone(),
+                        n.left.clone(),
+                        n.right.clone(),
+                        bit_depth + n.label_bit_length,
+                    ),
+                    _ => unreachable!("node kind is internal node"),
+                };
+
+                // Make sure the leaf node is available as it is embedded in the
+                // encoding of the internal node.
+                self.cache.borrow_mut().deref_node_ptr(
+                    ctx,
+                    leaf_node,
+                    Some(FetcherSyncGet::new(keys[0], bit_length, false)),
+                )?;
+                proof.push_node(&*node_ref.borrow())?;
+
+                // Lookups of keys that end here or are too short for the current
+                // label stop at this node, the others continue based on a bit
+                // value, exactly as in `_get_with_proof`.
+                let (right_keys, left_keys): (Vec<&Key>, Vec<&Key>) = keys
+                    .iter()
+                    .copied()
+                    .filter(|key| key.bit_length() > bit_length)
+                    .partition(|key| key.get_bit(bit_length));
+
+                self._get_proof_multi(ctx, left, bit_length, &left_keys, proof)?;
+                self._get_proof_multi(ctx, right, bit_length, &right_keys, proof)
+            }
+            NodeKind::Leaf => {
+                let node_ref = node_ref.unwrap();
+                proof.push_node(&*node_ref.borrow())?;
+                Ok(())
+            }
+        }
+    }
+
     fn _get_top(&self, ctx: Context, key: &[u8], check_only: bool) -> Result<Option<Vec<u8>>> {
         let ctx = ctx.freeze();
         let boxed_key = key.to_vec();
@@ -88,7 +420,7 @@ impl Tree {
             if check_only {
                 None
             } else {
-                Some(FetcherSyncGet::new(key, false))
+                Some(FetcherSyncGet::new(key, bit_depth, false))
             },
         )?;
 
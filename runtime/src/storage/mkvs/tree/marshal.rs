@@ -12,17 +12,68 @@ const VERSION_SIZE: usize = size_of::<u64>();
 /// Size of the encoded value length.
 const VALUE_LENGTH_SIZE: usize = size_of::<u32>();
 
-impl Marshal for NodeBox {
-    fn marshal_binary(&self) -> Result<Vec<u8>> {
+/// Hard caps on the sizes of keys and values accepted when unmarshalling
+/// nodes.
+///
+/// Length prefixes are always validated against the remaining input, these
+/// limits additionally allow rejecting nodes that are well-formed but larger
+/// than the caller is willing to handle. The default limits only reject what
+/// can not be encoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UnmarshalLimits {
+    /// Maximum size of a key, in bytes.
+    pub max_key_size: usize,
+    /// Maximum size of a value, in bytes.
+    pub max_value_size: usize,
+}
+
+impl Default for UnmarshalLimits {
+    fn default() -> Self {
+        Self {
+            max_key_size: Depth::MAX as usize,
+            max_value_size: u32::MAX as usize,
+        }
+    }
+}
+
+/// Read a length-prefixed field of `len` bytes starting at `pos`, making sure
+/// that it fits both the given limit and the remaining input.
+fn read_field(data: &[u8], pos: usize, len: usize, max_len: usize) -> Result<&[u8]> {
+    if len > max_len {
+        return Err(Error::MalformedNode.into());
+    }
+    match pos.checked_add(len) {
+        Some(end) if end <= data.len() => Ok(&data[pos..end]),
+        _ => Err(Error::MalformedNode.into()),
+    }
+}
+
+impl NodeBox {
+    /// Marshal the node into a binary form using the given encoding mode.
+    pub fn marshal_binary_mode(&self, mode: MarshalMode) -> Result<Vec<u8>> {
         match self {
-            NodeBox::Internal(ref n) => n.marshal_binary(),
+            NodeBox::Internal(ref n) => n.marshal_binary_mode(mode),
             NodeBox::Leaf(ref n) => n.marshal_binary(),
         }
     }
 
-    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
+    /// Unmarshal the node from the given byte slice, which must use the
+    /// given encoding mode.
+    pub fn unmarshal_binary_mode(&mut self, data: &[u8], mode: MarshalMode) -> Result<usize> {
+        self.unmarshal_binary_limited(data, mode, &UnmarshalLimits::default())
+    }
+
+    /// Unmarshal the node from the given byte slice, which must use the
+    /// given encoding mode, rejecting keys and values exceeding the given
+    /// limits.
+    pub fn unmarshal_binary_limited(
+        &mut self,
+        data: &[u8],
+        mode: MarshalMode,
+        limits: &UnmarshalLimits,
+    ) -> Result<usize> {
         if data.len() < 1 {
-            Err(TreeError::MalformedNode.into())
+            Err(Error::MalformedNode.into())
         } else {
             let mut kind = NodeKind::None;
             kind.unmarshal_binary(data)?;
@@ -38,17 +89,27 @@ impl Marshal for NodeBox {
                     });
                 }
                 _ => {
-                    return Err(TreeError::MalformedNode.into());
+                    return Err(Error::MalformedNode.into());
                 }
             };
             match self {
-                NodeBox::Internal(ref mut n) => n.unmarshal_binary(data),
-                NodeBox::Leaf(ref mut n) => n.unmarshal_binary(data),
+                NodeBox::Internal(ref mut n) => n.unmarshal_binary_limited(data, mode, limits),
+                NodeBox::Leaf(ref mut n) => n.unmarshal_binary_limited(data, limits),
             }
         }
     }
 }
 
+impl Marshal for NodeBox {
+    fn marshal_binary(&self) -> Result<Vec<u8>> {
+        self.marshal_binary_mode(MarshalMode::Full)
+    }
+
+    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
+        self.unmarshal_binary_mode(data, MarshalMode::Full)
+    }
+}
+
 impl Marshal for NodeKind {
     fn marshal_binary(&self) -> Result<Vec<u8>> {
         Ok(vec![*self as u8])
@@ -56,7 +117,7 @@ impl Marshal for NodeKind {
 
     fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
         if data.len() < 1 {
-            Err(TreeError::MalformedNode.into())
+            Err(Error::MalformedNode.into())
         } else {
             if data[0] == NodeKind::None as u8 {
                 *self = NodeKind::None;
@@ -65,15 +126,16 @@ impl Marshal for NodeKind {
             } else if data[0] == NodeKind::Leaf as u8 {
                 *self = NodeKind::Leaf;
             } else {
-                return Err(TreeError::MalformedNode.into());
+                return Err(Error::MalformedNode.into());
             }
             Ok(1)
         }
     }
 }
 
-impl Marshal for InternalNode {
-    fn marshal_binary(&self) -> Result<Vec<u8>> {
+impl InternalNode {
+    /// Marshal the node into a binary form using the given encoding mode.
+    pub fn marshal_binary_mode(&self, mode: MarshalMode) -> Result<Vec<u8>> {
         let leaf_node_binary: Vec<u8>;
         if self.leaf_node.borrow().is_null() {
             leaf_node_binary = vec![NodeKind::None as u8];
@@ -89,18 +151,34 @@ impl Marshal for InternalNode {
         result.append(&mut self.label_bit_length.marshal_binary()?);
         result.extend_from_slice(&self.label);
         result.extend_from_slice(leaf_node_binary.as_ref());
-        result.extend_from_slice(self.left.borrow().hash.as_ref());
-        result.extend_from_slice(self.right.borrow().hash.as_ref());
+        if mode == MarshalMode::Full {
+            result.extend_from_slice(self.left.borrow().hash.as_ref());
+            result.extend_from_slice(self.right.borrow().hash.as_ref());
+        }
 
         Ok(result)
     }
 
-    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
+    /// Unmarshal the node from the given byte slice, which must use the
+    /// given encoding mode.
+    pub fn unmarshal_binary_mode(&mut self, data: &[u8], mode: MarshalMode) -> Result<usize> {
+        self.unmarshal_binary_limited(data, mode, &UnmarshalLimits::default())
+    }
+
+    /// Unmarshal the node from the given byte slice, which must use the
+    /// given encoding mode, rejecting an embedded leaf node whose key or
+    /// value exceeds the given limits.
+    pub fn unmarshal_binary_limited(
+        &mut self,
+        data: &[u8],
+        mode: MarshalMode,
+        limits: &UnmarshalLimits,
+    ) -> Result<usize> {
         let mut pos = 0;
         if data.len() < 1 + VERSION_SIZE + size_of::<Depth>() + 1
             || data[pos] != NodeKind::Internal as u8
         {
-            return Err(TreeError::MalformedNode.into());
+            return Err(Error::MalformedNode.into());
         }
         pos += 1;
 
@@ -109,15 +187,11 @@ impl Marshal for InternalNode {
         pos += VERSION_SIZE;
 
         pos += self.label_bit_length.unmarshal_binary(&data[pos..])?;
-        self.label = vec![0; self.label_bit_length.to_bytes()];
-        if pos + self.label_bit_length.to_bytes() > data.len() {
-            return Err(TreeError::MalformedNode.into());
-        }
-        self.label
-            .clone_from_slice(&data[pos..pos + self.label_bit_length.to_bytes()]);
-        pos += self.label_bit_length.to_bytes();
+        let label_len = self.label_bit_length.to_bytes();
+        self.label = read_field(data, pos, label_len, label_len)?.to_vec();
+        pos += label_len;
         if pos >= data.len() {
-            return Err(TreeError::MalformedNode.into());
+            return Err(Error::MalformedNode.into());
         }
 
         if data[pos] == NodeKind::None as u8 {
@@ -127,7 +201,7 @@ impl Marshal for InternalNode {
             let mut leaf_node = LeafNode {
                 ..Default::default()
             };
-            pos += leaf_node.unmarshal_binary(&data[pos..])?;
+            pos += leaf_node.unmarshal_binary_limited(&data[pos..], limits)?;
             self.leaf_node = Rc::new(RefCell::new(NodePointer {
                 clean: true,
                 hash: leaf_node.get_hash(),
@@ -136,8 +210,12 @@ impl Marshal for InternalNode {
             }));
         };
 
-        // Hashes are only present in non-compact serialization.
-        if data.len() >= pos + Hash::len() * 2 {
+        // Hashes are only present in full serialization.
+        if mode == MarshalMode::Full {
+            if data.len() < pos + Hash::len() * 2 {
+                return Err(Error::MalformedNode.into());
+            }
+
             let left_hash = Hash::from(&data[pos..pos + Hash::len()]);
             pos += Hash::len();
             let right_hash = Hash::from(&data[pos..pos + Hash::len()]);
@@ -173,6 +251,16 @@ impl Marshal for InternalNode {
     }
 }
 
+impl Marshal for InternalNode {
+    fn marshal_binary(&self) -> Result<Vec<u8>> {
+        self.marshal_binary_mode(MarshalMode::Full)
+    }
+
+    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
+        self.unmarshal_binary_mode(data, MarshalMode::Full)
+    }
+}
+
 impl Marshal for LeafNode {
     fn marshal_binary(&self) -> Result<Vec<u8>> {
         let mut result: Vec<u8> = Vec::with_capacity(1 + VERSION_SIZE + VALUE_LENGTH_SIZE);
@@ -186,41 +274,67 @@ impl Marshal for LeafNode {
     }
 
     fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
+        self.unmarshal_binary_limited(data, &UnmarshalLimits::default())
+    }
+}
+
+impl LeafNode {
+    /// Unmarshal the node from the given byte slice, rejecting keys and
+    /// values exceeding the given limits.
+    pub fn unmarshal_binary_limited(
+        &mut self,
+        data: &[u8],
+        limits: &UnmarshalLimits,
+    ) -> Result<usize> {
+        let (node, pos) = LeafNode::unmarshal_borrowed_limited(data, limits)?;
+        *self = node.to_node();
+
+        Ok(pos)
+    }
+
+    /// Unmarshal a leaf node from the given byte slice without copying its
+    /// key and value. Returns the borrowed node and the number of bytes
+    /// consumed.
+    pub fn unmarshal_borrowed<'a>(data: &'a [u8]) -> Result<(LeafNodeRef<'a>, usize)> {
+        LeafNode::unmarshal_borrowed_limited(data, &UnmarshalLimits::default())
+    }
+
+    /// Unmarshal a leaf node from the given byte slice without copying its
+    /// key and value, rejecting keys and values exceeding the given limits.
+    pub fn unmarshal_borrowed_limited<'a>(
+        data: &'a [u8],
+        limits: &UnmarshalLimits,
+    ) -> Result<(LeafNodeRef<'a>, usize)> {
         if data.len() < 1 + VERSION_SIZE + size_of::<Depth>() + VALUE_LENGTH_SIZE
             || data[0] != NodeKind::Leaf as u8
         {
-            return Err(TreeError::MalformedNode.into());
+            return Err(Error::MalformedNode.into());
         }
 
-        self.clean = true;
-
         let mut pos = 1;
-        self.version
-            .unmarshal_binary(&data[pos..(pos + VERSION_SIZE)])?;
+        let mut version = 0u64;
+        version.unmarshal_binary(&data[pos..(pos + VERSION_SIZE)])?;
         pos += VERSION_SIZE;
 
-        self.key = Key::new();
-        let key_len = self.key.unmarshal_binary(&data[pos..])?;
-        pos += key_len;
-        if pos + VALUE_LENGTH_SIZE > data.len() {
-            return Err(TreeError::MalformedNode.into());
-        }
+        let mut key_len: Depth = 0;
+        pos += key_len.unmarshal_binary(&data[pos..])?;
+        let key = read_field(data, pos, key_len as usize, limits.max_key_size)?;
+        pos += key.len();
 
-        self.value = Value::new();
         let mut value_len = 0u32;
-        value_len.unmarshal_binary(&data[pos..(pos + VALUE_LENGTH_SIZE)])?;
+        value_len.unmarshal_binary(read_field(data, pos, VALUE_LENGTH_SIZE, VALUE_LENGTH_SIZE)?)?;
         pos += VALUE_LENGTH_SIZE;
-        if pos + (value_len as usize) > data.len() {
-            return Err(TreeError::MalformedNode.into());
-        }
-
-        self.value
-            .extend_from_slice(&data[pos..(pos + value_len as usize)]);
-        pos += value_len as usize;
-
-        self.update_hash();
-
-        Ok(pos)
+        let value = read_field(data, pos, value_len as usize, limits.max_value_size)?;
+        pos += value.len();
+
+        Ok((
+            LeafNodeRef {
+                version,
+                key,
+                value,
+            },
+            pos,
+        ))
     }
 }
 
@@ -234,16 +348,19 @@ impl Marshal for Key {
 
     fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
         if data.len() < size_of::<Depth>() {
-            return Err(TreeError::MalformedKey.into());
+            return Err(Error::MalformedKey.into());
         }
         let mut key_len: Depth = 0;
         key_len.unmarshal_binary(data)?;
 
-        if data.len() < size_of::<Depth>() + key_len as usize {
-            return Err(TreeError::MalformedKey.into());
-        }
-
-        self.extend_from_slice(&data[size_of::<Depth>()..(size_of::<Depth>() + key_len as usize)]);
-        Ok(size_of::<Depth>() + key_len as usize)
+        let key = read_field(
+            data,
+            size_of::<Depth>(),
+            key_len as usize,
+            Depth::MAX as usize,
+        )
+        .map_err(|_| Error::MalformedKey)?;
+        self.extend_from_slice(key);
+        Ok(size_of::<Depth>() + key.len())
     }
 }
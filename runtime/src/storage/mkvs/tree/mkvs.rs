@@ -3,7 +3,7 @@ use io_context::Context;
 
 use crate::{
     common::{crypto::hash::Hash, roothash::Namespace},
-    storage::mkvs::{tree::*, Prefix, WriteLog, MKVS},
+    storage::mkvs::{tree::*, FallibleMKVS, MKVSIterator, Prefix, WriteLog, MKVS},
 };
 
 unsafe impl Send for Tree {}
@@ -55,5 +55,51 @@ impl MKVS for Tree {
         let lock = self.lock.clone();
         let _guard = lock.lock().unwrap();
         self.pending_write_log.clear();
+        self.invalidate_snapshots();
+    }
+}
+
+impl FallibleMKVS for Tree {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let _lock = self.lock.lock().unwrap();
+        self.get(ctx, key)
+    }
+
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        let _lock = self.lock.lock().unwrap();
+        self.cache_contains_key(ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let lock = self.lock.clone();
+        let _guard = lock.lock().unwrap();
+        self.insert(ctx, key, value)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let lock = self.lock.clone();
+        let _guard = lock.lock().unwrap();
+        self.remove(ctx, key)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
+        let lock = self.lock.clone();
+        let _guard = lock.lock().unwrap();
+        self.prefetch_prefixes(ctx, prefixes, limit)
+    }
+
+    fn iter(&self, ctx: Context) -> Box<dyn MKVSIterator + '_> {
+        Box::new(self.iter(ctx))
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        let lock = self.lock.clone();
+        let _guard = lock.lock().unwrap();
+        Tree::commit(self, ctx, namespace, version)
     }
 }
@@ -2,25 +2,47 @@
 mod macros;
 
 mod commit;
+mod dump;
 mod errors;
+mod export;
+mod hasher;
 mod insert;
 mod iterator;
 mod lookup;
 mod marshal;
 mod mkvs;
+mod multiversion;
 mod node;
+mod overlay;
+mod pin;
 mod prefetch;
+mod readonly;
 mod remove;
+mod snapshot;
+mod stats;
+mod testvectors;
 mod tree;
 
 pub use commit::*;
+pub use dump::*;
 pub use errors::*;
+pub use export::*;
+pub use hasher::*;
 pub use insert::*;
 pub use iterator::*;
+pub use marshal::UnmarshalLimits;
+pub use multiversion::*;
 pub use node::*;
+pub use overlay::*;
+pub use readonly::*;
 pub use remove::*;
+pub use snapshot::*;
+pub use stats::*;
+pub use testvectors::*;
 pub use tree::*;
 
+#[cfg(test)]
+mod model_test;
 #[cfg(test)]
 mod node_test;
 #[cfg(test)]
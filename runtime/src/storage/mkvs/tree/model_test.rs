@@ -0,0 +1,125 @@
+//! Property-based tests checking the tree against a simple in-memory model.
+use std::collections::BTreeMap;
+
+use io_context::Context;
+use proptest::{collection::vec, prelude::*, sample::select};
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{sync::*, tree::*},
+};
+
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(Vec<u8>, Vec<u8>),
+    Remove(Vec<u8>),
+    Get(Vec<u8>),
+    Commit,
+}
+
+/// Keys are drawn from a small space so that operations frequently hit
+/// existing keys and keys sharing prefixes.
+fn key() -> impl Strategy<Value = Vec<u8>> {
+    vec(0u8..4, 0..4)
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (key(), vec(any::<u8>(), 1..16)).prop_map(|(key, value)| Op::Insert(key, value)),
+        2 => key().prop_map(Op::Remove),
+        3 => key().prop_map(Op::Get),
+        1 => Just(Op::Commit),
+    ]
+}
+
+/// Compute the root hash of a tree containing exactly the given entries.
+fn model_root(model: &BTreeMap<Vec<u8>, Vec<u8>>) -> Hash {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    for (key, value) in model {
+        tree.insert(Context::background(), key, value)
+            .expect("insert");
+    }
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    hash
+}
+
+fn check_model(ops: Vec<Op>, node_capacity: usize, value_capacity: usize) {
+    let mut model = BTreeMap::new();
+    // Reference tree which keeps all nodes in memory, also used to serve the
+    // nodes evicted from the trees with limited capacity.
+    let mut reference = Tree::make().new(Box::new(NoopReadSyncer));
+    let mut tree = Tree::make()
+        .with_capacity(node_capacity, value_capacity)
+        .new(Box::new(NoopReadSyncer));
+
+    for op in ops {
+        match op {
+            Op::Insert(key, value) => {
+                let previous = model.insert(key.clone(), value.clone());
+                let ctx = Context::background();
+                assert_eq!(previous, reference.insert(ctx, &key, &value).unwrap());
+                let ctx = Context::background();
+                assert_eq!(previous, tree.insert(ctx, &key, &value).unwrap());
+            }
+            Op::Remove(key) => {
+                let previous = model.remove(&key);
+                let ctx = Context::background();
+                assert_eq!(previous, reference.remove(ctx, &key).unwrap());
+                let ctx = Context::background();
+                assert_eq!(previous, tree.remove(ctx, &key).unwrap());
+            }
+            Op::Get(key) => {
+                let expected = model.get(&key).cloned();
+                let ctx = Context::background();
+                assert_eq!(expected, reference.get(ctx, &key).unwrap());
+                let ctx = Context::background();
+                assert_eq!(expected, tree.get(ctx, &key).unwrap());
+            }
+            Op::Commit => {
+                let (_, hash) =
+                    Tree::commit(&mut reference, Context::background(), Default::default(), 0)
+                        .expect("commit");
+                let (_, tree_hash) =
+                    Tree::commit(&mut tree, Context::background(), Default::default(), 0)
+                        .expect("commit");
+                assert_eq!(hash, tree_hash, "root hash depends on cache capacity");
+                assert_eq!(
+                    model_root(&model),
+                    hash,
+                    "root hash depends on operation history"
+                );
+
+                // Continue from the committed root, so further operations
+                // need to fetch evicted nodes.
+                let rs =
+                    MapReadSync::from_tree(Context::background(), &reference).expect("from_tree");
+                tree = Tree::make()
+                    .with_capacity(node_capacity, value_capacity)
+                    .with_root(Root {
+                        hash,
+                        ..Default::default()
+                    })
+                    .new(Box::new(rs));
+            }
+        }
+    }
+
+    let mut it = tree.iter(Context::background());
+    it.rewind();
+    let items = it.by_ref().collect::<Vec<_>>();
+    assert!(it.error().is_none(), "iterator error: {:?}", it.error());
+    assert_eq!(model.into_iter().collect::<Vec<_>>(), items);
+}
+
+proptest! {
+    #[test]
+    fn test_model(
+        ops in vec(op(), 1..200),
+        // The node capacity must fit the nodes on a root-to-leaf path.
+        node_capacity in select(vec![0usize, 16, 64]),
+        value_capacity in select(vec![0usize, 8, 64]),
+    ) {
+        check_model(ops, node_capacity, value_capacity);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use io_context::Context;
+
+use crate::storage::mkvs::{cache::*, sync::*, tree::*};
+
+/// A read-only view over multiple committed versions of a tree.
+///
+/// All versions share the same node cache and read syncer, so the cache
+/// capacity is shared between versions and nodes of frequently queried
+/// versions stay resident.
+pub struct MultiVersionTree {
+    tree: Tree,
+    roots: BTreeMap<u64, (Root, NodePtrRef)>,
+}
+
+impl MultiVersionTree {
+    /// Construct a new multi-version tree using the given read syncer and
+    /// cache capacities (see `Options::with_capacity`).
+    pub fn new(
+        read_syncer: Box<dyn ReadSync>,
+        node_capacity: usize,
+        value_capacity: usize,
+    ) -> Self {
+        Self {
+            tree: Tree::make()
+                .with_capacity(node_capacity, value_capacity)
+                .new(read_syncer),
+            roots: BTreeMap::new(),
+        }
+    }
+
+    /// Add a committed root. Any existing root for the same version is
+    /// replaced.
+    pub fn add_root(&mut self, root: Root) {
+        let ptr = Rc::new(RefCell::new(NodePointer {
+            clean: true,
+            hash: root.hash,
+            ..Default::default()
+        }));
+        self.roots.insert(root.version, (root, ptr));
+    }
+
+    /// Remove the root for the given version.
+    pub fn remove_root(&mut self, version: u64) -> Option<Root> {
+        self.roots.remove(&version).map(|(root, _)| root)
+    }
+
+    /// Return the root for the given version, if any.
+    pub fn get_root(&self, version: u64) -> Option<Root> {
+        self.roots.get(&version).map(|(root, _)| *root)
+    }
+
+    /// Return all versions with known roots in ascending order.
+    pub fn versions(&self) -> Vec<u64> {
+        self.roots.keys().cloned().collect()
+    }
+
+    /// Return the latest version with a known root, if any.
+    pub fn latest_version(&self) -> Option<u64> {
+        self.roots.keys().next_back().cloned()
+    }
+
+    /// Get an existing key at the given version.
+    pub fn get_at(&self, ctx: Context, version: u64, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (root, ptr) = self
+            .roots
+            .get(&version)
+            .ok_or_else(|| anyhow!("mkvs: unknown version {}", version))?;
+
+        {
+            let mut cache = self.tree.cache.borrow_mut();
+            cache.set_pending_root(ptr.clone());
+            cache.set_sync_root(*root);
+        }
+
+        self.tree.get(ctx, key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::interop::{Driver, ProtocolServer};
+
+    #[test]
+    fn test_multi_version_tree() {
+        let server = ProtocolServer::new();
+
+        let mut mvt = MultiVersionTree::new(server.read_sync(), 0, 0);
+        for version in 0..3 {
+            let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+            tree.insert(
+                Context::background(),
+                b"key",
+                format!("value {}", version).as_bytes(),
+            )
+            .unwrap();
+            tree.insert(Context::background(), b"common", b"common")
+                .unwrap();
+            if version == 1 {
+                tree.insert(Context::background(), b"only 1", b"one")
+                    .unwrap();
+            }
+            let (write_log, hash) = Tree::commit(
+                &mut tree,
+                Context::background(),
+                Default::default(),
+                version,
+            )
+            .expect("commit");
+            server.apply(&write_log, hash, Default::default(), version);
+
+            mvt.add_root(Root {
+                version,
+                hash,
+                ..Default::default()
+            });
+        }
+        assert_eq!(vec![0, 1, 2], mvt.versions());
+        assert_eq!(Some(2), mvt.latest_version());
+
+        // Query versions out of order to make sure switching works.
+        for version in &[2, 0, 1, 2, 1, 0] {
+            let value = mvt
+                .get_at(Context::background(), *version, b"key")
+                .expect("get_at");
+            assert_eq!(Some(format!("value {}", version).into_bytes()), value);
+
+            let value = mvt
+                .get_at(Context::background(), *version, b"common")
+                .expect("get_at");
+            assert_eq!(Some(b"common".to_vec()), value);
+
+            let value = mvt
+                .get_at(Context::background(), *version, b"only 1")
+                .expect("get_at");
+            if *version == 1 {
+                assert_eq!(Some(b"one".to_vec()), value);
+            } else {
+                assert_eq!(None, value);
+            }
+        }
+
+        assert!(mvt.get_at(Context::background(), 3, b"key").is_err());
+        assert!(mvt.remove_root(0).is_some());
+        assert!(mvt.get_at(Context::background(), 0, b"key").is_err());
+    }
+}
@@ -1,12 +1,21 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, mem, rc::Rc};
 
 use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use crate::{
     common::{crypto::hash::Hash, roothash::Namespace},
-    storage::mkvs::{cache::*, marshal::*},
+    storage::mkvs::{
+        cache::*,
+        marshal::*,
+        tree::{HashAlgorithm, NodeHasher, Sha512_256Hasher},
+    },
 };
 
+/// Estimated memory used by a node held in memory in addition to its key or
+/// label and value, in bytes.
+pub const NODE_OVERHEAD: usize = mem::size_of::<NodePointer>() + mem::size_of::<NodeBox>();
+
 /// Common interface for node-like objects in the tree.
 pub trait Node {
     /// Check whether the node is clean or not.
@@ -19,6 +28,31 @@ pub trait Node {
     fn extract(&self) -> NodeRef;
 }
 
+/// Type of a storage root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum RootType {
+    /// The type of the root is not known.
+    Invalid = 0,
+    /// A root of the runtime state tree.
+    State = 1,
+    /// A root of a transaction I/O tree.
+    IO = 2,
+}
+
+impl Default for RootType {
+    fn default() -> Self {
+        RootType::Invalid
+    }
+}
+
+impl RootType {
+    /// Whether the type of the root is not known.
+    pub fn is_invalid(&self) -> bool {
+        *self == RootType::Invalid
+    }
+}
+
 /// Storage root.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Root {
@@ -27,6 +61,13 @@ pub struct Root {
     pub namespace: Namespace,
     /// Monotonically increasing version number in which the root is stored.
     pub version: u64,
+    /// Type of the root, omitted if not known.
+    #[serde(default, skip_serializing_if = "RootType::is_invalid")]
+    pub root_type: RootType,
+    /// Digest algorithm used for hashing the nodes of the tree, omitted if
+    /// it is the default SHA-512/256.
+    #[serde(default, skip_serializing_if = "HashAlgorithm::is_default")]
+    pub hash_algorithm: HashAlgorithm,
     /// Merkle root hash.
     pub hash: Hash,
 }
@@ -44,6 +85,34 @@ impl Default for NodeBox {
     }
 }
 
+impl NodeBox {
+    /// Recompute the node's hash using the given node hasher.
+    pub fn update_hash_with(&mut self, hasher: &dyn NodeHasher) {
+        match self {
+            NodeBox::Internal(ref mut n) => n.update_hash_with(hasher),
+            NodeBox::Leaf(ref mut n) => n.update_hash_with(hasher),
+        }
+    }
+
+    /// Recompute the hash of a decoded node using the given node hasher.
+    ///
+    /// Decoding always hashes leaf nodes using SHA-512/256, so for internal
+    /// nodes the hash of the embedded leaf node is recomputed first.
+    pub fn update_decoded_hash_with(&mut self, hasher: &dyn NodeHasher) {
+        if let NodeBox::Internal(ref mut n) = self {
+            let mut leaf_ptr = n.leaf_node.borrow_mut();
+            if let Some(ref leaf_node) = leaf_ptr.node {
+                let mut leaf_node = leaf_node.borrow_mut();
+                leaf_node.update_hash_with(hasher);
+                let hash = leaf_node.get_hash();
+                drop(leaf_node);
+                leaf_ptr.hash = hash;
+            }
+        }
+        self.update_hash_with(hasher);
+    }
+}
+
 impl Node for NodeBox {
     fn is_clean(&self) -> bool {
         match self {
@@ -95,6 +164,7 @@ pub struct NodePointer {
     pub clean: bool,
     pub hash: Hash,
     pub node: Option<NodeRef>,
+    pub pinned: bool,
 
     pub cache_extra: CacheExtra<NodePointer>,
 }
@@ -198,7 +268,13 @@ impl CacheItem for NodePointer {
     }
 
     fn get_cached_size(&self) -> usize {
-        1
+        match self.node {
+            Some(ref node_ref) => match *node_ref.borrow() {
+                NodeBox::Internal(ref n) => NODE_OVERHEAD + n.label.len(),
+                NodeBox::Leaf(ref n) => NODE_OVERHEAD + n.key.len() + n.value.len(),
+            },
+            None => 0,
+        }
     }
 }
 
@@ -237,19 +313,7 @@ impl Node for InternalNode {
     }
 
     fn update_hash(&mut self) {
-        let leaf_node_hash = self.leaf_node.borrow().hash;
-        let left_hash = self.left.borrow().hash;
-        let right_hash = self.right.borrow().hash;
-
-        self.hash = Hash::digest_bytes_list(&[
-            &[NodeKind::Internal as u8],
-            &self.version.marshal_binary().unwrap(),
-            &self.label_bit_length.marshal_binary().unwrap(),
-            self.label.as_ref(),
-            leaf_node_hash.as_ref(),
-            left_hash.as_ref(),
-            right_hash.as_ref(),
-        ]);
+        self.update_hash_with(&Sha512_256Hasher);
     }
 
     fn extract(&self) -> NodeRef {
@@ -269,6 +333,25 @@ impl Node for InternalNode {
     }
 }
 
+impl InternalNode {
+    /// Recompute the node's hash using the given node hasher.
+    pub fn update_hash_with(&mut self, hasher: &dyn NodeHasher) {
+        let leaf_node_hash = self.leaf_node.borrow().hash;
+        let left_hash = self.left.borrow().hash;
+        let right_hash = self.right.borrow().hash;
+
+        self.hash = hasher.digest_bytes_list(&[
+            &[NodeKind::Internal as u8],
+            &self.version.marshal_binary().unwrap(),
+            &self.label_bit_length.marshal_binary().unwrap(),
+            self.label.as_ref(),
+            leaf_node_hash.as_ref(),
+            left_hash.as_ref(),
+            right_hash.as_ref(),
+        ]);
+    }
+}
+
 impl PartialEq for InternalNode {
     fn eq(&self, other: &InternalNode) -> bool {
         if self.clean && other.clean {
@@ -306,6 +389,16 @@ impl LeafNode {
 
         return node;
     }
+
+    /// Recompute the node's hash using the given node hasher.
+    pub fn update_hash_with(&mut self, hasher: &dyn NodeHasher) {
+        self.hash = LeafNodeRef {
+            version: self.version,
+            key: &self.key,
+            value: &self.value,
+        }
+        .get_hash_with(hasher);
+    }
 }
 
 impl Node for LeafNode {
@@ -318,12 +411,7 @@ impl Node for LeafNode {
     }
 
     fn update_hash(&mut self) {
-        self.hash = Hash::digest_bytes_list(&[
-            &[NodeKind::Leaf as u8],
-            &self.version.marshal_binary().unwrap(),
-            self.key.as_ref(),
-            self.value.as_ref(),
-        ]);
+        self.update_hash_with(&Sha512_256Hasher);
     }
 
     fn extract(&self) -> NodeRef {
@@ -352,6 +440,45 @@ impl PartialEq for LeafNode {
 
 impl Eq for LeafNode {}
 
+/// A borrowed view of a serialized leaf node.
+///
+/// Decoding into a view does not copy the key and value so it can be used to
+/// inspect serialized nodes held in long-lived buffers without allocating.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeafNodeRef<'a> {
+    pub version: u64,
+    pub key: &'a [u8],
+    pub value: &'a [u8],
+}
+
+impl<'a> LeafNodeRef<'a> {
+    /// Compute the hash of the referenced leaf node.
+    pub fn get_hash(&self) -> Hash {
+        self.get_hash_with(&Sha512_256Hasher)
+    }
+
+    /// Compute the hash of the referenced leaf node using the given node hasher.
+    pub fn get_hash_with(&self, hasher: &dyn NodeHasher) -> Hash {
+        hasher.digest_bytes_list(&[
+            &[NodeKind::Leaf as u8],
+            &self.version.marshal_binary().unwrap(),
+            self.key,
+            self.value,
+        ])
+    }
+
+    /// Copy the referenced leaf node into an owned, clean leaf node.
+    pub fn to_node(&self) -> LeafNode {
+        LeafNode {
+            clean: true,
+            version: self.version,
+            hash: self.get_hash(),
+            key: self.key.to_vec(),
+            value: self.value.to_vec(),
+        }
+    }
+}
+
 // Depth determines the maximum length of the key in bits.
 //
 // max length = 2^size_of(Depth)*8
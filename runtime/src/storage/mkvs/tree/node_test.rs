@@ -27,6 +27,114 @@ fn test_serialization_leaf() {
     assert_eq!(leaf_node.value, decoded_leaf_node.value);
 }
 
+#[test]
+fn test_serialization_leaf_borrowed() {
+    let mut leaf_node = LeafNode {
+        version: 42,
+        key: b"a golden key".to_vec(),
+        value: b"value".to_vec(),
+        ..Default::default()
+    };
+    leaf_node.update_hash();
+
+    let mut marshaled = leaf_node.marshal_binary().expect("marshal");
+    marshaled.extend_from_slice(b"trailing data");
+
+    let (decoded, size) = LeafNode::unmarshal_borrowed(&marshaled).expect("unmarshal");
+    assert_eq!(marshaled.len() - b"trailing data".len(), size);
+    assert_eq!(42, decoded.version);
+    assert_eq!(leaf_node.key.as_slice(), decoded.key);
+    assert_eq!(leaf_node.value.as_slice(), decoded.value);
+    assert_eq!(leaf_node.hash, decoded.get_hash());
+    assert_eq!(leaf_node, decoded.to_node());
+
+    // Truncated input must be rejected.
+    assert!(LeafNode::unmarshal_borrowed(&marshaled[..size - 1]).is_err());
+}
+
+#[test]
+fn test_serialization_leaf_adversarial_lengths() {
+    let mut leaf_node = LeafNode {
+        key: b"key".to_vec(),
+        value: b"value".to_vec(),
+        ..Default::default()
+    };
+    leaf_node.update_hash();
+    let marshaled = leaf_node.marshal_binary().expect("marshal");
+    let key_len_pos = 1 + 8;
+    let value_len_pos = key_len_pos + 2 + leaf_node.key.len();
+
+    let assert_malformed = |data: &[u8], limits: &UnmarshalLimits| {
+        let mut decoded = NodeBox::default();
+        let err = decoded
+            .unmarshal_binary_limited(data, MarshalMode::Full, limits)
+            .expect_err("unmarshal must fail");
+        match err.downcast_ref::<Error>() {
+            Some(Error::MalformedNode) => {}
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    };
+
+    // Key length larger than the input.
+    let mut data = marshaled.clone();
+    data[key_len_pos..key_len_pos + 2].copy_from_slice(&[0xff, 0xff]);
+    assert_malformed(&data, &UnmarshalLimits::default());
+
+    // Value length larger than the input.
+    let mut data = marshaled.clone();
+    data[value_len_pos..value_len_pos + 4].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+    assert_malformed(&data, &UnmarshalLimits::default());
+
+    // Well-formed nodes exceeding the limits.
+    let limits = UnmarshalLimits {
+        max_key_size: 2,
+        ..Default::default()
+    };
+    assert_malformed(&marshaled, &limits);
+    let limits = UnmarshalLimits {
+        max_value_size: 4,
+        ..Default::default()
+    };
+    assert_malformed(&marshaled, &limits);
+
+    // Limits also apply to leaf nodes embedded in internal nodes.
+    let internal_node = InternalNode {
+        leaf_node: Rc::new(RefCell::new(NodePointer {
+            clean: true,
+            hash: leaf_node.get_hash(),
+            node: Some(Rc::new(RefCell::new(NodeBox::Leaf(leaf_node)))),
+            ..Default::default()
+        })),
+        left: NodePointer::null_ptr(),
+        right: NodePointer::null_ptr(),
+        ..Default::default()
+    };
+    let marshaled = internal_node.marshal_binary().expect("marshal");
+    assert_malformed(&marshaled, &limits);
+
+    let limits = UnmarshalLimits {
+        max_value_size: 5,
+        ..Default::default()
+    };
+    let mut decoded = NodeBox::default();
+    decoded
+        .unmarshal_binary_limited(&marshaled, MarshalMode::Full, &limits)
+        .expect("unmarshal");
+}
+
+#[test]
+fn test_serialization_key_adversarial_length() {
+    let mut key = Key::new();
+    let err = key
+        .unmarshal_binary(&[0xff, 0xff, 0x00])
+        .expect_err("unmarshal must fail");
+    match err.downcast_ref::<Error>() {
+        Some(Error::MalformedKey) => {}
+        _ => panic!("unexpected error: {:?}", err),
+    }
+    assert!(key.is_empty());
+}
+
 #[test]
 fn test_serialization_internal() {
     let mut leaf_node = LeafNode {
@@ -95,6 +203,56 @@ fn test_serialization_internal() {
     assert_eq!(false, decoded_int_node.right.borrow().node.is_some());
 }
 
+#[test]
+fn test_serialization_internal_compact() {
+    let mut leaf_node = LeafNode {
+        key: b"a golden key".to_vec(),
+        value: b"value".to_vec(),
+        ..Default::default()
+    };
+    leaf_node.update_hash();
+    let int_node = InternalNode {
+        label: b"abc".to_vec(),
+        label_bit_length: 24 as Depth,
+        leaf_node: NodePointer::from_node(NodeBox::Leaf(leaf_node)),
+        left: NodePointer::hash_ptr(Hash::digest_bytes(b"everyone move to the left")),
+        right: NodePointer::hash_ptr(Hash::digest_bytes(b"everyone move to the right")),
+        ..Default::default()
+    };
+
+    let full = int_node
+        .marshal_binary_mode(MarshalMode::Full)
+        .expect("marshal");
+    let compact = int_node
+        .marshal_binary_mode(MarshalMode::Compact)
+        .expect("marshal");
+    assert_eq!(full.len(), compact.len() + 2 * Hash::len());
+    assert_eq!(&full[..compact.len()], compact.as_slice());
+
+    let mut decoded_int_node = InternalNode {
+        ..Default::default()
+    };
+    let size = decoded_int_node
+        .unmarshal_binary_mode(&compact, MarshalMode::Compact)
+        .expect("unmarshal");
+    assert_eq!(compact.len(), size);
+    assert_eq!(int_node.label, decoded_int_node.label);
+    assert_eq!(
+        int_node.leaf_node.borrow().hash,
+        decoded_int_node.leaf_node.borrow().hash
+    );
+
+    // Compact encoding must be rejected when a full encoding is expected.
+    let mut decoded_int_node = InternalNode {
+        ..Default::default()
+    };
+    assert!(decoded_int_node
+        .unmarshal_binary_mode(&compact, MarshalMode::Full)
+        .is_err());
+    let mut decoded_node = NodeBox::default();
+    assert!(decoded_node.unmarshal_binary(&compact).is_err());
+}
+
 #[test]
 fn test_hash_leaf() {
     let mut leaf_node = LeafNode {
@@ -0,0 +1,349 @@
+//! In-memory overlay on top of a tree.
+use std::{
+    collections::{btree_map, BTreeMap, BTreeSet},
+    iter::Peekable,
+};
+
+use anyhow::{Error, Result};
+use io_context::Context;
+
+use crate::{
+    common::{crypto::hash::Hash, roothash::Namespace},
+    storage::mkvs::{FallibleMKVS, LogEntry, MKVSIterator, Prefix, WriteLog},
+};
+
+/// A tree overlay which buffers all writes in memory.
+///
+/// The underlying tree is only read from until the overlay is flushed, so
+/// the overlay can be used to speculatively execute operations and then
+/// either discard their effects or flush them into the underlying tree.
+pub struct OverlayTree<T: FallibleMKVS> {
+    inner: T,
+    overlay: BTreeMap<Vec<u8>, Vec<u8>>,
+    dirty: BTreeSet<Vec<u8>>,
+}
+
+impl<T: FallibleMKVS> OverlayTree<T> {
+    /// Create a new overlay on top of the given tree.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            overlay: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+        }
+    }
+
+    /// Return a reference to the underlying tree.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Return the underlying tree, discarding any pending changes.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Fetch entry with given key.
+    pub fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        if let Some(value) = self.overlay.get(key) {
+            return Ok(Some(value.clone()));
+        }
+        if self.dirty.contains(key) {
+            return Ok(None);
+        }
+        self.inner.get(ctx, key)
+    }
+
+    /// Update entry with given key, returning the previous value if any.
+    pub fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let previous = self.get(ctx, key)?;
+        self.overlay.insert(key.to_vec(), value.to_vec());
+        self.dirty.insert(key.to_vec());
+        Ok(previous)
+    }
+
+    /// Remove entry with given key, returning the previous value if any.
+    pub fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let previous = self.get(ctx, key)?;
+        self.overlay.remove(key);
+        self.dirty.insert(key.to_vec());
+        Ok(previous)
+    }
+
+    /// Returns an iterator over the overlay merged with the underlying tree.
+    pub fn iter(&self, ctx: Context) -> OverlayTreeIterator<'_, T> {
+        OverlayTreeIterator::new(ctx, self)
+    }
+
+    /// Discard all pending changes.
+    pub fn discard(&mut self) {
+        self.overlay.clear();
+        self.dirty.clear();
+    }
+
+    /// Flush all pending changes into the underlying tree, without
+    /// committing it, and return the flushed changes.
+    ///
+    /// Removals of keys that were not present in the underlying tree are not
+    /// included in the returned write log.
+    pub fn flush(&mut self, ctx: Context) -> Result<WriteLog> {
+        let ctx = ctx.freeze();
+        let mut write_log = Vec::new();
+        for key in &self.dirty {
+            match self.overlay.get(key) {
+                Some(value) => {
+                    self.inner.insert(Context::create_child(&ctx), key, value)?;
+                    write_log.push(LogEntry::new(key, value));
+                }
+                None => {
+                    if self
+                        .inner
+                        .remove(Context::create_child(&ctx), key)?
+                        .is_some()
+                    {
+                        write_log.push(LogEntry {
+                            key: key.clone(),
+                            value: None,
+                        });
+                    }
+                }
+            }
+        }
+        self.discard();
+
+        Ok(write_log)
+    }
+}
+
+impl<T: FallibleMKVS> FallibleMKVS for OverlayTree<T> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        OverlayTree::get(self, ctx, key)
+    }
+
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        self.dirty.contains(key) || self.inner.cache_contains_key(ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        OverlayTree::insert(self, ctx, key, value)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        OverlayTree::remove(self, ctx, key)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
+        self.inner.prefetch_prefixes(ctx, prefixes, limit)
+    }
+
+    fn iter(&self, ctx: Context) -> Box<dyn MKVSIterator + '_> {
+        Box::new(OverlayTree::iter(self, ctx))
+    }
+
+    /// Flush all pending changes into the underlying tree and commit it.
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        let ctx = ctx.freeze();
+        self.flush(Context::create_child(&ctx))?;
+        self.inner
+            .commit(Context::create_child(&ctx), namespace, version)
+    }
+}
+
+/// Iterator over an overlay merged with its underlying tree.
+pub struct OverlayTreeIterator<'tree, T: FallibleMKVS> {
+    tree: &'tree OverlayTree<T>,
+    inner: Box<dyn MKVSIterator + 'tree>,
+    overlay: Peekable<btree_map::Range<'tree, Vec<u8>, Vec<u8>>>,
+    key: Option<Vec<u8>>,
+    value: Option<Vec<u8>>,
+}
+
+impl<'tree, T: FallibleMKVS> OverlayTreeIterator<'tree, T> {
+    fn new(ctx: Context, tree: &'tree OverlayTree<T>) -> Self {
+        Self {
+            tree,
+            inner: tree.inner.iter(ctx),
+            overlay: tree.overlay.range(Vec::new()..).peekable(),
+            key: None,
+            value: None,
+        }
+    }
+
+    /// Position the iterator at the smaller of the current overlay and
+    /// underlying tree keys.
+    fn update(&mut self) {
+        // Skip over keys in the underlying tree that were changed in the overlay.
+        let dirty = &self.tree.dirty;
+        while self.inner.key().map_or(false, |key| dirty.contains(key)) {
+            self.inner.next();
+        }
+
+        if self.inner.error().is_some() {
+            self.key = None;
+            self.value = None;
+            return;
+        }
+
+        let use_overlay = match (self.inner.key(), self.overlay.peek()) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(inner_key), Some((overlay_key, _))) => *overlay_key < inner_key,
+        };
+        if use_overlay {
+            let (key, value) = self.overlay.peek().expect("overlay has next item");
+            self.key = Some(key.to_vec());
+            self.value = Some(value.to_vec());
+        } else {
+            self.key = self.inner.key().cloned();
+            self.value = self.inner.value().cloned();
+        }
+    }
+
+    fn advance(&mut self) {
+        let in_overlay = match (self.overlay.peek(), self.key.as_ref()) {
+            (Some((overlay_key, _)), Some(key)) => *overlay_key == key,
+            _ => false,
+        };
+        if in_overlay {
+            self.overlay.next();
+        } else {
+            self.inner.next();
+        }
+        self.update();
+    }
+}
+
+impl<'tree, T: FallibleMKVS> MKVSIterator for OverlayTreeIterator<'tree, T> {
+    fn set_prefetch(&mut self, prefetch: usize) {
+        self.inner.set_prefetch(prefetch)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.key.is_some()
+    }
+
+    fn error(&self) -> &Option<Error> {
+        self.inner.error()
+    }
+
+    fn rewind(&mut self) {
+        self.seek(&[])
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        self.inner.seek(key);
+        self.overlay = self.tree.overlay.range(key.to_vec()..).peekable();
+        self.update();
+    }
+
+    fn key(&self) -> Option<&Vec<u8>> {
+        self.key.as_ref()
+    }
+
+    fn value(&self) -> Option<&Vec<u8>> {
+        self.value.as_ref()
+    }
+}
+
+impl<'tree, T: FallibleMKVS> Iterator for OverlayTreeIterator<'tree, T> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.is_valid() {
+            return None;
+        }
+
+        let key = self.key.as_ref().expect("iterator is valid").clone();
+        let value = self.value.as_ref().expect("iterator is valid").clone();
+        self.advance();
+
+        Some((key, value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::mkvs::{sync::NoopReadSyncer, LogEntryKind, Tree};
+
+    #[test]
+    fn test_overlay() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for key in &[b"a", b"c", b"e", b"g"] {
+            tree.insert(Context::background(), *key, b"base")
+                .expect("insert");
+        }
+
+        let mut overlay = OverlayTree::new(&mut tree);
+        overlay
+            .insert(Context::background(), b"b", b"overlay")
+            .expect("insert");
+        let previous = overlay
+            .insert(Context::background(), b"c", b"overlay")
+            .expect("insert");
+        assert_eq!(Some(b"base".to_vec()), previous);
+        overlay.remove(Context::background(), b"e").expect("remove");
+        overlay.remove(Context::background(), b"x").expect("remove");
+
+        assert_eq!(
+            Some(b"overlay".to_vec()),
+            overlay.get(Context::background(), b"c").expect("get")
+        );
+        assert_eq!(None, overlay.get(Context::background(), b"e").expect("get"));
+        assert_eq!(
+            Some(b"base".to_vec()),
+            overlay
+                .inner()
+                .get(Context::background(), b"e")
+                .expect("get")
+        );
+
+        // Iteration merges the overlay with the underlying tree.
+        let mut it = overlay.iter(Context::background());
+        it.rewind();
+        let items: Vec<_> = it.collect();
+        assert_eq!(
+            vec![
+                (b"a".to_vec(), b"base".to_vec()),
+                (b"b".to_vec(), b"overlay".to_vec()),
+                (b"c".to_vec(), b"overlay".to_vec()),
+                (b"g".to_vec(), b"base".to_vec()),
+            ],
+            items
+        );
+        let mut it = overlay.iter(Context::background());
+        it.seek(b"bb");
+        assert_eq!(Some(&b"c".to_vec()), it.key());
+        drop(it);
+
+        // Discarded changes do not reach the underlying tree.
+        overlay.discard();
+        assert_eq!(
+            Some(b"base".to_vec()),
+            overlay.get(Context::background(), b"e").expect("get")
+        );
+
+        // Flushed changes do.
+        overlay
+            .insert(Context::background(), b"b", b"overlay")
+            .expect("insert");
+        overlay.remove(Context::background(), b"e").expect("remove");
+        overlay.remove(Context::background(), b"x").expect("remove");
+        let write_log = overlay.flush(Context::background()).expect("flush");
+        assert_eq!(2, write_log.len());
+        assert_eq!(LogEntryKind::Insert, write_log[0].kind());
+        assert_eq!(LogEntryKind::Delete, write_log[1].kind());
+        drop(overlay);
+
+        assert_eq!(
+            Some(b"overlay".to_vec()),
+            tree.get(Context::background(), b"b").expect("get")
+        );
+        assert_eq!(None, tree.get(Context::background(), b"e").expect("get"));
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::storage::mkvs::{cache::*, tree::*};
+
+use super::iterator::FetcherSyncIterate;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PinMode {
+    /// Pin nodes, fetching any nodes that are not available locally.
+    Pin,
+    /// Pin nodes that are available locally.
+    Repin,
+    /// Unpin nodes that are available locally.
+    Unpin,
+}
+
+/// State of a pinning traversal.
+struct PinState<'a> {
+    prefix: &'a Key,
+    mode: PinMode,
+    unpinned: Vec<NodePtrRef>,
+}
+
+impl Tree {
+    /// Pin the subtree containing all keys with the given prefix in the cache.
+    ///
+    /// Any nodes of the subtree that are not available locally are fetched
+    /// from the read syncer. Pinned nodes, together with the nodes on the path
+    /// to them, are never evicted and do not count against the cache capacity.
+    /// Nodes added to the subtree later are pinned when they are committed.
+    pub fn pin_prefix(&mut self, ctx: Context, prefix: &[u8]) -> Result<()> {
+        let ctx = ctx.freeze();
+        let prefix = prefix.to_vec();
+        self.pin_subtree(&ctx, &prefix, PinMode::Pin)?;
+        self.pinned_prefixes.insert(prefix);
+
+        Ok(())
+    }
+
+    /// Unpin a subtree previously pinned with `pin_prefix`, making its nodes
+    /// eligible for eviction again.
+    pub fn unpin_prefix(&mut self, ctx: Context, prefix: &[u8]) -> Result<()> {
+        let ctx = ctx.freeze();
+        let prefix = prefix.to_vec();
+        if !self.pinned_prefixes.remove(&prefix) {
+            return Ok(());
+        }
+        let unpinned = self.pin_subtree(&ctx, &prefix, PinMode::Unpin)?;
+
+        // Nodes may be shared with other pinned subtrees, so pin those again
+        // before returning any nodes to the cache.
+        self.repin_prefixes(&ctx)?;
+        for ptr in unpinned {
+            let (pinned, clean) = {
+                let ptr = ptr.borrow();
+                (ptr.pinned, ptr.clean)
+            };
+            if !pinned && clean {
+                self.cache.borrow_mut().commit_node(ptr);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pin all locally available nodes of all pinned subtrees.
+    pub(super) fn repin_prefixes(&self, ctx: &Arc<Context>) -> Result<()> {
+        for prefix in &self.pinned_prefixes {
+            self.pin_subtree(ctx, prefix, PinMode::Repin)?;
+        }
+
+        Ok(())
+    }
+
+    fn pin_subtree(
+        &self,
+        ctx: &Arc<Context>,
+        prefix: &Key,
+        mode: PinMode,
+    ) -> Result<Vec<NodePtrRef>> {
+        let pending_root = self.cache.borrow().get_pending_root();
+        let mut state = PinState {
+            prefix,
+            mode,
+            unpinned: Vec::new(),
+        };
+        self._pin(ctx, &mut state, pending_root, Key::new(), 0)?;
+
+        Ok(state.unpinned)
+    }
+
+    fn _pin(
+        &self,
+        ctx: &Arc<Context>,
+        state: &mut PinState,
+        ptr: NodePtrRef,
+        path: Key,
+        bit_depth: Depth,
+    ) -> Result<()> {
+        let node_ref = match state.mode {
+            PinMode::Pin => self.cache.borrow_mut().deref_node_ptr(
+                ctx,
+                ptr.clone(),
+                Some(FetcherSyncIterate::new(&path, &path, bit_depth, 0)),
+            )?,
+            PinMode::Repin | PinMode::Unpin => ptr.borrow().node.clone(),
+        };
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => Ok(()),
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                let (leaf_node, left, right, path, bit_length) = match *node_ref.borrow() {
+                    NodeBox::Internal(ref n) => (
+                        n.leaf_node.clone(),
+                        n.left.clone(),
+                        n.right.clone(),
+                        path.merge(bit_depth, &n.label, n.label_bit_length),
+                        bit_depth + n.label_bit_length,
+                    ),
+                    _ => unreachable!("node kind is internal node"),
+                };
+
+                let prefix_bit_length = state.prefix.bit_length();
+                let common = path.common_prefix_len(bit_length, state.prefix, prefix_bit_length);
+                if common >= prefix_bit_length {
+                    // The whole subtree is covered by the prefix.
+                    self.set_pinned(state, ptr);
+                    self._pin(ctx, state, leaf_node, path.clone(), bit_length)?;
+                    self._pin(
+                        ctx,
+                        state,
+                        left,
+                        path.append_bit(bit_length, false),
+                        bit_length,
+                    )?;
+                    self._pin(
+                        ctx,
+                        state,
+                        right,
+                        path.append_bit(bit_length, true),
+                        bit_length,
+                    )
+                } else if common == bit_length {
+                    // The node is on the path to the subtree. Its leaf node is
+                    // pinned as well, as an internal node whose leaf node has
+                    // been evicted is refetched together with its subtree.
+                    self.set_pinned(state, ptr);
+                    self.set_pinned(state, leaf_node);
+                    if state.prefix.get_bit(bit_length) {
+                        self._pin(
+                            ctx,
+                            state,
+                            right,
+                            path.append_bit(bit_length, true),
+                            bit_length,
+                        )
+                    } else {
+                        self._pin(
+                            ctx,
+                            state,
+                            left,
+                            path.append_bit(bit_length, false),
+                            bit_length,
+                        )
+                    }
+                } else {
+                    // The prefix diverges from this subtree.
+                    Ok(())
+                }
+            }
+            NodeKind::Leaf => {
+                let node_ref = node_ref.unwrap();
+                if noderef_as!(node_ref, Leaf).key.starts_with(state.prefix) {
+                    self.set_pinned(state, ptr);
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn set_pinned(&self, state: &mut PinState, ptr: NodePtrRef) {
+        match state.mode {
+            PinMode::Pin | PinMode::Repin => self.cache.borrow_mut().pin_node(ptr),
+            PinMode::Unpin => {
+                self.cache.borrow_mut().unpin_node(ptr.clone());
+                state.unpinned.push(ptr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::{
+        interop::{Driver, ProtocolServer},
+        sync::*,
+    };
+
+    #[test]
+    fn test_pin_prefix() {
+        let server = ProtocolServer::new();
+
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..100 {
+            tree.insert(
+                Context::background(),
+                format!("hot {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .unwrap();
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .unwrap();
+        }
+        let (write_log, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        server.apply(&write_log, hash, Default::default(), 0);
+
+        let stats = StatsCollector::new(server.read_sync());
+        let mut remote_tree = Tree::make()
+            .with_capacity(50, 200)
+            .with_root(Root {
+                hash,
+                ..Default::default()
+            })
+            .new(Box::new(stats));
+        remote_tree
+            .pin_prefix(Context::background(), b"hot")
+            .expect("pin_prefix");
+
+        // Put the cache under pressure.
+        for i in 0..100 {
+            remote_tree
+                .get(Context::background(), format!("key {}", i).as_bytes())
+                .expect("get");
+        }
+
+        // Pinned keys should not require any further syncs.
+        let sync_count = |tree: &Tree| {
+            let cache = tree.cache.borrow();
+            let stats = cache
+                .get_read_syncer()
+                .as_any()
+                .downcast_ref::<StatsCollector>()
+                .expect("stats");
+            stats.sync_get_count + stats.sync_iterate_count
+        };
+        let before = sync_count(&remote_tree);
+        for i in 0..100 {
+            let value = remote_tree
+                .get(Context::background(), format!("hot {}", i).as_bytes())
+                .expect("get");
+            assert_eq!(Some(format!("value {}", i).into_bytes()), value);
+        }
+        assert_eq!(before, sync_count(&remote_tree));
+
+        // Unpinned keys are evicted again.
+        remote_tree
+            .unpin_prefix(Context::background(), b"hot")
+            .expect("unpin_prefix");
+        for i in 0..100 {
+            remote_tree
+                .get(Context::background(), format!("key {}", i).as_bytes())
+                .expect("get");
+        }
+        let before = sync_count(&remote_tree);
+        remote_tree
+            .get(Context::background(), b"hot 0")
+            .expect("get");
+        assert!(sync_count(&remote_tree) > before);
+    }
+}
@@ -28,6 +28,7 @@ impl<'a> ReadSyncFetcher for FetcherSyncGetPrefixes<'a> {
                 tree: TreeID {
                     root,
                     position: ptr.borrow().hash,
+                    ..Default::default()
                 },
                 prefixes: self.prefixes.clone(),
                 limit: self.limit,
@@ -39,12 +40,21 @@ impl<'a> ReadSyncFetcher for FetcherSyncGetPrefixes<'a> {
 
 impl Tree {
     /// Populate the in-memory tree with nodes for keys starting with given prefixes.
+    ///
+    /// At most `limit` keys are fetched in a single `sync_get_prefixes` request,
+    /// so this can be used to warm the cache before executing a batch of
+    /// transactions with a known access pattern. Nothing is fetched if no
+    /// prefixes are given.
     pub fn prefetch_prefixes(
         &self,
         ctx: Context,
         prefixes: &Vec<Prefix>,
         limit: u16,
     ) -> Result<()> {
+        if prefixes.is_empty() {
+            return Ok(());
+        }
+
         let ctx = ctx.freeze();
         let pending_root = self.cache.borrow().get_pending_root();
         self.cache.borrow_mut().remote_sync(
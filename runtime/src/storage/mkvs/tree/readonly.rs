@@ -0,0 +1,118 @@
+use anyhow::Result;
+use io_context::Context;
+
+use crate::storage::mkvs::{
+    cache::CacheStats,
+    sync::{Proof, ReadSync},
+    tree::*,
+    Prefix,
+};
+
+/// A tree that can only be queried.
+///
+/// Read-only trees do not have any methods for inserting or removing keys
+/// or for committing, so code holding one can not modify state.
+pub struct ReadOnlyTree {
+    tree: Tree,
+}
+
+impl Options {
+    /// Commit the options set so far into a newly constructed read-only
+    /// tree instance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the options are inconsistent, see `try_new`.
+    pub fn new_readonly(self, read_syncer: Box<dyn ReadSync>) -> ReadOnlyTree {
+        ReadOnlyTree {
+            tree: self.new(read_syncer),
+        }
+    }
+}
+
+impl Tree {
+    /// Open an existing root as a read-only tree.
+    pub fn open_readonly(read_syncer: Box<dyn ReadSync>, root: Root) -> ReadOnlyTree {
+        Tree::make().with_root(root).new_readonly(read_syncer)
+    }
+}
+
+impl ReadOnlyTree {
+    /// Get an existing key.
+    pub fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.tree.get(ctx, key)
+    }
+
+    /// Get multiple existing keys, fetching all of them at once.
+    pub fn get_multi(&self, ctx: Context, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.tree.get_multi(ctx, keys)
+    }
+
+    /// Check if the key exists in the local cache.
+    pub fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        self.tree.cache_contains_key(ctx, key)
+    }
+
+    /// Get an existing key together with a proof of its value.
+    pub fn get_with_proof(&self, ctx: Context, key: &[u8]) -> Result<(Option<Vec<u8>>, Proof)> {
+        self.tree.get_with_proof(ctx, key)
+    }
+
+    /// Get multiple existing keys together with a single proof of their
+    /// values.
+    pub fn get_multi_with_proof(
+        &self,
+        ctx: Context,
+        keys: &[&[u8]],
+    ) -> Result<(Vec<Option<Vec<u8>>>, Proof)> {
+        self.tree.get_multi_with_proof(ctx, keys)
+    }
+
+    /// Populate the in-memory tree with nodes for keys starting with given prefixes.
+    pub fn prefetch_prefixes(
+        &self,
+        ctx: Context,
+        prefixes: &Vec<Prefix>,
+        limit: u16,
+    ) -> Result<()> {
+        self.tree.prefetch_prefixes(ctx, prefixes, limit)
+    }
+
+    /// Returns an iterator over the tree.
+    pub fn iter(&self, ctx: Context) -> TreeIterator {
+        self.tree.iter(ctx)
+    }
+
+    /// Returns an iterator over the tree, positioned either at the given key
+    /// or at the next larger key.
+    pub fn iter_from(&self, ctx: Context, key: &[u8]) -> TreeIterator {
+        self.tree.iter_from(ctx, key)
+    }
+
+    /// Returns an iterator over all keys starting with the given prefix.
+    pub fn prefix_iter(&self, ctx: Context, prefix: &[u8]) -> PrefixIterator {
+        self.tree.prefix_iter(ctx, prefix)
+    }
+
+    /// Returns key/value pairs with keys in the range `[start, end)` in key
+    /// order, see `Tree::get_range`.
+    pub fn get_range(
+        &self,
+        ctx: Context,
+        start: &[u8],
+        end: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.tree.get_range(ctx, start, end, limit)
+    }
+
+    /// Return the type of the tree root.
+    pub fn root_type(&self) -> RootType {
+        self.tree.root_type()
+    }
+
+    /// Return statistics of the underlying in-memory cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.tree.cache_stats()
+    }
+}
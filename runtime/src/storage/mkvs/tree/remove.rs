@@ -1,16 +1,20 @@
-use std::sync::Arc;
+use std::{cmp::Ordering, sync::Arc};
 
 use anyhow::Result;
 use io_context::Context;
 
 use crate::storage::mkvs::{cache::*, tree::*};
 
-use super::lookup::FetcherSyncGet;
+use super::{iterator::FetcherSyncIterate, lookup::FetcherSyncGet};
 
 impl Tree {
     /// Remove a key from the tree and return true if the tree was modified.
     pub fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let ctx = ctx.freeze();
+        self.remove_one(&ctx, key)
+    }
+
+    pub(super) fn remove_one(&mut self, ctx: &Arc<Context>, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let boxed_key = key.to_vec();
         let pending_root = self.cache.borrow().get_pending_root();
 
@@ -22,15 +26,24 @@ impl Tree {
         // Remember where the path from root to target node ends (will end).
         self.cache.borrow_mut().mark_position();
 
-        let (new_root, changed, old_val) = self._remove(&ctx, pending_root, 0, &boxed_key, 0)?;
-        match self.pending_write_log.get_mut(&boxed_key) {
+        let (new_root, _, old_val) = self._remove(ctx, pending_root, 0, &boxed_key, 0)?;
+        self.cache.borrow_mut().set_pending_root(new_root);
+        self.record_remove(key, &old_val);
+
+        Ok(old_val)
+    }
+
+    /// Record a removal in the pending write log and the undo log.
+    fn record_remove(&mut self, key: &[u8], old_val: &Option<Value>) {
+        match self.pending_write_log.get_mut(key) {
             None => {
                 self.pending_write_log.insert(
-                    boxed_key.clone(),
+                    key.to_vec(),
                     PendingLogEntry {
-                        key: boxed_key,
+                        key: key.to_vec(),
                         value: None,
-                        existed: changed,
+                        existed: old_val.is_some(),
+                        previous: old_val.clone(),
                     },
                 );
             }
@@ -38,9 +51,202 @@ impl Tree {
                 entry.value = None;
             }
         };
+        self.record_undo(key, old_val);
+    }
+
+    /// Remove all keys in the range `[start, end)` from the tree and return
+    /// the number of removed keys. If `end` is `None` the range is unbounded
+    /// from above.
+    ///
+    /// The range is removed in a single walk of the tree. Subtrees whose keys
+    /// all lie within the range are pruned as a whole, while the keys they
+    /// contain are recorded in the write log as they are removed.
+    pub fn remove_range(
+        &mut self,
+        ctx: Context,
+        start: &[u8],
+        end: Option<&[u8]>,
+    ) -> Result<usize> {
+        let ctx = ctx.freeze();
+        let pending_root = self.cache.borrow().get_pending_root();
+        let start = start.to_vec();
+        let end = end.map(|end| end.to_vec());
+
+        // Remember where the paths from root to target nodes end (will end).
+        self.cache.borrow_mut().mark_position();
+
+        let mut removed = Vec::new();
+        let new_root = self._remove_range(
+            &ctx,
+            pending_root,
+            0,
+            &Key::new(),
+            &start,
+            end.as_ref(),
+            &mut removed,
+        )?;
         self.cache.borrow_mut().set_pending_root(new_root);
 
-        Ok(old_val)
+        Ok(removed.len())
+    }
+
+    fn _remove_range(
+        &mut self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        bit_depth: Depth,
+        path: &Key,
+        start: &Key,
+        end: Option<&Key>,
+        removed: &mut Vec<Key>,
+    ) -> Result<NodePtrRef> {
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr.clone(),
+            Some(FetcherSyncIterate::new(start, path, bit_depth, 0)),
+        )?;
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => Ok(ptr),
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                let bit_length = bit_depth + noderef_as!(node_ref, Internal).label_bit_length;
+                let path = path.merge(
+                    bit_depth,
+                    &noderef_as!(node_ref, Internal).label,
+                    noderef_as!(node_ref, Internal).label_bit_length,
+                );
+
+                let start_order = compare_path(&path, bit_length, start);
+                let end_order = end.map(|end| compare_path(&path, bit_length, end));
+                if start_order == Ordering::Less || end_order == Some(Ordering::Greater) {
+                    // The subtree lies outside the range.
+                    return Ok(ptr);
+                }
+                if start_order == Ordering::Greater
+                    && end_order.unwrap_or(Ordering::Less) == Ordering::Less
+                {
+                    // The subtree lies within the range, prune it as a whole.
+                    self._remove_subtree(ctx, ptr, &path, bit_depth, start, removed)?;
+                    return Ok(NodePointer::null_ptr());
+                }
+
+                let count = removed.len();
+                let leaf_node = noderef_as!(node_ref, Internal).leaf_node.clone();
+                let left = noderef_as!(node_ref, Internal).left.clone();
+                let right = noderef_as!(node_ref, Internal).right.clone();
+                let leaf_node =
+                    self._remove_range(ctx, leaf_node, bit_length, &path, start, end, removed)?;
+                let left = self._remove_range(ctx, left, bit_length, &path, start, end, removed)?;
+                let right =
+                    self._remove_range(ctx, right, bit_length, &path, start, end, removed)?;
+
+                let (remaining_left, remaining_right);
+                if let NodeBox::Internal(ref mut n) = *node_ref.borrow_mut() {
+                    n.leaf_node = leaf_node;
+                    n.left = left;
+                    n.right = right;
+                    if removed.len() == count {
+                        return Ok(ptr);
+                    }
+
+                    remaining_left = self.cache.borrow_mut().deref_node_ptr(
+                        ctx,
+                        n.left.clone(),
+                        Some(FetcherSyncIterate::new(start, &path, bit_length, 0)),
+                    )?;
+                    remaining_right = self.cache.borrow_mut().deref_node_ptr(
+                        ctx,
+                        n.right.clone(),
+                        Some(FetcherSyncIterate::new(start, &path, bit_length, 0)),
+                    )?;
+                } else {
+                    unreachable!("node kind is Internal");
+                }
+                let remaining_leaf = noderef_as!(node_ref, Internal)
+                    .leaf_node
+                    .borrow()
+                    .node
+                    .clone();
+
+                if remaining_leaf.is_none() && remaining_left.is_none() && remaining_right.is_none()
+                {
+                    // Nothing remains, remove the node.
+                    self.cache.borrow_mut().remove_node(ptr);
+                    return Ok(NodePointer::null_ptr());
+                }
+
+                let (new_ptr, _) = self.collapse(
+                    ptr,
+                    node_ref,
+                    remaining_leaf,
+                    remaining_left,
+                    remaining_right,
+                    true,
+                );
+                Ok(new_ptr)
+            }
+            NodeKind::Leaf => {
+                let node_ref = node_ref.unwrap();
+                let key = noderef_as!(node_ref, Leaf).key.clone();
+                if key < *start || end.map_or(false, |end| key >= *end) {
+                    return Ok(ptr);
+                }
+
+                let old_val = Some(noderef_as!(node_ref, Leaf).value.clone());
+                self.cache.borrow_mut().remove_node(ptr);
+                self.record_remove(&key, &old_val);
+                removed.push(key);
+                Ok(NodePointer::null_ptr())
+            }
+        }
+    }
+
+    /// Remove all nodes of a subtree, recording the removal of its keys.
+    fn _remove_subtree(
+        &mut self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        path: &Key,
+        bit_depth: Depth,
+        start: &Key,
+        removed: &mut Vec<Key>,
+    ) -> Result<()> {
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr.clone(),
+            Some(FetcherSyncIterate::new(start, path, bit_depth, 0)),
+        )?;
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => {}
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                let bit_length = bit_depth + noderef_as!(node_ref, Internal).label_bit_length;
+                let path = path.merge(
+                    bit_depth,
+                    &noderef_as!(node_ref, Internal).label,
+                    noderef_as!(node_ref, Internal).label_bit_length,
+                );
+                let leaf_node = noderef_as!(node_ref, Internal).leaf_node.clone();
+                let left = noderef_as!(node_ref, Internal).left.clone();
+                let right = noderef_as!(node_ref, Internal).right.clone();
+                self._remove_subtree(ctx, leaf_node, &path, bit_length, start, removed)?;
+                self._remove_subtree(ctx, left, &path, bit_length, start, removed)?;
+                self._remove_subtree(ctx, right, &path, bit_length, start, removed)?;
+                self.cache.borrow_mut().remove_node(ptr);
+            }
+            NodeKind::Leaf => {
+                let node_ref = node_ref.unwrap();
+                let key = noderef_as!(node_ref, Leaf).key.clone();
+                let old_val = Some(noderef_as!(node_ref, Leaf).value.clone());
+                self.cache.borrow_mut().remove_node(ptr);
+                self.record_remove(&key, &old_val);
+                removed.push(key);
+            }
+        }
+
+        Ok(())
     }
 
     fn _remove(
@@ -54,7 +260,7 @@ impl Tree {
         let node_ref = self.cache.borrow_mut().deref_node_ptr(
             ctx,
             ptr.clone(),
-            Some(FetcherSyncGet::new(key, true)),
+            Some(FetcherSyncGet::new(key, bit_depth, true)),
         )?;
 
         match classify_noderef!(?node_ref) {
@@ -106,102 +312,26 @@ impl Tree {
                     remaining_left = self.cache.borrow_mut().deref_node_ptr(
                         ctx,
                         n.left.clone(),
-                        Some(FetcherSyncGet::new(key, true)),
+                        Some(FetcherSyncGet::new(key, bit_length, true)),
                     )?;
                     remaining_right = self.cache.borrow_mut().deref_node_ptr(
                         ctx,
                         n.right.clone(),
-                        Some(FetcherSyncGet::new(key, true)),
+                        Some(FetcherSyncGet::new(key, bit_length, true)),
                     )?;
                 } else {
                     unreachable!("node kind is Internal");
                 }
 
-                // If exactly one child including LeafNode remains, collapse it.
-                match remaining_leaf {
-                    Some(_) => match remaining_left {
-                        Some(_) => (),
-                        None => match remaining_right {
-                            None => {
-                                let nd_leaf = noderef_as!(node_ref, Internal).leaf_node.clone();
-                                noderef_as_mut!(node_ref, Internal).leaf_node =
-                                    NodePointer::null_ptr();
-                                self.cache.borrow_mut().remove_node(ptr.clone());
-                                return Ok((nd_leaf, true, old_val));
-                            }
-                            Some(_) => (),
-                        },
-                    },
-                    None => {
-                        let mut nd_child: Option<NodeRef> = None;
-                        let mut node_ptr: NodePtrRef = NodePointer::null_ptr();
-                        let mut both_children = true;
-                        match remaining_left {
-                            Some(_) => match remaining_right {
-                                None => {
-                                    node_ptr = noderef_as!(node_ref, Internal).left.clone();
-                                    noderef_as_mut!(node_ref, Internal).left =
-                                        NodePointer::null_ptr();
-                                    nd_child = remaining_left;
-                                    both_children = false;
-                                }
-                                Some(_) => (),
-                            },
-                            None => match remaining_right {
-                                None => (),
-                                Some(_) => {
-                                    node_ptr = noderef_as!(node_ref, Internal).right.clone();
-                                    noderef_as_mut!(node_ref, Internal).right =
-                                        NodePointer::null_ptr();
-                                    nd_child = remaining_right;
-                                    both_children = false;
-                                }
-                            },
-                        }
-
-                        if !both_children {
-                            // If child is an internal node, also fix the label.
-                            match nd_child {
-                                Some(_) => match classify_noderef!(?nd_child) {
-                                    NodeKind::Internal => {
-                                        if let NodeBox::Internal(ref mut inode) =
-                                            *nd_child.unwrap().borrow_mut()
-                                        {
-                                            inode.label =
-                                                noderef_as!(node_ref, Internal).label.merge(
-                                                    noderef_as!(node_ref, Internal)
-                                                        .label_bit_length,
-                                                    &inode.label,
-                                                    inode.label_bit_length,
-                                                );
-                                            inode.label_bit_length +=
-                                                noderef_as!(node_ref, Internal).label_bit_length;
-                                            inode.clean = false;
-                                            node_ptr.borrow_mut().clean = false;
-                                        }
-                                    }
-                                    _ => (),
-                                },
-                                _ => (),
-                            }
-
-                            self.cache.borrow_mut().remove_node(ptr.clone());
-                            return Ok((node_ptr, true, old_val));
-                        }
-                    }
-                };
-
-                // Two or more children including leaf_node remain, just mark dirty bit.
-                if changed {
-                    noderef_as_mut!(node_ref, Internal).clean = false;
-                    ptr.borrow_mut().clean = false;
-                    // No longer eligible for eviction as it is dirty.
-                    self.cache
-                        .borrow_mut()
-                        .rollback_node(ptr.clone(), NodeKind::Internal);
-                }
-
-                return Ok((ptr.clone(), changed, old_val));
+                let (new_ptr, changed) = self.collapse(
+                    ptr,
+                    node_ref,
+                    remaining_leaf,
+                    remaining_left,
+                    remaining_right,
+                    changed,
+                );
+                return Ok((new_ptr, changed, old_val));
             }
             NodeKind::Leaf => {
                 // Remove from leaf node.
@@ -216,4 +346,136 @@ impl Tree {
             }
         };
     }
+
+    /// Collapse an internal node after the removal of some of its children,
+    /// if at most one child including the leaf node remains. Otherwise, just
+    /// mark the node dirty if it was changed.
+    fn collapse(
+        &mut self,
+        ptr: NodePtrRef,
+        node_ref: NodeRef,
+        remaining_leaf: Option<NodeRef>,
+        remaining_left: Option<NodeRef>,
+        remaining_right: Option<NodeRef>,
+        changed: bool,
+    ) -> (NodePtrRef, bool) {
+        // If exactly one child including LeafNode remains, collapse it.
+        match remaining_leaf {
+            Some(_) => match remaining_left {
+                Some(_) => (),
+                None => match remaining_right {
+                    None => {
+                        let nd_leaf = noderef_as!(node_ref, Internal).leaf_node.clone();
+                        noderef_as_mut!(node_ref, Internal).leaf_node = NodePointer::null_ptr();
+                        let mut cache = self.cache.borrow_mut();
+                        cache.remove_node(ptr.clone());
+                        // The leaf node moves up, so it cannot be refetched
+                        // at its new position until committed.
+                        cache.rollback_node(nd_leaf.clone(), NodeKind::Leaf);
+                        drop(cache);
+                        return (nd_leaf, true);
+                    }
+                    Some(_) => (),
+                },
+            },
+            None => {
+                let mut nd_child: Option<NodeRef> = None;
+                let mut node_ptr: NodePtrRef = NodePointer::null_ptr();
+                let mut both_children = true;
+                match remaining_left {
+                    Some(_) => match remaining_right {
+                        None => {
+                            node_ptr = noderef_as!(node_ref, Internal).left.clone();
+                            noderef_as_mut!(node_ref, Internal).left = NodePointer::null_ptr();
+                            nd_child = remaining_left;
+                            both_children = false;
+                        }
+                        Some(_) => (),
+                    },
+                    None => match remaining_right {
+                        None => (),
+                        Some(_) => {
+                            node_ptr = noderef_as!(node_ref, Internal).right.clone();
+                            noderef_as_mut!(node_ref, Internal).right = NodePointer::null_ptr();
+                            nd_child = remaining_right;
+                            both_children = false;
+                        }
+                    },
+                }
+
+                if !both_children {
+                    // If child is an internal node, also fix the label.
+                    match nd_child {
+                        Some(_) => match classify_noderef!(?nd_child) {
+                            NodeKind::Internal => {
+                                if let NodeBox::Internal(ref mut inode) =
+                                    *nd_child.unwrap().borrow_mut()
+                                {
+                                    inode.label = noderef_as!(node_ref, Internal).label.merge(
+                                        noderef_as!(node_ref, Internal).label_bit_length,
+                                        &inode.label,
+                                        inode.label_bit_length,
+                                    );
+                                    inode.label_bit_length +=
+                                        noderef_as!(node_ref, Internal).label_bit_length;
+                                    inode.clean = false;
+                                    node_ptr.borrow_mut().clean = false;
+                                    // No longer eligible for eviction as it is dirty.
+                                    let mut cache = self.cache.borrow_mut();
+                                    cache.rollback_node(node_ptr.clone(), NodeKind::Internal);
+                                    cache.rollback_node(inode.leaf_node.clone(), NodeKind::Leaf);
+                                }
+                            }
+                            NodeKind::Leaf => {
+                                // The leaf node moves up, so it cannot be
+                                // refetched at its new position until committed.
+                                self.cache
+                                    .borrow_mut()
+                                    .rollback_node(node_ptr.clone(), NodeKind::Leaf);
+                            }
+                            _ => (),
+                        },
+                        _ => (),
+                    }
+
+                    self.cache.borrow_mut().remove_node(ptr.clone());
+                    return (node_ptr, true);
+                }
+            }
+        };
+
+        // Two or more children including leaf_node remain, just mark dirty bit.
+        if changed {
+            noderef_as_mut!(node_ref, Internal).clean = false;
+            ptr.borrow_mut().clean = false;
+            // No longer eligible for eviction as it is dirty. The leaf
+            // node cannot be refetched via a dirty node either.
+            let mut cache = self.cache.borrow_mut();
+            cache.rollback_node(ptr.clone(), NodeKind::Internal);
+            cache.rollback_node(
+                noderef_as!(node_ref, Internal).leaf_node.clone(),
+                NodeKind::Leaf,
+            );
+        }
+
+        (ptr, changed)
+    }
+}
+
+/// Compare the keys starting with the given path to the given key.
+///
+/// Returns `Less` if all such keys are smaller than the key, `Greater` if
+/// none of them is smaller and `Equal` if the key itself starts with the path,
+/// so that there may be keys on both sides.
+fn compare_path(path: &Key, path_bit_length: Depth, key: &Key) -> Ordering {
+    let cp_len = path.common_prefix_len(path_bit_length, key, key.bit_length());
+    if cp_len == key.bit_length() {
+        Ordering::Greater
+    } else if cp_len == path_bit_length {
+        Ordering::Equal
+    } else if key.get_bit(cp_len) {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
 }
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::storage::mkvs::tree::*;
+
+/// An entry in the undo log, recording the value of a key before it was
+/// modified.
+pub(crate) struct UndoLogEntry {
+    key: Key,
+    value: Option<Value>,
+}
+
+/// A logical snapshot of the uncommitted state of a tree.
+///
+/// Snapshots are only valid until the next commit of the tree they were taken
+/// from, or until the tree is rolled back to a snapshot taken before them.
+pub struct Snapshot {
+    generation: u64,
+    undo_position: usize,
+    pending_write_log: BTreeMap<Key, PendingLogEntry>,
+}
+
+impl Tree {
+    /// Take a snapshot of the current (uncommitted) tree state.
+    ///
+    /// Taking a snapshot does not copy any nodes. Instead, while snapshots
+    /// are in use the tree records the previous values of all modified keys
+    /// so that `rollback_to` can undo modifications made after the snapshot.
+    pub fn snapshot(&mut self) -> Snapshot {
+        let undo_log = self.undo_log.get_or_insert_with(Vec::new);
+        let generation = self.snapshot_generation;
+        self.snapshot_generation += 1;
+        self.live_snapshots.push(generation);
+
+        Snapshot {
+            generation,
+            undo_position: undo_log.len(),
+            pending_write_log: self.pending_write_log.clone(),
+        }
+    }
+
+    /// Roll back all modifications made after the given snapshot was taken.
+    ///
+    /// This invalidates all snapshots taken after the given snapshot. Returns
+    /// `Error::InvalidSnapshot` if the snapshot itself is no longer valid.
+    pub fn rollback_to(&mut self, ctx: Context, snapshot: Snapshot) -> Result<()> {
+        let ctx = ctx.freeze();
+        let index = self
+            .live_snapshots
+            .binary_search(&snapshot.generation)
+            .map_err(|_| Error::InvalidSnapshot)?;
+        let mut undo_log = match self.undo_log.take() {
+            Some(undo_log) if undo_log.len() >= snapshot.undo_position => undo_log,
+            _ => return Err(Error::InvalidSnapshot.into()),
+        };
+
+        // Replay the undo log in reverse. As the tree structure only depends
+        // on its contents this also restores the original structure.
+        for entry in undo_log.drain(snapshot.undo_position..).rev() {
+            match entry.value {
+                Some(ref value) => self.insert_one(&ctx, &entry.key, value)?,
+                None => self.remove_one(&ctx, &entry.key)?,
+            };
+        }
+
+        self.pending_write_log = snapshot.pending_write_log;
+        self.undo_log = Some(undo_log);
+        self.live_snapshots.truncate(index);
+
+        Ok(())
+    }
+
+    pub(super) fn record_undo(&mut self, key: &[u8], value: &Option<Value>) {
+        if let Some(ref mut undo_log) = self.undo_log {
+            undo_log.push(UndoLogEntry {
+                key: key.to_vec(),
+                value: value.clone(),
+            });
+        }
+    }
+
+    pub(super) fn invalidate_snapshots(&mut self) {
+        self.undo_log = None;
+        self.live_snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::sync::*;
+
+    #[test]
+    fn test_snapshot() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        tree.insert(Context::background(), b"foo", b"bar").unwrap();
+        tree.insert(Context::background(), b"moo", b"boo").unwrap();
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+        tree.insert(Context::background(), b"foo", b"baz").unwrap();
+        let snapshot = tree.snapshot();
+
+        // Speculative modifications.
+        tree.insert(Context::background(), b"foo", b"speculative")
+            .unwrap();
+        tree.insert(Context::background(), b"new", b"key").unwrap();
+        tree.remove(Context::background(), b"moo").unwrap();
+        let nested = tree.snapshot();
+        tree.remove(Context::background(), b"new").unwrap();
+        tree.rollback_to(Context::background(), nested)
+            .expect("rollback_to");
+        assert_eq!(
+            Some(b"key".to_vec()),
+            tree.get(Context::background(), b"new").unwrap()
+        );
+
+        tree.rollback_to(Context::background(), snapshot)
+            .expect("rollback_to");
+        assert_eq!(
+            Some(b"baz".to_vec()),
+            tree.get(Context::background(), b"foo").unwrap()
+        );
+        assert_eq!(
+            Some(b"boo".to_vec()),
+            tree.get(Context::background(), b"moo").unwrap()
+        );
+        assert_eq!(None, tree.get(Context::background(), b"new").unwrap());
+
+        // The committed state should be as if the speculative modifications
+        // never happened.
+        let (write_log, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+        assert_eq!(1, write_log.len());
+        assert_eq!(b"foo".to_vec(), write_log[0].key);
+
+        let mut expected = Tree::make().new(Box::new(NoopReadSyncer));
+        expected
+            .insert(Context::background(), b"foo", b"baz")
+            .unwrap();
+        expected
+            .insert(Context::background(), b"moo", b"boo")
+            .unwrap();
+        let (_, expected_hash) =
+            Tree::commit(&mut expected, Context::background(), Default::default(), 1)
+                .expect("commit");
+        assert_eq!(expected_hash, hash);
+
+        // Snapshots taken after the snapshot rolled back to are invalidated.
+        tree.insert(Context::background(), b"foo", b"qux").unwrap();
+        let snapshot = tree.snapshot();
+        tree.insert(Context::background(), b"foo", b"speculative")
+            .unwrap();
+        let nested = tree.snapshot();
+        tree.rollback_to(Context::background(), snapshot)
+            .expect("rollback_to");
+        let snapshot = tree.snapshot();
+        tree.insert(Context::background(), b"foo", b"speculative")
+            .unwrap();
+        assert!(tree.rollback_to(Context::background(), nested).is_err());
+        assert_eq!(
+            Some(b"speculative".to_vec()),
+            tree.get(Context::background(), b"foo").unwrap()
+        );
+        tree.rollback_to(Context::background(), snapshot)
+            .expect("rollback_to");
+        assert_eq!(
+            Some(b"qux".to_vec()),
+            tree.get(Context::background(), b"foo").unwrap()
+        );
+
+        // Snapshots are invalidated by commit.
+        let snapshot = tree.snapshot();
+        Tree::commit(&mut tree, Context::background(), Default::default(), 2).expect("commit");
+        assert!(tree.rollback_to(Context::background(), snapshot).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::storage::mkvs::{cache::*, tree::*};
+
+use super::iterator::FetcherSyncIterate;
+
+/// Statistics about the structure of a tree.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TreeStats {
+    /// Maximum depth of the tree, in number of internal nodes traversed.
+    pub max_depth: Depth,
+    /// Average depth of leaf nodes.
+    pub avg_leaf_depth: f64,
+    /// Number of internal nodes.
+    pub internal_node_count: u64,
+    /// Number of leaf nodes.
+    pub leaf_node_count: u64,
+    /// Total size of all leaf values, in bytes.
+    pub leaf_value_size: u64,
+    /// Number of nodes with uncommitted modifications.
+    pub dirty_node_count: u64,
+}
+
+/// State accumulated during a statistics traversal.
+struct StatsAccumulator {
+    stats: TreeStats,
+    leaf_depth_sum: u64,
+    max_depth: Depth,
+}
+
+impl Tree {
+    /// Traverse the tree and collect statistics about its structure.
+    ///
+    /// Subtrees deeper than `max_depth` are not visited. If set to 0, the
+    /// whole tree is traversed. Note that any parts of the tree that are not
+    /// in the local cache will be fetched from the read syncer.
+    pub fn stats(&self, ctx: Context, max_depth: Depth) -> Result<TreeStats> {
+        let ctx = ctx.freeze();
+        let pending_root = self.cache.borrow().get_pending_root();
+
+        let mut acc = StatsAccumulator {
+            stats: TreeStats::default(),
+            leaf_depth_sum: 0,
+            max_depth,
+        };
+        self._stats(&ctx, &mut acc, pending_root, Key::new(), 0, 0)?;
+
+        let mut stats = acc.stats;
+        if stats.leaf_node_count > 0 {
+            stats.avg_leaf_depth = acc.leaf_depth_sum as f64 / stats.leaf_node_count as f64;
+        }
+
+        Ok(stats)
+    }
+
+    fn _stats(
+        &self,
+        ctx: &Arc<Context>,
+        acc: &mut StatsAccumulator,
+        ptr: NodePtrRef,
+        path: Key,
+        bit_depth: Depth,
+        depth: Depth,
+    ) -> Result<()> {
+        if acc.max_depth > 0 && depth > acc.max_depth {
+            return Ok(());
+        }
+        if depth > acc.stats.max_depth {
+            acc.stats.max_depth = depth;
+        }
+        if !ptr.borrow().clean && ptr.borrow().node.is_some() {
+            acc.stats.dirty_node_count += 1;
+        }
+
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr,
+            Some(FetcherSyncIterate::new(&path, &path, bit_depth, 0)),
+        )?;
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => Ok(()),
+            NodeKind::Internal => {
+                acc.stats.internal_node_count += 1;
+
+                let node_ref = node_ref.unwrap();
+                let (leaf_node, left, right, path, bit_length) = match *node_ref.borrow() {
+                    NodeBox::Internal(ref n) => (
+                        n.leaf_node.clone(),
+                        n.left.clone(),
+                        n.right.clone(),
+                        path.merge(bit_depth, &n.label, n.label_bit_length),
+                        bit_depth + n.label_bit_length,
+                    ),
+                    _ => unreachable!("node kind is internal node"),
+                };
+
+                self._stats(ctx, acc, leaf_node, path.clone(), bit_length, depth)?;
+                self._stats(
+                    ctx,
+                    acc,
+                    left,
+                    path.append_bit(bit_length, false),
+                    bit_length,
+                    depth + 1,
+                )?;
+                self._stats(
+                    ctx,
+                    acc,
+                    right,
+                    path.append_bit(bit_length, true),
+                    bit_length,
+                    depth + 1,
+                )
+            }
+            NodeKind::Leaf => {
+                let node_ref = node_ref.unwrap();
+                acc.stats.leaf_node_count += 1;
+                acc.stats.leaf_value_size += noderef_as!(node_ref, Leaf).value.len() as u64;
+                acc.leaf_depth_sum += depth as u64;
+
+                Ok(())
+            }
+        }
+    }
+}
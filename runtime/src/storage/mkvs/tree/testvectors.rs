@@ -0,0 +1,187 @@
+//! Test vectors for node, write log and proof encodings.
+use anyhow::Result;
+use io_context::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::cbor,
+    storage::mkvs::{sync::NoopReadSyncer, tree::*, LogEntry, WriteLog},
+};
+
+/// Version at which the test vector tree is committed.
+const TEST_VECTORS_VERSION: u64 = 1;
+/// Items of the test vector tree. The keys are chosen so that the tree has
+/// an internal node with an embedded leaf node.
+const TEST_VECTORS_ITEMS: &[(&[u8], &[u8])] = &[(b"a", b"foo"), (b"b", b"bar"), (b"bc", b"baz")];
+
+/// Node encoding test vector.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NodeTestVector {
+    /// Full binary encoding of the node.
+    #[serde(with = "base64_bytes")]
+    pub encoded: Vec<u8>,
+    /// Hash of the node.
+    #[serde(with = "base64_bytes")]
+    pub hash: Vec<u8>,
+}
+
+/// Write log entry in a write log test vector.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WriteLogEntryTestVector {
+    /// The key that was inserted or deleted.
+    #[serde(with = "base64_bytes")]
+    pub key: Vec<u8>,
+    /// The inserted value (null if the key was deleted).
+    #[serde(with = "base64_bytes_option")]
+    pub value: Option<Vec<u8>>,
+}
+
+/// Write log encoding test vector.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WriteLogTestVector {
+    /// CBOR encoding of the write log.
+    #[serde(with = "base64_bytes")]
+    pub encoded: Vec<u8>,
+    /// Write log entries.
+    pub entries: Vec<WriteLogEntryTestVector>,
+}
+
+/// Proof encoding test vector.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProofTestVector {
+    /// Root hash the proof must verify against.
+    #[serde(with = "base64_bytes")]
+    pub root: Vec<u8>,
+    /// The key the proof is for.
+    #[serde(with = "base64_bytes")]
+    pub key: Vec<u8>,
+    /// The value of the key (null if the key does not exist).
+    #[serde(with = "base64_bytes_option")]
+    pub value: Option<Vec<u8>>,
+    /// CBOR encoding of the proof.
+    #[serde(with = "base64_bytes")]
+    pub encoded: Vec<u8>,
+}
+
+/// Test vectors for node, write log and proof encodings.
+///
+/// The vectors are checked in as a JSON fixture shared with the Go
+/// implementation, so that both implementations are tested against the
+/// same encodings. All binary fields are base64-encoded.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub nodes: Vec<NodeTestVector>,
+    pub write_logs: Vec<WriteLogTestVector>,
+    pub proofs: Vec<ProofTestVector>,
+}
+
+impl Tree {
+    /// Generate test vectors for node, write log and proof encodings.
+    ///
+    /// The generated vectors are deterministic. Nodes are sorted by hash.
+    pub fn generate_test_vectors() -> Result<TestVectors> {
+        let ctx = Context::background().freeze();
+
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for (key, value) in TEST_VECTORS_ITEMS {
+            tree.insert(Context::create_child(&ctx), key, value)?;
+        }
+        let (_, root) = Tree::commit(
+            &mut tree,
+            Context::create_child(&ctx),
+            Default::default(),
+            TEST_VECTORS_VERSION,
+        )?;
+
+        let mut nodes: Vec<NodeTestVector> = tree
+            .export_nodes(Context::create_child(&ctx))?
+            .into_iter()
+            .map(|(hash, encoded)| NodeTestVector {
+                encoded,
+                hash: hash.as_ref().to_vec(),
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.hash.cmp(&b.hash));
+
+        let write_logs: Vec<WriteLog> = vec![
+            Vec::new(),
+            TEST_VECTORS_ITEMS
+                .iter()
+                .map(|(key, value)| LogEntry::new(key, value))
+                .collect(),
+            vec![
+                LogEntry::new(b"a", b"foo"),
+                LogEntry {
+                    key: b"bc".to_vec(),
+                    value: None,
+                },
+            ],
+        ];
+        let write_logs = write_logs
+            .into_iter()
+            .map(|write_log| WriteLogTestVector {
+                encoded: cbor::to_vec(&write_log),
+                entries: write_log
+                    .into_iter()
+                    .map(|entry| WriteLogEntryTestVector {
+                        key: entry.key,
+                        value: entry.value,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let mut proofs = Vec::new();
+        for key in &[&b"b"[..], &b"bc"[..]] {
+            let (value, proof) = tree.get_with_proof(Context::create_child(&ctx), key)?;
+            proofs.push(ProofTestVector {
+                root: root.as_ref().to_vec(),
+                key: key.to_vec(),
+                value,
+                encoded: cbor::to_vec(&proof),
+            });
+        }
+
+        Ok(TestVectors {
+            nodes,
+            write_logs,
+            proofs,
+        })
+    }
+}
+
+mod base64_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        base64::decode(&data).map_err(Error::custom)
+    }
+}
+
+mod base64_bytes_option {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        data: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(data) => serializer.serialize_str(&base64::encode(data)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(data) => base64::decode(&data).map(Some).map_err(Error::custom),
+            None => Ok(None),
+        }
+    }
+}
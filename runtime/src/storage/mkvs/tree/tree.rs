@@ -1,17 +1,21 @@
 use std::{
     cell::RefCell,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fmt,
     rc::Rc,
     sync::{Arc, Mutex},
 };
 
+use anyhow::Result;
+
 use crate::storage::mkvs::{cache::*, sync::*, tree::*};
 
+#[derive(Clone)]
 pub struct PendingLogEntry {
     pub key: Vec<u8>,
     pub value: Option<Vec<u8>>,
     pub existed: bool,
+    pub previous: Option<Vec<u8>>,
 }
 
 /// A container for the parameters used to construct a new MKVS tree instance.
@@ -19,6 +23,13 @@ pub struct Options {
     node_capacity: usize,
     value_capacity: usize,
     root: Option<Root>,
+    root_type: RootType,
+    hash_algorithm: Option<HashAlgorithm>,
+    max_key_size: usize,
+    max_value_size: usize,
+    max_proof_size: usize,
+    cache_policy: Rc<dyn CachePolicy>,
+    cache_hook: Option<CacheHook>,
 }
 
 impl Options {
@@ -26,8 +37,9 @@ impl Options {
     ///
     /// * `node_capacity` is the maximum number of nodes held by the
     ///   cache before eviction.
-    /// * `value_capacity` is the total size, in bytes, of values held
-    ///   by the cache before eviction.
+    /// * `value_capacity` is the total size, in bytes, of leaf nodes held
+    ///   by the cache before eviction. Each leaf node is accounted with its
+    ///   key, value and a fixed per-node overhead (`NODE_OVERHEAD`).
     ///
     /// If set to 0, the relevant cache will have an unlimited capacity. If left
     /// unspecified, the cache will default to 50_000 for nodes and 16MB for values.
@@ -37,15 +49,132 @@ impl Options {
         self
     }
 
+    /// Set the eviction policy of the underlying in-memory cache.
+    ///
+    /// If left unspecified, plain LRU eviction (`LRUPolicy`) is used.
+    pub fn with_cache_policy(mut self, cache_policy: Rc<dyn CachePolicy>) -> Self {
+        self.cache_policy = cache_policy;
+        self
+    }
+
+    /// Set a hook that is invoked for each event of the underlying in-memory
+    /// cache, e.g. to export cache metrics.
+    pub fn with_cache_hook(mut self, cache_hook: CacheHook) -> Self {
+        self.cache_hook = Some(cache_hook);
+        self
+    }
+
     /// Set an existing root as the root for the new tree.
     pub fn with_root(mut self, root: Root) -> Self {
         self.root = Some(root);
         self
     }
 
+    /// Set the type of the roots of the new tree.
+    ///
+    /// A root set via `with_root` must then either be of the same type or
+    /// untyped, in which case it is assumed to be of this type. If left
+    /// unspecified, the type of the root set via `with_root` is used.
+    pub fn with_root_type(mut self, root_type: RootType) -> Self {
+        self.root_type = root_type;
+        self
+    }
+
+    /// Set the maximum size of keys and values that can be inserted.
+    ///
+    /// * `max_key_size` is the maximum size of a key, in bytes.
+    /// * `max_value_size` is the maximum size of a value, in bytes.
+    ///
+    /// If set to 0, the relevant size is not limited. If left unspecified,
+    /// neither keys nor values are limited.
+    pub fn with_size_limits(mut self, max_key_size: usize, max_value_size: usize) -> Self {
+        self.max_key_size = max_key_size;
+        self.max_value_size = max_value_size;
+        self
+    }
+
+    /// Set the maximum estimated size of proofs generated by the tree, in
+    /// bytes (see `ProofBuilder`).
+    ///
+    /// Generating a larger proof fails with `Error::ProofTooLarge`. If set
+    /// to 0 or left unspecified, the size of proofs is not limited.
+    pub fn with_max_proof_size(mut self, max_proof_size: usize) -> Self {
+        self.max_proof_size = max_proof_size;
+        self
+    }
+
+    /// Set the digest algorithm used for hashing nodes.
+    ///
+    /// A root set via `with_root` must then use the same algorithm, as
+    /// proofs fetched from the read syncer are verified using the algorithm
+    /// of the root. If left unspecified, the algorithm of the root set via
+    /// `with_root` is used or, without a root, SHA-512/256.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = Some(hash_algorithm);
+        self
+    }
+
     /// Commit the options set so far into a newly constructed tree instance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the options are inconsistent, see `try_new`.
     pub fn new(self, read_syncer: Box<dyn ReadSync>) -> Tree {
-        Tree::new(read_syncer, &self)
+        self.try_new(read_syncer)
+            .expect("mkvs: inconsistent tree options")
+    }
+
+    /// Commit the options set so far into a newly constructed tree instance,
+    /// after checking that they are consistent.
+    ///
+    /// Returns `Error::RootTypeMismatch` if the type of the root does not
+    /// match the type set via `with_root_type` and
+    /// `Error::HashAlgorithmMismatch` if the hash algorithm of the root does
+    /// not match the one set via `with_hash_algorithm`.
+    pub fn try_new(mut self, read_syncer: Box<dyn ReadSync>) -> Result<Tree> {
+        if let Some(ref mut root) = self.root {
+            if self.root_type.is_invalid() {
+                self.root_type = root.root_type;
+            } else if root.root_type.is_invalid() {
+                root.root_type = self.root_type;
+            } else if root.root_type != self.root_type {
+                return Err(Error::RootTypeMismatch {
+                    expected: self.root_type,
+                    got: root.root_type,
+                }
+                .into());
+            }
+
+            match self.hash_algorithm {
+                Some(hash_algorithm) if hash_algorithm != root.hash_algorithm => {
+                    return Err(Error::HashAlgorithmMismatch {
+                        expected: hash_algorithm,
+                        got: root.hash_algorithm,
+                    }
+                    .into());
+                }
+                _ => self.hash_algorithm = Some(root.hash_algorithm),
+            }
+        }
+
+        Ok(Tree::new(read_syncer, &self))
+    }
+
+    /// Commit the options set so far into a newly constructed tree instance
+    /// backed by an asynchronous read syncer.
+    ///
+    /// The fetches of `get_async` and `iter_async` are then driven through
+    /// the asynchronous read syncer without blocking the current thread,
+    /// while all other operations block until their fetches complete.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the options are inconsistent, see `try_new`.
+    pub fn new_async(self, read_syncer: Box<dyn AsyncReadSync>) -> Tree {
+        let read_syncer = DeferringReadSync::new(read_syncer);
+        let mut tree = self.new(Box::new(read_syncer.clone()));
+        tree.async_read_syncer = Some(read_syncer);
+        tree
     }
 }
 
@@ -54,6 +183,16 @@ pub struct Tree {
     pub(crate) cache: RefCell<Box<LRUCache>>,
     pub(crate) pending_write_log: BTreeMap<Key, PendingLogEntry>,
     pub(crate) lock: Arc<Mutex<isize>>,
+    pub(crate) undo_log: Option<Vec<UndoLogEntry>>,
+    pub(crate) snapshot_generation: u64,
+    pub(crate) live_snapshots: Vec<u64>,
+    pub(crate) root_type: RootType,
+    pub(crate) hash_algorithm: HashAlgorithm,
+    pub(crate) max_key_size: usize,
+    pub(crate) max_value_size: usize,
+    pub(crate) max_proof_size: usize,
+    pub(crate) pinned_prefixes: BTreeSet<Key>,
+    pub(crate) async_read_syncer: Option<DeferringReadSync>,
 }
 
 impl Tree {
@@ -64,11 +203,27 @@ impl Tree {
                 opts.node_capacity,
                 opts.value_capacity,
                 read_syncer,
+                opts.cache_policy.clone(),
             )),
             pending_write_log: BTreeMap::new(),
             lock: Arc::new(Mutex::new(0)),
+            undo_log: None,
+            snapshot_generation: 0,
+            live_snapshots: Vec::new(),
+            root_type: opts.root_type,
+            hash_algorithm: opts
+                .hash_algorithm
+                .or_else(|| opts.root.map(|root| root.hash_algorithm))
+                .unwrap_or_default(),
+            max_key_size: opts.max_key_size,
+            max_value_size: opts.max_value_size,
+            max_proof_size: opts.max_proof_size,
+            pinned_prefixes: BTreeSet::new(),
+            async_read_syncer: None,
         };
 
+        tree.cache.borrow_mut().set_hook(opts.cache_hook.clone());
+
         if let Some(root) = opts.root {
             tree.cache
                 .borrow_mut()
@@ -89,8 +244,45 @@ impl Tree {
             node_capacity: 50_000,
             value_capacity: 16 * 1024 * 1024,
             root: None,
+            root_type: RootType::default(),
+            hash_algorithm: None,
+            max_key_size: 0,
+            max_value_size: 0,
+            max_proof_size: 0,
+            cache_policy: Rc::new(LRUPolicy),
+            cache_hook: None,
         }
     }
+
+    /// Return statistics about the contents and use of the in-memory cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.borrow().stats()
+    }
+
+    /// Return the digest algorithm used for hashing nodes.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Return the type of the roots of the tree.
+    pub fn root_type(&self) -> RootType {
+        self.root_type
+    }
+
+    /// Return a fetcher driving operations through the asynchronous read
+    /// syncer of the tree, if any.
+    pub(crate) fn async_fetcher(&self) -> AsyncFetcher {
+        AsyncFetcher::new(self.async_read_syncer.clone())
+    }
+
+    /// Whether the operation being driven by an `AsyncFetcher` has deferred
+    /// a fetch, in which case it fails and is retried once the fetch
+    /// completes.
+    pub(crate) fn is_fetch_deferred(&self) -> bool {
+        self.async_read_syncer
+            .as_ref()
+            .map_or(false, |rs| rs.is_deferred())
+    }
 }
 
 impl fmt::Debug for Tree {
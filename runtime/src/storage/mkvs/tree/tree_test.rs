@@ -1,12 +1,17 @@
 use io_context::Context;
+use rustc_hex::ToHex;
 use serde_json;
-use std::{collections::HashSet, fs::File, io::BufReader, iter::FromIterator, path::Path};
+use std::{
+    cell::RefCell, collections::HashSet, fs::File, io::BufReader, iter::FromIterator, path::Path,
+    rc::Rc, time::Duration,
+};
 
 use crate::{
-    common::crypto::hash::Hash,
+    common::{cbor, crypto::hash::Hash},
     storage::mkvs::{
         cache::*,
         interop::{Driver, ProtocolServer},
+        marshal::Marshal,
         sync::*,
         tests,
         tree::*,
@@ -503,6 +508,495 @@ fn test_insert_commit_batch() {
     assert_eq!(format!("{:?}", hash), ALL_ITEMS_ROOT);
 }
 
+#[test]
+fn test_pending_changes() {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    tree.insert(Context::background(), b"foo", b"bar").unwrap();
+    tree.insert(Context::background(), b"moo", b"boo").unwrap();
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    assert!(tree.pending_changes().is_empty());
+
+    tree.insert(Context::background(), b"foo", b"baz").unwrap();
+    tree.insert(Context::background(), b"foo", b"qux").unwrap();
+    tree.remove(Context::background(), b"moo").unwrap();
+    tree.insert(Context::background(), b"new", b"key").unwrap();
+    tree.insert(Context::background(), b"gone", b"soon")
+        .unwrap();
+    tree.remove(Context::background(), b"gone").unwrap();
+
+    let changes = tree.pending_changes();
+    assert_eq!(
+        vec![
+            PendingChange {
+                key: b"foo".to_vec(),
+                old_value: Some(b"bar".to_vec()),
+                new_value: Some(b"qux".to_vec()),
+            },
+            PendingChange {
+                key: b"moo".to_vec(),
+                old_value: Some(b"boo".to_vec()),
+                new_value: None,
+            },
+            PendingChange {
+                key: b"new".to_vec(),
+                old_value: None,
+                new_value: Some(b"key".to_vec()),
+            },
+        ],
+        changes
+    );
+
+    let (write_log, _) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+    assert_eq!(changes.len(), write_log.len());
+    for (change, entry) in changes.iter().zip(write_log.iter()) {
+        assert_eq!(change.key, entry.key);
+        assert_eq!(change.new_value, entry.value);
+    }
+    assert!(tree.pending_changes().is_empty());
+}
+
+#[test]
+fn test_size_limits() {
+    let mut tree = Tree::make()
+        .with_size_limits(4, 8)
+        .new(Box::new(NoopReadSyncer));
+
+    tree.insert(Context::background(), b"key", b"value")
+        .expect("insert");
+    tree.insert(Context::background(), b"keyz", b"12345678")
+        .expect("insert at limit");
+
+    let err = tree
+        .insert(Context::background(), b"key 1", b"value")
+        .expect_err("key too large");
+    match err.downcast_ref::<Error>() {
+        Some(Error::KeyTooLarge { size: 5, max: 4 }) => {}
+        _ => panic!("unexpected error: {}", err),
+    }
+
+    let err = tree
+        .insert(Context::background(), b"key", b"123456789")
+        .expect_err("value too large");
+    match err.downcast_ref::<Error>() {
+        Some(Error::ValueTooLarge { size: 9, max: 8 }) => {}
+        _ => panic!("unexpected error: {}", err),
+    }
+
+    // Batches are rejected as a whole.
+    let items = vec![
+        (b"a".to_vec(), b"a".to_vec()),
+        (b"b".to_vec(), b"123456789".to_vec()),
+    ];
+    assert!(tree.insert_batch(Context::background(), &items).is_err());
+    assert_eq!(None, tree.get(Context::background(), b"a").unwrap());
+
+    assert_eq!(
+        Some(b"value".to_vec()),
+        tree.get(Context::background(), b"key").unwrap()
+    );
+}
+
+#[test]
+fn test_sync_errors() {
+    let tree = Tree::make()
+        .with_root(Root {
+            hash: Hash::digest_bytes(b"remote root"),
+            ..Default::default()
+        })
+        .new(Box::new(NoopReadSyncer));
+
+    let err = tree
+        .get(Context::background(), b"key")
+        .expect_err("get should fail");
+    match err.downcast_ref::<Error>() {
+        Some(Error::SyncFailed { source }) => assert!(matches!(
+            source.downcast_ref::<SyncerError>(),
+            Some(SyncerError::Unsupported)
+        )),
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[test]
+fn test_cancellation() {
+    let expired = || {
+        let mut ctx = Context::background();
+        ctx.add_timeout(Duration::from_secs(0));
+        ctx
+    };
+    let is_cancelled =
+        |err: anyhow::Error| matches!(err.downcast_ref::<Error>(), Some(Error::Cancelled));
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    for i in 0..10 {
+        tree.insert(
+            Context::background(),
+            format!("key {}", i).as_bytes(),
+            format!("value {}", i).as_bytes(),
+        )
+        .expect("insert");
+    }
+    let err = Tree::commit(&mut tree, expired(), Default::default(), 0)
+        .expect_err("commit with expired context should fail");
+    assert!(is_cancelled(err));
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    // Fetches and iteration are aborted as well.
+    let root = Root {
+        hash,
+        ..Default::default()
+    };
+    let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+    let remote = Tree::make().with_root(root).new(Box::new(rs));
+    let err = remote
+        .get(expired(), b"key 0")
+        .expect_err("get with expired context should fail");
+    assert!(is_cancelled(err));
+
+    let mut it = remote.iter(expired());
+    it.rewind();
+    assert!(!it.is_valid());
+    assert!(it.error().is_some());
+
+    assert_eq!(
+        Some(b"value 0".to_vec()),
+        remote.get(Context::background(), b"key 0").expect("get")
+    );
+}
+
+#[test]
+fn test_insert_batch() {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+
+    let (keys, values) = generate_key_value_pairs();
+    let items: Vec<(Vec<u8>, Vec<u8>)> = keys
+        .iter()
+        .cloned()
+        .zip(values.iter().cloned())
+        .rev()
+        .collect();
+    let previous = tree
+        .insert_batch(Context::background(), &items)
+        .expect("insert_batch");
+    assert_eq!(items.len(), previous.len());
+    assert!(previous.iter().all(|v| v.is_none()));
+
+    for i in 0..keys.len() {
+        let value = tree
+            .get(Context::background(), keys[i].as_slice())
+            .expect("get")
+            .expect("get_some");
+        assert_eq!(values[i], value.as_slice());
+    }
+
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    assert_eq!(format!("{:?}", hash), ALL_ITEMS_ROOT);
+
+    // Duplicate keys within a batch are applied in order.
+    let items = vec![
+        (keys[0].clone(), b"first".to_vec()),
+        (b"new key".to_vec(), b"new".to_vec()),
+        (keys[0].clone(), b"second".to_vec()),
+    ];
+    let previous = tree
+        .insert_batch(Context::background(), &items)
+        .expect("insert_batch");
+    assert_eq!(
+        previous,
+        vec![Some(values[0].clone()), None, Some(b"first".to_vec())]
+    );
+    let value = tree
+        .get(Context::background(), keys[0].as_slice())
+        .expect("get")
+        .expect("get_some");
+    assert_eq!(b"second".to_vec(), value);
+
+    // Batches splitting existing edges and leaves must result in the same
+    // tree as inserting the items one by one.
+    let (long_keys, long_values) = generate_long_key_value_pairs();
+    let mut items: Vec<(Vec<u8>, Vec<u8>)> = long_keys
+        .iter()
+        .cloned()
+        .zip(long_values.iter().cloned())
+        .collect();
+    items.extend(vec![
+        (keys[1].clone(), b"updated".to_vec()),
+        (b"kex".to_vec(), b"split".to_vec()),
+        (b"key 1000".to_vec(), b"extended".to_vec()),
+        (b"k".to_vec(), b"prefix".to_vec()),
+    ]);
+    let mut expected = Tree::make().new(Box::new(NoopReadSyncer));
+    for i in 0..keys.len() {
+        expected
+            .insert(Context::background(), &keys[i], &values[i])
+            .expect("insert");
+    }
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    tree.insert_batch(
+        Context::background(),
+        &keys
+            .iter()
+            .cloned()
+            .zip(values.iter().cloned())
+            .collect::<Vec<_>>(),
+    )
+    .expect("insert_batch");
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    Tree::commit(&mut expected, Context::background(), Default::default(), 0).expect("commit");
+
+    let mut expected_previous = Vec::new();
+    for (key, value) in &items {
+        expected_previous.push(
+            expected
+                .insert(Context::background(), key, value)
+                .expect("insert"),
+        );
+    }
+    let previous = tree
+        .insert_batch(Context::background(), &items)
+        .expect("insert_batch");
+    assert_eq!(expected_previous, previous);
+
+    let (expected_write_log, expected_hash) =
+        Tree::commit(&mut expected, Context::background(), Default::default(), 1).expect("commit");
+    let (write_log, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+    assert_eq!(expected_hash, hash);
+    assert_eq!(expected_write_log, write_log);
+}
+
+#[test]
+fn test_insert_commit_parallel() {
+    let (keys, values) = generate_key_value_pairs_ex("P".to_owned(), 4000);
+
+    // Large enough to hash leaf nodes in parallel.
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    for i in 0..keys.len() {
+        tree.insert(Context::background(), &keys[i], &values[i])
+            .expect("insert");
+    }
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    // Small enough batches to hash leaf nodes sequentially.
+    let mut expected = Tree::make().new(Box::new(NoopReadSyncer));
+    for (i, (key, value)) in keys.iter().zip(values.iter()).enumerate() {
+        expected
+            .insert(Context::background(), key, value)
+            .expect("insert");
+        if i % 500 == 0 {
+            Tree::commit(&mut expected, Context::background(), Default::default(), 0)
+                .expect("commit");
+        }
+    }
+    let (_, expected_hash) =
+        Tree::commit(&mut expected, Context::background(), Default::default(), 0).expect("commit");
+
+    assert_eq!(expected_hash, hash);
+}
+
+#[test]
+fn test_commit_with_log() {
+    let (keys, values) = generate_key_value_pairs_ex("W".to_owned(), 4000);
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    for i in 0..keys.len() {
+        tree.insert(Context::background(), &keys[i], &values[i])
+            .expect("insert");
+    }
+    let (write_log, _) =
+        Tree::commit_with_log(&mut tree, Context::background(), Default::default(), 0)
+            .expect("commit_with_log");
+    let mut expected_write_log: WriteLog = keys
+        .iter()
+        .zip(values.iter())
+        .map(|(key, value)| LogEntry::new(key, value))
+        .collect();
+    expected_write_log.sort_by(|a, b| a.key.cmp(&b.key));
+    assert_eq!(expected_write_log, write_log);
+
+    // Updates, removals, no-op updates and keys that were inserted and removed
+    // again.
+    let mut expected_write_log = WriteLog::new();
+    for i in 0..keys.len() {
+        match i % 4 {
+            0 => {
+                let value = format!("updated {}", i).into_bytes();
+                tree.insert(Context::background(), &keys[i], &value)
+                    .expect("insert");
+                expected_write_log.push(LogEntry::new(&keys[i], &value));
+            }
+            1 => {
+                tree.remove(Context::background(), &keys[i])
+                    .expect("remove");
+                expected_write_log.push(LogEntry {
+                    key: keys[i].clone(),
+                    value: None,
+                });
+            }
+            2 => {
+                tree.insert(Context::background(), &keys[i], &values[i])
+                    .expect("insert");
+            }
+            _ => {}
+        }
+    }
+    tree.insert(Context::background(), b"transient", b"value")
+        .expect("insert");
+    tree.remove(Context::background(), b"transient")
+        .expect("remove");
+    expected_write_log.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let (write_log, hash) =
+        Tree::commit_with_log(&mut tree, Context::background(), Default::default(), 1)
+            .expect("commit_with_log");
+    assert_eq!(expected_write_log, write_log);
+
+    let mut expected = Tree::make().new(Box::new(NoopReadSyncer));
+    for i in 0..keys.len() {
+        expected
+            .insert(Context::background(), &keys[i], &values[i])
+            .expect("insert");
+    }
+    Tree::commit(&mut expected, Context::background(), Default::default(), 0).expect("commit");
+    for entry in &expected_write_log {
+        match entry.value {
+            Some(ref value) => expected
+                .insert(Context::background(), &entry.key, value)
+                .expect("insert"),
+            None => expected
+                .remove(Context::background(), &entry.key)
+                .expect("remove"),
+        };
+    }
+    let (_, expected_hash) =
+        Tree::commit(&mut expected, Context::background(), Default::default(), 1).expect("commit");
+    assert_eq!(expected_hash, hash);
+}
+
+#[test]
+fn test_hash_algorithm() {
+    let (keys, values) = generate_key_value_pairs_ex("H".to_owned(), 2000);
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    assert_eq!(HashAlgorithm::Sha512_256, tree.hash_algorithm());
+    let mut sha3_tree = Tree::make()
+        .with_hash_algorithm(HashAlgorithm::Sha3_256)
+        .new(Box::new(NoopReadSyncer));
+    assert_eq!(HashAlgorithm::Sha3_256, sha3_tree.hash_algorithm());
+    for i in 0..keys.len() {
+        tree.insert(Context::background(), &keys[i], &values[i])
+            .expect("insert");
+        sha3_tree
+            .insert(Context::background(), &keys[i], &values[i])
+            .expect("insert");
+    }
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let (_, sha3_hash) =
+        Tree::commit(&mut sha3_tree, Context::background(), Default::default(), 0).expect("commit");
+    assert_ne!(hash, sha3_hash);
+
+    for i in 0..keys.len() {
+        let value = sha3_tree.get(Context::background(), &keys[i]).expect("get");
+        assert_eq!(Some(values[i].clone()), value);
+    }
+
+    // Sequential hashing should give the same result as parallel hashing.
+    let mut expected = Tree::make()
+        .with_hash_algorithm(HashAlgorithm::Sha3_256)
+        .new(Box::new(NoopReadSyncer));
+    for (i, (key, value)) in keys.iter().zip(values.iter()).enumerate() {
+        expected
+            .insert(Context::background(), key, value)
+            .expect("insert");
+        if i % 500 == 0 {
+            Tree::commit(&mut expected, Context::background(), Default::default(), 0)
+                .expect("commit");
+        }
+    }
+    let (_, expected_hash) =
+        Tree::commit(&mut expected, Context::background(), Default::default(), 0).expect("commit");
+    assert_eq!(expected_hash, sha3_hash);
+}
+
+#[test]
+fn test_hash_algorithm_remote() {
+    let (keys, values) = generate_key_value_pairs_ex("H".to_owned(), 500);
+
+    let mut tree = Tree::make()
+        .with_hash_algorithm(HashAlgorithm::Sha3_256)
+        .new(Box::new(NoopReadSyncer));
+    for i in 0..keys.len() {
+        tree.insert(Context::background(), &keys[i], &values[i])
+            .expect("insert");
+    }
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let root = Root {
+        hash_algorithm: HashAlgorithm::Sha3_256,
+        hash,
+        ..Default::default()
+    };
+
+    // The algorithm is taken from the root and used to verify fetched nodes.
+    let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+    let remote = Tree::make()
+        .with_root(root)
+        .new(Box::new(VerifyingReadSync::new(Box::new(rs))));
+    assert_eq!(HashAlgorithm::Sha3_256, remote.hash_algorithm());
+    for i in 0..keys.len() {
+        let value = remote.get(Context::background(), &keys[i]).expect("get");
+        assert_eq!(Some(values[i].clone()), value);
+    }
+
+    // Proofs are verified using the algorithm of the root.
+    let (value, proof) = tree
+        .get_with_proof(Context::background(), &keys[42])
+        .expect("get_with_proof");
+    assert_eq!(Some(values[42].clone()), value);
+    ProofVerifier
+        .verify_proof_with(
+            Context::background(),
+            hash,
+            &proof,
+            HashAlgorithm::Sha3_256.hasher(),
+        )
+        .expect("verify_proof_with");
+    ProofVerifier
+        .verify_proof(Context::background(), hash, &proof)
+        .expect_err("verify_proof with the default algorithm should fail");
+
+    // A root hashed with the default algorithm fails to verify.
+    let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+    let remote = Tree::make()
+        .with_root(Root {
+            hash,
+            ..Default::default()
+        })
+        .new(Box::new(rs));
+    remote
+        .get(Context::background(), &keys[0])
+        .expect_err("get with a mismatched algorithm should fail");
+
+    // Conflicting algorithms are rejected.
+    let err = Tree::make()
+        .with_hash_algorithm(HashAlgorithm::Sha512_256)
+        .with_root(root)
+        .try_new(Box::new(NoopReadSyncer))
+        .expect_err("mixing hash algorithms should fail");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::HashAlgorithmMismatch {
+            expected: HashAlgorithm::Sha512_256,
+            got: HashAlgorithm::Sha3_256,
+        })
+    ));
+}
+
 #[test]
 fn test_insert_commit_each() {
     let mut tree = Tree::make()
@@ -653,6 +1147,164 @@ fn test_remove() {
     assert_eq!(hash, Hash::empty_hash());
 }
 
+#[test]
+fn test_remove_range() {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+
+    let (keys, values) = generate_key_value_pairs_ex("".to_owned(), 20);
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    // Removes "key 10" up to and including "key 14".
+    let removed = tree
+        .remove_range(Context::background(), b"key 10", Some(b"key 15"))
+        .expect("remove_range");
+    assert_eq!(5, removed);
+
+    for i in 0..keys.len() {
+        let value = tree
+            .get(Context::background(), keys[i].as_slice())
+            .expect("get");
+        if i >= 10 && i < 15 {
+            assert_eq!(None, value);
+        } else {
+            assert_eq!(Some(values[i].clone()), value);
+        }
+    }
+
+    let (write_log, _) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+    assert_eq!(5, write_log.len());
+    for entry in &write_log {
+        assert_eq!(LogEntryKind::Delete, entry.kind());
+    }
+
+    // Removing an empty range does nothing.
+    let removed = tree
+        .remove_range(Context::background(), b"key 10", Some(b"key 15"))
+        .expect("remove_range");
+    assert_eq!(0, removed);
+
+    // Unbounded range.
+    let removed = tree
+        .remove_range(Context::background(), b"", None)
+        .expect("remove_range");
+    assert_eq!(15, removed);
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 2).expect("commit");
+    assert_eq!(hash, Hash::empty_hash());
+
+    // Removing a range must result in the same tree as removing the keys in
+    // the range one by one.
+    let (mut keys, mut values) = generate_long_key_value_pairs();
+    let (more_keys, more_values) = generate_key_value_pairs_ex("".to_owned(), 200);
+    keys.extend(more_keys);
+    values.extend(more_values);
+    let ranges: Vec<(&[u8], Option<&[u8]>)> = vec![
+        (b"", Some(b"key 150")),
+        (b"key 1", Some(b"key 2")),
+        (b"key 15", None),
+        (b"Unlock the p", Some(b"Unlock the potential of your data")),
+        (b"U", Some(b"V")),
+        (b"key 199", Some(b"key 1990")),
+    ];
+    for (start, end) in ranges {
+        let mut expected = Tree::make().new(Box::new(NoopReadSyncer));
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..keys.len() {
+            expected
+                .insert(Context::background(), &keys[i], &values[i])
+                .expect("insert");
+            tree.insert(Context::background(), &keys[i], &values[i])
+                .expect("insert");
+        }
+        Tree::commit(&mut expected, Context::background(), Default::default(), 0).expect("commit");
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+        let mut expected_removed = 0;
+        for key in &keys {
+            if key.as_slice() >= start && end.map_or(true, |end| key.as_slice() < end) {
+                expected.remove(Context::background(), key).expect("remove");
+                expected_removed += 1;
+            }
+        }
+        let removed = tree
+            .remove_range(Context::background(), start, end)
+            .expect("remove_range");
+        assert_eq!(expected_removed, removed);
+
+        let (expected_write_log, expected_hash) =
+            Tree::commit(&mut expected, Context::background(), Default::default(), 1)
+                .expect("commit");
+        let (write_log, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+        assert_eq!(expected_hash, hash);
+        assert_eq!(expected_write_log, write_log);
+    }
+}
+
+#[test]
+fn test_tree_stats() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+
+    let stats = tree.stats(Context::background(), 0).expect("stats");
+    assert_eq!(TreeStats::default(), stats);
+
+    let (keys, values) = generate_key_value_pairs();
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    let value_size: u64 = values.iter().map(|v| v.len() as u64).sum();
+
+    let stats = tree.stats(Context::background(), 0).expect("stats");
+    assert_eq!(keys.len() as u64, stats.leaf_node_count);
+    assert_eq!(value_size, stats.leaf_value_size);
+    assert!(stats.internal_node_count > 0);
+    assert!(stats.dirty_node_count > 0);
+    assert!(stats.avg_leaf_depth <= stats.max_depth as f64);
+
+    let (write_log, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, hash, Default::default(), 0);
+
+    let committed_stats = tree.stats(Context::background(), 0).expect("stats");
+    assert_eq!(0, committed_stats.dirty_node_count);
+    assert_eq!(
+        stats.internal_node_count,
+        committed_stats.internal_node_count
+    );
+
+    // Statistics of a remote tree should match.
+    let remote_tree = Tree::make()
+        .with_capacity(0, 0)
+        .with_root(Root {
+            hash,
+            ..Default::default()
+        })
+        .new(server.read_sync());
+    let remote_stats = remote_tree.stats(Context::background(), 0).expect("stats");
+    assert_eq!(committed_stats, remote_stats);
+
+    // Limited depth.
+    let limited_stats = tree.stats(Context::background(), 2).expect("stats");
+    assert_eq!(2, limited_stats.max_depth);
+    assert!(limited_stats.leaf_node_count < stats.leaf_node_count);
+}
+
 #[test]
 fn test_syncer_basic() {
     let server = ProtocolServer::new();
@@ -708,6 +1360,60 @@ fn test_syncer_basic() {
     assert_eq!(0, stats.sync_iterate_count, "sync_iterate count");
 }
 
+#[test]
+fn test_get_with_proof() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+
+    let (keys, values) = generate_key_value_pairs();
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+
+    // Proofs cannot be generated for uncommitted changes.
+    assert!(tree
+        .get_with_proof(Context::background(), keys[0].as_slice())
+        .is_err());
+
+    let (write_log, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, hash, Default::default(), 0);
+
+    let remote_tree = Tree::make()
+        .with_capacity(0, 0)
+        .with_root(Root {
+            hash,
+            ..Default::default()
+        })
+        .new(server.read_sync());
+
+    let pv = ProofVerifier;
+    for tree in &[&tree, &remote_tree] {
+        for i in (0..keys.len()).step_by(97) {
+            let (value, proof) = tree
+                .get_with_proof(Context::background(), keys[i].as_slice())
+                .expect("get_with_proof");
+            assert_eq!(Some(values[i].clone()), value);
+            assert_eq!(hash, proof.untrusted_root);
+            pv.verify_proof(Context::background(), hash, &proof)
+                .expect("inclusion proof should verify");
+        }
+
+        let (value, proof) = tree
+            .get_with_proof(Context::background(), b"missing key")
+            .expect("get_with_proof");
+        assert_eq!(None, value);
+        pv.verify_proof(Context::background(), hash, &proof)
+            .expect("non-inclusion proof should verify");
+    }
+}
+
 #[test]
 fn test_syncer_remove() {
     let server = ProtocolServer::new();
@@ -887,6 +1593,11 @@ fn test_syncer_prefetch_prefixes() {
         })
         .new(Box::new(stats));
 
+    // Prefetching nothing should not result in any requests.
+    remote_tree
+        .prefetch_prefixes(Context::background(), &vec![], 1000)
+        .expect("prefetch_prefixes");
+
     // Prefetch keys starting with prefix "key".
     remote_tree
         .prefetch_prefixes(Context::background(), &vec![b"key".to_vec().into()], 1000)
@@ -911,10 +1622,92 @@ fn test_syncer_prefetch_prefixes() {
     assert_eq!(0, stats.sync_iterate_count, "sync_iterate count");
 }
 
+#[test]
+fn test_syncer_get_multi() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make()
+        .with_capacity(0, 0)
+        .new(Box::new(NoopReadSyncer));
+
+    let (keys, values) = generate_key_value_pairs();
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+
+    let (write_log, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, hash, Default::default(), 0);
+
+    let stats = StatsCollector::new(server.read_sync());
+    let remote_tree = Tree::make()
+        .with_capacity(0, 0)
+        .with_root(Root {
+            hash,
+            ..Default::default()
+        })
+        .new(Box::new(stats));
+
+    // Includes duplicates and a key that does not exist.
+    let lookup: Vec<&[u8]> = vec![
+        keys[5].as_slice(),
+        keys[500].as_slice(),
+        keys[5].as_slice(),
+        b"no such key",
+        keys[999].as_slice(),
+        keys[500].as_slice(),
+    ];
+    let result = remote_tree
+        .get_multi(Context::background(), &lookup)
+        .expect("get_multi");
+    assert_eq!(
+        vec![
+            Some(values[5].clone()),
+            Some(values[500].clone()),
+            Some(values[5].clone()),
+            None,
+            Some(values[999].clone()),
+            Some(values[500].clone()),
+        ],
+        result
+    );
+
+    {
+        let cache = remote_tree.cache.borrow();
+        let stats = cache
+            .get_read_syncer()
+            .as_any()
+            .downcast_ref::<StatsCollector>()
+            .expect("stats");
+        assert_eq!(1, stats.sync_get_multi_count, "sync_get_multi count");
+        assert_eq!(0, stats.sync_get_count, "sync_get count");
+        assert_eq!(0, stats.sync_get_prefixes_count, "sync_get_prefixes count");
+    }
+
+    // Everything is now resolved locally.
+    remote_tree
+        .get_multi(Context::background(), &lookup)
+        .expect("get_multi");
+    let cache = remote_tree.cache.borrow();
+    let stats = cache
+        .get_read_syncer()
+        .as_any()
+        .downcast_ref::<StatsCollector>()
+        .expect("stats");
+    assert_eq!(1, stats.sync_get_multi_count, "sync_get_multi count");
+    assert_eq!(0, stats.sync_get_count, "sync_get count");
+}
+
 #[test]
 fn test_value_eviction() {
+    let capacity = 16 * NODE_OVERHEAD + 512;
     let mut tree = Tree::make()
-        .with_capacity(0, 512)
+        .with_capacity(0, capacity)
         .new(Box::new(NoopReadSyncer));
 
     let (keys, values) = generate_key_value_pairs();
@@ -933,10 +1726,17 @@ fn test_value_eviction() {
         tree.cache.borrow().stats().internal_node_count,
         "cache.internal_node_count"
     );
-    // Only a subset of the leaf values should remain in cache.
+    // Only a subset of the leaf nodes should remain in cache. Keys and values
+    // are at most 9 bytes long each, so the cache should be filled up to that.
+    let leaf_value_size = tree.cache.borrow().stats().leaf_value_size;
+    assert!(leaf_value_size <= capacity, "cache.leaf_value_size");
+    assert!(
+        leaf_value_size > capacity - NODE_OVERHEAD - 18,
+        "cache.leaf_value_size"
+    );
     assert_eq!(
-        512,
-        tree.cache.borrow().stats().leaf_value_size,
+        leaf_value_size,
+        cached_leaves(&tree.cache.borrow().get_pending_root()).1,
         "cache.leaf_value_size"
     );
 }
@@ -975,13 +1775,144 @@ fn test_node_eviction() {
         tree.cache.borrow().stats().internal_node_count,
         "cache.internal_node_count"
     );
+    let (leaf_count, leaf_value_size) = cached_leaves(&tree.cache.borrow().get_pending_root());
+    assert_eq!(124, leaf_count, "cached leaf count");
     assert_eq!(
-        124,
+        leaf_value_size,
         tree.cache.borrow().stats().leaf_value_size,
         "cache.leaf_value_size"
     );
 }
 
+/// Count the leaf nodes resident in memory and their total cached size.
+fn cached_leaves(ptr: &NodePtrRef) -> (usize, usize) {
+    let node_ref = match ptr.borrow().node {
+        Some(ref node_ref) => node_ref.clone(),
+        None => return (0, 0),
+    };
+    let children = match *node_ref.borrow() {
+        NodeBox::Internal(ref n) => vec![n.leaf_node.clone(), n.left.clone(), n.right.clone()],
+        NodeBox::Leaf(_) => return (1, ptr.borrow().get_cached_size()),
+    };
+
+    children.iter().fold((0, 0), |(count, size), child| {
+        let (child_count, child_size) = cached_leaves(child);
+        (count + child_count, size + child_size)
+    })
+}
+
+#[test]
+fn test_cache_stats() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    let (keys, values) = generate_key_value_pairs();
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    let (write_log, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, hash, Default::default(), 0);
+
+    let events: Rc<RefCell<Vec<CacheEvent>>> = Rc::new(RefCell::new(Vec::new()));
+    let hook_events = events.clone();
+    let remote_tree = Tree::make()
+        .with_capacity(0, 0)
+        .with_cache_hook(Rc::new(move |event: CacheEvent| {
+            hook_events.borrow_mut().push(event)
+        }))
+        .with_root(Root {
+            hash,
+            ..Default::default()
+        })
+        .new(server.read_sync());
+
+    for _ in 0..2 {
+        for i in 0..keys.len() {
+            let value = remote_tree
+                .get(Context::background(), keys[i].as_slice())
+                .expect("get")
+                .expect("get_some");
+            assert_eq!(values[i], value.as_slice());
+        }
+    }
+
+    // Each key is fetched once and then served from the cache.
+    let stats = remote_tree.cache_stats();
+    assert_eq!(keys.len() as u64, stats.miss_count, "cache.miss_count");
+    assert_eq!(
+        keys.len() as u64,
+        stats.sync_fetch_count,
+        "cache.sync_fetch_count"
+    );
+    assert_eq!(0, stats.eviction_count, "cache.eviction_count");
+    assert!(stats.hit_count > 0, "cache.hit_count");
+
+    let count = |kind| {
+        events
+            .borrow()
+            .iter()
+            .filter(|event| **event == kind)
+            .count() as u64
+    };
+    assert_eq!(stats.hit_count, count(CacheEvent::Hit));
+    assert_eq!(stats.miss_count, count(CacheEvent::Miss));
+    assert_eq!(stats.sync_fetch_count, count(CacheEvent::SyncFetch));
+}
+
+#[test]
+fn test_cache_policies() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    let (keys, values) = generate_key_value_pairs();
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    let (write_log, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, hash, Default::default(), 0);
+
+    let policies: Vec<Rc<dyn CachePolicy>> = vec![
+        Rc::new(LRUPolicy),
+        Rc::new(SegmentedLRUPolicy),
+        Rc::new(ClockPolicy),
+    ];
+    let capacity = 16 * NODE_OVERHEAD + 512;
+    for policy in policies {
+        let remote_tree = Tree::make()
+            .with_capacity(0, capacity)
+            .with_cache_policy(policy)
+            .with_root(Root {
+                hash,
+                ..Default::default()
+            })
+            .new(server.read_sync());
+
+        // Interleave a hot key with a scan over all keys.
+        for i in 0..keys.len() {
+            for key in &[0, i] {
+                let value = remote_tree
+                    .get(Context::background(), keys[*key].as_slice())
+                    .expect("get")
+                    .expect("get_some");
+                assert_eq!(values[*key], value.as_slice());
+            }
+        }
+        assert!(remote_tree.cache.borrow().stats().leaf_value_size <= capacity);
+    }
+}
+
 /// Location of the test vectors directory (from Go).
 const TEST_VECTORS_DIR: &'static str = "../go/storage/mkvs/testdata";
 
@@ -1104,6 +2035,70 @@ fn test_special_case_from_json(fixture: &'static str) {
     }
 }
 
+/// Name of the encoding test vectors fixture, shared with Go.
+const ENCODING_TEST_VECTORS: &'static str = "encoding.json";
+
+#[test]
+fn test_encoding_test_vectors() {
+    let file = File::open(Path::new(TEST_VECTORS_DIR).join(ENCODING_TEST_VECTORS))
+        .expect("failed to open fixture");
+    let vectors: TestVectors =
+        serde_json::from_reader(BufReader::new(file)).expect("failed to parse fixture");
+    assert_eq!(
+        Tree::generate_test_vectors().expect("generate_test_vectors"),
+        vectors,
+        "encoding test vectors are out of date, regenerate them with generate_encoding_test_vectors"
+    );
+
+    for vector in &vectors.nodes {
+        let mut node = NodeBox::default();
+        let size = node
+            .unmarshal_binary(&vector.encoded)
+            .expect("unmarshal node");
+        assert_eq!(vector.encoded.len(), size);
+        node.update_hash();
+        assert_eq!(vector.hash, node.get_hash().as_ref().to_vec());
+        assert_eq!(vector.encoded, node.marshal_binary().expect("marshal node"));
+    }
+
+    for vector in &vectors.write_logs {
+        let write_log: WriteLog = cbor::from_slice(&vector.encoded).expect("decode write log");
+        let entries: WriteLog = vector
+            .entries
+            .iter()
+            .map(|entry| LogEntry {
+                key: entry.key.clone(),
+                value: entry.value.clone(),
+            })
+            .collect();
+        assert_eq!(entries, write_log);
+        assert_eq!(vector.encoded, cbor::to_vec(&write_log));
+    }
+
+    for vector in &vectors.proofs {
+        let proof: Proof = cbor::from_slice(&vector.encoded).expect("decode proof");
+        let root = Hash::from(&vector.root[..]);
+        ProofVerifier
+            .verify_proof(Context::background(), root, &proof)
+            .expect("verify proof");
+        assert_eq!(vector.encoded, cbor::to_vec(&proof));
+    }
+}
+
+/// Regenerate the encoding test vectors fixture.
+#[test]
+#[ignore]
+fn generate_encoding_test_vectors() {
+    let vectors = Tree::generate_test_vectors().expect("generate_test_vectors");
+    let mut data = serde_json::to_string_pretty(&vectors).expect("serialize test vectors");
+    data.push('\n');
+    std::fs::write(
+        Path::new(TEST_VECTORS_DIR).join(ENCODING_TEST_VECTORS),
+        data,
+    )
+    .expect("failed to write fixture");
+}
+
 #[test]
 fn test_special_case_1() {
     test_special_case_from_json("case-1.json")
@@ -1128,3 +2123,199 @@ fn test_special_case_4() {
 fn test_special_case_5() {
     test_special_case_from_json("case-5.json")
 }
+
+#[test]
+fn test_export_import_subtree() {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    for prefix in &["a/", "b/"] {
+        let (keys, values) = generate_key_value_pairs_ex(prefix.to_string(), 50);
+        for (key, value) in keys.iter().zip(values.iter()) {
+            tree.insert(Context::background(), key, value)
+                .expect("insert");
+        }
+    }
+    let err = tree
+        .export_subtree(Context::background(), b"a/")
+        .expect_err("export with uncommitted changes should fail");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::UncommittedChanges)
+    ));
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    // Only keys with the prefix are exported.
+    let data = tree
+        .export_subtree(Context::background(), b"a/")
+        .expect("export_subtree");
+    assert_eq!(SUBTREE_EXPORT_VERSION, data[0]);
+    let mut imported = Tree::make().new(Box::new(NoopReadSyncer));
+    imported
+        .import_subtree(Context::background(), &data)
+        .expect("import_subtree");
+    let mut it = imported.iter(Context::background());
+    it.rewind();
+    let items: Vec<_> = it.collect();
+    assert_eq!(50, items.len());
+    for (key, value) in items {
+        assert!(key.starts_with(b"a/"));
+        assert_eq!(
+            Some(value),
+            tree.get(Context::background(), &key).expect("get")
+        );
+    }
+
+    // Exporting with an empty prefix exports everything.
+    let data = tree
+        .export_subtree(Context::background(), b"")
+        .expect("export_subtree");
+    let mut imported = Tree::make().new(Box::new(NoopReadSyncer));
+    imported
+        .import_subtree(Context::background(), &data)
+        .expect("import_subtree");
+    let (_, hash) =
+        Tree::commit(&mut imported, Context::background(), Default::default(), 0).expect("commit");
+    assert_eq!(tree.cache.borrow().get_pending_root().borrow().hash, hash);
+
+    // Malformed exports are rejected without modifying the tree.
+    let mut imported = Tree::make().new(Box::new(NoopReadSyncer));
+    assert!(imported
+        .import_subtree(Context::background(), &data[..data.len() - 1])
+        .is_err());
+    assert!(imported
+        .import_subtree(Context::background(), &[SUBTREE_EXPORT_VERSION + 1])
+        .is_err());
+    assert!(imported.import_subtree(Context::background(), &[]).is_err());
+    assert!(imported.pending_write_log.is_empty());
+}
+
+#[test]
+fn test_dump() {
+    let make_tree = || {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        let (keys, values) = generate_key_value_pairs_ex("".to_string(), 10);
+        for (key, value) in keys.iter().zip(values.iter()) {
+            tree.insert(Context::background(), key, value)
+                .expect("insert");
+        }
+        let (_, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        (tree, hash)
+    };
+    let (tree, hash) = make_tree();
+
+    let dot = tree.dump(Context::background(), Format::Dot).expect("dump");
+    assert!(dot.starts_with("digraph mkvs {\n"));
+    assert!(dot.contains(&format!("{}", hash)));
+    for key in generate_key_value_pairs_ex("".to_string(), 10).0 {
+        assert!(dot.contains(&key.to_hex::<String>()));
+    }
+
+    let json = tree
+        .dump(Context::background(), Format::Json)
+        .expect("dump");
+    let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+    assert_eq!(Some(format!("{:x}", hash).as_str()), value["hash"].as_str());
+
+    // Dumps are deterministic.
+    let (other, _) = make_tree();
+    assert_eq!(
+        dot,
+        other
+            .dump(Context::background(), Format::Dot)
+            .expect("dump")
+    );
+
+    // Nodes which are not cached are not fetched.
+    let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+    let remote = Tree::make()
+        .with_root(Root {
+            hash,
+            ..Default::default()
+        })
+        .new(Box::new(rs));
+    let json = remote
+        .dump(Context::background(), Format::Json)
+        .expect("dump");
+    let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+    assert_eq!(serde_json::json!({ "hash": format!("{:x}", hash) }), value);
+}
+
+#[test]
+fn test_root_type() {
+    let root = Root {
+        root_type: RootType::IO,
+        hash: Hash::empty_hash(),
+        ..Default::default()
+    };
+
+    // Roots of a different type are rejected.
+    let err = Tree::make()
+        .with_root_type(RootType::State)
+        .with_root(root)
+        .try_new(Box::new(NoopReadSyncer))
+        .expect_err("mixing root types should fail");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::RootTypeMismatch {
+            expected: RootType::State,
+            got: RootType::IO,
+        })
+    ));
+
+    // Untyped roots are assumed to be of the expected type and the type is
+    // kept when committing.
+    let mut tree = Tree::make()
+        .with_root_type(RootType::State)
+        .with_root(Root {
+            root_type: RootType::Invalid,
+            ..root
+        })
+        .try_new(Box::new(NoopReadSyncer))
+        .expect("try_new");
+    assert_eq!(RootType::State, tree.root_type());
+    tree.insert(Context::background(), b"key", b"value")
+        .expect("insert");
+    Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+    assert_eq!(
+        RootType::State,
+        tree.cache.borrow().get_sync_root().root_type
+    );
+
+    // Without an expected type, the type of the root is used.
+    let tree = Tree::make()
+        .with_root(root)
+        .try_new(Box::new(NoopReadSyncer))
+        .expect("try_new");
+    assert_eq!(RootType::IO, tree.root_type());
+}
+
+#[test]
+fn test_readonly() {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+    let (keys, values) = generate_key_value_pairs();
+    for (key, value) in keys.iter().zip(values.iter()) {
+        tree.insert(Context::background(), key, value)
+            .expect("insert");
+    }
+    let (_, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let root = Root {
+        root_type: RootType::State,
+        hash,
+        ..Default::default()
+    };
+
+    let rs = MapReadSync::from_tree(Context::background(), &tree).expect("from_tree");
+    let readonly = Tree::open_readonly(Box::new(rs), root);
+    assert_eq!(RootType::State, readonly.root_type());
+    for (key, value) in keys.iter().zip(values.iter()) {
+        assert_eq!(
+            Some(value.clone()),
+            readonly.get(Context::background(), key).expect("get")
+        );
+    }
+
+    let mut it = readonly.iter(Context::background());
+    it.rewind();
+    assert_eq!(keys.len(), it.count());
+}
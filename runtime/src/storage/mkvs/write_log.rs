@@ -0,0 +1,263 @@
+//! Write log helpers.
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use anyhow::{anyhow, Result};
+use io_context::Context;
+
+use crate::storage::mkvs::{LogEntry, Tree, WriteLog};
+
+/// Operations on write logs.
+pub trait WriteLogExt {
+    /// Apply all updates of the write log to the given tree.
+    ///
+    /// The changes are not committed.
+    fn apply(&self, ctx: Context, tree: &mut Tree) -> Result<()>;
+
+    /// Merge a subsequent write log into this one, so that applying the
+    /// result has the same effect as applying both logs in order.
+    ///
+    /// The resulting entries are sorted by key, like the write logs returned
+    /// by `Tree::commit`.
+    fn merge(&mut self, other: WriteLog);
+
+    /// Compute the write log undoing this write log when applied after it.
+    ///
+    /// The given tree must be in the state the write log was applied to.
+    fn invert(&self, ctx: Context, tree_before: &Tree) -> Result<WriteLog>;
+
+    /// Compute the minimal write log transforming the contents of one tree
+    /// into the contents of another.
+    ///
+    /// Any parts of the trees that are not in their local caches will be
+    /// fetched from the read syncers.
+    fn diff(ctx: Context, from: &Tree, to: &Tree) -> Result<WriteLog>
+    where
+        Self: Sized;
+}
+
+impl WriteLogExt for WriteLog {
+    fn apply(&self, ctx: Context, tree: &mut Tree) -> Result<()> {
+        let ctx = ctx.freeze();
+        for entry in self {
+            match entry.value {
+                Some(ref value) => {
+                    tree.insert(Context::create_child(&ctx), &entry.key, value)?;
+                }
+                None => {
+                    tree.remove(Context::create_child(&ctx), &entry.key)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn merge(&mut self, other: WriteLog) {
+        let mut entries: BTreeMap<Vec<u8>, Option<Vec<u8>>> = BTreeMap::new();
+        for entry in self.drain(..).chain(other.into_iter()) {
+            entries.insert(entry.key, entry.value);
+        }
+
+        self.extend(
+            entries
+                .into_iter()
+                .map(|(key, value)| LogEntry { key, value }),
+        );
+    }
+
+    fn invert(&self, ctx: Context, tree_before: &Tree) -> Result<WriteLog> {
+        let ctx = ctx.freeze();
+        let mut undo_log = WriteLog::with_capacity(self.len());
+        for entry in self {
+            let value = tree_before.get(Context::create_child(&ctx), &entry.key)?;
+            undo_log.push(LogEntry {
+                key: entry.key.clone(),
+                value,
+            });
+        }
+
+        Ok(undo_log)
+    }
+
+    fn diff(ctx: Context, from: &Tree, to: &Tree) -> Result<WriteLog> {
+        let ctx = ctx.freeze();
+        let mut from_it = from.iter(Context::create_child(&ctx));
+        from_it.rewind();
+        let mut to_it = to.iter(Context::create_child(&ctx));
+        to_it.rewind();
+
+        let mut log = WriteLog::new();
+        let mut from_items = from_it.by_ref().peekable();
+        let mut to_items = to_it.by_ref().peekable();
+        loop {
+            let ordering = match (from_items.peek(), to_items.peek()) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((from_key, _)), Some((to_key, _))) => from_key.cmp(to_key),
+            };
+            match ordering {
+                Ordering::Less => {
+                    // Key has been removed.
+                    let (key, _) = from_items.next().expect("item has been peeked");
+                    log.push(LogEntry { key, value: None });
+                }
+                Ordering::Greater => {
+                    // Key has been inserted.
+                    let (key, value) = to_items.next().expect("item has been peeked");
+                    log.push(LogEntry::new(&key, &value));
+                }
+                Ordering::Equal => {
+                    // Key may have been updated.
+                    let (_, from_value) = from_items.next().expect("item has been peeked");
+                    let (key, value) = to_items.next().expect("item has been peeked");
+                    if from_value != value {
+                        log.push(LogEntry::new(&key, &value));
+                    }
+                }
+            }
+        }
+
+        // Iteration stops early on errors.
+        if let Some(error) = from_it.error().as_ref().or(to_it.error().as_ref()) {
+            return Err(anyhow!("mkvs: failed to iterate over tree: {}", error));
+        }
+
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::sync::NoopReadSyncer;
+
+    #[test]
+    fn test_write_log_apply() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..20 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        let (first_log, _) = tree
+            .commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+        for i in 10..30 {
+            tree.remove(Context::background(), format!("key {}", i).as_bytes())
+                .expect("remove");
+        }
+        tree.insert(Context::background(), b"key 0", b"updated")
+            .expect("insert");
+        let (second_log, root) = tree
+            .commit(Context::background(), Default::default(), 1)
+            .expect("commit");
+
+        // Replaying the logs in order should reproduce the tree.
+        let mut replayed = Tree::make().new(Box::new(NoopReadSyncer));
+        first_log
+            .apply(Context::background(), &mut replayed)
+            .expect("apply");
+        replayed
+            .commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+        second_log
+            .apply(Context::background(), &mut replayed)
+            .expect("apply");
+        let (_, replayed_root) = replayed
+            .commit(Context::background(), Default::default(), 1)
+            .expect("commit");
+        assert_eq!(root, replayed_root);
+
+        // Replaying the merged log should result in the same contents.
+        let mut merged = first_log.clone();
+        merged.merge(second_log);
+        assert_eq!(20, merged.len());
+        assert_eq!(
+            Some(&LogEntry {
+                key: b"key 0".to_vec(),
+                value: Some(b"updated".to_vec()),
+            }),
+            merged.first()
+        );
+        let mut replayed = Tree::make().new(Box::new(NoopReadSyncer));
+        merged
+            .apply(Context::background(), &mut replayed)
+            .expect("apply");
+        let items = |tree: &Tree| {
+            let mut it = tree.iter(Context::background());
+            it.rewind();
+            it.collect::<Vec<_>>()
+        };
+        assert_eq!(items(&tree), items(&replayed));
+    }
+
+    #[test]
+    fn test_write_log_invert_diff() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        for i in 0..20 {
+            tree.insert(
+                Context::background(),
+                format!("key {}", i).as_bytes(),
+                format!("value {}", i).as_bytes(),
+            )
+            .expect("insert");
+        }
+        tree.commit(Context::background(), Default::default(), 0)
+            .expect("commit");
+        let mut before = Tree::make().new(Box::new(NoopReadSyncer));
+        let mut it = tree.iter(Context::background());
+        it.rewind();
+        for (key, value) in it {
+            before
+                .insert(Context::background(), &key, &value)
+                .expect("insert");
+        }
+
+        let write_log = vec![
+            LogEntry::new(b"key 0", b"updated"),
+            LogEntry::new(b"key 5", b"value 5"),
+            LogEntry::new(b"new key", b"new value"),
+            LogEntry {
+                key: b"key 7".to_vec(),
+                value: None,
+            },
+        ];
+
+        // The diff between the trees only contains actual changes.
+        write_log
+            .apply(Context::background(), &mut tree)
+            .expect("apply");
+        let diff = WriteLog::diff(Context::background(), &before, &tree).expect("diff");
+        assert_eq!(
+            vec![
+                LogEntry::new(b"key 0", b"updated"),
+                LogEntry {
+                    key: b"key 7".to_vec(),
+                    value: None,
+                },
+                LogEntry::new(b"new key", b"new value"),
+            ],
+            diff
+        );
+        assert!(WriteLog::diff(Context::background(), &tree, &tree)
+            .expect("diff")
+            .is_empty());
+
+        // Applying the inverted write log restores the previous state.
+        let undo_log = write_log
+            .invert(Context::background(), &before)
+            .expect("invert");
+        undo_log
+            .apply(Context::background(), &mut tree)
+            .expect("apply");
+        assert!(WriteLog::diff(Context::background(), &before, &tree)
+            .expect("diff")
+            .is_empty());
+    }
+}
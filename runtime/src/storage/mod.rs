@@ -1,13 +1,18 @@
 //! Runtime storage interfaces and implementations.
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::Result;
 
 pub mod context;
 pub mod mkvs;
+pub mod typed;
 
 // Re-exports.
-pub use self::{context::StorageContext, mkvs::MKVS};
+pub use self::{
+    context::{StorageContext, StorageContextHandle},
+    mkvs::MKVS,
+    typed::TypedStore,
+};
 
 /// Trivial Key/Value storage.
 pub trait KeyValue: Send + Sync {
@@ -16,6 +21,24 @@ pub trait KeyValue: Send + Sync {
 
     /// Store a specific key/value into storage.
     fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()>;
+
+    /// Fetch the values for multiple keys.
+    ///
+    /// The default implementation fetches each key separately.
+    fn get_batch(&self, keys: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>> {
+        keys.into_iter().map(|key| self.get(key)).collect()
+    }
+
+    /// Store multiple key/values into storage, either all or none of them.
+    ///
+    /// The default implementation stores each key/value separately, so it
+    /// is not atomic.
+    fn insert_batch(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        for (key, value) in items {
+            self.insert(key, value)?;
+        }
+        Ok(())
+    }
 }
 
 impl<T: ?Sized + KeyValue> KeyValue for Arc<T> {
@@ -26,4 +49,132 @@ impl<T: ?Sized + KeyValue> KeyValue for Arc<T> {
     fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
         KeyValue::insert(&**self, key, value)
     }
+
+    fn get_batch(&self, keys: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>> {
+        KeyValue::get_batch(&**self, keys)
+    }
+
+    fn insert_batch(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        KeyValue::insert_batch(&**self, items)
+    }
+}
+
+/// Extension trait for Key/Value storage.
+pub trait KeyValueExt: KeyValue {
+    /// Run a closure in a transaction.
+    ///
+    /// All writes are buffered until the closure returns, and are then
+    /// stored via a single `insert_batch` if it succeeded or discarded if
+    /// it failed.
+    fn transaction<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Transaction<Self>) -> Result<R>;
+}
+
+impl<T: ?Sized + KeyValue> KeyValueExt for T {
+    fn transaction<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Transaction<Self>) -> Result<R>,
+    {
+        let mut txn = Transaction {
+            store: self,
+            writes: BTreeMap::new(),
+        };
+        let result = f(&mut txn)?;
+        if !txn.writes.is_empty() {
+            self.insert_batch(txn.writes.into_iter().collect())?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// A Key/Value storage transaction, see `KeyValueExt::transaction`.
+pub struct Transaction<'a, S: ?Sized + KeyValue> {
+    store: &'a S,
+    writes: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl<'a, S: ?Sized + KeyValue> Transaction<'a, S> {
+    /// Fetch the value for a specific key, including any writes made in
+    /// the transaction.
+    pub fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
+        match self.writes.get(&key) {
+            Some(value) => Ok(value.clone()),
+            None => self.store.get(key),
+        }
+    }
+
+    /// Store a specific key/value once the transaction succeeds.
+    pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        self.writes.insert(key, value);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    /// In-memory Key/Value storage.
+    #[derive(Default)]
+    pub(crate) struct MemoryKeyValue(pub(crate) Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+    impl KeyValue for MemoryKeyValue {
+        fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .get(&key)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+            self.0.lock().unwrap().insert(key, value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_batch() {
+        let store = MemoryKeyValue::default();
+        store
+            .insert_batch(vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec()),
+            ])
+            .unwrap();
+        assert_eq!(
+            vec![b"1".to_vec(), b"2".to_vec(), vec![]],
+            store
+                .get_batch(vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_transaction() {
+        let store = MemoryKeyValue::default();
+        let value = store
+            .transaction(|txn| {
+                txn.insert(b"a".to_vec(), b"1".to_vec());
+                txn.get(b"a".to_vec())
+            })
+            .unwrap();
+        assert_eq!(b"1".to_vec(), value);
+        assert_eq!(b"1".to_vec(), store.get(b"a".to_vec()).unwrap());
+
+        // Writes of failed transactions are discarded.
+        let result: Result<()> = store.transaction(|txn| {
+            txn.insert(b"b".to_vec(), b"2".to_vec());
+            Err(anyhow!("aborted"))
+        });
+        assert!(result.is_err());
+        assert!(store.get(b"b".to_vec()).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,167 @@
+//! Typed key/value storage.
+use anyhow::Result;
+use rand::{rngs::OsRng, Rng};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use super::KeyValue;
+use crate::common::{
+    cbor,
+    crypto::{
+        mrae::deoxysii::{DeoxysII, KEY_SIZE, NONCE_SIZE, TAG_SIZE},
+        secret::Secret,
+    },
+};
+
+/// Errors returned by typed store operations.
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("typed store: value is corrupted")]
+    CorruptedValue,
+}
+
+/// Typed wrapper around a `KeyValue` store.
+///
+/// Values are serialized as CBOR and all keys are prefixed with a namespace,
+/// so multiple components can share the same store. Values may optionally be
+/// encrypted with Deoxys-II under a key provided by the runtime, using the
+/// namespaced key as additional data so that values can not be moved between
+/// keys.
+///
+/// As with the underlying store, a missing key is represented by an empty
+/// value.
+pub struct TypedStore<S: KeyValue> {
+    inner: S,
+    prefix: Vec<u8>,
+    key: Option<Secret<{ KEY_SIZE }>>,
+}
+
+impl<S: KeyValue> TypedStore<S> {
+    /// Create a new typed store on top of the given store.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            prefix: Vec::new(),
+            key: None,
+        }
+    }
+
+    /// Append the given namespace to the prefix of all keys.
+    pub fn with_prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix.extend_from_slice(prefix);
+        self
+    }
+
+    /// Encrypt all values with the given key.
+    pub fn with_encryption_key(mut self, key: &[u8; KEY_SIZE]) -> Self {
+        self.key = Some(Secret::new(*key));
+        self
+    }
+
+    /// Return a reference to the underlying store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn namespaced_key(&self, key: &[u8]) -> Vec<u8> {
+        let mut namespaced = self.prefix.clone();
+        namespaced.extend_from_slice(key);
+        namespaced
+    }
+
+    /// Fetch the raw value for a specific key.
+    pub fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let key = self.namespaced_key(key);
+        let value = self.inner.get(key.clone())?;
+        if value.is_empty() {
+            return Ok(None);
+        }
+
+        let d2 = match self.key {
+            Some(ref d2_key) => DeoxysII::new(d2_key.as_bytes()),
+            None => return Ok(Some(value)),
+        };
+        if value.len() < TAG_SIZE + NONCE_SIZE {
+            return Err(Error::CorruptedValue.into());
+        }
+
+        // Split the ciphertext || tag || nonce.
+        let ct_len = value.len() - NONCE_SIZE;
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&value[ct_len..]);
+        let plaintext = d2
+            .open(&nonce, value[..ct_len].to_vec(), key)
+            .map_err(|_| Error::CorruptedValue)?;
+
+        Ok(Some(plaintext))
+    }
+
+    /// Store a raw value for a specific key.
+    pub fn insert_raw(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let key = self.namespaced_key(key);
+        let value = match self.key {
+            Some(ref d2_key) => {
+                let mut nonce = [0u8; NONCE_SIZE];
+                OsRng {}.fill(&mut nonce);
+                let d2 = DeoxysII::new(d2_key.as_bytes());
+                let mut ciphertext = d2.seal(&nonce, value.to_vec(), key.clone());
+                ciphertext.extend_from_slice(&nonce);
+                ciphertext
+            }
+            None => value.to_vec(),
+        };
+
+        self.inner.insert(key, value)
+    }
+
+    /// Fetch and deserialize the value for a specific key.
+    pub fn get_cbor<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>> {
+        match self.get_raw(key)? {
+            Some(value) => Ok(Some(cbor::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Serialize and store a value for a specific key.
+    pub fn insert_cbor<T: Serialize>(&self, key: &[u8], value: &T) -> Result<()> {
+        self.insert_raw(key, &cbor::to_vec(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::test::MemoryKeyValue;
+
+    #[test]
+    fn test_typed_store() {
+        let store = TypedStore::new(MemoryKeyValue::default()).with_prefix(b"test/");
+        assert_eq!(None, store.get_cbor::<u64>(b"key").unwrap());
+
+        store.insert_cbor(b"key", &42u64).unwrap();
+        assert_eq!(Some(42u64), store.get_cbor(b"key").unwrap());
+        assert_eq!(
+            cbor::to_vec(&42u64),
+            store.inner().get(b"test/key".to_vec()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_typed_store_encryption() {
+        let store = TypedStore::new(MemoryKeyValue::default())
+            .with_prefix(b"test/")
+            .with_encryption_key(&[1u8; KEY_SIZE]);
+
+        store.insert_cbor(b"key", &"secret".to_owned()).unwrap();
+        assert_eq!(Some("secret".to_owned()), store.get_cbor(b"key").unwrap());
+        let ciphertext = store.inner().get(b"test/key".to_vec()).unwrap();
+        assert_ne!(cbor::to_vec(&"secret".to_owned()), ciphertext);
+
+        // Values can not be moved between keys.
+        store
+            .inner()
+            .insert(b"test/other".to_vec(), ciphertext)
+            .unwrap();
+        assert!(store.get_cbor::<String>(b"other").is_err());
+    }
+}
@@ -1,10 +1,33 @@
 //! Runtime call context.
-use std::{any::Any, sync::Arc};
+use std::{any::Any, mem, sync::Arc};
 
+use anyhow::Result;
 use io_context::Context as IoContext;
+use thiserror::Error;
 
-use super::tags::{Tag, Tags};
-use crate::common::roothash::{Header, Message};
+use super::{
+    events::{Event, Events},
+    gas::GasMeter,
+    tags::{Tag, Tags},
+};
+use crate::common::{
+    crypto::{kdf, secret::Secret},
+    quantity::Quantity,
+    roothash::{Header, IncomingMessage, Message, MessageEvent},
+    staking::Address,
+};
+
+/// Domain separation label of the entropy of a batch.
+const BATCH_ENTROPY_LABEL: &[u8] = b"oasis-core/runtime: batch entropy";
+/// Domain separation label of random bytes drawn by a transaction.
+const RANDOM_BYTES_LABEL: &[u8] = b"oasis-core/runtime: random bytes";
+
+/// Context errors.
+#[derive(Error, Debug)]
+pub enum ContextError {
+    #[error("context: consensus entropy not available")]
+    EntropyNotAvailable,
+}
 
 struct NoRuntimeContext;
 
@@ -21,11 +44,31 @@ pub struct Context<'a> {
     /// running the transaction.
     pub check_only: bool,
 
+    /// Gas meter of the transaction which is being processed.
+    ///
+    /// Unless the dispatcher meters gas, the meter is unlimited.
+    pub gas: GasMeter,
+
     /// List of emitted tags for each transaction.
     tags: Vec<Tags>,
 
+    /// List of emitted events for each transaction.
+    events: Vec<Events>,
+
     /// List of messages emitted.
     messages: Vec<Message>,
+    /// Results of the messages contained in the block header.
+    message_results: Vec<MessageEvent>,
+    /// Incoming messages queued for the runtime.
+    incoming_messages: Vec<IncomingMessage>,
+
+    /// Entropy of the batch, derived from the consensus beacon.
+    entropy: Option<[u8; 32]>,
+    /// Index of the transaction which is being processed.
+    txn_index: u64,
+    /// Number of random draws made by the transaction which is being
+    /// processed.
+    random_draws: u64,
 }
 
 impl<'a> Context<'a> {
@@ -36,19 +79,106 @@ impl<'a> Context<'a> {
             header,
             runtime: Box::new(NoRuntimeContext),
             check_only,
+            gas: GasMeter::unlimited(),
             tags: Vec::new(),
+            events: Vec::new(),
             messages: Vec::new(),
+            message_results: Vec::new(),
+            incoming_messages: Vec::new(),
+            entropy: None,
+            txn_index: 0,
+            random_draws: 0,
         }
     }
 
+    /// Set the consensus beacon from which the randomness returned by
+    /// `random_bytes` is derived.
+    ///
+    /// The beacon is bound to the namespace and round of the block header,
+    /// so that every batch gets different randomness.
+    pub fn set_beacon(&mut self, beacon: &[u8]) {
+        let entropy: Secret<32> = kdf::kmac256(
+            beacon,
+            BATCH_ENTROPY_LABEL,
+            &[
+                self.header.namespace.as_ref(),
+                &self.header.round.to_be_bytes(),
+            ],
+        );
+        self.entropy = Some(*entropy.as_bytes());
+    }
+
     /// Start a new transaction.
     pub fn start_transaction(&mut self) {
+        self.txn_index = self.tags.len() as u64;
+        self.random_draws = 0;
         self.tags.push(Tags::new());
+        self.events.push(Events::new());
+    }
+
+    /// Override the index of the transaction which is being processed, for
+    /// transactions executed in their own context.
+    pub(crate) fn set_transaction_index(&mut self, index: u64) {
+        self.txn_index = index;
+    }
+
+    /// Return the entropy of the batch, if any.
+    pub(crate) fn entropy(&self) -> Option<[u8; 32]> {
+        self.entropy
+    }
+
+    /// Set the entropy of the batch.
+    pub(crate) fn set_entropy(&mut self, entropy: Option<[u8; 32]>) {
+        self.entropy = entropy;
+    }
+
+    /// Return the number of messages emitted so far.
+    pub(crate) fn message_count(&self) -> usize {
+        self.messages.len()
     }
 
-    /// Close the context and return the emitted tags and sent roothash messages.
-    pub fn close(self) -> (Vec<Tags>, Vec<Message>) {
-        (self.tags, self.messages)
+    /// Discard the messages emitted after the given number of messages, e.g.
+    /// by a transaction which failed.
+    pub(crate) fn discard_messages(&mut self, count: usize) {
+        self.messages.truncate(count);
+    }
+
+    /// Set the results of the messages contained in the block header.
+    pub(crate) fn set_message_results(&mut self, results: Vec<MessageEvent>) {
+        self.message_results = results;
+    }
+
+    /// Set the incoming messages queued for the runtime.
+    pub(crate) fn set_incoming_messages(&mut self, messages: Vec<IncomingMessage>) {
+        self.incoming_messages = messages;
+    }
+
+    /// Take the incoming messages queued for the runtime, leaving none.
+    pub(crate) fn take_incoming_messages(&mut self) -> Vec<IncomingMessage> {
+        mem::take(&mut self.incoming_messages)
+    }
+
+    /// Return the messages contained in the block header (i.e. emitted in
+    /// the previous round), together with the results of their execution.
+    ///
+    /// Results are only available when executing a batch, they are not
+    /// provided when checking transactions.
+    pub fn message_results(&self) -> Vec<(&Message, &MessageEvent)> {
+        let messages = self.header.messages.as_deref().unwrap_or_default();
+        self.message_results
+            .iter()
+            .filter_map(|result| {
+                messages
+                    .get(result.index as usize)
+                    .map(|message| (message, result))
+            })
+            .collect()
+    }
+
+    /// Close the context and return the emitted tags, emitted events and sent
+    /// roothash messages.
+    pub fn close(self) -> (Vec<Tags>, Vec<Events>, Vec<Message>) {
+        (self.tags, self.events, self.messages)
     }
 
     /// Emit a runtime-specific indexable tag refering to the specific
@@ -77,9 +207,146 @@ impl<'a> Context<'a> {
             .push(Tag::new(key.as_ref().to_vec(), value.as_ref().to_vec()))
     }
 
+    /// Emit an event refering to the specific transaction which is being
+    /// processed.
+    ///
+    /// Unlike tags, all emitted events are indexed, in emission order.
+    ///
+    /// # Panics
+    ///
+    /// Calling this method outside of a transaction will panic.
+    ///
+    pub fn emit_event(&mut self, event: Event) {
+        assert!(
+            !self.events.is_empty(),
+            "must only be called inside a transaction"
+        );
+
+        self.events
+            .last_mut()
+            .expect("events is not empty")
+            .push(event)
+    }
+
+    /// Return `n` random bytes for the transaction which is being processed.
+    ///
+    /// The bytes are derived from the consensus beacon (see `set_beacon`),
+    /// the index of the transaction in the batch and the number of previous
+    /// draws by the transaction, so all executors of a batch get the same
+    /// bytes. As the beacon is public, the bytes must not be used as
+    /// secrets.
+    ///
+    /// # Panics
+    ///
+    /// Calling this method outside of a transaction will panic.
+    ///
+    pub fn random_bytes(&mut self, n: usize) -> Result<Vec<u8>> {
+        assert!(
+            !self.tags.is_empty(),
+            "must only be called inside a transaction"
+        );
+        let entropy = self.entropy.ok_or(ContextError::EntropyNotAvailable)?;
+
+        let mut result = vec![0; n];
+        kdf::cshake256_into(
+            RANDOM_BYTES_LABEL,
+            &[
+                &entropy,
+                &self.txn_index.to_be_bytes(),
+                &self.random_draws.to_be_bytes(),
+            ],
+            &mut result,
+        );
+        self.random_draws += 1;
+        Ok(result)
+    }
+
     /// Send a roothash message as part of the block that contains this transaction.
     /// See RFC 0065 for information on roothash messages.
+    ///
+    /// Messages emitted by a transaction which fails are discarded. The
+    /// number of messages in a block is limited by the runtime descriptor
+    /// (`max_messages`), and a block which exceeds it is rejected.
     pub fn send_roothash_message(&mut self, message: Message) {
         self.messages.push(message);
     }
+
+    /// Transfer tokens from the runtime account to the given account.
+    ///
+    /// The transfer is executed by the consensus layer once the block is
+    /// finalized, and its result is available in the following round (see
+    /// `message_results`).
+    pub fn emit_staking_transfer(&mut self, to: Address, amount: Quantity) {
+        self.send_roothash_message(Message::staking_transfer(to, amount));
+    }
+
+    /// Withdraw tokens from the given account to the runtime account, using
+    /// an allowance given to the runtime account.
+    ///
+    /// The withdrawal is executed by the consensus layer once the block is
+    /// finalized, and its result is available in the following round (see
+    /// `message_results`).
+    pub fn emit_staking_withdraw(&mut self, from: Address, amount: Quantity) {
+        self.send_roothash_message(Message::staking_withdraw(from, amount));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_random_bytes() {
+        let header = Header::default();
+        let draw = |beacon: &[u8]| {
+            let mut ctx = Context::new(IoContext::background().freeze(), &header, false);
+            ctx.set_beacon(beacon);
+            let mut draws = Vec::new();
+            for _ in 0..2 {
+                ctx.start_transaction();
+                draws.push(ctx.random_bytes(16).unwrap());
+                draws.push(ctx.random_bytes(32).unwrap());
+            }
+            draws
+        };
+
+        // Draws are deterministic, and differ between draws, transactions
+        // and beacons.
+        let draws = draw(b"beacon");
+        assert_eq!(draws, draw(b"beacon"));
+        assert_eq!(16, draws[0].len());
+        assert_eq!(32, draws[1].len());
+        assert_ne!(draws[0], &draws[1][..16]);
+        assert_ne!(draws[0], draws[2]);
+        assert_ne!(draws, draw(b"other beacon"));
+
+        let mut ctx = Context::new(IoContext::background().freeze(), &header, false);
+        ctx.start_transaction();
+        assert!(ctx.random_bytes(16).is_err());
+    }
+
+    #[test]
+    fn test_message_results() {
+        let header = Header {
+            messages: Some(vec![
+                Message::staking_transfer(Default::default(), Quantity(1)),
+                Message::staking_withdraw(Default::default(), Quantity(2)),
+            ]),
+            ..Default::default()
+        };
+        let mut ctx = Context::new(IoContext::background().freeze(), &header, false);
+        assert!(ctx.message_results().is_empty());
+
+        let failed = MessageEvent {
+            module: "staking".to_owned(),
+            code: 3,
+            index: 1,
+        };
+        ctx.set_message_results(vec![MessageEvent::default(), failed.clone()]);
+        let results = ctx.message_results();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, &header.messages.as_ref().unwrap()[0]);
+        assert!(results[0].1.is_success());
+        assert_eq!(results[1], (&header.messages.as_ref().unwrap()[1], &failed));
+    }
 }
@@ -1,28 +1,57 @@
 //! Runtime transaction batch dispatcher.
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    panic,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
 };
 
 use anyhow::{anyhow, Context as AnyContext, Result};
+use io_context::Context as IoContext;
+use lazy_static::lazy_static;
 use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
 
 use super::{
     context::Context,
+    events::Events,
+    gas::{Gas, GasConfig, GasCosts, GasError, GasMeter},
+    speculative::SpeculativeState,
     tags::Tags,
-    types::{TxnBatch, TxnCall, TxnCheckResult, TxnOutput},
+    types::{ScheduleStats, TxnBatch, TxnCall, TxnCheckResult, TxnOutput},
 };
-use crate::common::{cbor, crypto::hash::Hash, roothash::Message as RoothashMessage};
+use crate::{
+    common::{
+        cbor,
+        crypto::hash::Hash,
+        metrics::{self, Counter},
+        roothash::{Header, IncomingMessage, Message as RoothashMessage, MessageEvent},
+        schema::{MethodInfo, Schema},
+    },
+    storage::{
+        mkvs::{Access, AccessTracker},
+        KeyValue, StorageContext, StorageContextHandle,
+    },
+};
+
+lazy_static! {
+    static ref PARALLEL_CONFLICTS: Arc<Counter> = metrics::registry().counter(
+        "oasis_runtime_txn_parallel_conflicts",
+        "Number of transactions re-executed due to conflicts during parallel execution.",
+    );
+}
 
 /// Dispatch error.
 #[derive(Error, Debug)]
 enum DispatchError {
     #[error("method not found: {method:?}")]
     MethodNotFound { method: String },
+    #[error("invalid batch schedule")]
+    InvalidSchedule,
+    #[error("incoming messages out of order")]
+    IncomingMessagesOutOfOrder,
 }
 
 /// Error indicating that performing a transaction check was successful.
@@ -45,15 +74,174 @@ pub trait BatchHandler {
     fn end_batch(&self, ctx: &mut Context);
 }
 
+/// Custom roothash message result handler.
+///
+/// A message result handler can be configured on the `Dispatcher` to handle
+/// the results of the roothash messages emitted in the previous round (e.g.,
+/// to refund a failed transfer). It is called once for each message, in
+/// emission order, before the batch is scheduled.
+pub trait MessageResultHandler {
+    /// Called to handle the result of a message.
+    ///
+    /// An error aborts the batch.
+    fn handle_result(
+        &self,
+        ctx: &mut Context,
+        message: &RoothashMessage,
+        result: &MessageEvent,
+    ) -> Result<()>;
+}
+
+impl<F> MessageResultHandler for F
+where
+    F: Fn(&mut Context, &RoothashMessage, &MessageEvent) -> Result<()>,
+{
+    fn handle_result(
+        &self,
+        ctx: &mut Context,
+        message: &RoothashMessage,
+        result: &MessageEvent,
+    ) -> Result<()> {
+        (*self)(ctx, message, result)
+    }
+}
+
+/// Custom incoming message handler.
+///
+/// An incoming message handler can be configured on the `Dispatcher` to
+/// process the messages sent to the runtime from the consensus layer (e.g.,
+/// deposits). It is called once for each queued message, in queue order,
+/// before the batch is scheduled.
+pub trait IncomingMessageHandler {
+    /// Called to process an incoming message.
+    ///
+    /// An error fails the message, which is still consumed. Messages are
+    /// processed outside of any transaction, so tags and events must not be
+    /// emitted.
+    fn process_incoming_message(&self, ctx: &mut Context, message: &IncomingMessage) -> Result<()>;
+}
+
+impl<F> IncomingMessageHandler for F
+where
+    F: Fn(&mut Context, &IncomingMessage) -> Result<()>,
+{
+    fn process_incoming_message(&self, ctx: &mut Context, message: &IncomingMessage) -> Result<()> {
+        (*self)(ctx, message)
+    }
+}
+
+/// Order in which the calls of a batch are executed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schedule {
+    /// Partitions of the batch, each a list of indices of calls in the batch.
+    ///
+    /// Partitions, and the calls within them, are executed in order. Every
+    /// call must be scheduled exactly once.
+    pub partitions: Vec<Vec<usize>>,
+}
+
+impl Schedule {
+    /// Schedule that executes all calls in batch order.
+    pub fn fifo(batch_size: usize) -> Self {
+        let partitions = if batch_size == 0 {
+            vec![]
+        } else {
+            vec![(0..batch_size).collect()]
+        };
+        Schedule { partitions }
+    }
+
+    /// Return the order in which calls are executed, checking that every call
+    /// of a batch of the given size is scheduled exactly once.
+    fn execution_order(&self, batch_size: usize) -> Result<Vec<usize>> {
+        let order: Vec<usize> = self.partitions.iter().flatten().cloned().collect();
+        let mut scheduled = vec![false; batch_size];
+        for index in &order {
+            match scheduled.get_mut(*index) {
+                Some(scheduled) if !*scheduled => *scheduled = true,
+                _ => return Err(DispatchError::InvalidSchedule.into()),
+            }
+        }
+        if order.len() != batch_size {
+            return Err(DispatchError::InvalidSchedule.into());
+        }
+        Ok(order)
+    }
+
+    fn stats(&self, order: &[usize]) -> ScheduleStats {
+        ScheduleStats {
+            partitions: self.partitions.len() as u64,
+            max_partition_size: self.partitions.iter().map(Vec::len).max().unwrap_or(0) as u64,
+            reordered: order
+                .iter()
+                .enumerate()
+                .filter(|(position, index)| position != *index)
+                .count() as u64,
+        }
+    }
+}
+
+/// Custom batch scheduler.
+///
+/// A batch scheduler can be configured on the `Dispatcher` to reorder or
+/// partition a batch (e.g., based on the read/write sets declared by its
+/// calls) before it is executed. Outputs, tags and events are always returned in
+/// batch order.
+///
+/// Scheduling must be deterministic, as all executors of a batch must arrive
+/// at the same state.
+pub trait BatchScheduler {
+    /// Called to schedule a batch before its first call is dispatched.
+    fn schedule(&self, batch: &TxnBatch, ctx: &Context) -> Result<Schedule>;
+}
+
+impl<F> BatchScheduler for F
+where
+    F: Fn(&TxnBatch, &Context) -> Result<Schedule>,
+{
+    fn schedule(&self, batch: &TxnBatch, ctx: &Context) -> Result<Schedule> {
+        (*self)(batch, ctx)
+    }
+}
+
+/// Batch scheduler which executes calls in batch order.
+pub struct FifoScheduler;
+
+impl BatchScheduler for FifoScheduler {
+    fn schedule(&self, batch: &TxnBatch, _ctx: &Context) -> Result<Schedule> {
+        Ok(Schedule::fifo(batch.len()))
+    }
+}
+
+/// Result of dispatching a batch.
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    /// Outputs of the calls, in batch order.
+    pub outputs: TxnBatch,
+    /// Tags emitted by the calls, in batch order.
+    pub tags: Vec<Tags>,
+    /// Events emitted by the calls, in batch order.
+    pub events: Vec<Events>,
+    /// Roothash messages emitted by the batch.
+    pub messages: Vec<RoothashMessage>,
+    /// Number of incoming messages processed, from the head of the queue.
+    pub in_msgs_count: u32,
+    /// Scheduling statistics.
+    pub stats: ScheduleStats,
+}
+
 /// Custom context initializer.
-pub trait ContextInitializer {
+///
+/// With parallel execution, the initializer is called for each transaction
+/// from the thread executing it.
+pub trait ContextInitializer: Send + Sync {
     /// Called to initialize the context.
     fn init(&self, ctx: &mut Context);
 }
 
 impl<F> ContextInitializer for F
 where
-    F: Fn(&mut Context),
+    F: Fn(&mut Context) + Send + Sync,
 {
     fn init(&self, ctx: &mut Context) {
         (*self)(ctx)
@@ -86,7 +274,7 @@ pub struct MethodDescriptor {
 }
 
 /// Handler for a runtime method.
-pub trait MethodHandler<Call, Output> {
+pub trait MethodHandler<Call, Output>: Send + Sync {
     /// Invoke the method implementation and return a response.
     fn handle(&self, call: &Call, ctx: &mut Context) -> Result<Output>;
 }
@@ -95,7 +283,7 @@ impl<Call, Output, F> MethodHandler<Call, Output> for F
 where
     Call: 'static,
     Output: 'static,
-    F: Fn(&Call, &mut Context) -> Result<Output> + 'static,
+    F: Fn(&Call, &mut Context) -> Result<Output> + Send + Sync + 'static,
 {
     fn handle(&self, call: &Call, ctx: &mut Context) -> Result<Output> {
         (*self)(&call, ctx)
@@ -103,7 +291,7 @@ where
 }
 
 /// Dispatcher for a runtime method.
-pub trait MethodHandlerDispatch {
+pub trait MethodHandlerDispatch: Send + Sync {
     /// Get method descriptor.
     fn get_descriptor(&self) -> &MethodDescriptor;
 
@@ -139,6 +327,8 @@ where
 pub struct Method {
     /// Method dispatcher.
     dispatcher: Box<dyn MethodHandlerDispatch>,
+    /// Method description reported to the host.
+    info: MethodInfo,
 }
 
 impl Method {
@@ -150,6 +340,7 @@ impl Method {
         Handler: MethodHandler<Call, Output> + 'static,
     {
         Method {
+            info: MethodInfo::new::<Call, Output>(&method.name),
             dispatcher: Box::new(MethodHandlerDispatchImpl {
                 descriptor: method,
                 handler: Box::new(handler),
@@ -157,6 +348,20 @@ impl Method {
         }
     }
 
+    /// Set the schemas of the method arguments and output.
+    ///
+    /// If left unspecified, the argument and output types are only
+    /// referred to by name.
+    pub fn with_schema(mut self, args: Schema, output: Schema) -> Self {
+        self.info.with_schema(args, output);
+        self
+    }
+
+    /// Return method description.
+    pub fn get_info(&self) -> &MethodInfo {
+        &self.info
+    }
+
     /// Return method name.
     pub fn get_name(&self) -> &String {
         &self.dispatcher.get_descriptor().name
@@ -174,15 +379,19 @@ impl Method {
 /// to process transactions.
 pub trait Dispatcher {
     /// Dispatches a batch of runtime requests.
-    fn dispatch_batch(
-        &self,
-        batch: &TxnBatch,
-        ctx: Context,
-    ) -> Result<(TxnBatch, Vec<Tags>, Vec<RoothashMessage>)>;
+    fn dispatch_batch(&self, batch: &TxnBatch, ctx: Context) -> Result<BatchResult>;
     /// Invoke the finalizer (if any).
     fn finalize(&self, new_storage_root: Hash);
     /// Configure abort batch flag.
     fn set_abort_batch_flag(&mut self, abort_batch: Arc<AtomicBool>);
+    /// Return descriptions of the registered methods.
+    fn methods(&self) -> Vec<MethodInfo> {
+        Vec::new()
+    }
+    /// Return names of the optional features used by the dispatcher.
+    fn features(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 /// No-op dispatcher.
@@ -198,14 +407,16 @@ impl NoopDispatcher {
 }
 
 impl Dispatcher for NoopDispatcher {
-    fn dispatch_batch(
-        &self,
-        _batch: &TxnBatch,
-        ctx: Context,
-    ) -> Result<(TxnBatch, Vec<Tags>, Vec<RoothashMessage>)> {
+    fn dispatch_batch(&self, _batch: &TxnBatch, ctx: Context) -> Result<BatchResult> {
         let outputs = TxnBatch::new(Vec::new());
-        let (tags, roothash_messages) = ctx.close();
-        Ok((outputs, tags, roothash_messages))
+        let (tags, events, messages) = ctx.close();
+        Ok(BatchResult {
+            outputs,
+            tags,
+            events,
+            messages,
+            ..Default::default()
+        })
     }
 
     fn finalize(&self, _new_storage_root: Hash) {
@@ -227,12 +438,24 @@ pub struct MethodDispatcher {
     methods: HashMap<String, Method>,
     /// Registered batch handler.
     batch_handler: Option<Box<dyn BatchHandler>>,
+    /// Registered batch scheduler.
+    batch_scheduler: Box<dyn BatchScheduler>,
+    /// Registered message result handler.
+    message_result_handler: Option<Box<dyn MessageResultHandler>>,
+    /// Registered incoming message handler.
+    incoming_message_handler: Option<Box<dyn IncomingMessageHandler>>,
+    /// Gas budget for processing incoming messages in a batch.
+    incoming_message_gas: Gas,
     /// Registered context initializer.
     ctx_initializer: Option<Box<dyn ContextInitializer>>,
     /// Registered finalizer.
     finalizer: Option<Box<dyn Finalizer>>,
     /// Abort batch flag.
     abort_batch: Option<Arc<AtomicBool>>,
+    /// Number of threads used to execute batches.
+    execution_threads: usize,
+    /// Gas metering configuration.
+    gas: Option<GasConfig>,
 }
 
 impl MethodDispatcher {
@@ -241,9 +464,15 @@ impl MethodDispatcher {
         MethodDispatcher {
             methods: HashMap::new(),
             batch_handler: None,
+            batch_scheduler: Box::new(FifoScheduler),
+            message_result_handler: None,
+            incoming_message_handler: None,
+            incoming_message_gas: 0,
             ctx_initializer: None,
             finalizer: None,
             abort_batch: None,
+            execution_threads: 1,
+            gas: None,
         }
     }
 
@@ -260,6 +489,46 @@ impl MethodDispatcher {
         self.batch_handler = Some(Box::new(handler));
     }
 
+    /// Configure batch scheduler.
+    ///
+    /// If left unspecified, calls are executed in batch order
+    /// (`FifoScheduler`).
+    pub fn set_batch_scheduler<S>(&mut self, scheduler: S)
+    where
+        S: BatchScheduler + 'static,
+    {
+        self.batch_scheduler = Box::new(scheduler);
+    }
+
+    /// Configure message result handler.
+    ///
+    /// The handler is not called when checking transactions.
+    pub fn set_message_result_handler<H>(&mut self, handler: H)
+    where
+        H: MessageResultHandler + 'static,
+    {
+        self.message_result_handler = Some(Box::new(handler));
+    }
+
+    /// Configure incoming message handler.
+    ///
+    /// Messages are processed in queue order, each with a gas meter limited
+    /// to what remains of the given per-batch gas budget and charged with the
+    /// configured gas costs (if any). A message which runs out of gas is
+    /// left in the queue for the next batch, along with all messages after
+    /// it, unless it is the first message of the batch in which case it is
+    /// consumed as failed so that it cannot block the queue. Roothash
+    /// messages emitted while processing a failed message are discarded.
+    ///
+    /// The handler is not called when checking transactions.
+    pub fn set_incoming_message_handler<H>(&mut self, handler: H, gas_budget: Gas)
+    where
+        H: IncomingMessageHandler + 'static,
+    {
+        self.incoming_message_handler = Some(Box::new(handler));
+        self.incoming_message_gas = gas_budget;
+    }
+
     /// Configure context initializer.
     pub fn set_context_initializer<I>(&mut self, initializer: I)
     where
@@ -276,70 +545,445 @@ impl MethodDispatcher {
         self.finalizer = Some(Box::new(finalizer));
     }
 
+    /// Configure parallel execution of batches on the given number of
+    /// threads.
+    ///
+    /// Each call is first executed speculatively against the state before
+    /// the batch, recording the keys it accesses. Calls are then committed
+    /// in execution order, and a call which accessed any key written by a
+    /// call committed before it is re-executed serially against the updated
+    /// state. The resulting state, outputs, tags and events are thus the same as with
+    /// serial execution, provided that calls only interact through storage.
+    ///
+    /// As every call gets its own context, calls must not rely on changes
+    /// to the context made by other calls. Batches are always executed
+    /// serially if a batch handler is configured, as the changes it makes
+    /// to the context would not be visible to the calls.
+    ///
+    /// If left unspecified (or set to at most one thread), batches are
+    /// executed serially.
+    pub fn set_parallel_execution(&mut self, threads: usize) {
+        self.execution_threads = threads;
+    }
+
+    /// Configure gas metering.
+    ///
+    /// Before a call is dispatched, the context is given a gas meter with
+    /// the gas limit requested by the call (capped at the maximum) and the
+    /// configured costs. Method handlers charge it for signature
+    /// verifications and runtime-specific opcodes, while storage accesses
+    /// are charged once the handler returns. A call which runs out of gas
+    /// results in an `OutOfGas` output and its storage writes are
+    /// discarded.
+    ///
+    /// If left unspecified, gas is not metered.
+    pub fn set_gas_metering(&mut self, config: GasConfig) {
+        self.gas = Some(config);
+    }
+
     /// Dispatches a raw runtime invocation request.
     fn dispatch(&self, call: &Vec<u8>, ctx: &mut Context) -> Vec<u8> {
-        let rsp = match self.dispatch_fallible(call, ctx) {
-            Ok(response) => TxnOutput::Success(response),
-            Err(error) => match error.downcast::<CheckOnlySuccess>() {
-                Ok(check_result) => TxnOutput::Success(cbor::to_value(check_result.0)),
-                Err(error) => TxnOutput::Error(format!("{}", error)),
-            },
+        dispatch_call(&self.methods, self.gas.as_ref(), call, ctx)
+    }
+
+    /// Processes the given incoming messages, see
+    /// `set_incoming_message_handler`.
+    ///
+    /// Returns the number of consumed messages.
+    fn process_incoming_messages(
+        &self,
+        handler: &dyn IncomingMessageHandler,
+        ctx: &mut Context,
+        messages: &[IncomingMessage],
+    ) -> Result<u32> {
+        let costs = self
+            .gas
+            .as_ref()
+            .map_or_else(|| Arc::new(GasCosts::default()), |gas| gas.costs.clone());
+        let mut remaining = self.incoming_message_gas;
+        let mut processed = 0;
+        for (index, message) in messages.iter().enumerate() {
+            if message.id != messages[0].id + index as u64 {
+                return Err(DispatchError::IncomingMessagesOutOfOrder.into());
+            }
+            if is_aborted(&self.abort_batch) {
+                return Err(anyhow!("batch aborted"));
+            }
+
+            let message_count = ctx.message_count();
+            ctx.gas = GasMeter::new(remaining, costs.clone());
+            let result =
+                with_metered_storage(ctx, |ctx| handler.process_incoming_message(ctx, message));
+            if let Err(error) = result {
+                ctx.discard_messages(message_count);
+                if GasError::is_out_of_gas(&error) && processed > 0 {
+                    break;
+                }
+            }
+            remaining -= ctx.gas.used();
+            processed += 1;
+        }
+        ctx.gas = GasMeter::unlimited();
+
+        Ok(processed)
+    }
+
+    /// Executes the calls of a batch in parallel, see
+    /// `set_parallel_execution`.
+    fn dispatch_parallel(
+        &self,
+        batch: &TxnBatch,
+        ctx: &Context,
+        order: &[usize],
+        stats: ScheduleStats,
+        storage: StorageContextHandle,
+    ) -> Result<BatchResult> {
+        let env = ExecutionEnvironment {
+            methods: &self.methods,
+            ctx_initializer: self.ctx_initializer.as_deref(),
+            io_ctx: ctx.io_ctx.clone(),
+            header: ctx.header,
+            check_only: ctx.check_only,
+            entropy: ctx.entropy(),
+            abort_batch: &self.abort_batch,
+            gas: self.gas.as_ref(),
+            untrusted_local: StorageContext::with_current(|_, untrusted_local| {
+                untrusted_local.clone()
+            }),
         };
 
-        cbor::to_vec(&rsp)
+        // Execute all calls speculatively against the current state.
+        let threads = self.execution_threads.min(order.len());
+        let next = AtomicUsize::new(0);
+        let (env_ref, next, storage_ref) = (&env, &next, &storage);
+        let mut speculative: Vec<Option<Execution>> = (0..order.len()).map(|_| None).collect();
+        let executed: Vec<Vec<(usize, Execution)>> = crossbeam::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(move |_| {
+                        let mut executed = Vec::new();
+                        loop {
+                            let position = next.fetch_add(1, Ordering::SeqCst);
+                            if position >= order.len() || env_ref.is_aborted() {
+                                return executed;
+                            }
+                            let call = &batch[order[position]];
+                            executed.push((
+                                position,
+                                env_ref.execute(position, call, storage_ref.clone()),
+                            ));
+                        }
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| panic::resume_unwind(err))
+                })
+                .collect()
+        })
+        .unwrap_or_else(|err| panic::resume_unwind(err));
+        for (position, execution) in executed.into_iter().flatten() {
+            speculative[position] = Some(execution);
+        }
+
+        // Commit in execution order, re-executing conflicting calls.
+        let mut written = BTreeSet::new();
+        let mut outputs = vec![Vec::new(); batch.len()];
+        let mut tags = vec![Tags::new(); batch.len()];
+        let mut events = vec![Events::new(); batch.len()];
+        let mut messages = Vec::new();
+        for (position, index) in order.iter().cloned().enumerate() {
+            if env.is_aborted() {
+                return Err(anyhow!("batch aborted"));
+            }
+
+            let execution = match speculative[position].take() {
+                Some(execution) if !execution.conflicts_with(&written) => execution,
+                _ => {
+                    PARALLEL_CONFLICTS.inc();
+                    env.execute(position, &batch[index], storage.clone())
+                }
+            };
+
+            apply_writes(&env.io_ctx, &execution.writes);
+            written.extend(execution.writes.into_iter().map(|(key, _)| key));
+            outputs[index] = execution.output;
+            tags[index] = execution.tags;
+            events[index] = execution.events;
+            messages.extend(execution.messages);
+        }
+
+        Ok(BatchResult {
+            outputs: TxnBatch::new(outputs),
+            tags,
+            events,
+            messages,
+            stats,
+            ..Default::default()
+        })
     }
+}
 
-    fn dispatch_fallible(&self, call: &Vec<u8>, ctx: &mut Context) -> Result<cbor::Value> {
-        let call: TxnCall = cbor::from_slice(call).context("unable to parse call")?;
+fn is_aborted(abort_batch: &Option<Arc<AtomicBool>>) -> bool {
+    abort_batch
+        .as_ref()
+        .map(|b| b.load(Ordering::SeqCst))
+        .unwrap_or(false)
+}
+
+/// Apply buffered writes to the current storage context.
+fn apply_writes(io_ctx: &Arc<IoContext>, writes: &BTreeMap<Vec<u8>, Option<Vec<u8>>>) {
+    StorageContext::with_current(|mkvs, _| {
+        for (key, value) in writes {
+            let ctx = IoContext::create_child(io_ctx);
+            match value {
+                Some(value) => mkvs.insert(ctx, key, value),
+                None => mkvs.remove(ctx, key),
+            };
+        }
+    });
+}
 
-        match self.methods.get(&call.method) {
-            Some(dispatcher) => dispatcher.dispatch(call, ctx),
-            None => Err(DispatchError::MethodNotFound {
+/// Dispatches a raw runtime invocation request.
+fn dispatch_call(
+    methods: &HashMap<String, Method>,
+    gas: Option<&GasConfig>,
+    call: &[u8],
+    ctx: &mut Context,
+) -> Vec<u8> {
+    let message_count = ctx.message_count();
+    let rsp = match dispatch_call_fallible(methods, gas, call, ctx) {
+        Ok(response) => TxnOutput::Success(response),
+        Err(error) => match error.downcast::<CheckOnlySuccess>() {
+            Ok(check_result) => TxnOutput::Success(cbor::to_value(check_result.0)),
+            Err(error) if GasError::is_out_of_gas(&error) => TxnOutput::OutOfGas {
+                limit: ctx.gas.limit(),
+            },
+            Err(error) => TxnOutput::Error(format!("{}", error)),
+        },
+    };
+    // Messages emitted by failed calls must not be executed.
+    if !matches!(rsp, TxnOutput::Success(_)) {
+        ctx.discard_messages(message_count);
+    }
+
+    cbor::to_vec(&rsp)
+}
+
+fn dispatch_call_fallible(
+    methods: &HashMap<String, Method>,
+    gas: Option<&GasConfig>,
+    call: &[u8],
+    ctx: &mut Context,
+) -> Result<cbor::Value> {
+    let call: TxnCall = cbor::from_slice_strict(call).context("unable to parse call")?;
+
+    let dispatcher = match methods.get(&call.method) {
+        Some(dispatcher) => dispatcher,
+        None => {
+            return Err(DispatchError::MethodNotFound {
                 method: call.method,
             }
-            .into()),
+            .into())
+        }
+    };
+    let gas = match gas {
+        Some(gas) => gas,
+        None => return dispatcher.dispatch(call, ctx),
+    };
+    ctx.gas = gas.meter(call.gas_limit);
+    with_metered_storage(ctx, |ctx| dispatcher.dispatch(call, ctx))
+}
+
+/// Runs the given function, charging the gas meter of the context for its
+/// storage accesses.
+fn with_metered_storage<T, F>(ctx: &mut Context, f: F) -> Result<T>
+where
+    F: FnOnce(&mut Context) -> Result<T>,
+{
+    let storage = match StorageContext::handle() {
+        Some(storage) => storage,
+        None => return f(ctx),
+    };
+
+    // Buffer writes and track accesses so that storage gas can be charged
+    // after the call, discarding its writes if it runs out of gas.
+    let untrusted_local =
+        StorageContext::with_current(|_, untrusted_local| untrusted_local.clone());
+    let mut state = AccessTracker::new(SpeculativeState::new(storage));
+    let mut result = StorageContext::enter_nested(&mut state, untrusted_local, || f(ctx));
+    if !result.as_ref().err().map_or(false, GasError::is_out_of_gas) {
+        match ctx.gas.consume_storage(&state.take_accesses()) {
+            Ok(()) => apply_writes(&ctx.io_ctx, &state.into_inner().into_writes()),
+            Err(error) => result = Err(error),
         }
     }
+    result
+}
+
+/// Everything needed to execute a call of a batch on any thread.
+struct ExecutionEnvironment<'a> {
+    methods: &'a HashMap<String, Method>,
+    ctx_initializer: Option<&'a dyn ContextInitializer>,
+    io_ctx: Arc<IoContext>,
+    header: &'a Header,
+    check_only: bool,
+    entropy: Option<[u8; 32]>,
+    abort_batch: &'a Option<Arc<AtomicBool>>,
+    gas: Option<&'a GasConfig>,
+    untrusted_local: Arc<dyn KeyValue>,
+}
+
+impl<'a> ExecutionEnvironment<'a> {
+    fn is_aborted(&self) -> bool {
+        is_aborted(self.abort_batch)
+    }
+
+    /// Execute a call at the given position in execution order in a fresh
+    /// context on top of the given storage context, without modifying it.
+    fn execute(&self, position: usize, call: &[u8], storage: StorageContextHandle) -> Execution {
+        let mut ctx = Context::new(self.io_ctx.clone(), self.header, self.check_only);
+        ctx.set_entropy(self.entropy);
+        if let Some(ctx_init) = self.ctx_initializer {
+            ctx_init.init(&mut ctx);
+        }
+        ctx.start_transaction();
+        ctx.set_transaction_index(position as u64);
+
+        let mut state = AccessTracker::new(SpeculativeState::new(storage));
+        let output = StorageContext::enter_nested(&mut state, self.untrusted_local.clone(), || {
+            dispatch_call(self.methods, self.gas, call, &mut ctx)
+        });
+        let (mut tags, mut events, messages) = ctx.close();
+
+        Execution {
+            output,
+            tags: tags.pop().unwrap_or_default(),
+            events: events.pop().unwrap_or_default(),
+            messages,
+            accesses: state.take_accesses(),
+            writes: state.into_inner().into_writes(),
+        }
+    }
+}
+
+/// Result of executing a single call speculatively.
+struct Execution {
+    output: Vec<u8>,
+    tags: Tags,
+    events: Events,
+    messages: Vec<RoothashMessage>,
+    accesses: Vec<Access>,
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl Execution {
+    /// Return whether the call accessed any of the given keys.
+    ///
+    /// Writes count as accesses, as they return the previous value.
+    fn conflicts_with(&self, keys: &BTreeSet<Vec<u8>>) -> bool {
+        self.accesses
+            .iter()
+            .any(|access| keys.contains(&access.key))
+    }
 }
 
 impl Dispatcher for MethodDispatcher {
-    fn dispatch_batch(
-        &self,
-        batch: &TxnBatch,
-        mut ctx: Context,
-    ) -> Result<(TxnBatch, Vec<Tags>, Vec<RoothashMessage>)> {
+    fn dispatch_batch(&self, batch: &TxnBatch, mut ctx: Context) -> Result<BatchResult> {
         if let Some(ref ctx_init) = self.ctx_initializer {
             ctx_init.init(&mut ctx);
         }
 
+        // Handle the results of the messages emitted in the previous round.
+        if let Some(ref handler) = self.message_result_handler {
+            let results: Vec<(RoothashMessage, MessageEvent)> = ctx
+                .message_results()
+                .into_iter()
+                .map(|(message, result)| (message.clone(), result.clone()))
+                .collect();
+            for (message, result) in results {
+                handler.handle_result(&mut ctx, &message, &result)?;
+            }
+        }
+
+        // Process the incoming messages queued for the runtime.
+        let incoming_messages = ctx.take_incoming_messages();
+        let in_msgs_count = match self.incoming_message_handler {
+            Some(ref handler) => {
+                self.process_incoming_messages(handler.as_ref(), &mut ctx, &incoming_messages)?
+            }
+            None => 0,
+        };
+
+        // Schedule batch.
+        let schedule = self.batch_scheduler.schedule(batch, &ctx)?;
+        let order = schedule.execution_order(batch.len())?;
+        let stats = schedule.stats(&order);
+
         // Invoke start batch handler.
         if let Some(ref handler) = self.batch_handler {
             handler.start_batch(&mut ctx);
         }
 
+        // Execute batch in parallel if possible.
+        let storage = if self.execution_threads > 1 && self.batch_handler.is_none() {
+            StorageContext::handle()
+        } else {
+            None
+        };
+        if let Some(storage) = storage {
+            let result = self.dispatch_parallel(batch, &ctx, &order, stats, storage)?;
+            // Keep the messages emitted before the calls were executed.
+            let (_, _, mut messages) = ctx.close();
+            messages.extend(result.messages);
+            return Ok(BatchResult {
+                messages,
+                in_msgs_count,
+                ..result
+            });
+        }
+
         // Process batch.
-        let mut vec = Vec::new();
-        for call in batch.iter() {
-            if self
-                .abort_batch
-                .as_ref()
-                .map(|b| b.load(Ordering::SeqCst))
-                .unwrap_or(false)
-            {
+        let mut outputs = vec![Vec::new(); batch.len()];
+        for index in order.iter().cloned() {
+            if is_aborted(&self.abort_batch) {
                 return Err(anyhow!("batch aborted"));
             }
             ctx.start_transaction();
-            vec.push(self.dispatch(call, &mut ctx));
+            outputs[index] = self.dispatch(&batch[index], &mut ctx);
         }
-        let outputs = TxnBatch::new(vec);
+        let outputs = TxnBatch::new(outputs);
 
         // Invoke end batch handler.
         if let Some(ref handler) = self.batch_handler {
             handler.end_batch(&mut ctx);
         }
 
-        let (tags, roothash_messages) = ctx.close();
-        Ok((outputs, tags, roothash_messages))
+        // Tags and events are emitted in execution order.
+        let (executed_tags, executed_events, messages) = ctx.close();
+        let mut tags = vec![Tags::new(); batch.len()];
+        let mut events = vec![Events::new(); batch.len()];
+        for (index, (txn_tags, txn_events)) in order
+            .into_iter()
+            .zip(executed_tags.into_iter().zip(executed_events))
+        {
+            tags[index] = txn_tags;
+            events[index] = txn_events;
+        }
+
+        Ok(BatchResult {
+            outputs,
+            tags,
+            events,
+            messages,
+            in_msgs_count,
+            stats,
+        })
     }
 
     fn finalize(&self, new_storage_root: Hash) {
@@ -352,14 +996,50 @@ impl Dispatcher for MethodDispatcher {
     fn set_abort_batch_flag(&mut self, abort_batch: Arc<AtomicBool>) {
         self.abort_batch = Some(abort_batch);
     }
+
+    fn methods(&self) -> Vec<MethodInfo> {
+        let mut methods: Vec<_> = self
+            .methods
+            .values()
+            .map(|method| method.get_info().clone())
+            .collect();
+        methods.sort_by(|a, b| a.name.cmp(&b.name));
+        methods
+    }
+
+    fn features(&self) -> Vec<String> {
+        let mut features = Vec::new();
+        if self.batch_handler.is_some() {
+            features.push("batch-handler".to_owned());
+        }
+        if self.execution_threads > 1 {
+            features.push("parallel-execution".to_owned());
+        }
+        if self.gas.is_some() {
+            features.push("gas-metering".to_owned());
+        }
+        if self.message_result_handler.is_some() {
+            features.push("message-result-handler".to_owned());
+        }
+        if self.incoming_message_handler.is_some() {
+            features.push("incoming-message-handler".to_owned());
+        }
+        features
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Mutex;
+
     use io_context::Context as IoContext;
     use serde::{Deserialize, Serialize};
 
-    use crate::common::{cbor, roothash::Header};
+    use crate::{
+        common::{cbor, quantity::Quantity, roothash::Header},
+        storage::mkvs::{sync::NoopReadSyncer, Tree},
+        transaction::{events::Event, gas::GasCosts},
+    };
 
     use super::*;
 
@@ -401,6 +1081,7 @@ mod tests {
                 text: "hello".to_owned(),
                 number: 21,
             }),
+            gas_limit: None,
         };
         let call_encoded = cbor::to_vec(&call);
 
@@ -430,4 +1111,414 @@ mod tests {
             _ => panic!("txn call should return success"),
         }
     }
+
+    #[test]
+    fn test_methods() {
+        let mut dispatcher = MethodDispatcher::new();
+        register_dummy_method(&mut dispatcher);
+        dispatcher.add_method(
+            Method::new(
+                MethodDescriptor {
+                    name: "add".to_owned(),
+                },
+                |call: &(u64, u64), _ctx: &mut Context| -> Result<u64> { Ok(call.0 + call.1) },
+            )
+            .with_schema(Schema::of::<(u64, u64)>(), Schema::of::<u64>()),
+        );
+        dispatcher.set_parallel_execution(4);
+
+        let methods = dispatcher.methods();
+        assert_eq!(2, methods.len());
+        assert_eq!("add", methods[0].name);
+        assert_eq!(
+            Schema::Tuple(vec![Schema::Unsigned, Schema::Unsigned]),
+            methods[0].args
+        );
+        assert_eq!(Schema::Unsigned, methods[0].output);
+        assert_eq!("dummy", methods[1].name);
+        assert_eq!(Schema::named::<Complex>(), methods[1].args);
+        assert_eq!(vec!["parallel-execution".to_owned()], dispatcher.features());
+    }
+
+    #[test]
+    fn test_batch_scheduler() {
+        let mut dispatcher = MethodDispatcher::new();
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let executed_clone = executed.clone();
+        dispatcher.add_method(Method::new(
+            MethodDescriptor {
+                name: "record".to_owned(),
+            },
+            move |call: &u32, ctx: &mut Context| -> Result<u32> {
+                executed_clone.lock().unwrap().push(*call);
+                ctx.emit_txn_tag(b"call", call.to_be_bytes());
+                ctx.emit_event(Event::new(
+                    b"call".to_vec(),
+                    call.to_be_bytes().to_vec(),
+                    vec![],
+                ));
+                Ok(*call)
+            },
+        ));
+        // Execute the second half of the batch first.
+        dispatcher.set_batch_scheduler(|batch: &TxnBatch, _ctx: &Context| {
+            let mid = batch.len() / 2;
+            Ok(Schedule {
+                partitions: vec![(mid..batch.len()).collect(), (0..mid).collect()],
+            })
+        });
+
+        let batch = TxnBatch::new(
+            (0..4u32)
+                .map(|i| {
+                    cbor::to_vec(&TxnCall {
+                        method: "record".to_owned(),
+                        args: cbor::to_value(i),
+                        gas_limit: None,
+                    })
+                })
+                .collect(),
+        );
+        let header = Header::default();
+        let ctx = Context::new(IoContext::background().freeze(), &header, false);
+        let result = dispatcher.dispatch_batch(&batch, ctx).unwrap();
+
+        assert_eq!(vec![2, 3, 0, 1], *executed.lock().unwrap());
+        assert_eq!(
+            ScheduleStats {
+                partitions: 2,
+                max_partition_size: 2,
+                reordered: 4,
+            },
+            result.stats
+        );
+        // Outputs, tags and events are in batch order.
+        for (i, (output, tags)) in result.outputs.iter().zip(&result.tags).enumerate() {
+            match cbor::from_slice::<TxnOutput>(output).unwrap() {
+                TxnOutput::Success(value) => {
+                    assert_eq!(i as u32, cbor::from_value::<u32>(value).unwrap())
+                }
+                _ => panic!("txn call should return success"),
+            }
+            assert_eq!((i as u32).to_be_bytes().to_vec(), tags[0].value);
+            assert_eq!((i as u32).to_be_bytes().to_vec(), result.events[i][0].value);
+        }
+
+        // Invalid schedules are rejected.
+        assert!(Schedule::fifo(2).execution_order(2).is_ok());
+        assert!(Schedule::fifo(0).execution_order(0).is_ok());
+        let invalid = Schedule {
+            partitions: vec![vec![0, 1], vec![1]],
+        };
+        assert!(invalid.execution_order(2).is_err());
+        assert!(invalid.execution_order(3).is_err());
+        assert!(Schedule::fifo(2).execution_order(3).is_err());
+    }
+
+    #[test]
+    fn test_messages() {
+        let mut dispatcher = MethodDispatcher::new();
+        dispatcher.add_method(Method::new(
+            MethodDescriptor {
+                name: "transfer".to_owned(),
+            },
+            |call: &(u64, bool), ctx: &mut Context| -> Result<()> {
+                ctx.emit_staking_transfer(Default::default(), Quantity::from(call.0));
+                if call.1 {
+                    return Err(anyhow!("transfer failed"));
+                }
+                Ok(())
+            },
+        ));
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let handled_clone = handled.clone();
+        dispatcher.set_message_result_handler(
+            move |_ctx: &mut Context,
+                  message: &RoothashMessage,
+                  result: &MessageEvent|
+                  -> Result<()> {
+                handled_clone
+                    .lock()
+                    .unwrap()
+                    .push((message.clone(), result.is_success()));
+                Ok(())
+            },
+        );
+
+        let batch = TxnBatch::new(
+            vec![(1u64, false), (2, true), (3, false)]
+                .into_iter()
+                .map(|args| {
+                    cbor::to_vec(&TxnCall {
+                        method: "transfer".to_owned(),
+                        args: cbor::to_value(args),
+                        gas_limit: None,
+                    })
+                })
+                .collect(),
+        );
+        let header = Header {
+            messages: Some(vec![RoothashMessage::staking_transfer(
+                Default::default(),
+                Quantity::from(10u64),
+            )]),
+            ..Default::default()
+        };
+        let mut ctx = Context::new(IoContext::background().freeze(), &header, false);
+        ctx.set_message_results(vec![MessageEvent {
+            module: "staking".to_owned(),
+            code: 3,
+            index: 0,
+        }]);
+        let result = dispatcher.dispatch_batch(&batch, ctx).unwrap();
+
+        // Results of the previous round are handled.
+        assert_eq!(
+            vec![(header.messages.clone().unwrap()[0].clone(), false)],
+            *handled.lock().unwrap()
+        );
+        // Messages emitted by failed calls are discarded.
+        assert_eq!(
+            vec![
+                RoothashMessage::staking_transfer(Default::default(), Quantity::from(1u64)),
+                RoothashMessage::staking_transfer(Default::default(), Quantity::from(3u64)),
+            ],
+            result.messages
+        );
+    }
+
+    #[test]
+    fn test_incoming_messages() {
+        let mut dispatcher = MethodDispatcher::new();
+        let processed = Arc::new(Mutex::new(Vec::new()));
+        let processed_clone = processed.clone();
+        dispatcher.set_incoming_message_handler(
+            move |ctx: &mut Context, message: &IncomingMessage| -> Result<()> {
+                ctx.gas.consume(10)?;
+                processed_clone.lock().unwrap().push(message.id);
+                ctx.emit_staking_transfer(message.caller, message.tokens);
+                if message.tag == 1 {
+                    return Err(anyhow!("message failed"));
+                }
+                Ok(())
+            },
+            25,
+        );
+
+        let messages: Vec<IncomingMessage> = (0..3)
+            .map(|tag| IncomingMessage {
+                id: 10 + tag,
+                tag,
+                tokens: Quantity::from(tag),
+                ..Default::default()
+            })
+            .collect();
+        let header = Header::default();
+        let batch = TxnBatch::new(Vec::new());
+
+        // Messages are processed in order until the gas budget runs out.
+        let mut ctx = Context::new(IoContext::background().freeze(), &header, false);
+        ctx.set_incoming_messages(messages.clone());
+        let result = dispatcher.dispatch_batch(&batch, ctx).unwrap();
+        assert_eq!(result.in_msgs_count, 2);
+        assert_eq!(vec![10, 11], *processed.lock().unwrap());
+        // Messages emitted while processing failed messages are discarded.
+        assert_eq!(
+            vec![RoothashMessage::staking_transfer(
+                Default::default(),
+                Quantity::from(0u64)
+            )],
+            result.messages
+        );
+
+        // A first message which exceeds the gas budget is consumed.
+        processed.lock().unwrap().clear();
+        let mut ctx = Context::new(IoContext::background().freeze(), &header, false);
+        ctx.set_incoming_messages(messages[2..].to_vec());
+        dispatcher.incoming_message_gas = 5;
+        let result = dispatcher.dispatch_batch(&batch, ctx).unwrap();
+        assert_eq!(result.in_msgs_count, 1);
+        assert!(processed.lock().unwrap().is_empty());
+        assert!(result.messages.is_empty());
+
+        // Messages must be in queue order.
+        let mut ctx = Context::new(IoContext::background().freeze(), &header, false);
+        ctx.set_incoming_messages(vec![messages[1].clone(), messages[0].clone()]);
+        assert!(dispatcher.dispatch_batch(&batch, ctx).is_err());
+    }
+
+    struct NoopKeyValue;
+
+    impl KeyValue for NoopKeyValue {
+        fn get(&self, _key: Vec<u8>) -> Result<Vec<u8>> {
+            Err(anyhow!("not found"))
+        }
+
+        fn insert(&self, _key: Vec<u8>, _value: Vec<u8>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parallel_execution() {
+        let calls = vec![("a", 1), ("b", 2), ("a", 3), ("c", 4), ("b", 5), ("a", 6)];
+        let batch = TxnBatch::new(
+            calls
+                .iter()
+                .map(|(key, amount)| {
+                    cbor::to_vec(&TxnCall {
+                        method: "add".to_owned(),
+                        args: cbor::to_value((key.to_string(), *amount as u64)),
+                        gas_limit: None,
+                    })
+                })
+                .collect(),
+        );
+
+        let execute = |threads: usize| {
+            let mut dispatcher = MethodDispatcher::new();
+            dispatcher.add_method(Method::new(
+                MethodDescriptor {
+                    name: "add".to_owned(),
+                },
+                |call: &(String, u64), ctx: &mut Context| -> Result<u64> {
+                    let io_ctx = ctx.io_ctx.clone();
+                    let value = StorageContext::with_current(|mkvs, _| {
+                        let value = mkvs
+                            .get(IoContext::create_child(&io_ctx), call.0.as_bytes())
+                            .map(|value| cbor::from_slice::<u64>(&value).unwrap())
+                            .unwrap_or(0)
+                            + call.1;
+                        mkvs.insert(
+                            IoContext::create_child(&io_ctx),
+                            call.0.as_bytes(),
+                            &cbor::to_vec(&value),
+                        );
+                        value
+                    });
+                    ctx.emit_txn_tag(call.0.as_bytes(), value.to_be_bytes());
+                    Ok(value)
+                },
+            ));
+            dispatcher.set_parallel_execution(threads);
+
+            let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+            let header = Header::default();
+            let ctx = Context::new(IoContext::background().freeze(), &header, false);
+            let result = StorageContext::enter(&mut tree, Arc::new(NoopKeyValue), || {
+                dispatcher.dispatch_batch(&batch, ctx)
+            })
+            .unwrap();
+
+            let outputs: Vec<u64> = result
+                .outputs
+                .iter()
+                .map(
+                    |output| match cbor::from_slice::<TxnOutput>(output).unwrap() {
+                        TxnOutput::Success(value) => cbor::from_value(value).unwrap(),
+                        _ => panic!("txn call should return success"),
+                    },
+                )
+                .collect();
+            let tags: Vec<Vec<u8>> = result
+                .tags
+                .iter()
+                .map(|tags| tags[0].value.clone())
+                .collect();
+            let state: Vec<Option<Vec<u8>>> = [b"a", b"b", b"c"]
+                .iter()
+                .map(|key| tree.get(IoContext::background(), *key).unwrap())
+                .collect();
+            (outputs, tags, state)
+        };
+
+        // Parallel execution has the same results as serial execution, even
+        // though calls conflict.
+        let (outputs, tags, state) = execute(1);
+        assert_eq!(vec![1, 2, 4, 4, 7, 10], outputs);
+        assert_eq!((outputs, tags, state), execute(4));
+    }
+
+    #[test]
+    fn test_gas_metering() {
+        let calls = vec![
+            ("a", None),
+            ("b", Some(50)),
+            ("c", Some(5)),
+            ("d", Some(200)),
+        ];
+        let batch = TxnBatch::new(
+            calls
+                .iter()
+                .map(|(key, gas_limit)| {
+                    cbor::to_vec(&TxnCall {
+                        method: "store".to_owned(),
+                        args: cbor::to_value(key.to_string()),
+                        gas_limit: *gas_limit,
+                    })
+                })
+                .collect(),
+        );
+
+        let mut costs = GasCosts {
+            storage_write: 100,
+            storage_write_byte: 1,
+            ..Default::default()
+        };
+        costs.opcodes.insert("store".to_owned(), 10);
+
+        let execute = |threads: usize| {
+            let mut dispatcher = MethodDispatcher::new();
+            dispatcher.add_method(Method::new(
+                MethodDescriptor {
+                    name: "store".to_owned(),
+                },
+                |call: &String, ctx: &mut Context| -> Result<u64> {
+                    ctx.gas.consume_opcode("store")?;
+                    let io_ctx = ctx.io_ctx.clone();
+                    StorageContext::with_current(|mkvs, _| {
+                        mkvs.insert(IoContext::create_child(&io_ctx), call.as_bytes(), b"x")
+                    });
+                    Ok(ctx.gas.limit())
+                },
+            ));
+            dispatcher.set_gas_metering(GasConfig {
+                costs: Arc::new(costs.clone()),
+                max_limit: 1000,
+            });
+            dispatcher.set_parallel_execution(threads);
+
+            let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+            let header = Header::default();
+            let ctx = Context::new(IoContext::background().freeze(), &header, false);
+            let result = StorageContext::enter(&mut tree, Arc::new(NoopKeyValue), || {
+                dispatcher.dispatch_batch(&batch, ctx)
+            })
+            .unwrap();
+
+            let outputs: Vec<Result<u64, u64>> = result
+                .outputs
+                .iter()
+                .map(
+                    |output| match cbor::from_slice::<TxnOutput>(output).unwrap() {
+                        TxnOutput::Success(value) => Ok(cbor::from_value(value).unwrap()),
+                        TxnOutput::OutOfGas { limit } => Err(limit),
+                        _ => panic!("txn call should not fail"),
+                    },
+                )
+                .collect();
+            let state: Vec<bool> = [b"a", b"b", b"c", b"d"]
+                .iter()
+                .map(|key| tree.get(IoContext::background(), *key).unwrap().is_some())
+                .collect();
+            (outputs, state)
+        };
+
+        // Calls which run out of gas, either in the handler or when charging
+        // for storage, have no effect on the state.
+        let (outputs, state) = execute(1);
+        assert_eq!(vec![Ok(1000), Err(50), Err(5), Ok(200)], outputs);
+        assert_eq!(vec![true, false, false, true], state);
+        assert_eq!((outputs, state), execute(4));
+    }
 }
@@ -0,0 +1,26 @@
+//! Transaction events.
+
+/// Event is a key/value pair of arbitrary byte blobs with runtime-dependent
+/// semantics, together with a list of topics.
+///
+/// Events are stored in the I/O tree both by emitting transaction and by
+/// topic, so that clients can look up all events with a given topic.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Event {
+    /// The event key.
+    pub key: Vec<u8>,
+    /// The event value.
+    pub value: Vec<u8>,
+    /// The topics the event is indexed under.
+    pub topics: Vec<Vec<u8>>,
+}
+
+/// A list of events, in emission order.
+pub type Events = Vec<Event>;
+
+impl Event {
+    /// Create a new event.
+    pub fn new(key: Vec<u8>, value: Vec<u8>, topics: Vec<Vec<u8>>) -> Self {
+        Self { key, value, topics }
+    }
+}
@@ -0,0 +1,226 @@
+//! Gas metering.
+//!
+//! Every transaction gets a gas meter in its context, which is charged for
+//! storage accesses (as recorded by an access tracker), signature
+//! verifications and runtime-specific opcodes according to a cost table.
+//! A transaction which exceeds its gas limit fails with an out-of-gas
+//! outcome and its storage writes are discarded.
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::storage::mkvs::{Access, AccessKind};
+
+/// Amount of gas.
+pub type Gas = u64;
+
+/// Gas metering errors.
+#[derive(Error, Debug)]
+pub enum GasError {
+    #[error("out of gas (limit {limit})")]
+    OutOfGas { limit: Gas },
+    #[error("unknown opcode: {0}")]
+    UnknownOpcode(String),
+}
+
+impl GasError {
+    /// Return whether the given error is an out-of-gas error.
+    pub fn is_out_of_gas(error: &anyhow::Error) -> bool {
+        matches!(
+            error.downcast_ref::<GasError>(),
+            Some(GasError::OutOfGas { .. })
+        )
+    }
+}
+
+/// Costs of metered operations.
+#[derive(Clone, Debug, Default)]
+pub struct GasCosts {
+    /// Cost of reading a key from storage.
+    pub storage_read: Gas,
+    /// Cost of each byte (key and value) read from storage.
+    pub storage_read_byte: Gas,
+    /// Cost of writing (or removing) a key in storage.
+    pub storage_write: Gas,
+    /// Cost of each byte (key and value) written to storage.
+    pub storage_write_byte: Gas,
+    /// Cost of verifying a signature.
+    pub signature_verification: Gas,
+    /// Costs of runtime-specific opcodes.
+    pub opcodes: BTreeMap<String, Gas>,
+}
+
+impl GasCosts {
+    /// Cost of the given storage access.
+    pub fn storage_access(&self, access: &Access) -> Gas {
+        let (base, per_byte) = match access.kind {
+            AccessKind::Read => (self.storage_read, self.storage_read_byte),
+            AccessKind::Write => (self.storage_write, self.storage_write_byte),
+        };
+        base.saturating_add(per_byte.saturating_mul(access.size() as Gas))
+    }
+}
+
+/// Gas metering configuration of a dispatcher.
+#[derive(Clone, Debug)]
+pub struct GasConfig {
+    /// Costs of metered operations.
+    pub costs: Arc<GasCosts>,
+    /// Maximum gas limit of a transaction. Transactions which do not
+    /// specify a gas limit get the maximum.
+    pub max_limit: Gas,
+}
+
+impl GasConfig {
+    /// Create a gas meter for a transaction requesting the given limit.
+    pub fn meter(&self, limit: Option<Gas>) -> GasMeter {
+        let limit = limit.map_or(self.max_limit, |limit| limit.min(self.max_limit));
+        GasMeter::new(limit, self.costs.clone())
+    }
+}
+
+/// Gas meter of a transaction.
+#[derive(Clone, Debug)]
+pub struct GasMeter {
+    limit: Gas,
+    used: Gas,
+    costs: Arc<GasCosts>,
+}
+
+impl Default for GasMeter {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+impl GasMeter {
+    /// Create a new gas meter with the given limit and cost table.
+    pub fn new(limit: Gas, costs: Arc<GasCosts>) -> Self {
+        Self {
+            limit,
+            used: 0,
+            costs,
+        }
+    }
+
+    /// Create a gas meter which never runs out of gas and where all
+    /// operations are free.
+    pub fn unlimited() -> Self {
+        Self::new(Gas::MAX, Arc::new(GasCosts::default()))
+    }
+
+    /// Gas limit.
+    pub fn limit(&self) -> Gas {
+        self.limit
+    }
+
+    /// Amount of gas used so far.
+    pub fn used(&self) -> Gas {
+        self.used
+    }
+
+    /// Amount of gas remaining.
+    pub fn remaining(&self) -> Gas {
+        self.limit - self.used
+    }
+
+    /// Cost table used by the meter.
+    pub fn costs(&self) -> &GasCosts {
+        &self.costs
+    }
+
+    /// Consume the given amount of gas.
+    ///
+    /// If there is not enough gas remaining, all of it is consumed and an
+    /// out-of-gas error is returned, which should be propagated by the
+    /// method handler.
+    pub fn consume(&mut self, amount: Gas) -> Result<()> {
+        if amount > self.remaining() {
+            self.used = self.limit;
+            return Err(GasError::OutOfGas { limit: self.limit }.into());
+        }
+        self.used += amount;
+        Ok(())
+    }
+
+    /// Consume gas for verifying the given number of signatures.
+    pub fn consume_signature_verifications(&mut self, count: u64) -> Result<()> {
+        self.consume(self.costs.signature_verification.saturating_mul(count))
+    }
+
+    /// Consume gas for executing a runtime-specific opcode.
+    pub fn consume_opcode(&mut self, opcode: &str) -> Result<()> {
+        let cost = *self
+            .costs
+            .opcodes
+            .get(opcode)
+            .ok_or_else(|| GasError::UnknownOpcode(opcode.to_owned()))?;
+        self.consume(cost)
+    }
+
+    /// Consume gas for the given storage accesses.
+    pub fn consume_storage(&mut self, accesses: &[Access]) -> Result<()> {
+        let cost = accesses.iter().fold(0 as Gas, |cost, access| {
+            cost.saturating_add(self.costs.storage_access(access))
+        });
+        self.consume(cost)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gas_meter() {
+        let mut costs = GasCosts {
+            storage_read: 10,
+            storage_read_byte: 1,
+            storage_write: 100,
+            storage_write_byte: 2,
+            signature_verification: 50,
+            ..Default::default()
+        };
+        costs.opcodes.insert("add".to_owned(), 3);
+        let config = GasConfig {
+            costs: Arc::new(costs),
+            max_limit: 1000,
+        };
+        assert_eq!(1000, config.meter(None).limit());
+        assert_eq!(1000, config.meter(Some(5000)).limit());
+
+        let mut meter = config.meter(Some(500));
+        meter.consume_opcode("add").unwrap();
+        assert!(meter.consume_opcode("mul").is_err());
+        meter.consume_signature_verifications(2).unwrap();
+        meter
+            .consume_storage(&[
+                Access {
+                    kind: AccessKind::Read,
+                    key: b"foo".to_vec(),
+                    value_size: 5,
+                },
+                Access {
+                    kind: AccessKind::Write,
+                    key: b"foo".to_vec(),
+                    value_size: 1,
+                },
+            ])
+            .unwrap();
+        assert_eq!(3 + 100 + 18 + 108, meter.used());
+        assert_eq!(500 - 229, meter.remaining());
+
+        let error = meter.consume(meter.remaining() + 1).unwrap_err();
+        assert!(GasError::is_out_of_gas(&error));
+        assert_eq!(500, meter.used());
+        assert!(!GasError::is_out_of_gas(
+            &meter.consume_opcode("mul").unwrap_err()
+        ));
+
+        let mut meter = GasMeter::unlimited();
+        meter.consume_signature_verifications(1000).unwrap();
+        meter.consume(1000).unwrap();
+        assert_eq!(1000, meter.used());
+    }
+}
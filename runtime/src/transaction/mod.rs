@@ -2,8 +2,11 @@
 
 pub mod context;
 pub mod dispatcher;
+pub mod events;
+pub mod gas;
 pub mod macros;
 pub mod rwset;
+mod speculative;
 pub mod tags;
 pub mod tree;
 pub mod types;
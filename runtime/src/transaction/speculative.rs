@@ -0,0 +1,153 @@
+//! Speculative transaction state.
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use io_context::Context;
+
+use crate::{
+    common::{crypto::hash::Hash, roothash::Namespace},
+    storage::{
+        mkvs::{Prefix, WriteLog, MKVS},
+        StorageContext, StorageContextHandle,
+    },
+};
+
+/// An MKVS which buffers all writes in memory and reads through to an
+/// entered storage context.
+///
+/// This is used to execute a transaction without modifying the underlying
+/// state, so that its writes can be applied later if the transaction turns
+/// out not to conflict with transactions executed before it.
+pub struct SpeculativeState {
+    base: StorageContextHandle,
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl SpeculativeState {
+    /// Create a new speculative state on top of the given storage context.
+    pub fn new(base: StorageContextHandle) -> Self {
+        Self {
+            base,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    /// Return the buffered writes, with `None` values for removed keys.
+    pub fn into_writes(self) -> BTreeMap<Vec<u8>, Option<Vec<u8>>> {
+        self.writes
+    }
+
+    fn with_base<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut dyn MKVS) -> R,
+    {
+        self.base
+            .enter(|| StorageContext::with_current(|mkvs, _| f(mkvs)))
+            .expect("storage context must not be exited during execution")
+    }
+}
+
+impl MKVS for SpeculativeState {
+    fn get(&self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        match self.writes.get(key) {
+            Some(value) => value.clone(),
+            None => self.with_base(|mkvs| mkvs.get(ctx, key)),
+        }
+    }
+
+    fn cache_contains_key(&self, ctx: Context, key: &[u8]) -> bool {
+        self.writes.contains_key(key) || self.with_base(|mkvs| mkvs.cache_contains_key(ctx, key))
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        let previous = self.get(ctx, key);
+        self.writes.insert(key.to_vec(), Some(value.to_vec()));
+        previous
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+        let previous = self.get(ctx, key);
+        self.writes.insert(key.to_vec(), None);
+        previous
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) {
+        self.with_base(|mkvs| mkvs.prefetch_prefixes(ctx, prefixes, limit))
+    }
+
+    fn commit(
+        &mut self,
+        _ctx: Context,
+        _namespace: Namespace,
+        _version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        Err(anyhow!("speculative state cannot be committed"))
+    }
+
+    fn rollback(&mut self) {
+        self.writes.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::storage::{
+        mkvs::{sync::NoopReadSyncer, Tree},
+        KeyValue,
+    };
+
+    struct NoopKeyValue;
+
+    impl KeyValue for NoopKeyValue {
+        fn get(&self, _key: Vec<u8>) -> Result<Vec<u8>> {
+            Err(anyhow!("not found"))
+        }
+
+        fn insert(&self, _key: Vec<u8>, _value: Vec<u8>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_speculative_state() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer));
+        tree.insert(Context::background(), b"foo", b"base").unwrap();
+        tree.insert(Context::background(), b"bar", b"base").unwrap();
+
+        StorageContext::enter(&mut tree, Arc::new(NoopKeyValue), || {
+            let mut state = SpeculativeState::new(StorageContext::handle().unwrap());
+            assert_eq!(
+                Some(b"base".to_vec()),
+                state.insert(Context::background(), b"foo", b"new")
+            );
+            assert_eq!(
+                Some(b"base".to_vec()),
+                state.remove(Context::background(), b"bar")
+            );
+            assert_eq!(
+                Some(b"new".to_vec()),
+                state.get(Context::background(), b"foo")
+            );
+            assert_eq!(None, state.get(Context::background(), b"bar"));
+
+            // The underlying state is not modified.
+            StorageContext::with_current(|mkvs, _| {
+                assert_eq!(
+                    Some(b"base".to_vec()),
+                    mkvs.get(Context::background(), b"foo")
+                );
+                assert_eq!(
+                    Some(b"base".to_vec()),
+                    mkvs.get(Context::background(), b"bar")
+                );
+            });
+
+            let writes = state.into_writes();
+            assert_eq!(Some(&Some(b"new".to_vec())), writes.get(&b"foo".to_vec()));
+            assert_eq!(Some(&None), writes.get(&b"bar".to_vec()));
+        });
+    }
+}
@@ -1,13 +1,15 @@
 //! Transaction I/O tree.
+use std::convert::TryInto;
+
 use anyhow::{anyhow, Result};
 use io_context::Context;
 use serde::{self, ser::SerializeSeq, Deserialize, Serializer};
 use serde_bytes::{self, Bytes};
 
-use super::tags::Tags;
+use super::{events::Events, tags::Tags};
 use crate::{
     common::{cbor, crypto::hash::Hash, key_format::KeyFormat},
-    storage::mkvs::{self, sync::ReadSync, Root, WriteLog},
+    storage::mkvs::{self, sync::ReadSync, Root, RootType, WriteLog},
 };
 
 // NOTE: This should be kept in sync with go/runtime/transaction/transaction.go.
@@ -97,6 +99,76 @@ impl KeyFormat for TagKeyFormat {
     }
 }
 
+/// Key format used for emitted events.
+#[derive(Debug, Default)]
+struct EventKeyFormat {
+    /// Transaction hash of the transaction that emitted the event.
+    tx_hash: Hash,
+    /// Index of the event among the events emitted by the transaction.
+    index: u32,
+}
+
+impl KeyFormat for EventKeyFormat {
+    fn prefix() -> u8 {
+        'V' as u8
+    }
+
+    fn size() -> usize {
+        32 + 4
+    }
+
+    fn encode_atoms(self, atoms: &mut Vec<Vec<u8>>) {
+        atoms.push(self.tx_hash.as_ref().to_vec());
+        atoms.push(self.index.to_be_bytes().to_vec());
+    }
+
+    fn decode_atoms(data: &[u8]) -> Self {
+        Self {
+            tx_hash: data[..32].into(),
+            index: u32::from_be_bytes(data[32..36].try_into().unwrap()),
+        }
+    }
+}
+
+/// Key format used for indexing emitted events by topic.
+///
+/// Topics are hashed so that a topic is never a prefix of another topic and
+/// all events with a given topic can be found by iterating over the keys
+/// with the topic hash prefix. The value is the event key.
+#[derive(Debug, Default)]
+struct EventTopicKeyFormat {
+    /// Hash of the topic.
+    topic_hash: Hash,
+    /// Transaction hash of the transaction that emitted the event.
+    tx_hash: Hash,
+    /// Index of the event among the events emitted by the transaction.
+    index: u32,
+}
+
+impl KeyFormat for EventTopicKeyFormat {
+    fn prefix() -> u8 {
+        'I' as u8
+    }
+
+    fn size() -> usize {
+        32 + 32 + 4
+    }
+
+    fn encode_atoms(self, atoms: &mut Vec<Vec<u8>>) {
+        atoms.push(self.topic_hash.as_ref().to_vec());
+        atoms.push(self.tx_hash.as_ref().to_vec());
+        atoms.push(self.index.to_be_bytes().to_vec());
+    }
+
+    fn decode_atoms(data: &[u8]) -> Self {
+        Self {
+            topic_hash: data[..32].into(),
+            tx_hash: data[32..64].into(),
+            index: u32::from_be_bytes(data[64..68].try_into().unwrap()),
+        }
+    }
+}
+
 /// The input transaction artifacts.
 ///
 /// These are the artifacts that are stored CBOR-serialized in the Merkle tree.
@@ -146,6 +218,30 @@ impl serde::Serialize for OutputArtifacts {
     }
 }
 
+/// An emitted event.
+///
+/// This is stored CBOR-serialized in the Merkle tree.
+struct EventArtifacts<'a> {
+    key: &'a [u8],
+    value: &'a [u8],
+    topics: &'a [Vec<u8>],
+}
+
+impl<'a> serde::Serialize for EventArtifacts<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let topics: Vec<&Bytes> = self.topics.iter().map(|t| Bytes::new(t)).collect();
+
+        let mut seq = serializer.serialize_seq(Some(3))?;
+        seq.serialize_element(&Bytes::new(self.key))?;
+        seq.serialize_element(&Bytes::new(self.value))?;
+        seq.serialize_element(&topics)?;
+        seq.end()
+    }
+}
+
 /// A Merkle tree containing transaction artifacts.
 pub struct Tree {
     io_root: Root,
@@ -157,7 +253,10 @@ impl Tree {
     pub fn new(read_syncer: Box<dyn ReadSync>, io_root: Root) -> Self {
         Self {
             io_root,
-            tree: mkvs::Tree::make().with_root(io_root).new(read_syncer),
+            tree: mkvs::Tree::make()
+                .with_root_type(RootType::IO)
+                .with_root(io_root)
+                .new(read_syncer),
         }
     }
 
@@ -218,6 +317,45 @@ impl Tree {
         Ok(())
     }
 
+    /// Add events emitted by a transaction.
+    ///
+    /// Each event is stored under the transaction hash and its index, and
+    /// indexed under each of its topics.
+    pub fn add_events(&mut self, ctx: Context, tx_hash: Hash, events: Events) -> Result<()> {
+        let ctx = ctx.freeze();
+
+        for (index, event) in events.into_iter().enumerate() {
+            let index: u32 = index
+                .try_into()
+                .map_err(|_| anyhow!("transaction: too many events"))?;
+
+            self.tree.insert(
+                Context::create_child(&ctx),
+                &EventKeyFormat { tx_hash, index }.encode(),
+                &cbor::to_vec(&EventArtifacts {
+                    key: &event.key,
+                    value: &event.value,
+                    topics: &event.topics,
+                }),
+            )?;
+
+            for topic in &event.topics {
+                self.tree.insert(
+                    Context::create_child(&ctx),
+                    &EventTopicKeyFormat {
+                        topic_hash: Hash::digest_bytes(topic),
+                        tx_hash,
+                        index,
+                    }
+                    .encode(),
+                    &event.key,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Commit updates to the underlying Merkle tree and return the write
     /// log and root hash.
     pub fn commit(&mut self, ctx: Context) -> Result<(WriteLog, Hash)> {
@@ -232,7 +370,10 @@ mod test {
 
     use crate::storage::mkvs::sync::*;
 
-    use super::{super::tags::Tag, *};
+    use super::{
+        super::{events::Event, tags::Tag},
+        *,
+    };
 
     #[test]
     fn test_transaction() {
@@ -279,4 +420,69 @@ mod test {
             "c65f4e8bd5314c26f245337a859ad244f4b1544acf60ef334cf0d0eadb47363b",
         );
     }
+
+    #[test]
+    fn test_events() {
+        let mut tree = Tree::new(
+            Box::new(NoopReadSyncer),
+            Root {
+                hash: Hash::empty_hash(),
+                ..Default::default()
+            },
+        );
+
+        let tx_hash = Hash::digest_bytes(b"this goes in");
+        tree.add_events(
+            Context::background(),
+            tx_hash,
+            vec![
+                Event::new(
+                    b"transfer".to_vec(),
+                    b"1".to_vec(),
+                    vec![b"alice".to_vec(), b"bob".to_vec()],
+                ),
+                Event::new(b"burn".to_vec(), b"2".to_vec(), vec![b"alice".to_vec()]),
+            ],
+        )
+        .unwrap();
+
+        // Events can be looked up by topic.
+        let prefix = EventTopicKeyFormat {
+            topic_hash: Hash::digest_bytes(b"alice"),
+            ..Default::default()
+        }
+        .encode_partial(1);
+        let found: Vec<(Hash, u32, Vec<u8>)> = tree
+            .tree
+            .prefix_iter(Context::background(), &prefix)
+            .map(|(key, value)| {
+                let key = EventTopicKeyFormat::decode(&key).unwrap();
+                (key.tx_hash, key.index, value)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (tx_hash, 0, b"transfer".to_vec()),
+                (tx_hash, 1, b"burn".to_vec())
+            ],
+            found
+        );
+
+        let event = tree
+            .tree
+            .get(
+                Context::background(),
+                &EventKeyFormat { tx_hash, index: 1 }.encode(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            cbor::to_vec(&EventArtifacts {
+                key: b"burn",
+                value: b"2",
+                topics: &[b"alice".to_vec()],
+            }),
+            event
+        );
+    }
 }
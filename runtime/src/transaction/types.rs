@@ -1,13 +1,13 @@
 //! Transaction protocol types.
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     ops::{Deref, DerefMut},
 };
 
 use serde::{Deserialize, Serialize};
 
-use super::rwset::ReadWriteSet;
-use crate::common::cbor::Value;
+use super::{gas::Gas, rwset::ReadWriteSet};
+use crate::common::cbor::{self, Value};
 
 /// Transaction call.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -16,6 +16,10 @@ pub struct TxnCall {
     pub method: String,
     /// Method arguments.
     pub args: Value,
+    /// Maximum amount of gas the call may use, if gas is metered. Defaults
+    /// to the maximum gas limit of the runtime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_limit: Option<Gas>,
 }
 
 /// Transaction call output.
@@ -25,6 +29,11 @@ pub enum TxnOutput {
     Success(Value),
     /// Call raised an error.
     Error(String),
+    /// Call ran out of gas.
+    OutOfGas {
+        /// Gas limit of the call.
+        limit: Gas,
+    },
 }
 
 /// The result of a successful CheckTx call.
@@ -32,6 +41,53 @@ pub enum TxnOutput {
 pub struct TxnCheckResult {
     /// Predicted read/write set.
     pub predicted_rw_set: ReadWriteSet,
+    /// Metadata used by the host to prioritize the transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<CheckTxMetadata>,
+}
+
+/// Transaction metadata returned by a successful CheckTx call, used by the
+/// host to order and limit transactions in its queue.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CheckTxMetadata {
+    /// Priority of the transaction, higher priority transactions are
+    /// scheduled first.
+    #[serde(default)]
+    pub priority: u64,
+    /// Runtime-specific weights of the transaction (e.g., gas or size), which
+    /// the host can use to limit the total weight of a batch.
+    #[serde(default)]
+    pub weights: BTreeMap<String, u64>,
+    /// Sender of the transaction (e.g., an account address).
+    #[serde(default, with = "serde_bytes")]
+    pub sender: Vec<u8>,
+    /// Sequence number (nonce) of the transaction for its sender.
+    /// Transactions of the same sender are scheduled in sequence order.
+    #[serde(default)]
+    pub sender_seq: u64,
+}
+
+impl CheckTxMetadata {
+    /// Extract the metadata from an encoded CheckTx output, if the check
+    /// succeeded and returned any metadata.
+    pub fn from_output(output: &[u8]) -> Option<Self> {
+        match cbor::from_slice(output).ok()? {
+            TxnOutput::Success(value) => cbor::from_value::<TxnCheckResult>(value).ok()?.meta,
+            TxnOutput::Error(_) | TxnOutput::OutOfGas { .. } => None,
+        }
+    }
+}
+
+/// Statistics about how a transaction batch was scheduled for execution.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleStats {
+    /// Number of partitions the batch was split into.
+    pub partitions: u64,
+    /// Number of calls in the largest partition.
+    pub max_partition_size: u64,
+    /// Number of calls executed at a different position than their position
+    /// in the batch.
+    pub reordered: u64,
 }
 
 /// Internal module to efficiently serialize batches.
@@ -115,7 +171,7 @@ impl Into<VecDeque<Vec<u8>>> for TxnBatch {
 mod tests {
     use super::*;
 
-    use crate::common::{cbor, crypto::hash::Hash};
+    use crate::common::crypto::hash::Hash;
 
     #[test]
     fn test_consistent_hash() {
@@ -126,4 +182,27 @@ mod tests {
             Hash::from("c451dd4fd065b815e784aac6b300e479b2167408f0eebbb95a8bd36b9e71e34d")
         );
     }
+
+    #[test]
+    fn test_check_tx_metadata() {
+        let meta = CheckTxMetadata {
+            priority: 10,
+            weights: vec![("gas".to_owned(), 1000)].into_iter().collect(),
+            sender: b"alice".to_vec(),
+            sender_seq: 3,
+        };
+        let output = cbor::to_vec(&TxnOutput::Success(cbor::to_value(TxnCheckResult {
+            meta: Some(meta.clone()),
+            ..Default::default()
+        })));
+        assert_eq!(Some(meta), CheckTxMetadata::from_output(&output));
+
+        let output = cbor::to_vec(&TxnOutput::Success(cbor::to_value(
+            TxnCheckResult::default(),
+        )));
+        assert_eq!(None, CheckTxMetadata::from_output(&output));
+        let output = cbor::to_vec(&TxnOutput::Error("failed".to_owned()));
+        assert_eq!(None, CheckTxMetadata::from_output(&output));
+        assert_eq!(None, CheckTxMetadata::from_output(b"garbage"));
+    }
 }
@@ -1,21 +1,102 @@
 //! Types used by the worker-host protocol.
 use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
 use serde_bytes;
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use crate::{
     common::{
+        cbor,
         crypto::{
             hash::Hash,
             signature::{PublicKey, Signature},
         },
-        roothash::{Block, ComputeResultsHeader},
+        logger::LogRecord,
+        metrics::MetricFamily,
+        roothash::{Block, ComputeResultsHeader, IncomingMessage, MessageEvent},
         runtime::RuntimeId,
-        sgx::avr::AVR,
+        schema::MethodInfo,
+        sgx::{avr::AVR, pcs::QuoteBundle, QuotePolicy},
+        version::{Version, PROTOCOL_VERSION},
     },
+    consensus::{epoch::EpochTime, LightBlock},
+    identity::AttestationStatus,
     storage::mkvs::{sync, WriteLog},
-    transaction::types::TxnBatch,
+    transaction::types::{CheckTxMetadata, ScheduleStats, TxnBatch},
 };
 
+/// A runtime host protocol version.
+///
+/// Message variants introduced in later minor versions of the protocol are
+/// only used if the host supports them, see `Body::min_protocol_version`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProtocolVersion(pub Version);
+
+impl ProtocolVersion {
+    /// The initial protocol version, assumed for hosts that do not report
+    /// a version.
+    pub const V1_0: ProtocolVersion = ProtocolVersion(Version::new(1, 0, 0));
+    /// Adds batched local storage operations.
+    pub const V1_1: ProtocolVersion = ProtocolVersion(Version::new(1, 1, 0));
+    /// Adds forwarding of runtime log records to the host.
+    pub const V1_2: ProtocolVersion = ProtocolVersion(Version::new(1, 2, 0));
+    /// Adds scraping of runtime metrics by the host.
+    pub const V1_3: ProtocolVersion = ProtocolVersion(Version::new(1, 3, 0));
+    /// Adds batch scheduling statistics to computed batches.
+    pub const V1_4: ProtocolVersion = ProtocolVersion(Version::new(1, 4, 0));
+    /// Adds transaction metadata to check results.
+    pub const V1_5: ProtocolVersion = ProtocolVersion(Version::new(1, 5, 0));
+    /// Adds runtime queries.
+    pub const V1_6: ProtocolVersion = ProtocolVersion(Version::new(1, 6, 0));
+    /// Adds runtime introspection.
+    pub const V1_7: ProtocolVersion = ProtocolVersion(Version::new(1, 7, 0));
+    /// Adds DCAP quotes for the runtime attestation key.
+    pub const V1_8: ProtocolVersion = ProtocolVersion(Version::new(1, 8, 0));
+    /// Adds quote policies configured by the host.
+    pub const V1_9: ProtocolVersion = ProtocolVersion(Version::new(1, 9, 0));
+    /// Adds attestation refresh requests and attestation status queries.
+    pub const V1_10: ProtocolVersion = ProtocolVersion(Version::new(1, 10, 0));
+    /// Adds storage sync requests for the consensus state.
+    pub const V1_11: ProtocolVersion = ProtocolVersion(Version::new(1, 11, 0));
+    /// Adds roothash message results to batch execution requests.
+    pub const V1_12: ProtocolVersion = ProtocolVersion(Version::new(1, 12, 0));
+    /// Adds incoming runtime messages to batch execution requests.
+    pub const V1_13: ProtocolVersion = ProtocolVersion(Version::new(1, 13, 0));
+    /// Adds consensus epoch update notifications.
+    pub const V1_14: ProtocolVersion = ProtocolVersion(Version::new(1, 14, 0));
+    /// Adds fetching of consensus light blocks from the host.
+    pub const V1_15: ProtocolVersion = ProtocolVersion(Version::new(1, 15, 0));
+    /// Adds chunked storage sync responses.
+    pub const V1_16: ProtocolVersion = ProtocolVersion(Version::new(1, 16, 0));
+    /// Adds batched storage sync get requests.
+    pub const V1_17: ProtocolVersion = ProtocolVersion(Version::new(1, 17, 0));
+
+    /// Return the protocol version supported by the runtime.
+    pub fn current() -> Self {
+        ProtocolVersion(PROTOCOL_VERSION)
+    }
+
+    /// Negotiate the protocol version to use with a host supporting the
+    /// given version.
+    ///
+    /// Returns `None` if the host uses an incompatible major version.
+    pub fn negotiate(host: Option<Version>) -> Option<Self> {
+        let host = match host {
+            Some(host) => ProtocolVersion(host),
+            None => return Some(Self::V1_0),
+        };
+        let current = Self::current();
+        if host.0.major() != current.0.major() {
+            return None;
+        }
+        Some(std::cmp::min(host, current))
+    }
+
+    /// Check whether the given message can be used with this version.
+    pub fn supports(&self, body: &Body) -> bool {
+        *self >= body.min_protocol_version()
+    }
+}
+
 /// Computed batch.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ComputedBatch {
@@ -28,6 +109,9 @@ pub struct ComputedBatch {
     /// If this runtime uses a TEE, then this is the signature of the batch's
     /// BatchSigMessage with the node's RAK for this runtime.
     pub rak_sig: Signature,
+    /// Statistics about how the batch was scheduled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_stats: Option<ScheduleStats>,
 }
 
 /// Storage sync request.
@@ -36,12 +120,40 @@ pub enum StorageSyncRequest {
     SyncGet(sync::GetRequest),
     SyncGetPrefixes(sync::GetPrefixesRequest),
     SyncIterate(sync::IterateRequest),
+    SyncNextChunk(sync::NextChunkRequest),
+    SyncGetMulti(sync::GetMultiRequest),
+}
+
+/// Storage whose nodes are requested by a storage sync request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum HostStorageEndpoint {
+    /// Runtime state and I/O trees.
+    Runtime = 0,
+    /// Consensus state tree.
+    Consensus = 1,
+}
+
+impl HostStorageEndpoint {
+    /// Whether the endpoint is the runtime storage.
+    pub fn is_runtime(&self) -> bool {
+        *self == HostStorageEndpoint::Runtime
+    }
+}
+
+impl Default for HostStorageEndpoint {
+    fn default() -> Self {
+        HostStorageEndpoint::Runtime
+    }
 }
 
 /// Storage sync response.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum StorageSyncResponse {
     ProofResponse(sync::ProofResponse),
+    PartialProofResponse(sync::PartialProofResponse),
+    /// Proof responses for each key of a `SyncGetMulti` request, in order.
+    ProofResponses(Vec<sync::ProofResponse>),
 }
 
 /// Runtime host protocol message body.
@@ -63,6 +175,8 @@ pub enum Body {
     // Runtime interface.
     RuntimeInfoRequest {
         runtime_id: RuntimeId,
+        #[serde(default)]
+        host_protocol_version: Option<u64>,
     },
     RuntimeInfoResponse {
         protocol_version: u64,
@@ -88,6 +202,18 @@ pub enum Body {
         avr: AVR,
     },
     RuntimeCapabilityTEERakAvrResponse {},
+    RuntimeCapabilityTEERakQuoteRequest {
+        quote: QuoteBundle,
+    },
+    RuntimeCapabilityTEERakQuoteResponse {},
+    RuntimeCapabilityTEEQuotePolicyUpdateRequest {
+        policy: QuotePolicy,
+    },
+    RuntimeCapabilityTEEQuotePolicyUpdateResponse {},
+    RuntimeAttestationStatusRequest {},
+    RuntimeAttestationStatusResponse {
+        status: AttestationStatus,
+    },
     RuntimeRPCCallRequest {
         #[serde(with = "serde_bytes")]
         request: Vec<u8>,
@@ -110,11 +236,23 @@ pub enum Body {
     },
     RuntimeCheckTxBatchResponse {
         results: TxnBatch,
+        /// Metadata of each checked transaction, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        meta: Option<Vec<Option<CheckTxMetadata>>>,
     },
     RuntimeExecuteTxBatchRequest {
         io_root: Hash,
         inputs: TxnBatch,
         block: Block,
+        /// Consensus beacon for the batch (empty if not provided by the host).
+        #[serde(default, with = "serde_bytes")]
+        beacon: Vec<u8>,
+        /// Results of executing the roothash messages contained in the block.
+        #[serde(default)]
+        message_results: Vec<MessageEvent>,
+        /// Incoming messages queued for the runtime, in queue order.
+        #[serde(default)]
+        in_msgs: Vec<IncomingMessage>,
     },
     RuntimeExecuteTxBatchResponse {
         batch: ComputedBatch,
@@ -124,6 +262,30 @@ pub enum Body {
         signed_policy_raw: Vec<u8>,
     },
     RuntimeKeyManagerPolicyUpdateResponse {},
+    RuntimeMetricsRequest {},
+    RuntimeMetricsResponse {
+        metrics: Vec<MetricFamily>,
+    },
+    RuntimeQueryRequest {
+        method: String,
+        args: cbor::Value,
+        block: Block,
+    },
+    RuntimeQueryResponse {
+        data: cbor::Value,
+    },
+    RuntimeIntrospectionRequest {},
+    RuntimeIntrospectionResponse {
+        #[serde(flatten)]
+        info: RuntimeIntrospection,
+    },
+    RuntimeConsensusEpochUpdateRequest {
+        epoch: EpochTime,
+        height: i64,
+        #[serde(default, with = "serde_bytes")]
+        beacon: Vec<u8>,
+    },
+    RuntimeConsensusEpochUpdateResponse {},
 
     // Host interface.
     HostRPCCallRequest {
@@ -138,6 +300,8 @@ pub enum Body {
     HostStorageSyncRequest {
         #[serde(flatten)]
         request: StorageSyncRequest,
+        #[serde(default, skip_serializing_if = "HostStorageEndpoint::is_runtime")]
+        endpoint: HostStorageEndpoint,
     },
     HostStorageSyncResponse {
         #[serde(flatten)]
@@ -162,6 +326,101 @@ pub enum Body {
         value: Vec<u8>,
     },
     HostLocalStorageSetResponse {},
+    HostLocalStorageGetBatchRequest {
+        keys: Vec<serde_bytes::ByteBuf>,
+    },
+    HostLocalStorageGetBatchResponse {
+        values: Vec<serde_bytes::ByteBuf>,
+    },
+    HostLocalStorageSetBatchRequest {
+        items: Vec<LocalStorageItem>,
+    },
+    HostLocalStorageSetBatchResponse {},
+    HostLogRequest {
+        records: Vec<LogRecord>,
+    },
+    HostLogResponse {},
+    HostAttestationRefreshRequest {},
+    HostAttestationRefreshResponse {},
+    HostFetchConsensusBlockRequest {
+        height: i64,
+    },
+    HostFetchConsensusBlockResponse {
+        block: LightBlock,
+    },
+}
+
+impl Body {
+    /// Return the minimum protocol version that supports the message.
+    pub fn min_protocol_version(&self) -> ProtocolVersion {
+        match self {
+            Body::HostStorageSyncRequest {
+                request: StorageSyncRequest::SyncGetMulti(_),
+                ..
+            }
+            | Body::HostStorageSyncResponse {
+                response: StorageSyncResponse::ProofResponses(_),
+            } => ProtocolVersion::V1_17,
+            Body::HostStorageSyncRequest {
+                request: StorageSyncRequest::SyncNextChunk(_),
+                ..
+            }
+            | Body::HostStorageSyncResponse {
+                response: StorageSyncResponse::PartialProofResponse(_),
+            } => ProtocolVersion::V1_16,
+            Body::HostStorageSyncRequest {
+                endpoint: HostStorageEndpoint::Consensus,
+                ..
+            } => ProtocolVersion::V1_11,
+            Body::HostLocalStorageGetBatchRequest { .. }
+            | Body::HostLocalStorageGetBatchResponse { .. }
+            | Body::HostLocalStorageSetBatchRequest { .. }
+            | Body::HostLocalStorageSetBatchResponse {} => ProtocolVersion::V1_1,
+            Body::HostLogRequest { .. } | Body::HostLogResponse {} => ProtocolVersion::V1_2,
+            Body::RuntimeMetricsRequest {} | Body::RuntimeMetricsResponse { .. } => {
+                ProtocolVersion::V1_3
+            }
+            Body::RuntimeQueryRequest { .. } | Body::RuntimeQueryResponse { .. } => {
+                ProtocolVersion::V1_6
+            }
+            Body::RuntimeIntrospectionRequest {} | Body::RuntimeIntrospectionResponse { .. } => {
+                ProtocolVersion::V1_7
+            }
+            Body::RuntimeCapabilityTEERakQuoteRequest { .. }
+            | Body::RuntimeCapabilityTEERakQuoteResponse {} => ProtocolVersion::V1_8,
+            Body::RuntimeCapabilityTEEQuotePolicyUpdateRequest { .. }
+            | Body::RuntimeCapabilityTEEQuotePolicyUpdateResponse {} => ProtocolVersion::V1_9,
+            Body::RuntimeAttestationStatusRequest {}
+            | Body::RuntimeAttestationStatusResponse { .. }
+            | Body::HostAttestationRefreshRequest {}
+            | Body::HostAttestationRefreshResponse {} => ProtocolVersion::V1_10,
+            Body::RuntimeConsensusEpochUpdateRequest { .. }
+            | Body::RuntimeConsensusEpochUpdateResponse {} => ProtocolVersion::V1_14,
+            Body::HostFetchConsensusBlockRequest { .. }
+            | Body::HostFetchConsensusBlockResponse { .. } => ProtocolVersion::V1_15,
+            _ => ProtocolVersion::V1_0,
+        }
+    }
+}
+
+/// Description of the methods and features of a runtime.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeIntrospection {
+    /// Transaction methods, ordered by name.
+    pub methods: Vec<MethodInfo>,
+    /// Query methods, ordered by name.
+    pub queries: Vec<MethodInfo>,
+    /// Names of the optional features used by the runtime.
+    pub features: Vec<String>,
+}
+
+/// A key/value pair stored in the host's local storage.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LocalStorageItem {
+    #[serde(with = "serde_bytes")]
+    pub key: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -197,6 +456,18 @@ impl<'de> serde::Deserialize<'de> for MessageType {
     }
 }
 
+/// Header of a runtime protocol message.
+///
+/// This is used to respond to messages whose body cannot be decoded, e.g.
+/// because they were introduced in a later version of the protocol.
+#[derive(Debug, Deserialize)]
+pub struct MessageHeader {
+    /// Unique request identifier.
+    pub id: u64,
+    /// Message type.
+    pub message_type: MessageType,
+}
+
 /// Runtime protocol message.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Message {
@@ -210,3 +481,99 @@ pub struct Message {
     #[serde(with = "serde_bytes")]
     pub span_context: Vec<u8>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::cbor;
+
+    #[test]
+    fn test_protocol_version() {
+        let current = ProtocolVersion::current();
+        assert_eq!(
+            Some(ProtocolVersion::V1_0),
+            ProtocolVersion::negotiate(None)
+        );
+        assert_eq!(Some(current), ProtocolVersion::negotiate(Some(current.0)));
+        let older = ProtocolVersion(Version::new(1, 0, 3));
+        assert_eq!(Some(older), ProtocolVersion::negotiate(Some(older.0)));
+        assert_eq!(
+            Some(current),
+            ProtocolVersion::negotiate(Some(Version::new(1, 42, 0)))
+        );
+        assert_eq!(
+            None,
+            ProtocolVersion::negotiate(Some(Version::new(2, 0, 0)))
+        );
+
+        let batch = Body::HostLocalStorageGetBatchRequest { keys: vec![] };
+        assert!(!ProtocolVersion::V1_0.supports(&batch));
+        assert!(!older.supports(&batch));
+        assert!(ProtocolVersion::V1_1.supports(&batch));
+        assert!(ProtocolVersion::V1_0.supports(&Body::RuntimePingRequest {}));
+        let log = Body::HostLogRequest { records: vec![] };
+        assert!(!ProtocolVersion::V1_1.supports(&log));
+        assert!(ProtocolVersion::V1_2.supports(&log));
+        let refresh = Body::HostAttestationRefreshRequest {};
+        assert!(!ProtocolVersion::V1_9.supports(&refresh));
+        assert!(ProtocolVersion::V1_10.supports(&refresh));
+        let consensus_sync = Body::HostStorageSyncRequest {
+            request: StorageSyncRequest::SyncGet(Default::default()),
+            endpoint: HostStorageEndpoint::Consensus,
+        };
+        assert!(!ProtocolVersion::V1_10.supports(&consensus_sync));
+        assert!(ProtocolVersion::V1_11.supports(&consensus_sync));
+        let epoch_update = Body::RuntimeConsensusEpochUpdateRequest {
+            epoch: 1,
+            height: 1,
+            beacon: vec![],
+        };
+        assert!(!ProtocolVersion::V1_13.supports(&epoch_update));
+        assert!(ProtocolVersion::V1_14.supports(&epoch_update));
+        let fetch_block = Body::HostFetchConsensusBlockRequest { height: 1 };
+        assert!(!ProtocolVersion::V1_14.supports(&fetch_block));
+        assert!(ProtocolVersion::V1_15.supports(&fetch_block));
+        let next_chunk = Body::HostStorageSyncRequest {
+            request: StorageSyncRequest::SyncNextChunk(sync::NextChunkRequest {
+                request: 1,
+                chunk: 1,
+            }),
+            endpoint: HostStorageEndpoint::Consensus,
+        };
+        assert!(!ProtocolVersion::V1_15.supports(&next_chunk));
+        assert!(ProtocolVersion::V1_16.supports(&next_chunk));
+        let get_multi = Body::HostStorageSyncRequest {
+            request: StorageSyncRequest::SyncGetMulti(Default::default()),
+            endpoint: HostStorageEndpoint::Consensus,
+        };
+        assert!(!ProtocolVersion::V1_16.supports(&get_multi));
+        assert!(ProtocolVersion::V1_17.supports(&get_multi));
+    }
+
+    #[test]
+    fn test_message_header() {
+        #[derive(Serialize)]
+        enum FutureBody {
+            RuntimeFutureRequest {},
+        }
+        #[derive(Serialize)]
+        struct FutureMessage {
+            id: u64,
+            message_type: MessageType,
+            body: FutureBody,
+            #[serde(with = "serde_bytes")]
+            span_context: Vec<u8>,
+        }
+
+        let encoded = cbor::to_vec(&FutureMessage {
+            id: 42,
+            message_type: MessageType::Request,
+            body: FutureBody::RuntimeFutureRequest {},
+            span_context: vec![],
+        });
+        assert!(cbor::from_slice::<Message>(&encoded).is_err());
+        let header: MessageHeader = cbor::from_slice(&encoded).unwrap();
+        assert_eq!(42, header.id);
+        assert!(matches!(header.message_type, MessageType::Request));
+    }
+}
@@ -14,11 +14,13 @@ use oasis_core_runtime::{
         version::Version,
     },
     executor::Executor,
+    query::Context as QueryContext,
     rak::RAK,
     register_runtime_txn_methods, runtime_context,
     storage::{StorageContext, MKVS},
     transaction::{dispatcher::CheckOnlySuccess, Context as TxnContext},
-    version_from_cargo, Protocol, RpcDemux, RpcDispatcher, TxnDispatcher, TxnMethDispatcher,
+    version_from_cargo, Protocol, QueryRouter, RpcDemux, RpcDispatcher, TxnDispatcher,
+    TxnMethDispatcher,
 };
 use simple_keymanager::trusted_policy_signers;
 use simple_keyvalue_api::{with_api, Key, KeyValue};
@@ -70,6 +72,14 @@ fn get(args: &Key, ctx: &mut TxnContext) -> Result<Option<String>> {
     Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
 }
 
+/// Query a key/value pair, without a transaction.
+fn query_get(args: &Key, ctx: &QueryContext) -> Result<Option<String>> {
+    let existing = ctx
+        .state
+        .get(IoContext::create_child(&ctx.io_ctx), args.key.as_bytes());
+    Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
+}
+
 /// Remove a key/value pair.
 fn remove(args: &Key, ctx: &mut TxnContext) -> Result<Option<String>> {
     if ctx.check_only {
@@ -249,10 +259,12 @@ pub fn main() {
     let init = |protocol: &Arc<Protocol>,
                 rak: &Arc<RAK>,
                 _rpc_demux: &mut RpcDemux,
-                rpc: &mut RpcDispatcher|
+                rpc: &mut RpcDispatcher,
+                query: &mut QueryRouter|
      -> Option<Box<dyn TxnDispatcher>> {
         let mut txn = TxnMethDispatcher::new();
         with_api! { register_runtime_txn_methods!(txn, api); }
+        query.register("keyvalue.Get", query_get);
 
         // Create the key manager client.
         let rt_id = protocol.get_runtime_id();